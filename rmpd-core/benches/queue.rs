@@ -0,0 +1,61 @@
+//! Queue benchmarks: building, reordering and trimming a 10k-song queue.
+//!
+//! Run with: cargo bench -p rmpd-core --features test-utils --bench queue
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rmpd_core::queue::Queue;
+use rmpd_core::test_utils::make_test_song;
+use std::hint::black_box;
+
+const QUEUE_LEN: u32 = 10_000;
+
+fn filled_queue() -> Queue {
+    let mut queue = Queue::new();
+    for i in 0..QUEUE_LEN {
+        queue.add(make_test_song(&format!("music/{i:05}.flac"), i));
+    }
+    queue
+}
+
+fn queue_operations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queue");
+
+    group.bench_function("add_10k", |b| b.iter(filled_queue));
+    group.bench_function("add_at_front", |b| {
+        b.iter_batched_ref(
+            filled_queue,
+            |queue| queue.add_at(make_test_song("music/new.flac", 0), Some(0)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("move_front_to_back", |b| {
+        b.iter_batched_ref(
+            filled_queue,
+            |queue| queue.move_item(0, QUEUE_LEN - 1),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("delete_front", |b| {
+        b.iter_batched_ref(filled_queue, |queue| queue.delete(0), BatchSize::LargeInput)
+    });
+    group.bench_function("delete_id_middle", |b| {
+        b.iter_batched_ref(
+            filled_queue,
+            |queue| queue.delete_id(QUEUE_LEN / 2),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("shuffle", |b| {
+        b.iter_batched_ref(filled_queue, |queue| queue.shuffle(), BatchSize::LargeInput)
+    });
+
+    let queue = filled_queue();
+    group.bench_function("get_by_id_last", |b| {
+        b.iter(|| queue.get_by_id(black_box(QUEUE_LEN - 1)).is_some())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, queue_operations);
+criterion_main!(benches);
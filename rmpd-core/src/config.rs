@@ -1,5 +1,5 @@
 use crate::error::{Result, RmpdError};
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -16,13 +16,27 @@ pub struct Config {
     pub decoder: DecoderConfig,
     #[serde(default)]
     pub database: DatabaseConfig,
+    /// Tag rewrites applied at scan time, in order (see [`TagRule`]).
+    #[serde(default, rename = "tag_rule")]
+    pub tag_rules: Vec<TagRule>,
+    /// External commands run on player and database events (see [`Hook`]).
+    #[serde(default, rename = "hook")]
+    pub hooks: Vec<Hook>,
+    /// Tokens for the HTTP endpoints (see [`ApiToken`]).
+    #[serde(default, rename = "api_token")]
+    pub api_tokens: Vec<ApiToken>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GeneralConfig {
+    /// Main music directory. Empty when unset; see
+    /// [`GeneralConfig::available_music_directory`].
+    #[serde(default)]
     pub music_directory: Utf8PathBuf,
     #[serde(default = "default_playlist_dir")]
     pub playlist_directory: Utf8PathBuf,
+    /// SQLite database of the library. `":memory:"` keeps it in RAM only:
+    /// nothing is written, and the music directory is scanned at startup.
     #[serde(default = "default_db_file")]
     pub db_file: Utf8PathBuf,
     #[serde(default = "default_state_file")]
@@ -33,6 +47,139 @@ pub struct GeneralConfig {
     pub follow_symlinks: bool,
     #[serde(default = "default_charset")]
     pub filesystem_charset: String,
+    /// Additional music directories merged into the virtual tree, each shown
+    /// as a top-level directory (see [`MusicRoot`]).
+    #[serde(default, rename = "music_root")]
+    pub music_roots: Vec<MusicRoot>,
+    /// MPD's `metadata_to_use`: the tag types to store and send, e.g.
+    /// `"artist,album,title"` or `"+comment,-genre"` (see
+    /// [`crate::tag::MetadataMask::parse`]). `None` keeps MPD's default.
+    #[serde(default)]
+    pub metadata_to_use: Option<String>,
+    /// Locale whose collation orders `list` values and `lsinfo` listings,
+    /// e.g. `"de"` or `"sv"`. `None` keeps MPD's byte order.
+    #[serde(default)]
+    pub collation: Option<String>,
+}
+
+/// An extra music directory, e.g. an external drive next to the main
+/// library. Its songs get URIs prefixed with `name/`, so they stay stable no
+/// matter where the drive is mounted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MusicRoot {
+    /// Top-level directory name in the virtual tree (a single path component).
+    pub name: String,
+    /// Directory on disk.
+    pub path: Utf8PathBuf,
+}
+
+/// A `[[tag_rule]]` block: rewrite the values of one tag as files are
+/// scanned, leaving the files themselves untouched.
+///
+/// Every match of `pattern` in a value of `tag` is replaced by `replace`
+/// (which may use `$1`-style captures); a value left empty is dropped. With
+/// `into` set, `value` is expanded for each match and added to that tag, so
+/// `"A feat. B"` can become artist `A` plus performer `B`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TagRule {
+    /// Tag name, e.g. `genre` (case-insensitive).
+    pub tag: String,
+    /// Regular expression matched against each value.
+    pub pattern: String,
+    #[serde(default)]
+    pub replace: String,
+    /// Tag receiving text extracted from each match.
+    #[serde(default)]
+    pub into: Option<String>,
+    /// What `into` receives, expanded like `replace`.
+    #[serde(default = "default_tag_rule_value")]
+    pub value: String,
+}
+
+/// A `[[hook]]` block: run an external command whenever `event` happens,
+/// replacing the wrapper scripts (mpdcron, `mpc idleloop`) people run around
+/// MPD.
+///
+/// `command` is run through `sh -c` in the background, with `RMPD_*`
+/// environment variables describing the event and the current song.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Hook {
+    pub event: HookEvent,
+    pub command: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// What a [`Hook`] fires on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// A new song started, manually or by advancing through the queue.
+    SongChange,
+    /// Playback started or resumed.
+    Play,
+    Pause,
+    Stop,
+    /// A library update or source sync finished.
+    DatabaseUpdate,
+}
+
+impl HookEvent {
+    /// The name used in the config and passed as `RMPD_EVENT`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SongChange => "song_change",
+            Self::Play => "play",
+            Self::Pause => "pause",
+            Self::Stop => "stop",
+            Self::DatabaseUpdate => "database_update",
+        }
+    }
+}
+
+/// An `[[api_token]]` block: a bearer token for rmpd's HTTP endpoints
+/// (`network.art_port`, `network.stream_port`, `network.metrics_port`),
+/// kept apart from MPD passwords so a web UI or remote listener can be let
+/// in without the MPD secret.
+///
+/// Once any token is configured, every HTTP request must present one whose
+/// `scopes` include what the endpoint needs, as `Authorization: Bearer
+/// <token>` or as a `?token=` query parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApiToken {
+    /// Label for the token in logs.
+    #[serde(default)]
+    pub name: String,
+    pub token: String,
+    #[serde(default = "default_api_scopes")]
+    pub scopes: Vec<ApiScope>,
+}
+
+/// What an [`ApiToken`] may do. Like MPD permissions, each scope stands on
+/// its own: `admin` does not imply `read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Fetch album art and listen to streams.
+    Read,
+    /// Control playback and the queue.
+    Control,
+    /// Change the library and the server.
+    Admin,
+}
+
+fn default_api_scopes() -> Vec<ApiScope> {
+    vec![ApiScope::Read]
+}
+
+impl GeneralConfig {
+    /// The music directory, if one is configured and exists.
+    #[must_use]
+    pub fn available_music_directory(&self) -> Option<&Utf8Path> {
+        let dir = self.music_directory.as_path();
+        (!dir.as_str().is_empty() && dir.is_dir()).then_some(dir)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -45,9 +192,71 @@ pub struct NetworkConfig {
     pub unix_socket: Option<Utf8PathBuf>,
     #[serde(default = "default_max_connections")]
     pub max_connections: usize,
+    /// Seconds a client may stay silent between commands before it is
+    /// disconnected; clients waiting in `idle` are exempt. 0 disables it.
     #[serde(default = "default_connection_timeout")]
     pub connection_timeout: u64,
+    /// Seconds a TCP connection may be quiet before the kernel starts
+    /// probing it, so peers that vanished without closing (a phone leaving
+    /// Wi-Fi) are dropped even while in `idle`. 0 disables keepalive.
+    #[serde(default = "default_tcp_keepalive")]
+    pub tcp_keepalive: u64,
+    /// Longest accepted command line in bytes (including the newline). A
+    /// client sending a longer line gets an ACK and is disconnected.
+    #[serde(default = "default_max_command_length")]
+    pub max_command_length: usize,
+    /// Maximum total size of one `command_list_begin` … `command_list_end`
+    /// block in KiB, like MPD's `max_command_list_size`. Exceeding it
+    /// disconnects the client.
+    #[serde(default = "default_max_command_list_size")]
+    pub max_command_list_size: usize,
+    /// Seconds a client may take to read one response before it is treated
+    /// as stalled and disconnected.
+    #[serde(default = "default_write_timeout")]
+    pub write_timeout: u64,
+    /// Largest single response buffered for a client in KiB, like MPD's
+    /// `max_output_buffer_size`. A bigger response disconnects the client.
+    #[serde(default = "default_max_output_buffer_size")]
+    pub max_output_buffer_size: usize,
+    /// Password clients send to gain permissions. MPD's `secret@read,add`
+    /// form limits what it grants (default: everything).
     pub password: Option<String>,
+    /// Permissions of clients that have not sent a password, as a list of
+    /// `read`, `add`, `control` and `admin` (MPD's `default_permissions`).
+    /// `"read"` makes a listening-only endpoint; `"read,add"` also lets
+    /// guests queue songs. Unset: everything, or nothing when a password is
+    /// configured.
+    #[serde(default)]
+    pub default_permissions: Option<String>,
+    /// Permissions of clients on the Unix socket that have not sent a
+    /// password, in the same form (MPD's `local_permissions`). Lets local
+    /// users control the player without the password remote clients need.
+    /// Unset: the same as `default_permissions`.
+    #[serde(default)]
+    pub local_permissions: Option<String>,
+    /// Port on `bind_address` serving album art over HTTP at
+    /// `/art/<song id>` and `/art/<album>`, for web clients that cannot use
+    /// the chunked `albumart` command. Unset: disabled.
+    #[serde(default)]
+    pub art_port: Option<u16>,
+    /// Port on `bind_address` streaming library songs (`/stream/<uri>`) and
+    /// the live `httpd` output (`/live`) re-encoded by the built-in encoders,
+    /// for listening remotely from a browser. Unset: disabled.
+    #[serde(default)]
+    pub stream_port: Option<u16>,
+    /// Bitrate in kbit/s of `stream_port` streams that do not ask for one
+    /// with `?bitrate=`.
+    #[serde(default = "default_stream_bitrate")]
+    pub stream_bitrate: u32,
+    /// Most `stream_port` streams transcoding at once; further clients are
+    /// turned away with `503`.
+    #[serde(default = "default_stream_max_clients")]
+    pub stream_max_clients: usize,
+    /// Port on `bind_address` serving the daemon statistics of
+    /// `daemonstats` at `/metrics`, in the Prometheus text format. Unset:
+    /// disabled.
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
     /// Advertise the daemon on the session D-Bus via the MPRIS interface
     /// (`org.mpris.MediaPlayer2.rmpd`) so desktop environments, `playerctl`,
     /// and media keys can discover and control rmpd.
@@ -77,6 +286,9 @@ pub struct AudioConfig {
     pub replay_gain_preamp: f32,
     #[serde(default)]
     pub replay_gain_missing_preamp: f32,
+    /// Automatic gain control for untagged libraries, like MPD's
+    /// `volume_normalization`: slowly raises quiet audio towards a common
+    /// level without clipping. Independent of ReplayGain.
     #[serde(default)]
     pub volume_normalization: bool,
     #[serde(default = "default_true")]
@@ -87,10 +299,42 @@ pub struct AudioConfig {
     pub mixramp_db: f32,
     #[serde(default)]
     pub mixramp_delay: f32,
+    /// During a crossfade, bring the incoming track in at the outgoing
+    /// track's loudness (from their ReplayGain tags, after any ReplayGain
+    /// applied) and ease it to its own level by the end of the overlap.
+    #[serde(default)]
+    pub crossfade_loudness_match: bool,
+    /// Skip the crossfade between continuous tracks: the next track of the
+    /// same album and disc, or a pair whose MixRamp tags show no silence
+    /// between them. Such pairs play gaplessly, keeping live and mixed
+    /// albums intact.
+    #[serde(default = "default_true")]
+    pub crossfade_skip_continuous: bool,
+    /// Resample every song to this rate (Hz) before it reaches the outputs,
+    /// like MPD's `audio_output_format`. Consecutive tracks at different
+    /// rates then play gaplessly through one open output. Unset (default)
+    /// reopens the output at each song's own rate.
+    #[serde(default)]
+    pub output_sample_rate: Option<u32>,
     /// Put MPD into pause mode instead of starting playback after startup
     /// Default: false (auto-resume if was playing)
     #[serde(default)]
     pub restore_paused: bool,
+    /// Keep the system from sleeping while playing, with a logind inhibitor
+    /// on Linux or an IOKit power assertion on macOS. Released on pause
+    /// and stop.
+    #[serde(default = "default_true")]
+    pub inhibit_sleep: bool,
+    /// Remember where songs at least this long (seconds) were left off and
+    /// resume them from there the next time they play. The position is kept
+    /// in the song's `resume_position` sticker. Unset (default) = off.
+    #[serde(default)]
+    pub resume_min_duration: Option<u32>,
+    /// Directories (relative to the music directory) whose songs are
+    /// resumed the same way whatever their length, e.g. `["Audiobooks",
+    /// "Podcasts"]`.
+    #[serde(default)]
+    pub resume_directories: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -105,8 +349,8 @@ pub struct OutputConfig {
 }
 
 /// Look up a string-valued setting from a flattened TOML settings table,
-/// trimmed and non-empty. Booleans/integers are stringified (for keys like
-/// `dop`, `max_bitrate`). Returns `None` when absent or empty.
+/// trimmed and non-empty. Booleans/integers/floats are stringified (for keys
+/// like `dop`, `max_bitrate`, `quality`). Returns `None` when absent or empty.
 fn setting_str(table: &toml::Table, key: &str) -> Option<String> {
     match table.get(key) {
         Some(toml::Value::String(s)) => {
@@ -115,6 +359,7 @@ fn setting_str(table: &toml::Table, key: &str) -> Option<String> {
         }
         Some(toml::Value::Boolean(b)) => Some(b.to_string()),
         Some(toml::Value::Integer(i)) => Some(i.to_string()),
+        Some(toml::Value::Float(f)) => Some(f.to_string()),
         _ => None,
     }
 }
@@ -133,12 +378,27 @@ impl OutputConfig {
     }
 
     /// Look up a string-valued setting from the flattened `[[output]]` table,
-    /// trimmed and non-empty. Booleans/integers are stringified (for keys like
-    /// `dop`). Returns `None` when absent or empty.
+    /// trimmed and non-empty. Booleans/integers/floats are stringified (for
+    /// keys like `dop`, `quality`). Returns `None` when absent or empty.
     #[must_use]
     pub fn setting_str(&self, key: &str) -> Option<String> {
         setting_str(&self.settings, key)
     }
+
+    /// Look up a boolean setting from the flattened `[[output]]` table. Accepts
+    /// TOML booleans and MPD-style strings (`"yes"`/`"no"`, `"true"`/`"false"`,
+    /// `"on"`/`"off"`, `"1"`/`"0"`). Returns `None` when absent or unrecognized.
+    #[must_use]
+    pub fn setting_bool(&self, key: &str) -> Option<bool> {
+        match setting_str(&self.settings, key)?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "yes" | "true" | "on" | "1" => Some(true),
+            "no" | "false" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -187,8 +447,15 @@ pub struct DatabaseConfig {
     pub filesystem_watch: bool,
     #[serde(default = "default_cache_size")]
     pub cache_size: usize,
+    /// Keep an SQLite FTS5 index for `search any`. Off (or when SQLite was
+    /// built without FTS5) `search any` matches tag substrings instead.
     #[serde(default = "default_true")]
     pub fts_enabled: bool,
+    /// Allow clients to write tags back to library files with the
+    /// `writetag`/`cleartag` extension commands. Off by default: the library
+    /// is read-only.
+    #[serde(default)]
+    pub tag_editing: bool,
 }
 
 impl Default for DatabaseConfig {
@@ -198,6 +465,7 @@ impl Default for DatabaseConfig {
             filesystem_watch: true,
             cache_size: 64,
             fts_enabled: true,
+            tag_editing: false,
         }
     }
 }
@@ -248,6 +516,19 @@ pub enum ResamplerQuality {
     Linear,
 }
 
+impl ResamplerQuality {
+    /// The name used in the config.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SincBest => "sinc_best",
+            Self::SincMedium => "sinc_medium",
+            Self::SincFast => "sinc_fast",
+            Self::Linear => "linear",
+        }
+    }
+}
+
 /// DSD over PCM (DoP) policy for DSD sources.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -263,6 +544,18 @@ pub enum DopMode {
     Auto,
 }
 
+impl DopMode {
+    /// The name used in the config.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::No => "no",
+            Self::Yes => "yes",
+            Self::Auto => "auto",
+        }
+    }
+}
+
 // Default value functions
 fn default_music_dir() -> Utf8PathBuf {
     // Honor $XDG_MUSIC_DIR (e.g. ~/Musica) when set, else fall back to ~/Music.
@@ -279,17 +572,42 @@ fn default_playlist_dir() -> Utf8PathBuf {
 }
 
 fn default_db_file() -> Utf8PathBuf {
-    dirs::config_dir()
-        .map(|p| p.join("rmpd/database.db"))
-        .and_then(|p| Utf8PathBuf::try_from(p).ok())
-        .unwrap_or_else(|| Utf8PathBuf::from("~/.config/rmpd/database.db"))
+    default_data_file(
+        dirs::data_dir(),
+        "database.db",
+        "~/.local/share/rmpd/database.db",
+    )
 }
 
 fn default_state_file() -> Utf8PathBuf {
-    dirs::config_dir()
-        .map(|p| p.join("rmpd/state"))
+    // Only Linux has a separate state directory ($XDG_STATE_HOME).
+    default_data_file(
+        dirs::state_dir().or_else(dirs::data_dir),
+        "state",
+        "~/.local/state/rmpd/state",
+    )
+}
+
+/// `rmpd/<name>` under `base` (e.g. `$XDG_DATA_HOME`), or `fallback` when the
+/// platform has no such directory.
+fn default_data_file(base: Option<PathBuf>, name: &str, fallback: &str) -> Utf8PathBuf {
+    let path = base
+        .map(|p| p.join("rmpd").join(name))
         .and_then(|p| Utf8PathBuf::try_from(p).ok())
-        .unwrap_or_else(|| Utf8PathBuf::from("~/.config/rmpd/state"))
+        .unwrap_or_else(|| Utf8PathBuf::from(fallback));
+    let legacy = dirs::config_dir()
+        .map(|p| p.join("rmpd").join(name))
+        .and_then(|p| Utf8PathBuf::try_from(p).ok());
+    prefer_legacy(path, legacy)
+}
+
+/// Older versions kept the database and state file in the config directory;
+/// keep using such a file until one exists at the new location.
+fn prefer_legacy(path: Utf8PathBuf, legacy: Option<Utf8PathBuf>) -> Utf8PathBuf {
+    match legacy {
+        Some(legacy) if !path.exists() && legacy.is_file() => legacy,
+        _ => path,
+    }
 }
 
 fn default_log_level() -> String {
@@ -316,6 +634,34 @@ const fn default_connection_timeout() -> u64 {
     60
 }
 
+const fn default_tcp_keepalive() -> u64 {
+    60
+}
+
+const fn default_max_command_length() -> usize {
+    8 * 1024
+}
+
+const fn default_max_command_list_size() -> usize {
+    2048
+}
+
+const fn default_write_timeout() -> u64 {
+    30
+}
+
+const fn default_max_output_buffer_size() -> usize {
+    8192
+}
+
+const fn default_stream_bitrate() -> u32 {
+    96
+}
+
+const fn default_stream_max_clients() -> usize {
+    4
+}
+
 fn default_output() -> String {
     "default".to_owned()
 }
@@ -340,6 +686,10 @@ fn default_cache_size() -> usize {
     64
 }
 
+fn default_tag_rule_value() -> String {
+    "$1".to_owned()
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_path = Self::find_config_file()?;
@@ -361,7 +711,21 @@ impl Config {
 
     #[must_use]
     pub fn load_or_default() -> Self {
-        Self::load().unwrap_or_else(|_| Self::default())
+        Self::load().unwrap_or_else(|_| {
+            let mut config = Self::default();
+            config.expand_paths();
+            config.ensure_directories();
+            config
+        })
+    }
+
+    /// Re-read the config for a reload: from `path` when the server was
+    /// started with an explicit config file, else from the default locations.
+    pub fn reload(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load_from_path(path),
+            None => Self::load(),
+        }
     }
 
     /// Effective DoP mode. Prefers `[audio].dop`; if that is the default `No`,
@@ -436,13 +800,22 @@ impl Config {
         Err(RmpdError::Config("Config file not found".to_owned()))
     }
 
+    /// Expand `~` and environment variables in every configured path.
     fn expand_paths(&mut self) {
-        use crate::path::expand_tilde;
-
-        self.general.music_directory = expand_tilde(&self.general.music_directory);
-        self.general.playlist_directory = expand_tilde(&self.general.playlist_directory);
-        self.general.db_file = expand_tilde(&self.general.db_file);
-        self.general.state_file = expand_tilde(&self.general.state_file);
+        use crate::path::expand_path;
+
+        let general = &mut self.general;
+        for path in [
+            &mut general.music_directory,
+            &mut general.playlist_directory,
+            &mut general.db_file,
+            &mut general.state_file,
+        ] {
+            *path = expand_path(path);
+        }
+        for root in &mut general.music_roots {
+            root.path = expand_path(&root.path);
+        }
     }
 
     /// Create the directories referenced by the config entries if they do not
@@ -466,12 +839,68 @@ impl Config {
     }
 
     fn validate(&self) -> Result<()> {
-        if !self.general.music_directory.exists() {
-            return Err(RmpdError::Config(format!(
-                "Music directory not found: {}",
+        // Not fatal: the server runs with an empty library until the
+        // directory appears and the config is reloaded.
+        if self.general.music_directory.as_str().is_empty() {
+            tracing::warn!(
+                "no music_directory configured: the library stays empty until \
+                 general.music_directory is set and the config reloaded (SIGHUP)"
+            );
+        } else if !self.general.music_directory.is_dir() {
+            tracing::warn!(
+                "music directory not found: {}; the library stays empty until it \
+                 exists and the config is reloaded (SIGHUP)",
                 self.general.music_directory
+            );
+        }
+        let mut names = std::collections::HashSet::new();
+        for root in &self.general.music_roots {
+            if root.name.is_empty() || root.name.contains('/') || root.name.starts_with('.') {
+                return Err(RmpdError::Config(format!(
+                    "Invalid music root name {:?}: must be a single, non-hidden path component",
+                    root.name
+                )));
+            }
+            if !names.insert(root.name.as_str()) {
+                return Err(RmpdError::Config(format!(
+                    "Duplicate music root name: {}",
+                    root.name
+                )));
+            }
+            if !root.path.exists() {
+                tracing::warn!("music root {} not found: {}", root.name, root.path);
+            }
+        }
+        if let Some(spec) = &self.general.metadata_to_use {
+            crate::tag::MetadataMask::parse(spec).map_err(RmpdError::Config)?;
+        }
+        if let Some(rate) = self.audio.output_sample_rate
+            && !(8_000..=768_000).contains(&rate)
+        {
+            return Err(RmpdError::Config(format!(
+                "Invalid audio.output_sample_rate {rate}: expected 8000-768000 Hz"
+            )));
+        }
+        if let Some(hook) = self.hooks.iter().find(|h| h.command.trim().is_empty()) {
+            return Err(RmpdError::Config(format!(
+                "Hook for {} has an empty command",
+                hook.event.as_str()
             )));
         }
+        for (i, token) in self.api_tokens.iter().enumerate() {
+            if token.token.trim().is_empty() {
+                return Err(RmpdError::Config(format!(
+                    "api_token {} has an empty token",
+                    token.name
+                )));
+            }
+            if self.api_tokens[..i].iter().any(|t| t.token == token.token) {
+                return Err(RmpdError::Config(format!(
+                    "api_token {} repeats another token",
+                    token.name
+                )));
+            }
+        }
         Ok(())
     }
 }
@@ -487,6 +916,9 @@ impl Default for Config {
                 log_level: default_log_level(),
                 follow_symlinks: false,
                 filesystem_charset: default_charset(),
+                music_roots: Vec::new(),
+                metadata_to_use: None,
+                collation: None,
             },
             network: NetworkConfig {
                 bind_address: default_bind_address(),
@@ -494,7 +926,19 @@ impl Default for Config {
                 unix_socket: None,
                 max_connections: default_max_connections(),
                 connection_timeout: default_connection_timeout(),
+                tcp_keepalive: default_tcp_keepalive(),
+                max_command_length: default_max_command_length(),
+                max_command_list_size: default_max_command_list_size(),
+                write_timeout: default_write_timeout(),
+                max_output_buffer_size: default_max_output_buffer_size(),
                 password: None,
+                default_permissions: None,
+                local_permissions: None,
+                art_port: None,
+                stream_port: None,
+                stream_bitrate: default_stream_bitrate(),
+                stream_max_clients: default_stream_max_clients(),
+                metrics_port: None,
                 mpris: true,
             },
             audio: AudioConfig {
@@ -511,12 +955,21 @@ impl Default for Config {
                 crossfade: 0.0,
                 mixramp_db: default_mixramp_db(),
                 mixramp_delay: 0.0,
+                crossfade_loudness_match: false,
+                crossfade_skip_continuous: true,
+                output_sample_rate: None,
                 restore_paused: false,
+                inhibit_sleep: true,
+                resume_min_duration: None,
+                resume_directories: Vec::new(),
             },
             output: vec![],
             source: Vec::new(),
             decoder: DecoderConfig::default(),
             database: DatabaseConfig::default(),
+            tag_rules: Vec::new(),
+            hooks: Vec::new(),
+            api_tokens: Vec::new(),
         }
     }
 }
@@ -573,6 +1026,22 @@ mod tests {
         assert_eq!(c.output_device(), None);
     }
 
+    #[test]
+    fn output_setting_bool_accepts_mpd_style_values() {
+        let mut out = output_block(true);
+        out.settings
+            .insert("tags".to_owned(), toml::Value::String("no".to_owned()));
+        out.settings
+            .insert("always_on".to_owned(), toml::Value::Boolean(true));
+        out.settings
+            .insert("bogus".to_owned(), toml::Value::String("maybe".to_owned()));
+        assert_eq!(out.setting_bool("tags"), Some(false));
+        assert_eq!(out.setting_bool("always_on"), Some(true));
+        assert_eq!(out.setting_bool("dop"), Some(true));
+        assert_eq!(out.setting_bool("bogus"), None);
+        assert_eq!(out.setting_bool("missing"), None);
+    }
+
     #[test]
     fn ensure_directories_creates_configured_dirs() {
         let base = std::env::temp_dir().join(format!("rmpd-cfgtest-{}", std::process::id()));
@@ -580,7 +1049,7 @@ mod tests {
         std::fs::create_dir_all(&base).unwrap();
         let base = Utf8PathBuf::try_from(base).unwrap();
 
-        // music_directory must already exist (validate requires it).
+        // An existing music_directory keeps validate quiet.
         let music = base.join("music");
         std::fs::create_dir_all(&music).unwrap();
 
@@ -601,6 +1070,204 @@ mod tests {
         let _ = std::fs::remove_dir_all(&base);
     }
 
+    #[test]
+    fn default_data_files_leave_the_config_dir() {
+        let c = Config::default();
+        if let Some(data) = dirs::data_dir() {
+            let legacy = dirs::config_dir().unwrap().join("rmpd/database.db");
+            if !legacy.exists() {
+                assert_eq!(c.general.db_file, data.join("rmpd/database.db"));
+            }
+        }
+        assert!(c.general.state_file.ends_with("rmpd/state"));
+    }
+
+    #[test]
+    fn legacy_data_file_is_kept_until_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = Utf8PathBuf::try_from(dir.path().to_path_buf()).unwrap();
+        let new = base.join("data/rmpd/database.db");
+        let legacy = base.join("config/rmpd/database.db");
+
+        assert_eq!(prefer_legacy(new.clone(), Some(legacy.clone())), new);
+        std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        std::fs::write(&legacy, b"").unwrap();
+        assert_eq!(prefer_legacy(new.clone(), Some(legacy.clone())), legacy);
+        std::fs::create_dir_all(new.parent().unwrap()).unwrap();
+        std::fs::write(&new, b"").unwrap();
+        assert_eq!(prefer_legacy(new.clone(), Some(legacy)), new);
+    }
+
+    #[test]
+    fn paths_expand_home_and_variables() {
+        let Some(home) = dirs::home_dir().and_then(|h| Utf8PathBuf::from_path_buf(h).ok()) else {
+            return;
+        };
+        let mut c = Config {
+            general: toml::from_str(
+                r#"
+                music_directory = "${HOME}/Music"
+                db_file = "~/.rmpd/db"
+                playlist_directory = "$RMPD_SURELY_UNSET_VAR/playlists"
+                "#,
+            )
+            .unwrap(),
+            ..Config::default()
+        };
+        c.expand_paths();
+        assert_eq!(c.general.music_directory, home.join("Music"));
+        assert_eq!(c.general.db_file, home.join(".rmpd/db"));
+        assert_eq!(
+            c.general.playlist_directory,
+            "$RMPD_SURELY_UNSET_VAR/playlists"
+        );
+    }
+
+    #[test]
+    fn missing_music_directory_is_not_fatal() {
+        let mut c = Config {
+            general: toml::from_str("").unwrap(),
+            ..Config::default()
+        };
+        assert!(c.general.music_directory.as_str().is_empty());
+        assert!(c.general.available_music_directory().is_none());
+        assert!(c.validate().is_ok());
+
+        c.general.music_directory = Utf8PathBuf::from("/nonexistent/rmpd-music");
+        assert!(c.general.available_music_directory().is_none());
+        assert!(c.validate().is_ok());
+
+        c.general.music_directory = Utf8PathBuf::from("/");
+        assert_eq!(
+            c.general.available_music_directory(),
+            Some(Utf8Path::new("/"))
+        );
+    }
+
+    #[test]
+    fn music_roots_deserialize_and_validate() {
+        let toml_str = r#"
+music_directory = "/"
+
+[[music_root]]
+name = "external"
+path = "/mnt/external/music"
+"#;
+        let general: GeneralConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            general.music_roots,
+            [MusicRoot {
+                name: "external".to_owned(),
+                path: Utf8PathBuf::from("/mnt/external/music"),
+            }]
+        );
+
+        let mut c = Config::default();
+        c.general.music_directory = Utf8PathBuf::from("/");
+        c.general.music_roots = general.music_roots.clone();
+        assert!(c.validate().is_ok(), "a missing root only warns");
+
+        c.general.music_roots.push(general.music_roots[0].clone());
+        assert!(c.validate().is_err(), "duplicate names are rejected");
+
+        c.general.music_roots = vec![MusicRoot {
+            name: "a/b".to_owned(),
+            path: Utf8PathBuf::from("/"),
+        }];
+        assert!(c.validate().is_err(), "names must be one path component");
+    }
+
+    #[test]
+    fn tag_rules_deserialize_with_defaults() {
+        let toml_str = r#"
+[[tag_rule]]
+tag = "artist"
+pattern = '\s+feat\.\s+(.+)$'
+into = "performer"
+"#;
+        #[derive(Deserialize)]
+        struct Rules {
+            tag_rule: Vec<TagRule>,
+        }
+        let rules: Rules = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            rules.tag_rule,
+            [TagRule {
+                tag: "artist".to_owned(),
+                pattern: r"\s+feat\.\s+(.+)$".to_owned(),
+                replace: String::new(),
+                into: Some("performer".to_owned()),
+                value: "$1".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn hooks_deserialize_and_validate() {
+        let toml_str = r#"
+[[hook]]
+event = "song_change"
+command = "notify-send \"$RMPD_SONG_TITLE\""
+
+[[hook]]
+event = "database_update"
+command = "   "
+enabled = false
+"#;
+        #[derive(Deserialize)]
+        struct Hooks {
+            hook: Vec<Hook>,
+        }
+        let hooks: Hooks = toml::from_str(toml_str).unwrap();
+        assert_eq!(hooks.hook[0].event, HookEvent::SongChange);
+        assert!(hooks.hook[0].enabled, "enabled defaults to true");
+        assert_eq!(hooks.hook[1].event, HookEvent::DatabaseUpdate);
+
+        let mut c = Config::default();
+        c.general.music_directory = Utf8PathBuf::from("/");
+        c.hooks = vec![hooks.hook[0].clone()];
+        assert!(c.validate().is_ok());
+        c.hooks.push(hooks.hook[1].clone());
+        assert!(c.validate().is_err(), "empty commands are rejected");
+    }
+
+    #[test]
+    fn api_tokens_deserialize_and_validate() {
+        let toml_str = r#"
+[[api_token]]
+name = "web"
+token = "0123abcd"
+scopes = ["read", "control"]
+
+[[api_token]]
+token = "listen"
+"#;
+        #[derive(Deserialize)]
+        struct Tokens {
+            api_token: Vec<ApiToken>,
+        }
+        let tokens: Tokens = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            tokens.api_token[0].scopes,
+            [ApiScope::Read, ApiScope::Control]
+        );
+        assert_eq!(
+            tokens.api_token[1].scopes,
+            [ApiScope::Read],
+            "read by default"
+        );
+
+        let mut c = Config::default();
+        c.general.music_directory = Utf8PathBuf::from("/");
+        c.api_tokens = tokens.api_token.clone();
+        assert!(c.validate().is_ok());
+        c.api_tokens.push(tokens.api_token[1].clone());
+        assert!(c.validate().is_err(), "tokens must be unique");
+        c.api_tokens.pop();
+        c.api_tokens[0].token = " ".to_owned();
+        assert!(c.validate().is_err(), "empty tokens are rejected");
+    }
+
     #[test]
     fn source_config_deserializes_from_toml() {
         let toml_str = r#"
@@ -13,11 +13,15 @@ pub enum Event {
     PositionChanged(Duration),
     VolumeChanged(u8),
     BitrateChanged(Option<u32>), // Instantaneous bitrate in kbps (for VBR files)
-    SongFinished,
+    /// The decoder reached the end of the song. Carries the engine's playback
+    /// generation at the time, so an event that raced a manual `play`/`next`
+    /// (which starts a new generation) can be recognised as stale.
+    SongFinished(u64),
     /// The engine advanced to the look-ahead (next) song in-thread — gaplessly
     /// or via crossfade — instead of stopping. The protocol promotes its fed
-    /// "next" to current and feeds the following song.
-    AdvancedToNext,
+    /// "next" to current and feeds the following song. Carries the playback
+    /// generation like [`Event::SongFinished`].
+    AdvancedToNext(u64),
     /// A remote stream's ICY "now playing" title changed. Carries the new
     /// title (None clears it). Notifies the `player` subsystem so idle clients
     /// re-query `currentsong`.
@@ -34,6 +38,24 @@ pub enum Event {
     /// re-query `listplaylists` / `listplaylistinfo`.
     StoredPlaylistChanged,
 
+    // Sticker events
+    /// A sticker on the song at `uri` was set, incremented, decremented or
+    /// deleted. Notifies the `sticker` idle subsystem so clients re-query
+    /// ratings and play counts.
+    StickerChanged {
+        uri: String,
+    },
+
+    // Client messaging events
+    /// A client subscribed to or unsubscribed from a channel. Notifies the
+    /// `subscription` idle subsystem so clients re-query `channels`.
+    SubscriptionChanged,
+    /// A message was queued for the clients with ids `recipients`. Only
+    /// their `message` idle subsystem is notified.
+    MessageQueued {
+        recipients: Vec<u64>,
+    },
+
     // Database events
     DatabaseUpdateStarted,
     DatabaseUpdateProgress {
@@ -41,10 +63,19 @@ pub enum Event {
         total: u32,
     },
     DatabaseUpdateFinished,
+    /// An update job (library scan or source sync) added, updated or removed
+    /// songs. Emitted just before `DatabaseUpdateFinished`; a job that found
+    /// nothing to do leaves the `database` subsystem quiet.
+    DatabaseChanged,
 
     // Output events
     OutputsChanged,
 
+    // Partition events
+    /// A partition was created or deleted. Notifies the `partition` idle
+    /// subsystem so clients re-query `listpartitions`.
+    PartitionsChanged,
+
     // Filesystem watcher events
     FilesystemWatchStarted,
     FilesystemWatchStopped,
@@ -53,6 +84,11 @@ pub enum Event {
     SongDeleted {
         path: String,
     },
+    /// Cached artwork for `path` (a song, or a directory whose cover file
+    /// changed) was invalidated; clients should refetch `albumart`.
+    ArtworkChanged {
+        path: String,
+    },
 }
 
 /// Maps to MPD's idle subsystems
@@ -81,7 +117,7 @@ impl Event {
             // NOT for position/bitrate changes - those are too frequent and should be polled
             Event::PlayerStateChanged(_)
             | Event::SongChanged(_)
-            | Event::SongFinished
+            | Event::SongFinished(_)
             | Event::StreamTitleChanged(_) => &[Subsystem::Player],
             // Position and bitrate changes are internal - don't notify idle
             Event::PositionChanged(_) | Event::BitrateChanged(_) => &[],
@@ -89,34 +125,99 @@ impl Event {
             Event::QueueChanged => &[Subsystem::Playlist],
             Event::QueueOptionsChanged => &[Subsystem::Options],
             Event::StoredPlaylistChanged => &[Subsystem::StoredPlaylist],
+            Event::StickerChanged { .. } => &[Subsystem::Sticker],
+            Event::SubscriptionChanged => &[Subsystem::Subscription],
+            Event::MessageQueued { .. } => &[Subsystem::Message],
             Event::DatabaseUpdateStarted | Event::DatabaseUpdateProgress { .. } => {
                 &[Subsystem::Update]
             }
-            Event::DatabaseUpdateFinished => &[Subsystem::Database, Subsystem::Update],
-            Event::SongAdded(_) | Event::SongUpdated(_) | Event::SongDeleted { .. } => {
-                &[Subsystem::Database]
-            }
+            Event::DatabaseUpdateFinished => &[Subsystem::Update],
+            Event::DatabaseChanged
+            | Event::SongAdded(_)
+            | Event::SongUpdated(_)
+            | Event::SongDeleted { .. }
+            | Event::ArtworkChanged { .. } => &[Subsystem::Database],
             Event::OutputsChanged => &[Subsystem::Output],
+            Event::PartitionsChanged => &[Subsystem::Partition],
             Event::FilesystemWatchStarted | Event::FilesystemWatchStopped => &[],
             _ => &[],
         }
     }
 }
 
+impl Event {
+    /// Whether the event concerns one partition's player or queue rather
+    /// than the whole server (see [`EventBus::child`]).
+    pub fn is_partition_local(&self) -> bool {
+        matches!(
+            self,
+            Event::PlayerStateChanged(_)
+                | Event::SongChanged(_)
+                | Event::PositionChanged(_)
+                | Event::VolumeChanged(_)
+                | Event::BitrateChanged(_)
+                | Event::SongFinished(_)
+                | Event::AdvancedToNext(_)
+                | Event::StreamTitleChanged(_)
+                | Event::QueueChanged
+                | Event::QueueOptionsChanged
+        )
+    }
+}
+
 /// Central event bus
 #[derive(Debug, Clone)]
 pub struct EventBus {
     sender: broadcast::Sender<Event>,
+    /// Server-wide bus of a partition's bus; see [`EventBus::child`].
+    parent: Option<broadcast::Sender<Event>>,
 }
 
 impl EventBus {
     pub fn new() -> Self {
         let (sender, _) = broadcast::channel(4096);
-        Self { sender }
+        Self {
+            sender,
+            parent: None,
+        }
+    }
+
+    /// A bus for one partition. Its partition-local events (see
+    /// [`Event::is_partition_local`]) stay on it; every other event goes
+    /// through `self`, so each partition, and the server, sees it. Must be
+    /// called within a Tokio runtime: a task forwards those events until the
+    /// child is dropped.
+    pub fn child(&self) -> EventBus {
+        let child = EventBus {
+            sender: broadcast::channel(4096).0,
+            parent: Some(self.sender.clone()),
+        };
+        let weak = child.sender.downgrade();
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) if event.is_partition_local() => {}
+                    Ok(event) => {
+                        let Some(sender) = weak.upgrade() else { break };
+                        let _ = sender.send(event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("partition event forwarder lagged, {n} events skipped");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        child
     }
 
     pub fn emit(&self, event: Event) {
-        if let Err(e) = self.sender.send(event) {
+        let sender = match &self.parent {
+            Some(parent) if !event.is_partition_local() => parent,
+            _ => &self.sender,
+        };
+        if let Err(e) = sender.send(event) {
             tracing::debug!("event dropped (no active subscribers): {}", e);
         }
     }
@@ -131,3 +232,36 @@ impl Default for EventBus {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn child_bus_keeps_partition_events_local() {
+        let server = EventBus::new();
+        let partition = server.child();
+        let mut server_rx = server.subscribe();
+        let mut partition_rx = partition.subscribe();
+
+        partition.emit(Event::QueueChanged);
+        server.emit(Event::VolumeChanged(50));
+        assert!(matches!(partition_rx.recv().await, Ok(Event::QueueChanged)));
+        assert!(matches!(
+            server_rx.recv().await,
+            Ok(Event::VolumeChanged(50))
+        ));
+
+        // Server-wide events reach both, whichever bus they are emitted on.
+        partition.emit(Event::StoredPlaylistChanged);
+        server.emit(Event::DatabaseChanged);
+        for expected in [Subsystem::StoredPlaylist, Subsystem::Database] {
+            let event = server_rx.recv().await.unwrap();
+            assert_eq!(event.subsystems(), [expected]);
+            let event = partition_rx.recv().await.unwrap();
+            assert_eq!(event.subsystems(), [expected]);
+        }
+        assert!(server_rx.try_recv().is_err());
+        assert!(partition_rx.try_recv().is_err());
+    }
+}
@@ -7,9 +7,24 @@
 ///            | (EXPRESSION) OR (EXPRESSION)
 ///            | ! EXPRESSION
 ///            | TAG OPERATOR VALUE
+///            | added-since VALUE
+///            | modified-since VALUE
 /// OPERATOR := == | != | =~ | !~ | < | > | <= | >=
+///
+/// `added` and `Last-Modified` compare the song's timestamps instead of tags;
+/// their values (and those of the `*-since` forms) are Unix seconds or ISO 8601.
 use crate::error::{Result, RmpdError};
 use crate::tag::tag_fallback_chain;
+use crate::time::parse_timestamp;
+
+/// The `songs` column behind a timestamp pseudo-tag (`added`, `Last-Modified`).
+fn timestamp_column(tag: &str) -> Option<&'static str> {
+    match tag.to_ascii_lowercase().as_str() {
+        "added" => Some("added_at"),
+        "last-modified" => Some("last_modified"),
+        _ => None,
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum FilterExpression {
@@ -69,6 +84,11 @@ impl FilterExpression {
                     return (format!("path {sql_op} ?"), vec![value_param]);
                 }
 
+                if let Some(column) = timestamp_column(&tag_lower) {
+                    let (sql_op, value_param) = op_to_sql(op, value);
+                    return (format!("{column} {sql_op} ?"), vec![value_param]);
+                }
+
                 let fallback_tags = tag_fallback_chain(&tag_lower);
                 // Tag names are client-controlled (unrecognized tags pass through
                 // `tag_fallback_chain` verbatim), so they must be bound as
@@ -98,10 +118,14 @@ impl FilterExpression {
                     return (sql, tag_params);
                 }
 
-                let (sql_op, value_param) = op_to_sql(op, value);
+                let (value_cmp, value_param) = typed_comparison(&tag_lower, op, value)
+                    .unwrap_or_else(|| {
+                        let (sql_op, value_param) = op_to_sql(op, value);
+                        (format!("st.value {sql_op} ?"), value_param)
+                    });
                 let sql = format!(
                     "EXISTS (SELECT 1 FROM song_tags st WHERE st.song_id = songs.id \
-                     AND st.tag IN ({tag_placeholders}) AND st.value {sql_op} ?)"
+                     AND st.tag IN ({tag_placeholders}) AND {value_cmp})"
                 );
                 let mut params = tag_params;
                 params.push(value_param);
@@ -127,6 +151,45 @@ impl FilterExpression {
     }
 }
 
+/// Tags holding numbers (possibly as `N/TOTAL`), compared numerically by the
+/// relational operators.
+const NUMERIC_TAGS: &[&str] = &["track", "disc", "movementnumber"];
+
+/// Tags holding `YYYY[-MM[-DD]]` dates. Relational operators compare them at
+/// the precision of the filter value, so `date >= "1990"` matches `1990-05-01`.
+const DATE_TAGS: &[&str] = &["date", "originaldate"];
+
+/// SQL comparison of `st.value` for relational operators on numeric and date
+/// tags, or `None` to compare as plain strings.
+fn typed_comparison(tag: &str, op: &CompareOp, value: &str) -> Option<(String, String)> {
+    if !matches!(
+        op,
+        CompareOp::Less | CompareOp::Greater | CompareOp::LessEqual | CompareOp::GreaterEqual
+    ) {
+        return None;
+    }
+    let (sql_op, _) = op_to_sql(op, value);
+    let value = value.trim();
+
+    if NUMERIC_TAGS.contains(&tag) && value.parse::<i64>().is_ok() {
+        // CAST takes the leading integer, so "3/12" compares as 3.
+        return Some((
+            format!("CAST(st.value AS INTEGER) {sql_op} ?"),
+            value.to_string(),
+        ));
+    }
+    if DATE_TAGS.contains(&tag)
+        && value.starts_with(|c: char| c.is_ascii_digit())
+        && value.bytes().all(|b| b.is_ascii_digit() || b == b'-')
+    {
+        return Some((
+            format!("substr(st.value, 1, {}) {sql_op} ?", value.len()),
+            value.to_string(),
+        ));
+    }
+    None
+}
+
 fn op_to_sql(op: &CompareOp, value: &str) -> (&'static str, String) {
     match op {
         CompareOp::Equal => ("=", value.to_string()),
@@ -219,14 +282,37 @@ impl<'a> Parser<'a> {
         let tag = self.parse_identifier()?;
         self.skip_whitespace();
 
+        let since = match tag.to_ascii_lowercase().as_str() {
+            "added-since" => Some("added"),
+            "modified-since" => Some("Last-Modified"),
+            _ => None,
+        };
+        if let Some(tag) = since {
+            let value = self.parse_quoted_value()?;
+            return Ok(FilterExpression::Compare {
+                tag: tag.to_owned(),
+                op: CompareOp::GreaterEqual,
+                value: Self::timestamp_value(&value)?,
+            });
+        }
+
         let op = self.parse_operator()?;
         self.skip_whitespace();
 
-        let value = self.parse_quoted_value()?;
+        let mut value = self.parse_quoted_value()?;
+        if timestamp_column(&tag).is_some() {
+            value = Self::timestamp_value(&value)?;
+        }
 
         Ok(FilterExpression::Compare { tag, op, value })
     }
 
+    fn timestamp_value(value: &str) -> Result<String> {
+        parse_timestamp(value)
+            .map(|secs| secs.to_string())
+            .ok_or_else(|| RmpdError::ParseError(format!("Invalid timestamp: {value}")))
+    }
+
     fn parse_identifier(&mut self) -> Result<String> {
         let start = self.pos;
         while self.pos < self.input.len() {
@@ -444,4 +530,60 @@ mod tests {
         assert!(sql.contains("st.value != ''"), "got: {sql}");
         assert_eq!(params, vec!["artist"]);
     }
+
+    #[test]
+    fn test_added_since_compares_timestamp_column() {
+        let expr = FilterExpression::parse("(added-since '2024-03-01')").unwrap();
+        assert_eq!(
+            expr,
+            FilterExpression::Compare {
+                tag: "added".to_string(),
+                op: CompareOp::GreaterEqual,
+                value: "1709251200".to_string(),
+            }
+        );
+        let (sql, params) = expr.to_sql();
+        assert_eq!(sql, "added_at >= ?");
+        assert_eq!(params, vec!["1709251200"]);
+
+        let (sql, _) = FilterExpression::parse("(modified-since '1700000000')")
+            .unwrap()
+            .to_sql();
+        assert_eq!(sql, "last_modified >= ?");
+
+        let (sql, params) = FilterExpression::parse("(added < '2024-03-01T00:00:00Z')")
+            .unwrap()
+            .to_sql();
+        assert_eq!(sql, "added_at < ?");
+        assert_eq!(params, vec!["1709251200"]);
+
+        assert!(FilterExpression::parse("(added-since 'last week')").is_err());
+    }
+
+    #[test]
+    fn test_numeric_tags_compare_as_integers() {
+        let (sql, params) = FilterExpression::parse("(track >= '3')").unwrap().to_sql();
+        assert!(sql.contains("CAST(st.value AS INTEGER) >= ?"), "got: {sql}");
+        assert_eq!(params.last().map(String::as_str), Some("3"));
+
+        // Equality and non-numeric values keep string semantics.
+        let (sql, _) = FilterExpression::parse("(track == '3')").unwrap().to_sql();
+        assert!(sql.contains("st.value = ?"), "got: {sql}");
+        let (sql, _) = FilterExpression::parse("(disc > 'x')").unwrap().to_sql();
+        assert!(sql.contains("st.value > ?"), "got: {sql}");
+    }
+
+    #[test]
+    fn test_date_compares_at_filter_precision() {
+        let (sql, params) = FilterExpression::parse("(date >= '1990')")
+            .unwrap()
+            .to_sql();
+        assert!(sql.contains("substr(st.value, 1, 4) >= ?"), "got: {sql}");
+        assert_eq!(params.last().map(String::as_str), Some("1990"));
+
+        let (sql, _) = FilterExpression::parse("(originaldate < '1990-05-01')")
+            .unwrap()
+            .to_sql();
+        assert!(sql.contains("substr(st.value, 1, 10) < ?"), "got: {sql}");
+    }
 }
@@ -3,123 +3,156 @@
 //! A generic message broker that allows clients to subscribe to named channels
 //! and send/receive messages. Originally designed for MPD protocol but can be
 //! used for any pub-sub messaging needs.
+//!
+//! Subscribers are identified by a numeric client id. Every subscriber has its
+//! own message queue: a message sent to a channel is copied to the queue of
+//! each client subscribed at that moment, and reading drains only the
+//! reader's queue, so one client never consumes another's messages.
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-/// Maximum messages to store per channel
-const MAX_MESSAGES_PER_CHANNEL: usize = 100;
+/// Maximum messages queued per client; older ones are dropped first
+pub const MAX_MESSAGES_PER_CLIENT: usize = 100;
+
+/// Maximum channels one client may subscribe to (MPD's limit)
+pub const MAX_SUBSCRIPTIONS_PER_CLIENT: usize = 16;
 
 /// A message in a channel
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Message {
     pub channel: String,
     pub text: String,
 }
 
+/// Outcome of [`MessageBroker::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscribeResult {
+    Ok,
+    AlreadySubscribed,
+    /// The client holds [`MAX_SUBSCRIPTIONS_PER_CLIENT`] subscriptions
+    Full,
+}
+
 /// Message broker managing channels and message delivery
 #[derive(Debug, Clone)]
 pub struct MessageBroker {
     inner: Arc<RwLock<MessageBrokerInner>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct MessageBrokerInner {
-    /// Messages queued for each channel
-    channels: HashMap<String, VecDeque<Message>>,
-    /// Number of active subscribers per channel
-    subscriber_counts: HashMap<String, usize>,
+    /// Subscribed client ids per channel; channels without subscribers are
+    /// removed
+    channels: BTreeMap<String, BTreeSet<u64>>,
+    /// Messages waiting to be read, per client
+    queues: HashMap<u64, VecDeque<Message>>,
+}
+
+impl MessageBrokerInner {
+    fn subscription_count(&self, client: u64) -> usize {
+        self.channels
+            .values()
+            .filter(|subscribers| subscribers.contains(&client))
+            .count()
+    }
 }
 
 impl MessageBroker {
     /// Create a new message broker
     pub fn new() -> Self {
         Self {
-            inner: Arc::new(RwLock::new(MessageBrokerInner {
-                channels: HashMap::new(),
-                subscriber_counts: HashMap::new(),
-            })),
+            inner: Arc::new(RwLock::new(MessageBrokerInner::default())),
         }
     }
 
-    /// Send a message to a channel. Returns false if nobody is subscribed.
-    /// Send a message to a channel. Returns false if nobody is subscribed.
-    pub async fn send_message(&self, channel: String, text: String) -> bool {
+    /// Subscribe `client` to `channel`.
+    pub async fn subscribe(&self, client: u64, channel: &str) -> SubscribeResult {
         let mut inner = self.inner.write().await;
-        // Check if anyone is subscribed
-        let count = inner.subscriber_counts.get(&channel).copied().unwrap_or(0);
-        if count == 0 {
-            return false;
+        if inner
+            .channels
+            .get(channel)
+            .is_some_and(|subscribers| subscribers.contains(&client))
+        {
+            return SubscribeResult::AlreadySubscribed;
         }
-        let message = Message {
-            channel: channel.clone(),
-            text,
-        };
-
-        let queue = inner.channels.entry(channel).or_insert_with(VecDeque::new);
-        queue.push_back(message);
-
-        // Limit queue size
-        if queue.len() > MAX_MESSAGES_PER_CHANNEL {
-            queue.pop_front();
+        if inner.subscription_count(client) >= MAX_SUBSCRIPTIONS_PER_CLIENT {
+            return SubscribeResult::Full;
         }
-        true
+        inner
+            .channels
+            .entry(channel.to_owned())
+            .or_default()
+            .insert(client);
+        SubscribeResult::Ok
     }
 
-    /// Register a subscription to a channel.
-    pub async fn register_subscriber(&self, channel: &str) {
+    /// Unsubscribe `client` from `channel`. Returns false if it was not
+    /// subscribed. Messages already queued for the client are kept.
+    pub async fn unsubscribe(&self, client: u64, channel: &str) -> bool {
         let mut inner = self.inner.write().await;
-        *inner
-            .subscriber_counts
-            .entry(channel.to_string())
-            .or_insert(0) += 1;
+        let Some(subscribers) = inner.channels.get_mut(channel) else {
+            return false;
+        };
+        let removed = subscribers.remove(&client);
+        if subscribers.is_empty() {
+            inner.channels.remove(channel);
+        }
+        removed
     }
 
-    /// Unregister a subscription from a channel.
-    pub async fn unregister_subscriber(&self, channel: &str) {
+    /// Drop every subscription and queued message of a disconnected
+    /// `client`. Returns true if it had any subscription.
+    pub async fn remove_client(&self, client: u64) -> bool {
         let mut inner = self.inner.write().await;
-        if let Some(count) = inner.subscriber_counts.get_mut(channel) {
-            if *count > 0 {
-                *count -= 1;
-            }
-            if *count == 0 {
-                inner.subscriber_counts.remove(channel);
-            }
-        }
+        inner.queues.remove(&client);
+        let mut had_subscriptions = false;
+        inner.channels.retain(|_, subscribers| {
+            had_subscriptions |= subscribers.remove(&client);
+            !subscribers.is_empty()
+        });
+        had_subscriptions
     }
 
-    /// Get all messages from channels the client is subscribed to
-    pub async fn read_messages(&self, subscribed_channels: &[String]) -> Vec<Message> {
+    /// Send a message to every client subscribed to `channel`. Returns the
+    /// ids of the clients it was queued for (empty if nobody is subscribed).
+    pub async fn send_message(&self, channel: &str, text: &str) -> Vec<u64> {
         let mut inner = self.inner.write().await;
-        let mut messages = Vec::new();
-
-        for channel in subscribed_channels {
-            if let Some(queue) = inner.channels.get_mut(channel) {
-                // Drain all messages from this channel
-                messages.extend(queue.drain(..));
+        let recipients: Vec<u64> = inner
+            .channels
+            .get(channel)
+            .map(|subscribers| subscribers.iter().copied().collect())
+            .unwrap_or_default();
+
+        for &client in &recipients {
+            let queue = inner.queues.entry(client).or_default();
+            queue.push_back(Message {
+                channel: channel.to_owned(),
+                text: text.to_owned(),
+            });
+            // Limit queue size
+            if queue.len() > MAX_MESSAGES_PER_CLIENT {
+                queue.pop_front();
             }
         }
+        recipients
+    }
 
-        messages
+    /// Take all messages queued for `client`, oldest first
+    pub async fn read_messages(&self, client: u64) -> Vec<Message> {
+        let mut inner = self.inner.write().await;
+        inner
+            .queues
+            .remove(&client)
+            .map(Vec::from)
+            .unwrap_or_default()
     }
 
-    /// Get list of all active channels (channels with messages or subscribers)
+    /// Channels with at least one subscriber, sorted
     pub async fn list_channels(&self) -> Vec<String> {
         let inner = self.inner.read().await;
-        // Include channels with messages OR active subscribers
-        let mut channels: std::collections::HashSet<String> = inner
-            .channels
-            .iter()
-            .filter(|(_, queue)| !queue.is_empty())
-            .map(|(name, _)| name.clone())
-            .collect();
-        for name in inner.subscriber_counts.keys() {
-            channels.insert(name.clone());
-        }
-        let mut result: Vec<String> = channels.into_iter().collect();
-        result.sort();
-        result
+        inner.channels.keys().cloned().collect()
     }
 }
 
@@ -137,91 +170,102 @@ mod tests {
     async fn test_send_and_read_message() {
         let broker = MessageBroker::new();
 
-        broker.register_subscriber("test").await;
-        broker
-            .send_message("test".to_string(), "hello".to_string())
-            .await;
+        assert_eq!(broker.subscribe(1, "test").await, SubscribeResult::Ok);
+        assert_eq!(broker.send_message("test", "hello").await, vec![1]);
 
-        let messages = broker.read_messages(&["test".to_string()]).await;
+        let messages = broker.read_messages(1).await;
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].channel, "test");
         assert_eq!(messages[0].text, "hello");
     }
 
     #[tokio::test]
-    async fn test_multiple_channels() {
+    async fn test_each_subscriber_gets_a_copy() {
         let broker = MessageBroker::new();
 
-        broker.register_subscriber("channel1").await;
-        broker.register_subscriber("channel2").await;
-        broker
-            .send_message("channel1".to_string(), "msg1".to_string())
-            .await;
-        broker
-            .send_message("channel2".to_string(), "msg2".to_string())
-            .await;
-
-        let messages = broker.read_messages(&["channel1".to_string()]).await;
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].text, "msg1");
+        broker.subscribe(1, "test").await;
+        broker.subscribe(2, "test").await;
+        broker.subscribe(3, "other").await;
+        assert_eq!(broker.send_message("test", "hello").await, vec![1, 2]);
 
-        let messages = broker.read_messages(&["channel2".to_string()]).await;
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].text, "msg2");
+        assert_eq!(broker.read_messages(1).await.len(), 1);
+        assert_eq!(broker.read_messages(2).await.len(), 1);
+        assert!(broker.read_messages(3).await.is_empty());
     }
 
     #[tokio::test]
     async fn test_messages_are_consumed() {
         let broker = MessageBroker::new();
 
-        broker.register_subscriber("test").await;
-        broker
-            .send_message("test".to_string(), "hello".to_string())
-            .await;
+        broker.subscribe(1, "test").await;
+        broker.send_message("test", "hello").await;
 
         // First read gets the message
-        let messages = broker.read_messages(&["test".to_string()]).await;
+        let messages = broker.read_messages(1).await;
         assert_eq!(messages.len(), 1);
 
         // Second read gets nothing (messages consumed)
-        let messages = broker.read_messages(&["test".to_string()]).await;
+        let messages = broker.read_messages(1).await;
         assert_eq!(messages.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_send_without_subscribers() {
+        let broker = MessageBroker::new();
+
+        assert!(broker.send_message("test", "hello").await.is_empty());
+        broker.subscribe(1, "test").await;
+        assert!(broker.unsubscribe(1, "test").await);
+        assert!(!broker.unsubscribe(1, "test").await);
+        assert!(broker.send_message("test", "hello").await.is_empty());
+    }
+
     #[tokio::test]
     async fn test_list_channels() {
         let broker = MessageBroker::new();
 
-        broker.register_subscriber("channel1").await;
-        broker.register_subscriber("channel2").await;
-        broker
-            .send_message("channel1".to_string(), "msg1".to_string())
-            .await;
-        broker
-            .send_message("channel2".to_string(), "msg2".to_string())
-            .await;
+        broker.subscribe(1, "channel2").await;
+        broker.subscribe(2, "channel1").await;
+        broker.subscribe(2, "channel2").await;
+        assert_eq!(
+            broker.list_channels().await,
+            vec!["channel1".to_string(), "channel2".to_string()]
+        );
+
+        assert!(broker.remove_client(2).await);
+        assert_eq!(broker.list_channels().await, vec!["channel2".to_string()]);
+        assert!(!broker.remove_client(2).await);
+    }
 
-        let channels = broker.list_channels().await;
-        assert_eq!(channels.len(), 2);
-        assert!(channels.contains(&"channel1".to_string()));
-        assert!(channels.contains(&"channel2".to_string()));
+    #[tokio::test]
+    async fn test_subscription_limits() {
+        let broker = MessageBroker::new();
+
+        assert_eq!(broker.subscribe(1, "test").await, SubscribeResult::Ok);
+        assert_eq!(
+            broker.subscribe(1, "test").await,
+            SubscribeResult::AlreadySubscribed
+        );
+        for i in 1..MAX_SUBSCRIPTIONS_PER_CLIENT {
+            broker.subscribe(1, &format!("channel{i}")).await;
+        }
+        assert_eq!(broker.subscribe(1, "onemore").await, SubscribeResult::Full);
+        assert_eq!(broker.subscribe(2, "onemore").await, SubscribeResult::Ok);
     }
 
     #[tokio::test]
     async fn test_max_messages_limit() {
         let broker = MessageBroker::new();
 
-        broker.register_subscriber("test").await;
-        // Send more than MAX_MESSAGES_PER_CHANNEL
+        broker.subscribe(1, "test").await;
+        // Send more than MAX_MESSAGES_PER_CLIENT
         for i in 0..150 {
-            broker
-                .send_message("test".to_string(), format!("msg{}", i))
-                .await;
+            broker.send_message("test", &format!("msg{}", i)).await;
         }
 
-        let messages = broker.read_messages(&["test".to_string()]).await;
-        // Should only keep the last MAX_MESSAGES_PER_CHANNEL messages
-        assert_eq!(messages.len(), MAX_MESSAGES_PER_CHANNEL);
+        let messages = broker.read_messages(1).await;
+        // Should only keep the last MAX_MESSAGES_PER_CLIENT messages
+        assert_eq!(messages.len(), MAX_MESSAGES_PER_CLIENT);
         // First message should be msg50 (last 100 messages)
         assert_eq!(messages[0].text, "msg50");
     }
@@ -5,6 +5,7 @@ use crate::state::PlayerStatus;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::RwLock;
 
 /// Represents a single partition with its own playback state
@@ -28,7 +29,8 @@ pub struct PartitionState {
     /// Lock-free state access for performance
     pub atomic_state: Arc<std::sync::atomic::AtomicU8>,
 
-    /// Event bus for this partition
+    /// Event bus for this partition; for partitions other than the default
+    /// one, a child of the server's bus (see [`EventBus::child`])
     pub event_bus: EventBus,
 
     /// Message broker for client messaging
@@ -36,28 +38,60 @@ pub struct PartitionState {
 
     /// Output IDs assigned to this partition
     pub assigned_outputs: Arc<RwLock<Vec<u32>>>,
+
+    /// Number of connected clients that selected this partition
+    clients: Arc<AtomicUsize>,
 }
 
 impl PartitionState {
     /// Create a new partition with the given name
     pub fn new(name: String) -> Self {
-        let event_bus = EventBus::new();
-        let status = Arc::new(RwLock::new(PlayerStatus::default()));
+        Self::with_queue(
+            name,
+            Arc::new(RwLock::new(Queue::new())),
+            Arc::new(RwLock::new(PlayerStatus::default())),
+            EventBus::new(),
+        )
+    }
+
+    /// Create a partition around an existing queue, player status and event
+    /// bus, as the default partition does with the server's own
+    pub fn with_queue(
+        name: String,
+        queue: Arc<RwLock<Queue>>,
+        status: Arc<RwLock<PlayerStatus>>,
+        event_bus: EventBus,
+    ) -> Self {
         let atomic_state = Arc::new(std::sync::atomic::AtomicU8::new(
             crate::state::PlayerState::Stop as u8,
         ));
 
         Self {
             name,
-            queue: Arc::new(RwLock::new(Queue::new())),
+            queue,
             status,
             atomic_state,
             event_bus,
             message_broker: MessageBroker::new(),
             assigned_outputs: Arc::new(RwLock::new(Vec::new())),
+            clients: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Count a client into this partition until the returned guard is
+    /// dropped (the client switches partition or disconnects)
+    pub fn enter(&self) -> PartitionClient {
+        self.clients.fetch_add(1, Ordering::AcqRel);
+        PartitionClient {
+            clients: self.clients.clone(),
+        }
+    }
+
+    /// Number of connected clients in this partition
+    pub fn client_count(&self) -> usize {
+        self.clients.load(Ordering::Acquire)
+    }
+
     /// Assign an output to this partition
     pub async fn assign_output(&self, output_id: u32) {
         let mut outputs = self.assigned_outputs.write().await;
@@ -78,6 +112,18 @@ impl PartitionState {
     }
 }
 
+/// Keeps a client counted in a partition while alive
+#[derive(Debug)]
+pub struct PartitionClient {
+    clients: Arc<AtomicUsize>,
+}
+
+impl Drop for PartitionClient {
+    fn drop(&mut self) {
+        self.clients.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 /// Information about a partition for serialization
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartitionInfo {
@@ -93,18 +139,22 @@ pub struct PartitionManager {
 impl PartitionManager {
     /// Create a new partition manager with a default partition
     pub fn new() -> Arc<Self> {
+        Self::with_default(PartitionState::new("default".to_string()))
+    }
+
+    /// Create a partition manager whose "default" partition is `default`
+    pub fn with_default(default: PartitionState) -> Arc<Self> {
         let mut partitions = HashMap::new();
         // Always pre-populate the "default" partition — MPD always has it
-        partitions.insert(
-            "default".to_string(),
-            Arc::new(PartitionState::new("default".to_string())),
-        );
+        partitions.insert("default".to_string(), Arc::new(default));
         Arc::new(Self {
             partitions: RwLock::new(partitions),
         })
     }
 
-    /// Create a new partition
+    /// Create a new partition with an empty queue. Its events are local to
+    /// it except for server-wide ones, shared through the default
+    /// partition's bus.
     pub async fn create_partition(&self, name: String) -> Result<Arc<PartitionState>, String> {
         let mut partitions = self.partitions.write().await;
 
@@ -112,13 +162,23 @@ impl PartitionManager {
             return Err(format!("Partition already exists: {}", name));
         }
 
-        let partition = Arc::new(PartitionState::new(name.clone()));
+        let event_bus = match partitions.get("default") {
+            Some(default) => default.event_bus.child(),
+            None => EventBus::new(),
+        };
+        let partition = Arc::new(PartitionState::with_queue(
+            name.clone(),
+            Arc::new(RwLock::new(Queue::new())),
+            Arc::new(RwLock::new(PlayerStatus::default())),
+            event_bus,
+        ));
         partitions.insert(name, partition.clone());
 
         Ok(partition)
     }
 
-    /// Delete a partition
+    /// Delete a partition. Like MPD, only an unused one: no client may have
+    /// it selected and no output may be assigned to it.
     pub async fn delete_partition(&self, name: &str) -> Result<(), String> {
         // Cannot delete default partition
         if name == "default" {
@@ -127,8 +187,14 @@ impl PartitionManager {
 
         let mut partitions = self.partitions.write().await;
 
-        if !partitions.contains_key(name) {
+        let Some(partition) = partitions.get(name) else {
             return Err(format!("Partition not found: {}", name));
+        };
+        if partition.client_count() > 0 {
+            return Err(format!("Partition still has clients: {}", name));
+        }
+        if !partition.assigned_outputs.read().await.is_empty() {
+            return Err(format!("Partition still has outputs: {}", name));
         }
 
         partitions.remove(name);
@@ -284,6 +350,45 @@ mod tests {
         assert!(names.contains(&"default".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_default_partition_shares_queue() {
+        let queue = Arc::new(RwLock::new(Queue::new()));
+        let status = Arc::new(RwLock::new(PlayerStatus::default()));
+        let manager = PartitionManager::with_default(PartitionState::with_queue(
+            "default".to_string(),
+            queue.clone(),
+            status,
+            EventBus::new(),
+        ));
+        let other = manager.create_partition("other".to_string()).await.unwrap();
+
+        queue
+            .write()
+            .await
+            .add(crate::test_utils::make_test_song("a.flac", 1));
+        let default = manager.get_partition("default").await.unwrap();
+        assert_eq!(default.queue.read().await.len(), 1);
+        assert!(other.queue.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_partition_in_use() {
+        let manager = PartitionManager::new();
+
+        let part = manager.create_partition("test".to_string()).await.unwrap();
+        let client = part.enter();
+        let result = manager.delete_partition("test").await;
+        assert!(result.unwrap_err().contains("still has clients"));
+
+        drop(client);
+        part.assign_output(0).await;
+        let result = manager.delete_partition("test").await;
+        assert!(result.unwrap_err().contains("still has outputs"));
+
+        part.remove_output(0).await;
+        assert!(manager.delete_partition("test").await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_output_assignment() {
         let partition = PartitionState::new("test".to_string());
@@ -1,16 +1,100 @@
-/// Shared path utilities: tilde expansion and path resolution.
-use camino::Utf8PathBuf;
-
-/// Expand `~/...` to the user's home directory.
-pub fn expand_tilde(path: &Utf8PathBuf) -> Utf8PathBuf {
-    let path_str = path.as_str();
-    if path_str.starts_with("~/")
-        && let Some(home) = dirs::home_dir()
-        && let Some(home_str) = home.to_str()
+/// Shared path utilities: `~`/variable expansion and path resolution.
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::config::MusicRoot;
+
+/// Expand a leading `~` to the user's home directory and `$VAR`/`${VAR}` to
+/// environment variables, so one config file works for every user. The XDG
+/// user directories (`$XDG_MUSIC_DIR` and the like) usually live in
+/// `user-dirs.dirs` rather than the environment, so they fall back to the
+/// platform's idea of them when unset. Unknown variables are left as written.
+pub fn expand_path(path: &Utf8Path) -> Utf8PathBuf {
+    expand_path_with(path, dirs::home_dir(), |name| {
+        std::env::var(name).ok().or_else(|| user_dir(name))
+    })
+}
+
+/// Platform default for a well-known directory variable.
+fn user_dir(name: &str) -> Option<String> {
+    let dir = match name {
+        "HOME" => dirs::home_dir(),
+        "XDG_MUSIC_DIR" => dirs::audio_dir(),
+        "XDG_CONFIG_HOME" => dirs::config_dir(),
+        "XDG_DATA_HOME" => dirs::data_dir(),
+        "XDG_CACHE_HOME" => dirs::cache_dir(),
+        "XDG_STATE_HOME" => dirs::state_dir(),
+        _ => None,
+    }?;
+    dir.into_os_string().into_string().ok()
+}
+
+/// [`expand_path`] with the home directory and variable lookup supplied.
+fn expand_path_with(
+    path: &Utf8Path,
+    home: Option<PathBuf>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Utf8PathBuf {
+    let mut out = String::with_capacity(path.as_str().len());
+    let mut rest = path.as_str();
+
+    // Only `~` and `~/...`; `~user` is left alone.
+    if let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty()
+            || after.starts_with('/')
+            || after.starts_with(std::path::MAIN_SEPARATOR))
+        && let Some(home) = home.as_deref().and_then(Path::to_str)
     {
-        return Utf8PathBuf::from(path_str.replacen('~', home_str, 1));
+        out.push_str(home);
+        rest = after;
+    }
+
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, tail) = match after.strip_prefix('{') {
+            Some(braced) => match braced.split_once('}') {
+                Some(split) => split,
+                None => ("", after),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                after.split_at(end)
+            }
+        };
+        match Some(name).filter(|n| !n.is_empty()).and_then(&var) {
+            Some(value) => {
+                out.push_str(&value);
+                rest = tail;
+            }
+            None => {
+                out.push('$');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Utf8PathBuf::from(out)
+}
+
+/// Whether the platform's usual filesystems compare names case-insensitively
+/// (APFS/HFS+ on macOS, NTFS on Windows). Song lookups fall back to a
+/// case-folded match there, since a client may spell a URI in any case.
+pub const CASE_INSENSITIVE_FS: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+/// Turn a path relative to a music root into a URI, whose components are
+/// always separated by `/`. Only Windows uses `\` as a separator; elsewhere
+/// a backslash is an ordinary file name character and is kept.
+pub fn to_uri(relative: &str) -> Cow<'_, str> {
+    if std::path::MAIN_SEPARATOR == '\\' && relative.contains('\\') {
+        Cow::Owned(relative.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(relative)
     }
-    path.clone()
 }
 
 /// Resolve a relative path to an absolute path using the music directory.
@@ -30,6 +114,110 @@ pub fn resolve_path(rel_path: &str, music_dir: Option<&str>) -> String {
     }
 }
 
+/// Resolve a song URI against the main music directory and any extra
+/// [`MusicRoot`]s: a URI whose first component names a root maps into that
+/// root's directory, everything else behaves like [`resolve_path`].
+pub fn resolve_in_roots(rel_path: &str, music_dir: Option<&str>, roots: &[MusicRoot]) -> String {
+    match split_root(rel_path, roots) {
+        Some((root, rest)) => resolve_path(rest, Some(root.path.as_str())),
+        None => resolve_path(rel_path, music_dir),
+    }
+}
+
+/// Split a URI into the extra root it belongs to and the path inside that
+/// root (empty for the root directory itself).
+pub fn split_root<'a, 'r>(
+    rel_path: &'a str,
+    roots: &'r [MusicRoot],
+) -> Option<(&'r MusicRoot, &'a str)> {
+    let (first, rest) = rel_path.split_once('/').unwrap_or((rel_path, ""));
+    roots
+        .iter()
+        .find(|root| root.name == first)
+        .map(|root| (root, rest))
+}
+
+/// Why a client URI was refused by [`resolve_library_path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PathError {
+    /// `..` components or NUL bytes.
+    #[error("Malformed path")]
+    Malformed,
+    /// Points (directly or through a symlink) outside every library root.
+    #[error("Access denied")]
+    OutsideLibrary,
+    /// Relative URI but no music directory to resolve it against.
+    #[error("music directory not configured")]
+    NoMusicDirectory,
+}
+
+/// Map a client-supplied URI onto a local file inside the library.
+///
+/// Unlike [`resolve_in_roots`], which trusts its input, this is the check
+/// every command taking a URI from the wire goes through before touching the
+/// filesystem. `..` components are rejected outright, absolute paths must lie
+/// inside the music directory or an extra root, and the result is
+/// canonicalized so a symlink cannot lead out of its root unless
+/// `follow_symlinks` is set. Paths that do not exist are checked through
+/// their nearest existing ancestor and returned as joined, leaving "no such
+/// file" to the caller.
+pub fn resolve_library_path(
+    uri: &str,
+    music_dir: Option<&str>,
+    roots: &[MusicRoot],
+    follow_symlinks: bool,
+) -> Result<PathBuf, PathError> {
+    check_uri(uri)?;
+    let rel = Path::new(uri);
+
+    let (base, path) = if rel.is_absolute() {
+        let base = music_dir
+            .map(Path::new)
+            .into_iter()
+            .chain(roots.iter().map(|root| root.path.as_std_path()))
+            .find(|base| rel.starts_with(base))
+            .ok_or(PathError::OutsideLibrary)?;
+        (base, rel.to_path_buf())
+    } else {
+        let (base, rest) = match split_root(uri, roots) {
+            Some((root, rest)) => (root.path.as_std_path(), rest),
+            None => (
+                Path::new(music_dir.ok_or(PathError::NoMusicDirectory)?),
+                uri,
+            ),
+        };
+        (base, base.join(rest))
+    };
+
+    if !follow_symlinks && let Ok(base) = base.canonicalize() {
+        // A file about to be created does not exist yet, but the directory
+        // it lands in may still be a symlink out of the library.
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.symlink_metadata().is_ok())
+            .unwrap_or(&path);
+        // A dangling symlink cannot be told to stay inside.
+        match existing.canonicalize() {
+            Ok(canonical) if canonical.starts_with(&base) => {}
+            _ => return Err(PathError::OutsideLibrary),
+        }
+    }
+    Ok(path)
+}
+
+/// Lexical half of [`resolve_library_path`], for commands that only look a
+/// URI up in the database: refuse `..` components and NUL bytes.
+pub fn check_uri(uri: &str) -> Result<(), PathError> {
+    if uri.contains('\0')
+        || Path::new(uri)
+            .components()
+            .any(|c| c == Component::ParentDir)
+    {
+        return Err(PathError::Malformed);
+    }
+    Ok(())
+}
+
 /// Whether `s` begins with a URI scheme (`scheme://`), e.g. `http://host/x`.
 /// Used to distinguish remote stream URIs from local relative paths.
 #[must_use]
@@ -53,6 +241,60 @@ pub fn is_uri(s: &str) -> bool {
 mod tests {
     use super::*;
 
+    fn expand(path: &str) -> Utf8PathBuf {
+        let home = PathBuf::from(if cfg!(windows) {
+            "C:\\Users\\alice"
+        } else {
+            "/home/alice"
+        });
+        expand_path_with(Utf8Path::new(path), Some(home), |name| match name {
+            "XDG_MUSIC_DIR" => Some("/srv/music".to_owned()),
+            "HOME" => Some("/home/alice".to_owned()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn expand_path_replaces_home_and_variables() {
+        if cfg!(windows) {
+            assert_eq!(expand("~\\Music"), "C:\\Users\\alice\\Music");
+        }
+        let home = if cfg!(windows) {
+            "C:\\Users\\alice"
+        } else {
+            "/home/alice"
+        };
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand("~/Music"), format!("{home}/Music"));
+        assert_eq!(expand("~bob/Music"), "~bob/Music");
+        assert_eq!(expand("/music/~/a"), "/music/~/a");
+
+        assert_eq!(expand("$XDG_MUSIC_DIR"), "/srv/music");
+        assert_eq!(expand("$XDG_MUSIC_DIR/rock"), "/srv/music/rock");
+        assert_eq!(expand("${HOME}/.rmpd.db"), "/home/alice/.rmpd.db");
+        assert_eq!(expand("/data/${HOME}x"), "/data//home/alicex");
+    }
+
+    #[test]
+    fn expand_path_keeps_unknown_variables() {
+        assert_eq!(expand("$UNSET/music"), "$UNSET/music");
+        assert_eq!(expand("${UNSET}/music"), "${UNSET}/music");
+        assert_eq!(expand("/music/$"), "/music/$");
+        assert_eq!(expand("/music/${HOME"), "/music/${HOME");
+        assert_eq!(expand("/a$b$XDG_MUSIC_DIR"), "/a$b/srv/music");
+        assert_eq!(expand("/price$5"), "/price$5");
+    }
+
+    #[test]
+    fn to_uri_normalizes_platform_separators() {
+        assert_eq!(to_uri("Artist/Album/01.flac"), "Artist/Album/01.flac");
+        if cfg!(windows) {
+            assert_eq!(to_uri("Artist\\Album\\01.flac"), "Artist/Album/01.flac");
+        } else {
+            assert_eq!(to_uri("AC\\DC/01.flac"), "AC\\DC/01.flac");
+        }
+    }
+
     #[test]
     fn is_uri_detects_schemes() {
         assert!(is_uri("http://host/stream"));
@@ -78,4 +320,92 @@ mod tests {
         );
         assert_eq!(resolve_path("a/b.flac", Some("/music")), "/music/a/b.flac");
     }
+
+    #[test]
+    fn resolve_in_roots_maps_prefixed_uris() {
+        let roots = [MusicRoot {
+            name: "ext".to_owned(),
+            path: Utf8PathBuf::from("/mnt/ext/"),
+        }];
+        assert_eq!(
+            resolve_in_roots("ext/a/b.flac", Some("/music"), &roots),
+            "/mnt/ext/a/b.flac"
+        );
+        assert_eq!(
+            resolve_in_roots("extra/b.flac", Some("/music"), &roots),
+            "/music/extra/b.flac"
+        );
+        assert_eq!(resolve_in_roots("ext", Some("/music"), &roots), "/mnt/ext/");
+        assert_eq!(split_root("ext/x", &roots).map(|(_, rest)| rest), Some("x"));
+        assert!(split_root("x/ext", &roots).is_none());
+    }
+
+    #[test]
+    fn resolve_library_path_rejects_escapes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        std::fs::create_dir_all(music.join("album")).unwrap();
+        std::fs::write(music.join("album/a.flac"), b"").unwrap();
+        let music_dir = music.to_str();
+
+        assert_eq!(
+            resolve_library_path("album/a.flac", music_dir, &[], false),
+            Ok(music.join("album/a.flac"))
+        );
+        // Names that merely contain dots are fine; `..` components are not.
+        assert!(resolve_library_path("album/a..flac", music_dir, &[], false).is_ok());
+        assert_eq!(
+            resolve_library_path("album/../../secret", music_dir, &[], false),
+            Err(PathError::Malformed)
+        );
+        assert_eq!(
+            resolve_library_path("/etc/passwd", music_dir, &[], false),
+            Err(PathError::OutsideLibrary)
+        );
+        let absolute = music.join("album/a.flac");
+        assert!(resolve_library_path(absolute.to_str().unwrap(), music_dir, &[], false).is_ok());
+        // Missing files inside the library are left to the caller.
+        assert_eq!(
+            resolve_library_path("album/new/b.flac", music_dir, &[], false),
+            Ok(music.join("album/new/b.flac"))
+        );
+        assert_eq!(
+            resolve_library_path("a.flac", None, &[], false),
+            Err(PathError::NoMusicDirectory)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_library_path_rejects_symlink_escapes() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(&music).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(dir.path().join("secret"), b"").unwrap();
+        symlink(dir.path().join("secret"), music.join("link")).unwrap();
+        symlink(&outside, music.join("linkdir")).unwrap();
+        symlink(dir.path().join("gone"), music.join("dangling")).unwrap();
+        let music_dir = music.to_str();
+
+        // Symlinks out of the library only resolve when following is enabled.
+        assert_eq!(
+            resolve_library_path("link", music_dir, &[], false),
+            Err(PathError::OutsideLibrary)
+        );
+        assert!(resolve_library_path("link", music_dir, &[], true).is_ok());
+        // Not even for a file that does not exist yet.
+        assert_eq!(
+            resolve_library_path("linkdir/new.flac", music_dir, &[], false),
+            Err(PathError::OutsideLibrary)
+        );
+        assert_eq!(
+            resolve_library_path("dangling", music_dir, &[], false),
+            Err(PathError::OutsideLibrary)
+        );
+        assert!(resolve_library_path("linkdir/new.flac", music_dir, &[], true).is_ok());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::song::Song;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
@@ -12,8 +13,33 @@ pub struct QueueItem {
     pub priority: u8,
     /// Optional playback range (start, end) in seconds
     pub range: Option<(f64, f64)>,
-    /// Custom tags attached to this queue item
-    pub tags: Option<HashMap<String, String>>,
+    /// Tags set with `addtagid`, by lowercase tag name. Each replaces all of
+    /// the song's own values of that tag; see [`QueueItem::tagged_song`].
+    pub tags: Option<BTreeMap<String, Vec<String>>>,
+    /// Queue version at which this item was added, moved or last edited;
+    /// see [`Queue::changes_since`].
+    pub version: u32,
+}
+
+impl QueueItem {
+    /// The song with this item's tag overrides applied: overridden tags
+    /// lose their original values and the added ones follow the song's
+    /// other tags.
+    pub fn tagged_song(&self) -> Cow<'_, Song> {
+        let Some(overrides) = &self.tags else {
+            return Cow::Borrowed(&self.song);
+        };
+        let mut song = (*self.song).clone();
+        song.tags
+            .retain(|(key, _)| !overrides.contains_key(key.as_ref()));
+        for (key, values) in overrides {
+            for value in values {
+                song.tags
+                    .push((crate::song::intern_tag_key(key), value.clone()));
+            }
+        }
+        Cow::Owned(song)
+    }
 }
 
 impl Serialize for QueueItem {
@@ -22,13 +48,14 @@ impl Serialize for QueueItem {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("QueueItem", 6)?;
+        let mut state = serializer.serialize_struct("QueueItem", 7)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("position", &self.position)?;
         state.serialize_field("song", self.song.as_ref())?;
         state.serialize_field("priority", &self.priority)?;
         state.serialize_field("range", &self.range)?;
         state.serialize_field("tags", &self.tags)?;
+        state.serialize_field("version", &self.version)?;
         state.end()
     }
 }
@@ -50,6 +77,7 @@ impl<'de> Deserialize<'de> for QueueItem {
             Priority,
             Range,
             Tags,
+            Version,
         }
 
         struct QueueItemVisitor;
@@ -71,6 +99,7 @@ impl<'de> Deserialize<'de> for QueueItem {
                 let mut priority = None;
                 let mut range = None;
                 let mut tags = None;
+                let mut version = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -111,6 +140,12 @@ impl<'de> Deserialize<'de> for QueueItem {
                             }
                             tags = Some(map.next_value()?);
                         }
+                        Field::Version => {
+                            if version.is_some() {
+                                return Err(de::Error::duplicate_field("version"));
+                            }
+                            version = Some(map.next_value()?);
+                        }
                     }
                 }
 
@@ -120,6 +155,7 @@ impl<'de> Deserialize<'de> for QueueItem {
                 let priority = priority.unwrap_or(0);
                 let range = range;
                 let tags = tags;
+                let version = version.unwrap_or(0);
 
                 Ok(QueueItem {
                     id,
@@ -128,13 +164,16 @@ impl<'de> Deserialize<'de> for QueueItem {
                     priority,
                     range,
                     tags,
+                    version,
                 })
             }
         }
 
         deserializer.deserialize_struct(
             "QueueItem",
-            &["id", "position", "song", "priority", "range", "tags"],
+            &[
+                "id", "position", "song", "priority", "range", "tags", "version",
+            ],
             QueueItemVisitor,
         )
     }
@@ -156,6 +195,7 @@ impl Queue {
         let id = self.next_id;
         self.next_id += 1;
 
+        self.version += 1;
         let position = self.items.len() as u32;
         self.items.push(QueueItem {
             id,
@@ -164,17 +204,16 @@ impl Queue {
             priority: 0, // Default priority
             range: None, // No range restriction by default
             tags: None,  // No custom tags by default
+            version: self.version,
         });
-
-        self.version += 1;
         id
     }
 
     pub fn delete(&mut self, position: u32) -> Option<QueueItem> {
         if (position as usize) < self.items.len() {
             let item = self.items.remove(position as usize);
-            self.reindex();
             self.version += 1;
+            self.reindex();
             Some(item)
         } else {
             None
@@ -184,8 +223,8 @@ impl Queue {
     pub fn delete_id(&mut self, id: u32) -> Option<QueueItem> {
         if let Some(idx) = self.items.iter().position(|item| item.id == id) {
             let item = self.items.remove(idx);
-            self.reindex();
             self.version += 1;
+            self.reindex();
             Some(item)
         } else {
             None
@@ -221,27 +260,57 @@ impl Queue {
         &self.items
     }
 
-    pub fn shuffle(&mut self) {
-        use rand::rng;
-        use rand::seq::SliceRandom;
+    /// Items at positions `start..end`, clamped to the queue, so `(n, u32::MAX)`
+    /// reads an open-ended `n:` range. Empty when `start` is past the end or
+    /// the range is inverted.
+    pub fn slice(&self, start: u32, end: u32) -> &[QueueItem] {
+        let end = (end as usize).min(self.items.len());
+        self.items.get(start as usize..end).unwrap_or(&[])
+    }
 
-        self.items.shuffle(&mut rng());
-        self.reindex();
-        self.version += 1;
+    /// Items in the optional position range of a queue inspection command;
+    /// the whole queue when there is none.
+    pub fn items_in(&self, range: Option<(u32, u32)>) -> &[QueueItem] {
+        match range {
+            Some((start, end)) => self.slice(start, end),
+            None => &self.items,
+        }
+    }
+
+    pub fn shuffle(&mut self) {
+        self.shuffle_around(0, self.items.len() as u32, None);
     }
 
     pub fn shuffle_range(&mut self, start: u32, end: u32) {
+        self.shuffle_around(start, end, None);
+    }
+
+    /// Shuffle positions `start..end` (clamped to the queue), bumping the
+    /// version once. When `current` (a position) lies in the range, that item
+    /// is moved to `start` and the rest of the range is shuffled after it, as
+    /// MPD does for the playing song. Returns the new position of `current`.
+    pub fn shuffle_around(&mut self, start: u32, end: u32, current: Option<u32>) -> Option<u32> {
         use rand::rng;
         use rand::seq::SliceRandom;
 
-        let start_idx = start as usize;
+        let mut start_idx = start as usize;
         let end_idx = end.min(self.items.len() as u32) as usize;
+        if start_idx >= end_idx {
+            return current;
+        }
 
-        if start_idx < end_idx && end_idx <= self.items.len() {
-            self.items[start_idx..end_idx].shuffle(&mut rng());
-            self.reindex();
-            self.version += 1;
+        let mut new_current = current;
+        if let Some(pos) = current.map(|p| p as usize)
+            && (start_idx..end_idx).contains(&pos)
+        {
+            self.items.swap(start_idx, pos);
+            new_current = Some(start_idx as u32);
+            start_idx += 1;
         }
+        self.items[start_idx..end_idx].shuffle(&mut rng());
+        self.version += 1;
+        self.reindex();
+        new_current
     }
 
     pub fn move_item(&mut self, from: u32, to: u32) -> bool {
@@ -251,8 +320,8 @@ impl Queue {
 
         let item = self.items.remove(from as usize);
         self.items.insert(to as usize, item);
-        self.reindex();
         self.version += 1;
+        self.reindex();
         true
     }
 
@@ -263,8 +332,8 @@ impl Queue {
             }
             let item = self.items.remove(from_idx);
             self.items.insert(to as usize, item);
-            self.reindex();
             self.version += 1;
+            self.reindex();
             true
         } else {
             false
@@ -276,8 +345,8 @@ impl Queue {
             return false;
         }
         self.items.swap(pos1 as usize, pos2 as usize);
-        self.reindex();
         self.version += 1;
+        self.reindex();
         true
     }
 
@@ -287,8 +356,8 @@ impl Queue {
 
         if let (Some(i1), Some(i2)) = (idx1, idx2) {
             self.items.swap(i1, i2);
-            self.reindex();
             self.version += 1;
+            self.reindex();
             true
         } else {
             false
@@ -299,6 +368,7 @@ impl Queue {
         let id = self.next_id;
         self.next_id += 1;
 
+        self.version += 1;
         let pos = position.unwrap_or(self.items.len() as u32);
         let item = QueueItem {
             id,
@@ -307,6 +377,7 @@ impl Queue {
             priority: 0, // Default priority
             range: None, // No range restriction by default
             tags: None,  // No custom tags by default
+            version: self.version,
         };
 
         if pos as usize >= self.items.len() {
@@ -316,12 +387,12 @@ impl Queue {
         }
 
         self.reindex();
-        self.version += 1;
         id
     }
 
     /// Set priority for songs in the given position range
     pub fn set_priority_range(&mut self, priority: u8, ranges: &[(u32, u32)]) {
+        self.version += 1;
         for &(start, end) in ranges {
             let start_idx = start as usize;
             let end_idx = end.min(self.items.len() as u32) as usize;
@@ -329,23 +400,25 @@ impl Queue {
             for idx in start_idx..end_idx {
                 if idx < self.items.len() {
                     self.items[idx].priority = priority;
+                    self.items[idx].version = self.version;
                 }
             }
         }
-        self.version += 1;
     }
 
     /// Set priority for songs with the given IDs
     pub fn set_priority_ids(&mut self, priority: u8, ids: &[u32]) -> bool {
+        let version = self.version + 1;
         let mut any_changed = false;
         for &id in ids {
             if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
                 item.priority = priority;
+                item.version = version;
                 any_changed = true;
             }
         }
         if any_changed {
-            self.version += 1;
+            self.version = version;
         }
         any_changed
     }
@@ -356,29 +429,33 @@ impl Queue {
     /// Returns true if the item was found and updated.
     pub fn set_range_by_id(&mut self, id: u32, range: Option<(f64, f64)>) -> bool {
         if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
-            item.range = range;
             self.version += 1;
+            item.range = range;
+            item.version = self.version;
             true
         } else {
             false
         }
     }
 
-    /// Add a custom tag to a queue item
+    /// Add a value of `tag` to a queue item. The first value added replaces
+    /// the song's own values of the tag; later ones are added alongside.
     ///
     /// Returns true if the item was found and updated.
-    pub fn add_tag_by_id(&mut self, id: u32, tag: String, value: String) -> bool {
+    pub fn add_tag_by_id(&mut self, id: u32, tag: &str, value: String) -> bool {
         if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
-            let tags = item.tags.get_or_insert_with(HashMap::new);
-            tags.insert(tag, value);
+            let tags = item.tags.get_or_insert_with(BTreeMap::new);
+            tags.entry(tag.to_lowercase()).or_default().push(value);
             self.version += 1;
+            item.version = self.version;
             true
         } else {
             false
         }
     }
 
-    /// Clear tags from a queue item
+    /// Clear tags added to a queue item with [`Self::add_tag_by_id`],
+    /// bringing back the song's own values.
     ///
     /// If tag is Some, clears only that tag. If None, clears all tags.
     /// Returns true if the item was found.
@@ -387,8 +464,8 @@ impl Queue {
             if let Some(tag_name) = tag {
                 // Clear specific tag
                 if let Some(tags) = &mut item.tags {
-                    tags.remove(tag_name);
-                    // If no tags left, remove the HashMap
+                    tags.remove(&tag_name.to_lowercase());
+                    // If no tags left, remove the map
                     if tags.is_empty() {
                         item.tags = None;
                     }
@@ -398,6 +475,22 @@ impl Queue {
                 item.tags = None;
             }
             self.version += 1;
+            item.version = self.version;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Update the song of the item with `id` in place, e.g. once metadata
+    /// for an ad-hoc stream arrives.
+    ///
+    /// Returns true if the item was found.
+    pub fn update_song_by_id(&mut self, id: u32, update: impl FnOnce(&mut Song)) -> bool {
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
+            update(Arc::make_mut(&mut item.song));
+            self.version += 1;
+            item.version = self.version;
             true
         } else {
             false
@@ -409,9 +502,29 @@ impl Queue {
         self.items.iter_mut().find(|item| item.id == id)
     }
 
+    /// Items added or changed after queue `version`, in queue order, limited
+    /// to the optional position range. Removed items are not listed: the queue
+    /// length tells how many went away from the end. A version of 0, or one
+    /// newer than the queue's (from before a restart), lists every item.
+    pub fn changes_since(
+        &self,
+        version: u32,
+        range: Option<(u32, u32)>,
+    ) -> impl Iterator<Item = &QueueItem> {
+        let all = version == 0 || version > self.version;
+        self.items_in(range)
+            .iter()
+            .filter(move |item| all || item.version > version)
+    }
+
+    /// Renumber positions after items moved, marking the ones that changed
+    /// position as changed in the current version.
     fn reindex(&mut self) {
         for (idx, item) in self.items.iter_mut().enumerate() {
-            item.position = idx as u32;
+            if item.position != idx as u32 {
+                item.position = idx as u32;
+                item.version = self.version;
+            }
         }
     }
 }
@@ -452,6 +565,25 @@ mod tests {
         assert_eq!(queue.get(5).unwrap().song.tag("title"), Some("Song 5"));
     }
 
+    #[test]
+    fn test_shuffle_around_keeps_current_at_front() {
+        let mut queue = Queue::new();
+        for i in 0..10 {
+            queue.add(create_test_song(i as u64, &i.to_string()));
+        }
+        let current_id = queue.get(6).unwrap().id;
+        let version = queue.version();
+
+        assert_eq!(queue.shuffle_around(0, 10, Some(6)), Some(0));
+        assert_eq!(queue.get(0).unwrap().id, current_id);
+        assert_eq!(queue.version(), version + 1);
+
+        // Outside the range, the current song stays put.
+        assert_eq!(queue.shuffle_around(2, 5, Some(0)), Some(0));
+        assert_eq!(queue.get(0).unwrap().id, current_id);
+        assert_eq!(queue.len(), 10);
+    }
+
     #[test]
     fn test_shuffle_range_bounds() {
         let mut queue = Queue::new();
@@ -468,4 +600,81 @@ mod tests {
         // Should still have 5 items
         assert_eq!(queue.len(), 5);
     }
+
+    #[test]
+    fn test_update_song_by_id() {
+        let mut queue = Queue::new();
+        let id = queue.add(create_test_song(1, "1"));
+        let snapshot = queue.get_by_id(id).unwrap().song.clone();
+        let version = queue.version();
+
+        assert!(queue.update_song_by_id(id, |song| {
+            song.tags.push(("name".into(), "Radio".to_owned()));
+        }));
+        assert_eq!(queue.get_by_id(id).unwrap().song.tag("name"), Some("Radio"));
+        assert_eq!(snapshot.tag("name"), None, "earlier clones are unaffected");
+        assert_eq!(queue.version(), version + 1);
+        assert!(!queue.update_song_by_id(id + 1, |_| {}));
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut queue = Queue::new();
+        let ids: Vec<u32> = (0..5)
+            .map(|i| queue.add(create_test_song(i, &i.to_string())))
+            .collect();
+        let version = queue.version();
+        let changed = |queue: &Queue, range| {
+            queue
+                .changes_since(version, range)
+                .map(|item| item.id)
+                .collect::<Vec<_>>()
+        };
+        assert!(changed(&queue, None).is_empty());
+        assert_eq!(queue.changes_since(0, None).count(), 5);
+        assert_eq!(queue.changes_since(version + 1, None).count(), 5);
+
+        // Moving 3 to 1 shifts 1 and 2 down; 0 and 4 keep their place.
+        assert!(queue.move_item(3, 1));
+        assert_eq!(changed(&queue, None), [ids[3], ids[1], ids[2]]);
+
+        assert!(queue.set_priority_ids(7, &[ids[4]]));
+        assert_eq!(changed(&queue, None), [ids[3], ids[1], ids[2], ids[4]]);
+        assert_eq!(changed(&queue, Some((3, 5))), [ids[2], ids[4]]);
+
+        // Deleting the first item shifts every other one.
+        let version = queue.version();
+        queue.delete(0);
+        assert_eq!(queue.changes_since(version, None).count(), 4);
+    }
+
+    #[test]
+    fn test_tag_overrides() {
+        let mut queue = Queue::new();
+        let id = queue.add(create_test_song(1, "1"));
+
+        assert!(queue.add_tag_by_id(id, "Title", "Live".to_owned()));
+        assert!(queue.add_tag_by_id(id, "artist", "A".to_owned()));
+        assert!(queue.add_tag_by_id(id, "Artist", "B".to_owned()));
+        let song = queue.get_by_id(id).unwrap().tagged_song();
+        assert_eq!(song.tag("title"), Some("Live"));
+        assert_eq!(song.tag_values("artist").collect::<Vec<_>>(), ["A", "B"]);
+        assert_eq!(
+            queue.get_by_id(id).unwrap().song.tag("title"),
+            Some("Song 1"),
+            "the queued song itself is untouched"
+        );
+
+        assert!(queue.clear_tags_by_id(id, Some("TITLE")));
+        let song = queue.get_by_id(id).unwrap().tagged_song();
+        assert_eq!(song.tag("title"), Some("Song 1"));
+        assert_eq!(song.tag("artist"), Some("A"));
+
+        assert!(queue.clear_tags_by_id(id, None));
+        assert!(matches!(
+            queue.get_by_id(id).unwrap().tagged_song(),
+            Cow::Borrowed(_)
+        ));
+        assert!(!queue.add_tag_by_id(id + 1, "title", "x".to_owned()));
+    }
 }
@@ -17,6 +17,7 @@ pub fn intern_tag_key(key: &str) -> Cow<'static, str> {
         "performer" => Cow::Borrowed("performer"),
         "comment" => Cow::Borrowed("comment"),
         "disc" => Cow::Borrowed("disc"),
+        "disctotal" => Cow::Borrowed("disctotal"),
         "label" => Cow::Borrowed("label"),
         "albumartist" => Cow::Borrowed("albumartist"),
         "musicbrainz_artistid" => Cow::Borrowed("musicbrainz_artistid"),
@@ -73,6 +74,7 @@ pub fn canonical_tag_name(tag: &str) -> &'static str {
         "grouping" => "Grouping",
         "comment" => "Comment",
         "disc" => "Disc",
+        "disctotal" => "DiscTotal",
         "label" => "Label",
         "musicbrainz_artistid" => "MUSICBRAINZ_ARTISTID",
         "musicbrainz_albumid" => "MUSICBRAINZ_ALBUMID",
@@ -207,6 +209,13 @@ impl Song {
     pub fn display_album(&self) -> &str {
         self.tag("album").unwrap_or("Unknown Album")
     }
+
+    /// Disc and track numbers, 0 when missing or not numeric. Ordering by
+    /// this pair plays a multi-disc album disc by disc.
+    pub fn disc_and_track(&self) -> (u32, u32) {
+        let number = |tag| self.tag(tag).and_then(|v| v.parse().ok()).unwrap_or(0);
+        (number("disc"), number("track"))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
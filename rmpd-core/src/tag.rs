@@ -1,6 +1,6 @@
 /// Shared tag utilities: fallback chains, normalization, and canonical mappings.
-use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, OnceLock};
 
 /// Lazy-initialized HashMap for O(1) VorbisComment tag lookups
 static VORBIS_TAG_MAP_HASH: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
@@ -21,6 +21,7 @@ static VORBIS_TAG_MAP_HASH: LazyLock<HashMap<&'static str, &'static str>> = Lazy
     m.insert("mood", "mood");
     m.insert("work", "work");
     m.insert("movement", "movement");
+    m.insert("movementname", "movement");
     m.insert("movementnumber", "movementnumber");
     m.insert("ensemble", "ensemble");
     m.insert("location", "location");
@@ -29,6 +30,8 @@ static VORBIS_TAG_MAP_HASH: LazyLock<HashMap<&'static str, &'static str>> = Lazy
     m.insert("tracknumber", "track");
     m.insert("disc", "disc");
     m.insert("discnumber", "disc");
+    m.insert("disctotal", "disctotal");
+    m.insert("totaldiscs", "disctotal");
     m.insert("date", "date");
     m.insert("originaldate", "originaldate");
     m.insert("label", "label");
@@ -106,3 +109,123 @@ pub fn normalize_decimal(s: &str) -> Option<String> {
 pub fn vorbis_tag_map_get(key: &str) -> Option<&'static str> {
     VORBIS_TAG_MAP_HASH.get(key).copied()
 }
+
+/// Tag types MPD knows, lowercase, in `tagtypes` order.
+pub const TAG_TYPES: &[&str] = &[
+    "artist",
+    "artistsort",
+    "album",
+    "albumsort",
+    "albumartist",
+    "albumartistsort",
+    "title",
+    "titlesort",
+    "track",
+    "name",
+    "genre",
+    "mood",
+    "date",
+    "originaldate",
+    "composer",
+    "composersort",
+    "performer",
+    "conductor",
+    "work",
+    "movement",
+    "movementnumber",
+    "ensemble",
+    "location",
+    "grouping",
+    "comment",
+    "disc",
+    "label",
+    "musicbrainz_artistid",
+    "musicbrainz_albumid",
+    "musicbrainz_albumartistid",
+    "musicbrainz_trackid",
+    "musicbrainz_releasetrackid",
+    "musicbrainz_workid",
+    "musicbrainz_releasegroupid",
+];
+
+/// The tag types named by MPD's `metadata_to_use` option: the only ones the
+/// scanner stores and responses carry. Keys that are not MPD tag types
+/// (see [`TAG_TYPES`]) are never masked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataMask {
+    tags: HashSet<&'static str>,
+}
+
+impl Default for MetadataMask {
+    /// MPD's default: every tag type except `comment`.
+    fn default() -> Self {
+        Self {
+            tags: TAG_TYPES
+                .iter()
+                .copied()
+                .filter(|tag| *tag != "comment")
+                .collect(),
+        }
+    }
+}
+
+impl MetadataMask {
+    /// Parse a `metadata_to_use` value the way MPD does: `none`, a
+    /// comma-separated list replacing the default (`artist,album,title`), or,
+    /// when the value starts with `+` or `-`, changes to the default
+    /// (`+comment,-genre`). Names are case-insensitive.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if spec.eq_ignore_ascii_case("none") {
+            return Ok(Self {
+                tags: HashSet::new(),
+            });
+        }
+        let mut mask = if spec.starts_with(['+', '-']) {
+            Self::default()
+        } else {
+            Self {
+                tags: HashSet::new(),
+            }
+        };
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let (remove, name) = if let Some(name) = item.strip_prefix('-') {
+                (true, name.trim())
+            } else {
+                (false, item.strip_prefix('+').unwrap_or(item).trim())
+            };
+            let lower = name.to_lowercase();
+            let Some(tag) = TAG_TYPES.iter().copied().find(|t| *t == lower) else {
+                return Err(format!("unknown tag type {name:?} in metadata_to_use"));
+            };
+            if remove {
+                mask.tags.remove(tag);
+            } else {
+                mask.tags.insert(tag);
+            }
+        }
+        Ok(mask)
+    }
+
+    /// Whether values of `tag` (any case) are kept.
+    pub fn allows(&self, tag: &str) -> bool {
+        if self.tags.contains(tag) {
+            return true;
+        }
+        let lower = tag.to_lowercase();
+        self.tags.contains(lower.as_str()) || !TAG_TYPES.contains(&lower.as_str())
+    }
+}
+
+static METADATA_TO_USE: OnceLock<MetadataMask> = OnceLock::new();
+
+/// Install the process-wide `metadata_to_use` mask (MPD's global tag mask).
+/// Set once at startup; later calls are ignored and return false.
+pub fn set_metadata_to_use(mask: MetadataMask) -> bool {
+    METADATA_TO_USE.set(mask).is_ok()
+}
+
+/// The configured `metadata_to_use` mask, if any.
+pub fn metadata_to_use() -> Option<&'static MetadataMask> {
+    METADATA_TO_USE.get()
+}
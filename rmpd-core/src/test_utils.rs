@@ -124,6 +124,26 @@ pub fn make_test_song(path: &str, track: u32) -> Song {
 
 // ── Fixture utilities ────────────────────────────────────────────────
 
+/// Write a minimal 16-bit stereo 44.1 kHz PCM WAV holding `frames` frames
+/// of silence to `path`, for tests that need a real audio file on disk.
+pub fn write_wav(path: &std::path::Path, frames: u32) {
+    let data_len = frames * 4;
+    let mut wav = b"RIFF".to_vec();
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&44100u32.to_le_bytes());
+    wav.extend_from_slice(&(44100u32 * 4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    std::fs::write(path, wav).expect("write wav");
+}
+
 /// Audio format for test fixture generation (FFmpeg-based).
 ///
 /// Shared across player and library fixture generators.
@@ -135,6 +155,8 @@ pub enum AudioFormat {
     Opus,
     M4a,
     Wav,
+    WavPack,
+    Tta,
 }
 
 impl AudioFormat {
@@ -147,6 +169,8 @@ impl AudioFormat {
             AudioFormat::Opus => "opus",
             AudioFormat::M4a => "m4a",
             AudioFormat::Wav => "wav",
+            AudioFormat::WavPack => "wv",
+            AudioFormat::Tta => "tta",
         }
     }
 
@@ -159,6 +183,8 @@ impl AudioFormat {
             AudioFormat::Opus => "libopus",
             AudioFormat::M4a => "aac",
             AudioFormat::Wav => "pcm_s16le",
+            AudioFormat::WavPack => "wavpack",
+            AudioFormat::Tta => "tta",
         }
     }
 }
@@ -241,10 +267,14 @@ mod tests {
         assert_eq!(AudioFormat::Opus.extension(), "opus");
         assert_eq!(AudioFormat::M4a.extension(), "m4a");
         assert_eq!(AudioFormat::Wav.extension(), "wav");
+        assert_eq!(AudioFormat::WavPack.extension(), "wv");
+        assert_eq!(AudioFormat::Tta.extension(), "tta");
 
         assert_eq!(AudioFormat::Flac.codec(), "flac");
         assert_eq!(AudioFormat::Mp3.codec(), "libmp3lame");
         assert_eq!(AudioFormat::Opus.codec(), "libopus");
+        assert_eq!(AudioFormat::WavPack.codec(), "wavpack");
+        assert_eq!(AudioFormat::Tta.codec(), "tta");
     }
 
     #[test]
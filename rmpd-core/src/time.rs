@@ -1,4 +1,4 @@
-/// Shared time utilities: Unix timestamp conversion and ISO 8601 formatting.
+/// Shared time utilities: Unix timestamp conversion and ISO 8601 formatting and parsing.
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Convert a SystemTime to Unix timestamp (seconds since epoch).
@@ -55,3 +55,65 @@ pub fn format_iso8601(timestamp: i64) -> String {
 
     format!("{year:04}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds:02}Z")
 }
+
+/// Parse a timestamp as accepted by MPD's `added-since`/`modified-since`
+/// filters: Unix seconds, or ISO 8601 `YYYY-MM-DD[THH:MM[:SS]][Z]` (UTC).
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<i64>() {
+        return Some(secs);
+    }
+
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once('T').unwrap_or((s, ""));
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: i64 = date.next()?.parse().ok()?;
+    let day: i64 = date.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut seconds_of_day = 0;
+    if !time.is_empty() {
+        let mut time = time.splitn(3, ':');
+        let hours: i64 = time.next()?.parse().ok()?;
+        let minutes: i64 = time.next()?.parse().ok()?;
+        let seconds: i64 = match time.next() {
+            Some(sec) => sec.split('.').next()?.parse().ok()?,
+            None => 0,
+        };
+        if hours > 23 || minutes > 59 || seconds > 60 {
+            return None;
+        }
+        seconds_of_day = hours * 3600 + minutes * 60 + seconds;
+    }
+
+    // Days since the epoch of a proleptic Gregorian date (Howard Hinnant's
+    // days_from_civil).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + seconds_of_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timestamp_round_trips_iso8601() {
+        for ts in [0, 951_782_400, 1_700_000_000, 4_102_444_800] {
+            assert_eq!(parse_timestamp(&format_iso8601(ts)), Some(ts));
+        }
+        assert_eq!(parse_timestamp("2024-03-01"), Some(1_709_251_200));
+        assert_eq!(parse_timestamp("2024-03-01T12:30"), Some(1_709_296_200));
+        assert_eq!(parse_timestamp("1700000000"), Some(1_700_000_000));
+        assert_eq!(parse_timestamp("2024-13-01"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+}
@@ -298,3 +298,21 @@ fn test_version_increments() {
     let v3 = queue.version();
     assert!(v3 > v2);
 }
+
+#[test]
+fn test_slice_clamps_to_queue() {
+    let mut queue = Queue::new();
+    for i in 1..=4 {
+        queue.add(create_test_song(i, &format!("song{i}")));
+    }
+
+    let positions = |items: &[rmpd_core::queue::QueueItem]| {
+        items.iter().map(|item| item.position).collect::<Vec<_>>()
+    };
+    assert_eq!(positions(queue.slice(1, 3)), vec![1, 2]);
+    assert_eq!(positions(queue.slice(2, u32::MAX)), vec![2, 3]);
+    assert!(queue.slice(4, u32::MAX).is_empty());
+    assert!(queue.slice(3, 1).is_empty());
+    assert_eq!(queue.items_in(None).len(), 4);
+    assert_eq!(positions(queue.items_in(Some((3, 10)))), vec![3]);
+}
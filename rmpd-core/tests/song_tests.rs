@@ -334,3 +334,24 @@ fn test_song_tag_with_fallback() {
     // artist should return itself
     assert_eq!(song.tag_with_fallback("artist"), Some("Test Artist"));
 }
+
+#[test]
+fn test_metadata_mask_parse() {
+    use rmpd_core::tag::MetadataMask;
+
+    let default = MetadataMask::default();
+    assert!(default.allows("artist") && default.allows("MUSICBRAINZ_TRACKID"));
+    assert!(!default.allows("comment"));
+
+    let listed = MetadataMask::parse("Artist, album,title").unwrap();
+    assert!(listed.allows("artist") && listed.allows("Title"));
+    assert!(!listed.allows("genre"));
+    assert!(listed.allows("x-custom"), "non-MPD keys are never masked");
+
+    let incremental = MetadataMask::parse("+comment,-genre").unwrap();
+    assert!(incremental.allows("comment") && incremental.allows("album"));
+    assert!(!incremental.allows("genre"));
+
+    assert!(!MetadataMask::parse("none").unwrap().allows("title"));
+    assert!(MetadataMask::parse("artist,bogus").is_err());
+}
@@ -0,0 +1,67 @@
+//! Database query benchmarks against a generated 100k-song library.
+//!
+//! The library comes from the test fixtures' [`SyntheticLibrary`] and is
+//! built once per run in a temporary directory; the timed part is the
+//! queries `find`, `list`, `search` and `lsinfo` run against it.
+//!
+//! Run with: cargo bench -p rmpd-library --bench database
+
+#[path = "../tests/fixtures/library.rs"]
+mod library;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use library::SyntheticLibrary;
+use rmpd_core::filter::FilterExpression;
+use std::collections::HashMap;
+use std::hint::black_box;
+
+const SONG_COUNT: usize = 100_000;
+
+fn queries(c: &mut Criterion) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (db, songs) = SyntheticLibrary::new(SONG_COUNT)
+        .build_in(dir.path())
+        .unwrap();
+
+    // Query the most prolific artist and one of its album directories.
+    let mut per_artist: HashMap<&str, usize> = HashMap::new();
+    for song in &songs {
+        *per_artist.entry(song.tag("artist").unwrap()).or_default() += 1;
+    }
+    let (artist, _) = per_artist.into_iter().max_by_key(|&(_, n)| n).unwrap();
+    let artist_dir = songs
+        .iter()
+        .find(|s| s.tag("albumartist") == Some(artist))
+        .and_then(|s| s.path.parent()?.parent())
+        .unwrap()
+        .to_string();
+    let title = songs[SONG_COUNT / 2].tag("title").unwrap();
+
+    let mut group = c.benchmark_group("database_100k");
+    group.bench_function("find_artist", |b| {
+        b.iter(|| db.find_songs("artist", black_box(artist)).unwrap())
+    });
+    let filter = FilterExpression::parse("((genre == 'Jazz') AND (date >= '2010'))").unwrap();
+    group.bench_function("find_filter", |b| {
+        b.iter(|| db.find_songs_filter(black_box(&filter)).unwrap())
+    });
+    group.bench_function("list_album", |b| {
+        b.iter(|| db.list_tag_values(black_box("album")).unwrap())
+    });
+    group.bench_function("list_album_by_artist", |b| {
+        b.iter(|| {
+            db.list_filtered("album", "artist", black_box(artist))
+                .unwrap()
+        })
+    });
+    group.bench_function("search_title", |b| {
+        b.iter(|| db.search_songs(black_box(title)).unwrap())
+    });
+    group.bench_function("lsinfo_artist", |b| {
+        b.iter(|| db.list_directory(black_box(&artist_dir)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
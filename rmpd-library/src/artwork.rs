@@ -2,13 +2,161 @@ use lofty::file::TaggedFileExt;
 use lofty::picture::PictureType;
 use rmpd_core::error::{Result, RmpdError};
 use sha2::{Digest, Sha256};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
 
-use crate::database::Database;
+use crate::store::LibraryStore;
+use crate::{dsd, rawtag};
 
 const MAX_ARTWORK_SIZE: usize = 5 * 1024 * 1024; // 5MB
 
-fn infer_mime(data: &[u8]) -> &'static str {
+/// Chunk size of artwork transfers until a client sets its `binarylimit`;
+/// MPD's default.
+pub const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+/// Cover image file names looked up in a directory, in order of preference
+/// and matched ignoring case: MPD's `cover.*`, then the `folder.*` and
+/// `front.*` written by other players and rippers.
+pub const COVER_FILE_NAMES: &[&str] = &[
+    "cover.png",
+    "cover.jpg",
+    "cover.jpeg",
+    "cover.webp",
+    "cover.tiff",
+    "cover.bmp",
+    "folder.png",
+    "folder.jpg",
+    "folder.jpeg",
+    "folder.webp",
+    "front.png",
+    "front.jpg",
+    "front.jpeg",
+    "front.webp",
+];
+
+/// Total size of the cover images kept in memory.
+const COVER_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Preferred [`COVER_FILE_NAMES`] entry in `dir`.
+fn find_cover_file(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let name = name.to_str()?;
+            let rank = COVER_FILE_NAMES
+                .iter()
+                .position(|cover| name.eq_ignore_ascii_case(cover))?;
+            Some((rank, entry.path()))
+        })
+        .filter(|(_, path)| path.is_file())
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, path)| path)
+}
+
+/// A directory's cover image as last read, valid while neither the
+/// directory (a cover added or removed) nor the file changes.
+struct CachedCover {
+    dir_stamp: Stamp,
+    file: PathBuf,
+    file_stamp: Stamp,
+    data: Arc<[u8]>,
+    last_used: u64,
+}
+
+/// Cover images by directory, shared by all clients since covers are
+/// requested again for every chunk and by every client showing the album.
+#[derive(Default)]
+struct CoverCache {
+    covers: HashMap<PathBuf, CachedCover>,
+    bytes: usize,
+    tick: u64,
+}
+
+static COVER_CACHE: LazyLock<Mutex<CoverCache>> = LazyLock::new(Mutex::default);
+
+/// Modification time and size, which change along with a file.
+type Stamp = (SystemTime, u64);
+
+fn stamp(path: &Path) -> std::io::Result<Stamp> {
+    let meta = std::fs::metadata(path)?;
+    Ok((meta.modified()?, meta.len()))
+}
+
+/// The cover image file of `dir`, from memory when unchanged on disk.
+fn directory_cover(dir: &Path) -> Result<Option<Arc<[u8]>>> {
+    let Ok(dir_stamp) = stamp(dir) else {
+        return Ok(None);
+    };
+    let mut cache = COVER_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.tick += 1;
+    let tick = cache.tick;
+    if let Some(cover) = cache.covers.get_mut(dir)
+        && cover.dir_stamp == dir_stamp
+        && stamp(&cover.file).is_ok_and(|s| s == cover.file_stamp)
+    {
+        cover.last_used = tick;
+        return Ok(Some(Arc::clone(&cover.data)));
+    }
+    if let Some(stale) = cache.covers.remove(dir) {
+        cache.bytes -= stale.data.len();
+    }
+    drop(cache);
+
+    let Some(file) = find_cover_file(dir) else {
+        return Ok(None);
+    };
+    let read = |file: &Path| -> std::io::Result<(Stamp, Vec<u8>)> {
+        let file_stamp = stamp(file)?;
+        let len = file_stamp.1;
+        if len > MAX_ARTWORK_SIZE as u64 {
+            return Err(std::io::Error::other(format!(
+                "artwork too large: {len} bytes (max {MAX_ARTWORK_SIZE})"
+            )));
+        }
+        Ok((file_stamp, std::fs::read(file)?))
+    };
+    let (file_stamp, data) =
+        read(&file).map_err(|e| RmpdError::Library(format!("Failed to read cover: {e}")))?;
+    let data: Arc<[u8]> = data.into();
+
+    let mut cache = COVER_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    while cache.bytes + data.len() > COVER_CACHE_BYTES {
+        let Some(oldest) = cache
+            .covers
+            .iter()
+            .min_by_key(|(_, cover)| cover.last_used)
+            .map(|(dir, _)| dir.clone())
+        else {
+            break;
+        };
+        if let Some(evicted) = cache.covers.remove(&oldest) {
+            cache.bytes -= evicted.data.len();
+        }
+    }
+    if data.len() <= COVER_CACHE_BYTES {
+        cache.bytes += data.len();
+        let previous = cache.covers.insert(
+            dir.to_path_buf(),
+            CachedCover {
+                dir_stamp,
+                file,
+                file_stamp,
+                data: Arc::clone(&data),
+                last_used: tick,
+            },
+        );
+        if let Some(previous) = previous {
+            cache.bytes -= previous.data.len();
+        }
+    }
+    Ok(Some(data))
+}
+
+pub(crate) fn infer_mime(data: &[u8]) -> &'static str {
     if data.starts_with(b"\xFF\xD8\xFF") {
         "image/jpeg"
     } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
@@ -31,12 +179,24 @@ fn sha256_hex(data: &[u8]) -> String {
 
 #[derive(Debug)]
 pub struct AlbumArtExtractor {
-    db: Database,
+    db: Box<dyn LibraryStore>,
+    chunk_size: usize,
 }
 
 impl AlbumArtExtractor {
-    pub fn new(db: Database) -> Self {
-        Self { db }
+    pub fn new(db: Box<dyn LibraryStore>) -> Self {
+        Self {
+            db,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Serve chunks of at most `chunk_size` bytes (a client's `binarylimit`)
+    /// instead of [`DEFAULT_CHUNK_SIZE`].
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
     }
 
     /// Extract album art from a file and cache it
@@ -53,23 +213,16 @@ impl AlbumArtExtractor {
         }
 
         // Not in cache, extract from file using absolute path
-        let abs_path = Path::new(file_path);
-        let tagged_file = lofty::read_from_path(abs_path)
-            .map_err(|e| RmpdError::Library(format!("Failed to read file: {e}")))?;
+        let pictures = embedded_pictures(Path::new(file_path))?;
 
         // Try to find front cover
-        let picture = if let Some(primary_tag) = tagged_file.primary_tag() {
-            primary_tag
-                .pictures()
-                .iter()
-                .find(|p| matches!(p.pic_type(), PictureType::CoverFront | PictureType::Other))
-                .or_else(|| primary_tag.pictures().first())
-        } else {
-            None
-        };
+        let picture = pictures
+            .iter()
+            .find(|p| matches!(p.pic_type, PictureType::CoverFront | PictureType::Other))
+            .or_else(|| pictures.first());
 
         if let Some(pic) = picture {
-            let data = pic.data();
+            let data = &pic.data;
 
             // Check size limit
             if data.len() > MAX_ARTWORK_SIZE {
@@ -84,15 +237,15 @@ impl AlbumArtExtractor {
 
             // Get MIME type from tag, fall back to magic-byte inference
             let mime_type = pic
-                .mime_type()
-                .map(|m| m.to_string())
+                .mime_type
+                .clone()
                 .unwrap_or_else(|| infer_mime(data).to_owned());
 
             // Store in cache using relative path as key
             self.db
                 .store_artwork(cache_key, "front", &mime_type, data, &hash)?;
 
-            Ok(Some((data.to_vec(), mime_type)))
+            Ok(Some((data.clone(), mime_type)))
         } else {
             Ok(None)
         }
@@ -125,7 +278,27 @@ impl AlbumArtExtractor {
         self.db.has_artwork(cache_key, "front").unwrap_or(false)
     }
 
-    /// Get album art from cache or extract if not cached
+    /// SHA-256 (hex) of the artwork cached for `cache_key`, if any; changes
+    /// whenever the cached image does.
+    pub fn cached_hash(&self, cache_key: &str) -> Result<Option<String>> {
+        self.db.get_artwork_hash(cache_key, "front")
+    }
+
+    /// The chunk at `offset` of the artwork cached for `cache_key`, read
+    /// straight from the database without loading the whole image.
+    fn cached_chunk(&self, cache_key: &str, offset: usize) -> Result<Option<ArtworkData>> {
+        Ok(self
+            .db
+            .read_artwork_chunk(cache_key, "front", offset, self.chunk_size)?
+            .map(|(data, mime_type, total_size)| ArtworkData {
+                mime_type,
+                total_size,
+                data,
+            }))
+    }
+
+    /// Get the embedded picture of a song (`readpicture`) from cache or
+    /// extract it if not cached
     /// `cache_key`: relative path for cache lookup (e.g., "01.m4a")
     /// `file_path`: absolute path for file reading (e.g., "/home/user/Music/01.m4a")
     pub fn get_artwork(
@@ -134,46 +307,210 @@ impl AlbumArtExtractor {
         file_path: &str,
         offset: usize,
     ) -> Result<Option<ArtworkData>> {
+        // Clients fetch large images in many chunks; after the first request
+        // each one is served from the cache without copying the whole blob.
+        if let Some(artwork) = self.cached_chunk(cache_key, offset)? {
+            return Ok(Some(artwork));
+        }
         let (data, stored_mime) = match self.extract_and_cache(cache_key, file_path)? {
             Some(result) => result,
             None => return Ok(None),
         };
 
-        // Use the stored MIME type; fall back to magic-byte inference only when empty.
-        let mime_type = if stored_mime.is_empty() {
-            infer_mime(&data).to_owned()
-        } else {
-            stored_mime
+        Ok(Some(ArtworkData::chunk(
+            &data,
+            stored_mime,
+            offset,
+            self.chunk_size,
+        )))
+    }
+
+    /// Get album art for a song (`albumart Album/01.flac`): the cover image
+    /// file ([`COVER_FILE_NAMES`]) in the directory of `file_path`, an
+    /// absolute path. As in MPD, embedded pictures are not looked at; those
+    /// are [`Self::get_artwork`]'s.
+    pub fn get_song_cover(&self, file_path: &str, offset: usize) -> Result<Option<ArtworkData>> {
+        let Some(dir) = Path::new(file_path).parent() else {
+            return Ok(None);
+        };
+        Ok(directory_cover(dir)?
+            .map(|data| ArtworkData::chunk(&data, String::new(), offset, self.chunk_size)))
+    }
+
+    /// Get album art for a directory URI (`albumart Album/`).
+    ///
+    /// Resolution order follows MPD: a [`COVER_FILE_NAMES`] image in the
+    /// directory itself, then the embedded art of the first song in it (in
+    /// `lsinfo` order) that has any. `dir_uri` is a library URI; `resolve`
+    /// maps library URIs to paths on disk (which may span several music
+    /// roots).
+    pub fn get_directory_artwork(
+        &self,
+        dir_uri: &str,
+        resolve: impl Fn(&str) -> String,
+        offset: usize,
+    ) -> Result<Option<ArtworkData>> {
+        let dir_uri = dir_uri.trim_matches('/');
+        let abs_dir = resolve(dir_uri);
+        let abs_dir = Path::new(&abs_dir);
+
+        if let Some(data) = directory_cover(abs_dir)? {
+            return Ok(Some(ArtworkData::chunk(
+                &data,
+                String::new(),
+                offset,
+                self.chunk_size,
+            )));
+        }
+
+        // Unknown to the database: no songs to fall back on.
+        let Ok(listing) = self.db.list_directory(dir_uri) else {
+            return Ok(None);
         };
+        for song in &listing.songs {
+            let abs_song = resolve(song.path.as_str());
+            if let Ok(Some(artwork)) = self.cached_chunk(song.path.as_str(), offset) {
+                return Ok(Some(artwork));
+            }
+            // A song without readable art is skipped, not an error.
+            if let Ok(Some((data, mime))) = self.extract_and_cache(song.path.as_str(), &abs_song) {
+                return Ok(Some(ArtworkData::chunk(
+                    &data,
+                    mime,
+                    offset,
+                    self.chunk_size,
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
 
-        // Handle offset for chunked transfer
-        // MPD protocol uses 8KB (8192 byte) chunks
-        const CHUNK_SIZE: usize = 8192;
+#[derive(Debug)]
+pub struct ArtworkData {
+    pub mime_type: String,
+    pub total_size: usize,
+    pub data: Vec<u8>,
+}
 
+impl ArtworkData {
+    /// The chunk of `data` starting at `offset`, at most `chunk_size` bytes,
+    /// for chunked transfer. `mime_type` falls back to magic-byte inference
+    /// when empty.
+    fn chunk(data: &[u8], mime_type: String, offset: usize, chunk_size: usize) -> Self {
+        let mime_type = if mime_type.is_empty() {
+            infer_mime(data).to_owned()
+        } else {
+            mime_type
+        };
+        // An offset past the end yields an empty chunk; the protocol layer
+        // decides whether that is an error.
         let chunk = if offset >= data.len() {
-            // Return empty chunk when offset is past the end
-            // This is needed for proper MPD protocol compliance
             Vec::new()
         } else {
-            let end = (offset + CHUNK_SIZE).min(data.len());
+            let end = offset.saturating_add(chunk_size).min(data.len());
             data[offset..end].to_vec()
         };
-
-        Ok(Some(ArtworkData {
+        Self {
             mime_type,
             total_size: data.len(),
             data: chunk,
-        }))
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct ArtworkData {
-    pub mime_type: String,
-    pub total_size: usize,
+// ── Embedded picture discovery ──────────────────────────────────────────────
+
+/// One embedded picture, independent of the tag format it was stored in.
+pub(crate) struct EmbeddedPicture {
+    pub pic_type: PictureType,
+    pub mime_type: Option<String>,
     pub data: Vec<u8>,
 }
 
+/// Every embedded picture in `path`.
+///
+/// All tags lofty finds are searched, primary first, so covers stored only in
+/// a secondary tag (APEv2 binary items on MP3/WavPack/APE, alongside ID3v2)
+/// are found too; MP4 `covr` atoms arrive through the same path. DSD
+/// containers (DSF/DFF) carry their tags in an ID3v2 chunk that is read
+/// directly when lofty yields nothing for them.
+pub(crate) fn embedded_pictures(path: &Path) -> Result<Vec<EmbeddedPicture>> {
+    let is_dsd = dsd::is_dsd_path(path);
+
+    let tagged_file = match lofty::read_from_path(path) {
+        Ok(f) => f,
+        Err(_) if is_dsd => return dsd_pictures(path),
+        Err(e) => return Err(RmpdError::Library(format!("Failed to read file: {e}"))),
+    };
+
+    let primary = tagged_file.primary_tag();
+    let mut pictures: Vec<EmbeddedPicture> = primary
+        .into_iter()
+        .chain(
+            tagged_file
+                .tags()
+                .iter()
+                .filter(|t| primary.is_none_or(|p| p.tag_type() != t.tag_type())),
+        )
+        .flat_map(|tag| tag.pictures())
+        .map(|p| EmbeddedPicture {
+            pic_type: p.pic_type(),
+            mime_type: p.mime_type().map(|m| m.to_string()),
+            data: p.data().to_vec(),
+        })
+        .collect();
+
+    if pictures.is_empty() && is_dsd {
+        pictures = dsd_pictures(path)?;
+    }
+    Ok(pictures)
+}
+
+/// Pictures from the ID3v2 tag of a DSF or DSDIFF file.
+fn dsd_pictures(path: &Path) -> Result<Vec<EmbeddedPicture>> {
+    let Some(tag) = dsd::read_dsd_info(path)?.id3 else {
+        return Ok(Vec::new());
+    };
+    let (major, frames) = rawtag::id3v2_frames(&tag);
+    Ok(frames
+        .iter()
+        .filter(|f| (major == 2 && f.id == b"PIC") || (major >= 3 && f.id == b"APIC"))
+        .filter_map(|f| parse_apic(f.body, major == 2))
+        .collect())
+}
+
+/// Decode one APIC/PIC frame body.
+fn parse_apic(body: &[u8], v22: bool) -> Option<EmbeddedPicture> {
+    let (&encoding, rest) = body.split_first()?;
+    let (mime_type, rest) = if v22 {
+        // v2.2 stores a three-character image format instead of a MIME type.
+        let mime = match rest.get(..3)? {
+            b"PNG" => Some("image/png".to_owned()),
+            b"JPG" => Some("image/jpeg".to_owned()),
+            _ => None,
+        };
+        (mime, &rest[3..])
+    } else {
+        let nul = rest.iter().position(|&b| b == 0)?;
+        let mime = String::from_utf8_lossy(&rest[..nul]).into_owned();
+        ((!mime.is_empty()).then_some(mime), &rest[nul + 1..])
+    };
+    let (&pic_type, rest) = rest.split_first()?;
+    // Skip the description, terminated per its text encoding.
+    let data = if matches!(encoding, 1 | 2) {
+        let end = rest.chunks_exact(2).position(|c| c == [0, 0])?;
+        &rest[end * 2 + 2..]
+    } else {
+        &rest[rest.iter().position(|&b| b == 0)? + 1..]
+    };
+    (!data.is_empty()).then(|| EmbeddedPicture {
+        pic_type: PictureType::from_u8(pic_type),
+        mime_type,
+        data: data.to_vec(),
+    })
+}
+
 pub(crate) fn picture_type_to_string(pic_type: PictureType) -> String {
     match pic_type {
         PictureType::CoverFront => "front",
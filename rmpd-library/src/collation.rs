@@ -0,0 +1,100 @@
+//! Locale-aware ordering of `list` values and `lsinfo` listings.
+//!
+//! MPD sorts tag values and directory names by byte order, which puts
+//! "Édith Piaf" after "Zappa" and "de Staat" after every capitalised name.
+//! With `general.collation` set to a locale (`"de"`, `"sv"`, `"root"`, …)
+//! those listings follow that locale's ICU collation instead; left unset,
+//! the byte order clients expect from MPD is kept.
+
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+use icu_collator::{CollatorBorrowed, CollatorPreferences};
+use icu_locale_core::Locale;
+use rmpd_core::error::{Result, RmpdError};
+
+static COLLATOR: OnceLock<CollatorBorrowed<'static>> = OnceLock::new();
+
+/// ICU collator for `locale`, a BCP 47 tag such as `"de"` or
+/// `"es-u-co-trad"`; `"root"` is accepted for the locale-neutral order.
+pub fn collator_for(locale: &str) -> Result<CollatorBorrowed<'static>> {
+    let tag = if locale.eq_ignore_ascii_case("root") {
+        "und"
+    } else {
+        locale
+    };
+    let locale: Locale = tag
+        .parse()
+        .map_err(|e| RmpdError::Config(format!("invalid collation locale {locale:?}: {e}")))?;
+    CollatorBorrowed::try_new(CollatorPreferences::from(&locale), Default::default())
+        .map_err(|e| RmpdError::Library(format!("ICU collator unavailable: {e}")))
+}
+
+/// Install the process-wide listing collation for `locale`. Set once at
+/// startup; returns false if a collation was already installed.
+pub fn set_collation(locale: &str) -> Result<bool> {
+    Ok(COLLATOR.set(collator_for(locale)?).is_ok())
+}
+
+/// The configured listing collator, if any.
+pub fn collator() -> Option<&'static CollatorBorrowed<'static>> {
+    COLLATOR.get()
+}
+
+/// Order two listing names: by `collator` when given, else by bytes. Names
+/// the collator considers equal fall back to byte order, so sorting stays
+/// deterministic.
+pub fn compare_with(collator: Option<&CollatorBorrowed<'_>>, a: &str, b: &str) -> Ordering {
+    match collator {
+        Some(collator) => collator.compare(a, b).then_with(|| a.cmp(b)),
+        None => a.cmp(b),
+    }
+}
+
+/// Order two listing names by the configured collation.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    compare_with(collator(), a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collator: Option<&CollatorBorrowed<'_>>, names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|s| (*s).to_owned()).collect();
+        names.sort_by(|a, b| compare_with(collator, a, b));
+        names
+    }
+
+    #[test]
+    fn unset_collation_keeps_byte_order() {
+        assert_eq!(
+            sorted(None, &["Zappa", "Édith Piaf", "de Staat", "Abba"]),
+            ["Abba", "Zappa", "de Staat", "Édith Piaf"]
+        );
+    }
+
+    #[test]
+    fn locale_collation_orders_accents_and_case_naturally() {
+        let root = collator_for("root").unwrap();
+        assert_eq!(
+            sorted(Some(&root), &["Zappa", "Édith Piaf", "de Staat", "Abba"]),
+            ["Abba", "de Staat", "Édith Piaf", "Zappa"]
+        );
+    }
+
+    #[test]
+    fn locale_tailoring_is_applied() {
+        // Swedish sorts Å, Ä and Ö after Z; German sorts Ä with A.
+        let names = ["Äventyr", "Zorn", "Abba"];
+        let sv = collator_for("sv").unwrap();
+        assert_eq!(sorted(Some(&sv), &names), ["Abba", "Zorn", "Äventyr"]);
+        let de = collator_for("de").unwrap();
+        assert_eq!(sorted(Some(&de), &names), ["Abba", "Äventyr", "Zorn"]);
+    }
+
+    #[test]
+    fn invalid_locale_is_rejected() {
+        assert!(collator_for("not a locale!").is_err());
+    }
+}
@@ -1,11 +1,14 @@
+use crate::lyrics::{Lyrics, LyricsSource};
 use camino::Utf8PathBuf;
 use icu_collator::{CollatorBorrowed, CollatorPreferences};
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::{Song, intern_tag_key};
 use rmpd_core::tag::tag_fallback_chain;
 use rmpd_core::time::system_time_to_unix_secs;
-use rusqlite::{Connection, OptionalExtension, Row, functions::FunctionFlags, params};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Row, functions::FunctionFlags, params};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
@@ -27,23 +30,36 @@ fn song_cmp(a: &Song, b: &Song, col: &CollatorBorrowed<'_>) -> Ordering {
     if album_ord != Ordering::Equal {
         return album_ord;
     }
-    let disc_a: u32 = a.tag("disc").and_then(|v| v.parse().ok()).unwrap_or(0);
-    let disc_b: u32 = b.tag("disc").and_then(|v| v.parse().ok()).unwrap_or(0);
-    let disc_ord = disc_a.cmp(&disc_b);
-    if disc_ord != Ordering::Equal {
-        return disc_ord;
-    }
-    let track_a: u32 = a.tag("track").and_then(|v| v.parse().ok()).unwrap_or(0);
-    let track_b: u32 = b.tag("track").and_then(|v| v.parse().ok()).unwrap_or(0);
-    let track_ord = track_a.cmp(&track_b);
-    if track_ord != Ordering::Equal {
-        return track_ord;
+    let number_ord = a.disc_and_track().cmp(&b.disc_and_track());
+    if number_ord != Ordering::Equal {
+        return number_ord;
     }
     let a_name = a.path.file_name().unwrap_or(a.path.as_str());
     let b_name = b.path.file_name().unwrap_or(b.path.as_str());
     col.compare(a_name, b_name)
 }
 
+/// Put query results in database order, as MPD returns them: directory by
+/// directory, songs within one in [`song_cmp`] order. The tracks of a
+/// multi-disc album then come out disc by disc.
+fn sort_database_order(songs: &mut [Song]) -> Result<()> {
+    let by_directory = |a: &Song, b: &Song, col: &CollatorBorrowed<'_>| {
+        a.path
+            .parent()
+            .cmp(&b.path.parent())
+            .then_with(|| song_cmp(a, b, col))
+    };
+    match crate::collation::collator() {
+        Some(col) => songs.sort_by(|a, b| by_directory(a, b, col)),
+        None => {
+            let col = CollatorBorrowed::try_new(CollatorPreferences::default(), Default::default())
+                .map_err(|e| RmpdError::Library(format!("ICU collator unavailable: {e}")))?;
+            songs.sort_by(|a, b| by_directory(a, b, &col));
+        }
+    }
+    Ok(())
+}
+
 /// An entry yielded during a recursive directory walk.
 pub enum WalkEntry<'a> {
     /// A song file.
@@ -80,6 +96,33 @@ const SONGS_FTS_CREATE_SQL: &str = "
         content='', contentless_delete=1
     )";
 
+/// Tags covered by the FTS index, and matched by the `LIKE` fallback of
+/// [`Database::search_songs`] when the index is not used.
+const FTS_TAGS: &str = "'title', 'artist', 'album', 'albumartist', 'genre', 'composer'";
+
+/// Whether the FTS index may be used at all; see [`set_fts_enabled`].
+static FTS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn the FTS5 search index on or off for the whole process, as
+/// `database.fts_enabled` does. Set once at startup, before the database is
+/// opened. Without the index `search any` matches tag values with `LIKE`.
+pub fn set_fts_enabled(enabled: bool) {
+    FTS_ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn fts_enabled() -> bool {
+    FTS_ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether the SQLite behind `conn` has the fts5 module. Some platforms ship
+/// SQLite built without it, and creating `songs_fts` would then fail.
+fn fts5_available(conn: &Connection) -> bool {
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE temp.fts5_probe USING fts5(x); DROP TABLE temp.fts5_probe;",
+    )
+    .is_ok()
+}
+
 /// Trigger that removes a song's FTS row when the song row is deleted. With
 /// `contentless_delete=1` the row is removed by a plain `DELETE` on its rowid
 /// (the special 'delete' insert command is rejected on such tables).
@@ -88,6 +131,55 @@ const SONGS_FTS_DELETE_TRIGGER_SQL: &str = "
         DELETE FROM songs_fts WHERE rowid = old.id;
     END";
 
+/// Normalized artist and album names: one row per distinct non-empty
+/// `artist` / `album` tag value, with the number of tag rows carrying it, so
+/// `stats` and `list artist|album` read a small table instead of running
+/// `DISTINCT` over `song_tags`. The triggers keep both tables in sync with
+/// every tag insert and delete, including the `ON DELETE CASCADE` from
+/// `songs`, so scans, the watcher and source syncs maintain them alike.
+const NAME_TABLES_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS artists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        song_count INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS albums (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        song_count INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TRIGGER IF NOT EXISTS artists_tag_insert AFTER INSERT ON song_tags
+    WHEN new.tag = 'artist' AND new.value != '' BEGIN
+        INSERT INTO artists (name, song_count) VALUES (new.value, 1)
+            ON CONFLICT(name) DO UPDATE SET song_count = song_count + 1;
+    END;
+    CREATE TRIGGER IF NOT EXISTS artists_tag_delete AFTER DELETE ON song_tags
+    WHEN old.tag = 'artist' AND old.value != '' BEGIN
+        UPDATE artists SET song_count = song_count - 1 WHERE name = old.value;
+        DELETE FROM artists WHERE name = old.value AND song_count <= 0;
+    END;
+    CREATE TRIGGER IF NOT EXISTS albums_tag_insert AFTER INSERT ON song_tags
+    WHEN new.tag = 'album' AND new.value != '' BEGIN
+        INSERT INTO albums (name, song_count) VALUES (new.value, 1)
+            ON CONFLICT(name) DO UPDATE SET song_count = song_count + 1;
+    END;
+    CREATE TRIGGER IF NOT EXISTS albums_tag_delete AFTER DELETE ON song_tags
+    WHEN old.tag = 'album' AND old.value != '' BEGIN
+        UPDATE albums SET song_count = song_count - 1 WHERE name = old.value;
+        DELETE FROM albums WHERE name = old.value AND song_count <= 0;
+    END;";
+
+/// Re-link stored playlist items to a song inserted under their URI.
+///
+/// `playlist_items.uri` is the authoritative reference; `song_id` is a cache
+/// that `ON DELETE SET NULL` clears when a song row goes away. Without this
+/// trigger a song removed and re-added by a rescan (or a rebuilt database)
+/// would come back under a new id and stay unlinked.
+const PLAYLIST_ITEMS_RELINK_TRIGGER_SQL: &str = "
+    CREATE TRIGGER IF NOT EXISTS playlist_items_relink AFTER INSERT ON songs BEGIN
+        UPDATE playlist_items SET song_id = new.id WHERE uri = new.path;
+    END";
+
 /// Construct a Song (without tags) from a database row.
 /// Tags are loaded separately via `load_tags_for_songs`.
 fn song_from_row(row: &Row<'_>) -> rusqlite::Result<Song> {
@@ -120,15 +212,33 @@ fn get_playlist_id(conn: &Connection, name: &str) -> Result<i64> {
     .ok_or_else(|| RmpdError::Library(format!("Playlist not found: {name}")))
 }
 
+/// `db_file` value that keeps the library in RAM, as SQLite spells it.
+pub const MEMORY_DB: &str = ":memory:";
+
+/// Where [`MEMORY_DB`] is opened: a plain `:memory:` connection gets a
+/// private database, while every connection of the process opening this
+/// memdb URI shares one.
+const MEMORY_DB_URI: &str = "file:/rmpd-memory?vfs=memdb";
+
+/// Whether `path` asks for the in-memory library ([`MEMORY_DB`]).
+pub fn is_memory_db(path: &str) -> bool {
+    path == MEMORY_DB
+}
+
 /// Open a SQLite connection configured for rmpd: WAL journaling (lets readers
 /// run concurrently with a writer), a busy timeout (writers wait under WAL
-/// contention instead of erroring), and foreign-key enforcement.
+/// contention instead of erroring), and foreign-key enforcement. The
+/// in-memory library keeps its default journal, as memdb has no WAL.
 fn open_connection(path: &str) -> Result<Connection> {
-    let conn = Connection::open(path)?;
+    let conn = if is_memory_db(path) {
+        Connection::open(MEMORY_DB_URI)?
+    } else {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        conn
+    };
     conn.busy_timeout(Duration::from_secs(5))?;
-    conn.execute_batch(
-        "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA foreign_keys = ON;",
-    )?;
+    conn.execute_batch("PRAGMA synchronous = NORMAL; PRAGMA foreign_keys = ON;")?;
     conn.create_scalar_function(
         "regexp",
         2,
@@ -153,17 +263,26 @@ fn open_connection(path: &str) -> Result<Connection> {
 #[derive(Debug)]
 pub struct DbPool {
     path: String,
-    idle: Mutex<Vec<Connection>>,
-    max_idle: usize,
+    idle: Arc<IdleConns>,
+    /// Whether the FTS index is used; see [`Database::search_songs`].
+    fts: bool,
+    /// Held open for the in-memory library, which SQLite frees along with
+    /// its last connection while pooled ones come and go.
+    _memory_keeper: Option<Connection>,
 }
 
 impl DbPool {
     /// Create a pool for `path`, running schema migration/initialisation once.
     pub fn new(path: &str) -> Result<Arc<Self>> {
         let conn = open_connection(path)?;
+        let fts = fts_enabled() && fts5_available(&conn);
+        if fts_enabled() && !fts {
+            tracing::warn!("SQLite lacks FTS5, `search any` falls back to substring matching");
+        }
         // Run migration + schema setup exactly once, on this connection.
         let db = Database {
             conn: DbConn::Direct(conn),
+            fts,
         };
         db.migrate_schema()?;
         db.init_schema()?;
@@ -172,27 +291,51 @@ impl DbPool {
         };
         Ok(Arc::new(Self {
             path: path.to_owned(),
-            idle: Mutex::new(vec![conn]),
-            max_idle: 8,
+            idle: Arc::new(IdleConns {
+                conns: Mutex::new(vec![conn]),
+                max: 8,
+            }),
+            fts,
+            _memory_keeper: is_memory_db(path)
+                .then(|| open_connection(path))
+                .transpose()?,
         }))
     }
 
     /// Check out a connection, reusing an idle one when available.
-    pub fn checkout(self: &Arc<Self>) -> Result<PooledConn> {
-        let reused = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+    pub fn checkout(&self) -> Result<PooledConn> {
+        let reused = self.idle.lock().pop();
         let conn = match reused {
             Some(conn) => conn,
             None => open_connection(&self.path)?,
         };
         Ok(PooledConn {
             conn: Some(conn),
-            pool: Arc::clone(self),
+            idle: Arc::clone(&self.idle),
         })
     }
 
+    /// The database file (or [`MEMORY_DB`]) this pool opens.
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Idle connections of a [`DbPool`], shared with the connections it lends.
+#[derive(Debug)]
+struct IdleConns {
+    conns: Mutex<Vec<Connection>>,
+    max: usize,
+}
+
+impl IdleConns {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Connection>> {
+        self.conns.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     fn checkin(&self, conn: Connection) {
-        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
-        if idle.len() < self.max_idle {
+        let mut idle = self.lock();
+        if idle.len() < self.max {
             idle.push(conn);
         }
         // Otherwise drop the connection: the pool is already at capacity.
@@ -203,13 +346,13 @@ impl DbPool {
 #[derive(Debug)]
 pub struct PooledConn {
     conn: Option<Connection>,
-    pool: Arc<DbPool>,
+    idle: Arc<IdleConns>,
 }
 
 impl Drop for PooledConn {
     fn drop(&mut self) {
         if let Some(conn) = self.conn.take() {
-            self.pool.checkin(conn);
+            self.idle.checkin(conn);
         }
     }
 }
@@ -234,6 +377,9 @@ impl std::ops::Deref for DbConn {
 #[derive(Debug)]
 pub struct Database {
     conn: DbConn,
+    /// Whether `songs_fts` is kept up to date and searched. Off when
+    /// disabled by [`set_fts_enabled`] or when SQLite lacks FTS5.
+    fts: bool,
 }
 
 impl Database {
@@ -241,19 +387,46 @@ impl Database {
     /// tests). Runs schema migration/initialisation.
     pub fn open(path: &str) -> Result<Self> {
         let conn = open_connection(path)?;
+        let fts = fts_enabled() && fts5_available(&conn);
         let db = Self {
             conn: DbConn::Direct(conn),
+            fts,
         };
         db.migrate_schema()?;
         db.init_schema()?;
         Ok(db)
     }
 
+    /// Open `path` for reading only, leaving the file untouched: nothing is
+    /// created and no migration runs (used by `--scan-dry-run`). A missing
+    /// file, like the in-memory library of a fresh process, reads as an
+    /// empty library.
+    pub fn open_read_only(path: &str) -> Result<Self> {
+        if is_memory_db(path) || !std::path::Path::new(path).exists() {
+            let db = Self {
+                conn: DbConn::Direct(Connection::open_in_memory()?),
+                fts: false,
+            };
+            db.init_schema()?;
+            return Ok(db);
+        }
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self {
+            conn: DbConn::Direct(conn),
+            fts: false,
+        })
+    }
+
     /// Construct a database backed by a pooled connection. The pool already ran
     /// schema setup, so this just borrows a ready connection.
-    pub fn from_pool(pool: &Arc<DbPool>) -> Result<Self> {
+    pub fn from_pool(pool: &DbPool) -> Result<Self> {
         Ok(Self {
             conn: DbConn::Pooled(pool.checkout()?),
+            fts: pool.fts,
         })
     }
 
@@ -263,6 +436,10 @@ impl Database {
     ///   v2→v3: Add songs.source column for remote catalog origin
     ///   v3→v4: Recreate songs_fts with contentless_delete=1 (fixes FTS index
     ///          corruption triggered by row deletes such as clear_source)
+    ///   v4→v5: Replace the unused artists/albums tables with the trigger-maintained
+    ///          name tables (NAME_TABLES_SQL) and fill them from song_tags
+    ///   v5→v6: Add songs.album_gain_derived marking album gains computed by
+    ///          `derive_album_gains` rather than read from tags
     fn migrate_schema(&self) -> Result<()> {
         // Check if song_tags already exists with the old UNIQUE constraint.
         // We detect this by looking at sqlite_master for the table definition.
@@ -317,6 +494,19 @@ impl Database {
                 self.conn
                     .execute("ALTER TABLE songs ADD COLUMN source TEXT", [])?;
             }
+
+            // v5→v6: flag for derived album gains, same guard as above.
+            let has_derived: i64 = self.conn.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('songs') WHERE name='album_gain_derived'",
+                [],
+                |r| r.get(0),
+            )?;
+            if has_derived == 0 {
+                self.conn.execute(
+                    "ALTER TABLE songs ADD COLUMN album_gain_derived INTEGER NOT NULL DEFAULT 0",
+                    [],
+                )?;
+            }
         }
 
         // v3→v4: songs_fts must be declared with contentless_delete=1 (SQLite >= 3.43)
@@ -337,7 +527,8 @@ impl Database {
                 |row| row.get(0),
             )
             .optional()?;
-        if let Some(sql) = fts_sql
+        if self.fts
+            && let Some(sql) = fts_sql
             && !sql.contains("contentless_delete")
         {
             self.conn.execute_batch(
@@ -347,20 +538,41 @@ impl Database {
             )?;
             self.conn.execute(SONGS_FTS_CREATE_SQL, [])?;
             self.conn.execute(SONGS_FTS_DELETE_TRIGGER_SQL, [])?;
-            // Rebuild from song_tags: re-run the canonical per-song FTS insert for
-            // every song. The table is freshly empty, so each insert's rowid-only
+            // The table is freshly empty, so each insert's rowid-only
             // pre-delete is a harmless no-op.
-            let ids: Vec<i64> = {
-                let mut stmt = self.conn.prepare("SELECT id FROM songs")?;
-                stmt.query_map([], |row| row.get(0))?
-                    .collect::<std::result::Result<Vec<_>, _>>()?
-            };
-            for id in ids {
-                self.update_fts_for_song(id as u64)?;
-            }
+            self.index_all_songs_fts()?;
             self.conn.execute_batch("COMMIT;")?;
         }
 
+        // v4→v5: the old artists/albums tables were created but never filled.
+        // Databases with tags and without the counted name tables get them
+        // built from song_tags; fresh databases get them from init_schema.
+        let has_song_tags: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='song_tags'",
+            [],
+            |r| r.get(0),
+        )?;
+        let has_name_tables: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('artists') WHERE name='song_count'",
+            [],
+            |r| r.get(0),
+        )?;
+        if has_song_tags > 0 && has_name_tables == 0 {
+            self.conn.execute_batch(&format!(
+                "BEGIN;
+                 DROP TABLE IF EXISTS albums;
+                 DROP TABLE IF EXISTS artists;
+                 {NAME_TABLES_SQL}
+                 INSERT INTO artists (name, song_count)
+                     SELECT value, COUNT(*) FROM song_tags
+                     WHERE tag = 'artist' AND value != '' GROUP BY value;
+                 INSERT INTO albums (name, song_count)
+                     SELECT value, COUNT(*) FROM song_tags
+                     WHERE tag = 'album' AND value != '' GROUP BY value;
+                 COMMIT;"
+            ))?;
+        }
+
         Ok(())
     }
 
@@ -384,6 +596,7 @@ impl Database {
                 added_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
                 last_modified INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
                 source TEXT,
+                album_gain_derived INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY (directory_id) REFERENCES directories(id)
             )",
             [],
@@ -410,27 +623,8 @@ impl Database {
             [],
         )?;
 
-        // Artists table (normalized)
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS artists (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE COLLATE NOCASE
-            )",
-            [],
-        )?;
-
-        // Albums table (normalized)
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS albums (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                artist_id INTEGER,
-                date TEXT,
-                UNIQUE(name, artist_id),
-                FOREIGN KEY (artist_id) REFERENCES artists(id)
-            )",
-            [],
-        )?;
+        // Artist and album names (normalized, trigger-maintained). See NAME_TABLES_SQL.
+        self.conn.execute_batch(NAME_TABLES_SQL)?;
 
         // Playlists table
         self.conn.execute(
@@ -468,6 +662,28 @@ impl Database {
             [],
         )?;
 
+        // Queue snapshots ("bookmarks"): a saved queue with its playback position
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS queue_snapshots (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                current INTEGER,
+                elapsed REAL NOT NULL DEFAULT 0,
+                mtime INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS queue_snapshot_items (
+                id INTEGER PRIMARY KEY,
+                snapshot_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                uri TEXT NOT NULL,
+                FOREIGN KEY (snapshot_id) REFERENCES queue_snapshots(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
         // Artwork table (album art cache)
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS artwork (
@@ -484,8 +700,33 @@ impl Database {
             [],
         )?;
 
+        // Lyrics table (embedded lyrics tags and .lrc sidecars, filled by the scanner)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS lyrics (
+                song_path TEXT PRIMARY KEY,
+                text TEXT NOT NULL,
+                synced INTEGER NOT NULL DEFAULT 0,
+                source TEXT NOT NULL,
+                mtime INTEGER NOT NULL,
+                FOREIGN KEY (song_path) REFERENCES songs(path) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Playlist files found inside the music directory, per directory (filled by the scanner)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS playlist_files (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL UNIQUE,
+                directory_id INTEGER NOT NULL,
+                mtime INTEGER NOT NULL,
+                FOREIGN KEY (directory_id) REFERENCES directories(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
         // Full-text search index over song tags. See SONGS_FTS_CREATE_SQL.
-        self.conn.execute(SONGS_FTS_CREATE_SQL, [])?;
+        self.init_fts()?;
 
         // Indexes on song_tags
         self.conn.execute(
@@ -502,6 +743,15 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_songs_directory ON songs(directory_id)",
             [],
         )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_songs_added ON songs(added_at)",
+            [],
+        )?;
+        // Case-folded lookups (get_song_by_path fallback, scanner case check)
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_songs_path_nocase ON songs(path COLLATE NOCASE)",
+            [],
+        )?;
 
         // Indexes on directories
         self.conn.execute(
@@ -509,6 +759,12 @@ impl Database {
             [],
         )?;
 
+        // Indexes on playlist_files
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_playlist_files_directory ON playlist_files(directory_id)",
+            [],
+        )?;
+
         // Indexes on artwork
         self.conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_artwork_path ON artwork(song_path)",
@@ -519,8 +775,15 @@ impl Database {
             [],
         )?;
 
-        // Keep the FTS index in sync on song deletes. See SONGS_FTS_DELETE_TRIGGER_SQL.
-        self.conn.execute(SONGS_FTS_DELETE_TRIGGER_SQL, [])?;
+        // Indexes on playlist_items
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_playlist_items_uri ON playlist_items(uri)",
+            [],
+        )?;
+
+        // Follow songs re-inserted under a new id. See PLAYLIST_ITEMS_RELINK_TRIGGER_SQL.
+        self.conn.execute(PLAYLIST_ITEMS_RELINK_TRIGGER_SQL, [])?;
+        self.relink_playlist_items()?;
 
         Ok(())
     }
@@ -574,12 +837,61 @@ impl Database {
         Ok(())
     }
 
+    /// Create the FTS index and the trigger keeping it in sync on song
+    /// deletes (see SONGS_FTS_DELETE_TRIGGER_SQL). Without FTS only the
+    /// trigger is dropped, so deletes never touch the index; an index found
+    /// without its trigger missed changes meanwhile and is rebuilt.
+    fn init_fts(&self) -> Result<()> {
+        if !self.fts {
+            self.conn
+                .execute("DROP TRIGGER IF EXISTS songs_fts_delete", [])?;
+            return Ok(());
+        }
+        let exists = |kind: &str, name: &str| -> Result<bool> {
+            let count: i64 = self.conn.query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = ?1 AND name = ?2",
+                params![kind, name],
+                |r| r.get(0),
+            )?;
+            Ok(count > 0)
+        };
+        let stale = exists("table", "songs_fts")? && !exists("trigger", "songs_fts_delete")?;
+        self.conn.execute(SONGS_FTS_CREATE_SQL, [])?;
+        self.conn.execute(SONGS_FTS_DELETE_TRIGGER_SQL, [])?;
+        if stale {
+            self.conn.execute_batch(
+                "BEGIN;
+                 INSERT INTO songs_fts(songs_fts) VALUES('delete-all');",
+            )?;
+            self.index_all_songs_fts()?;
+            self.conn.execute_batch("COMMIT;")?;
+        }
+        Ok(())
+    }
+
+    /// Index every song from song_tags by re-running the canonical per-song
+    /// FTS insert.
+    fn index_all_songs_fts(&self) -> Result<()> {
+        let ids: Vec<i64> = {
+            let mut stmt = self.conn.prepare("SELECT id FROM songs")?;
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+        for id in ids {
+            self.update_fts_for_song(id as u64)?;
+        }
+        Ok(())
+    }
+
     /// Update the FTS index for a song by reading its tags from song_tags.
     fn update_fts_for_song(&self, song_id: u64) -> Result<()> {
+        if !self.fts {
+            return Ok(());
+        }
         // Single query to get all needed tags at once
-        let mut stmt = self.conn.prepare_cached(
-            "SELECT tag, value FROM song_tags WHERE song_id = ?1 AND tag IN ('title', 'artist', 'album', 'albumartist', 'genre', 'composer')"
-        )?;
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT tag, value FROM song_tags WHERE song_id = ?1 AND tag IN ({FTS_TAGS})"
+        ))?;
 
         let mut title = String::new();
         let mut artist = String::new();
@@ -658,6 +970,7 @@ impl Database {
                 replay_gain_track_peak = excluded.replay_gain_track_peak,
                 replay_gain_album_gain = excluded.replay_gain_album_gain,
                 replay_gain_album_peak = excluded.replay_gain_album_peak,
+                album_gain_derived = 0,
                 last_modified = excluded.mtime
             RETURNING id",
             params![
@@ -697,6 +1010,81 @@ impl Database {
         Ok(song_id)
     }
 
+    /// Fill in album ReplayGain for albums whose files carry track gains but
+    /// no album gain, so `replay_gain_mode album` keeps levels consistent
+    /// within them. The album gain is the mean of the track gains and the
+    /// album peak the highest track peak; an album is the songs sharing a
+    /// directory and `album` tag.
+    ///
+    /// Derived values are recomputed from scratch on every call, and an
+    /// album where any file has its own album gain is left alone. Returns the
+    /// number of songs given a derived album gain.
+    pub fn derive_album_gains(&self) -> Result<usize> {
+        self.derive_album_gains_where(None)
+    }
+
+    /// [`Self::derive_album_gains`] for the albums in the directory of the
+    /// song at `path` only, after that song was added or changed.
+    pub fn derive_album_gains_near(&self, path: &str) -> Result<usize> {
+        let dir: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT directory_id FROM songs WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match dir {
+            Some(dir) => self.derive_album_gains_where(Some(dir)),
+            None => Ok(0),
+        }
+    }
+
+    /// Derive album gains in the directory `dir`, or everywhere.
+    fn derive_album_gains_where(&self, dir: Option<i64>) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE songs
+             SET replay_gain_album_gain = NULL, replay_gain_album_peak = NULL,
+                 album_gain_derived = 0
+             WHERE album_gain_derived = 1 AND (?1 IS NULL OR directory_id = ?1)",
+            params![dir],
+        )?;
+        let derived = tx.execute(
+            "UPDATE songs
+             SET replay_gain_album_gain = a.gain,
+                 replay_gain_album_peak = a.peak,
+                 album_gain_derived = 1
+             FROM song_tags t
+             JOIN (
+                 SELECT s.directory_id AS dir, at.value AS album,
+                        AVG(s.replay_gain_track_gain) AS gain,
+                        MAX(s.replay_gain_track_peak) AS peak
+                 FROM songs s
+                 JOIN song_tags at ON at.song_id = s.id AND at.tag = 'album'
+                 WHERE ?1 IS NULL OR s.directory_id = ?1
+                 GROUP BY s.directory_id, at.value
+                 HAVING COUNT(s.replay_gain_track_gain) > 0
+                    AND COUNT(s.replay_gain_album_gain) = 0
+             ) a ON a.album = t.value
+             WHERE t.song_id = songs.id AND t.tag = 'album' AND songs.directory_id = a.dir",
+            params![dir],
+        )?;
+        tx.commit()?;
+        Ok(derived)
+    }
+
+    /// Add or update `songs` in one transaction. Much faster than calling
+    /// [`Self::add_song`] per song when importing a large batch.
+    pub fn add_songs(&self, songs: &[Song]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for song in songs {
+            self.add_song(song)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn get_song(&self, id: u64) -> Result<Option<Song>> {
         let query = format!("SELECT {SONG_COLUMNS} FROM songs WHERE id = ?1");
         let song = self
@@ -712,12 +1100,25 @@ impl Database {
         }
     }
 
+    /// Look up a song by URI. On case-insensitive platforms (see
+    /// [`rmpd_core::path::CASE_INSENSITIVE_FS`]) a URI without an exact match
+    /// falls back to the single song whose path differs only in case.
     pub fn get_song_by_path(&self, path: &str) -> Result<Option<Song>> {
         let query = format!("SELECT {SONG_COLUMNS} FROM songs WHERE path = ?1");
-        let song = self
+        let mut song = self
             .conn
             .query_row(&query, params![path], song_from_row)
             .optional()?;
+        if song.is_none() && rmpd_core::path::CASE_INSENSITIVE_FS {
+            let query = format!("SELECT {SONG_COLUMNS} FROM songs WHERE path = ?1 COLLATE NOCASE");
+            let mut stmt = self.conn.prepare(&query)?;
+            let mut matches = stmt
+                .query_map(params![path], song_from_row)?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if matches.len() == 1 {
+                song = matches.pop();
+            }
+        }
         match song {
             Some(mut s) => {
                 s.tags = self.load_tags_for_song(s.id)?;
@@ -755,20 +1156,77 @@ impl Database {
             .query_row("SELECT COUNT(*) FROM songs", [], |row| row.get(0))?)
     }
 
+    /// Composition of the local library under `dir` (everything when empty):
+    /// song counts and playtime by format, sample rate, bit depth and
+    /// lossless/lossy. `resolve` maps song URIs to files on disk, which are
+    /// stat'ed for the total size.
+    pub fn format_report(
+        &self,
+        dir: &str,
+        resolve: impl Fn(&str) -> String,
+    ) -> Result<FormatReport> {
+        let dir = dir.trim_matches('/');
+        let prefix = format!("{dir}/");
+        let mut stmt = self.conn.prepare(
+            "SELECT path, duration, sample_rate, bits_per_sample FROM songs
+             WHERE source IS NULL
+               AND (?1 = '' OR path = ?1 OR substr(path, 1, length(?2)) = ?2)",
+        )?;
+        let rows = stmt.query_map(params![dir, prefix], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<f64>>(1)?,
+                row.get::<_, Option<u32>>(2)?,
+                row.get::<_, Option<u16>>(3)?,
+            ))
+        })?;
+
+        let mut report = FormatReport::default();
+        let mut formats = BTreeMap::new();
+        let mut sample_rates = BTreeMap::new();
+        let mut bit_depths = BTreeMap::new();
+        for row in rows {
+            let (path, duration, sample_rate, bits) = row?;
+            let duration = duration.unwrap_or(0.0);
+            let format = camino::Utf8Path::new(&path)
+                .extension()
+                .map(str::to_lowercase)
+                .unwrap_or_default();
+            let quality = if is_lossless(&format, bits) {
+                &mut report.lossless
+            } else {
+                &mut report.lossy
+            };
+
+            let groups = [
+                Some(&mut report.total),
+                Some(quality),
+                Some(formats.entry(format).or_default()),
+                sample_rate.map(|rate| sample_rates.entry(rate).or_default()),
+                bits.map(|bits| bit_depths.entry(bits).or_default()),
+            ];
+            for group in groups.into_iter().flatten() {
+                group.songs += 1;
+                group.playtime += duration;
+            }
+            report.size += std::fs::metadata(resolve(&path)).map_or(0, |m| m.len());
+        }
+        report.formats = formats.into_iter().collect();
+        report.sample_rates = sample_rates.into_iter().collect();
+        report.bit_depths = bit_depths.into_iter().collect();
+        Ok(report)
+    }
+
     pub fn count_artists(&self) -> Result<u32> {
-        Ok(self.conn.query_row(
-            "SELECT COUNT(DISTINCT value) FROM song_tags WHERE tag = 'artist' AND value != ''",
-            [],
-            |row| row.get(0),
-        )?)
+        Ok(self
+            .conn
+            .query_row("SELECT COUNT(*) FROM artists", [], |row| row.get(0))?)
     }
 
     pub fn count_albums(&self) -> Result<u32> {
-        Ok(self.conn.query_row(
-            "SELECT COUNT(DISTINCT value) FROM song_tags WHERE tag = 'album' AND value != ''",
-            [],
-            |row| row.get(0),
-        )?)
+        Ok(self
+            .conn
+            .query_row("SELECT COUNT(*) FROM albums", [], |row| row.get(0))?)
     }
 
     /// Get all database statistics in a single query.
@@ -841,7 +1299,13 @@ impl Database {
         format!("\"{}\"{}", body.replace('"', "\"\""), suffix)
     }
 
+    /// Full-text search over title, artist, album, album artist, genre and
+    /// composer, as `search any` does. Without the FTS index this is a
+    /// case-insensitive substring match on the same tags.
     pub fn search_songs(&self, query: &str) -> Result<Vec<Song>> {
+        if !self.fts {
+            return self.search_songs_like(query);
+        }
         let escaped_query = Self::escape_fts_query(query);
         let sql = format!(
             "SELECT {SONG_COLUMNS_ALIASED}
@@ -858,13 +1322,49 @@ impl Database {
         Ok(songs)
     }
 
+    /// [`Self::search_songs`] without the FTS index. A trailing `*` (an FTS
+    /// prefix search) is dropped: a substring match covers prefixes.
+    fn search_songs_like(&self, query: &str) -> Result<Vec<Song>> {
+        let query = query.strip_suffix('*').unwrap_or(query);
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let sql = format!(
+            "SELECT {SONG_COLUMNS} FROM songs
+             WHERE id IN (SELECT song_id FROM song_tags
+                          WHERE tag IN ({FTS_TAGS}) AND value LIKE ?1 ESCAPE '\\')
+             ORDER BY path"
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut songs: Vec<Song> = stmt
+            .query_map(params![pattern], song_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.load_tags_for_songs(&mut songs)?;
+        sort_database_order(&mut songs)?;
+        Ok(songs)
+    }
+
     /// List unique values for any tag, with MPD-style fallback.
     /// Sorted with ICU root-locale collation to match MPD's IcuCollate().
     pub fn list_tag_values(&self, tag: &str) -> Result<Vec<String>> {
         let tag_lower = tag.to_lowercase();
         let chain = tag_fallback_chain(&tag_lower);
 
-        let mut values: Vec<String> = if chain.len() == 1 {
+        let name_table = match chain.as_slice() {
+            ["artist"] => Some("artists"),
+            ["album"] => Some("albums"),
+            _ => None,
+        };
+        let mut values: Vec<String> = if let Some(table) = name_table {
+            // Normalized names, already distinct and non-empty
+            let mut stmt = self.conn.prepare(&format!("SELECT name FROM {table}"))?;
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        } else if chain.len() == 1 {
             // Simple case — single tag
             let query = "SELECT DISTINCT value FROM song_tags WHERE tag = ?1";
             let mut stmt = self.conn.prepare(query)?;
@@ -929,12 +1429,13 @@ impl Database {
             values.push(String::new());
         }
         // Sort with lexicographic order to match MPD which uses std::map<std::string>
-        // (pure byte/codepoint order). Empties sort first (matching MPD's empty string behavior).
+        // (pure byte/codepoint order) unless a collation is configured. Empties sort
+        // first (matching MPD's empty string behavior).
         values.sort_by(|a, b| match (a.is_empty(), b.is_empty()) {
             (true, true) => Ordering::Equal,
             (true, false) => Ordering::Less,
             (false, true) => Ordering::Greater,
-            (false, false) => a.cmp(b),
+            (false, false) => crate::collation::compare(a, b),
         });
         Ok(values)
     }
@@ -960,8 +1461,9 @@ impl Database {
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        // MPD uses std::map<std::string> which sorts by byte order (not ICU collation).
-        values.sort();
+        // MPD uses std::map<std::string> which sorts by byte order; a
+        // configured collation overrides it.
+        values.sort_by(|a, b| crate::collation::compare(a, b));
         Ok(values)
     }
 
@@ -1009,6 +1511,7 @@ impl Database {
                 .collect::<std::result::Result<Vec<_>, _>>()?
         };
         self.load_tags_for_songs(&mut songs)?;
+        sort_database_order(&mut songs)?;
         Ok(songs)
     }
 
@@ -1026,6 +1529,7 @@ impl Database {
                 .query_map(params![pattern], song_from_row)?
                 .collect::<std::result::Result<Vec<_>, _>>()?;
             self.load_tags_for_songs(&mut songs)?;
+            sort_database_order(&mut songs)?;
             return Ok(songs);
         }
         // Use SQLite LIKE which is case-insensitive for ASCII by default.
@@ -1041,6 +1545,7 @@ impl Database {
             .query_map(params![tag_lower, pattern], song_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         self.load_tags_for_songs(&mut songs)?;
+        sort_database_order(&mut songs)?;
         Ok(songs)
     }
 
@@ -1057,6 +1562,7 @@ impl Database {
             .query_map(params![value], song_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         self.load_tags_for_songs(&mut songs)?;
+        sort_database_order(&mut songs)?;
         Ok(songs)
     }
 
@@ -1083,6 +1589,7 @@ impl Database {
             .query_map(params_refs.as_slice(), song_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         self.load_tags_for_songs(&mut songs)?;
+        sort_database_order(&mut songs)?;
         Ok(songs)
     }
 
@@ -1096,6 +1603,32 @@ impl Database {
         Ok(songs)
     }
 
+    /// Paths of all songs found by local scans (excluding remote source rows),
+    /// sorted.
+    pub fn list_local_song_paths(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM songs WHERE source IS NULL ORDER BY path")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(paths)
+    }
+
+    /// Paths of the local songs below the directory `dir`, sorted.
+    pub fn local_song_paths_under(&self, dir: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/", dir.trim_end_matches('/'));
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM songs
+             WHERE source IS NULL AND substr(path, 1, length(?1)) = ?1
+             ORDER BY path",
+        )?;
+        let paths = stmt
+            .query_map(params![prefix], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(paths)
+    }
+
     pub fn delete_song_by_path(&self, path: &str) -> Result<()> {
         self.conn.execute(
             "DELETE FROM songs WHERE path = ?1 AND source IS NULL",
@@ -1104,6 +1637,82 @@ impl Database {
         Ok(())
     }
 
+    /// Paths of local songs equal to `path` when compared case-insensitively
+    /// (ASCII case folding, SQLite's `NOCASE`), other than `path` itself.
+    pub fn song_paths_differing_in_case(&self, path: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM songs
+             WHERE path = ?1 COLLATE NOCASE AND path != ?1 AND source IS NULL",
+        )?;
+        let paths = stmt
+            .query_map(params![path], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(paths)
+    }
+
+    /// Move a local song row from `from` to `to`, keeping its id, tags,
+    /// stickers, lyrics, cached artwork and stored-playlist entries. Used when
+    /// a case-insensitive filesystem reports an indexed file under a new
+    /// spelling. Returns false when no local song is stored at `from`.
+    pub fn rename_song_path(&self, from: &str, to: &str) -> Result<bool> {
+        let root_path = Utf8PathBuf::from("/");
+        let to_path = Utf8PathBuf::from(to);
+        let dir_path = to_path.parent().unwrap_or(root_path.as_path());
+        let dir_id = self.get_or_create_directory(dir_path)?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        // lyrics/artwork reference songs(path): check the keys at commit,
+        // once both sides carry the new path.
+        tx.execute_batch("PRAGMA defer_foreign_keys = ON;")?;
+        let moved = tx.execute(
+            "UPDATE songs SET path = ?2, directory_id = ?3 WHERE path = ?1 AND source IS NULL",
+            params![from, to, dir_id],
+        )?;
+        if moved == 0 {
+            return Ok(false);
+        }
+        tx.execute(
+            "UPDATE lyrics SET song_path = ?2 WHERE song_path = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE artwork SET song_path = ?2 WHERE song_path = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE OR REPLACE stickers SET uri = ?2 WHERE uri = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE playlist_items SET uri = ?2 WHERE uri = ?1",
+            params![from, to],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Remove the local songs at `paths` whose files are gone, together with
+    /// their song stickers, in one transaction. Artwork and lyrics rows go
+    /// with the song through `ON DELETE CASCADE`. Returns the paths that were
+    /// actually removed (remote source rows and unknown paths are skipped).
+    pub fn prune_songs(&self, paths: &[String]) -> Result<Vec<String>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut removed = Vec::new();
+        {
+            let mut delete_song =
+                tx.prepare("DELETE FROM songs WHERE path = ?1 AND source IS NULL")?;
+            let mut delete_stickers = tx.prepare("DELETE FROM stickers WHERE uri = ?1")?;
+            for path in paths {
+                if delete_song.execute(params![path])? > 0 {
+                    delete_stickers.execute(params![path])?;
+                    removed.push(path.clone());
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
     /// Ensure the root directory (path="", parent_id=NULL) exists and return its id.
     /// Local scans create this automatically via `get_or_create_directory`; this
     /// method creates it on first use in a remote-only deployment.
@@ -1206,6 +1815,7 @@ impl Database {
                 replay_gain_track_peak = excluded.replay_gain_track_peak,
                 replay_gain_album_gain = excluded.replay_gain_album_gain,
                 replay_gain_album_peak = excluded.replay_gain_album_peak,
+                album_gain_derived = 0,
                 last_modified = excluded.mtime,
                 source = excluded.source
             RETURNING id",
@@ -1265,6 +1875,37 @@ impl Database {
             .optional()?)
     }
 
+    /// Up to `len` bytes of a cached picture starting at `offset`, with its
+    /// MIME type and total size. Reads through SQLite's incremental blob I/O,
+    /// so serving one chunk never loads the whole image.
+    pub fn read_artwork_chunk(
+        &self,
+        path: &str,
+        picture_type: &str,
+        offset: usize,
+        len: usize,
+    ) -> Result<Option<(Vec<u8>, String, usize)>> {
+        let row: Option<(i64, String)> = self
+            .conn
+            .query_row(
+                "SELECT id, mime_type FROM artwork WHERE song_path = ?1 AND picture_type = ?2",
+                params![path, picture_type],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((id, mime_type)) = row else {
+            return Ok(None);
+        };
+        let blob = self
+            .conn
+            .blob_open(rusqlite::MAIN_DB, "artwork", "data", id, true)?;
+        let total = blob.len();
+        let start = offset.min(total);
+        let mut data = vec![0; len.min(total - start)];
+        blob.read_at_exact(&mut data, start)?;
+        Ok(Some((data, mime_type, total)))
+    }
+
     pub fn store_artwork(
         &self,
         path: &str,
@@ -1281,6 +1922,14 @@ impl Database {
         Ok(())
     }
 
+    /// Drop every cached picture of `path`, returning how many were removed.
+    /// Called when the file changed on disk so the next request re-extracts.
+    pub fn delete_artwork(&self, path: &str) -> Result<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM artwork WHERE song_path = ?1", params![path])?)
+    }
+
     pub fn has_artwork(&self, path: &str, picture_type: &str) -> Result<bool> {
         Ok(self.conn.query_row(
             "SELECT EXISTS(SELECT 1 FROM artwork WHERE song_path = ?1 AND picture_type = ?2)",
@@ -1289,7 +1938,84 @@ impl Database {
         )?)
     }
 
-    /// List directory contents (songs + subdirectories)
+    /// SHA-256 (hex) of a cached picture, as stored by [`Self::store_artwork`].
+    pub fn get_artwork_hash(&self, path: &str, picture_type: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT hash FROM artwork WHERE song_path = ?1 AND picture_type = ?2",
+                params![path, picture_type],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    // Lyrics methods
+    pub fn get_lyrics(&self, path: &str) -> Result<Option<Lyrics>> {
+        let row: Option<(String, bool, String, i64)> = self
+            .conn
+            .query_row(
+                "SELECT text, synced, source, mtime FROM lyrics WHERE song_path = ?1",
+                params![path],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()?;
+        Ok(row.map(|(text, synced, source, mtime)| Lyrics {
+            text,
+            synced,
+            source: LyricsSource::parse(&source).unwrap_or(LyricsSource::Embedded),
+            mtime,
+        }))
+    }
+
+    pub fn store_lyrics(&self, path: &str, lyrics: &Lyrics) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO lyrics (song_path, text, synced, source, mtime)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                path,
+                lyrics.text,
+                lyrics.synced,
+                lyrics.source.as_str(),
+                lyrics.mtime
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_lyrics(&self, path: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM lyrics WHERE song_path = ?1", params![path])?;
+        Ok(())
+    }
+
+    // Playlist file methods
+    /// Replace the playlist files recorded for directory `dir` with
+    /// `playlists` (`(path, mtime)` pairs, paths relative to the music directory).
+    pub fn set_directory_playlists(&self, dir: &str, playlists: &[(String, i64)]) -> Result<()> {
+        let dir_id = if playlists.is_empty() {
+            match self.resolve_dir_id(dir)? {
+                Some(id) => id,
+                None => return Ok(()),
+            }
+        } else {
+            self.get_or_create_directory(camino::Utf8Path::new(dir))?
+        };
+
+        self.conn.execute(
+            "DELETE FROM playlist_files WHERE directory_id = ?1",
+            params![dir_id],
+        )?;
+        for (path, mtime) in playlists {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO playlist_files (path, directory_id, mtime) VALUES (?1, ?2, ?3)",
+                params![path, dir_id, mtime],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// List directory contents (songs + subdirectories + playlist files)
     pub fn list_directory(&self, path: &str) -> Result<DirectoryListing> {
         let dir_id = self.resolve_dir_id(path)?;
 
@@ -1321,6 +2047,9 @@ impl Database {
                 directories.push(row?);
             }
         }
+        if crate::collation::collator().is_some() {
+            directories.sort_by(|a, b| crate::collation::compare(&a.0, &b.0));
+        }
 
         // Get songs in this directory (no ORDER BY; sort in Rust after loading tags)
         let mut stmt = self.conn.prepare(&format!(
@@ -1332,11 +2061,33 @@ impl Database {
         self.load_tags_for_songs(&mut songs)?;
 
         // Sort to match MPD's song_cmp: Album (ICU) -> Disc -> Track -> Filename (ICU)
-        let col = CollatorBorrowed::try_new(CollatorPreferences::default(), Default::default())
-            .map_err(|e| RmpdError::Library(format!("ICU collator unavailable: {e}")))?;
-        songs.sort_by(|a, b| song_cmp(a, b, &col));
+        match crate::collation::collator() {
+            Some(col) => songs.sort_by(|a, b| song_cmp(a, b, col)),
+            None => {
+                let col =
+                    CollatorBorrowed::try_new(CollatorPreferences::default(), Default::default())
+                        .map_err(|e| RmpdError::Library(format!("ICU collator unavailable: {e}")))?;
+                songs.sort_by(|a, b| song_cmp(a, b, &col));
+            }
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT path, mtime FROM playlist_files WHERE directory_id = ?1 ORDER BY path",
+        )?;
+        let mut playlists = stmt
+            .query_map(params![dir_id.unwrap_or(0)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if crate::collation::collator().is_some() {
+            playlists.sort_by(|a, b| crate::collation::compare(&a.0, &b.0));
+        }
 
-        Ok(DirectoryListing { directories, songs })
+        Ok(DirectoryListing {
+            directories,
+            songs,
+            playlists,
+        })
     }
 
     /// List all songs under a directory recursively
@@ -1461,28 +2212,57 @@ impl Database {
 
     /// Save current queue as a playlist
     pub fn save_playlist(&self, name: &str, songs: &[Song]) -> Result<()> {
+        let uris: Vec<String> = songs.iter().map(|song| song.path.to_string()).collect();
+        self.index_playlist(name, &uris)
+    }
+
+    /// Record the stored playlist `name` as holding `uris`, in order,
+    /// replacing what was indexed for it before. Used to mirror the playlist
+    /// files of the playlist directory.
+    pub fn index_playlist(&self, name: &str, uris: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
         // Upsert the playlist row and return its (possibly new) id via RETURNING.
-        let playlist_id: i64 = self.conn.query_row(
+        let playlist_id: i64 = tx.query_row(
             "INSERT OR REPLACE INTO playlists (name, mtime) VALUES (?1, strftime('%s', 'now')) RETURNING id",
             params![name],
             |row| row.get(0),
         )?;
 
-        self.conn.execute(
+        tx.execute(
             "DELETE FROM playlist_items WHERE playlist_id = ?1",
             params![playlist_id],
         )?;
 
-        for (position, song) in songs.iter().enumerate() {
-            self.conn.execute(
-                "INSERT INTO playlist_items (playlist_id, position, song_id, uri) VALUES (?1, ?2, ?3, ?4)",
-                params![playlist_id, position as i64, song.id as i64, song.path.as_str()],
+        // Link by path rather than trusting song.id: queue entries may not be
+        // library songs, and the URI is what the playlist really refers to.
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO playlist_items (playlist_id, position, song_id, uri)
+                 VALUES (?1, ?2, (SELECT id FROM songs WHERE path = ?3), ?3)",
             )?;
+            for (position, uri) in uris.iter().enumerate() {
+                stmt.execute(params![playlist_id, position as i64, uri])?;
+            }
         }
 
+        tx.commit()?;
         Ok(())
     }
 
+    /// Point every stored playlist item at the current id of the song with
+    /// its URI (or NULL when no such song exists).
+    ///
+    /// Run on open to repair databases written before items were re-linked
+    /// automatically; returns the number of items whose link changed.
+    pub fn relink_playlist_items(&self) -> Result<usize> {
+        Ok(self.conn.execute(
+            "UPDATE playlist_items
+             SET song_id = (SELECT id FROM songs WHERE path = playlist_items.uri)
+             WHERE song_id IS NOT (SELECT id FROM songs WHERE path = playlist_items.uri)",
+            [],
+        )?)
+    }
+
     /// Load playlist and return songs
     pub fn load_playlist(&self, name: &str) -> Result<Vec<Song>> {
         let playlist_id = get_playlist_id(&self.conn, name)?;
@@ -1490,7 +2270,7 @@ impl Database {
         let sql = format!(
             "SELECT {SONG_COLUMNS_ALIASED}
              FROM playlist_items pi
-             JOIN songs s ON pi.song_id = s.id
+             JOIN songs s ON s.path = pi.uri
              WHERE pi.playlist_id = ?1
              ORDER BY pi.position"
         );
@@ -1675,6 +2455,101 @@ impl Database {
         Ok(())
     }
 
+    // Queue snapshot methods
+
+    /// Save `uris` as the queue snapshot `name`, with `current` the queue
+    /// position of the current song and `elapsed` the seconds played into
+    /// it, replacing any snapshot of that name.
+    pub fn save_queue_snapshot(
+        &self,
+        name: &str,
+        uris: &[String],
+        current: Option<u32>,
+        elapsed: f64,
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM queue_snapshots WHERE name = ?1", params![name])?;
+        let snapshot_id: i64 = tx.query_row(
+            "INSERT INTO queue_snapshots (name, current, elapsed) VALUES (?1, ?2, ?3) RETURNING id",
+            params![name, current, elapsed],
+            |row| row.get(0),
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO queue_snapshot_items (snapshot_id, position, uri) VALUES (?1, ?2, ?3)",
+            )?;
+            for (position, uri) in uris.iter().enumerate() {
+                stmt.execute(params![snapshot_id, position as i64, uri])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The queue snapshot `name`, if one was saved.
+    pub fn get_queue_snapshot(&self, name: &str) -> Result<Option<QueueSnapshot>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT id, current, elapsed, mtime FROM queue_snapshots WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<u32>>(1)?,
+                        row.get::<_, f64>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((id, current, elapsed, last_modified)) = row else {
+            return Ok(None);
+        };
+        let mut stmt = self.conn.prepare(
+            "SELECT uri FROM queue_snapshot_items WHERE snapshot_id = ?1 ORDER BY position",
+        )?;
+        let uris = stmt
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(Some(QueueSnapshot {
+            name: name.to_owned(),
+            last_modified,
+            uris,
+            current,
+            elapsed,
+        }))
+    }
+
+    /// All queue snapshots, by name.
+    pub fn list_queue_snapshots(&self) -> Result<Vec<QueueSnapshot>> {
+        let names = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT name FROM queue_snapshots ORDER BY name")?;
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<Vec<String>, _>>()?
+        };
+        let mut snapshots = Vec::with_capacity(names.len());
+        for name in names {
+            snapshots.extend(self.get_queue_snapshot(&name)?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Delete the queue snapshot `name`.
+    pub fn delete_queue_snapshot(&self, name: &str) -> Result<()> {
+        let affected = self
+            .conn
+            .execute("DELETE FROM queue_snapshots WHERE name = ?1", params![name])?;
+        if affected == 0 {
+            return Err(RmpdError::Library(format!(
+                "Queue snapshot not found: {name}"
+            )));
+        }
+        Ok(())
+    }
+
     // Sticker methods
 
     pub fn get_sticker(&self, uri: &str, name: &str) -> Result<Option<String>> {
@@ -1743,6 +2618,30 @@ impl Database {
 
         Ok(results)
     }
+
+    /// Distinct sticker names across the whole database, sorted.
+    pub fn sticker_names(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT name FROM stickers ORDER BY name")?;
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(names)
+    }
+
+    /// Distinct `(name, type)` sticker pairs, optionally restricted to one
+    /// sticker type. Only song stickers are stored, so the type is `song`.
+    pub fn sticker_names_types(&self, sticker_type: Option<&str>) -> Result<Vec<(String, String)>> {
+        if sticker_type.is_some_and(|t| t != "song") {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .sticker_names()?
+            .into_iter()
+            .map(|name| (name, "song".to_string()))
+            .collect())
+    }
 }
 
 /// Directory listing result
@@ -1750,6 +2649,42 @@ impl Database {
 pub struct DirectoryListing {
     pub directories: Vec<(String, i64)>,
     pub songs: Vec<Song>,
+    /// Playlist files in the directory, as `(path, mtime)`
+    pub playlists: Vec<(String, i64)>,
+}
+
+/// Song count and playtime of one group in a [`FormatReport`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FormatGroup {
+    pub songs: u32,
+    /// Seconds.
+    pub playtime: f64,
+}
+
+/// Library composition returned by [`Database::format_report`]. Groups are
+/// sorted by key; songs with an unknown sample rate or bit depth are left out
+/// of those groupings.
+#[derive(Debug, Default)]
+pub struct FormatReport {
+    pub total: FormatGroup,
+    /// Total size of the files in bytes.
+    pub size: u64,
+    pub lossless: FormatGroup,
+    pub lossy: FormatGroup,
+    /// By lowercase file extension.
+    pub formats: Vec<(String, FormatGroup)>,
+    pub sample_rates: Vec<(u32, FormatGroup)>,
+    pub bit_depths: Vec<(u16, FormatGroup)>,
+}
+
+/// Whether a song is losslessly encoded, judged by its extension. MP4
+/// containers hold either AAC or ALAC; only the latter reports a bit depth.
+fn is_lossless(extension: &str, bits_per_sample: Option<u16>) -> bool {
+    match extension {
+        "flac" | "wav" | "aif" | "aiff" | "ape" | "wv" | "tta" | "dsf" | "dff" => true,
+        "m4a" | "mp4" => bits_per_sample.is_some(),
+        _ => false,
+    }
 }
 
 /// Playlist information
@@ -1759,3 +2694,15 @@ pub struct PlaylistInfo {
     pub last_modified: i64,
     pub song_count: u32,
 }
+
+/// A saved queue and where playback stood in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueSnapshot {
+    pub name: String,
+    pub last_modified: i64,
+    pub uris: Vec<String>,
+    /// Queue position of the song that was current, if any
+    pub current: Option<u32>,
+    /// Seconds played into the current song
+    pub elapsed: f64,
+}
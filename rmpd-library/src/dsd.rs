@@ -0,0 +1,299 @@
+//! DSD container parsing (DSF and DSDIFF).
+//!
+//! lofty's audio properties do not describe 1-bit audio, so the scanner reads
+//! the real DSD rate (DSD64 = 2.8224 MHz, DSD128, DSD256, ...) and channel
+//! layout straight from the container. Both containers keep their tags in a
+//! raw ID3v2 blob — pointed to from the DSF header, or stored in an `ID3 `
+//! chunk of a DSDIFF file — which is also exposed here for
+//! [`crate::rawtag`] to read when lofty cannot open the file.
+
+use rmpd_core::error::{Result, RmpdError};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Base rate of the DSD family: DSD64 is 64 × 44.1 kHz.
+const DSD_BASE_RATE: u32 = 44_100;
+
+/// Upper bound for an embedded ID3v2 blob (covers included).
+const MAX_ID3_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Audio properties and raw tag of a DSF or DSDIFF file.
+#[derive(Debug, Clone)]
+pub struct DsdInfo {
+    /// 1-bit sample rate in Hz (2 822 400 for DSD64).
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub samples_per_channel: u64,
+    /// Raw ID3v2 tag, if the file carries one.
+    pub id3: Option<Vec<u8>>,
+}
+
+impl DsdInfo {
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.samples_per_channel as f64 / f64::from(self.sample_rate))
+    }
+
+    /// The `N` of "DSD`N`" (64, 128, 256, ...).
+    pub fn rate_multiple(&self) -> u32 {
+        self.sample_rate / DSD_BASE_RATE
+    }
+
+    /// Bitrate in kbit/s (one bit per sample per channel).
+    pub fn bitrate(&self) -> u32 {
+        self.sample_rate / 1000 * u32::from(self.channels)
+    }
+}
+
+/// Whether `path` has a DSD container extension (`.dsf` / `.dff`).
+pub fn is_dsd_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("dsf") || e.eq_ignore_ascii_case("dff"))
+}
+
+/// Read the audio properties and ID3v2 tag of a DSF or DSDIFF file.
+pub fn read_dsd_info(path: &Path) -> Result<DsdInfo> {
+    let file =
+        File::open(path).map_err(|e| RmpdError::Library(format!("Failed to open file: {e}")))?;
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    let io = |e: std::io::Error| RmpdError::Library(format!("Failed to read DSD file: {e}"));
+    reader.read_exact(&mut magic).map_err(io)?;
+    let info = match &magic {
+        b"DSD " => read_dsf(&mut reader).map_err(io)?,
+        b"FRM8" => read_dff(&mut reader).map_err(io)?,
+        _ => None,
+    };
+    info.ok_or_else(|| RmpdError::Library(format!("Not a DSD file: {}", path.display())))
+}
+
+fn u32_le(b: &[u8]) -> u32 {
+    u32::from_le_bytes(b[..4].try_into().expect("4 bytes"))
+}
+
+fn u64_le(b: &[u8]) -> u64 {
+    u64::from_le_bytes(b[..8].try_into().expect("8 bytes"))
+}
+
+fn u64_be(b: &[u8]) -> u64 {
+    u64::from_be_bytes(b[..8].try_into().expect("8 bytes"))
+}
+
+fn read_id3<R: Read>(reader: R, size: u64) -> std::io::Result<Option<Vec<u8>>> {
+    let mut tag = Vec::new();
+    reader.take(size.min(MAX_ID3_SIZE)).read_to_end(&mut tag)?;
+    Ok((!tag.is_empty()).then_some(tag))
+}
+
+/// DSF: `DSD ` chunk (size, file size, metadata pointer) followed by the
+/// `fmt ` chunk. The metadata pointer locates the trailing ID3v2 tag.
+fn read_dsf<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<DsdInfo>> {
+    let mut header = [0u8; 24];
+    reader.read_exact(&mut header)?;
+    let metadata_pointer = u64_le(&header[16..]);
+
+    let mut fmt = [0u8; 52];
+    reader.read_exact(&mut fmt)?;
+    if &fmt[..4] != b"fmt " {
+        return Ok(None);
+    }
+    let channels = u32_le(&fmt[24..]);
+    let sample_rate = u32_le(&fmt[28..]);
+    let samples_per_channel = u64_le(&fmt[36..]);
+
+    let id3 = if metadata_pointer == 0 {
+        None
+    } else {
+        reader.seek(SeekFrom::Start(metadata_pointer))?;
+        read_id3(reader, MAX_ID3_SIZE)?
+    };
+
+    Ok(Some(DsdInfo {
+        sample_rate,
+        channels: u8::try_from(channels).unwrap_or(0),
+        samples_per_channel,
+        id3,
+    }))
+}
+
+/// DSDIFF: an `FRM8` form of type `DSD ` holding `PROP` (sample rate and
+/// channels), the sound data (`DSD ` or DST-compressed `DST `) and the
+/// (unofficial but widely written) `ID3 ` chunk. All sizes are big-endian and
+/// chunks are padded to an even length.
+fn read_dff<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<DsdInfo>> {
+    let mut form = [0u8; 12];
+    reader.read_exact(&mut form)?;
+    if &form[8..] != b"DSD " {
+        return Ok(None);
+    }
+
+    let mut sample_rate = 0;
+    let mut channels = 0u8;
+    let mut data_bytes = None;
+    let mut dst_frames = None;
+    let mut id3 = None;
+
+    let mut header = [0u8; 12];
+    while reader.read_exact(&mut header).is_ok() {
+        let size = u64_be(&header[4..]);
+        let next = reader
+            .stream_position()?
+            .checked_add(size)
+            .and_then(|n| n.checked_add(size & 1))
+            .ok_or_else(|| oversized_chunk(&header[..4]))?;
+        match &header[..4] {
+            b"PROP" => {
+                let mut prop = vec![0u8; usize::try_from(size.min(64 * 1024)).unwrap_or(0)];
+                reader.read_exact(&mut prop)?;
+                if prop.starts_with(b"SND ") {
+                    let mut pos = 4;
+                    while pos + 12 <= prop.len() {
+                        let end = usize::try_from(u64_be(&prop[pos + 4..]))
+                            .ok()
+                            .and_then(|sub_size| sub_size.checked_add(pos + 12))
+                            .ok_or_else(|| oversized_chunk(&prop[pos..pos + 4]))?;
+                        let body = &prop[pos + 12..end.min(prop.len())];
+                        match &prop[pos..pos + 4] {
+                            b"FS  " if body.len() >= 4 => {
+                                sample_rate = u32::from_be_bytes(body[..4].try_into().expect("4"));
+                            }
+                            b"CHNL" if body.len() >= 2 => {
+                                channels = u8::try_from(u16::from_be_bytes([body[0], body[1]]))
+                                    .unwrap_or(0);
+                            }
+                            _ => {}
+                        }
+                        // Odd-sized chunks are padded to an even length.
+                        pos = end
+                            .checked_add((end - pos) & 1)
+                            .ok_or_else(|| oversized_chunk(&prop[pos..pos + 4]))?;
+                    }
+                }
+            }
+            b"DSD " => data_bytes = Some(size),
+            b"DST " => {
+                // First sub-chunk is FRTE: frame count (u32) and rate (u16).
+                let mut frte = [0u8; 18];
+                if reader.read_exact(&mut frte).is_ok() && &frte[..4] == b"FRTE" {
+                    let frames = u32::from_be_bytes(frte[12..16].try_into().expect("4"));
+                    let rate = u16::from_be_bytes([frte[16], frte[17]]);
+                    dst_frames = Some((u64::from(frames), u64::from(rate)));
+                }
+            }
+            b"ID3 " => id3 = read_id3(&mut *reader, size)?,
+            _ => {}
+        }
+        reader.seek(SeekFrom::Start(next))?;
+    }
+
+    if sample_rate == 0 || channels == 0 {
+        return Ok(None);
+    }
+    let samples_per_channel = match (data_bytes, dst_frames) {
+        (Some(bytes), _) => bytes.saturating_mul(8) / u64::from(channels),
+        (None, Some((frames, rate))) if rate > 0 => frames * u64::from(sample_rate) / rate,
+        _ => 0,
+    };
+    Ok(Some(DsdInfo {
+        sample_rate,
+        channels,
+        samples_per_channel,
+        id3,
+    }))
+}
+
+/// A chunk whose declared size runs past the end of any possible file.
+fn oversized_chunk(id: &[u8]) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!(
+            "DSDIFF chunk {:?} is oversized",
+            String::from_utf8_lossy(id)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dsd_rate_multiple_and_duration() {
+        let info = DsdInfo {
+            sample_rate: 11_289_600,
+            channels: 2,
+            samples_per_channel: 11_289_600 * 3,
+            id3: None,
+        };
+        assert_eq!(info.rate_multiple(), 256);
+        assert_eq!(info.duration(), Duration::from_secs(3));
+        assert_eq!(info.bitrate(), 22_578);
+    }
+
+    /// A DSDIFF form (without its `FRM8` magic) holding `chunks`.
+    fn dff(chunks: &[(&[u8; 4], u64, &[u8])]) -> std::io::Cursor<Vec<u8>> {
+        let mut bytes = 0u64.to_be_bytes().to_vec();
+        bytes.extend_from_slice(b"DSD ");
+        for (id, size, body) in chunks {
+            bytes.extend_from_slice(*id);
+            bytes.extend_from_slice(&size.to_be_bytes());
+            bytes.extend_from_slice(body);
+        }
+        std::io::Cursor::new(bytes)
+    }
+
+    fn prop(sub_chunks: &[(&[u8; 4], u64, &[u8])]) -> Vec<u8> {
+        let mut prop = b"SND ".to_vec();
+        for (id, size, body) in sub_chunks {
+            prop.extend_from_slice(*id);
+            prop.extend_from_slice(&size.to_be_bytes());
+            prop.extend_from_slice(body);
+        }
+        prop
+    }
+
+    #[test]
+    fn dff_reads_rate_channels_and_length() {
+        let prop = prop(&[
+            (b"FS  ", 4, &2_822_400u32.to_be_bytes()),
+            (b"CHNL", 2, &2u16.to_be_bytes()),
+        ]);
+        let mut reader = dff(&[(b"PROP", prop.len() as u64, &prop), (b"DSD ", 4, &[0; 4])]);
+        let info = read_dff(&mut reader).unwrap().expect("a DSDIFF file");
+        assert_eq!(info.sample_rate, 2_822_400);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.samples_per_channel, 16);
+    }
+
+    #[test]
+    fn dff_oversized_chunks_are_rejected() {
+        let mut reader = dff(&[(b"DSD ", u64::MAX, &[])]);
+        let err = read_dff(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let prop = prop(&[(b"FS  ", u64::MAX - 8, &2_822_400u32.to_be_bytes())]);
+        let mut reader = dff(&[(b"PROP", prop.len() as u64, &prop)]);
+        let err = read_dff(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn dff_truncated_chunks_end_the_walk() {
+        // A sound chunk claiming more data than the file holds, and a
+        // property chunk cut off mid-header.
+        let prop = prop(&[(b"FS  ", 4, &2_822_400u32.to_be_bytes())]);
+        let mut reader = dff(&[
+            (b"PROP", prop.len() as u64, &prop),
+            (b"DSD ", 1 << 40, &[0; 4]),
+        ]);
+        assert!(read_dff(&mut reader).unwrap().is_none(), "no channel count");
+
+        let mut reader = dff(&[(b"PROP", 64, b"SND FS  \0\0")]);
+        assert!(read_dff(&mut reader).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use rmpd_core::error::{Result, RmpdError};
-use rmpd_player::SymphoniaDecoder;
+use rmpd_player::open_decoder;
 use std::path::Path;
 use std::sync::Mutex;
 
@@ -50,8 +50,8 @@ impl Fingerprinter {
     /// Returns a base64-encoded fingerprint string compatible with AcoustID.
     /// Only processes the first 120 seconds of audio as recommended by Chromaprint.
     pub fn fingerprint_file(&mut self, path: &Path) -> Result<String> {
-        // Open audio file with Symphonia decoder
-        let mut decoder = SymphoniaDecoder::open(path)?;
+        // Open audio file with the first decoder that accepts it
+        let mut decoder = open_decoder(path)?;
 
         // Get audio format info
         let sample_rate = decoder.sample_rate();
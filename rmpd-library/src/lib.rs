@@ -2,17 +2,32 @@
 
 // Music library and database
 pub mod artwork;
+pub mod collation;
 pub mod cue;
 pub mod database;
+pub mod dsd;
 pub mod fingerprint;
+pub mod lyrics;
 pub mod metadata;
+mod rawtag;
 pub mod scanner;
+pub mod store;
+pub mod tag_rules;
+pub mod tag_writer;
+pub mod tta;
+pub mod update_lock;
 pub mod watcher;
 
 pub use artwork::{AlbumArtExtractor, ArtworkData};
 pub use cue::{CueTrack, parse_cue};
-pub use database::{Database, DbPool, DirectoryListing, PlaylistInfo, WalkEntry};
+pub use database::{
+    Database, DbPool, DirectoryListing, FormatGroup, FormatReport, PlaylistInfo, QueueSnapshot,
+    WalkEntry,
+};
 pub use fingerprint::Fingerprinter;
+pub use lyrics::{Lyrics, LyricsSource};
 pub use metadata::{Artwork, MetadataExtractor};
-pub use scanner::{ScanStats, Scanner};
+pub use scanner::{ScanReport, ScanStats, Scanner};
+pub use store::{LibraryBackend, LibraryStore};
+pub use tag_rules::TagRewriter;
 pub use watcher::FilesystemWatcher;
@@ -0,0 +1,201 @@
+//! Lyrics discovery: embedded lyrics tags and `.lrc` sidecar files.
+//!
+//! Embedded lyrics come from ID3v2 `USLT`, Vorbis `LYRICS`/`UNSYNCEDLYRICS`,
+//! MP4 `©lyr` and APEv2 `Lyrics` (DSDIFF and TTA through [`crate::rawtag`]).
+//! A sidecar next to the audio file with the same stem (`song.lrc`) wins over
+//! embedded text, since users drop those in deliberately. Lyrics count as
+//! synced when they carry LRC `[mm:ss.xx]` timestamps.
+
+use crate::{dsd, rawtag, tta};
+use lofty::prelude::*;
+use lofty::tag::ItemKey;
+use rmpd_core::time::system_time_to_unix_secs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where a song's lyrics were found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricsSource {
+    Embedded,
+    Sidecar,
+}
+
+impl LyricsSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Embedded => "embedded",
+            Self::Sidecar => "sidecar",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "embedded" => Some(Self::Embedded),
+            "sidecar" => Some(Self::Sidecar),
+            _ => None,
+        }
+    }
+}
+
+/// Lyrics of one song.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lyrics {
+    /// Lyrics text with `\n` line endings; LRC timestamps are kept as-is.
+    pub text: String,
+    pub synced: bool,
+    pub source: LyricsSource,
+    /// Modification time of the file the lyrics were read from.
+    pub mtime: i64,
+}
+
+/// The `.lrc` sidecar of `audio`, if one exists.
+pub fn sidecar_path(audio: &Path) -> Option<PathBuf> {
+    ["lrc", "LRC"]
+        .iter()
+        .map(|ext| audio.with_extension(ext))
+        .find(|p| p.is_file())
+}
+
+/// Modification time of the `.lrc` sidecar of `audio`, if one exists.
+pub fn sidecar_mtime(audio: &Path) -> Option<i64> {
+    let meta = fs::metadata(sidecar_path(audio)?).ok()?;
+    Some(system_time_to_unix_secs(
+        meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+    ))
+}
+
+/// Lyrics for the audio file at `path`: the sidecar if present, otherwise
+/// the embedded lyrics tag.
+pub fn read_lyrics(path: &Path) -> Option<Lyrics> {
+    let mtime_of = |p: &Path| {
+        fs::metadata(p)
+            .and_then(|m| m.modified())
+            .map(system_time_to_unix_secs)
+            .unwrap_or(0)
+    };
+
+    if let Some(sidecar) = sidecar_path(path)
+        && let Ok(bytes) = fs::read(&sidecar)
+        && let Some(text) = normalize(&String::from_utf8_lossy(&bytes))
+    {
+        return Some(Lyrics {
+            synced: is_synced(&text),
+            text,
+            source: LyricsSource::Sidecar,
+            mtime: mtime_of(&sidecar),
+        });
+    }
+
+    let text = normalize(&embedded_lyrics(path)?)?;
+    Some(Lyrics {
+        synced: is_synced(&text),
+        text,
+        source: LyricsSource::Embedded,
+        mtime: mtime_of(path),
+    })
+}
+
+/// Lyrics stored in the file's own tags.
+fn embedded_lyrics(path: &Path) -> Option<String> {
+    // lofty opens neither TTA nor (every) DSDIFF file: read the raw tags.
+    if tta::is_tta_path(path) {
+        if let Ok(Some(tags)) = rawtag::read_apev2(path)
+            && tags.lyrics.is_some()
+        {
+            return tags.lyrics;
+        }
+        let id3 = tta::read_tta_info(path).ok()?.id3?;
+        return rawtag::id3v2_tags(&id3).lyrics;
+    }
+
+    let tagged_file = match lofty::read_from_path(path) {
+        Ok(f) => f,
+        Err(_) if dsd::is_dsd_path(path) => {
+            let id3 = dsd::read_dsd_info(path).ok()?.id3?;
+            return rawtag::id3v2_tags(&id3).lyrics;
+        }
+        Err(_) => return None,
+    };
+
+    let primary = tagged_file.primary_tag();
+    primary
+        .into_iter()
+        .chain(
+            tagged_file
+                .tags()
+                .iter()
+                .filter(|t| primary.is_none_or(|p| p.tag_type() != t.tag_type())),
+        )
+        .find_map(|tag| {
+            tag.get_string(ItemKey::Lyrics)
+                .or_else(|| {
+                    ItemKey::from_key(tag.tag_type(), "UNSYNCEDLYRICS")
+                        .and_then(|key| tag.get_string(key))
+                })
+                .map(str::to_owned)
+        })
+}
+
+/// Strip a BOM, unify line endings and trim; `None` when nothing is left.
+fn normalize(text: &str) -> Option<String> {
+    let text = text
+        .trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .replace('\r', "\n");
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// Whether any line starts with an LRC time tag (`[mm:ss]`, `[mm:ss.xx]`).
+pub fn is_synced(text: &str) -> bool {
+    text.lines().any(|line| {
+        let Some(rest) = line.trim_start().strip_prefix('[') else {
+            return false;
+        };
+        let Some((stamp, _)) = rest.split_once(']') else {
+            return false;
+        };
+        let Some((min, sec)) = stamp.split_once(':') else {
+            return false;
+        };
+        !min.is_empty()
+            && min.bytes().all(|b| b.is_ascii_digit())
+            && sec.split_once(['.', ':']).map_or(sec, |(s, _)| s).len() == 2
+            && sec
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b == b'.' || b == b':')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_lrc_time_tags() {
+        assert!(is_synced("[ar:Artist]\n[00:12.34]Line\n[01:02]Next"));
+        assert!(is_synced("[123:45.6]Long song"));
+        assert!(!is_synced("[ar:Artist]\nPlain line"));
+        assert!(!is_synced("Verse [1:2]"));
+    }
+
+    #[test]
+    fn sidecar_wins_over_missing_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio = dir.path().join("song.flac");
+        fs::write(&audio, b"not really flac").unwrap();
+        assert_eq!(read_lyrics(&audio), None);
+
+        fs::write(
+            dir.path().join("song.lrc"),
+            "\u{feff}[00:01.00]One\r\n[00:02.00]Two\r\n",
+        )
+        .unwrap();
+        let lyrics = read_lyrics(&audio).unwrap();
+        assert_eq!(lyrics.text, "[00:01.00]One\n[00:02.00]Two");
+        assert!(lyrics.synced);
+        assert_eq!(lyrics.source, LyricsSource::Sidecar);
+        assert_eq!(sidecar_mtime(&audio), Some(lyrics.mtime));
+    }
+}
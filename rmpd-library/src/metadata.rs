@@ -1,4 +1,7 @@
-use crate::artwork::picture_type_to_string;
+use crate::artwork::{embedded_pictures, infer_mime, picture_type_to_string};
+use crate::dsd::{self, DsdInfo};
+use crate::rawtag::{self, RawTags};
+use crate::tta;
 use camino::Utf8PathBuf;
 use lofty::config::ParseOptions;
 use lofty::flac::FlacFile;
@@ -14,7 +17,7 @@ use rmpd_core::tag::{normalize_decimal, vorbis_tag_map_get};
 use rmpd_core::time::system_time_to_unix_secs;
 use std::fs;
 use std::io::BufReader;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 /// Collect Vorbis comment key/value pairs into owned `(key, value)` tuples.
 fn collect_vorbis_pairs(comments: &lofty::ogg::VorbisComments) -> Vec<(String, String)> {
@@ -29,16 +32,60 @@ fn is_bogus_dsf_comment(s: &str) -> bool {
     trimmed.len() >= 16 && trimmed.chars().all(|c| c.is_ascii_hexdigit() || c == ' ')
 }
 
-const ITEM_KEY_TAG_MAP: &[(ItemKey, &str)] = &[
+/// ReplayGain values: (track gain, track peak, album gain, album peak).
+type ReplayGain = (Option<f32>, Option<f32>, Option<f32>, Option<f32>);
+
+/// Fill a ReplayGain value still missing from `rg` from a `REPLAYGAIN_*`
+/// key. The key is matched case-insensitively: foobar2000 writes the MP4
+/// freeform atoms in lower case.
+fn fill_replay_gain(rg: &mut ReplayGain, key: &str, value: &str) {
+    let value = value.trim();
+    let gain = || value.trim_end_matches("dB").trim().parse::<f32>().ok();
+    let (slot, parsed) = match key.to_ascii_lowercase().as_str() {
+        "replaygain_track_gain" => (&mut rg.0, gain()),
+        "replaygain_track_peak" => (&mut rg.1, value.parse().ok()),
+        "replaygain_album_gain" => (&mut rg.2, gain()),
+        "replaygain_album_peak" => (&mut rg.3, value.parse().ok()),
+        _ => return,
+    };
+    if slot.is_none() {
+        *slot = parsed;
+    }
+}
+
+/// ReplayGain of an Opus `R128_TRACK_GAIN`/`R128_ALBUM_GAIN` comment
+/// (RFC 7845): a Q7.8 dB value relative to -23 LUFS, moved to ReplayGain's
+/// -18 LUFS reference.
+fn r128_gain(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse::<i16>()
+        .ok()
+        .map(|q| f32::from(q) / 256.0 + 5.0)
+}
+
+/// Original sample count of an iTunes gapless comment: `iTunSMPB` holds
+/// hex fields ` 00000000 <delay> <padding> <samples> ...`.
+fn itunsmpb_samples(value: &str) -> Option<u64> {
+    let samples = value.split_whitespace().nth(3)?;
+    u64::from_str_radix(samples, 16).ok().filter(|&n| n > 0)
+}
+
+pub(crate) const ITEM_KEY_TAG_MAP: &[(ItemKey, &str)] = &[
     (ItemKey::TrackTitle, "title"),
     (ItemKey::TrackArtist, "artist"),
     (ItemKey::AlbumTitle, "album"),
     (ItemKey::AlbumArtist, "albumartist"),
     (ItemKey::TrackNumber, "track"),
     (ItemKey::DiscNumber, "disc"),
+    (ItemKey::DiscTotal, "disctotal"),
     (ItemKey::Genre, "genre"),
     (ItemKey::Composer, "composer"),
     (ItemKey::Performer, "performer"),
+    (ItemKey::Conductor, "conductor"),
+    (ItemKey::Work, "work"),
+    (ItemKey::Movement, "movement"),
+    (ItemKey::MovementNumber, "movementnumber"),
     (ItemKey::Comment, "comment"),
     (ItemKey::ContentGroup, "grouping"),
     (ItemKey::Label, "label"),
@@ -60,6 +107,37 @@ const ITEM_KEY_TAG_MAP: &[(ItemKey, &str)] = &[
     (ItemKey::MusicBrainzWorkId, "musicbrainz_workid"),
 ];
 
+/// A song carrying only the tags read by [`rawtag`]; audio properties are
+/// filled in by the caller.
+fn song_from_raw_tags(path: &Utf8PathBuf, tags: RawTags, mtime: i64) -> Song {
+    Song {
+        id: 0,
+        path: path.clone(),
+        duration: None,
+        sample_rate: None,
+        channels: None,
+        bits_per_sample: None,
+        bitrate: None,
+        replay_gain_track_gain: tags.replay_gain_track_gain,
+        replay_gain_track_peak: tags.replay_gain_track_peak,
+        replay_gain_album_gain: tags.replay_gain_album_gain,
+        replay_gain_album_peak: tags.replay_gain_album_peak,
+        added_at: mtime,
+        last_modified: mtime,
+        tags: tags.tags,
+    }
+}
+
+/// Report a DSD file as MPD does: the 1-bit rate (e.g. 2822400 for DSD64)
+/// with a sample size of 1, which the protocol layer prints as `dsd64:2`.
+fn apply_dsd_properties(song: &mut Song, info: &DsdInfo) {
+    song.sample_rate = Some(info.sample_rate);
+    song.channels = Some(info.channels);
+    song.bits_per_sample = Some(1);
+    song.duration = Some(info.duration());
+    song.bitrate = Some(info.bitrate());
+}
+
 #[derive(Debug, Clone)]
 pub struct Artwork {
     pub picture_type: String,
@@ -77,15 +155,40 @@ impl MetadataExtractor {
 
         let mtime = system_time_to_unix_secs(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
 
-        let tagged_file = Probe::open(path.as_str())
-            .map_err(|e| RmpdError::Library(format!("Failed to open file: {e}")))?
-            .guess_file_type()
-            .map_err(|e| RmpdError::Library(format!("Failed to detect file type: {e}")))?
-            .read()
-            .map_err(|e| RmpdError::Library(format!("Failed to read file: {e}")))?;
+        // lofty has no TTA support: read the header and tags directly.
+        if tta::is_tta_path(path.as_std_path()) {
+            return Self::song_from_tta(path, mtime, metadata.len());
+        }
+
+        // DSD containers: audio properties come from the container itself
+        // (lofty has no notion of 1-bit audio).
+        let dsd = dsd::is_dsd_path(path.as_std_path())
+            .then(|| dsd::read_dsd_info(path.as_std_path()))
+            .and_then(|r| {
+                r.map_err(|e| tracing::debug!("{path}: {e}; falling back to lofty"))
+                    .ok()
+            });
+
+        let probed = Probe::open(path.as_str())
+            .map_err(|e| RmpdError::Library(format!("Failed to open file: {e}")))
+            .and_then(|p| {
+                p.guess_file_type()
+                    .map_err(|e| RmpdError::Library(format!("Failed to detect file type: {e}")))
+            })
+            .and_then(|p| {
+                p.read()
+                    .map_err(|e| RmpdError::Library(format!("Failed to read file: {e}")))
+            });
+        let (tagged_file, dsd) = match (probed, dsd) {
+            (Ok(f), dsd) => (f, dsd),
+            // lofty cannot open every DSD file (notably DSDIFF); the container
+            // and its ID3v2 chunk are enough to index it.
+            (Err(_), Some(info)) => return Ok(Self::song_from_dsd(path, &info, mtime)),
+            (Err(e), None) => return Err(e),
+        };
 
         let properties = tagged_file.properties();
-        let duration = Some(properties.duration());
+        let mut duration = Some(properties.duration());
         let sample_rate = properties.sample_rate();
         let channels = properties.channels();
         let bitrate = properties.audio_bitrate();
@@ -96,6 +199,10 @@ impl MetadataExtractor {
 
         tracing::debug!("extracting metadata from: {}", path);
         let mut tags: Vec<(std::borrow::Cow<'static, str>, String)> = Vec::new();
+        // Disc total from a "1/2" disc number, used when no total tag is set
+        let mut disc_total: Option<String> = None;
+        let mut r128_track_gain = None;
+        let mut r128_album_gain = None;
 
         // For VorbisComment-based formats (FLAC/OGG/Opus), use raw key extraction
         // with MPD's canonical key mapping to avoid lofty mapping non-standard key
@@ -164,9 +271,23 @@ impl MetadataExtractor {
                     continue;
                 }
                 let key_lower = raw_key.to_lowercase();
+                if tagged_file.file_type() == lofty::file::FileType::Opus {
+                    match key_lower.as_str() {
+                        "r128_track_gain" => r128_track_gain = r128_gain(&val),
+                        "r128_album_gain" => r128_album_gain = r128_gain(&val),
+                        _ => {}
+                    }
+                }
                 if let Some(tag_name) = vorbis_tag_map_get(&key_lower) {
+                    // A "1/2" disc number carries the disc total
+                    if tag_name == "disc"
+                        && let Some((_, total)) = val.split_once('/')
+                        && let Some(total) = normalize_decimal(total)
+                    {
+                        disc_total.get_or_insert(total);
+                    }
                     // Normalize Track/Disc: strip leading zeros, preserve zero values
-                    let effective_val = if tag_name == "track" || tag_name == "disc" {
+                    let effective_val = if matches!(tag_name, "track" | "disc" | "disctotal") {
                         match normalize_decimal(&val) {
                             Some(v) => v,
                             None => continue,
@@ -196,7 +317,7 @@ impl MetadataExtractor {
                             continue;
                         }
                         // Normalize Track/Disc: strip leading zeros, preserve zero values
-                        if *tag_name == "track" || *tag_name == "disc" {
+                        if matches!(*tag_name, "track" | "disc" | "disctotal") {
                             if let Some(normalized) = normalize_decimal(val) {
                                 tags.push((intern_tag_key(tag_name), normalized));
                             }
@@ -254,6 +375,18 @@ impl MetadataExtractor {
             {
                 tags.push((intern_tag_key("disc"), norm));
             }
+            disc_total = tag
+                .disk_total()
+                .and_then(|n| normalize_decimal(&n.to_string()));
+
+            // Ensemble has no lofty key; read it as a custom field (TXXX in ID3)
+            if let Some(key) = ItemKey::from_key(tag.tag_type(), "ENSEMBLE")
+                && let Some(val) = tag.get_string(key)
+                && !val.is_empty()
+                && !tags.iter().any(|(k, _)| k.as_ref() == "ensemble")
+            {
+                tags.push((intern_tag_key("ensemble"), val.to_string()));
+            }
 
             // MixRamp analysis tags (TXXX frames in ID3 / equivalent in other formats)
             if let Some(key) = ItemKey::from_key(tag.tag_type(), "MIXRAMP_START")
@@ -269,8 +402,13 @@ impl MetadataExtractor {
                 tags.push((intern_tag_key("mixramp_end"), val.to_string()));
             }
         }
+        if let Some(total) = disc_total
+            && !tags.iter().any(|(k, _)| k.as_ref() == "disctotal")
+        {
+            tags.push((intern_tag_key("disctotal"), total));
+        }
 
-        let replay_gain = if let Some(tag) = tag {
+        let mut replay_gain: ReplayGain = if let Some(tag) = tag {
             (
                 tag.get_string(ItemKey::ReplayGainTrackGain)
                     .and_then(|s| s.trim_end_matches(" dB").parse::<f32>().ok()),
@@ -285,7 +423,32 @@ impl MetadataExtractor {
             (None, None, None, None)
         };
 
-        Ok(Song {
+        // Opus stores its gain as EBU R128 comments, which MPD prefers over
+        // any REPLAYGAIN_* ones.
+        if r128_track_gain.is_some() {
+            replay_gain.0 = r128_track_gain;
+        }
+        if r128_album_gain.is_some() {
+            replay_gain.2 = r128_album_gain;
+        }
+
+        // iTunes freeform atoms: ReplayGain under any capitalisation, and the
+        // gapless info whose sample count excludes encoder delay and padding.
+        if tagged_file.file_type() == lofty::file::FileType::Mp4 {
+            for (key, val) in Self::read_comments_from_mp4(path).unwrap_or_default() {
+                if key == "iTunSMPB" {
+                    if let Some(samples) = itunsmpb_samples(&val)
+                        && let Some(rate) = sample_rate.filter(|&r| r > 0)
+                    {
+                        duration = Some(Duration::from_secs_f64(samples as f64 / f64::from(rate)));
+                    }
+                } else {
+                    fill_replay_gain(&mut replay_gain, &key, &val);
+                }
+            }
+        }
+
+        let mut song = Song {
             id: 0,
             path: path.clone(),
             duration,
@@ -294,7 +457,10 @@ impl MetadataExtractor {
             bits_per_sample: {
                 let ext = path.extension().map(|e| e.to_lowercase());
                 match ext.as_deref() {
-                    Some("m4a" | "aac") => Some(0),
+                    // Lossy decoders output floating point; ALAC keeps its
+                    // bit depth.
+                    Some("m4a") => Some(properties.bit_depth().map_or(0, u16::from)),
+                    Some("aac" | "mpc") => Some(0),
                     _ => Some(properties.bit_depth().unwrap_or(16) as u16),
                 }
             },
@@ -306,35 +472,65 @@ impl MetadataExtractor {
             added_at: mtime,
             last_modified: mtime,
             tags,
-        })
-    }
-
-    pub fn extract_artwork_from_file(path: &Utf8PathBuf) -> Result<Vec<Artwork>> {
-        let tagged_file = Probe::open(path.as_str())
-            .map_err(|e| RmpdError::Library(format!("Failed to open file: {e}")))?
-            .read()
-            .map_err(|e| RmpdError::Library(format!("Failed to read file: {e}")))?;
-
-        let tag = tagged_file
-            .primary_tag()
-            .or_else(|| tagged_file.first_tag());
+        };
 
-        let mut artworks = Vec::new();
-
-        if let Some(tag) = tag {
-            for picture in tag.pictures() {
-                artworks.push(Artwork {
-                    picture_type: picture_type_to_string(picture.pic_type()),
-                    mime_type: picture
-                        .mime_type()
-                        .map(|m| m.to_string())
-                        .unwrap_or_else(|| "image/jpeg".to_string()),
-                    data: picture.data().to_vec(),
-                });
+        if let Some(info) = &dsd {
+            // lofty opened the file but found no tag in it: read the ID3v2
+            // chunk ourselves.
+            if song.tags.is_empty() && info.id3.is_some() {
+                return Ok(Self::song_from_dsd(path, info, mtime));
             }
+            apply_dsd_properties(&mut song, info);
         }
 
-        Ok(artworks)
+        Ok(song)
+    }
+
+    /// Build a song from a DSD container and its raw ID3v2 tag alone.
+    fn song_from_dsd(path: &Utf8PathBuf, info: &DsdInfo, mtime: i64) -> Song {
+        let tags = info
+            .id3
+            .as_deref()
+            .map(rawtag::id3v2_tags)
+            .unwrap_or_default();
+        let mut song = song_from_raw_tags(path, tags, mtime);
+        apply_dsd_properties(&mut song, info);
+        song
+    }
+
+    /// Build a song from a TTA file: APEv2 tags win over a leading ID3v2 tag.
+    fn song_from_tta(path: &Utf8PathBuf, mtime: i64, file_len: u64) -> Result<Song> {
+        let info = tta::read_tta_info(path.as_std_path())?;
+        let tags = match rawtag::read_apev2(path.as_std_path())? {
+            Some(tags) => tags,
+            None => info
+                .id3
+                .as_deref()
+                .map(rawtag::id3v2_tags)
+                .unwrap_or_default(),
+        };
+        let mut song = song_from_raw_tags(path, tags, mtime);
+        let duration = info.duration();
+        song.sample_rate = Some(info.sample_rate);
+        song.channels = Some(info.channels);
+        song.bits_per_sample = Some(info.bits_per_sample);
+        song.duration = Some(duration);
+        song.bitrate = (!duration.is_zero())
+            .then(|| (file_len as f64 * 8.0 / duration.as_secs_f64() / 1000.0).round() as u32);
+        Ok(song)
+    }
+
+    pub fn extract_artwork_from_file(path: &Utf8PathBuf) -> Result<Vec<Artwork>> {
+        Ok(embedded_pictures(path.as_std_path())?
+            .into_iter()
+            .map(|picture| Artwork {
+                picture_type: picture_type_to_string(picture.pic_type),
+                mime_type: picture
+                    .mime_type
+                    .unwrap_or_else(|| infer_mime(&picture.data).to_owned()),
+                data: picture.data,
+            })
+            .collect())
     }
 
     pub fn is_supported_file(path: &Utf8PathBuf) -> bool {
@@ -351,6 +547,8 @@ impl MetadataExtractor {
                     | "wma"
                     | "ape"
                     | "wv"
+                    | "mpc"
+                    | "tta"
                     | "dsf"
                     | "dff"
             )
@@ -0,0 +1,529 @@
+//! Minimal readers for raw ID3v2 and APEv2 tags.
+//!
+//! Used for files lofty cannot open: DSDIFF (ID3v2 in an `ID3 ` chunk, see
+//! [`crate::dsd`]) and TTA (APEv2 at the end, or ID3v2 at the start). Only the
+//! text fields rmpd indexes, lyrics and the ReplayGain values are mapped;
+//! pictures are handled by `artwork`.
+
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::intern_tag_key;
+use rmpd_core::tag::{normalize_decimal, vorbis_tag_map_get};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// ── Raw ID3v2 ────────────────────────────────────────────────────────────────
+
+/// One frame of a raw ID3v2 tag. v2.2 identifiers are three characters.
+pub(crate) struct Id3Frame<'a> {
+    pub id: &'a [u8],
+    pub body: &'a [u8],
+}
+
+/// Split a raw ID3v2 tag into frames. Returns the major version (2, 3 or 4)
+/// and the frames; an unrecognised tag yields no frames.
+pub(crate) fn id3v2_frames(tag: &[u8]) -> (u8, Vec<Id3Frame<'_>>) {
+    let mut frames = Vec::new();
+    if tag.len() < 10 || &tag[..3] != b"ID3" {
+        return (0, frames);
+    }
+    let major = tag[3];
+    let syncsafe = |b: &[u8]| {
+        b.iter()
+            .fold(0usize, |acc, &x| (acc << 7) | usize::from(x & 0x7F))
+    };
+    let plain = |b: &[u8]| b.iter().fold(0usize, |acc, &x| (acc << 8) | usize::from(x));
+    let end = (10 + syncsafe(&tag[6..10])).min(tag.len());
+
+    let mut pos = 10;
+    if tag[5] & 0x40 != 0 && major >= 3 && tag.len() >= 14 {
+        // Extended header: v2.4 counts its own size field, v2.3 does not.
+        pos += match major {
+            4 => syncsafe(&tag[10..14]),
+            _ => plain(&tag[10..14]) + 4,
+        };
+    }
+
+    let (id_len, header_len) = if major == 2 { (3, 6) } else { (4, 10) };
+    while pos + header_len <= end {
+        let id = &tag[pos..pos + id_len];
+        if id[0] == 0 {
+            break; // padding
+        }
+        let size_bytes = &tag[pos + id_len..pos + 2 * id_len];
+        let size = if major == 4 {
+            syncsafe(size_bytes)
+        } else {
+            plain(size_bytes)
+        };
+        let body_start = pos + header_len;
+        let Some(body) = tag.get(body_start..body_start + size) else {
+            break;
+        };
+        frames.push(Id3Frame { id, body });
+        pos = body_start + size;
+    }
+    (major, frames)
+}
+
+/// Decode an ID3v2 string in the given text encoding (0 = Latin-1,
+/// 1 = UTF-16 with BOM, 2 = UTF-16BE, 3 = UTF-8).
+pub(crate) fn decode_id3_text(encoding: u8, bytes: &[u8]) -> String {
+    match encoding {
+        1 | 2 => {
+            let (big_endian, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                _ => (encoding == 2, bytes),
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| {
+                    if big_endian {
+                        u16::from_be_bytes([c[0], c[1]])
+                    } else {
+                        u16::from_le_bytes([c[0], c[1]])
+                    }
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        3 => String::from_utf8_lossy(bytes).into_owned(),
+        _ => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// Values of a text frame body. v2.4 separates multiple values with NULs.
+fn text_values(body: &[u8]) -> Vec<String> {
+    let Some((&encoding, rest)) = body.split_first() else {
+        return Vec::new();
+    };
+    decode_id3_text(encoding, rest)
+        .split('\0')
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// ID3v2 text frames (v2.3/v2.4 id, v2.2 id) and the MPD tag they fill.
+const ID3_TEXT_FRAMES: &[(&[u8], &[u8], &str)] = &[
+    (b"TIT2", b"TT2", "title"),
+    (b"TPE1", b"TP1", "artist"),
+    (b"TALB", b"TAL", "album"),
+    (b"TPE2", b"TP2", "albumartist"),
+    (b"TRCK", b"TRK", "track"),
+    (b"TPOS", b"TPA", "disc"),
+    (b"TCON", b"TCO", "genre"),
+    (b"TCOM", b"TCM", "composer"),
+    (b"TPE3", b"TP3", "performer"),
+    (b"TIT1", b"TT1", "grouping"),
+    (b"TPUB", b"TPB", "label"),
+    (b"TDRC", b"TYE", "date"),
+    (b"TYER", b"TYE", "date"),
+    (b"TDOR", b"TOR", "originaldate"),
+    (b"TSOP", b"TSP", "artistsort"),
+    (b"TSO2", b"TS2", "albumartistsort"),
+    (b"MVNM", b"MVN", "movement"),
+    (b"MVIN", b"MVI", "movementnumber"),
+];
+
+/// `TXXX` descriptions (case-insensitive) filling MPD tags that have no
+/// ID3v2 frame of their own.
+const ID3_USER_TEXT_TAGS: &[(&str, &str)] = &[
+    ("work", "work"),
+    ("conductor", "conductor"),
+    ("ensemble", "ensemble"),
+];
+
+/// Tags, lyrics and ReplayGain values read from a raw ID3v2 or APEv2 tag.
+#[derive(Debug, Default)]
+pub(crate) struct RawTags {
+    pub tags: Vec<(Cow<'static, str>, String)>,
+    /// Unsynchronised lyrics (`USLT` / APEv2 `Lyrics`); not an MPD tag.
+    pub lyrics: Option<String>,
+    pub replay_gain_track_gain: Option<f32>,
+    pub replay_gain_track_peak: Option<f32>,
+    pub replay_gain_album_gain: Option<f32>,
+    pub replay_gain_album_peak: Option<f32>,
+}
+
+impl RawTags {
+    /// Add one value for MPD tag `name`; Track/Disc "3/12" is stored as "3",
+    /// and a disc's "/2" total as `disctotal`.
+    fn push(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        if name == "disc"
+            && let Some((_, total)) = value.split_once('/')
+            && let Some(total) = normalize_decimal(total)
+            && !self.has("disctotal")
+        {
+            self.tags.push((intern_tag_key("disctotal"), total));
+        }
+        let value = if matches!(name, "track" | "disc") {
+            match normalize_decimal(value.split('/').next().unwrap_or_default()) {
+                Some(v) => v,
+                None => return,
+            }
+        } else {
+            value.to_owned()
+        };
+        self.tags.push((intern_tag_key(name), value));
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.tags.iter().any(|(k, _)| k == name)
+    }
+
+    /// Record a `REPLAYGAIN_*` key; returns false for any other key.
+    fn replay_gain(&mut self, key: &str, value: &str) -> bool {
+        let value = value.trim();
+        let gain = value.trim_end_matches("dB").trim().parse::<f32>().ok();
+        match key.to_ascii_lowercase().as_str() {
+            "replaygain_track_gain" => self.replay_gain_track_gain = gain,
+            "replaygain_track_peak" => self.replay_gain_track_peak = value.parse().ok(),
+            "replaygain_album_gain" => self.replay_gain_album_gain = gain,
+            "replaygain_album_peak" => self.replay_gain_album_peak = value.parse().ok(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Lyrics text of a `USLT`/`ULT` frame body: encoding, language and a
+/// NUL-terminated content descriptor precede the text.
+fn uslt_text(body: &[u8]) -> Option<String> {
+    let (&encoding, rest) = body.split_first()?;
+    let rest = rest.get(3..)?;
+    let text_start = if matches!(encoding, 1 | 2) {
+        // UTF-16: the terminator is a NUL code unit.
+        rest.chunks_exact(2).position(|c| c == [0, 0])? * 2 + 2
+    } else {
+        rest.iter().position(|&b| b == 0)? + 1
+    };
+    let text = decode_id3_text(encoding, &rest[text_start..]);
+    let text = text.trim_end_matches('\0');
+    (!text.trim().is_empty()).then(|| text.to_owned())
+}
+
+/// Map the text, `USLT` and `TXXX` ReplayGain frames of a raw ID3v2 tag.
+pub(crate) fn id3v2_tags(tag: &[u8]) -> RawTags {
+    let (major, frames) = id3v2_frames(tag);
+    let mut out = RawTags::default();
+    for frame in &frames {
+        if frame.id == b"TXXX" || frame.id == b"TXX" {
+            let Some((&encoding, rest)) = frame.body.split_first() else {
+                continue;
+            };
+            let text = decode_id3_text(encoding, rest);
+            if let Some((desc, value)) = text.split_once('\0') {
+                let value = value.trim_end_matches('\0');
+                if out.replay_gain(desc, value) {
+                    continue;
+                }
+                if let Some(&(_, name)) = ID3_USER_TEXT_TAGS
+                    .iter()
+                    .find(|(d, _)| d.eq_ignore_ascii_case(desc))
+                {
+                    out.push(name, value);
+                }
+            }
+            continue;
+        }
+        if frame.id == b"USLT" || frame.id == b"ULT" {
+            out.lyrics = out.lyrics.or_else(|| uslt_text(frame.body));
+            continue;
+        }
+
+        let Some(&(_, _, name)) = ID3_TEXT_FRAMES.iter().find(|(v3, v2, _)| {
+            if major == 2 {
+                frame.id == *v2
+            } else {
+                frame.id == *v3
+            }
+        }) else {
+            continue;
+        };
+        if matches!(name, "date" | "originaldate") && out.has(name) {
+            continue; // first date frame wins (TDRC is written before TYER)
+        }
+        for value in text_values(frame.body) {
+            out.push(name, &value);
+        }
+    }
+    out
+}
+
+// ── Raw APEv2 ────────────────────────────────────────────────────────────────
+
+/// Size of an APEv2 header or footer.
+const APE_FOOTER_LEN: usize = 32;
+
+/// Upper bound for an APEv2 tag (binary cover items included).
+const MAX_APE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Read the APEv2 tag at the end of `path` (before an optional ID3v1 tag).
+pub(crate) fn read_apev2(path: &Path) -> Result<Option<RawTags>> {
+    let io = |e: std::io::Error| RmpdError::Library(format!("Failed to read APE tag: {e}"));
+    let mut file = File::open(path).map_err(io)?;
+    let len = file.metadata().map_err(io)?.len();
+
+    for id3v1 in [0u64, 128] {
+        let Some(footer_pos) = len.checked_sub(id3v1 + APE_FOOTER_LEN as u64) else {
+            continue;
+        };
+        let mut footer = [0u8; APE_FOOTER_LEN];
+        file.seek(SeekFrom::Start(footer_pos)).map_err(io)?;
+        file.read_exact(&mut footer).map_err(io)?;
+        if !footer.starts_with(b"APETAGEX") {
+            continue;
+        }
+        let size = u32::from_le_bytes(footer[12..16].try_into().expect("4 bytes")) as usize;
+        let count = u32::from_le_bytes(footer[16..20].try_into().expect("4 bytes")) as usize;
+        if !(APE_FOOTER_LEN..=MAX_APE_SIZE).contains(&size) {
+            return Ok(None);
+        }
+        // `size` counts the items and the footer, not the optional header.
+        let items_len = size - APE_FOOTER_LEN;
+        let Some(items_pos) = footer_pos.checked_sub(items_len as u64) else {
+            return Ok(None);
+        };
+        let mut items = vec![0u8; items_len];
+        file.seek(SeekFrom::Start(items_pos)).map_err(io)?;
+        file.read_exact(&mut items).map_err(io)?;
+        return Ok(Some(apev2_tags(&items, count)));
+    }
+    Ok(None)
+}
+
+/// APEv2 item keys (lowercase) that differ from their Vorbis comment name.
+const APE_KEY_ALIASES: &[(&str, &str)] = &[("year", "date"), ("album artist", "albumartist")];
+
+/// Map the text items of an APEv2 item block.
+fn apev2_tags(mut items: &[u8], count: usize) -> RawTags {
+    let mut out = RawTags::default();
+    for _ in 0..count {
+        if items.len() < 8 {
+            break;
+        }
+        let value_len = u32::from_le_bytes(items[..4].try_into().expect("4 bytes")) as usize;
+        let flags = u32::from_le_bytes(items[4..8].try_into().expect("4 bytes"));
+        let rest = &items[8..];
+        let Some(key_end) = rest.iter().position(|&b| b == 0) else {
+            break;
+        };
+        let Some(value) = rest.get(key_end + 1..key_end + 1 + value_len) else {
+            break;
+        };
+        items = &rest[key_end + 1 + value_len..];
+
+        // Bits 1-2: 0 = UTF-8 text, 1 = binary, 2 = external locator.
+        if (flags >> 1) & 3 != 0 {
+            continue;
+        }
+        let key = String::from_utf8_lossy(&rest[..key_end]).to_ascii_lowercase();
+        let value = String::from_utf8_lossy(value);
+        if out.replay_gain(&key, &value) {
+            continue;
+        }
+        if key == "lyrics" {
+            out.lyrics = Some(value.into_owned());
+            continue;
+        }
+        let name = APE_KEY_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == key)
+            .map(|(_, name)| *name)
+            .or_else(|| vorbis_tag_map_get(&key));
+        if let Some(name) = name {
+            // Multiple values are NUL-separated.
+            for v in value.split('\0') {
+                out.push(name, v);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_frame(id: &[u8; 4], encoding: u8, text: &[u8]) -> Vec<u8> {
+        let mut frame = id.to_vec();
+        frame.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, encoding]);
+        frame.extend_from_slice(text);
+        frame
+    }
+
+    fn tag_v23(frames: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = frames.concat();
+        let n = body.len();
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        tag.extend_from_slice(&[
+            ((n >> 21) & 0x7F) as u8,
+            ((n >> 14) & 0x7F) as u8,
+            ((n >> 7) & 0x7F) as u8,
+            (n & 0x7F) as u8,
+        ]);
+        tag.extend_from_slice(&body);
+        tag.extend_from_slice(&[0; 16]); // padding
+        tag
+    }
+
+    #[test]
+    fn maps_text_frames_and_replay_gain() {
+        let tag = tag_v23(&[
+            text_frame(b"TIT2", 3, "Sõng".as_bytes()),
+            text_frame(b"TPE1", 0, b"Artist"),
+            text_frame(b"TRCK", 0, b"03/12"),
+            text_frame(b"TYER", 0, b"1999"),
+            text_frame(b"TXXX", 0, b"REPLAYGAIN_TRACK_GAIN\0-6.50 dB"),
+        ]);
+        let parsed = id3v2_tags(&tag);
+        let get = |k: &str| {
+            parsed
+                .tags
+                .iter()
+                .find(|(key, _)| key == k)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get("title"), Some("Sõng"));
+        assert_eq!(get("artist"), Some("Artist"));
+        assert_eq!(get("track"), Some("3"));
+        assert_eq!(get("date"), Some("1999"));
+        assert_eq!(parsed.replay_gain_track_gain, Some(-6.5));
+    }
+
+    #[test]
+    fn decodes_utf16_with_bom() {
+        let text: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("Ab".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_id3_text(1, &text), "Ab");
+        assert_eq!(decode_id3_text(0, b"\xE9"), "é");
+    }
+
+    #[test]
+    fn reads_uslt_lyrics() {
+        let tag = tag_v23(&[text_frame(b"USLT", 0, b"eng\0[00:01.00]La la\nLa")]);
+        assert_eq!(
+            id3v2_tags(&tag).lyrics.as_deref(),
+            Some("[00:01.00]La la\nLa")
+        );
+
+        // UTF-16 with a BOM-prefixed descriptor "d".
+        let body = b"eng\xFF\xFEd\0\0\0\xFF\xFEH\0i\0";
+        let tag = tag_v23(&[text_frame(b"USLT", 1, body)]);
+        assert_eq!(id3v2_tags(&tag).lyrics.as_deref(), Some("Hi"));
+    }
+
+    #[test]
+    fn maps_classical_frames() {
+        let tag = tag_v23(&[
+            text_frame(b"MVNM", 0, b"Allegro"),
+            text_frame(b"MVIN", 0, b"1/4"),
+            text_frame(b"TXXX", 0, b"WORK\0Symphony No. 5"),
+            text_frame(b"TXXX", 0, b"Conductor\0Carlos Kleiber"),
+            text_frame(b"TXXX", 0, b"ENSEMBLE\0Wiener Philharmoniker"),
+        ]);
+        let parsed = id3v2_tags(&tag);
+        let get = |k: &str| {
+            parsed
+                .tags
+                .iter()
+                .find(|(key, _)| key == k)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get("movement"), Some("Allegro"));
+        assert_eq!(get("movementnumber"), Some("1/4"));
+        assert_eq!(get("work"), Some("Symphony No. 5"));
+        assert_eq!(get("conductor"), Some("Carlos Kleiber"));
+        assert_eq!(get("ensemble"), Some("Wiener Philharmoniker"));
+    }
+
+    #[test]
+    fn disc_total_comes_from_tpos() {
+        let tag = tag_v23(&[text_frame(b"TPOS", 0, b"02/3")]);
+        let tags = id3v2_tags(&tag).tags;
+        assert!(tags.iter().any(|(k, v)| k == "disc" && v == "2"));
+        assert!(tags.iter().any(|(k, v)| k == "disctotal" && v == "3"));
+    }
+
+    #[test]
+    fn truncated_tag_yields_no_frames() {
+        let mut tag = tag_v23(&[text_frame(b"TIT2", 0, b"Title")]);
+        tag.truncate(14);
+        assert!(id3v2_frames(&tag).1.is_empty());
+        assert!(id3v2_frames(b"not a tag").1.is_empty());
+    }
+
+    fn ape_item(key: &str, value: &[u8], flags: u32) -> Vec<u8> {
+        let mut item = (value.len() as u32).to_le_bytes().to_vec();
+        item.extend_from_slice(&flags.to_le_bytes());
+        item.extend_from_slice(key.as_bytes());
+        item.push(0);
+        item.extend_from_slice(value);
+        item
+    }
+
+    #[test]
+    fn maps_apev2_text_items() {
+        let items = [
+            ape_item("Title", b"Ape Song", 0),
+            ape_item("Artist", b"One\0Two", 0),
+            ape_item("Album Artist", b"Band", 0),
+            ape_item("Year", b"2001", 0),
+            ape_item("Track", b"7/9", 0),
+            ape_item("Cover Art (Front)", b"c.png\0\x89PNG", 2),
+            ape_item("REPLAYGAIN_ALBUM_GAIN", b"+1.25 dB", 0),
+            ape_item("Lyrics", b"Line one\nLine two", 0),
+        ]
+        .concat();
+        let parsed = apev2_tags(&items, 8);
+        let values = |k: &str| -> Vec<&str> {
+            parsed
+                .tags
+                .iter()
+                .filter(|(key, _)| key == k)
+                .map(|(_, v)| v.as_str())
+                .collect()
+        };
+        assert_eq!(values("title"), ["Ape Song"]);
+        assert_eq!(values("artist"), ["One", "Two"]);
+        assert_eq!(values("albumartist"), ["Band"]);
+        assert_eq!(values("date"), ["2001"]);
+        assert_eq!(values("track"), ["7"]);
+        assert_eq!(parsed.replay_gain_album_gain, Some(1.25));
+        assert_eq!(parsed.lyrics.as_deref(), Some("Line one\nLine two"));
+        assert_eq!(parsed.tags.len(), 6, "binary items are skipped");
+    }
+
+    #[test]
+    fn reads_apev2_footer_before_id3v1() {
+        let items = ape_item("Title", b"Tail", 0);
+        let mut footer = b"APETAGEX".to_vec();
+        footer.extend_from_slice(&2000u32.to_le_bytes());
+        footer.extend_from_slice(&((items.len() + 32) as u32).to_le_bytes());
+        footer.extend_from_slice(&1u32.to_le_bytes());
+        footer.extend_from_slice(&0x8000_0000u32.to_le_bytes());
+        footer.extend_from_slice(&[0; 8]);
+
+        let mut file = b"audio data".to_vec();
+        file.extend_from_slice(&items);
+        file.extend_from_slice(&footer);
+        file.extend_from_slice(b"TAG");
+        file.extend_from_slice(&[0; 125]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("x.tta");
+        std::fs::write(&path, file).unwrap();
+        let parsed = read_apev2(&path).unwrap().expect("APEv2 tag");
+        assert_eq!(parsed.tags[0].1, "Tail");
+    }
+}
@@ -1,5 +1,6 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use rayon::prelude::*;
+use rmpd_core::config::MusicRoot;
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::event::{Event, EventBus};
 use std::fs;
@@ -8,7 +9,9 @@ use std::path::Path;
 use tracing::{debug, info, warn};
 
 use crate::database::Database;
+use crate::lyrics::{self, Lyrics, LyricsSource};
 use crate::metadata::MetadataExtractor;
+use crate::tag_rules::TagRewriter;
 use rmpd_core::time::system_time_to_unix_secs;
 
 /// Information about a file to be processed
@@ -26,6 +29,7 @@ struct FileInfo {
 struct ExtractedMetadata {
     file_info: FileInfo,
     song: Option<rmpd_core::song::Song>,
+    lyrics: Option<Lyrics>,
     error: Option<String>,
 }
 
@@ -34,6 +38,14 @@ pub struct Scanner {
     event_bus: EventBus,
     music_directory: Option<Utf8PathBuf>,
     follow_symlinks: bool,
+    /// Walk the tree without writing directories or playlists to the database.
+    dry_run: bool,
+    /// Extra music directories scanned after the main one.
+    roots: Vec<MusicRoot>,
+    /// Virtual-tree prefix (the root name) for paths under `music_directory`.
+    uri_prefix: Option<String>,
+    /// `[[tag_rule]]` rewrites applied to every extracted song.
+    tag_rewriter: TagRewriter,
 }
 
 impl Scanner {
@@ -42,9 +54,25 @@ impl Scanner {
             event_bus,
             music_directory: None,
             follow_symlinks,
+            dry_run: false,
+            roots: Vec::new(),
+            uri_prefix: None,
+            tag_rewriter: TagRewriter::default(),
         }
     }
 
+    /// Also scan `roots`, storing their songs under `<name>/...` URIs.
+    pub fn with_roots(mut self, roots: Vec<MusicRoot>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    /// Rewrite tags of scanned songs with `rewriter` before storing them.
+    pub fn with_tag_rewriter(mut self, rewriter: TagRewriter) -> Self {
+        self.tag_rewriter = rewriter;
+        self
+    }
+
     /// Returns a copy of this scanner with `music_directory` set to `dir`.
     ///
     /// `scan_directory` uses this instead of inline struct construction so that if
@@ -54,13 +82,43 @@ impl Scanner {
             event_bus: self.event_bus.clone(),
             music_directory: Some(dir),
             follow_symlinks: self.follow_symlinks,
+            dry_run: self.dry_run,
+            roots: self.roots.clone(),
+            uri_prefix: None,
+            tag_rewriter: self.tag_rewriter.clone(),
+        }
+    }
+
+    /// Scanner for one extra root, producing `<root.name>/...` paths.
+    fn with_root(&self, root: &MusicRoot) -> Self {
+        Self {
+            uri_prefix: Some(root.name.clone()),
+            ..self.with_music_dir(root.path.clone())
         }
     }
 
+    /// Scan `root_path` and every extra root into `db`.
+    ///
+    /// Emits `DatabaseUpdateStarted` before walking and `DatabaseUpdateFinished`
+    /// once done (also when the scan fails), with `DatabaseChanged` in between
+    /// when songs were added, updated or removed. A failed scan may have
+    /// written part of its work, so it is reported as a change too.
+    ///
+    /// Waits for other library writers (see [`crate::update_lock`]) first.
     pub fn scan_directory(&self, db: &Database, root_path: &Path) -> Result<ScanStats> {
+        let _update = crate::update_lock::lock();
         info!("starting music library scan: {}", root_path.display());
         self.event_bus.emit(Event::DatabaseUpdateStarted);
 
+        let result = self.scan_all(db, root_path);
+        if result.as_ref().map_or(true, ScanStats::changed) {
+            self.event_bus.emit(Event::DatabaseChanged);
+        }
+        self.event_bus.emit(Event::DatabaseUpdateFinished);
+        result
+    }
+
+    fn scan_all(&self, db: &Database, root_path: &Path) -> Result<ScanStats> {
         let mut stats = ScanStats::default();
 
         // Build a scanner variant that knows the music directory so that make_relative_path
@@ -71,23 +129,179 @@ impl Scanner {
 
         scanner_with_dir.scan_recursive(db, root_path, &mut stats)?;
 
+        for root in &self.roots {
+            if !root.path.is_dir() {
+                warn!("music root {} not found: {}", root.name, root.path);
+                stats.errors += 1;
+                continue;
+            }
+            info!("scanning music root {}: {}", root.name, root.path);
+            let root_mtime = fs::metadata(&root.path)
+                .and_then(|m| m.modified())
+                .map(system_time_to_unix_secs)
+                .ok();
+            if let Err(e) =
+                db.get_or_create_directory_with_mtime(Utf8Path::new(&root.name), root_mtime)
+            {
+                warn!("failed to record music root {}: {}", root.name, e);
+            }
+            self.with_root(root)
+                .scan_recursive(db, root.path.as_std_path(), &mut stats)?;
+        }
+
+        scanner_with_dir.prune_missing(db, &mut stats)?;
+
+        // Albums tagged with track gains only get a derived album gain.
+        match db.derive_album_gains() {
+            Ok(0) => {}
+            Ok(n) => debug!("derived album gain for {n} songs"),
+            Err(e) => warn!("failed to derive album gains: {e}"),
+        }
+
         info!(
-            "scan complete: {} files scanned, {} added, {} updated, {} errors",
-            stats.scanned, stats.added, stats.updated, stats.errors
+            "scan complete: {} files scanned, {} added, {} updated, {} removed, {} errors",
+            stats.scanned, stats.added, stats.updated, stats.removed, stats.errors
         );
 
-        self.event_bus.emit(Event::DatabaseUpdateFinished);
-
         Ok(stats)
     }
 
+    /// Report what `scan_directory` would change without touching the database.
+    ///
+    /// Walks the tree with the same filters as a real scan (hidden entries,
+    /// symlink policy, supported extensions, mtime checks) but skips metadata
+    /// extraction and every database write. `removed` lists local songs whose
+    /// files are no longer on disk.
+    pub fn plan_directory(&self, db: &Database, root_path: &Path) -> Result<ScanReport> {
+        info!("planning music library scan: {}", root_path.display());
+
+        let music_dir = Utf8PathBuf::try_from(root_path.to_path_buf())
+            .map_err(|_| RmpdError::Library("Music directory path is not valid UTF-8".into()))?;
+        let mut planner = self.with_music_dir(music_dir.clone());
+        planner.dry_run = true;
+
+        let mut stats = ScanStats::default();
+        let mut files = Vec::new();
+        planner.collect_root(db, root_path, &mut files, &mut stats)?;
+        for root in &self.roots {
+            if root.path.is_dir() {
+                planner.with_root(root).collect_root(
+                    db,
+                    root.path.as_std_path(),
+                    &mut files,
+                    &mut stats,
+                )?;
+            } else {
+                warn!("music root {} not found: {}", root.name, root.path);
+                stats.errors += 1;
+            }
+        }
+
+        let mut report = ScanReport {
+            scanned: stats.scanned,
+            errors: stats.errors,
+            ..ScanReport::default()
+        };
+        for file in files {
+            if file.existing_song.is_some() {
+                report.updated.push(file.relative_path);
+            } else {
+                report.added.push(file.relative_path);
+            }
+        }
+        for path in db.list_local_song_paths()? {
+            let on_disk =
+                rmpd_core::path::resolve_in_roots(&path, Some(music_dir.as_str()), &self.roots);
+            if !Path::new(&on_disk).exists() {
+                report.removed.push(Utf8PathBuf::from(path));
+            }
+        }
+        report.added.sort();
+        report.updated.sort();
+
+        info!(
+            "scan plan: {} to add, {} to update, {} to remove",
+            report.added.len(),
+            report.updated.len(),
+            report.removed.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Delete local songs whose files are no longer on disk and emit
+    /// `SongDeleted` for each. Songs under an extra root that is currently
+    /// missing are kept, so an unmounted drive does not empty the library.
+    fn prune_missing(&self, db: &Database, stats: &mut ScanStats) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        let music_dir = self.music_directory.as_ref().map(|d| d.as_str());
+        let missing: Vec<String> = db
+            .list_local_song_paths()?
+            .into_iter()
+            .filter(
+                |path| match rmpd_core::path::split_root(path, &self.roots) {
+                    Some((root, _)) if !root.path.is_dir() => false,
+                    _ => !Path::new(&rmpd_core::path::resolve_in_roots(
+                        path,
+                        music_dir,
+                        &self.roots,
+                    ))
+                    .exists(),
+                },
+            )
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        for path in db.prune_songs(&missing)? {
+            debug!("removed: {}", path);
+            stats.removed += 1;
+            self.event_bus.emit(Event::SongDeleted { path });
+        }
+        Ok(())
+    }
+
+    /// If a local song is stored under a path that differs from `relative`
+    /// only in case, and the filesystem resolves that spelling to the very
+    /// file described by `metadata` without listing it as a name of its own
+    /// (a case-insensitive filesystem, as opposed to a hard link), move the
+    /// row to `relative` so the file is not indexed twice. Returns whether a
+    /// row was moved.
+    fn adopt_case_variant(
+        &self,
+        db: &Database,
+        relative: &str,
+        metadata: &fs::Metadata,
+    ) -> Result<bool> {
+        let music_dir = self.music_directory.as_ref().map(|d| d.as_str());
+        for variant in db.song_paths_differing_in_case(relative)? {
+            let on_disk = rmpd_core::path::resolve_in_roots(&variant, music_dir, &self.roots);
+            let on_disk = Path::new(&on_disk);
+            let same_file = fs::metadata(on_disk)
+                .is_ok_and(|m| m.dev() == metadata.dev() && m.ino() == metadata.ino());
+            if same_file && !is_listed(on_disk) && db.rename_song_path(&variant, relative)? {
+                debug!("renamed: {} -> {}", variant, relative);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// Convert absolute path to relative path (relative to music_directory)
     fn make_relative_path(&self, abs_path: &Utf8PathBuf) -> Result<Utf8PathBuf> {
         if let Some(music_dir) = &self.music_directory {
             // Strip music directory prefix
             if let Some(relative) = abs_path.as_str().strip_prefix(music_dir.as_str()) {
+                let relative = rmpd_core::path::to_uri(relative);
                 let relative = relative.trim_start_matches('/');
-                return Ok(Utf8PathBuf::from(relative));
+                return Ok(match &self.uri_prefix {
+                    Some(prefix) if relative.is_empty() => Utf8PathBuf::from(prefix),
+                    Some(prefix) => Utf8PathBuf::from(format!("{prefix}/{relative}")),
+                    None => Utf8PathBuf::from(relative),
+                });
             }
         }
         // Fallback: return as-is if we can't make it relative
@@ -96,24 +310,14 @@ impl Scanner {
 
     fn scan_recursive(&self, db: &Database, path: &Path, stats: &mut ScanStats) -> Result<()> {
         // SOURCE ISOLATION: this scan only processes local filesystem files and
-        // only calls `db.add_song()` (which never sets `source`). Any future
-        // reconcile/prune step that deletes songs no longer on disk MUST use
-        // `Database::delete_song_by_path` (which is guarded with `AND source IS NULL`)
-        // or an equivalent query with a `WHERE source IS NULL` predicate to avoid
-        // evicting remote catalog rows inserted by `Database::add_source_song`.
+        // only calls `db.add_song()` (which never sets `source`). The prune step
+        // (`prune_missing`) goes through `Database::prune_songs`, which is guarded
+        // with `AND source IS NULL` so it never evicts remote catalog rows
+        // inserted by `Database::add_source_song`.
 
         // Step 1: Collect all audio files and their metadata (sequential directory walk).
-        // `visited_dirs` tracks (dev, ino) pairs already recursed into, shared across the
-        // whole tree walk, so a symlink cycle (or any other filesystem loop) can't cause
-        // unbounded recursion when `follow_symlinks` is enabled.
         let mut files_to_process = Vec::new();
-        let mut visited_dirs = std::collections::HashSet::new();
-        // Seed with the root itself so a symlink cycle that loops back to the
-        // scan root (rather than to some deeper ancestor) is also detected.
-        if let Ok(root_meta) = fs::metadata(path) {
-            visited_dirs.insert((root_meta.dev(), root_meta.ino()));
-        }
-        self.collect_audio_files(db, path, &mut files_to_process, stats, &mut visited_dirs)?;
+        self.collect_root(db, path, &mut files_to_process, stats)?;
 
         // Step 2: Extract metadata in parallel
         let extracted: Vec<ExtractedMetadata> = files_to_process
@@ -123,9 +327,12 @@ impl Scanner {
                     Ok(mut song) => {
                         // Replace absolute path with relative path for storage
                         song.path = file_info.relative_path.clone();
+                        self.tag_rewriter.apply(&mut song);
+                        let lyrics = lyrics::read_lyrics(file_info.absolute_path.as_std_path());
                         ExtractedMetadata {
                             file_info,
                             song: Some(song),
+                            lyrics,
                             error: None,
                         }
                     }
@@ -134,6 +341,7 @@ impl Scanner {
                         ExtractedMetadata {
                             file_info,
                             song: None,
+                            lyrics: None,
                             error: Some(error_msg),
                         }
                     }
@@ -159,8 +367,20 @@ impl Scanner {
             if let Some(song) = extracted_meta.song {
                 match db.add_song(&song) {
                     Ok(_) => {
+                        let stored = match &extracted_meta.lyrics {
+                            Some(lyrics) => db.store_lyrics(song.path.as_str(), lyrics),
+                            None => db.delete_lyrics(song.path.as_str()),
+                        };
+                        if let Err(e) = stored {
+                            warn!("failed to store lyrics for {}: {}", song.path, e);
+                        }
                         let is_update = extracted_meta.file_info.existing_song.is_some();
                         if is_update {
+                            // The upsert keeps the row (and its cached art);
+                            // the file changed, so its embedded art may have too.
+                            if let Err(e) = db.delete_artwork(song.path.as_str()) {
+                                warn!("failed to invalidate artwork for {}: {}", song.path, e);
+                            }
                             debug!("updated: {}", song.path);
                             updated += 1;
                         } else {
@@ -183,6 +403,26 @@ impl Scanner {
         Ok(())
     }
 
+    /// Walk one scan root. `visited_dirs` tracks (dev, ino) pairs already
+    /// recursed into, shared across the whole tree walk, so a symlink cycle (or
+    /// any other filesystem loop) can't cause unbounded recursion when
+    /// `follow_symlinks` is enabled.
+    fn collect_root(
+        &self,
+        db: &Database,
+        path: &Path,
+        files: &mut Vec<FileInfo>,
+        stats: &mut ScanStats,
+    ) -> Result<()> {
+        let mut visited_dirs = std::collections::HashSet::new();
+        // Seed with the root itself so a symlink cycle that loops back to the
+        // scan root (rather than to some deeper ancestor) is also detected.
+        if let Ok(root_meta) = fs::metadata(path) {
+            visited_dirs.insert((root_meta.dev(), root_meta.ino()));
+        }
+        self.collect_audio_files(db, path, files, stats, &mut visited_dirs)
+    }
+
     /// Collect all audio files from the directory tree (sequential walk)
     fn collect_audio_files(
         &self,
@@ -194,6 +434,7 @@ impl Scanner {
     ) -> Result<()> {
         let entries = fs::read_dir(path)
             .map_err(|e| RmpdError::Library(format!("Failed to read directory: {e}")))?;
+        let mut playlists = Vec::new();
 
         for entry in entries {
             let entry = match entry {
@@ -269,8 +510,9 @@ impl Scanner {
                             .modified()
                             .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
                     );
-                    if let Err(e) =
-                        db.get_or_create_directory_with_mtime(rel_dir.as_path(), Some(dir_mtime))
+                    if !self.dry_run
+                        && let Err(e) = db
+                            .get_or_create_directory_with_mtime(rel_dir.as_path(), Some(dir_mtime))
                     {
                         warn!("failed to record directory {:?}: {}", entry_path, e);
                     }
@@ -293,6 +535,18 @@ impl Scanner {
                     }
                 };
 
+                if is_playlist_file(utf8_path.as_std_path()) {
+                    if let Ok(relative_path) = self.make_relative_path(&utf8_path) {
+                        let mtime = system_time_to_unix_secs(
+                            metadata
+                                .modified()
+                                .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
+                        );
+                        playlists.push((relative_path.into_string(), mtime));
+                    }
+                    continue;
+                }
+
                 // Check if this is a supported audio file
                 if !MetadataExtractor::is_supported_file(&utf8_path) {
                     continue;
@@ -319,7 +573,7 @@ impl Scanner {
                 };
 
                 // Check if file already exists in database (using relative path)
-                let existing_song = match db.get_song_by_path(relative_path.as_str()) {
+                let mut existing_song = match db.get_song_by_path(relative_path.as_str()) {
                     Ok(s) => s,
                     Err(e) => {
                         warn!("database error checking {}: {}", relative_path, e);
@@ -328,15 +582,33 @@ impl Scanner {
                     }
                 };
 
+                // Not stored under this exact spelling: it may be stored under
+                // another case of the same file (case-insensitive filesystem).
+                if !self.dry_run
+                    && existing_song
+                        .as_ref()
+                        .is_none_or(|s| s.path != relative_path)
+                {
+                    match self.adopt_case_variant(db, relative_path.as_str(), &metadata) {
+                        Ok(true) => {
+                            existing_song =
+                                db.get_song_by_path(relative_path.as_str()).ok().flatten();
+                        }
+                        Ok(false) => {}
+                        Err(e) => warn!("database error checking {}: {}", relative_path, e),
+                    }
+                }
+
                 let mtime = system_time_to_unix_secs(
                     metadata
                         .modified()
                         .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
                 );
 
-                // Skip if file hasn't been modified
+                // Skip if neither the file nor its .lrc sidecar has been modified
                 if let Some(ref existing) = existing_song
                     && existing.last_modified >= mtime
+                    && !lyrics_stale(db, relative_path.as_str(), &entry_path)
                 {
                     continue;
                 }
@@ -351,14 +623,79 @@ impl Scanner {
             }
         }
 
+        if !self.dry_run
+            && let Ok(utf8_dir) = Utf8PathBuf::try_from(path.to_path_buf())
+            && let Ok(rel_dir) = self.make_relative_path(&utf8_dir)
+            && let Err(e) = db.set_directory_playlists(rel_dir.as_str(), &playlists)
+        {
+            warn!("failed to record playlists in {:?}: {}", path, e);
+        }
+
         Ok(())
     }
 }
 
+/// Whether `path`'s file name appears verbatim in its directory listing.
+/// On a case-insensitive filesystem a differently-cased spelling resolves to
+/// the file but is not listed.
+fn is_listed(path: &Path) -> bool {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    fs::read_dir(dir).is_ok_and(|entries| entries.flatten().any(|e| e.file_name() == name))
+}
+
+/// Whether `path` is a playlist file (`.m3u`, `.m3u8`, `.pls`, `.xspf`,
+/// `.asx` or `.cue`) that lsinfo lists next to the songs of its directory.
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "m3u" | "m3u8" | "pls" | "xspf" | "asx" | "cue"
+            )
+        })
+}
+
+/// Whether an `.lrc` sidecar of an otherwise unchanged song was added,
+/// modified or removed since the stored lyrics were read.
+fn lyrics_stale(db: &Database, relative_path: &str, absolute_path: &Path) -> bool {
+    let stored = db.get_lyrics(relative_path).ok().flatten();
+    match (lyrics::sidecar_mtime(absolute_path), stored) {
+        (Some(mtime), Some(stored)) => {
+            stored.source != LyricsSource::Sidecar || mtime > stored.mtime
+        }
+        (Some(_), None) => true,
+        (None, Some(stored)) => stored.source == LyricsSource::Sidecar,
+        (None, None) => false,
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ScanStats {
     pub scanned: u32,
     pub added: u32,
     pub updated: u32,
+    /// Songs deleted because their files disappeared.
+    pub removed: u32,
+    pub errors: u32,
+}
+
+impl ScanStats {
+    /// Whether the scan added, updated or removed any song.
+    pub fn changed(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+/// Outcome of [`Scanner::plan_directory`]: the paths a real scan would touch,
+/// relative to the music directory and sorted.
+#[derive(Debug, Default, Clone)]
+pub struct ScanReport {
+    pub scanned: u32,
     pub errors: u32,
+    pub added: Vec<Utf8PathBuf>,
+    pub updated: Vec<Utf8PathBuf>,
+    pub removed: Vec<Utf8PathBuf>,
 }
@@ -0,0 +1,297 @@
+//! Storage backends for the music library.
+//!
+//! The protocol layer talks to the library through [`LibraryBackend`] (shared
+//! for the daemon's lifetime) and the [`LibraryStore`] handles it hands out,
+//! one per command, instead of naming the SQLite [`Database`] directly.
+//! SQLite, through [`DbPool`], is the default and currently only backend; an
+//! embedded key-value store such as sled or redb can be added by implementing
+//! these two traits, typically behind a cargo feature.
+
+use std::path::Path;
+
+use rmpd_core::error::Result;
+use rmpd_core::filter::FilterExpression;
+use rmpd_core::song::Song;
+
+use crate::database::{
+    Database, DbPool, DirectoryListing, FormatReport, PlaylistInfo, QueueSnapshot, WalkEntry,
+};
+use crate::lyrics::Lyrics;
+use crate::scanner::{ScanReport, ScanStats, Scanner};
+
+/// A library database shared by all clients, handing out a
+/// [`LibraryStore`] per command and running library scans against itself.
+pub trait LibraryBackend: Send + Sync + std::fmt::Debug {
+    /// A handle for running queries; cheap enough to get one per command.
+    fn store(&self) -> Result<Box<dyn LibraryStore>>;
+
+    /// Update the library from the files under `root`, as
+    /// [`Scanner::scan_directory`] does.
+    fn scan(&self, scanner: &Scanner, root: &Path) -> Result<ScanStats>;
+
+    /// What [`Self::scan`] would change, without changing it.
+    fn plan_scan(&self, scanner: &Scanner, root: &Path) -> Result<ScanReport>;
+}
+
+/// Queries and updates run by command handlers. Method semantics are those
+/// of the [`Database`] methods of the same name.
+pub trait LibraryStore: Send + std::fmt::Debug {
+    fn get_song_by_path(&self, path: &str) -> Result<Option<Song>>;
+    fn get_all_songs(&self) -> Result<Vec<Song>>;
+    fn add_song(&self, song: &Song) -> Result<u64>;
+    fn find_songs(&self, tag: &str, value: &str) -> Result<Vec<Song>>;
+    fn find_songs_any(&self, value: &str) -> Result<Vec<Song>>;
+    fn find_songs_by_prefix(&self, prefix: &str) -> Result<Vec<Song>>;
+    fn find_songs_filter(&self, filter: &FilterExpression) -> Result<Vec<Song>>;
+    fn search_songs(&self, query: &str) -> Result<Vec<Song>>;
+    fn search_songs_by_tag(&self, tag: &str, value: &str) -> Result<Vec<Song>>;
+    fn list_tag_values(&self, tag: &str) -> Result<Vec<String>>;
+    fn list_filtered(&self, tag: &str, filter_tag: &str, filter_value: &str)
+    -> Result<Vec<String>>;
+
+    fn list_directory(&self, path: &str) -> Result<DirectoryListing>;
+    fn get_directory_mtime(&self, path: &str) -> Result<Option<i64>>;
+    fn walk_recursive(
+        &self,
+        path: &str,
+        visitor: &mut dyn FnMut(WalkEntry<'_>) -> Result<()>,
+    ) -> Result<()>;
+
+    /// `(songs, artists, albums, playtime, last update)`.
+    fn get_stats(&self) -> Result<(u32, u32, u32, u64, i64)>;
+    fn format_report(&self, dir: &str, resolve: &dyn Fn(&str) -> String) -> Result<FormatReport>;
+    fn get_lyrics(&self, path: &str) -> Result<Option<Lyrics>>;
+
+    fn index_playlist(&self, name: &str, uris: &[String]) -> Result<()>;
+    fn list_playlists(&self) -> Result<Vec<PlaylistInfo>>;
+    fn delete_playlist(&self, name: &str) -> Result<()>;
+
+    fn save_queue_snapshot(
+        &self,
+        name: &str,
+        uris: &[String],
+        current: Option<u32>,
+        elapsed: f64,
+    ) -> Result<()>;
+    fn get_queue_snapshot(&self, name: &str) -> Result<Option<QueueSnapshot>>;
+    fn list_queue_snapshots(&self) -> Result<Vec<QueueSnapshot>>;
+    fn delete_queue_snapshot(&self, name: &str) -> Result<()>;
+
+    fn get_artwork(&self, path: &str, picture_type: &str) -> Result<Option<(Vec<u8>, String)>>;
+    fn has_artwork(&self, path: &str, picture_type: &str) -> Result<bool>;
+    fn get_artwork_hash(&self, path: &str, picture_type: &str) -> Result<Option<String>>;
+    fn store_artwork(
+        &self,
+        path: &str,
+        picture_type: &str,
+        mime_type: &str,
+        data: &[u8],
+        hash: &str,
+    ) -> Result<()>;
+    /// `(chunk, mime type, total size)`.
+    fn read_artwork_chunk(
+        &self,
+        path: &str,
+        picture_type: &str,
+        offset: usize,
+        len: usize,
+    ) -> Result<Option<(Vec<u8>, String, usize)>>;
+
+    fn get_sticker(&self, uri: &str, name: &str) -> Result<Option<String>>;
+    fn set_sticker(&self, uri: &str, name: &str, value: &str) -> Result<()>;
+    fn delete_sticker(&self, uri: &str, name: Option<&str>) -> Result<()>;
+    fn list_stickers(&self, uri: &str) -> Result<Vec<(String, String)>>;
+    fn find_stickers(&self, uri: &str, name: &str) -> Result<Vec<(String, String)>>;
+    fn sticker_names(&self) -> Result<Vec<String>>;
+    fn sticker_names_types(&self, sticker_type: Option<&str>) -> Result<Vec<(String, String)>>;
+}
+
+impl LibraryBackend for DbPool {
+    fn store(&self) -> Result<Box<dyn LibraryStore>> {
+        Ok(Box::new(Database::from_pool(self)?))
+    }
+
+    // Scans get their own connection: they hold write transactions for a
+    // long time and would otherwise return a busy connection to the pool.
+    fn scan(&self, scanner: &Scanner, root: &Path) -> Result<ScanStats> {
+        scanner.scan_directory(&Database::open(self.path())?, root)
+    }
+
+    fn plan_scan(&self, scanner: &Scanner, root: &Path) -> Result<ScanReport> {
+        scanner.plan_directory(&Database::from_pool(self)?, root)
+    }
+}
+
+impl LibraryStore for Database {
+    fn get_song_by_path(&self, path: &str) -> Result<Option<Song>> {
+        Database::get_song_by_path(self, path)
+    }
+
+    fn get_all_songs(&self) -> Result<Vec<Song>> {
+        Database::get_all_songs(self)
+    }
+
+    fn add_song(&self, song: &Song) -> Result<u64> {
+        Database::add_song(self, song)
+    }
+
+    fn find_songs(&self, tag: &str, value: &str) -> Result<Vec<Song>> {
+        Database::find_songs(self, tag, value)
+    }
+
+    fn find_songs_any(&self, value: &str) -> Result<Vec<Song>> {
+        Database::find_songs_any(self, value)
+    }
+
+    fn find_songs_by_prefix(&self, prefix: &str) -> Result<Vec<Song>> {
+        Database::find_songs_by_prefix(self, prefix)
+    }
+
+    fn find_songs_filter(&self, filter: &FilterExpression) -> Result<Vec<Song>> {
+        Database::find_songs_filter(self, filter)
+    }
+
+    fn search_songs(&self, query: &str) -> Result<Vec<Song>> {
+        Database::search_songs(self, query)
+    }
+
+    fn search_songs_by_tag(&self, tag: &str, value: &str) -> Result<Vec<Song>> {
+        Database::search_songs_by_tag(self, tag, value)
+    }
+
+    fn list_tag_values(&self, tag: &str) -> Result<Vec<String>> {
+        Database::list_tag_values(self, tag)
+    }
+
+    fn list_filtered(
+        &self,
+        tag: &str,
+        filter_tag: &str,
+        filter_value: &str,
+    ) -> Result<Vec<String>> {
+        Database::list_filtered(self, tag, filter_tag, filter_value)
+    }
+
+    fn list_directory(&self, path: &str) -> Result<DirectoryListing> {
+        Database::list_directory(self, path)
+    }
+
+    fn get_directory_mtime(&self, path: &str) -> Result<Option<i64>> {
+        Database::get_directory_mtime(self, path)
+    }
+
+    fn walk_recursive(
+        &self,
+        path: &str,
+        visitor: &mut dyn FnMut(WalkEntry<'_>) -> Result<()>,
+    ) -> Result<()> {
+        Database::walk_recursive(self, path, &mut |entry| visitor(entry))
+    }
+
+    fn get_stats(&self) -> Result<(u32, u32, u32, u64, i64)> {
+        Database::get_stats(self)
+    }
+
+    fn format_report(&self, dir: &str, resolve: &dyn Fn(&str) -> String) -> Result<FormatReport> {
+        Database::format_report(self, dir, resolve)
+    }
+
+    fn get_lyrics(&self, path: &str) -> Result<Option<Lyrics>> {
+        Database::get_lyrics(self, path)
+    }
+
+    fn index_playlist(&self, name: &str, uris: &[String]) -> Result<()> {
+        Database::index_playlist(self, name, uris)
+    }
+
+    fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        Database::list_playlists(self)
+    }
+
+    fn delete_playlist(&self, name: &str) -> Result<()> {
+        Database::delete_playlist(self, name)
+    }
+
+    fn save_queue_snapshot(
+        &self,
+        name: &str,
+        uris: &[String],
+        current: Option<u32>,
+        elapsed: f64,
+    ) -> Result<()> {
+        Database::save_queue_snapshot(self, name, uris, current, elapsed)
+    }
+
+    fn get_queue_snapshot(&self, name: &str) -> Result<Option<QueueSnapshot>> {
+        Database::get_queue_snapshot(self, name)
+    }
+
+    fn list_queue_snapshots(&self) -> Result<Vec<QueueSnapshot>> {
+        Database::list_queue_snapshots(self)
+    }
+
+    fn delete_queue_snapshot(&self, name: &str) -> Result<()> {
+        Database::delete_queue_snapshot(self, name)
+    }
+
+    fn get_artwork(&self, path: &str, picture_type: &str) -> Result<Option<(Vec<u8>, String)>> {
+        Database::get_artwork(self, path, picture_type)
+    }
+
+    fn has_artwork(&self, path: &str, picture_type: &str) -> Result<bool> {
+        Database::has_artwork(self, path, picture_type)
+    }
+
+    fn get_artwork_hash(&self, path: &str, picture_type: &str) -> Result<Option<String>> {
+        Database::get_artwork_hash(self, path, picture_type)
+    }
+
+    fn store_artwork(
+        &self,
+        path: &str,
+        picture_type: &str,
+        mime_type: &str,
+        data: &[u8],
+        hash: &str,
+    ) -> Result<()> {
+        Database::store_artwork(self, path, picture_type, mime_type, data, hash)
+    }
+
+    fn read_artwork_chunk(
+        &self,
+        path: &str,
+        picture_type: &str,
+        offset: usize,
+        len: usize,
+    ) -> Result<Option<(Vec<u8>, String, usize)>> {
+        Database::read_artwork_chunk(self, path, picture_type, offset, len)
+    }
+
+    fn get_sticker(&self, uri: &str, name: &str) -> Result<Option<String>> {
+        Database::get_sticker(self, uri, name)
+    }
+
+    fn set_sticker(&self, uri: &str, name: &str, value: &str) -> Result<()> {
+        Database::set_sticker(self, uri, name, value)
+    }
+
+    fn delete_sticker(&self, uri: &str, name: Option<&str>) -> Result<()> {
+        Database::delete_sticker(self, uri, name)
+    }
+
+    fn list_stickers(&self, uri: &str) -> Result<Vec<(String, String)>> {
+        Database::list_stickers(self, uri)
+    }
+
+    fn find_stickers(&self, uri: &str, name: &str) -> Result<Vec<(String, String)>> {
+        Database::find_stickers(self, uri, name)
+    }
+
+    fn sticker_names(&self) -> Result<Vec<String>> {
+        Database::sticker_names(self)
+    }
+
+    fn sticker_names_types(&self, sticker_type: Option<&str>) -> Result<Vec<(String, String)>> {
+        Database::sticker_names_types(self, sticker_type)
+    }
+}
@@ -0,0 +1,183 @@
+//! Scan-time tag rewriting from `[[tag_rule]]` config blocks.
+//!
+//! Rules run in order over every extracted song before it is stored, so
+//! library hygiene (unifying genre spellings, moving "feat." credits into
+//! `performer`) does not require retagging the files. Changing the rules only
+//! affects files as they are (re)scanned. The same pass enforces
+//! `metadata_to_use`, so masked tag types never reach the database.
+
+use regex::Regex;
+use rmpd_core::config::TagRule;
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::{Song, intern_tag_key};
+use rmpd_core::tag::MetadataMask;
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    tag: String,
+    pattern: Regex,
+    replace: String,
+    into: Option<String>,
+    value: String,
+}
+
+/// Compiled set of [`TagRule`]s, plus the optional `metadata_to_use` mask
+/// applied after them; empty (a no-op) by default.
+#[derive(Debug, Clone, Default)]
+pub struct TagRewriter {
+    rules: Vec<CompiledRule>,
+    mask: Option<MetadataMask>,
+}
+
+impl TagRewriter {
+    /// Compile `rules`, failing on the first invalid pattern.
+    pub fn new(rules: &[TagRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern).map_err(|e| {
+                    RmpdError::Config(format!("Invalid tag_rule pattern for {}: {e}", rule.tag))
+                })?;
+                Ok(CompiledRule {
+                    tag: rule.tag.to_lowercase(),
+                    pattern,
+                    replace: rule.replace.clone(),
+                    into: rule.into.as_deref().map(str::to_lowercase),
+                    value: rule.value.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules, mask: None })
+    }
+
+    /// Drop values of tag types outside `mask` once the rules have run, so
+    /// they are never stored.
+    #[must_use]
+    pub fn with_metadata_mask(mut self, mask: MetadataMask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.mask.is_none()
+    }
+
+    /// Apply every rule, then the metadata mask, to `song`'s tags in place.
+    pub fn apply(&self, song: &mut Song) {
+        for rule in &self.rules {
+            let mut extracted = Vec::new();
+            song.tags.retain_mut(|(key, value)| {
+                if key.as_ref() != rule.tag || !rule.pattern.is_match(value) {
+                    return true;
+                }
+                if rule.into.is_some() {
+                    for caps in rule.pattern.captures_iter(value) {
+                        let mut out = String::new();
+                        caps.expand(&rule.value, &mut out);
+                        let out = out.trim();
+                        if !out.is_empty() {
+                            extracted.push(out.to_owned());
+                        }
+                    }
+                }
+                let rewritten = rule.pattern.replace_all(value, rule.replace.as_str());
+                *value = rewritten.trim().to_owned();
+                !value.is_empty()
+            });
+            if let Some(into) = &rule.into {
+                for value in extracted {
+                    if !song.tag_values(into).any(|v| v == value) {
+                        song.tags.push((intern_tag_key(into), value));
+                    }
+                }
+            }
+        }
+        if let Some(mask) = &self.mask {
+            song.tags.retain(|(key, _)| mask.allows(key));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn rule(tag: &str, pattern: &str, replace: &str, into: Option<&str>) -> TagRule {
+        TagRule {
+            tag: tag.to_owned(),
+            pattern: pattern.to_owned(),
+            replace: replace.to_owned(),
+            into: into.map(str::to_owned),
+            value: "$1".to_owned(),
+        }
+    }
+
+    fn song(tags: &[(&'static str, &str)]) -> Song {
+        let mut song = rmpd_core::test_utils::create_test_song(1, "a");
+        song.tags = tags
+            .iter()
+            .map(|(k, v)| (Cow::Borrowed(*k), (*v).to_owned()))
+            .collect();
+        song
+    }
+
+    #[test]
+    fn rewrites_and_moves_values() {
+        let rewriter = TagRewriter::new(&[
+            rule(
+                "Genre",
+                r"(?i)^(original )?(motion picture )?(soundtrack|ost)$",
+                "Soundtrack",
+                None,
+            ),
+            rule(
+                "artist",
+                r"\s+(?:feat\.|ft\.)\s+(.+)$",
+                "",
+                Some("performer"),
+            ),
+            rule("comment", r".*", "", None),
+        ])
+        .unwrap();
+
+        let mut s = song(&[
+            ("artist", "Alice feat. Bob"),
+            ("genre", "Original Motion Picture Soundtrack"),
+            ("genre", "Jazz"),
+            ("comment", "ripped by someone"),
+        ]);
+        rewriter.apply(&mut s);
+
+        assert_eq!(s.tag("artist"), Some("Alice"));
+        assert_eq!(
+            s.tag_values("genre").collect::<Vec<_>>(),
+            ["Soundtrack", "Jazz"]
+        );
+        assert_eq!(s.tag("performer"), Some("Bob"));
+        assert_eq!(s.tag("comment"), None, "emptied values are dropped");
+    }
+
+    #[test]
+    fn metadata_mask_drops_tags_after_rules() {
+        let mask = MetadataMask::parse("artist,performer").unwrap();
+        let rewriter =
+            TagRewriter::new(&[rule("artist", r"\s+feat\.\s+(.+)$", "", Some("performer"))])
+                .unwrap()
+                .with_metadata_mask(mask);
+
+        let mut s = song(&[("artist", "Alice feat. Bob"), ("genre", "Jazz")]);
+        rewriter.apply(&mut s);
+
+        assert_eq!(s.tag("artist"), Some("Alice"));
+        assert_eq!(s.tag("performer"), Some("Bob"));
+        assert_eq!(s.tag("genre"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(TagRewriter::new(&[rule("genre", "(", "", None)]).is_err());
+        assert!(TagRewriter::default().is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+//! Writing tags back to audio files.
+//!
+//! Backs the opt-in `writetag`/`cleartag` extension commands. Only tags that
+//! map onto a lofty [`ItemKey`] can be written, and only to formats lofty can
+//! save (not TTA, and not DSD files lofty cannot open). Changes go to the
+//! file's primary tag, which is created when the file has none.
+
+use crate::metadata::ITEM_KEY_TAG_MAP;
+use camino::Utf8Path;
+use lofty::config::WriteOptions;
+use lofty::prelude::*;
+use lofty::tag::{ItemKey, Tag, TagType};
+use rmpd_core::error::{Result, RmpdError};
+
+/// Tags outside [`ITEM_KEY_TAG_MAP`] that can be written too.
+const EXTRA_WRITABLE_TAGS: &[&str] = &["date", "rating"];
+
+/// Whether `tag` (an rmpd tag name such as `title`, case-insensitive) can be
+/// written by [`write_tag`].
+pub fn is_writable(tag: &str) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    EXTRA_WRITABLE_TAGS.contains(&tag.as_str()) || ITEM_KEY_TAG_MAP.iter().any(|(_, n)| *n == tag)
+}
+
+/// The lofty key storing `tag` in a tag of type `tag_type`.
+fn item_key(tag_type: TagType, tag: &str) -> Option<ItemKey> {
+    match tag {
+        "date" => Some(ItemKey::RecordingDate),
+        // No common rating field: Vorbis comments and APE use `RATING`.
+        "rating" => ItemKey::from_key(tag_type, "RATING"),
+        _ => ITEM_KEY_TAG_MAP
+            .iter()
+            .find(|(_, name)| *name == tag)
+            .map(|(key, _)| *key),
+    }
+}
+
+/// Set `tag` of the file at `path` to `value`, replacing every existing
+/// value, or remove the tag when `value` is `None`.
+pub fn write_tag(path: &Utf8Path, tag: &str, value: Option<&str>) -> Result<()> {
+    let tag = tag.to_ascii_lowercase();
+    let mut tagged_file = lofty::read_from_path(path)?;
+    let tag_type = tagged_file.primary_tag_type();
+    let key = item_key(tag_type, &tag).ok_or_else(|| {
+        RmpdError::Library(format!("{tag} cannot be written to {tag_type:?} tags"))
+    })?;
+
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(file_tag) = tagged_file.tag_mut(tag_type) else {
+        return Err(RmpdError::Library(format!("{path}: cannot create a tag")));
+    };
+
+    match value {
+        Some(value) => {
+            if !file_tag.insert_text(key, value.to_owned()) {
+                return Err(RmpdError::Library(format!(
+                    "{tag} cannot be written to {tag_type:?} tags"
+                )));
+            }
+        }
+        None => {
+            file_tag.remove_key(key);
+        }
+    }
+    file_tag.save_to_path(path, WriteOptions::default())?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! TTA (True Audio) header parsing.
+//!
+//! lofty does not open TTA files, so the scanner reads the `TTA1` header
+//! itself. Tags are read with [`crate::rawtag`]: APEv2 at the end of the file
+//! (what most taggers write), or an ID3v2 tag in front of the header.
+
+use rmpd_core::error::{Result, RmpdError};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Audio properties and leading ID3v2 tag of a TTA file.
+#[derive(Debug, Clone)]
+pub struct TtaInfo {
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bits_per_sample: u16,
+    pub samples_per_channel: u64,
+    /// Raw ID3v2 tag in front of the TTA header, if any.
+    pub id3: Option<Vec<u8>>,
+}
+
+impl TtaInfo {
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.samples_per_channel as f64 / f64::from(self.sample_rate))
+    }
+}
+
+/// Whether `path` has the `.tta` extension.
+pub fn is_tta_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("tta"))
+}
+
+/// Read the `TTA1` header of `path`, skipping a leading ID3v2 tag.
+pub fn read_tta_info(path: &Path) -> Result<TtaInfo> {
+    let io = |e: std::io::Error| RmpdError::Library(format!("Failed to read TTA file: {e}"));
+    let mut reader = BufReader::new(File::open(path).map_err(io)?);
+
+    let mut header = [0u8; 22];
+    reader.read_exact(&mut header[..10]).map_err(io)?;
+    let mut id3 = None;
+    if header.starts_with(b"ID3") {
+        let size = header[6..10]
+            .iter()
+            .fold(0usize, |acc, &x| (acc << 7) | usize::from(x & 0x7F));
+        // Footer flag: a second 10-byte block follows the frames.
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        let mut tag = header[..10].to_vec();
+        tag.resize(10 + size, 0);
+        reader.read_exact(&mut tag[10..]).map_err(io)?;
+        reader.seek(SeekFrom::Current(footer)).map_err(io)?;
+        id3 = Some(tag);
+        reader.read_exact(&mut header[..10]).map_err(io)?;
+    }
+    reader.read_exact(&mut header[10..]).map_err(io)?;
+
+    if !header.starts_with(b"TTA1") {
+        return Err(RmpdError::Library(format!(
+            "Not a TTA file: {}",
+            path.display()
+        )));
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().expect("4 bytes"));
+    Ok(TtaInfo {
+        channels: u8::try_from(u16_at(6)).unwrap_or(0),
+        bits_per_sample: u16_at(8),
+        sample_rate: u32_at(10),
+        samples_per_channel: u64::from(u32_at(14)),
+        id3,
+    })
+}
@@ -0,0 +1,55 @@
+//! Serialization of library writers.
+//!
+//! A library scan, the filesystem watcher and the other jobs that rewrite
+//! songs (source syncs, `writetag`) all write the same database. Run at the
+//! same time they race: the watcher re-adds a file while the scan is pruning
+//! its directory, or both insert the same new song. Each takes [`lock`] for
+//! the length of its work, so they run one after another in arrival order
+//! instead. Like the database file, the lock is process-wide.
+
+use std::sync::{Mutex, MutexGuard};
+
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held while one writer updates the library; dropping it lets the next run.
+#[must_use = "the library is only locked while the guard is held"]
+pub struct UpdateGuard {
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Wait until no other writer is updating the library, then keep others out
+/// until the returned guard is dropped. Blocking.
+pub fn lock() -> UpdateGuard {
+    UpdateGuard {
+        // A writer that panicked left nothing to repair: the database rolls
+        // back its own unfinished transactions.
+        _guard: UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn writers_run_one_at_a_time() {
+        let guard = lock();
+        let ran = Arc::new(AtomicBool::new(false));
+        let waiter = {
+            let ran = Arc::clone(&ran);
+            std::thread::spawn(move || {
+                let _guard = lock();
+                ran.store(true, Ordering::SeqCst);
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!ran.load(Ordering::SeqCst), "second writer waits");
+        drop(guard);
+        waiter.join().unwrap();
+        assert!(ran.load(Ordering::SeqCst));
+    }
+}
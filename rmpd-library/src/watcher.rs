@@ -10,8 +10,11 @@ use tokio::sync::Mutex;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+use crate::artwork::COVER_FILE_NAMES;
 use crate::database::Database;
+use crate::lyrics;
 use crate::metadata::MetadataExtractor;
+use crate::tag_rules::TagRewriter;
 
 const DEBOUNCE_DURATION: Duration = Duration::from_millis(300);
 const EVENT_CHANNEL_SIZE: usize = 1024;
@@ -20,6 +23,7 @@ pub struct FilesystemWatcher {
     music_dir: PathBuf,
     db: Arc<Mutex<Database>>,
     event_bus: EventBus,
+    tag_rewriter: TagRewriter,
     debouncer: Option<Debouncer<RecommendedWatcher, RecommendedCache>>,
 }
 
@@ -39,10 +43,17 @@ impl FilesystemWatcher {
             music_dir,
             db,
             event_bus,
+            tag_rewriter: TagRewriter::default(),
             debouncer: None,
         })
     }
 
+    /// Rewrite tags of changed songs with `rewriter`, as the scanner does.
+    pub fn with_tag_rewriter(mut self, rewriter: TagRewriter) -> Self {
+        self.tag_rewriter = rewriter;
+        self
+    }
+
     /// Start watching the music directory
     pub async fn start(&mut self) -> Result<()> {
         info!("starting filesystem watcher for {:?}", self.music_dir);
@@ -51,6 +62,7 @@ impl FilesystemWatcher {
         let db = Arc::clone(&self.db);
         let event_bus = self.event_bus.clone();
         let music_dir = self.music_dir.clone();
+        let tag_rewriter = self.tag_rewriter.clone();
 
         // Create debouncer
         let debouncer = new_debouncer(
@@ -79,27 +91,36 @@ impl FilesystemWatcher {
         // Emit start event
         self.event_bus.emit(RmpdEvent::FilesystemWatchStarted);
 
-        // Spawn event handler task
-        tokio::spawn(async move {
-            while let Some(result) = rx.recv().await {
-                match result {
-                    Ok(events) => {
-                        for event in events {
-                            if let Err(e) =
-                                handle_fs_event(&event, &music_dir, &db, &event_bus).await
-                            {
-                                error!("failed to handle filesystem event: {}", e);
+        // Handle events on a dedicated thread: tag extraction and database
+        // writes block, and must not stall the async runtime. The thread ends
+        // when the debouncer (and with it the sender) is dropped.
+        std::thread::Builder::new()
+            .name("rmpd-watcher".to_owned())
+            .spawn(move || {
+                while let Some(result) = rx.blocking_recv() {
+                    match result {
+                        Ok(events) => {
+                            for event in events {
+                                if let Err(e) = handle_fs_event(
+                                    &event,
+                                    &music_dir,
+                                    &db,
+                                    &event_bus,
+                                    &tag_rewriter,
+                                ) {
+                                    error!("failed to handle filesystem event: {}", e);
+                                }
                             }
                         }
-                    }
-                    Err(errors) => {
-                        for error in errors {
-                            error!("filesystem watch error: {}", error);
+                        Err(errors) => {
+                            for error in errors {
+                                error!("filesystem watch error: {}", error);
+                            }
                         }
                     }
                 }
-            }
-        });
+            })
+            .map_err(|e| RmpdError::Library(format!("Failed to start watcher thread: {e}")))?;
 
         Ok(())
     }
@@ -120,12 +141,16 @@ impl Drop for FilesystemWatcher {
     }
 }
 
-async fn handle_fs_event(
+fn handle_fs_event(
     event: &Event,
     music_dir: &Path,
     db: &Arc<Mutex<Database>>,
     event_bus: &EventBus,
+    tag_rewriter: &TagRewriter,
 ) -> Result<()> {
+    // Wait for a running scan, which may be writing the same songs.
+    let _update = crate::update_lock::lock();
+
     // Filter out non-audio files and hidden files
     let is_audio_file = |path: &Path| -> bool {
         if let Some(name) = path.file_name()
@@ -139,15 +164,64 @@ async fn handle_fs_event(
             .map(|ext| {
                 matches!(
                     ext.to_lowercase().as_str(),
-                    "mp3" | "flac" | "ogg" | "opus" | "m4a" | "aac" | "wav" | "wv" | "ape" | "mpc"
+                    "mp3"
+                        | "flac"
+                        | "ogg"
+                        | "opus"
+                        | "m4a"
+                        | "aac"
+                        | "wav"
+                        | "wv"
+                        | "ape"
+                        | "mpc"
+                        | "tta"
+                        | "dsf"
+                        | "dff"
                 )
             })
             .unwrap_or(false)
     };
+    let is_lyrics_sidecar = |path: &Path| -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lrc"))
+    };
+    let is_cover_file = |path: &Path| -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                COVER_FILE_NAMES
+                    .iter()
+                    .any(|cover| name.eq_ignore_ascii_case(cover))
+            })
+    };
+
+    // A cover file applies to its whole directory, and its in-memory copy is
+    // dropped once the file or directory changes, so there is nothing cached
+    // to drop here: tell clients to refetch. This holds for creation,
+    // modification and removal alike.
+    if matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        for path in event.paths.iter().filter(|p| is_cover_file(p)) {
+            if let Some(dir) = path.parent()
+                && let Ok(relative_dir) = dir.strip_prefix(music_dir)
+            {
+                let dir_str = rmpd_core::path::to_uri(&relative_dir.to_string_lossy()).into_owned();
+                debug!("cover changed: {:?}", path);
+                event_bus.emit(RmpdEvent::ArtworkChanged { path: dir_str });
+            }
+        }
+    }
 
     match event.kind {
         EventKind::Create(_) | EventKind::Modify(_) => {
             for path in &event.paths {
+                if is_lyrics_sidecar(path) {
+                    refresh_sidecar_lyrics(path, music_dir, db, &is_audio_file)?;
+                    continue;
+                }
                 if !is_audio_file(path) {
                     continue;
                 }
@@ -161,22 +235,35 @@ async fn handle_fs_event(
                     }
                 };
 
-                let path_str = relative_path.to_string_lossy().to_string();
+                let path_str =
+                    rmpd_core::path::to_uri(&relative_path.to_string_lossy()).into_owned();
 
                 debug!("file created/modified: {}", path_str);
 
                 // Extract metadata
                 let path_buf = camino::Utf8PathBuf::from(path.to_string_lossy().to_string());
                 match MetadataExtractor::extract_from_file(&path_buf) {
-                    Ok(song) => {
+                    Ok(mut song) => {
+                        tag_rewriter.apply(&mut song);
+                        let lyrics = lyrics::read_lyrics(path);
+
                         // Database operations need to be done with lock
-                        let db_guard = db.lock().await;
+                        let db_guard = db.blocking_lock();
 
                         // Check if song already exists
                         let exists = db_guard.get_song_by_path(&path_str)?.is_some();
 
                         // Add/update in database
                         db_guard.add_song(&song)?;
+                        match &lyrics {
+                            Some(lyrics) => db_guard.store_lyrics(song.path.as_str(), lyrics)?,
+                            None => db_guard.delete_lyrics(song.path.as_str())?,
+                        }
+                        // The embedded picture may have changed with the tags.
+                        let artwork_dropped = db_guard.delete_artwork(&path_str)? > 0;
+                        if let Err(e) = db_guard.derive_album_gains_near(&path_str) {
+                            warn!("failed to derive album gains: {e}");
+                        }
 
                         drop(db_guard); // Release lock before emitting event
 
@@ -188,6 +275,9 @@ async fn handle_fs_event(
                             debug!("song added: {}", path_str);
                             event_bus.emit(RmpdEvent::SongAdded(song));
                         }
+                        if artwork_dropped {
+                            event_bus.emit(RmpdEvent::ArtworkChanged { path: path_str });
+                        }
                     }
                     Err(e) => {
                         warn!("failed to extract metadata from {}: {}", path_str, e);
@@ -197,28 +287,45 @@ async fn handle_fs_event(
         }
         EventKind::Remove(_) => {
             for path in &event.paths {
-                if !is_audio_file(path) {
+                if is_lyrics_sidecar(path) {
+                    refresh_sidecar_lyrics(path, music_dir, db, &is_audio_file)?;
                     continue;
                 }
-
                 let relative_path = match path.strip_prefix(music_dir) {
                     Ok(p) => p,
                     Err(_) => continue,
                 };
 
-                let path_str = relative_path.to_string_lossy().to_string();
+                let path_str =
+                    rmpd_core::path::to_uri(&relative_path.to_string_lossy()).into_owned();
 
-                debug!("file removed: {}", path_str);
+                if path_str.is_empty() {
+                    continue;
+                }
 
-                // Remove from database
-                let db_guard = db.lock().await;
-                db_guard.delete_song_by_path(&path_str)?;
-                drop(db_guard);
+                // The path is gone, so whether it was a file or a directory
+                // (`Vol. 2` looks like a file) is up to the index. A removed
+                // directory is reported once, not per file: drop the indexed
+                // songs below it that are gone too.
+                let db_guard = db.blocking_lock();
+                let mut doomed: Vec<String> = db_guard
+                    .local_song_paths_under(&path_str)?
+                    .into_iter()
+                    .filter(|p| !music_dir.join(p).exists())
+                    .collect();
+                if is_audio_file(path) {
+                    debug!("file removed: {}", path_str);
+                    doomed.push(path_str);
+                } else if doomed.is_empty() {
+                    continue;
+                }
+                let removed = db_guard.prune_songs(&doomed)?;
+                drop(db_guard); // Release lock before emitting events
 
-                // Emit event
-                event_bus.emit(RmpdEvent::SongDeleted {
-                    path: path_str.clone(),
-                });
+                for path in removed {
+                    debug!("song removed: {}", path);
+                    event_bus.emit(RmpdEvent::SongDeleted { path });
+                }
             }
         }
         _ => {
@@ -228,3 +335,93 @@ async fn handle_fs_event(
 
     Ok(())
 }
+
+/// Re-read the lyrics of every indexed audio file next to the `.lrc` sidecar
+/// `lrc` with the same stem (after it was created, changed or removed).
+fn refresh_sidecar_lyrics(
+    lrc: &Path,
+    music_dir: &Path,
+    db: &Arc<Mutex<Database>>,
+    is_audio_file: &(dyn Fn(&Path) -> bool + Sync),
+) -> Result<()> {
+    let (Some(dir), Some(stem)) = (lrc.parent(), lrc.file_stem()) else {
+        return Ok(());
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        let audio = entry.path();
+        if audio.file_stem() != Some(stem) || !is_audio_file(&audio) {
+            continue;
+        }
+        let Ok(relative_path) = audio.strip_prefix(music_dir) else {
+            continue;
+        };
+        let path_str = rmpd_core::path::to_uri(&relative_path.to_string_lossy()).into_owned();
+        let lyrics = lyrics::read_lyrics(&audio);
+
+        let db_guard = db.blocking_lock();
+        if db_guard.get_song_by_path(&path_str)?.is_none() {
+            continue;
+        }
+        match &lyrics {
+            Some(lyrics) => db_guard.store_lyrics(&path_str, lyrics)?,
+            None => db_guard.delete_lyrics(&path_str)?,
+        }
+        debug!("lyrics refreshed: {}", path_str);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::RemoveKind;
+    use rmpd_core::test_utils::make_test_song;
+
+    #[test]
+    fn removing_a_dotted_directory_drops_its_songs() {
+        let music_dir = tempfile::TempDir::new().unwrap();
+        let album = music_dir.path().join("Artist/Vol. 2");
+        std::fs::create_dir_all(&album).unwrap();
+        std::fs::write(music_dir.path().join("Artist/kept.flac"), b"").unwrap();
+
+        let db_path = music_dir.path().join("rmpd.db");
+        let db = Database::open(db_path.to_str().unwrap()).unwrap();
+        for (track, path) in [
+            "Artist/Vol. 2/01.flac",
+            "Artist/Vol. 2/02.flac",
+            "Artist/kept.flac",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            db.add_song(&make_test_song(path, track as u32 + 1))
+                .unwrap();
+        }
+        let db = Arc::new(Mutex::new(db));
+        let event_bus = EventBus::new();
+        let mut rx = event_bus.subscribe();
+
+        std::fs::remove_dir_all(&album).unwrap();
+        let event = Event::new(EventKind::Remove(RemoveKind::Any)).add_path(album);
+        handle_fs_event(
+            &event,
+            music_dir.path(),
+            &db,
+            &event_bus,
+            &TagRewriter::default(),
+        )
+        .unwrap();
+
+        let db = db.blocking_lock();
+        assert_eq!(db.list_local_song_paths().unwrap(), ["Artist/kept.flac"]);
+        let mut removed = Vec::new();
+        while let Ok(RmpdEvent::SongDeleted { path }) = rx.try_recv() {
+            removed.push(path);
+        }
+        assert_eq!(removed, ["Artist/Vol. 2/01.flac", "Artist/Vol. 2/02.flac"]);
+    }
+}
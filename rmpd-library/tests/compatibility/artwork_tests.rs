@@ -3,7 +3,7 @@
 /// These tests validate that rmpd correctly extracts embedded artwork
 /// from audio files and stores it in the database.
 use crate::common::rmpd_harness::RmpdTestHarness;
-use crate::fixtures::{AudioFormat, FixtureGenerator, TestMetadata};
+use crate::fixtures::{AudioFormat, FixtureGenerator, TestMetadata, dsd};
 
 /// Helper to check if FFmpeg is available
 macro_rules! require_ffmpeg {
@@ -287,3 +287,229 @@ fn test_artwork_across_formats() {
         }
     }
 }
+
+// ── Container-specific fixtures ──────────────────────────────────────────────
+//
+// FFmpeg cannot write covers into every container, so these tests build the
+// tag bytes themselves: APEv2 binary items (MP3, WavPack) and the ID3v2 chunk
+// of DSD files (DSF, DFF).
+
+/// Small PNG-looking payload with embedded NULs, so a parser that stops at
+/// the first zero byte is caught.
+fn test_cover() -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    data.extend((0..600u32).map(|i| (i % 7) as u8));
+    data
+}
+
+/// Replace any trailing APEv2 tag of `path` with one holding a binary
+/// `Cover Art (Front)` item.
+fn append_apev2_cover(path: &std::path::Path, image: &[u8]) {
+    let mut file = std::fs::read(path).unwrap();
+    if file.len() >= 32 && file[file.len() - 32..].starts_with(b"APETAGEX") {
+        let footer = &file[file.len() - 32..];
+        let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as usize;
+        let flags = u32::from_le_bytes(footer[20..24].try_into().unwrap());
+        let header = if flags & 0x8000_0000 != 0 { 32 } else { 0 };
+        file.truncate(file.len() - size - header);
+    }
+
+    let mut value = b"cover.png\0".to_vec();
+    value.extend_from_slice(image);
+    let mut item = (value.len() as u32).to_le_bytes().to_vec();
+    item.extend_from_slice(&2u32.to_le_bytes()); // binary item
+    item.extend_from_slice(b"Cover Art (Front)\0");
+    item.extend_from_slice(&value);
+
+    let block = |flags: u32| {
+        let mut b = b"APETAGEX".to_vec();
+        b.extend_from_slice(&2000u32.to_le_bytes());
+        b.extend_from_slice(&((item.len() + 32) as u32).to_le_bytes());
+        b.extend_from_slice(&1u32.to_le_bytes());
+        b.extend_from_slice(&flags.to_le_bytes());
+        b.extend_from_slice(&[0; 8]);
+        b
+    };
+    file.extend(block(0xA000_0000)); // header
+    file.extend_from_slice(&item);
+    file.extend(block(0x8000_0000)); // footer
+    std::fs::write(path, file).unwrap();
+}
+
+/// Copy a cached fixture so the test can modify it.
+fn scratch_copy(dir: &tempfile::TempDir, src: &std::path::Path) -> std::path::PathBuf {
+    let dst = dir.path().join(src.file_name().unwrap());
+    std::fs::copy(src, &dst).unwrap();
+    dst
+}
+
+fn assert_front_cover(harness: &RmpdTestHarness, path: &std::path::Path, image: &[u8]) {
+    let artworks = harness.extract_artwork(path.to_str().unwrap()).unwrap();
+    let cover = artworks
+        .iter()
+        .find(|a| a.data == image)
+        .unwrap_or_else(|| panic!("no cover found in {}", path.display()));
+    assert_eq!(cover.mime_type, "image/png");
+}
+
+#[test]
+fn test_artwork_from_dsf_id3_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+    let path = dir.path().join("track.dsf");
+    let tag = dsd::id3v2_tag(3, &[dsd::id3_apic_frame(3, &test_cover())]);
+    dsd::write_dsf(&path, dsd::DSD64, 2, 1, Some(&tag));
+
+    assert_front_cover(&harness, &path, &test_cover());
+}
+
+#[test]
+fn test_artwork_from_dff_id3_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+    let path = dir.path().join("track.dff");
+    let tag = dsd::id3v2_tag(4, &[dsd::id3_apic_frame(4, &test_cover())]);
+    dsd::write_dff(&path, dsd::DSD64, 2, 4096, Some(&tag));
+
+    assert_front_cover(&harness, &path, &test_cover());
+}
+
+#[test]
+fn test_artwork_from_apev2_on_mp3() {
+    require_ffmpeg!();
+
+    let generator = FixtureGenerator::new().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    // ID3v2 (primary tag) carries text only; the cover lives in APEv2.
+    let fixture = generator
+        .generate(AudioFormat::Mp3, &TestMetadata::default())
+        .unwrap();
+    let path = scratch_copy(&dir, &fixture);
+    append_apev2_cover(&path, &test_cover());
+
+    assert_front_cover(&harness, &path, &test_cover());
+}
+
+#[test]
+fn test_artwork_from_wavpack() {
+    require_ffmpeg!();
+
+    let generator = FixtureGenerator::new().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let fixture = generator
+        .generate(AudioFormat::WavPack, &TestMetadata::default())
+        .unwrap();
+    let path = scratch_copy(&dir, &fixture);
+    append_apev2_cover(&path, &test_cover());
+
+    assert_front_cover(&harness, &path, &test_cover());
+}
+
+#[test]
+fn test_artwork_from_mp4_covr() {
+    require_ffmpeg!();
+
+    let generator = FixtureGenerator::new().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+
+    let metadata = TestMetadata {
+        title: "Test covr".to_string(),
+        artist: "Test Artist".to_string(),
+        album: "Test Album".to_string(),
+        ..Default::default()
+    };
+    let path = generator
+        .generate_with_artwork(AudioFormat::M4a, &metadata)
+        .unwrap();
+    let artworks = harness.extract_artwork(path.to_str().unwrap()).unwrap();
+
+    assert!(!artworks.is_empty(), "covr atom should yield a picture");
+    assert_eq!(artworks[0].mime_type, "image/png");
+    assert!(artworks[0].data.starts_with(b"\x89PNG\r\n\x1a\n"));
+}
+
+#[test]
+fn test_cached_artwork_is_served_in_chunks() {
+    use rmpd_library::AlbumArtExtractor;
+    use rmpd_library::database::Database;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::open(dir.path().join("test.db").to_str().unwrap()).unwrap();
+    db.add_song(&rmpd_core::test_utils::make_test_song("a.flac", 1))
+        .unwrap();
+    let mut image = test_cover();
+    image.extend((0..20_000u32).map(|i| (i % 251) as u8));
+
+    let extractor = AlbumArtExtractor::new(Box::new(db));
+    extractor.cache_external("a.flac", &image).unwrap();
+
+    let mut served = Vec::new();
+    while served.len() < image.len() {
+        let chunk = extractor
+            .get_artwork("a.flac", "", served.len())
+            .unwrap()
+            .expect("artwork is cached");
+        assert_eq!(chunk.total_size, image.len());
+        assert_eq!(chunk.mime_type, "image/png");
+        assert!(!chunk.data.is_empty() && chunk.data.len() <= 8192);
+        served.extend(chunk.data);
+    }
+    assert_eq!(served, image);
+
+    let past_end = extractor.get_artwork("a.flac", "", image.len()).unwrap();
+    assert!(past_end.unwrap().data.is_empty());
+}
+
+#[test]
+fn test_chunk_size_follows_binary_limit() {
+    use rmpd_library::AlbumArtExtractor;
+    use rmpd_library::database::Database;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::open(dir.path().join("test.db").to_str().unwrap()).unwrap();
+    db.add_song(&rmpd_core::test_utils::make_test_song("a.flac", 1))
+        .unwrap();
+    let mut image = test_cover();
+    image.extend((0..20_000u32).map(|i| (i % 251) as u8));
+
+    let extractor = AlbumArtExtractor::new(Box::new(db)).with_chunk_size(1024);
+    extractor.cache_external("a.flac", &image).unwrap();
+    let chunk = extractor.get_artwork("a.flac", "", 0).unwrap().unwrap();
+    assert_eq!(chunk.data, image[..1024]);
+
+    let whole = extractor.with_chunk_size(image.len() * 2);
+    let chunk = whole.get_artwork("a.flac", "", 100).unwrap().unwrap();
+    assert_eq!(chunk.data, image[100..]);
+}
+
+#[test]
+fn test_song_cover_comes_from_directory_file() {
+    use rmpd_library::AlbumArtExtractor;
+    use rmpd_library::database::Database;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::open(dir.path().join("test.db").to_str().unwrap()).unwrap();
+    let album = dir.path().join("Album");
+    std::fs::create_dir(&album).unwrap();
+    let song = album.join("01.flac");
+    let song = song.to_str().unwrap();
+
+    let extractor = AlbumArtExtractor::new(Box::new(db));
+    assert!(extractor.get_song_cover(song, 0).unwrap().is_none());
+
+    // `folder.*` is preferred over `front.*`, whatever the case.
+    std::fs::write(album.join("front.png"), b"front").unwrap();
+    std::fs::write(album.join("FOLDER.jpg"), b"folder").unwrap();
+    let cover = extractor.get_song_cover(song, 0).unwrap().unwrap();
+    assert_eq!(cover.data, b"folder");
+
+    // A changed cover file is picked up, not served from the cache.
+    std::fs::write(album.join("FOLDER.jpg"), b"new folder").unwrap();
+    let cover = extractor.get_song_cover(song, 0).unwrap().unwrap();
+    assert_eq!(cover.data, b"new folder");
+    assert_eq!(cover.total_size, 10);
+}
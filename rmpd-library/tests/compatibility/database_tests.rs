@@ -172,6 +172,35 @@ fn test_find_by_album() {
     }
 }
 
+#[test]
+fn test_find_by_album_orders_discs() {
+    let harness = RmpdTestHarness::new().unwrap();
+
+    // Filenames sort the two discs' tracks into each other.
+    for (path, disc, track) in [
+        ("Box/01 a.flac", 1, 1),
+        ("Box/01 b.flac", 2, 1),
+        ("Box/02 a.flac", 1, 2),
+        ("Box/02 b.flac", 2, 2),
+    ] {
+        let mut song = rmpd_core::test_utils::make_test_song(path, track);
+        song.tags.push(("disc".into(), disc.to_string()));
+        harness.add_song(&song).unwrap();
+    }
+
+    let result = harness.find_by_album("Test Album").unwrap();
+    let paths: Vec<&str> = result.iter().map(|s| s.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "Box/01 a.flac",
+            "Box/02 a.flac",
+            "Box/01 b.flac",
+            "Box/02 b.flac"
+        ]
+    );
+}
+
 #[test]
 fn test_count_songs() {
     require_ffmpeg!();
@@ -769,3 +798,339 @@ fn test_fts_contentless_delete_migration_from_legacy_db() {
     assert_eq!(db2.count_songs().unwrap(), 1);
     assert_eq!(db2.search_songs("legacbeta").unwrap().len(), 1);
 }
+
+/// Build a local song tagged with `artist` and `album`.
+fn make_tagged_song(path: &str, artist: &str, album: &str) -> rmpd_core::song::Song {
+    let mut song = make_local_song(path);
+    song.tags.extend([
+        (
+            rmpd_core::song::intern_tag_key("artist"),
+            artist.to_string(),
+        ),
+        (rmpd_core::song::intern_tag_key("album"), album.to_string()),
+    ]);
+    song
+}
+
+/// The artists/albums tables follow every add, retag and delete.
+#[test]
+fn test_name_tables_track_tag_changes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir
+        .path()
+        .join("names.db")
+        .to_string_lossy()
+        .to_string();
+    let db = rmpd_library::database::Database::open(&db_path).unwrap();
+
+    db.add_song(&make_tagged_song("a.flac", "Alpha", "One"))
+        .unwrap();
+    db.add_song(&make_tagged_song("b.flac", "Alpha", "Two"))
+        .unwrap();
+    db.add_song(&make_tagged_song("c.flac", "alpha", "Two"))
+        .unwrap();
+    assert_eq!(db.count_artists().unwrap(), 2, "names are case-sensitive");
+    assert_eq!(db.count_albums().unwrap(), 2);
+    assert_eq!(
+        db.list_tag_values("artist").unwrap(),
+        ["Alpha".to_string(), "alpha".to_string()]
+    );
+
+    // Retagging (re-adding the same path) moves the song between names.
+    db.add_song(&make_tagged_song("c.flac", "Beta", "Two"))
+        .unwrap();
+    assert_eq!(
+        db.list_tag_values("artist").unwrap(),
+        ["Alpha".to_string(), "Beta".to_string()]
+    );
+
+    db.delete_song_by_path("a.flac").unwrap();
+    assert_eq!(db.list_tag_values("album").unwrap(), ["Two".to_string()]);
+    assert_eq!(db.count_artists().unwrap(), 2);
+
+    db.add_song(&make_local_song("untagged.flac")).unwrap();
+    assert_eq!(
+        db.list_tag_values("artist").unwrap(),
+        [String::new(), "Alpha".to_string(), "Beta".to_string()],
+        "songs without an artist still list an empty value"
+    );
+}
+
+/// Databases from before the name tables get them filled from song_tags.
+#[test]
+fn test_name_tables_migration_backfills() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("old.db").to_string_lossy().to_string();
+    {
+        let db = rmpd_library::database::Database::open(&db_path).unwrap();
+        db.add_song(&make_tagged_song("a.flac", "Alpha", "One"))
+            .unwrap();
+        db.add_song(&make_tagged_song("b.flac", "Beta", "One"))
+            .unwrap();
+    }
+    {
+        // Recreate the old, never-populated layout.
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "DROP TRIGGER artists_tag_insert;
+             DROP TRIGGER artists_tag_delete;
+             DROP TRIGGER albums_tag_insert;
+             DROP TRIGGER albums_tag_delete;
+             DROP TABLE albums;
+             DROP TABLE artists;
+             CREATE TABLE artists (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE COLLATE NOCASE);
+             CREATE TABLE albums (id INTEGER PRIMARY KEY, name TEXT NOT NULL, artist_id INTEGER,
+                 date TEXT, UNIQUE(name, artist_id));",
+        )
+        .unwrap();
+    }
+
+    let db = rmpd_library::database::Database::open(&db_path).unwrap();
+    assert_eq!(db.count_artists().unwrap(), 2);
+    assert_eq!(db.count_albums().unwrap(), 1);
+    db.delete_song_by_path("a.flac").unwrap();
+    assert_eq!(db.count_artists().unwrap(), 1, "triggers are installed");
+}
+
+/// Stored playlists follow their URIs when songs are removed and re-added
+/// under new ids, as a rescan or database rebuild does.
+#[test]
+fn test_playlist_items_survive_song_reinsert() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir
+        .path()
+        .join("playlists.db")
+        .to_string_lossy()
+        .to_string();
+    let db = rmpd_library::database::Database::open(&db_path).unwrap();
+
+    let a = make_local_song("a.flac");
+    let b = make_local_song("b.flac");
+    let old_id = db.add_song(&a).unwrap();
+    db.add_song(&b).unwrap();
+    db.save_playlist("mix", &[a.clone(), b.clone()]).unwrap();
+
+    db.delete_song_by_path("a.flac").unwrap();
+    let paths = |songs: Vec<rmpd_core::song::Song>| -> Vec<String> {
+        songs.into_iter().map(|s| s.path.to_string()).collect()
+    };
+    assert_eq!(paths(db.load_playlist("mix").unwrap()), ["b.flac"]);
+
+    // Filler so the re-inserted row cannot reuse the old id.
+    db.add_song(&make_local_song("c.flac")).unwrap();
+    let new_id = db.add_song(&a).unwrap();
+    assert_ne!(old_id, new_id);
+    assert_eq!(
+        paths(db.load_playlist("mix").unwrap()),
+        ["a.flac", "b.flac"]
+    );
+    assert_eq!(db.relink_playlist_items().unwrap(), 0, "already linked");
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let linked: i64 = conn
+        .query_row(
+            "SELECT song_id FROM playlist_items WHERE uri = 'a.flac'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(linked as u64, new_id);
+}
+
+/// Indexing a playlist file replaces its entries and keeps URIs that are not
+/// library songs.
+#[test]
+fn test_index_playlist_replaces_entries() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir
+        .path()
+        .join("index.db")
+        .to_string_lossy()
+        .to_string();
+    let db = rmpd_library::database::Database::open(&db_path).unwrap();
+    db.add_song(&make_local_song("a.flac")).unwrap();
+    db.add_song(&make_local_song("b.flac")).unwrap();
+
+    let uris = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    db.index_playlist("mix", &uris(&["a.flac", "b.flac"]))
+        .unwrap();
+    db.index_playlist(
+        "mix",
+        &uris(&["b.flac", "http://radio/stream", "gone.flac"]),
+    )
+    .unwrap();
+
+    let playlists = db.list_playlists().unwrap();
+    assert_eq!(playlists.len(), 1);
+    assert_eq!(playlists[0].name, "mix");
+    assert_eq!(playlists[0].song_count, 3);
+    let songs = db.load_playlist("mix").unwrap();
+    assert_eq!(songs.len(), 1, "only library songs load");
+    assert_eq!(songs[0].path.as_str(), "b.flac");
+
+    db.delete_playlist("mix").unwrap();
+    assert!(db.list_playlists().unwrap().is_empty());
+}
+
+/// Moving a song to a new spelling keeps its id and everything keyed by path.
+#[test]
+fn test_rename_song_path_keeps_related_rows() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir
+        .path()
+        .join("case.db")
+        .to_string_lossy()
+        .to_string();
+    let db = rmpd_library::database::Database::open(&db_path).unwrap();
+
+    let song = make_local_song("Artist/track.flac");
+    let id = db.add_song(&song).unwrap();
+    db.add_song(&make_local_song("other.flac")).unwrap();
+    db.set_sticker("Artist/track.flac", "rating", "4").unwrap();
+    db.store_artwork("Artist/track.flac", "front", "image/png", b"art", "h")
+        .unwrap();
+    db.save_playlist("p", &[song]).unwrap();
+
+    assert_eq!(
+        db.song_paths_differing_in_case("artist/Track.flac")
+            .unwrap(),
+        ["Artist/track.flac"]
+    );
+    assert!(
+        db.song_paths_differing_in_case("Artist/track.flac")
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(
+        db.rename_song_path("Artist/track.flac", "artist/Track.flac")
+            .unwrap()
+    );
+    assert!(!db.rename_song_path("missing.flac", "Missing.flac").unwrap());
+
+    let moved = db.get_song_by_path("artist/Track.flac").unwrap().unwrap();
+    assert_eq!(moved.id, id);
+    assert_eq!(
+        db.get_sticker("artist/Track.flac", "rating").unwrap(),
+        Some("4".to_string())
+    );
+    assert!(db.has_artwork("artist/Track.flac", "front").unwrap());
+    let playlist = db.load_playlist("p").unwrap();
+    assert_eq!(playlist[0].path.as_str(), "artist/Track.flac");
+    assert_eq!(db.count_songs().unwrap(), 2);
+}
+
+#[test]
+fn test_derive_album_gains() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let db = rmpd_library::Database::open(dir.path().join("test.db").to_str().unwrap()).unwrap();
+    let song = |path: &str, track: Option<(f32, f32)>, album_gain: Option<f32>| {
+        let mut song = rmpd_core::test_utils::make_test_song(path, 1);
+        song.replay_gain_track_gain = track.map(|(gain, _)| gain);
+        song.replay_gain_track_peak = track.map(|(_, peak)| peak);
+        song.replay_gain_album_gain = album_gain;
+        song
+    };
+    let album_gain = |path: &str| {
+        let song = db.get_song_by_path(path).unwrap().unwrap();
+        (song.replay_gain_album_gain, song.replay_gain_album_peak)
+    };
+
+    // Track gains only: the album gets their mean and the highest peak, and
+    // so does a track of it without gains.
+    db.add_song(&song("a/1.flac", Some((-6.0, 0.8)), None))
+        .unwrap();
+    db.add_song(&song("a/2.flac", Some((-8.0, 0.9)), None))
+        .unwrap();
+    db.add_song(&song("a/3.flac", None, None)).unwrap();
+    // Album gain on one file: the album is left alone.
+    db.add_song(&song("b/1.flac", Some((-3.0, 0.5)), Some(-4.0)))
+        .unwrap();
+    db.add_song(&song("b/2.flac", Some((-5.0, 0.5)), None))
+        .unwrap();
+    // No gains at all.
+    db.add_song(&song("c/1.flac", None, None)).unwrap();
+
+    assert_eq!(db.derive_album_gains().unwrap(), 3);
+    assert_eq!(album_gain("a/1.flac"), (Some(-7.0), Some(0.9)));
+    assert_eq!(album_gain("a/3.flac"), (Some(-7.0), Some(0.9)));
+    assert_eq!(album_gain("b/1.flac").0, Some(-4.0));
+    assert_eq!(album_gain("b/2.flac").0, None);
+    assert_eq!(album_gain("c/1.flac"), (None, None));
+
+    // A rescanned track changes the derived value.
+    db.add_song(&song("a/2.flac", Some((-4.0, 0.9)), None))
+        .unwrap();
+    assert_eq!(db.derive_album_gains().unwrap(), 3);
+    assert_eq!(album_gain("a/1.flac").0, Some(-5.0));
+
+    // Once a file carries a real album gain, nothing is derived any more.
+    db.add_song(&song("a/1.flac", Some((-6.0, 0.8)), Some(-5.5)))
+        .unwrap();
+    assert_eq!(db.derive_album_gains().unwrap(), 0);
+    assert_eq!(album_gain("a/1.flac").0, Some(-5.5));
+    assert_eq!(album_gain("a/2.flac"), (None, None));
+
+    // Deriving near one song leaves the other directories alone.
+    db.add_song(&song("d/1.flac", Some((-2.0, 0.4)), None))
+        .unwrap();
+    db.add_song(&song("e/1.flac", Some((-3.0, 0.5)), None))
+        .unwrap();
+    assert_eq!(db.derive_album_gains_near("d/1.flac").unwrap(), 1);
+    assert_eq!(album_gain("d/1.flac"), (Some(-2.0), Some(0.4)));
+    assert_eq!(album_gain("e/1.flac"), (None, None));
+    assert_eq!(db.derive_album_gains_near("missing.flac").unwrap(), 0);
+}
+
+/// The protocol layer only sees the library through `LibraryBackend`; the
+/// SQLite pool hands out stores sharing one database and plans scans.
+#[test]
+fn test_pool_as_library_backend() {
+    use rmpd_library::{LibraryBackend, Scanner};
+    use std::sync::Arc;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let db_path = dir.path().join("backend.db").to_string_lossy().to_string();
+    let backend: Arc<dyn LibraryBackend> = rmpd_library::DbPool::new(&db_path).unwrap();
+
+    let store = backend.store().unwrap();
+    store
+        .add_song(&rmpd_core::test_utils::make_test_song("gone.flac", 1))
+        .unwrap();
+    store.set_sticker("gone.flac", "rating", "3").unwrap();
+    drop(store);
+
+    let store = backend.store().unwrap();
+    let song = store.get_song_by_path("gone.flac").unwrap().unwrap();
+    assert_eq!(song.tag("title"), Some("Track 1"));
+    assert_eq!(
+        store.get_sticker("gone.flac", "rating").unwrap().as_deref(),
+        Some("3")
+    );
+
+    let music_dir = dir.path().join("music");
+    std::fs::create_dir(&music_dir).unwrap();
+    let scanner = Scanner::new(rmpd_core::event::EventBus::new(), false);
+    let report = backend.plan_scan(&scanner, &music_dir).unwrap();
+    assert_eq!(report.removed, ["gone.flac"]);
+    assert!(report.added.is_empty());
+}
+
+/// `db_file = ":memory:"` keeps the library in RAM: every connection of the
+/// process shares it, and the pool keeps it alive between connections. The
+/// only test opening `MEMORY_DB`, since it is shared process-wide.
+#[test]
+fn test_memory_db_is_shared_while_pool_lives() {
+    use rmpd_library::database::{Database, MEMORY_DB};
+
+    let pool = rmpd_library::DbPool::new(MEMORY_DB).unwrap();
+    let writer = Database::open(MEMORY_DB).unwrap();
+    writer
+        .add_song(&rmpd_core::test_utils::make_test_song("mem/one.flac", 1))
+        .unwrap();
+    drop(writer);
+
+    let reader = Database::from_pool(&pool).unwrap();
+    let song = reader.get_song_by_path("mem/one.flac").unwrap();
+    assert_eq!(song.unwrap().tag("title"), Some("Track 1"));
+}
@@ -5,7 +5,17 @@
 use std::time::Duration;
 
 use crate::common::rmpd_harness::RmpdTestHarness;
-use crate::fixtures::pregenerated;
+use crate::fixtures::{AudioFormat, FixtureGenerator, TestMetadata, dsd, mp4, opus, pregenerated};
+
+/// Helper to check if FFmpeg is available
+macro_rules! require_ffmpeg {
+    () => {
+        if !FixtureGenerator::is_ffmpeg_available() {
+            eprintln!("FFmpeg not available - skipping test");
+            return;
+        }
+    };
+}
 
 #[test]
 fn test_flac_metadata_extraction() {
@@ -86,6 +96,138 @@ fn test_m4a_metadata_extraction() {
     assert_eq!(song.tag("album"), Some("Test Album M4A"));
 }
 
+#[test]
+fn test_opus_r128_gain() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gain.opus");
+    // 50 × 20 ms = 1 s after pre-skip.
+    opus::write_opus(
+        &path,
+        50,
+        &[
+            ("TITLE", "Opus Gain"),
+            ("ARTIST", "Opus Artist"),
+            ("TRACKNUMBER", "04"),
+            ("REPLAYGAIN_TRACK_GAIN", "-3.00 dB"),
+            ("REPLAYGAIN_TRACK_PEAK", "0.5"),
+            // Q7.8 dB relative to -23 LUFS: -5 dB and +1 dB.
+            ("R128_TRACK_GAIN", "-1280"),
+            ("R128_ALBUM_GAIN", "256"),
+        ],
+    );
+
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.tag("title"), Some("Opus Gain"));
+    assert_eq!(song.tag("artist"), Some("Opus Artist"));
+    assert_eq!(song.tag("track"), Some("4"));
+    assert_eq!(song.sample_rate, Some(48000));
+    assert_eq!(song.channels, Some(2));
+    let secs = song.duration.unwrap().as_secs_f64();
+    assert!((0.99..=1.01).contains(&secs), "duration {secs}");
+
+    // R128 gains win over REPLAYGAIN_* and move to the -18 LUFS reference.
+    assert_eq!(song.replay_gain_track_gain, Some(0.0));
+    assert_eq!(song.replay_gain_album_gain, Some(6.0));
+    assert_eq!(song.replay_gain_track_peak, Some(0.5));
+}
+
+/// Common `ilst` items of the M4A fixtures, followed by `extra`.
+fn m4a_items<'a>(extra: &[mp4::Item<'a>]) -> Vec<mp4::Item<'a>> {
+    let mut items = vec![
+        mp4::Item::Text(b"\xa9nam", "M4A Song"),
+        mp4::Item::Text(b"\xa9ART", "M4A Artist"),
+        mp4::Item::Text(b"\xa9alb", "M4A Album"),
+        mp4::Item::Text(b"\xa9day", "2023"),
+        mp4::Item::Track(3, 12),
+    ];
+    items.extend_from_slice(extra);
+    items
+}
+
+#[test]
+fn test_m4a_gapless_duration() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    // 46 AAC frames: 2112 samples of encoder delay, 44100 of audio and 892
+    // of padding.
+    let path = dir.path().join("gapless.m4a");
+    mp4::write_m4a(
+        &path,
+        mp4::Codec::Aac,
+        46 * 1024,
+        &m4a_items(&[mp4::Item::Freeform(
+            "iTunSMPB",
+            " 00000000 00000840 0000037C 000000000000AC44 00000000 00000000 00000000 00000000",
+        )]),
+    );
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.tag("title"), Some("M4A Song"));
+    assert_eq!(song.tag("artist"), Some("M4A Artist"));
+    assert_eq!(song.tag("album"), Some("M4A Album"));
+    assert_eq!(song.tag("date"), Some("2023"));
+    assert_eq!(song.tag("track"), Some("3"));
+    assert_eq!(song.sample_rate, Some(mp4::SAMPLE_RATE));
+    assert_eq!(song.channels, Some(2));
+    assert_eq!(song.bits_per_sample, Some(0));
+    assert_eq!(song.duration, Some(Duration::from_secs(1)));
+
+    // Without iTunSMPB the priming and padding count towards the length.
+    let path = dir.path().join("plain.m4a");
+    mp4::write_m4a(&path, mp4::Codec::Aac, 46 * 1024, &m4a_items(&[]));
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    let secs = song.duration.unwrap().as_secs_f64();
+    assert!((1.06..=1.075).contains(&secs), "duration {secs}");
+}
+
+#[test]
+fn test_m4a_replay_gain_freeform_atoms() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("replaygain.m4a");
+    // foobar2000 writes these in lower case.
+    mp4::write_m4a(
+        &path,
+        mp4::Codec::Aac,
+        44 * 1024,
+        &m4a_items(&[
+            mp4::Item::Freeform("replaygain_track_gain", "-7.25 dB"),
+            mp4::Item::Freeform("replaygain_track_peak", "0.988525"),
+            mp4::Item::Freeform("replaygain_album_gain", "-6.50 dB"),
+            mp4::Item::Freeform("replaygain_album_peak", "1.000000"),
+        ]),
+    );
+
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.replay_gain_track_gain, Some(-7.25));
+    assert_eq!(song.replay_gain_track_peak, Some(0.988525));
+    assert_eq!(song.replay_gain_album_gain, Some(-6.5));
+    assert_eq!(song.replay_gain_album_peak, Some(1.0));
+}
+
+#[test]
+fn test_alac_metadata_extraction() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("alac.m4a");
+    mp4::write_m4a(
+        &path,
+        mp4::Codec::Alac { bits: 24 },
+        mp4::SAMPLE_RATE,
+        &m4a_items(&[]),
+    );
+
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.tag("title"), Some("M4A Song"));
+    assert_eq!(song.tag("track"), Some("3"));
+    assert_eq!(song.sample_rate, Some(mp4::SAMPLE_RATE));
+    assert_eq!(song.channels, Some(2));
+    // Lossless: MPD reports the real bit depth, not the float of AAC.
+    assert_eq!(song.bits_per_sample, Some(24));
+    assert_eq!(song.duration, Some(Duration::from_secs(1)));
+}
+
 #[test]
 fn test_wav_metadata_extraction() {
     let harness = RmpdTestHarness::new().unwrap();
@@ -231,3 +373,111 @@ fn test_multiple_formats_consistency() {
         assert!(song.duration.is_some());
     }
 }
+
+fn dsd_text_tag(major: u8) -> Vec<u8> {
+    dsd::id3v2_tag(
+        major,
+        &[
+            dsd::id3_text_frame(major, b"TIT2", "DSD Song"),
+            dsd::id3_text_frame(major, b"TPE1", "DSD Artist"),
+            dsd::id3_text_frame(major, b"TALB", "DSD Album"),
+            dsd::id3_text_frame(major, b"TRCK", "02/10"),
+        ],
+    )
+}
+
+#[test]
+fn test_dsf_metadata_extraction() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dsd64.dsf");
+    // 86 blocks × 4096 bytes × 8 bits ≈ 1 s at DSD64.
+    dsd::write_dsf(&path, dsd::DSD64, 2, 86, Some(&dsd_text_tag(3)));
+
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.tag("title"), Some("DSD Song"));
+    assert_eq!(song.tag("artist"), Some("DSD Artist"));
+    assert_eq!(song.tag("album"), Some("DSD Album"));
+    assert_eq!(song.tag("track"), Some("2"));
+
+    assert_eq!(song.sample_rate, Some(dsd::DSD64));
+    assert_eq!(song.channels, Some(2));
+    assert_eq!(song.bits_per_sample, Some(1));
+    let secs = song.duration.unwrap().as_secs_f64();
+    assert!((0.9..=1.1).contains(&secs), "duration {secs}");
+}
+
+#[test]
+fn test_dff_metadata_extraction() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dsd128.dff");
+    // 5644800 bits per channel per second = 705600 bytes: half a second.
+    dsd::write_dff(&path, dsd::DSD128, 2, 352_800, Some(&dsd_text_tag(4)));
+
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.tag("title"), Some("DSD Song"));
+    assert_eq!(song.tag("artist"), Some("DSD Artist"));
+
+    assert_eq!(song.sample_rate, Some(dsd::DSD128));
+    assert_eq!(song.channels, Some(2));
+    assert_eq!(song.bits_per_sample, Some(1));
+    assert_eq!(song.duration, Some(Duration::from_millis(500)));
+}
+
+#[test]
+fn test_dsd_rates_without_tags() {
+    let harness = RmpdTestHarness::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    for (name, rate) in [
+        ("dsd64.dsf", dsd::DSD64),
+        ("dsd128.dsf", dsd::DSD128),
+        ("dsd256.dsf", dsd::DSD256),
+    ] {
+        let path = dir.path().join(name);
+        dsd::write_dsf(&path, rate, 2, 1, None);
+        let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+        assert_eq!(song.sample_rate, Some(rate), "{name}");
+        assert_eq!(song.bits_per_sample, Some(1), "{name}");
+    }
+
+    let path = dir.path().join("mono.dff");
+    dsd::write_dff(&path, dsd::DSD256, 1, 4096, None);
+    let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+    assert_eq!(song.sample_rate, Some(dsd::DSD256));
+    assert_eq!(song.channels, Some(1));
+}
+
+#[test]
+fn test_legacy_lossless_metadata_extraction() {
+    require_ffmpeg!();
+
+    let generator = FixtureGenerator::new().unwrap();
+    let harness = RmpdTestHarness::new().unwrap();
+
+    // FFmpeg writes APEv2 tags for both; lofty reads WavPack, while TTA
+    // goes through the library's own header and APEv2 readers.
+    for format in [AudioFormat::WavPack, AudioFormat::Tta] {
+        let metadata = TestMetadata {
+            title: format!("Legacy {format:?}"),
+            artist: "Legacy Artist".to_string(),
+            album: "Legacy Album".to_string(),
+            ..Default::default()
+        };
+        let path = generator.generate(format, &metadata).unwrap();
+        let song = harness.extract_metadata(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            song.tag("title"),
+            Some(metadata.title.as_str()),
+            "{format:?}"
+        );
+        assert_eq!(song.tag("artist"), Some("Legacy Artist"), "{format:?}");
+        assert_eq!(song.tag("album"), Some("Legacy Album"), "{format:?}");
+        assert_eq!(song.sample_rate, Some(44100), "{format:?}");
+        assert_eq!(song.channels, Some(2), "{format:?}");
+        let secs = song.duration.unwrap().as_secs_f64();
+        assert!((0.9..=1.1).contains(&secs), "{format:?} duration {secs}");
+    }
+}
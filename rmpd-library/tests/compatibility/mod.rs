@@ -9,4 +9,6 @@ mod edge_cases_tests;
 /// - Search operations
 /// - Artwork handling
 mod metadata_tests;
+mod scale_tests;
 mod search_tests;
+mod tag_writer_tests;
@@ -0,0 +1,124 @@
+/// find/list/lsinfo against a generated 5,000-song library
+///
+/// Every expectation is derived from the generated songs themselves, so these
+/// tests check that queries stay exact (nothing dropped, nothing duplicated)
+/// once the library is large enough for real-world tag distributions.
+use std::collections::{BTreeSet, HashMap};
+
+use rmpd_core::filter::FilterExpression;
+use rmpd_core::song::Song;
+use rmpd_library::database::Database;
+use tempfile::TempDir;
+
+use crate::fixtures::SyntheticLibrary;
+
+const SIZE: usize = 5_000;
+
+fn library() -> (TempDir, Database, Vec<Song>) {
+    let dir = TempDir::new().unwrap();
+    let (db, songs) = SyntheticLibrary::new(SIZE).build_in(dir.path()).unwrap();
+    (dir, db, songs)
+}
+
+fn paths(songs: &[Song]) -> BTreeSet<&str> {
+    songs.iter().map(|s| s.path.as_str()).collect()
+}
+
+#[test]
+fn test_generated_library_is_deterministic() {
+    let first = SyntheticLibrary::new(500).songs();
+    let second = SyntheticLibrary::new(500).songs();
+    assert_eq!(first.len(), 500);
+    assert_eq!(paths(&first), paths(&second));
+    assert_ne!(
+        paths(&first),
+        paths(&SyntheticLibrary::with_seed(500, 2).songs())
+    );
+}
+
+#[test]
+fn test_find_artist_at_scale() {
+    let (_dir, db, songs) = library();
+    let mut by_artist: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for song in &songs {
+        by_artist
+            .entry(song.tag("artist").unwrap())
+            .or_default()
+            .insert(song.path.as_str());
+    }
+    let (top, expected) = by_artist
+        .iter()
+        .max_by_key(|(_, paths)| paths.len())
+        .unwrap();
+    assert!(expected.len() > 50, "head artist should be prolific");
+
+    let found = db.find_songs("artist", top).unwrap();
+    assert_eq!(found.len(), expected.len());
+    assert_eq!(&paths(&found), expected);
+}
+
+#[test]
+fn test_find_filter_at_scale() {
+    let (_dir, db, songs) = library();
+    let expected: BTreeSet<&str> = songs
+        .iter()
+        .filter(|s| s.tag("genre") == Some("Rock") && s.tag("date").is_some_and(|d| d >= "2000"))
+        .map(|s| s.path.as_str())
+        .collect();
+    assert!(!expected.is_empty());
+
+    let filter = FilterExpression::parse("((genre == 'Rock') AND (date >= '2000'))").unwrap();
+    let found = db.find_songs_filter(&filter).unwrap();
+    assert_eq!(paths(&found), expected);
+}
+
+#[test]
+fn test_list_tags_at_scale() {
+    let (_dir, db, songs) = library();
+    for tag in ["album", "albumartist", "genre"] {
+        let expected: BTreeSet<&str> = songs.iter().filter_map(|s| s.tag(tag)).collect();
+        let listed = db.list_tag_values(tag).unwrap();
+        assert_eq!(listed.len(), expected.len(), "list {tag}");
+        assert_eq!(
+            listed.iter().map(String::as_str).collect::<BTreeSet<_>>(),
+            expected,
+            "list {tag}"
+        );
+    }
+    assert!(
+        db.list_tag_values("albumartist")
+            .unwrap()
+            .iter()
+            .any(|a| a == "Various Artists")
+    );
+}
+
+#[test]
+fn test_lsinfo_at_scale() {
+    let (_dir, db, songs) = library();
+    let root = db.list_directory("").unwrap();
+    let top_dirs: BTreeSet<&str> = songs
+        .iter()
+        .map(|s| s.path.as_str().split('/').next().unwrap())
+        .collect();
+    assert_eq!(root.directories.len(), top_dirs.len());
+    assert!(root.songs.is_empty());
+
+    let artist_dir = root.directories[0].0.as_str();
+    let album_dirs: BTreeSet<String> = songs
+        .iter()
+        .filter_map(|s| s.path.parent())
+        .filter(|dir| dir.parent().map(|p| p.as_str()) == Some(artist_dir))
+        .map(|dir| dir.to_string())
+        .collect();
+    let listing = db.list_directory(artist_dir).unwrap();
+    let listed: BTreeSet<String> = listing.directories.into_iter().map(|(p, _)| p).collect();
+    assert_eq!(listed, album_dirs);
+
+    let album_dir = album_dirs.first().unwrap();
+    let expected = songs
+        .iter()
+        .filter(|s| s.path.parent().map(|p| p.as_str()) == Some(album_dir.as_str()))
+        .count();
+    assert_eq!(db.list_directory(album_dir).unwrap().songs.len(), expected);
+}
@@ -341,3 +341,47 @@ fn test_search_performance() {
 
     assert_eq!(results.len(), 50); // Half the songs are Rock
 }
+
+#[test]
+fn test_filter_numeric_and_date_ranges() {
+    use rmpd_core::filter::FilterExpression;
+    use rmpd_core::song::intern_tag_key;
+    use rmpd_core::test_utils::create_test_song;
+
+    let harness = RmpdTestHarness::new().unwrap();
+    for (id, track, date) in [
+        (1, "2/12", "1989-12-31"),
+        (2, "9", "1990"),
+        (3, "10/12", "1990-05-01"),
+        (4, "11", "2001"),
+    ] {
+        let mut song = create_test_song(id, &id.to_string());
+        song.tags.push((intern_tag_key("track"), track.to_string()));
+        song.tags.push((intern_tag_key("date"), date.to_string()));
+        harness.add_song(&song).unwrap();
+    }
+
+    let titles = |filter: &str| -> Vec<String> {
+        let filter = FilterExpression::parse(filter).unwrap();
+        let mut titles: Vec<String> = harness
+            .database
+            .find_songs_filter(&filter)
+            .unwrap()
+            .iter()
+            .filter_map(|s| s.tag("title").map(str::to_string))
+            .collect();
+        titles.sort();
+        titles
+    };
+
+    // "10" < "9" as strings; numerically 9, 10 and 11 are all >= 9.
+    assert_eq!(titles("(track >= '9')"), ["Song 2", "Song 3", "Song 4"]);
+    assert_eq!(titles("(track < '3')"), ["Song 1"]);
+    // A year filter covers full dates within that year.
+    assert_eq!(titles("(date >= '1990')"), ["Song 2", "Song 3", "Song 4"]);
+    assert_eq!(
+        titles("((date >= '1990') AND (date <= '1990'))"),
+        ["Song 2", "Song 3"]
+    );
+    assert_eq!(titles("(date < '1990-05-01')"), ["Song 1", "Song 2"]);
+}
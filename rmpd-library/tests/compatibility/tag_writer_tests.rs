@@ -0,0 +1,84 @@
+/// Tag writing tests
+///
+/// Copies pre-generated fixtures to a temporary directory, rewrites their
+/// tags with `rmpd_library::tag_writer` and reads them back through the
+/// metadata extractor the scanner uses.
+use std::path::PathBuf;
+
+use camino::Utf8PathBuf;
+use rmpd_library::MetadataExtractor;
+use rmpd_library::tag_writer::{is_writable, write_tag};
+use tempfile::TempDir;
+
+use crate::fixtures::pregenerated;
+
+fn copy_fixture(dir: &TempDir, fixture: PathBuf) -> Utf8PathBuf {
+    let target = dir.path().join(fixture.file_name().unwrap());
+    std::fs::copy(&fixture, &target).unwrap();
+    Utf8PathBuf::from_path_buf(target).unwrap()
+}
+
+#[test]
+fn test_write_tags_round_trip() {
+    let dir = TempDir::new().unwrap();
+    for fixture in [
+        pregenerated::basic_flac(),
+        pregenerated::basic_mp3(),
+        pregenerated::basic_ogg(),
+        pregenerated::basic_opus(),
+        pregenerated::basic_m4a(),
+    ] {
+        let path = copy_fixture(&dir, fixture);
+        let original = MetadataExtractor::extract_from_file(&path).unwrap();
+        write_tag(&path, "title", Some("Edited Title")).unwrap();
+        write_tag(&path, "Artist", Some("Édith Artist")).unwrap();
+        write_tag(&path, "album", Some("Edited Album")).unwrap();
+
+        let song = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert_eq!(song.tag("title"), Some("Edited Title"), "{path}");
+        assert_eq!(song.tag("artist"), Some("Édith Artist"), "{path}");
+        assert_eq!(song.tag("album"), Some("Edited Album"), "{path}");
+        assert_eq!(
+            song.tag("date"),
+            original.tag("date"),
+            "{path}: other tags kept"
+        );
+        assert!(song.duration.is_some(), "{path}: audio left intact");
+    }
+}
+
+#[test]
+fn test_clear_tag() {
+    let dir = TempDir::new().unwrap();
+    let path = copy_fixture(&dir, pregenerated::basic_flac());
+    write_tag(&path, "genre", None).unwrap();
+
+    let song = MetadataExtractor::extract_from_file(&path).unwrap();
+    assert_eq!(song.tag("genre"), None);
+    assert_eq!(song.tag("title"), Some("Test Song"));
+}
+
+#[test]
+fn test_write_rating_to_vorbis_comments() {
+    let dir = TempDir::new().unwrap();
+    let path = copy_fixture(&dir, pregenerated::basic_flac());
+    write_tag(&path, "rating", Some("8")).unwrap();
+
+    let comments = MetadataExtractor::read_raw_comments(&path).unwrap();
+    assert!(
+        comments
+            .iter()
+            .any(|(k, v)| k.eq_ignore_ascii_case("rating") && v == "8"),
+        "{comments:?}"
+    );
+}
+
+#[test]
+fn test_writable_tags() {
+    for tag in ["title", "Artist", "album", "albumartist", "date", "rating"] {
+        assert!(is_writable(tag), "{tag}");
+    }
+    for tag in ["lyrics", "duration", "file", ""] {
+        assert!(!is_writable(tag), "{tag}");
+    }
+}
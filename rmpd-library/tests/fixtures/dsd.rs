@@ -0,0 +1,151 @@
+//! Synthetic DSD fixtures (DSF and DSDIFF) with raw ID3v2 tags.
+//!
+//! FFmpeg cannot write DSD containers, so these are assembled byte by byte:
+//! just enough container structure for the library's DSD reader and lofty,
+//! with DSD silence (0x69) as the sound data.
+
+use std::path::Path;
+
+/// DSD64: 64 × 44.1 kHz.
+pub const DSD64: u32 = 2_822_400;
+/// DSD128: 128 × 44.1 kHz.
+pub const DSD128: u32 = 5_644_800;
+/// DSD256: 256 × 44.1 kHz.
+pub const DSD256: u32 = 11_289_600;
+
+/// DSF block size per channel.
+const DSF_BLOCK: usize = 4096;
+
+fn syncsafe(n: usize) -> [u8; 4] {
+    [
+        ((n >> 21) & 0x7F) as u8,
+        ((n >> 14) & 0x7F) as u8,
+        ((n >> 7) & 0x7F) as u8,
+        (n & 0x7F) as u8,
+    ]
+}
+
+/// One ID3v2 frame (major version 3 or 4).
+pub fn id3_frame(major: u8, id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut frame = id.to_vec();
+    if major == 4 {
+        frame.extend_from_slice(&syncsafe(body.len()));
+    } else {
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    }
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// UTF-8 text frame (ID3v2.4 encoding 3; also read by v2.3 parsers).
+pub fn id3_text_frame(major: u8, id: &[u8; 4], text: &str) -> Vec<u8> {
+    let mut body = vec![3u8];
+    body.extend_from_slice(text.as_bytes());
+    id3_frame(major, id, &body)
+}
+
+/// Front-cover APIC frame holding a PNG image.
+pub fn id3_apic_frame(major: u8, image: &[u8]) -> Vec<u8> {
+    let mut body = vec![0u8]; // ISO-8859-1 description
+    body.extend_from_slice(b"image/png\0");
+    body.push(3); // front cover
+    body.extend_from_slice(b"Cover\0");
+    body.extend_from_slice(image);
+    id3_frame(major, b"APIC", &body)
+}
+
+/// Complete ID3v2 tag from pre-built frames.
+pub fn id3v2_tag(major: u8, frames: &[Vec<u8>]) -> Vec<u8> {
+    let body = frames.concat();
+    let mut tag = vec![b'I', b'D', b'3', major, 0, 0];
+    tag.extend_from_slice(&syncsafe(body.len()));
+    tag.extend_from_slice(&body);
+    tag
+}
+
+/// Write a DSF file of `blocks` blocks per channel at DSD rate `rate`,
+/// followed by `id3` (if any) as its metadata chunk.
+pub fn write_dsf(path: &Path, rate: u32, channels: u32, blocks: usize, id3: Option<&[u8]>) {
+    let sound_len = DSF_BLOCK * blocks * channels as usize;
+    let data_len = 12 + sound_len;
+    let id3_offset = 28 + 52 + data_len;
+    let total = id3_offset + id3.map_or(0, <[u8]>::len);
+
+    let mut file = b"DSD ".to_vec();
+    file.extend_from_slice(&28u64.to_le_bytes());
+    file.extend_from_slice(&(total as u64).to_le_bytes());
+    let pointer = if id3.is_some() { id3_offset as u64 } else { 0 };
+    file.extend_from_slice(&pointer.to_le_bytes());
+
+    file.extend_from_slice(b"fmt ");
+    file.extend_from_slice(&52u64.to_le_bytes());
+    // version, DSD raw, channel type, channel count, rate, 1 bit/sample
+    let channel_type = if channels == 2 { 2 } else { 1 };
+    for v in [1u32, 0, channel_type, channels, rate, 1] {
+        file.extend_from_slice(&v.to_le_bytes());
+    }
+    file.extend_from_slice(&((DSF_BLOCK * blocks * 8) as u64).to_le_bytes());
+    file.extend_from_slice(&(DSF_BLOCK as u32).to_le_bytes());
+    file.extend_from_slice(&0u32.to_le_bytes());
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&(data_len as u64).to_le_bytes());
+    file.extend(std::iter::repeat_n(0x69u8, sound_len));
+
+    if let Some(id3) = id3 {
+        file.extend_from_slice(id3);
+    }
+    std::fs::write(path, file).unwrap();
+}
+
+fn dff_chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    chunk.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+/// Write a DSDIFF file holding `bytes_per_channel` bytes of DSD per channel
+/// at rate `rate`, with `id3` (if any) in an `ID3 ` chunk.
+pub fn write_dff(
+    path: &Path,
+    rate: u32,
+    channels: u16,
+    bytes_per_channel: usize,
+    id3: Option<&[u8]>,
+) {
+    let mut prop = b"SND ".to_vec();
+    prop.extend(dff_chunk(b"FS  ", &rate.to_be_bytes()));
+    let mut chnl = channels.to_be_bytes().to_vec();
+    for ch in 0..channels {
+        chnl.extend_from_slice(match ch {
+            0 => b"SLFT",
+            1 => b"SRGT",
+            _ => b"C000",
+        });
+    }
+    prop.extend(dff_chunk(b"CHNL", &chnl));
+    let mut cmpr = b"DSD ".to_vec();
+    cmpr.extend_from_slice(b"\x0enot compressed\0");
+    prop.extend(dff_chunk(b"CMPR", &cmpr));
+
+    let mut chunks = dff_chunk(b"FVER", &0x0105_0000u32.to_be_bytes());
+    chunks.extend(dff_chunk(b"PROP", &prop));
+    chunks.extend(dff_chunk(
+        b"DSD ",
+        &vec![0x69u8; bytes_per_channel * usize::from(channels)],
+    ));
+    if let Some(id3) = id3 {
+        chunks.extend(dff_chunk(b"ID3 ", id3));
+    }
+
+    let mut file = b"FRM8".to_vec();
+    file.extend_from_slice(&(4 + chunks.len() as u64).to_be_bytes());
+    file.extend_from_slice(b"DSD ");
+    file.extend_from_slice(&chunks);
+    std::fs::write(path, file).unwrap();
+}
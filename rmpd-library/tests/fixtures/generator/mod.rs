@@ -293,6 +293,10 @@ impl FixtureGenerator {
         }
 
         cmd.arg("-map").arg("0:a").arg("-map").arg("1:v");
+        // MP4 only accepts a cover as-is (the `covr` atom), never re-encoded.
+        if format == AudioFormat::M4a {
+            cmd.arg("-codec:v").arg("copy");
+        }
         cmd.arg("-metadata:s:v").arg("title=Album cover");
         cmd.arg("-metadata:s:v").arg("comment=Cover (front)");
 
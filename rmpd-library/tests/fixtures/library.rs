@@ -0,0 +1,219 @@
+/// Synthetic large-library builder
+///
+/// Generates song rows with realistic tag distributions and writes them
+/// straight into a database, without FFmpeg or any audio files, so find/list/
+/// lsinfo can be tested (and benchmarked) against tens of thousands of songs.
+///
+/// The shape mirrors a typical collection: a few prolific artists and a long
+/// tail of one-album ones, 8-14 track albums (some multi-disc), compilations
+/// under "Various Artists", a skewed genre mix, mostly recent years, a sprinkle
+/// of non-ASCII names and some songs missing their date or genre. Generation
+/// is deterministic: the same size and seed always yield the same rows.
+use std::path::Path;
+use std::time::Duration;
+
+use rmpd_core::error::Result;
+use rmpd_core::song::{Song, intern_tag_key};
+use rmpd_library::database::Database;
+
+const GENRES: &[&str] = &[
+    "Rock",
+    "Pop",
+    "Electronic",
+    "Jazz",
+    "Hip-Hop",
+    "Classical",
+    "Metal",
+    "Folk",
+    "Soul",
+    "Ambient",
+    "Blues",
+    "Reggae",
+];
+const NAME_WORDS: &[&str] = &[
+    "Black", "Silver", "Night", "River", "Echo", "Velvet", "Static", "Golden", "Paper", "Glass",
+    "Northern", "Electric", "Quiet", "Wild", "Hollow", "Neon", "Crystal", "Lost", "Ocean", "Iron",
+];
+const ACCENTED_WORDS: &[&str] = &[
+    "Sigur", "Björk", "Señor", "Mötley", "Żywiec", "Café", "Ólafur",
+];
+const COMPILATION_ARTIST: &str = "Various Artists";
+
+/// SplitMix64: tiny, seedable and good enough for fixture data.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Skewed towards 0: index `i` is picked roughly `1/(i+1)` as often as 0.
+    fn skewed(&mut self, n: usize) -> usize {
+        let u = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        (((n as f64 + 1.0).powf(u) - 1.0) as usize).min(n - 1)
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// Builder for a synthetic library of `size` songs.
+pub struct SyntheticLibrary {
+    size: usize,
+    seed: u64,
+}
+
+impl SyntheticLibrary {
+    pub fn new(size: usize) -> Self {
+        Self::with_seed(size, 1)
+    }
+
+    /// A different seed gives a different (but still reproducible) library.
+    pub fn with_seed(size: usize, seed: u64) -> Self {
+        Self { size, seed }
+    }
+
+    /// Generate the songs, sorted by path.
+    pub fn songs(&self) -> Vec<Song> {
+        let mut rng = Rng(self.seed);
+        // Roughly one artist per 60 songs, so the head artists own dozens of
+        // albums while most of the tail has one or two.
+        let artists: Vec<String> = (0..(self.size / 60).max(1))
+            .map(|i| artist_name(&mut rng, i))
+            .collect();
+
+        let mut songs = Vec::with_capacity(self.size);
+        let mut album_no = 0;
+        while songs.len() < self.size {
+            album_no += 1;
+            let compilation = rng.chance(5);
+            let album_artist = if compilation {
+                COMPILATION_ARTIST.to_owned()
+            } else {
+                artists[rng.skewed(artists.len())].clone()
+            };
+            let album = format!(
+                "{} {} {album_no}",
+                NAME_WORDS[rng.below(NAME_WORDS.len())],
+                NAME_WORDS[rng.below(NAME_WORDS.len())]
+            );
+            let genre = GENRES[rng.skewed(GENRES.len())];
+            let year = 2025 - rng.skewed(65);
+            let discs = if rng.chance(10) { 2 } else { 1 };
+            let tracks = 8 + rng.below(7);
+            let hires = rng.chance(15);
+
+            for disc in 1..=discs {
+                for track in 1..=tracks {
+                    if songs.len() == self.size {
+                        break;
+                    }
+                    let artist = if compilation {
+                        artists[rng.below(artists.len())].clone()
+                    } else {
+                        album_artist.clone()
+                    };
+                    let title = format!(
+                        "{} {} {}",
+                        NAME_WORDS[rng.below(NAME_WORDS.len())],
+                        NAME_WORDS[rng.below(NAME_WORDS.len())],
+                        songs.len() + 1
+                    );
+                    let disc_prefix = if discs > 1 {
+                        format!("{disc}-")
+                    } else {
+                        String::new()
+                    };
+                    let path = format!(
+                        "{}/{year} - {}/{disc_prefix}{track:02} - {}.flac",
+                        album_artist.replace('/', "_"),
+                        album.replace('/', "_"),
+                        title.replace('/', "_")
+                    );
+
+                    let mut tags = vec![
+                        (intern_tag_key("title"), title),
+                        (intern_tag_key("artist"), artist),
+                        (intern_tag_key("album"), album.clone()),
+                        (intern_tag_key("albumartist"), album_artist.clone()),
+                        (intern_tag_key("track"), track.to_string()),
+                    ];
+                    if discs > 1 {
+                        tags.push((intern_tag_key("disc"), disc.to_string()));
+                    }
+                    if !rng.chance(5) {
+                        tags.push((intern_tag_key("date"), year.to_string()));
+                    }
+                    if !rng.chance(3) {
+                        tags.push((intern_tag_key("genre"), genre.to_owned()));
+                    }
+                    if genre == "Classical" {
+                        tags.push((
+                            intern_tag_key("composer"),
+                            artists[rng.skewed(artists.len())].clone(),
+                        ));
+                    }
+
+                    let length_ms = 120_000 + rng.below(300_000) as u64;
+                    songs.push(Song {
+                        id: 0,
+                        path: path.into(),
+                        duration: Some(Duration::from_millis(length_ms)),
+                        sample_rate: Some(if hires { 96000 } else { 44100 }),
+                        channels: Some(2),
+                        bits_per_sample: Some(if hires { 24 } else { 16 }),
+                        bitrate: None,
+                        replay_gain_track_gain: None,
+                        replay_gain_track_peak: None,
+                        replay_gain_album_gain: None,
+                        replay_gain_album_peak: None,
+                        added_at: 0,
+                        last_modified: 0,
+                        tags,
+                    });
+                }
+            }
+        }
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        songs
+    }
+
+    /// Generate the songs and add them to `db` in one transaction.
+    pub fn populate(&self, db: &Database) -> Result<Vec<Song>> {
+        let songs = self.songs();
+        db.add_songs(&songs)?;
+        Ok(songs)
+    }
+
+    /// Create `dir/library.db` holding the generated songs.
+    pub fn build_in(&self, dir: &Path) -> Result<(Database, Vec<Song>)> {
+        let db = Database::open(dir.join("library.db").to_str().unwrap())?;
+        let songs = self.populate(&db)?;
+        Ok((db, songs))
+    }
+}
+
+fn artist_name(rng: &mut Rng, index: usize) -> String {
+    let first = if rng.chance(4) {
+        ACCENTED_WORDS[rng.below(ACCENTED_WORDS.len())]
+    } else {
+        NAME_WORDS[rng.below(NAME_WORDS.len())]
+    };
+    let second = NAME_WORDS[rng.below(NAME_WORDS.len())];
+    // The index keeps names unique; "The" bands exercise article handling.
+    if rng.chance(20) {
+        format!("The {first} {second} {index}")
+    } else {
+        format!("{first} {second} {index}")
+    }
+}
@@ -1,4 +1,9 @@
+pub mod dsd;
 pub mod generator;
+pub mod library;
+pub mod mp4;
+pub mod opus;
 pub mod pregenerated;
 
 pub use generator::{AudioFormat, FixtureGenerator, TestMetadata};
+pub use library::SyntheticLibrary;
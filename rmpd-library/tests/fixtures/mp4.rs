@@ -0,0 +1,223 @@
+//! Synthetic M4A fixtures (AAC or ALAC) with iTunes metadata atoms.
+//!
+//! Built atom by atom so tests can set the freeform `----` atoms FFmpeg does
+//! not write (`iTunSMPB`, lower-case `replaygain_*`): a single sound track
+//! whose sample description and durations are real, an `ilst` under
+//! `moov/udta/meta`, and a zero-filled `mdat`. Not meant to be decoded.
+
+use std::path::Path;
+
+/// Sample rate of every fixture track.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Audio codec of the fixture's sample description.
+#[derive(Debug, Clone, Copy)]
+pub enum Codec {
+    /// AAC-LC (`mp4a` + `esds`), 1024-sample frames.
+    Aac,
+    /// ALAC (`alac` + its magic cookie) at the given bit depth.
+    Alac { bits: u8 },
+}
+
+/// One `ilst` item.
+#[derive(Debug, Clone, Copy)]
+pub enum Item<'a> {
+    /// UTF-8 text under an Apple fourcc such as `©nam`.
+    Text(&'a [u8; 4], &'a str),
+    /// `trkn`: track number and total.
+    Track(u16, u16),
+    /// `----:com.apple.iTunes:<name>` text.
+    Freeform(&'a str, &'a str),
+}
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out
+}
+
+/// Full box: version 0 and `flags` in front of `body`.
+fn full_atom(kind: &[u8; 4], flags: u32, body: &[u8]) -> Vec<u8> {
+    let mut full = flags.to_be_bytes().to_vec();
+    full.extend_from_slice(body);
+    atom(kind, &full)
+}
+
+fn data_atom(type_code: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = type_code.to_be_bytes().to_vec();
+    body.extend_from_slice(&[0; 4]); // locale
+    body.extend_from_slice(payload);
+    atom(b"data", &body)
+}
+
+fn ilst_item(item: &Item) -> Vec<u8> {
+    match item {
+        Item::Text(fourcc, text) => atom(fourcc, &data_atom(1, text.as_bytes())),
+        Item::Track(track, total) => {
+            let mut payload = vec![0, 0];
+            payload.extend_from_slice(&track.to_be_bytes());
+            payload.extend_from_slice(&total.to_be_bytes());
+            payload.extend_from_slice(&[0, 0]);
+            atom(b"trkn", &data_atom(0, &payload))
+        }
+        Item::Freeform(name, text) => {
+            let mut body = full_atom(b"mean", 0, b"com.apple.iTunes");
+            body.extend(full_atom(b"name", 0, name.as_bytes()));
+            body.extend(data_atom(1, text.as_bytes()));
+            atom(b"----", &body)
+        }
+    }
+}
+
+/// Unity transformation matrix of `mvhd`/`tkhd`.
+fn matrix() -> Vec<u8> {
+    [0x0001_0000u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000]
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect()
+}
+
+fn sample_entry(codec: Codec) -> Vec<u8> {
+    let bits = match codec {
+        Codec::Aac => 16,
+        Codec::Alac { bits } => bits,
+    };
+    let mut entry = vec![0; 6]; // reserved
+    entry.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+    entry.extend_from_slice(&[0; 8]); // version, revision, vendor
+    entry.extend_from_slice(&2u16.to_be_bytes()); // channels
+    entry.extend_from_slice(&u16::from(bits).to_be_bytes());
+    entry.extend_from_slice(&[0; 4]); // compression id, packet size
+    entry.extend_from_slice(&(SAMPLE_RATE << 16).to_be_bytes());
+
+    match codec {
+        Codec::Aac => {
+            // AudioSpecificConfig: AAC-LC, 44.1 kHz, stereo.
+            let dsi = [0x05, 0x02, 0x12, 0x10];
+            let mut config = vec![0x04, 13 + dsi.len() as u8, 0x40, 0x15];
+            config.extend_from_slice(&[0; 3]); // buffer size
+            config.extend_from_slice(&128_000u32.to_be_bytes()); // max bitrate
+            config.extend_from_slice(&128_000u32.to_be_bytes()); // avg bitrate
+            config.extend_from_slice(&dsi);
+            let mut es = vec![0x03, (3 + config.len() + 3) as u8, 0, 1, 0];
+            es.extend_from_slice(&config);
+            es.extend_from_slice(&[0x06, 1, 0x02]); // SLConfig
+            entry.extend(full_atom(b"esds", 0, &es));
+            atom(b"mp4a", &entry)
+        }
+        Codec::Alac { bits } => {
+            let mut cookie = 4096u32.to_be_bytes().to_vec(); // frame length
+            cookie.extend_from_slice(&[0, bits, 40, 10, 14, 2]);
+            cookie.extend_from_slice(&255u16.to_be_bytes()); // max run
+            cookie.extend_from_slice(&0u32.to_be_bytes()); // max frame bytes
+            cookie.extend_from_slice(&0u32.to_be_bytes()); // avg bitrate
+            cookie.extend_from_slice(&SAMPLE_RATE.to_be_bytes());
+            entry.extend(full_atom(b"alac", 0, &cookie));
+            atom(b"alac", &entry)
+        }
+    }
+}
+
+/// Write an M4A file whose track lasts `samples` samples at [`SAMPLE_RATE`],
+/// tagged with `items`.
+pub fn write_m4a(path: &Path, codec: Codec, samples: u32, items: &[Item]) {
+    let mut ftyp = b"M4A ".to_vec();
+    ftyp.extend_from_slice(&0u32.to_be_bytes());
+    ftyp.extend_from_slice(b"M4A mp42isom");
+
+    let mut mvhd = vec![0; 8]; // creation, modification time
+    mvhd.extend_from_slice(&SAMPLE_RATE.to_be_bytes());
+    mvhd.extend_from_slice(&samples.to_be_bytes());
+    mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate
+    mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume
+    mvhd.extend_from_slice(&[0; 10]);
+    mvhd.extend(matrix());
+    mvhd.extend_from_slice(&[0; 24]);
+    mvhd.extend_from_slice(&2u32.to_be_bytes()); // next track id
+
+    let mut tkhd = vec![0; 8];
+    tkhd.extend_from_slice(&1u32.to_be_bytes()); // track id
+    tkhd.extend_from_slice(&[0; 4]);
+    tkhd.extend_from_slice(&samples.to_be_bytes());
+    tkhd.extend_from_slice(&[0; 8]);
+    tkhd.extend_from_slice(&[0, 0, 0, 0, 0x01, 0x00, 0, 0]); // layer, group, volume
+    tkhd.extend(matrix());
+    tkhd.extend_from_slice(&[0; 8]); // width, height
+
+    let mut mdhd = vec![0; 8];
+    mdhd.extend_from_slice(&SAMPLE_RATE.to_be_bytes());
+    mdhd.extend_from_slice(&samples.to_be_bytes());
+    mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]); // language "und"
+
+    let mut hdlr = vec![0; 4];
+    hdlr.extend_from_slice(b"soun");
+    hdlr.extend_from_slice(&[0; 13]);
+
+    let frame_len = match codec {
+        Codec::Aac => 1024,
+        Codec::Alac { .. } => 4096,
+    };
+    let frames = samples.div_ceil(frame_len);
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend(sample_entry(codec));
+    let mut stts = 1u32.to_be_bytes().to_vec();
+    stts.extend_from_slice(&frames.to_be_bytes());
+    stts.extend_from_slice(&frame_len.to_be_bytes());
+    let mut stsc = 1u32.to_be_bytes().to_vec();
+    for v in [1u32, frames, 1] {
+        stsc.extend_from_slice(&v.to_be_bytes());
+    }
+    let frame_bytes = 16u32;
+    let mut stsz = frame_bytes.to_be_bytes().to_vec();
+    stsz.extend_from_slice(&frames.to_be_bytes());
+    let mut stbl = full_atom(b"stsd", 0, &stsd);
+    stbl.extend(full_atom(b"stts", 0, &stts));
+    stbl.extend(full_atom(b"stsc", 0, &stsc));
+    stbl.extend(full_atom(b"stsz", 0, &stsz));
+    // stco is patched below, once the mdat offset is known.
+    stbl.extend(full_atom(b"stco", 0, &[0, 0, 0, 1, 0, 0, 0, 0]));
+
+    let mut minf = full_atom(b"smhd", 0, &[0; 4]);
+    let dref = full_atom(
+        b"dref",
+        0,
+        &[&1u32.to_be_bytes()[..], &full_atom(b"url ", 1, &[])].concat(),
+    );
+    minf.extend(atom(b"dinf", &dref));
+    minf.extend(atom(b"stbl", &stbl));
+
+    let mut mdia = full_atom(b"mdhd", 0, &mdhd);
+    mdia.extend(full_atom(b"hdlr", 0, &hdlr));
+    mdia.extend(atom(b"minf", &minf));
+
+    let mut trak = full_atom(b"tkhd", 7, &tkhd);
+    trak.extend(atom(b"mdia", &mdia));
+
+    let mut meta_hdlr = vec![0; 4];
+    meta_hdlr.extend_from_slice(b"mdirappl");
+    meta_hdlr.extend_from_slice(&[0; 9]);
+    let mut meta = full_atom(b"hdlr", 0, &meta_hdlr);
+    meta.extend(atom(
+        b"ilst",
+        &items.iter().flat_map(ilst_item).collect::<Vec<_>>(),
+    ));
+    let udta = full_atom(b"meta", 0, &meta);
+
+    let mut moov = full_atom(b"mvhd", 0, &mvhd);
+    moov.extend(atom(b"trak", &trak));
+    moov.extend(atom(b"udta", &udta));
+
+    let mut file = atom(b"ftyp", &ftyp);
+    file.extend(atom(b"moov", &moov));
+    let mdat_data_offset = (file.len() + 8) as u32;
+    let stco_offset = find_atom(&file, b"stco").expect("stco written above") + 16;
+    file[stco_offset..stco_offset + 4].copy_from_slice(&mdat_data_offset.to_be_bytes());
+    file.extend(atom(b"mdat", &vec![0; (frames * frame_bytes) as usize]));
+    std::fs::write(path, file).unwrap();
+}
+
+/// Offset of the first atom of type `kind` (its size field).
+fn find_atom(file: &[u8], kind: &[u8; 4]) -> Option<usize> {
+    file.windows(4).position(|w| w == kind).map(|pos| pos - 4)
+}
@@ -0,0 +1,86 @@
+//! Synthetic Ogg Opus fixtures with arbitrary OpusTags comments.
+//!
+//! Assembled page by page so tests control every comment, including the
+//! `R128_*` gains: an `OpusHead` and an `OpusTags` page followed by one page
+//! of 20 ms zero-length frames. Enough for lofty and the library's Opus tag
+//! handling; not meant to be decoded.
+
+use std::path::Path;
+
+/// Pre-skip written into `OpusHead`, the usual libopus value.
+pub const PRE_SKIP: u16 = 312;
+
+const SERIAL: u32 = 0x5270_6d64;
+
+/// Ogg's CRC-32: polynomial 0x04C11DB7, no reflection, zero initial value.
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |crc, &byte| {
+        let mut crc = crc ^ (u32::from(byte) << 24);
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// One Ogg page holding `packets`, each ending on this page.
+fn ogg_page(header_type: u8, granule: u64, sequence: u32, packets: &[Vec<u8>]) -> Vec<u8> {
+    let mut lacing = Vec::new();
+    for packet in packets {
+        lacing.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+        lacing.push((packet.len() % 255) as u8);
+    }
+    assert!(lacing.len() <= 255, "too many segments for one page");
+
+    let mut page = b"OggS".to_vec();
+    page.push(0); // stream structure version
+    page.push(header_type);
+    page.extend_from_slice(&granule.to_le_bytes());
+    page.extend_from_slice(&SERIAL.to_le_bytes());
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&[0; 4]); // CRC, filled in below
+    page.push(lacing.len() as u8);
+    page.extend_from_slice(&lacing);
+    for packet in packets {
+        page.extend_from_slice(packet);
+    }
+    let crc = ogg_crc(&page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+    page
+}
+
+/// Write a stereo Ogg Opus file of `frames` (at most 255) 20 ms frames with
+/// `comments` as OpusTags.
+pub fn write_opus(path: &Path, frames: u32, comments: &[(&str, &str)]) {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(2); // channels
+    head.extend_from_slice(&PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes()); // input sample rate
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // mapping family: mono/stereo
+
+    let mut tags = b"OpusTags".to_vec();
+    let vendor = b"rmpd test fixtures";
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for (key, value) in comments {
+        let comment = format!("{key}={value}");
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+    }
+
+    // TOC 0xFC: CELT fullband 20 ms, stereo, one frame; no frame data.
+    let audio: Vec<Vec<u8>> = (0..frames).map(|_| vec![0xFC]).collect();
+    let granule = u64::from(frames) * 960 + u64::from(PRE_SKIP);
+
+    let mut file = ogg_page(0x02, 0, 0, &[head]);
+    file.extend(ogg_page(0x00, 0, 1, &[tags]));
+    file.extend(ogg_page(0x04, granule, 2, &audio));
+    std::fs::write(path, file).unwrap();
+}
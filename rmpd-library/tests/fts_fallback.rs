@@ -0,0 +1,51 @@
+//! `search any` without the FTS5 index. Kept in its own test binary because
+//! `set_fts_enabled` switches the index off for the whole process.
+use rmpd_core::test_utils::make_test_song;
+use rmpd_library::database::{Database, set_fts_enabled};
+use tempfile::TempDir;
+
+#[test]
+fn search_falls_back_to_substring_match_and_index_is_rebuilt() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir
+        .path()
+        .join("fallback.db")
+        .to_string_lossy()
+        .to_string();
+
+    set_fts_enabled(false);
+    let db = Database::open(&db_path).unwrap();
+    db.add_song(&make_test_song("a/one.flac", 1)).unwrap();
+    db.add_song(&make_test_song("a/two.flac", 2)).unwrap();
+
+    // Substring, case-insensitive, over the indexed tags only.
+    assert_eq!(db.search_songs("rack 2").unwrap().len(), 1);
+    assert_eq!(db.search_songs("test artist").unwrap().len(), 2);
+    assert_eq!(db.search_songs("Track*").unwrap().len(), 2);
+    assert!(
+        db.search_songs("2024").unwrap().is_empty(),
+        "date is not indexed"
+    );
+
+    // LIKE wildcards and the escape character itself are matched literally.
+    let mut song = make_test_song("a/three.flac", 3);
+    song.tags[0].1 = r"AC\DC 100%".to_string();
+    db.add_song(&song).unwrap();
+    let found = db.search_songs("\\").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path.as_str(), "a/three.flac");
+    assert_eq!(db.search_songs(r"C\D").unwrap().len(), 1);
+    assert_eq!(db.search_songs("100%").unwrap().len(), 1);
+    assert!(db.search_songs("Track_").unwrap().is_empty());
+    db.delete_song_by_path("a/three.flac").unwrap();
+    db.delete_song_by_path("a/one.flac").unwrap();
+    drop(db);
+
+    // With FTS back on, the index misses the songs added meanwhile and is
+    // rebuilt on open.
+    set_fts_enabled(true);
+    let db = Database::open(&db_path).unwrap();
+    let found = db.search_songs("Track").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path.as_str(), "a/two.flac");
+}
@@ -1,5 +1,10 @@
-/// Regression tests for `Scanner::collect_audio_files`'s directory-tree walk.
+/// Regression tests for `Scanner`: the directory-tree walk, lyrics sidecars,
+/// playlist files and dry-run planning.
+use camino::Utf8PathBuf;
+use rmpd_core::config::MusicRoot;
 use rmpd_core::event::EventBus;
+use rmpd_core::test_utils::{make_test_song, write_wav};
+use rmpd_library::LyricsSource;
 use rmpd_library::database::Database;
 use rmpd_library::scanner::Scanner;
 use tempfile::TempDir;
@@ -49,3 +54,308 @@ fn scan_with_symlink_cycle_terminates() {
          instead of recursing until the OS's own symlink-loop limit errors out"
     );
 }
+
+/// `.lrc` sidecars are picked up, and dropped again, by rescans even when the
+/// audio file itself is unchanged.
+#[test]
+fn scan_tracks_lyrics_sidecars() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir(&music_dir).expect("create music dir");
+    write_wav(&music_dir.join("song.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let scanner = Scanner::new(EventBus::new(), false);
+
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(database.get_lyrics("song.wav").unwrap(), None);
+
+    std::fs::write(
+        music_dir.join("song.lrc"),
+        "[ti:Song]\n[00:00.10]Hello\n[00:00.20]World\n",
+    )
+    .unwrap();
+    let stats = scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(stats.updated, 1, "new sidecar should trigger a re-read");
+    let lyrics = database.get_lyrics("song.wav").unwrap().expect("lyrics");
+    assert_eq!(lyrics.text, "[ti:Song]\n[00:00.10]Hello\n[00:00.20]World");
+    assert!(lyrics.synced);
+    assert_eq!(lyrics.source, LyricsSource::Sidecar);
+
+    let stats = scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(stats.updated, 0, "unchanged sidecar is not re-read");
+
+    std::fs::remove_file(music_dir.join("song.lrc")).unwrap();
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(database.get_lyrics("song.wav").unwrap(), None);
+}
+
+/// Playlist files in the music directory show up in their directory's
+/// listing and disappear again once deleted.
+#[test]
+fn scan_records_playlist_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(music_dir.join("album")).expect("create album dir");
+    write_wav(&music_dir.join("album/song.wav"), 4410);
+    std::fs::write(music_dir.join("album/album.m3u"), "song.wav\n").unwrap();
+    std::fs::write(
+        music_dir.join("all.pls"),
+        "[playlist]\nFile1=album/song.wav\n",
+    )
+    .unwrap();
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let scanner = Scanner::new(EventBus::new(), false);
+    scanner.scan_directory(&database, &music_dir).unwrap();
+
+    let album = database.list_directory("album").unwrap();
+    let names: Vec<&str> = album.playlists.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(names, ["album/album.m3u"]);
+    let root = database.list_directory("").unwrap();
+    let names: Vec<&str> = root.playlists.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(names, ["all.pls"]);
+
+    std::fs::remove_file(music_dir.join("album/album.m3u")).unwrap();
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    assert!(
+        database
+            .list_directory("album")
+            .unwrap()
+            .playlists
+            .is_empty()
+    );
+}
+
+/// A dry run reports additions, updates and removals without writing to the
+/// database.
+#[test]
+fn plan_reports_changes_without_writing() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(music_dir.join("album")).expect("create album dir");
+    write_wav(&music_dir.join("album/a.wav"), 4410);
+    write_wav(&music_dir.join("album/b.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let scanner = Scanner::new(EventBus::new(), false);
+
+    let report = scanner.plan_directory(&database, &music_dir).unwrap();
+    assert_eq!(report.added, ["album/a.wav", "album/b.wav"]);
+    assert!(report.updated.is_empty());
+    assert!(report.removed.is_empty());
+    assert_eq!(
+        database.count_songs().unwrap(),
+        0,
+        "dry run must not add songs"
+    );
+    assert!(database.list_local_song_paths().unwrap().is_empty());
+
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    let report = scanner.plan_directory(&database, &music_dir).unwrap();
+    assert!(report.added.is_empty() && report.updated.is_empty());
+
+    // A read-only handle plans the same, and a missing database file plans
+    // everything as new without being created.
+    let read_only = Database::open_read_only(db_path.to_str().unwrap()).unwrap();
+    let report = scanner.plan_directory(&read_only, &music_dir).unwrap();
+    assert!(report.added.is_empty() && report.updated.is_empty());
+    assert!(read_only.add_song(&make_test_song("x.wav", 1)).is_err());
+    let missing = temp_dir.path().join("missing/rmpd.db");
+    let empty = Database::open_read_only(missing.to_str().unwrap()).unwrap();
+    let report = scanner.plan_directory(&empty, &music_dir).unwrap();
+    assert_eq!(report.added, ["album/a.wav", "album/b.wav"]);
+    assert!(!missing.parent().unwrap().exists());
+
+    let future = std::time::SystemTime::now() + std::time::Duration::from_secs(3600);
+    std::fs::File::options()
+        .write(true)
+        .open(music_dir.join("album/a.wav"))
+        .unwrap()
+        .set_modified(future)
+        .unwrap();
+    std::fs::remove_file(music_dir.join("album/b.wav")).unwrap();
+    write_wav(&music_dir.join("c.wav"), 4410);
+
+    let report = scanner.plan_directory(&database, &music_dir).unwrap();
+    assert_eq!(report.added, ["c.wav"]);
+    assert_eq!(report.updated, ["album/a.wav"]);
+    assert_eq!(report.removed, ["album/b.wav"]);
+    assert_eq!(
+        database.count_songs().unwrap(),
+        2,
+        "dry run must not touch rows"
+    );
+}
+
+#[test]
+fn scan_merges_extra_music_roots() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    let external = temp_dir.path().join("external");
+    std::fs::create_dir_all(&music_dir).expect("create music dir");
+    std::fs::create_dir_all(external.join("album")).expect("create external dir");
+    write_wav(&music_dir.join("a.wav"), 4410);
+    write_wav(&external.join("album/x.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let roots = vec![MusicRoot {
+        name: "ext".to_owned(),
+        path: Utf8PathBuf::from_path_buf(external).unwrap(),
+    }];
+    let scanner = Scanner::new(EventBus::new(), false).with_roots(roots);
+
+    let report = scanner.plan_directory(&database, &music_dir).unwrap();
+    assert_eq!(report.added, ["a.wav", "ext/album/x.wav"]);
+
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(
+        database.list_local_song_paths().unwrap(),
+        ["a.wav", "ext/album/x.wav"]
+    );
+
+    let listing = database.list_directory("").unwrap();
+    assert!(listing.directories.iter().any(|(path, _)| path == "ext"));
+    let listing = database.list_directory("ext/album").unwrap();
+    assert_eq!(listing.songs.len(), 1);
+}
+
+#[test]
+fn rescan_invalidates_artwork_of_changed_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(&music_dir).expect("create music dir");
+    write_wav(&music_dir.join("a.wav"), 4410);
+    write_wav(&music_dir.join("b.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let scanner = Scanner::new(EventBus::new(), false);
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    for song in ["a.wav", "b.wav"] {
+        database
+            .store_artwork(song, "front", "image/png", b"old", "hash")
+            .unwrap();
+    }
+
+    let future = std::time::SystemTime::now() + std::time::Duration::from_secs(3600);
+    std::fs::File::options()
+        .write(true)
+        .open(music_dir.join("a.wav"))
+        .unwrap()
+        .set_modified(future)
+        .unwrap();
+    scanner.scan_directory(&database, &music_dir).unwrap();
+
+    assert!(!database.has_artwork("a.wav", "front").unwrap());
+    assert!(
+        database.has_artwork("b.wav", "front").unwrap(),
+        "unchanged files keep their cached art"
+    );
+}
+
+/// A rescan drops songs whose files were deleted, with their stickers and
+/// cached art, and announces each removal.
+#[test]
+fn rescan_prunes_deleted_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(&music_dir).expect("create music dir");
+    write_wav(&music_dir.join("a.wav"), 4410);
+    write_wav(&music_dir.join("b.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let event_bus = EventBus::new();
+    let scanner = Scanner::new(event_bus.clone(), false);
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    database.set_sticker("b.wav", "rating", "5").unwrap();
+    database
+        .store_artwork("b.wav", "front", "image/png", b"art", "hash")
+        .unwrap();
+
+    std::fs::remove_file(music_dir.join("b.wav")).unwrap();
+    let mut events = event_bus.subscribe();
+    let stats = scanner.scan_directory(&database, &music_dir).unwrap();
+
+    assert_eq!(stats.removed, 1);
+    assert_eq!(database.list_local_song_paths().unwrap(), ["a.wav"]);
+    assert_eq!(database.get_sticker("b.wav", "rating").unwrap(), None);
+    assert!(!database.has_artwork("b.wav", "front").unwrap());
+    let mut deleted = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let rmpd_core::event::Event::SongDeleted { path } = event {
+            deleted.push(path);
+        }
+    }
+    assert_eq!(deleted, ["b.wav"]);
+}
+
+/// On a case-sensitive filesystem two spellings are two files, even when
+/// hard-linked to the same inode; only a case-insensitive filesystem (where
+/// the stored spelling resolves to the file without being listed) merges them.
+#[test]
+fn scan_keeps_hard_linked_case_variants_apart() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(&music_dir).expect("create music dir");
+    write_wav(&music_dir.join("a.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let scanner = Scanner::new(EventBus::new(), false);
+    scanner.scan_directory(&database, &music_dir).unwrap();
+
+    if std::fs::hard_link(music_dir.join("a.wav"), music_dir.join("A.wav")).is_err() {
+        return; // case-insensitive filesystem: the link name already exists
+    }
+    scanner.scan_directory(&database, &music_dir).unwrap();
+    assert_eq!(
+        database.list_local_song_paths().unwrap(),
+        ["A.wav", "a.wav"]
+    );
+}
+
+/// Every scan brackets itself with update events, but only a scan that
+/// changed the library reports a database change.
+#[test]
+fn scan_reports_database_change_only_when_songs_change() {
+    use rmpd_core::event::Event;
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let music_dir = temp_dir.path().join("music");
+    std::fs::create_dir_all(&music_dir).expect("create music dir");
+    write_wav(&music_dir.join("a.wav"), 4410);
+
+    let db_path = temp_dir.path().join("test.db");
+    let database = Database::open(db_path.to_str().unwrap()).expect("open database");
+    let event_bus = EventBus::new();
+    let scanner = Scanner::new(event_bus.clone(), false);
+
+    let scan = || {
+        let mut events = event_bus.subscribe();
+        let stats = scanner.scan_directory(&database, &music_dir).unwrap();
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                Event::DatabaseUpdateStarted => seen.push("started"),
+                Event::DatabaseChanged => seen.push("changed"),
+                Event::DatabaseUpdateFinished => seen.push("finished"),
+                _ => {}
+            }
+        }
+        (stats, seen)
+    };
+
+    let (stats, seen) = scan();
+    assert!(stats.changed());
+    assert_eq!(seen, ["started", "changed", "finished"]);
+
+    let (stats, seen) = scan();
+    assert!(!stats.changed());
+    assert_eq!(seen, ["started", "finished"]);
+}
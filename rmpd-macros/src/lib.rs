@@ -2,24 +2,62 @@
 //!
 //! Provides `#[derive(CommandMetadata)]` which generates `command_name()` and
 //! `command_required_permission()` methods from `#[command(...)]` attributes on
-//! each enum variant.
+//! each enum variant, plus a name-sorted `COMMANDS` table of
+//! `crate::registry::CommandSpec` entries (the central command registry used
+//! by the parser, the dispatcher and the `commands`/`notcommands` reflection
+//! commands).
 //!
 //! # Attribute syntax
 //!
 //! ```ignore
-//! #[command(name = "play", permission = 4)]
+//! #[command(name = "play", permission = 4, args = "0..=1")]
 //! Play { position: Option<u32> },
 //! ```
 //!
 //! - `name` (required): the MPD wire name of the command.
 //! - `permission` (optional, default `0`): the required permission bitmask (`u8`).
+//! - `args` (optional, default `"0.."`): accepted argument count, written as
+//!   `"N"`, `"MIN..=MAX"` or `"MIN.."` (unbounded).
+//! - `hidden` (optional flag): registered for parsing but not advertised by
+//!   `commands` (pseudo-commands such as `noidle` or `command_list_begin`).
+//! - `internal` (optional flag): not a wire command at all (parser error
+//!   variants); left out of `COMMANDS` entirely.
+//!
+//! Variants sharing a wire name (e.g. the `sticker` subcommands) must agree on
+//! their permission, arity and visibility; a mismatch is a compile error.
+
+use std::collections::BTreeMap;
 
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{Data, DeriveInput, Fields, Lit, parse_macro_input};
 
-/// Derive `command_name(&self) -> &'static str` and
-/// `command_required_permission(&self) -> u8` from `#[command(...)]` attributes.
+/// Registry entry collected from one `#[command(...)]` attribute.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Spec {
+    permission: u8,
+    min_args: usize,
+    max_args: Option<usize>,
+    hidden: bool,
+}
+
+/// Parse an `args` value: `"N"`, `"MIN..=MAX"` or `"MIN.."`.
+fn parse_args(value: &str) -> Option<(usize, Option<usize>)> {
+    if let Some((min, max)) = value.split_once("..=") {
+        let min = min.trim().parse().ok()?;
+        let max = max.trim().parse().ok()?;
+        (min <= max).then_some((min, Some(max)))
+    } else if let Some(min) = value.strip_suffix("..") {
+        Some((min.trim().parse().ok()?, None))
+    } else {
+        let n = value.trim().parse().ok()?;
+        Some((n, Some(n)))
+    }
+}
+
+/// Derive `command_name(&self) -> &'static str`,
+/// `command_required_permission(&self) -> u8` and the `COMMANDS` registry
+/// from `#[command(...)]` attributes.
 #[proc_macro_derive(CommandMetadata, attributes(command))]
 pub fn derive_command_metadata(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -39,12 +77,19 @@ pub fn derive_command_metadata(input: TokenStream) -> TokenStream {
 
     let mut name_arms = Vec::new();
     let mut perm_arms = Vec::new();
+    let mut registry: BTreeMap<String, Spec> = BTreeMap::new();
 
     for variant in variants {
         let ident = &variant.ident;
 
         let mut cmd_name: Option<String> = None;
-        let mut cmd_perm: u8 = 0;
+        let mut spec = Spec {
+            permission: 0,
+            min_args: 0,
+            max_args: None,
+            hidden: false,
+        };
+        let mut internal = false;
 
         for attr in &variant.attrs {
             if !attr.path().is_ident("command") {
@@ -64,14 +109,36 @@ pub fn derive_command_metadata(input: TokenStream) -> TokenStream {
                     let value = meta.value()?;
                     let lit: Lit = value.parse()?;
                     if let Lit::Int(i) = lit {
-                        cmd_perm = i.base10_parse()?;
+                        spec.permission = i.base10_parse()?;
                     } else {
                         return Err(meta.error("expected integer literal for `permission`"));
                     }
+                } else if meta.path.is_ident("args") {
+                    let value = meta.value()?;
+                    let lit: Lit = value.parse()?;
+                    let parsed = match lit {
+                        Lit::Str(s) => parse_args(&s.value()),
+                        _ => None,
+                    };
+                    match parsed {
+                        Some((min, max)) => {
+                            spec.min_args = min;
+                            spec.max_args = max;
+                        }
+                        None => {
+                            return Err(meta
+                                .error("expected `args = \"N\"`, `\"MIN..=MAX\"` or `\"MIN..\"`"));
+                        }
+                    }
+                } else if meta.path.is_ident("hidden") {
+                    spec.hidden = true;
+                } else if meta.path.is_ident("internal") {
+                    internal = true;
                 } else {
-                    return Err(
-                        meta.error("unknown attribute key; expected `name` or `permission`")
-                    );
+                    return Err(meta.error(
+                        "unknown attribute key; expected `name`, `permission`, `args`, \
+                         `hidden` or `internal`",
+                    ));
                 }
                 Ok(())
             }) {
@@ -94,18 +161,64 @@ pub fn derive_command_metadata(input: TokenStream) -> TokenStream {
             }
         };
 
+        if !internal {
+            if let Some(existing) = registry.get(&cmd_name)
+                && *existing != spec
+            {
+                return syn::Error::new_spanned(
+                    variant,
+                    format!(
+                        "command `{}` is declared elsewhere with a different permission, \
+                         arity or visibility",
+                        cmd_name
+                    ),
+                )
+                .to_compile_error()
+                .into();
+            }
+            registry.insert(cmd_name.clone(), spec);
+        }
+
         let pattern = match &variant.fields {
             Fields::Unit => quote! { Self::#ident },
             Fields::Named(_) => quote! { Self::#ident { .. } },
             Fields::Unnamed(_) => quote! { Self::#ident(..) },
         };
 
+        let cmd_perm = spec.permission;
         name_arms.push(quote! { #pattern => #cmd_name, });
         perm_arms.push(quote! { #pattern => #cmd_perm, });
     }
 
+    let registry_entries = registry.iter().map(|(cmd_name, spec)| {
+        let Spec {
+            permission,
+            min_args,
+            hidden,
+            ..
+        } = *spec;
+        let max_args = match spec.max_args {
+            Some(max) => quote! { ::core::option::Option::Some(#max) },
+            None => quote! { ::core::option::Option::None },
+        };
+        quote! {
+            crate::registry::CommandSpec {
+                name: #cmd_name,
+                permission: #permission,
+                min_args: #min_args,
+                max_args: #max_args,
+                hidden: #hidden,
+            },
+        }
+    });
+
     let expanded = quote! {
         impl #name {
+            /// Every registered command, sorted by wire name.
+            pub const COMMANDS: &'static [crate::registry::CommandSpec] = &[
+                #(#registry_entries)*
+            ];
+
             /// Return the MPD wire name of this command (for ACK error messages).
             pub fn command_name(&self) -> &'static str {
                 match self {
@@ -27,7 +27,18 @@ impl PauseState {
 
 /// An audio output backend.
 ///
-/// All methods are called from a blocking (non-async) thread.
+/// All methods are called from a blocking (non-async) thread — each output
+/// runs on its own `MultiOutput` worker. The lifecycle is:
+///
+/// 1. **configure** — the plugin's factory (see
+///    [`OutputPlugin`](crate::output_registry::OutputPlugin)) reads its
+///    `[[output]]` settings and the stream format, without opening anything;
+/// 2. **open** — [`start`](Self::start) on the worker thread;
+/// 3. **play** — [`write`](Self::write) once per decoded chunk, interleaved
+///    with [`pause`](Self::pause) / [`resume`](Self::resume);
+/// 4. **drain** — [`drain`](Self::drain) when playback ends naturally, so
+///    buffered audio is not cut off;
+/// 5. **close** — [`stop`](Self::stop).
 pub trait AudioOutput: Send {
     /// Open the output device / file / pipe and prepare for playback.
     fn start(&mut self) -> Result<()>;
@@ -35,6 +46,12 @@ pub trait AudioOutput: Send {
     /// Write interleaved f32 PCM samples (range −1.0 … +1.0).
     fn write(&mut self, samples: &[f32]) -> Result<()>;
 
+    /// Block until everything passed to `write` has been played or flushed.
+    /// Default: no-op, for outputs that do not buffer.
+    fn drain(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Stop playback and close the underlying resource.
     fn stop(&mut self) -> Result<()>;
 
@@ -1,6 +1,9 @@
 //! Shared sample format conversion utilities for audio output backends.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
 
 /// Convert f32 samples to s16le bytes, writing into the provided buffer.
 /// The buffer is cleared and filled with the converted bytes.
@@ -43,6 +46,7 @@ pub struct SampleBuffer<T> {
     rx: Receiver<Vec<T>>,
     buffer: Vec<T>,
     pos: usize,
+    backlog: Option<Backlog>,
 }
 
 impl<T: Default + Copy> SampleBuffer<T> {
@@ -52,6 +56,16 @@ impl<T: Default + Copy> SampleBuffer<T> {
             rx,
             buffer: Vec::new(),
             pos: 0,
+            backlog: None,
+        }
+    }
+
+    /// Like [`SampleBuffer::new`], but reports every fully played chunk to
+    /// `backlog` so the writer can tell when the device has caught up.
+    pub fn with_backlog(rx: Receiver<Vec<T>>, backlog: Backlog) -> Self {
+        Self {
+            backlog: Some(backlog),
+            ..Self::new(rx)
         }
     }
 
@@ -68,6 +82,11 @@ impl<T: Default + Copy> SampleBuffer<T> {
         if self.pos < self.buffer.len() {
             let val = self.buffer[self.pos];
             self.pos += 1;
+            if self.pos == self.buffer.len()
+                && let Some(backlog) = &self.backlog
+            {
+                backlog.played(self.buffer.len());
+            }
             val
         } else {
             T::default()
@@ -75,6 +94,72 @@ impl<T: Default + Copy> SampleBuffer<T> {
     }
 }
 
+/// Count of samples handed to a device callback but not yet played.
+///
+/// The writer [`add`](Backlog::add)s each chunk before sending it and the
+/// callback's [`SampleBuffer`] subtracts it once the last sample has been
+/// read, so an output's `drain` can wait for the device to play out its tail
+/// before the stream is torn down. A chunk added once the device has played
+/// everything before it counts as an underrun (see [`crate::stats`]).
+#[derive(Clone, Debug, Default)]
+pub struct Backlog {
+    pending: Arc<AtomicUsize>,
+    /// A chunk was added since the last `reset` or drain, so running dry
+    /// before the next one is an underrun rather than a cold start.
+    fed: Arc<AtomicBool>,
+}
+
+impl Backlog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `samples` about to be queued for the device.
+    pub fn add(&self, samples: usize) {
+        if self.fed.swap(true, Ordering::AcqRel) && self.pending() == 0 {
+            crate::stats::record_underrun();
+        }
+        self.pending.fetch_add(samples, Ordering::AcqRel);
+    }
+
+    /// Record `samples` as no longer pending: played by the device, or
+    /// never delivered because the send failed.
+    pub fn played(&self, samples: usize) {
+        // Saturate: a chunk queued before a `reset` may finish afterwards.
+        let _ = self
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                Some(n.saturating_sub(samples))
+            });
+    }
+
+    /// Samples still waiting to be played.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    /// Forget everything queued, e.g. when the stream is dropped unplayed.
+    pub fn reset(&self) {
+        self.pending.store(0, Ordering::Release);
+        self.fed.store(false, Ordering::Release);
+    }
+
+    /// Block until every queued sample has been played or `timeout` passes.
+    /// Returns `false` on timeout (e.g. a device that stopped pulling data).
+    pub fn wait_empty(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.pending() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        // Drained on purpose: the next chunk starts afresh.
+        self.fed.store(false, Ordering::Release);
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,4 +237,44 @@ mod tests {
 
         assert_eq!(buf.next_sample(), 0);
     }
+
+    #[test]
+    fn backlog_tracks_fully_played_chunks() {
+        let (tx, rx) = sync_channel::<Vec<f32>>(2);
+        let backlog = Backlog::new();
+        let mut buf = SampleBuffer::with_backlog(rx, backlog.clone());
+
+        backlog.add(3);
+        tx.send(vec![1.0, 2.0]).unwrap();
+        tx.send(vec![3.0]).unwrap();
+
+        buf.next_sample();
+        assert_eq!(
+            backlog.pending(),
+            3,
+            "a partly played chunk is still pending"
+        );
+        buf.next_sample();
+        assert_eq!(backlog.pending(), 1);
+        assert!(!backlog.wait_empty(Duration::from_millis(20)));
+        buf.next_sample();
+        assert!(backlog.wait_empty(Duration::ZERO));
+    }
+
+    #[test]
+    fn backlog_counts_chunks_added_after_running_dry() {
+        let (tx, rx) = sync_channel::<Vec<f32>>(2);
+        let backlog = Backlog::new();
+        let mut buf = SampleBuffer::with_backlog(rx, backlog.clone());
+
+        backlog.add(1);
+        tx.send(vec![1.0]).unwrap();
+        buf.next_sample();
+        let before = crate::stats::underruns();
+        backlog.add(1);
+        assert!(
+            crate::stats::underruns() > before,
+            "the device played everything before the second chunk"
+        );
+    }
 }
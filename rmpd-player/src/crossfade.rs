@@ -10,6 +10,7 @@
 //! see `docs/PLUGIN_ARCHITECTURE.md`. This module is that integration's
 //! verified foundation.
 
+use rmpd_core::song::Song;
 use std::f32::consts::FRAC_PI_2;
 
 /// Equal-power (constant-power) crossfade gains for a normalised `progress`
@@ -137,6 +138,66 @@ pub fn mixramp_overlap_seconds(
     }
 }
 
+/// Largest loudness difference [`loudness_match_db`] evens out; beyond this
+/// the tags are more likely wrong than the tracks that different.
+pub const MAX_LOUDNESS_MATCH_DB: f32 = 12.0;
+
+/// Gain in dB that brings the incoming track to the outgoing track's
+/// loudness, from each track's ReplayGain track gain and the gain actually
+/// applied to it (both in dB). A track's level after gain is `applied - rg`
+/// relative to the ReplayGain reference, so with ReplayGain enabled the
+/// result is near zero and only the tracks' residual difference is matched.
+/// `None` when either track has no ReplayGain tag.
+#[must_use]
+pub fn loudness_match_db(
+    cur_rg_gain: Option<f32>,
+    cur_applied_db: f32,
+    next_rg_gain: Option<f32>,
+    next_applied_db: f32,
+) -> Option<f32> {
+    let cur_level = cur_applied_db - cur_rg_gain?;
+    let next_level = next_applied_db - next_rg_gain?;
+    Some((cur_level - next_level).clamp(-MAX_LOUDNESS_MATCH_DB, MAX_LOUDNESS_MATCH_DB))
+}
+
+/// Extra gain for the incoming track at `progress` through the overlap: the
+/// full `match_db` as it fades in, easing (in dB) to unity by the end of the
+/// window so the track carries on at its own level once the fade is over.
+#[must_use]
+pub fn loudness_match_gain(match_db: f32, progress: f32) -> f32 {
+    mixramp_db_to_gain(match_db * (1.0 - progress.clamp(0.0, 1.0)))
+}
+
+/// Level (dBFS) below which [`is_continuous`] counts a track boundary as
+/// silent, read from the MixRamp tags.
+pub const CONTINUITY_SILENCE_DB: f32 = -50.0;
+
+/// Longest stretch (seconds) below [`CONTINUITY_SILENCE_DB`] at a track
+/// boundary that still counts as no silence at all.
+pub const CONTINUITY_MAX_GAP: f32 = 0.05;
+
+/// Whether `next` carries straight on from `cur`, so a crossfade would blend
+/// over the music of a live or continuously mixed album: the next track of
+/// the same album and disc, or a boundary where the MixRamp tags show the
+/// music running into the very end of `cur` and from the very start of
+/// `next`. Such pairs play gaplessly instead.
+#[must_use]
+pub fn is_continuous(cur: &Song, next: &Song) -> bool {
+    let (cur_disc, cur_track) = cur.disc_and_track();
+    let (next_disc, next_track) = next.disc_and_track();
+    let same_album = cur.tag("album").is_some()
+        && cur.tag("album") == next.tag("album")
+        && cur.tag("albumartist") == next.tag("albumartist");
+    if same_album && cur_track > 0 && cur_disc == next_disc && next_track == cur_track + 1 {
+        return true;
+    }
+    let no_gap = |ramp: Option<&str>| {
+        ramp.and_then(|ramp| mixramp_interpolate(ramp, CONTINUITY_SILENCE_DB))
+            .is_some_and(|secs| (0.0..=CONTINUITY_MAX_GAP).contains(&secs))
+    };
+    no_gap(cur.tag("mixramp_end")) && no_gap(next.tag("mixramp_start"))
+}
+
 /// Interleaved-sample count for a fractional-seconds window.
 #[must_use]
 pub fn window_samples_secs(sample_rate: u32, channels: u8, seconds: f32) -> usize {
@@ -288,6 +349,80 @@ mod tests {
         );
     }
 
+    // ── loudness matching ─────────────────────────────────────────────────
+
+    #[test]
+    fn loudness_match_uses_post_gain_levels() {
+        // Without ReplayGain applied, a loud track (-9 dB gain) following a
+        // quiet one (+3 dB gain) enters 12 dB down.
+        assert_eq!(
+            loudness_match_db(Some(3.0), 0.0, Some(-9.0), 0.0),
+            Some(-12.0)
+        );
+        // With track gain applied both already sit at the reference level.
+        assert_eq!(
+            loudness_match_db(Some(3.0), 3.0, Some(-9.0), -9.0),
+            Some(0.0)
+        );
+        // Album gain applied: only the tracks' offset within their albums is left.
+        assert_eq!(
+            loudness_match_db(Some(3.0), 1.0, Some(-9.0), -8.0),
+            Some(-1.0)
+        );
+        assert_eq!(loudness_match_db(None, 0.0, Some(-9.0), 0.0), None);
+        assert_eq!(
+            loudness_match_db(Some(-20.0), 0.0, Some(10.0), 0.0),
+            Some(MAX_LOUDNESS_MATCH_DB)
+        );
+    }
+
+    #[test]
+    fn loudness_match_gain_eases_to_unity() {
+        assert!(close(loudness_match_gain(-6.0, 0.0), 0.501_187_2));
+        assert!(close(
+            loudness_match_gain(-6.0, 0.5),
+            mixramp_db_to_gain(-3.0)
+        ));
+        assert!(close(loudness_match_gain(-6.0, 1.0), 1.0));
+        assert!(close(loudness_match_gain(0.0, 0.3), 1.0));
+    }
+
+    // ── is_continuous ─────────────────────────────────────────────────────
+
+    fn song(tags: &[(&str, &str)]) -> Song {
+        let mut song = rmpd_core::test_utils::create_test_song(1, "x");
+        for (key, value) in tags {
+            song.tags
+                .push((rmpd_core::song::intern_tag_key(key), (*value).to_owned()));
+        }
+        song
+    }
+
+    #[test]
+    fn next_album_track_is_continuous() {
+        let track = |n| song(&[("album", "Live"), ("disc", "1"), ("track", n)]);
+        assert!(is_continuous(&track("3"), &track("4")));
+        assert!(!is_continuous(&track("4"), &track("3")));
+        assert!(!is_continuous(&track("3"), &track("5")));
+        let other = song(&[("album", "Studio"), ("disc", "1"), ("track", "4")]);
+        assert!(!is_continuous(&track("3"), &other));
+        // Without an album tag, track numbers alone say nothing.
+        assert!(!is_continuous(
+            &song(&[("track", "1")]),
+            &song(&[("track", "2")])
+        ));
+    }
+
+    #[test]
+    fn mixramp_tags_without_silence_are_continuous() {
+        let cur = song(&[("mixramp_end", "-60.00 0.00;-50.00 0.01;-40.00 0.02;")]);
+        let next = song(&[("mixramp_start", "-60.00 0.00;-50.00 0.02;-40.00 0.03;")]);
+        assert!(is_continuous(&cur, &next));
+        let fade_in = song(&[("mixramp_start", "-60.00 0.50;-50.00 1.20;-40.00 2.00;")]);
+        assert!(!is_continuous(&cur, &fade_in));
+        assert!(!is_continuous(&cur, &song(&[])));
+    }
+
     // ── window_samples_secs ───────────────────────────────────────────────
 
     #[test]
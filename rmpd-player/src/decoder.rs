@@ -1,3 +1,4 @@
+use crate::gapless::{self, GaplessTrimmer};
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::AudioFormat;
 use std::path::Path;
@@ -31,6 +32,8 @@ pub struct SymphoniaDecoder {
     channel_data_layout: Option<ChannelDataLayout>,
     bit_order: Option<BitOrder>,
     uses_pcm_conversion: bool,
+    /// Encoder delay/padding trimming for MP3 and AAC files that record it.
+    gapless: Option<GaplessTrimmer>,
     /// ICY "now playing" title handle when decoding a remote stream.
     stream_title: Option<rmpd_stream::TitleHandle>,
 }
@@ -40,10 +43,12 @@ impl SymphoniaDecoder {
         // Open the media source: a remote stream URL or a local file.
         let mut hint = Hint::new();
         let stream_title;
+        let gapless_info;
         let mss = if let Some(url) = path.to_str().filter(|s| rmpd_stream::is_http_uri(s)) {
             let source = rmpd_stream::HttpSource::connect(url)
                 .map_err(|e| RmpdError::Player(format!("Failed to open stream: {e}")))?;
             stream_title = Some(source.title_handle());
+            gapless_info = None;
             if let Some(ext) = url_extension(url) {
                 hint.with_extension(ext);
             }
@@ -52,6 +57,7 @@ impl SymphoniaDecoder {
             let file = std::fs::File::open(path)
                 .map_err(|e| RmpdError::Player(format!("Failed to open file: {e}")))?;
             stream_title = None;
+            gapless_info = gapless::read_gapless_info(path);
             if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
                 hint.with_extension(ext);
             }
@@ -96,9 +102,11 @@ impl SymphoniaDecoder {
         let channel_data_layout = audio.channel_data_layout;
         let bit_order = audio.bit_order;
 
-        // Calculate total duration from the track frame count and timebase.
-        let total_duration = match (track.num_frames, time_base) {
-            (Some(n_frames), Some(tb)) => tb
+        // Calculate total duration from the track frame count and timebase;
+        // a gapless track lasts exactly as long as its audio frames.
+        let total_duration = match (gapless_info, track.num_frames, time_base) {
+            (Some(info), ..) => Some(info.frames as f64 / f64::from(sample_rate)),
+            (None, Some(n_frames), Some(tb)) => tb
                 .calc_time(Timestamp::new(n_frames as i64))
                 .map(|t| t.as_secs_f64()),
             _ => None,
@@ -125,6 +133,7 @@ impl SymphoniaDecoder {
             channel_data_layout,
             bit_order,
             uses_pcm_conversion: false,
+            gapless: gapless_info.map(GaplessTrimmer::new),
             stream_title,
         })
     }
@@ -215,6 +224,11 @@ impl SymphoniaDecoder {
                 }
             }
 
+            // Only padding is left.
+            if self.gapless.as_ref().is_some_and(GaplessTrimmer::is_done) {
+                break;
+            }
+
             // Read the next packet.
             let packet = match self.reader.next_packet() {
                 Ok(Some(packet)) => packet,
@@ -230,6 +244,7 @@ impl SymphoniaDecoder {
                 }
                 Err(e) => {
                     tracing::error!("failed to read packet: {}", e);
+                    crate::stats::record_decode_error();
                     return Err(RmpdError::Player(format!("Failed to read packet: {e}")));
                 }
             };
@@ -253,8 +268,13 @@ impl SymphoniaDecoder {
             // Decode the packet.
             let decoded = match self.decoder.decode(&packet) {
                 Ok(decoded) => decoded,
-                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(SymphoniaError::DecodeError(_)) => {
+                    // A corrupt packet: skip it and keep playing.
+                    crate::stats::record_decode_error();
+                    continue;
+                }
                 Err(e) => {
+                    crate::stats::record_decode_error();
                     return Err(RmpdError::Player(format!("Failed to decode packet: {e}")));
                 }
             };
@@ -280,6 +300,12 @@ impl SymphoniaDecoder {
             // Copy decoded audio as interleaved f32 into the reusable buffer.
             decoded.copy_to_vec_interleaved(&mut self.sample_buf);
             self.sample_pos = 0;
+            if let Some(trimmer) = &mut self.gapless {
+                trimmer.trim(
+                    &mut self.sample_buf,
+                    usize::from(self.channels.unwrap_or(2)),
+                );
+            }
         }
 
         Ok(samples_written)
@@ -290,6 +316,14 @@ impl SymphoniaDecoder {
             return Err(RmpdError::Player("Invalid seek position".to_owned()));
         }
 
+        // Seek past the priming samples of a gapless track.
+        let position = match &mut self.gapless {
+            Some(trimmer) => {
+                let rate = f64::from(self.sample_rate);
+                trimmer.seek((position * rate) as u64) as f64 / rate
+            }
+            None => position,
+        };
         let time = Time::try_from_secs_f64(position)
             .ok_or_else(|| RmpdError::Player("Invalid seek position".to_owned()))?;
 
@@ -402,6 +436,157 @@ impl Decoder for SymphoniaDecoder {
     }
 }
 
+/// The decoder opened for one song: Symphonia, or the `ffmpeg` fallback
+/// for formats Symphonia cannot read.
+pub enum SongDecoder {
+    Symphonia(SymphoniaDecoder),
+    #[cfg(feature = "ffmpeg")]
+    Ffmpeg(crate::ffmpeg_decoder::FfmpegDecoder),
+}
+
+/// Open `path` with the first decoder that accepts it.
+///
+/// Symphonia is always tried first. With the `ffmpeg` feature, a local file
+/// Symphonia rejects is handed to [`FfmpegDecoder`](crate::ffmpeg_decoder::FfmpegDecoder).
+pub fn open_decoder(path: &Path) -> Result<SongDecoder> {
+    match SymphoniaDecoder::open(path) {
+        Ok(decoder) => Ok(SongDecoder::Symphonia(decoder)),
+        #[cfg(feature = "ffmpeg")]
+        Err(e) if !path.to_str().is_some_and(rmpd_stream::is_http_uri) => {
+            tracing::debug!(
+                "symphonia cannot decode {}: {e}; trying ffmpeg",
+                path.display()
+            );
+            crate::ffmpeg_decoder::FfmpegDecoder::open(path)
+                .map(SongDecoder::Ffmpeg)
+                .map_err(|ffmpeg_err| {
+                    RmpdError::Player(format!("{e} (ffmpeg fallback: {ffmpeg_err})"))
+                })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl SongDecoder {
+    /// The Symphonia decoder, for DSD-only operations.
+    fn dsd(&mut self) -> Result<&mut SymphoniaDecoder> {
+        match self {
+            Self::Symphonia(d) => Ok(d),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => Err(RmpdError::Player(
+                "DSD operations need the symphonia decoder".to_owned(),
+            )),
+        }
+    }
+
+    pub fn is_dsd(&self) -> bool {
+        match self {
+            Self::Symphonia(d) => d.is_dsd(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => false,
+        }
+    }
+
+    pub fn enable_pcm_conversion(&mut self, output_rate: u32) -> Result<()> {
+        match self {
+            Self::Symphonia(d) => d.enable_pcm_conversion(output_rate),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => Ok(()),
+        }
+    }
+
+    pub fn read(&mut self, buffer: &mut [f32]) -> Result<usize> {
+        match self {
+            Self::Symphonia(d) => d.read(buffer),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(d) => d.read(buffer),
+        }
+    }
+
+    pub fn seek(&mut self, position: f64) -> Result<()> {
+        match self {
+            Self::Symphonia(d) => d.seek(position),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(d) => d.seek(position),
+        }
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        match self {
+            Self::Symphonia(d) => d.format(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(d) => d.format(),
+        }
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        match self {
+            Self::Symphonia(d) => d.duration(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(d) => d.duration(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.format().sample_rate
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.format().channels
+    }
+
+    pub fn current_bitrate(&self) -> Option<u32> {
+        match self {
+            Self::Symphonia(d) => d.current_bitrate(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(d) => d.current_bitrate(),
+        }
+    }
+
+    pub fn stream_title(&self) -> Option<String> {
+        match self {
+            Self::Symphonia(d) => d.stream_title(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => None,
+        }
+    }
+
+    pub fn channel_data_layout(&self) -> Option<ChannelDataLayout> {
+        match self {
+            Self::Symphonia(d) => d.channel_data_layout(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => None,
+        }
+    }
+
+    pub fn bit_order(&self) -> Option<BitOrder> {
+        match self {
+            Self::Symphonia(d) => d.bit_order(),
+            #[cfg(feature = "ffmpeg")]
+            Self::Ffmpeg(_) => None,
+        }
+    }
+
+    pub fn read_dsd_raw(&mut self, buffer: &mut Vec<u8>) -> Result<usize> {
+        self.dsd()?.read_dsd_raw(buffer)
+    }
+}
+
+impl Decoder for SongDecoder {
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize> {
+        self.read(buffer)
+    }
+    fn seek(&mut self, position: f64) -> Result<()> {
+        self.seek(position)
+    }
+    fn format(&self) -> AudioFormat {
+        self.format()
+    }
+    fn duration(&self) -> Option<f64> {
+        self.duration()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Decoder plugin SPI (MPD-style DecoderPlugin registry)
 // ---------------------------------------------------------------------------
@@ -413,12 +598,12 @@ pub struct DecoderPlugin {
     pub mime_types: &'static [&'static str],
 }
 
-/// The Symphonia-backed decoder handles all of rmpd's supported formats.
+/// The Symphonia-backed decoder handles most of rmpd's supported formats.
 pub static SYMPHONIA_DECODER: DecoderPlugin = DecoderPlugin {
     name: "symphonia",
     suffixes: &[
         "flac", "mp3", "ogg", "oga", "opus", "wav", "wave", "aiff", "aif", "m4a", "mp4", "aac",
-        "alac", "ape", "wv", "mpc", "dsf", "dff", "webm", "mka", "caf",
+        "alac", "dsf", "dff", "webm", "mka", "caf",
     ],
     mime_types: &[
         "audio/flac",
@@ -429,14 +614,33 @@ pub static SYMPHONIA_DECODER: DecoderPlugin = DecoderPlugin {
         "audio/x-wav",
         "audio/aac",
         "audio/mp4",
-        "audio/x-ape",
-        "audio/x-wavpack",
         "audio/x-dsd",
+        "application/x-dsf",
+        "application/x-dff",
+    ],
+};
+
+/// The `ffmpeg` command-line fallback: legacy lossless and lossy formats
+/// Symphonia has no decoder for.
+#[cfg(feature = "ffmpeg")]
+pub static FFMPEG_DECODER: DecoderPlugin = DecoderPlugin {
+    name: "ffmpeg",
+    suffixes: &["wv", "mpc", "mp+", "mpp", "ape", "tta", "wma"],
+    mime_types: &[
+        "audio/x-wavpack",
+        "audio/x-musepack",
+        "audio/x-ape",
+        "audio/x-tta",
+        "audio/x-ms-wma",
     ],
 };
 
 /// All compiled-in decoder plugins (compile-time registry, MPD-style).
+#[cfg(not(feature = "ffmpeg"))]
 pub static DECODER_PLUGINS: &[&DecoderPlugin] = &[&SYMPHONIA_DECODER];
+/// All compiled-in decoder plugins (compile-time registry, MPD-style).
+#[cfg(feature = "ffmpeg")]
+pub static DECODER_PLUGINS: &[&DecoderPlugin] = &[&SYMPHONIA_DECODER, &FFMPEG_DECODER];
 
 /// Find a decoder plugin that lists `suffix` (case-insensitive, no leading dot).
 #[must_use]
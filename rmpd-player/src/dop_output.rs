@@ -1,6 +1,6 @@
 /// DoP-specific audio output using integer samples
 /// DoP requires exact bit patterns, so we use I32 format instead of F32
-use crate::conversion::SampleBuffer;
+use crate::conversion::{Backlog, SampleBuffer};
 use crate::cpal_utils::CpalDeviceConfig;
 use cpal::traits::{DeviceTrait, StreamTrait};
 use cpal::{Device, SampleFormat, Stream, StreamConfig};
@@ -46,6 +46,8 @@ pub struct DopOutput {
     sample_sender: Option<SyncSender<Vec<i32>>>,
     config: StreamConfig,
     is_paused: bool,
+    /// DoP samples sent to the device callback but not yet played.
+    backlog: Backlog,
 }
 
 impl DopOutput {
@@ -63,6 +65,7 @@ impl DopOutput {
             sample_sender: None,
             config: device_config.config,
             is_paused: false,
+            backlog: Backlog::new(),
         })
     }
 
@@ -84,7 +87,7 @@ impl DopOutput {
 
         let stream = match sample_format {
             SampleFormat::I32 | SampleFormat::I24 => {
-                let mut buf = SampleBuffer::new(rx);
+                let mut buf = SampleBuffer::with_backlog(rx, self.backlog.clone());
                 self.device
                     .build_output_stream(
                         self.config,
@@ -102,7 +105,7 @@ impl DopOutput {
             }
             _ => {
                 tracing::warn!("no I32 format available, using fallback conversion");
-                let mut buf = SampleBuffer::new(rx);
+                let mut buf = SampleBuffer::with_backlog(rx, self.backlog.clone());
                 self.device
                     .build_output_stream(
                         self.config,
@@ -157,10 +160,12 @@ impl DopOutput {
         let chunk_size = self.config.sample_rate as usize / 50 * self.config.channels as usize;
         for chunk in primer_samples.chunks(chunk_size) {
             // Bounded wait so a stalled callback can't hang priming forever.
+            self.backlog.add(chunk.len());
             if !matches!(
                 send_bounded(&tx, chunk.to_vec(), Duration::from_millis(500)),
                 SendOutcome::Sent
             ) {
+                self.backlog.played(chunk.len());
                 tracing::warn!("DoP primer send stalled; continuing");
                 break;
             }
@@ -188,15 +193,35 @@ impl DopOutput {
         // to playback rate). If the output callback stalls (device xrun or
         // disconnect), don't block forever — drop this buffer so the playback
         // loop stays responsive to stop/seek and the device can be released.
+        self.backlog.add(samples.len());
         match send_bounded(sender, samples.to_vec(), Duration::from_millis(500)) {
             SendOutcome::Sent => Ok(samples.len()),
-            SendOutcome::TimedOut => Ok(0),
+            SendOutcome::TimedOut => {
+                self.backlog.played(samples.len());
+                Ok(0)
+            }
             SendOutcome::Disconnected => {
+                self.backlog.played(samples.len());
                 Err(RmpdError::Player("DoP output stream closed".to_owned()))
             }
         }
     }
 
+    /// Wait for the DAC to play everything already written. Called at the end
+    /// of a DSD stream so `stop`'s PCM reset does not cut off its tail.
+    pub fn drain(&mut self) -> Result<()> {
+        if self.stream.is_none() || self.is_paused {
+            return Ok(());
+        }
+        if !self.backlog.wait_empty(Duration::from_secs(2)) {
+            tracing::warn!(
+                "DoP output drain timed out with {} samples unplayed",
+                self.backlog.pending()
+            );
+        }
+        Ok(())
+    }
+
     pub fn pause(&mut self) -> Result<()> {
         if let Some(ref stream) = self.stream {
             stream
@@ -245,6 +270,7 @@ impl DopOutput {
         }
         self.sample_sender = None;
         self.is_paused = false;
+        self.backlog.reset();
         Ok(())
     }
 
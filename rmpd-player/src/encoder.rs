@@ -1,10 +1,16 @@
-//! Pluggable PCM-to-wire encoders for network audio outputs.
+//! Pluggable PCM-to-wire encoders shared by streaming and recording outputs.
 //!
 //! Each encoder converts interleaved f32 samples to the byte format expected
-//! by a particular streaming protocol.  The trait is object-safe so outputs
-//! can choose an encoder at construction time.
+//! by a particular container or protocol.  The trait is object-safe so outputs
+//! can choose an encoder at construction time; [`create_encoder`] resolves the
+//! `encoder`, `quality` and `bitrate` keys of an `[[output]]` block against
+//! [`ENCODER_PLUGINS`], so every output that encodes accepts the same settings.
 
+use crate::conversion::f32_to_i16;
+use rmpd_core::config::OutputConfig;
+use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::AudioFormat;
+use std::ops::RangeInclusive;
 
 /// Encodes interleaved f32 PCM into a wire byte stream for network outputs.
 pub trait Encoder: Send {
@@ -17,6 +23,226 @@ pub trait Encoder: Send {
 
     /// Encode one chunk of interleaved f32 samples (−1.0 …= 1.0) to wire bytes.
     fn encode(&mut self, samples: &[f32]) -> Vec<u8>;
+
+    /// Flush samples buffered inside the encoder at end of stream.
+    /// Default: nothing is buffered.
+    fn finish(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Header to rewrite at offset 0 of a seekable sink (e.g. a recorder file)
+    /// once `body_bytes` of encoded audio have followed the initial header.
+    /// Default: `None`, the streaming header is already final.
+    fn final_header(&self, _body_bytes: u64) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+// ──────────────────────────────────────────────────────────────────────────────
+// Settings and registry
+// ──────────────────────────────────────────────────────────────────────────────
+
+/// Rate-control settings shared by every encoder, read from the MPD-style
+/// `quality` and `bitrate` keys of an `[[output]]` block. Each plugin declares
+/// which of them it honors; setting one it does not is a configuration error.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EncoderSettings {
+    /// Variable-bitrate quality (MPD `quality`, e.g. `-1.0 … 10.0`).
+    pub quality: Option<f32>,
+    /// Constant target bitrate in kbit/s (MPD `bitrate`).
+    pub bitrate: Option<u32>,
+}
+
+impl EncoderSettings {
+    /// Parse `quality` / `bitrate` from an output block. Like MPD, setting both
+    /// is a configuration error.
+    pub fn from_config(cfg: &OutputConfig) -> Result<Self> {
+        let quality = cfg
+            .setting_str("quality")
+            .map(|s| {
+                s.parse::<f32>().map_err(|_| {
+                    RmpdError::Config(format!("output \"{}\": invalid quality \"{s}\"", cfg.name))
+                })
+            })
+            .transpose()?;
+        let bitrate = cfg
+            .setting_str("bitrate")
+            .map(|s| {
+                s.parse::<u32>().map_err(|_| {
+                    RmpdError::Config(format!("output \"{}\": invalid bitrate \"{s}\"", cfg.name))
+                })
+            })
+            .transpose()?;
+        if quality.is_some() && bitrate.is_some() {
+            return Err(RmpdError::Config(format!(
+                "output \"{}\": quality and bitrate are both defined",
+                cfg.name
+            )));
+        }
+        Ok(Self { quality, bitrate })
+    }
+}
+
+/// Builds an encoder for a stream format.
+pub type EncoderFactory = fn(AudioFormat, &EncoderSettings) -> Result<Box<dyn Encoder>>;
+
+/// Static descriptor for one encoder backend.
+pub struct EncoderPlugin {
+    /// Canonical encoder name, as used in `encoder = "…"`.
+    pub name: &'static str,
+    /// Additional names that select this encoder.
+    pub aliases: &'static [&'static str],
+    /// Accepted `quality` values; `None` when the encoder has no quality
+    /// setting.
+    pub quality: Option<RangeInclusive<f32>>,
+    /// Accepted `bitrate` values in kbit/s; `None` when the encoder has no
+    /// bitrate setting.
+    pub bitrate: Option<RangeInclusive<u32>>,
+    pub factory: EncoderFactory,
+}
+
+impl EncoderPlugin {
+    /// Whether `name` (already lowercased) selects this encoder.
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    /// Reject settings this encoder does not honor or values out of its range.
+    fn check(&self, settings: &EncoderSettings) -> std::result::Result<(), String> {
+        if let Some(quality) = settings.quality {
+            match &self.quality {
+                None => return Err(format!("the {} encoder has no quality setting", self.name)),
+                Some(range) if !range.contains(&quality) => {
+                    return Err(format!(
+                        "{} quality {quality} is outside {} … {}",
+                        self.name,
+                        range.start(),
+                        range.end()
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(bitrate) = settings.bitrate {
+            match &self.bitrate {
+                None => return Err(format!("the {} encoder has no bitrate setting", self.name)),
+                Some(range) if !range.contains(&bitrate) => {
+                    return Err(format!(
+                        "{} bitrate {bitrate} kbit/s is outside {} … {}",
+                        self.name,
+                        range.start(),
+                        range.end()
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pcm_factory(format: AudioFormat, _settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    Ok(Box::new(PcmEncoder::new(format)))
+}
+
+fn wave_factory(format: AudioFormat, _settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    Ok(Box::new(WavEncoder::new(format)))
+}
+
+fn flac_factory(format: AudioFormat, _settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    if !(1..=8).contains(&format.channels) {
+        return Err(RmpdError::Player(format!(
+            "flac encoder supports 1-8 channels, got {}",
+            format.channels
+        )));
+    }
+    Ok(Box::new(FlacEncoder::new(format)))
+}
+
+pub static PCM_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "pcm",
+    aliases: &["null", "raw"],
+    quality: None,
+    bitrate: None,
+    factory: pcm_factory,
+};
+
+pub static WAVE_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "wave",
+    aliases: &["wav"],
+    quality: None,
+    bitrate: None,
+    factory: wave_factory,
+};
+
+pub static FLAC_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "flac",
+    aliases: &[],
+    quality: None,
+    bitrate: None,
+    factory: flac_factory,
+};
+
+/// All compiled-in encoder plugins (compile-time registry, MPD-style).
+pub static ENCODER_PLUGINS: &[&EncoderPlugin] = &[
+    &WAVE_ENCODER,
+    &FLAC_ENCODER,
+    #[cfg(feature = "vorbis")]
+    &crate::vorbis_encoder::VORBIS_ENCODER,
+    #[cfg(feature = "opus")]
+    &crate::opus_encoder::OPUS_ENCODER,
+    #[cfg(feature = "lame")]
+    &crate::lame_encoder::LAME_ENCODER,
+    &PCM_ENCODER,
+];
+
+/// Find an encoder plugin by name or alias (case-insensitive).
+#[must_use]
+pub fn encoder_plugin(name: &str) -> Option<&'static EncoderPlugin> {
+    let name = name.to_lowercase();
+    ENCODER_PLUGINS.iter().copied().find(|p| p.matches(&name))
+}
+
+/// Resolve `cfg`'s `encoder` key (`default` when unset) and its `quality` /
+/// `bitrate` keys, rejecting encoders that are not compiled in and settings
+/// the encoder does not honor.
+fn resolve_encoder(
+    cfg: &OutputConfig,
+    default: &str,
+) -> Result<(&'static EncoderPlugin, EncoderSettings)> {
+    let name = cfg
+        .setting_str("encoder")
+        .unwrap_or_else(|| default.to_owned());
+    let plugin = encoder_plugin(&name).ok_or_else(|| {
+        let available: Vec<&str> = ENCODER_PLUGINS.iter().map(|p| p.name).collect();
+        RmpdError::Config(format!(
+            "output \"{}\": unknown encoder \"{name}\" (available: {})",
+            cfg.name,
+            available.join(", ")
+        ))
+    })?;
+    let settings = EncoderSettings::from_config(cfg)?;
+    plugin
+        .check(&settings)
+        .map_err(|e| RmpdError::Config(format!("output \"{}\": {e}", cfg.name)))?;
+    Ok((plugin, settings))
+}
+
+/// Check the encoder settings of `cfg` without building an encoder, so a bad
+/// `[[output]]` block fails at startup rather than when playback begins.
+pub fn validate_encoder(cfg: &OutputConfig, default: &str) -> Result<()> {
+    resolve_encoder(cfg, default).map(|_| ())
+}
+
+/// Build the encoder selected by `cfg`'s `encoder` key (`default` when unset),
+/// configured from its `quality` / `bitrate` keys.
+pub fn create_encoder(
+    format: AudioFormat,
+    cfg: &OutputConfig,
+    default: &str,
+) -> Result<Box<dyn Encoder>> {
+    let (plugin, settings) = resolve_encoder(cfg, default)?;
+    (plugin.factory)(format, &settings)
 }
 
 // ──────────────────────────────────────────────────────────────────────────────
@@ -73,26 +299,20 @@ impl WavEncoder {
     }
 }
 
-impl Encoder for WavEncoder {
-    fn content_type(&self) -> &str {
-        "audio/wav"
-    }
-
-    fn header(&self) -> Vec<u8> {
+impl WavEncoder {
+    /// Canonical 44-byte RIFF/WAVE PCM-16 header with the given chunk sizes.
+    fn riff_header(&self, riff_size: u32, data_size: u32) -> Vec<u8> {
         let channels = self.format.channels as u16;
         let sample_rate = self.format.sample_rate;
         let byte_rate: u32 = sample_rate * u32::from(channels) * 2;
         let block_align: u16 = channels * 2;
         const BITS_PER_SAMPLE: u16 = 16;
-        // Use 0xFFFF_FFFF for both RIFF and data sizes — standard trick for
-        // streaming WAV where the total length is not known up front.
-        const STREAMING: u32 = 0xFFFF_FFFF;
 
         let mut h = Vec::with_capacity(44);
 
         // RIFF chunk descriptor (12 bytes)
         h.extend_from_slice(b"RIFF");
-        h.extend_from_slice(&STREAMING.to_le_bytes()); // riff_size
+        h.extend_from_slice(&riff_size.to_le_bytes());
         h.extend_from_slice(b"WAVE");
 
         // "fmt " sub-chunk (24 bytes)
@@ -107,11 +327,24 @@ impl Encoder for WavEncoder {
 
         // "data" sub-chunk header (8 bytes)
         h.extend_from_slice(b"data");
-        h.extend_from_slice(&STREAMING.to_le_bytes()); // data_size
+        h.extend_from_slice(&data_size.to_le_bytes());
 
         // Total: 12 + 24 + 8 = 44 bytes
         h
     }
+}
+
+impl Encoder for WavEncoder {
+    fn content_type(&self) -> &str {
+        "audio/wav"
+    }
+
+    fn header(&self) -> Vec<u8> {
+        // Use 0xFFFF_FFFF for both RIFF and data sizes — standard trick for
+        // streaming WAV where the total length is not known up front.
+        const STREAMING: u32 = 0xFFFF_FFFF;
+        self.riff_header(STREAMING, STREAMING)
+    }
 
     fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
         let mut out = Vec::with_capacity(samples.len() * 2);
@@ -121,6 +354,505 @@ impl Encoder for WavEncoder {
         }
         out
     }
+
+    /// WAV's classic RIFF format uses 32-bit size fields, a hard format limit
+    /// (~4 GiB). If the body exceeds it the fields are clamped (with a
+    /// warning) rather than wrapped, which keeps the header internally
+    /// consistent instead of corrupt; recordings beyond that would need
+    /// RF64/BWF, which is out of scope here.
+    fn final_header(&self, body_bytes: u64) -> Option<Vec<u8>> {
+        let data_size = if body_bytes > u64::from(u32::MAX) {
+            tracing::warn!(
+                "wave encoder: data size {body_bytes} bytes exceeds WAV's 32-bit \
+                 limit; clamping header field to u32::MAX (file content is unaffected)"
+            );
+            u32::MAX
+        } else {
+            body_bytes as u32
+        };
+        let riff_size = (36 + body_bytes).min(u64::from(u32::MAX)) as u32;
+        Some(self.riff_header(riff_size, data_size))
+    }
+}
+
+// ──────────────────────────────────────────────────────────────────────────────
+// FlacEncoder
+// ──────────────────────────────────────────────────────────────────────────────
+
+/// Frames per FLAC block (the reference encoder's default).
+const FLAC_BLOCK_FRAMES: usize = 4096;
+
+/// Bits per sample of the encoded stream.
+const FLAC_BITS_PER_SAMPLE: u32 = 16;
+
+/// Highest order of FLAC's fixed polynomial predictors.
+const FLAC_MAX_FIXED_ORDER: usize = 4;
+
+/// Highest residual partition order tried (2^8 partitions per subframe).
+const FLAC_MAX_PARTITION_ORDER: u32 = 8;
+
+/// Largest parameter of the 4-bit Rice coding; 15 is the escape code.
+const FLAC_MAX_RICE_PARAM: u32 = 14;
+
+/// Native FLAC stream of 16-bit samples.
+///
+/// Each channel of a block is coded with whichever of FLAC's fixed polynomial
+/// predictors (orders 0–4) leaves the cheapest Rice-coded residual, falling
+/// back to a constant or verbatim subframe when that is smaller. Stereo blocks
+/// also try left/side, side/right and mid/side decorrelation. This is the
+/// reference encoder's `-1`-style fixed prediction without LPC: well short of
+/// its best ratio, but a real reduction over PCM at a trivial CPU cost. Samples
+/// are buffered into fixed 4096-frame blocks; [`Encoder::finish`] emits any
+/// remainder as a shorter final block.
+pub struct FlacEncoder {
+    format: AudioFormat,
+    /// Interleaved samples not yet emitted as a full block.
+    pending: Vec<i16>,
+    frame_number: u32,
+}
+
+impl FlacEncoder {
+    pub fn new(format: AudioFormat) -> Self {
+        Self {
+            format,
+            pending: Vec::new(),
+            frame_number: 0,
+        }
+    }
+
+    /// Append one FLAC frame holding `block` (whole interleaved frames) to `out`.
+    fn write_frame(&mut self, block: &[i16], out: &mut Vec<u8>) {
+        let channels = usize::from(self.format.channels);
+        let frames = block.len() / channels;
+        let planar: Vec<Vec<i32>> = (0..channels)
+            .map(|ch| {
+                block
+                    .chunks_exact(channels)
+                    .map(|frame| i32::from(frame[ch]))
+                    .collect()
+            })
+            .collect();
+        let (assignment, subframes) = plan_channels(&planar);
+
+        let start = out.len();
+        // Sync code, reserved bit, fixed-blocksize strategy.
+        out.extend_from_slice(&[0xFF, 0xF8]);
+        // Block size: 16-bit (n - 1) after the frame number; sample rate: from
+        // STREAMINFO.
+        out.push(0x70);
+        // Channel assignment, 16 bits per sample, reserved bit.
+        out.push((assignment << 4) | (0b100 << 1));
+        push_utf8_number(out, self.frame_number);
+        out.extend_from_slice(&((frames - 1) as u16).to_be_bytes());
+        let header_crc = crc8(&out[start..]);
+        out.push(header_crc);
+
+        let mut bits = BitWriter::default();
+        for subframe in &subframes {
+            subframe.write(&mut bits);
+        }
+        out.extend(bits.into_bytes());
+
+        let frame_crc = crc16(&out[start..]);
+        out.extend_from_slice(&frame_crc.to_be_bytes());
+        // Fixed-blocksize frame numbers are 31 bits wide.
+        self.frame_number = (self.frame_number + 1) & 0x7FFF_FFFF;
+    }
+}
+
+impl Encoder for FlacEncoder {
+    fn content_type(&self) -> &str {
+        "audio/flac"
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let block = FLAC_BLOCK_FRAMES as u16;
+        let mut h = Vec::with_capacity(42);
+        h.extend_from_slice(b"fLaC");
+        // Metadata block header: last-block flag, type 0 (STREAMINFO), 34 bytes.
+        h.extend_from_slice(&[0x80, 0, 0, 34]);
+        h.extend_from_slice(&block.to_be_bytes()); // min block size
+        h.extend_from_slice(&block.to_be_bytes()); // max block size
+        h.extend_from_slice(&[0; 3]); // min frame size (unknown)
+        h.extend_from_slice(&[0; 3]); // max frame size (unknown)
+        // Sample rate (20 bits), channels - 1 (3), bits per sample - 1 (5),
+        // total samples (36; 0 = unknown, as for a live stream).
+        let packed = (u64::from(self.format.sample_rate) << 44)
+            | (u64::from(self.format.channels - 1) << 41)
+            | (15u64 << 36);
+        h.extend_from_slice(&packed.to_be_bytes());
+        h.extend_from_slice(&[0; 16]); // MD5 of the audio (unset)
+        h
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        self.pending.extend(samples.iter().map(|&s| f32_to_i16(s)));
+        let block_len = FLAC_BLOCK_FRAMES * usize::from(self.format.channels);
+        let mut out = Vec::new();
+        while self.pending.len() >= block_len {
+            let block: Vec<i16> = self.pending.drain(..block_len).collect();
+            self.write_frame(&block, &mut out);
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.write_frame(&block, &mut out);
+        }
+        out
+    }
+}
+
+/// Append `n` (< 2^31) in FLAC's UTF-8-style variable-length coding.
+fn push_utf8_number(out: &mut Vec<u8>, n: u32) {
+    let len: u32 = match n {
+        0..=0x7F => {
+            out.push(n as u8);
+            return;
+        }
+        0x80..=0x7FF => 2,
+        0x800..=0xFFFF => 3,
+        0x1_0000..=0x1F_FFFF => 4,
+        0x20_0000..=0x3FF_FFFF => 5,
+        _ => 6,
+    };
+    let lead = !(0xFFu8 >> len);
+    out.push(lead | (n >> (6 * (len - 1))) as u8);
+    for i in (0..len - 1).rev() {
+        out.push(0x80 | ((n >> (6 * i)) & 0x3F) as u8);
+    }
+}
+
+/// CRC-8 (polynomial 0x07, init 0) over a FLAC frame header.
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &b| {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// CRC-16 (polynomial 0x8005, init 0) over a whole FLAC frame.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &b| {
+        crc ^= u16::from(b) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Code the channels of one block, returning the frame's channel assignment
+/// and the subframe of each coded channel. Stereo also tries the three
+/// decorrelated pairings and keeps the cheapest.
+fn plan_channels(planar: &[Vec<i32>]) -> (u8, Vec<Subframe>) {
+    let independent = || {
+        planar
+            .iter()
+            .map(|samples| Subframe::plan(samples, FLAC_BITS_PER_SAMPLE))
+            .collect::<Vec<_>>()
+    };
+    let [left, right] = planar else {
+        return ((planar.len() - 1) as u8, independent());
+    };
+
+    let side: Vec<i32> = left.iter().zip(right).map(|(l, r)| l - r).collect();
+    let mid: Vec<i32> = left.iter().zip(right).map(|(l, r)| (l + r) >> 1).collect();
+    let left = Subframe::plan(left, FLAC_BITS_PER_SAMPLE);
+    let right = Subframe::plan(right, FLAC_BITS_PER_SAMPLE);
+    // The side channel needs one extra bit.
+    let side = Subframe::plan(&side, FLAC_BITS_PER_SAMPLE + 1);
+    let mid = Subframe::plan(&mid, FLAC_BITS_PER_SAMPLE);
+
+    let costs = [
+        left.bits() + right.bits(), // 0b0001: left, right
+        left.bits() + side.bits(),  // 0b1000: left, side
+        side.bits() + right.bits(), // 0b1001: side, right
+        mid.bits() + side.bits(),   // 0b1010: mid, side
+    ];
+    let best = (0..costs.len()).min_by_key(|&i| costs[i]).unwrap_or(0);
+    match best {
+        1 => (0b1000, vec![left, side]),
+        2 => (0b1001, vec![side, right]),
+        3 => (0b1010, vec![mid, side]),
+        _ => (0b0001, vec![left, right]),
+    }
+}
+
+/// One coded channel of a FLAC frame.
+enum Subframe {
+    /// Every sample has the same value.
+    Constant { value: i32, bps: u32 },
+    /// Samples stored as they are.
+    Verbatim { samples: Vec<i32>, bps: u32 },
+    /// Fixed polynomial prediction with a Rice-coded residual.
+    Fixed {
+        warmup: Vec<i32>,
+        residual: Vec<i32>,
+        rice: RiceCoding,
+        bps: u32,
+    },
+}
+
+impl Subframe {
+    /// Pick the cheapest coding of `samples`, which are `bps` bits wide.
+    fn plan(samples: &[i32], bps: u32) -> Self {
+        if samples.iter().all(|&s| s == samples[0]) {
+            return Self::Constant {
+                value: samples[0],
+                bps,
+            };
+        }
+        let verbatim_bits = 8 + samples.len() as u64 * u64::from(bps);
+        let best = (0..=FLAC_MAX_FIXED_ORDER.min(samples.len() - 1))
+            .map(|order| {
+                let residual = fixed_residual(samples, order);
+                let rice = RiceCoding::plan(&residual, samples.len(), order);
+                (order, residual, rice)
+            })
+            .min_by_key(|(order, _, rice)| *order as u64 * u64::from(bps) + rice.bits);
+        match best {
+            Some((order, residual, rice))
+                if 8 + order as u64 * u64::from(bps) + rice.bits < verbatim_bits =>
+            {
+                Self::Fixed {
+                    warmup: samples[..order].to_vec(),
+                    residual,
+                    rice,
+                    bps,
+                }
+            }
+            _ => Self::Verbatim {
+                samples: samples.to_vec(),
+                bps,
+            },
+        }
+    }
+
+    /// Coded size in bits, subframe header included.
+    fn bits(&self) -> u64 {
+        match self {
+            Self::Constant { bps, .. } => 8 + u64::from(*bps),
+            Self::Verbatim { samples, bps } => 8 + samples.len() as u64 * u64::from(*bps),
+            Self::Fixed {
+                warmup, rice, bps, ..
+            } => 8 + warmup.len() as u64 * u64::from(*bps) + rice.bits,
+        }
+    }
+
+    fn write(&self, bits: &mut BitWriter) {
+        // Subframe headers: zero pad bit, 6-bit type, no wasted bits.
+        match self {
+            Self::Constant { value, bps } => {
+                bits.put(8, 0b0000_0000);
+                bits.put_signed(*bps, *value);
+            }
+            Self::Verbatim { samples, bps } => {
+                bits.put(8, 0b0000_0010);
+                for &s in samples {
+                    bits.put_signed(*bps, s);
+                }
+            }
+            Self::Fixed {
+                warmup,
+                residual,
+                rice,
+                bps,
+            } => {
+                bits.put(8, (0b00_1000 | warmup.len() as u64) << 1);
+                for &s in warmup {
+                    bits.put_signed(*bps, s);
+                }
+                rice.write(residual, bits);
+            }
+        }
+    }
+}
+
+/// Residual of FLAC's fixed polynomial predictor of `order` (0–4) over
+/// `samples`, one value per sample after the `order` warm-up samples.
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    const COEFFICIENTS: [&[i64]; FLAC_MAX_FIXED_ORDER + 1] =
+        [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+    let coefficients = COEFFICIENTS[order];
+    (order..samples.len())
+        .map(|i| {
+            let prediction: i64 = coefficients
+                .iter()
+                .enumerate()
+                .map(|(j, &c)| c * i64::from(samples[i - 1 - j]))
+                .sum();
+            // At most 16 × 2^17 for 17-bit side samples, well within i32.
+            (i64::from(samples[i]) - prediction) as i32
+        })
+        .collect()
+}
+
+/// Map a signed residual onto the unsigned values Rice codes.
+fn fold(residual: i32) -> u64 {
+    if residual >= 0 {
+        u64::from(residual.unsigned_abs()) << 1
+    } else {
+        (u64::from(residual.unsigned_abs()) << 1) - 1
+    }
+}
+
+/// Partitioned Rice coding of one subframe's residual.
+struct RiceCoding {
+    /// Warm-up samples preceding the residual.
+    predictor_order: usize,
+    /// The residual is split into `2^order` partitions.
+    order: u32,
+    /// Rice parameter of each partition.
+    params: Vec<u32>,
+    /// Coded size in bits, coding method and partition order included.
+    bits: u64,
+}
+
+impl RiceCoding {
+    /// Pick the partition order and parameters that code `residual` (of a
+    /// `block`-sample subframe predicted with `predictor_order`) in the fewest
+    /// bits.
+    fn plan(residual: &[i32], block: usize, predictor_order: usize) -> Self {
+        let folded: Vec<u64> = residual.iter().map(|&r| fold(r)).collect();
+        let mut best: Option<Self> = None;
+        let mut order = 0;
+        loop {
+            let mut params = Vec::with_capacity(1 << order);
+            // Coding method (2) and partition order (4).
+            let mut bits = 6;
+            let mut start = 0;
+            for partition in 0..1usize << order {
+                let len = partition_len(block, order, predictor_order, partition);
+                let (param, cost) = rice_param(&folded[start..start + len]);
+                params.push(param);
+                bits += 4 + cost;
+                start += len;
+            }
+            if best.as_ref().is_none_or(|b| bits < b.bits) {
+                best = Some(Self {
+                    predictor_order,
+                    order,
+                    params,
+                    bits,
+                });
+            }
+
+            // Every partition must be whole and the first must outlast the
+            // warm-up samples.
+            let next = order + 1;
+            if next > FLAC_MAX_PARTITION_ORDER
+                || block % (1 << next) != 0
+                || block >> next <= predictor_order
+            {
+                break;
+            }
+            order = next;
+        }
+        best.expect("partition order 0 is always planned")
+    }
+
+    fn write(&self, residual: &[i32], bits: &mut BitWriter) {
+        let block = residual.len() + self.predictor_order;
+        // Coding method 0: 4-bit Rice parameters.
+        bits.put(2, 0);
+        bits.put(4, u64::from(self.order));
+        let mut start = 0;
+        for (partition, &param) in self.params.iter().enumerate() {
+            let len = partition_len(block, self.order, self.predictor_order, partition);
+            bits.put(4, u64::from(param));
+            for &r in &residual[start..start + len] {
+                let value = fold(r);
+                bits.put_unary(value >> param);
+                bits.put(param, value);
+            }
+            start += len;
+        }
+    }
+}
+
+/// Samples in `partition` of a `block`-sample subframe split into
+/// `2^order` partitions; the first one omits the warm-up samples.
+fn partition_len(block: usize, order: u32, predictor_order: usize, partition: usize) -> usize {
+    let len = block >> order;
+    if partition == 0 {
+        len - predictor_order
+    } else {
+        len
+    }
+}
+
+/// The Rice parameter coding `folded` in the fewest bits, and that size.
+/// The size is estimated from the sum of the values, which slightly
+/// overstates the unary parts but ranks parameters the same way.
+fn rice_param(folded: &[u64]) -> (u32, u64) {
+    let count = folded.len() as u64;
+    let sum: u64 = folded.iter().sum();
+    (0..=FLAC_MAX_RICE_PARAM)
+        .map(|k| (k, count * u64::from(k + 1) + (sum >> k)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+/// MSB-first bit packer for FLAC subframes.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits not yet making up a whole byte (fewer than 8).
+    acc: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    /// Append the low `n` (≤ 32) bits of `value`.
+    fn put(&mut self, n: u32, value: u64) {
+        if n == 0 {
+            return;
+        }
+        self.acc = (self.acc << n) | (value & ((1 << n) - 1));
+        self.len += n;
+        while self.len >= 8 {
+            self.len -= 8;
+            self.bytes.push((self.acc >> self.len) as u8);
+        }
+        self.acc &= (1 << self.len) - 1;
+    }
+
+    /// Append `value` as an `n`-bit two's-complement number.
+    fn put_signed(&mut self, n: u32, value: i32) {
+        self.put(n, value as u64);
+    }
+
+    /// Append `zeros` zero bits and a terminating one.
+    fn put_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.put(32, 0);
+            zeros -= 32;
+        }
+        self.put(zeros as u32 + 1, 1);
+    }
+
+    /// The packed bytes, zero-padded to a byte boundary.
+    fn into_bytes(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.put(8 - self.len, 0);
+        }
+        self.bytes
+    }
 }
 
 // ──────────────────────────────────────────────────────────────────────────────
@@ -221,4 +953,212 @@ mod tests {
         let v = i16::from_le_bytes([bytes[0], bytes[1]]);
         assert_eq!(v, i16::MAX);
     }
+
+    #[test]
+    fn wav_final_header_carries_real_sizes() {
+        let enc = WavEncoder::new(stereo_44100());
+        let h = enc.final_header(1000).expect("wave rewrites its header");
+        assert_eq!(h.len(), 44);
+        assert_eq!(u32::from_le_bytes(h[4..8].try_into().unwrap()), 1036);
+        assert_eq!(u32::from_le_bytes(h[40..44].try_into().unwrap()), 1000);
+    }
+
+    // ── FlacEncoder ─────────────────────────────────────────────────────────
+
+    #[test]
+    fn crc_check_values() {
+        // Standard check values over "123456789" for CRC-8 and CRC-16/UMTS.
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(b"123456789"), 0xFEE8);
+    }
+
+    #[test]
+    fn utf8_number_coding() {
+        let code = |n| {
+            let mut v = Vec::new();
+            push_utf8_number(&mut v, n);
+            v
+        };
+        assert_eq!(code(0x41), vec![0x41]);
+        assert_eq!(code(0x80), vec![0xC2, 0x80]);
+        assert_eq!(code(0x800), vec![0xE0, 0xA0, 0x80]);
+    }
+
+    #[test]
+    fn flac_header_is_streaminfo() {
+        let enc = FlacEncoder::new(stereo_44100());
+        let h = enc.header();
+        assert_eq!(h.len(), 42);
+        assert_eq!(&h[0..4], b"fLaC");
+        assert_eq!(h[4], 0x80, "single, last metadata block of type STREAMINFO");
+        let packed = u64::from_be_bytes(h[18..26].try_into().unwrap());
+        assert_eq!(packed >> 44, 44100);
+        assert_eq!((packed >> 41) & 0x7, 1, "channels - 1");
+        assert_eq!((packed >> 36) & 0x1F, 15, "bits per sample - 1");
+    }
+
+    #[test]
+    fn flac_buffers_until_a_full_block() {
+        let mut enc = FlacEncoder::new(stereo_44100());
+        assert!(enc.encode(&[0.0_f32; 2 * 100]).is_empty());
+        let tail = enc.finish();
+        // header(6) + crc8(1) + 2 × CONSTANT subframe (1 + 2 bytes) + crc16(2)
+        assert_eq!(tail.len(), 6 + 1 + 2 * 3 + 2);
+        assert_eq!(&tail[0..2], &[0xFF, 0xF8]);
+        assert!(enc.finish().is_empty(), "finish must drain the buffer");
+    }
+
+    #[test]
+    fn flac_frame_crcs_are_consistent() {
+        let mut enc = FlacEncoder::new(stereo_44100());
+        let frame = enc.encode(&vec![0.25_f32; 2 * FLAC_BLOCK_FRAMES]);
+        assert!(!frame.is_empty());
+        assert_eq!(crc8(&frame[..6]), frame[6]);
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        assert_eq!(crc16(body), u16::from_be_bytes([crc[0], crc[1]]));
+    }
+
+    #[test]
+    fn flac_round_trips_through_symphonia() {
+        let format = stereo_44100();
+        let mut enc = FlacEncoder::new(format);
+        let input: Vec<f32> = (0..2 * 5000)
+            .map(|i| ((i as f32) * 0.01).sin() * 0.5)
+            .collect();
+        let mut bytes = enc.header();
+        bytes.extend(enc.encode(&input));
+        bytes.extend(enc.finish());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roundtrip.flac");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut dec = crate::decoder::SymphoniaDecoder::open(&path).expect("valid FLAC stream");
+        assert_eq!(dec.format().sample_rate, 44100);
+        assert_eq!(dec.format().channels, 2);
+        let mut decoded = Vec::new();
+        let mut buf = vec![0.0_f32; 4096];
+        loop {
+            let n = dec.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decoded.len(), input.len());
+        for (a, b) in input.iter().zip(&decoded) {
+            assert!((a - b).abs() < 1e-3, "sample mismatch: {a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn flac_compresses_below_pcm() {
+        let mut enc = FlacEncoder::new(stereo_44100());
+        let input: Vec<f32> = (0..2 * 4 * FLAC_BLOCK_FRAMES)
+            .map(|i| ((i / 2) as f32 * 0.03).sin() * 0.5 + ((i / 2) as f32 * 0.11).sin() * 0.1)
+            .collect();
+        let mut bytes = enc.encode(&input);
+        bytes.extend(enc.finish());
+        let pcm_bytes = input.len() * 2;
+        assert!(
+            bytes.len() < pcm_bytes / 2,
+            "{} bytes of FLAC for {pcm_bytes} bytes of PCM",
+            bytes.len()
+        );
+    }
+
+    #[test]
+    fn flac_fixed_residual_of_a_ramp_vanishes() {
+        let ramp: Vec<i32> = (0..32).map(|i| i * 100 - 1600).collect();
+        assert!(fixed_residual(&ramp, 2).iter().all(|&r| r == 0));
+        assert_eq!(fixed_residual(&ramp, 1), vec![100; 31]);
+    }
+
+    #[test]
+    fn bit_writer_packs_msb_first() {
+        let mut bits = BitWriter::default();
+        bits.put(3, 0b101);
+        bits.put_unary(2);
+        bits.put_signed(4, -1);
+        assert_eq!(bits.into_bytes(), vec![0b1010_0111, 0b1100_0000]);
+    }
+
+    // ── Registry ────────────────────────────────────────────────────────────
+
+    fn output_cfg(pairs: &[(&str, toml::Value)]) -> OutputConfig {
+        let mut cfg = OutputConfig::cpal_default();
+        for (k, v) in pairs {
+            cfg.settings.insert((*k).to_owned(), v.clone());
+        }
+        cfg
+    }
+
+    #[test]
+    fn encoder_lookup_resolves_aliases() {
+        assert_eq!(encoder_plugin("WAV").map(|p| p.name), Some("wave"));
+        assert_eq!(encoder_plugin("null").map(|p| p.name), Some("pcm"));
+        assert_eq!(encoder_plugin("vorbis").is_some(), cfg!(feature = "vorbis"));
+        assert_eq!(encoder_plugin("mp3").is_some(), cfg!(feature = "lame"));
+    }
+
+    #[test]
+    fn create_encoder_uses_default_and_named_encoder() {
+        let fmt = stereo_44100();
+        let enc = create_encoder(fmt, &output_cfg(&[]), "wave").unwrap();
+        assert_eq!(enc.content_type(), "audio/wav");
+        let cfg = output_cfg(&[("encoder", toml::Value::String("flac".into()))]);
+        let enc = create_encoder(fmt, &cfg, "wave").unwrap();
+        assert_eq!(enc.content_type(), "audio/flac");
+    }
+
+    #[test]
+    fn create_encoder_rejects_unknown_encoder() {
+        let cfg = output_cfg(&[("encoder", toml::Value::String("bogus".into()))]);
+        assert!(create_encoder(stereo_44100(), &cfg, "wave").is_err());
+    }
+
+    #[test]
+    fn unsupported_settings_are_rejected() {
+        let cfg = output_cfg(&[
+            ("encoder", toml::Value::String("flac".into())),
+            ("quality", toml::Value::Float(5.0)),
+        ]);
+        assert!(validate_encoder(&cfg, "wave").is_err());
+        assert!(create_encoder(stereo_44100(), &cfg, "wave").is_err());
+        let cfg = output_cfg(&[("bitrate", toml::Value::Integer(192))]);
+        assert!(validate_encoder(&cfg, "wave").is_err());
+        assert!(validate_encoder(&output_cfg(&[]), "wave").is_ok());
+    }
+
+    #[cfg(feature = "vorbis")]
+    #[test]
+    fn vorbis_quality_is_range_checked() {
+        let cfg = |q: f64| {
+            output_cfg(&[
+                ("encoder", toml::Value::String("vorbis".into())),
+                ("quality", toml::Value::Float(q)),
+            ])
+        };
+        assert!(validate_encoder(&cfg(5.0), "wave").is_ok());
+        assert!(validate_encoder(&cfg(11.0), "wave").is_err());
+    }
+
+    #[test]
+    fn quality_and_bitrate_are_mutually_exclusive() {
+        let cfg = output_cfg(&[("quality", toml::Value::Float(5.0))]);
+        assert_eq!(
+            EncoderSettings::from_config(&cfg).unwrap().quality,
+            Some(5.0)
+        );
+        let cfg = output_cfg(&[("bitrate", toml::Value::Integer(192))]);
+        assert_eq!(
+            EncoderSettings::from_config(&cfg).unwrap().bitrate,
+            Some(192)
+        );
+        let cfg = output_cfg(&[
+            ("quality", toml::Value::Float(5.0)),
+            ("bitrate", toml::Value::Integer(192)),
+        ]);
+        assert!(EncoderSettings::from_config(&cfg).is_err());
+    }
 }
@@ -1,13 +1,15 @@
 use crate::audio_output::AudioOutput;
-use crate::decoder::SymphoniaDecoder;
+use crate::decoder::{SongDecoder, open_decoder};
 use crate::dop::DopEncoder;
 use crate::dop_output::DopOutput;
+use crate::filter::{AudioFilter, NormalizeFilter};
 use crate::output::CpalOutput;
+use crate::resampler::FixedRate;
 use parking_lot::Mutex;
 use rmpd_core::config::{DopMode, OutputConfig, ReplayGainMode, ResamplerQuality};
 use rmpd_core::error::Result;
 use rmpd_core::event::{Event, EventBus};
-use rmpd_core::song::Song;
+use rmpd_core::song::{AudioFormat, Song};
 use rmpd_core::state::PlayerState;
 use std::path::Path;
 use std::sync::Arc;
@@ -103,6 +105,12 @@ pub struct PlaybackEngine {
     mixramp_db: f32,
     /// Extra delay applied after the MixRamp overlap window (seconds).
     mixramp_delay: f32,
+    /// Match the incoming track's loudness to the outgoing one's during a
+    /// crossfade (see [`crate::crossfade::loudness_match_db`]).
+    crossfade_loudness_match: bool,
+    /// Play continuous pairs of tracks gaplessly instead of crossfading
+    /// them (see [`crate::crossfade::is_continuous`]).
+    crossfade_skip_continuous: bool,
     /// Pre-fetched next song for gapless / crossfade transitions.
     ///
     /// The protocol layer sets this while the current song is playing; the
@@ -113,6 +121,18 @@ pub struct PlaybackEngine {
     /// Output buffer time in milliseconds (0 uses a safe default).
     /// Sizes the PCM output's internal ring buffer / sync-channel depth.
     buffer_time_ms: u32,
+    /// Device mixer that replaces software volume when an output is
+    /// configured with `mixer_type = "hardware"`.
+    hardware_mixer: Option<Arc<dyn crate::filter::Mixer + Sync>>,
+    /// Watches `hardware_mixer` for changes made by other applications.
+    mixer_monitor: Option<crate::hardware_mixer::MixerMonitor>,
+    /// Fixed rate every song is resampled to before reaching the outputs
+    /// (`[audio].output_sample_rate`); `None` opens outputs at each song's
+    /// own rate.
+    output_sample_rate: Option<u32>,
+    /// Bumped every time the playback thread is stopped; the thread tags its
+    /// `SongFinished`/`AdvancedToNext` events with the value it started under.
+    generation: u64,
 }
 
 impl PlaybackEngine {
@@ -141,11 +161,42 @@ impl PlaybackEngine {
             crossfade: 0,
             mixramp_db: 0.0,
             mixramp_delay: 0.0,
+            crossfade_loudness_match: false,
+            crossfade_skip_continuous: false,
             next_song: Arc::new(Mutex::new(None)),
             buffer_time_ms: 500, // matches AudioConfig::default_buffer_time()
+            hardware_mixer: None,
+            mixer_monitor: None,
+            output_sample_rate: None,
+            generation: 0,
         }
     }
 
+    /// A second engine, for another partition, with this one's audio settings
+    /// (ReplayGain, normalization, resampling, DoP, buffer time, output rate)
+    /// but no outputs of its own until some are moved to it. Crossfade and
+    /// volume are per partition and start at their defaults.
+    pub fn new_sibling(
+        &self,
+        event_bus: EventBus,
+        status: Arc<RwLock<rmpd_core::state::PlayerStatus>>,
+        atomic_state: Arc<AtomicU8>,
+    ) -> Self {
+        let mut engine = Self::new(event_bus, status, atomic_state);
+        engine.outputs = Vec::new();
+        engine.replay_gain_mode = self.replay_gain_mode;
+        engine.replay_gain_preamp = self.replay_gain_preamp;
+        engine.replay_gain_missing_preamp = self.replay_gain_missing_preamp;
+        engine.volume_normalization = self.volume_normalization;
+        engine.resampler_quality = self.resampler_quality;
+        engine.dop_mode = self.dop_mode;
+        engine.crossfade_loudness_match = self.crossfade_loudness_match;
+        engine.crossfade_skip_continuous = self.crossfade_skip_continuous;
+        engine.buffer_time_ms = self.buffer_time_ms;
+        engine.output_sample_rate = self.output_sample_rate;
+        engine
+    }
+
     pub fn set_outputs(&mut self, outputs: Vec<OutputConfig>) {
         self.outputs = outputs;
     }
@@ -163,6 +214,31 @@ impl PlaybackEngine {
         self.replay_gain_missing_preamp = missing_preamp;
     }
 
+    /// Route volume through a hardware mixer instead of the software volume
+    /// filter. Adopts the mixer's current volume and starts monitoring it, so
+    /// changes made by other applications surface as `mixer` idle events.
+    pub async fn set_hardware_mixer(&mut self, mixer: Arc<dyn crate::filter::Mixer + Sync>) {
+        let vol = mixer.volume();
+        let previous = self.volume.swap(vol, Ordering::AcqRel);
+        self.status.write().await.volume = vol;
+        if previous != vol {
+            self.event_bus.emit(Event::VolumeChanged(vol));
+        }
+        // Drop any previous monitor (joins its thread) before starting anew.
+        self.mixer_monitor = None;
+        self.mixer_monitor = Some(crate::hardware_mixer::MixerMonitor::spawn(
+            mixer.clone(),
+            self.volume.clone(),
+            self.status.clone(),
+            self.event_bus.clone(),
+            crate::hardware_mixer::MIXER_POLL_INTERVAL,
+        ));
+        self.hardware_mixer = Some(mixer);
+    }
+
+    /// Run the automatic gain control (MPD's `volume_normalization`) on the
+    /// audio after ReplayGain, and cap ReplayGain at the track's peak.
+    /// Takes effect from the next song played.
     pub fn set_volume_normalization(&mut self, on: bool) {
         self.volume_normalization = on;
     }
@@ -173,6 +249,14 @@ impl PlaybackEngine {
         self.resampler_quality = quality;
     }
 
+    /// Resample every song to `rate` before it reaches the outputs. Tracks at
+    /// different rates then share one open output and can follow each other
+    /// gaplessly; `None` (the default) reopens the output whenever the rate
+    /// changes.
+    pub fn set_output_sample_rate(&mut self, rate: Option<u32>) {
+        self.output_sample_rate = rate;
+    }
+
     /// Set the DSD-over-PCM (DoP) mode for DSD sources.
     pub fn set_dop_mode(&mut self, mode: DopMode) {
         self.dop_mode = mode;
@@ -193,6 +277,20 @@ impl PlaybackEngine {
         self.mixramp_delay = delay;
     }
 
+    /// Fade the incoming track in at the outgoing track's loudness, judged
+    /// by their ReplayGain tags, easing to its own level over the crossfade.
+    /// Takes effect from the next song played.
+    pub fn set_crossfade_loudness_match(&mut self, on: bool) {
+        self.crossfade_loudness_match = on;
+    }
+
+    /// Play the next track of the same album, or one the MixRamp tags show
+    /// running on without silence, gaplessly rather than crossfading into
+    /// it. Takes effect from the next song played.
+    pub fn set_crossfade_skip_continuous(&mut self, on: bool) {
+        self.crossfade_skip_continuous = on;
+    }
+
     /// Feed the next song for a gapless or crossfade transition.
     ///
     /// Callable while playing (`&self`) — uses interior mutability.  The
@@ -238,7 +336,12 @@ impl PlaybackEngine {
         let song_path = playback_song.resolved_path.clone();
         let event_bus = self.event_bus.clone();
         let stop_flag = self.stop_flag.clone();
-        let volume = self.volume.clone();
+        // With a hardware mixer the device attenuates; keep samples at unity.
+        let volume = if self.hardware_mixer.is_some() {
+            Arc::new(AtomicU8::new(100))
+        } else {
+            self.volume.clone()
+        };
         let status_clone = self.status.clone();
         let atomic_state_clone = self.atomic_state.clone();
         let outputs = self.outputs.clone();
@@ -261,8 +364,12 @@ impl PlaybackEngine {
         let volume_normalization = self.volume_normalization;
         let mixramp_db = self.mixramp_db;
         let mixramp_delay = self.mixramp_delay;
+        let crossfade_loudness_match = self.crossfade_loudness_match;
+        let crossfade_skip_continuous = self.crossfade_skip_continuous;
         let range = playback_song.range;
         let buffer_time_ms = self.buffer_time_ms;
+        let output_sample_rate = self.output_sample_rate;
+        let generation = self.generation;
 
         let handle = thread::spawn(move || {
             if let Err(e) = Self::playback_thread(
@@ -287,8 +394,12 @@ impl PlaybackEngine {
                 volume_normalization,
                 mixramp_db,
                 mixramp_delay,
+                crossfade_loudness_match,
+                crossfade_skip_continuous,
                 range,
                 buffer_time_ms,
+                output_sample_rate,
+                generation,
             ) {
                 error!("playback error: {}", e);
             }
@@ -335,8 +446,11 @@ impl PlaybackEngine {
         debug!("stopping playback");
         self.stop_internal().await?;
         // User stop: tear down the cached output/device (song transitions use
-        // stop_internal, which keeps it for gapless reuse).
-        self.output_slot.clear();
+        // stop_internal, which keeps it for gapless reuse). If the last song
+        // played to its end this drains the output first, which blocks for up
+        // to the buffer time — keep it off the async runtime.
+        let output_slot = self.output_slot.clone();
+        let _ = tokio::task::spawn_blocking(move || output_slot.clear()).await;
         // Emit event to notify clients (external stop)
         self.event_bus.emit(Event::SongChanged(None));
         crate::httpd_output::set_now_playing(None);
@@ -349,6 +463,8 @@ impl PlaybackEngine {
 
         // Set stop flag
         self.stop_flag.store(true, Ordering::Release);
+        // Completion events still in flight from this thread are now stale.
+        self.generation += 1;
 
         // Clear command channel
         self.command_tx = None;
@@ -376,6 +492,12 @@ impl PlaybackEngine {
         Ok(())
     }
 
+    /// Current playback generation; compare with the value carried by
+    /// `SongFinished`/`AdvancedToNext` to drop events from a stopped thread.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub async fn get_state(&self) -> PlayerState {
         let status = self.status.read().await;
         status.state
@@ -390,9 +512,22 @@ impl PlaybackEngine {
         self.current_song.lock().clone()
     }
 
+    /// Set the volume (hardware mixer, or the software volume filter), record
+    /// it in `PlayerStatus`, and wake `idle mixer` clients if it changed. The
+    /// status is written before the event, so a woken client never reads a
+    /// stale volume.
     pub async fn set_volume(&mut self, vol: u8) -> Result<()> {
-        self.volume.store(vol, Ordering::Release);
-        self.event_bus.emit(Event::VolumeChanged(vol));
+        // Write the hardware first: the mixer monitor compares it against
+        // `self.volume`, and must never see the new value there while the
+        // hardware still holds the old one (it would report a spurious revert).
+        if let Some(mixer) = &self.hardware_mixer {
+            mixer.set_volume(vol);
+        }
+        let previous = self.volume.swap(vol, Ordering::AcqRel);
+        self.status.write().await.volume = vol;
+        if previous != vol {
+            self.event_bus.emit(Event::VolumeChanged(vol));
+        }
         Ok(())
     }
 
@@ -423,13 +558,17 @@ impl PlaybackEngine {
         volume_normalization: bool,
         mixramp_db: f32,
         mixramp_delay: f32,
+        crossfade_loudness_match: bool,
+        crossfade_skip_continuous: bool,
         range: Option<(f64, f64)>,
         buffer_time_ms: u32,
+        output_sample_rate: Option<u32>,
+        generation: u64,
     ) -> Result<()> {
         // Shadow as mutable so per-song gain can be updated on in-thread advance.
         let mut gain_scale = gain_scale;
         // Open decoder (pass-through mode by default)
-        let mut decoder = SymphoniaDecoder::open(path)?;
+        let mut decoder = open_decoder(path)?;
 
         // Overrides the cpal stream rate for DSD-to-PCM: drives the device at
         // its native rate and lets rmpd's own StreamResampler bridge the gap,
@@ -470,6 +609,7 @@ impl PlaybackEngine {
                             event_bus,
                             stop_flag,
                             command_rx,
+                            generation,
                         );
                     }
                     Err(e) => {
@@ -511,7 +651,21 @@ impl PlaybackEngine {
         }
 
         // Standard PCM playback (works for all formats including DSD with PCM conversion)
-        let format = decoder.format();
+        let mut format = decoder.format();
+
+        // A fixed output rate converts every song here, ahead of the outputs,
+        // so the output (keyed on the fixed rate) survives rate changes.
+        let mut fixed_rate = output_sample_rate.map(|rate| FixedRate::new(rate, resampler_quality));
+        if let Some(fixed) = fixed_rate.as_mut() {
+            fixed.set_source(format.sample_rate, format.channels as usize);
+            dsd_target_rate = None;
+        }
+        let output_format = AudioFormat {
+            sample_rate: fixed_rate
+                .as_ref()
+                .map_or(format.sample_rate, FixedRate::target),
+            ..format
+        };
 
         debug!(
             "decoder opened: {}Hz, {} channels",
@@ -534,24 +688,24 @@ impl PlaybackEngine {
             .map(|c| format!("{}|{}", c.output_type, c.name))
             .collect();
         let key = crate::output_slot::OutputKey {
-            sample_rate: format.sample_rate,
-            channels: format.channels,
-            bits_per_sample: format.bits_per_sample,
+            sample_rate: output_format.sample_rate,
+            channels: output_format.channels,
+            bits_per_sample: output_format.bits_per_sample,
             signature,
         };
         // Reuse the existing output (and its open device) across consecutive
         // same-key tracks for gapless transitions; rebuild on format/output
         // change. The closure (which opens devices) runs only on a cache miss.
+        let params = crate::output_registry::OutputParams {
+            format: output_format,
+            quality: resampler_quality,
+            buffer_time_ms,
+            dsd_target_rate,
+        };
         let multi = output_slot.acquire(key, || {
             let mut boxes: Vec<Box<dyn AudioOutput>> = Vec::with_capacity(effective_outputs.len());
             for (i, cfg) in effective_outputs.iter().enumerate() {
-                match Self::create_output(
-                    format,
-                    cfg,
-                    resampler_quality,
-                    buffer_time_ms,
-                    dsd_target_rate,
-                ) {
+                match crate::output_registry::create_output(&params, cfg) {
                     Ok(b) => boxes.push(b),
                     Err(e) => {
                         if i == 0 {
@@ -573,8 +727,11 @@ impl PlaybackEngine {
 
         // ── Playback state ────────────────────────────────────────────────────
         let mut buffer = vec![0.0f32; BUFFER_SIZE];
+        // Lives as long as the thread, so the gain carries over in-thread
+        // advances instead of restarting at 1:1 on every song.
+        let mut normalizer = volume_normalization.then(NormalizeFilter::new);
         let mut total_samples_played: u64 = 0;
-        let samples_per_second = format.sample_rate as u64 * format.channels as u64;
+        let mut samples_per_second = format.sample_rate as u64 * format.channels as u64;
         // Track whether we have sent pause/resume to the workers to avoid
         // spamming the same message every 100 ms.
         let mut multi_paused = false;
@@ -660,11 +817,16 @@ impl PlaybackEngine {
                     let cf_start = ((duration - crossfade_secs as f64) * samples_per_second as f64)
                         .max(0.0) as u64;
 
-                    if total_samples_played >= cf_start {
+                    // A continuous pair keeps the next song in its slot for
+                    // the gapless path at end of stream.
+                    if total_samples_played >= cf_start
+                        && !(crossfade_skip_continuous
+                            && Self::next_is_continuous(&current_song, &next_song))
+                    {
                         // Claim the pre-fetched next song (destructive take —
                         // only the first crossing of cf_start ever finds a value).
                         let cf_next = next_song.lock().take().and_then(|ps| {
-                            SymphoniaDecoder::open(ps.resolved_path.as_std_path())
+                            open_decoder(ps.resolved_path.as_std_path())
                                 .ok()
                                 .filter(|dec| {
                                     !dec.is_dsd()
@@ -687,10 +849,16 @@ impl PlaybackEngine {
                             // MixRamp: derive overlap window from tags; fall
                             // back to time-based crossfade if either tag is
                             // absent or the threshold is not crossed.
-                            let cur_end_tag: Option<String> = current_song
+                            let (cur_end_tag, cur_track_gain) = current_song
                                 .lock()
                                 .as_ref()
-                                .and_then(|s| s.tag("mixramp_end").map(str::to_owned));
+                                .map(|s| {
+                                    (
+                                        s.tag("mixramp_end").map(str::to_owned),
+                                        s.replay_gain_track_gain,
+                                    )
+                                })
+                                .unwrap_or_default();
                             let next_start_tag: Option<String> =
                                 ps.song.tag("mixramp_start").map(str::to_owned);
                             let cur_rg_db = 20.0_f32 * gain_scale.max(1e-9_f32).log10();
@@ -705,6 +873,19 @@ impl PlaybackEngine {
                             )
                             .filter(|&s| s > 0.0)
                             .unwrap_or(crossfade_secs as f32);
+                            // Even out the tracks' loudness after gain, so a
+                            // loud track does not burst in under a quiet one.
+                            let match_db = crossfade_loudness_match
+                                .then(|| {
+                                    crate::crossfade::loudness_match_db(
+                                        cur_track_gain,
+                                        cur_rg_db,
+                                        ps.song.replay_gain_track_gain,
+                                        next_rg_db,
+                                    )
+                                })
+                                .flatten()
+                                .unwrap_or(0.0);
                             let window = crate::crossfade::window_samples_secs(
                                 format.sample_rate,
                                 format.channels,
@@ -782,7 +963,14 @@ impl PlaybackEngine {
                                     for s in cf_cur[..n_cur].iter_mut() {
                                         *s *= gain_scale;
                                     }
-                                    if multi.write(Arc::from(&cf_cur[..n_cur])).is_err() {
+                                    if Self::write_block(
+                                        &multi,
+                                        fixed_rate.as_mut(),
+                                        normalizer.as_mut(),
+                                        &mut cf_cur[..n_cur],
+                                    )
+                                    .is_err()
+                                    {
                                         warn!("output disconnected (crossfade/next-eof)");
                                         break 'song;
                                     }
@@ -796,6 +984,8 @@ impl PlaybackEngine {
                                 let progress =
                                     (overlap_done as f32 / window as f32).clamp(0.0, 1.0);
                                 let (g_out, g_in) = crate::crossfade::equal_power_gains(progress);
+                                let g_in = g_in
+                                    * crate::crossfade::loudness_match_gain(match_db, progress);
 
                                 // In-place: cf_cur = cur*gain*g_out + nxt*gain*g_in
                                 for s in cf_cur[..n_mix].iter_mut() {
@@ -808,7 +998,14 @@ impl PlaybackEngine {
                                     next_gain_scale * g_in,
                                 );
 
-                                if multi.write(Arc::from(&cf_cur[..n_mix])).is_err() {
+                                if Self::write_block(
+                                    &multi,
+                                    fixed_rate.as_mut(),
+                                    normalizer.as_mut(),
+                                    &mut cf_cur[..n_mix],
+                                )
+                                .is_err()
+                                {
                                     warn!("output disconnected during crossfade");
                                     break 'song;
                                 }
@@ -833,7 +1030,10 @@ impl PlaybackEngine {
                                 decoder = next_dec;
                                 total_samples_played = next_pos;
                                 *current_song.lock() = Some((*ps.song).clone());
-                                event_bus.emit(Event::AdvancedToNext);
+                                crate::httpd_output::set_now_playing(Some(
+                                    crate::httpd_output::now_playing_label(&ps.song),
+                                ));
+                                event_bus.emit(Event::AdvancedToNext(generation));
                                 // Update gain for the now-active next song.
                                 gain_scale = next_gain_scale;
                                 // Break inner loop; 'song iterates with new decoder.
@@ -863,13 +1063,15 @@ impl PlaybackEngine {
                     // and we always take the SongFinished branch — byte-identical
                     // to the pre-look-ahead engine.  Only when the protocol has
                     // pre-fed a format-compatible next song does the gapless path
-                    // activate.
+                    // activate. With a fixed output rate the sample rate may
+                    // change too: the next song is resampled like this one.
                     let gapless_next = next_song.lock().take().and_then(|ps| {
-                        SymphoniaDecoder::open(ps.resolved_path.as_std_path())
+                        open_decoder(ps.resolved_path.as_std_path())
                             .ok()
                             .filter(|dec| {
                                 !dec.is_dsd()
-                                    && dec.format().sample_rate == format.sample_rate
+                                    && (fixed_rate.is_some()
+                                        || dec.format().sample_rate == format.sample_rate)
                                     && dec.format().channels == format.channels
                             })
                             .map(|dec| (dec, ps))
@@ -881,8 +1083,19 @@ impl PlaybackEngine {
                             // open, audio is continuous with no gap.
                             decoder = next_dec;
                             total_samples_played = 0;
+                            format = decoder.format();
+                            samples_per_second = format.sample_rate as u64 * format.channels as u64;
+                            if let Some(fixed) = fixed_rate.as_mut() {
+                                fixed.set_source(format.sample_rate, format.channels as usize);
+                            }
                             *current_song.lock() = Some((*ps.song).clone());
-                            event_bus.emit(Event::AdvancedToNext);
+                            // Publish the new title right away so streaming
+                            // listeners see the change at the track boundary
+                            // rather than on the next ~1 s position tick.
+                            crate::httpd_output::set_now_playing(Some(
+                                crate::httpd_output::now_playing_label(&ps.song),
+                            ));
+                            event_bus.emit(Event::AdvancedToNext(generation));
                             // Recompute gain for the new song (it has its own tags).
                             gain_scale = Self::compute_gain_scale(
                                 &ps.song,
@@ -895,7 +1108,9 @@ impl PlaybackEngine {
                         }
                         None => {
                             // Default (dormant) path — identical to today.
-                            event_bus.emit(Event::SongFinished);
+                            // Whoever tears the output down next drains it.
+                            output_slot.mark_finished();
+                            event_bus.emit(Event::SongFinished(generation));
                             break 'song;
                         }
                     }
@@ -927,8 +1142,14 @@ impl PlaybackEngine {
                 }
 
                 // Fan the chunk out to all outputs.
-                let chunk: Arc<[f32]> = Arc::from(&buffer[..samples_read]);
-                if multi.write(chunk).is_err() {
+                if Self::write_block(
+                    &multi,
+                    fixed_rate.as_mut(),
+                    normalizer.as_mut(),
+                    &mut buffer[..samples_read],
+                )
+                .is_err()
+                {
                     warn!("primary output disconnected; stopping playback");
                     break 'song;
                 }
@@ -938,7 +1159,8 @@ impl PlaybackEngine {
 
                 if reached_range_end {
                     debug!("reached range end at {total_samples_played} samples");
-                    event_bus.emit(Event::SongFinished);
+                    output_slot.mark_finished();
+                    event_bus.emit(Event::SongFinished(generation));
                     break 'song;
                 }
 
@@ -976,14 +1198,18 @@ impl PlaybackEngine {
         Ok(())
     }
 
-    fn create_output(
-        format: rmpd_core::song::AudioFormat,
-        cfg: &OutputConfig,
-        quality: ResamplerQuality,
-        buffer_time_ms: u32,
-        dsd_target_rate: Option<u32>,
-    ) -> Result<Box<dyn AudioOutput>> {
-        crate::output_registry::create_output(format, quality, cfg, buffer_time_ms, dsd_target_rate)
+    /// Whether the fed next song carries straight on from the current one
+    /// (see [`crate::crossfade::is_continuous`]).
+    fn next_is_continuous(
+        current_song: &Mutex<Option<Song>>,
+        next_song: &Mutex<Option<rmpd_core::playback::PlaybackSong>>,
+    ) -> bool {
+        let next = next_song.lock();
+        let current = current_song.lock();
+        match (current.as_ref(), next.as_ref()) {
+            (Some(cur), Some(next)) => crate::crossfade::is_continuous(cur, &next.song),
+            _ => false,
+        }
     }
 
     fn compute_gain_scale(
@@ -1028,7 +1254,7 @@ impl PlaybackEngine {
     /// starting the stream here means any failure (configured device can't do the
     /// DoP rate, device busy, no DoP DAC) surfaces as an error so the caller can
     /// cleanly revert to PCM instead of aborting playback.
-    fn setup_dop(decoder: &SymphoniaDecoder) -> Result<(DopEncoder, DopOutput)> {
+    fn setup_dop(decoder: &SongDecoder) -> Result<(DopEncoder, DopOutput)> {
         let dsd_sample_rate = decoder.sample_rate();
         let channels = decoder.channels();
         let channel_layout = decoder
@@ -1065,15 +1291,39 @@ impl PlaybackEngine {
         Ok((dop_encoder, output))
     }
 
+    /// Hand one block of decoded samples to the outputs, normalizing it and
+    /// converting it to the fixed output rate first when those are enabled.
+    fn write_block(
+        multi: &crate::multi_output::MultiOutput,
+        fixed_rate: Option<&mut FixedRate>,
+        normalizer: Option<&mut NormalizeFilter>,
+        block: &mut [f32],
+    ) -> Result<()> {
+        if let Some(normalizer) = normalizer {
+            normalizer.apply(block);
+        }
+        let block: &[f32] = block;
+        let block = match fixed_rate {
+            Some(fixed) => fixed.convert(block),
+            None => std::borrow::Cow::Borrowed(block),
+        };
+        if block.is_empty() {
+            return Ok(());
+        }
+        multi.write(Arc::from(&*block))
+    }
+
     /// DSD playback loop over an already-started DoP output.
+    #[allow(clippy::too_many_arguments)]
     fn run_dsd_dop(
-        mut decoder: SymphoniaDecoder,
+        mut decoder: SongDecoder,
         mut dop_encoder: DopEncoder,
         mut output: DopOutput,
         atomic_state: Arc<AtomicU8>,
         event_bus: EventBus,
         stop_flag: Arc<AtomicBool>,
         command_rx: mpsc::Receiver<PlaybackCommand>,
+        generation: u64,
     ) -> Result<()> {
         let dsd_sample_rate = decoder.sample_rate();
         let channels = decoder.channels();
@@ -1124,7 +1374,8 @@ impl PlaybackEngine {
 
             if bytes_read == 0 {
                 debug!("end of DSD stream reached");
-                event_bus.emit(Event::SongFinished);
+                output.drain()?;
+                event_bus.emit(Event::SongFinished(generation));
                 break;
             }
 
@@ -0,0 +1,281 @@
+//! FFmpeg fallback decoder (feature `ffmpeg`).
+//!
+//! Symphonia has no WavPack, Musepack, Monkey's Audio or TTA decoder. For
+//! those (and anything else Symphonia rejects) the engine falls back to the
+//! `ffmpeg` command-line tool: `ffprobe` reports the stream parameters and
+//! `ffmpeg` decodes to interleaved native-endian `f32` on a pipe, the same
+//! sample layout [`SymphoniaDecoder`](crate::SymphoniaDecoder) produces.
+//! Seeking restarts the pipe at the requested position.
+
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::AudioFormat;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// Stream parameters reported by `ffprobe`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ProbeInfo {
+    sample_rate: u32,
+    channels: u8,
+    duration: Option<f64>,
+    bitrate: Option<u32>,
+}
+
+/// Parse `ffprobe -of default=noprint_wrappers=1` output (`key=value` lines;
+/// unknown values are printed as `N/A`).
+fn parse_probe(output: &str) -> Option<ProbeInfo> {
+    let mut sample_rate = None;
+    let mut channels = None;
+    let mut duration = None;
+    let mut bitrate = None;
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "sample_rate" => sample_rate = value.parse().ok(),
+            "channels" => channels = value.parse().ok(),
+            "duration" => duration = duration.or(value.parse().ok()),
+            // Stream bitrate first; the container's is the fallback.
+            "bit_rate" => {
+                bitrate = bitrate.or(value.parse::<u32>().ok().map(|bps| bps / 1000));
+            }
+            _ => {}
+        }
+    }
+    Some(ProbeInfo {
+        sample_rate: sample_rate?,
+        channels: channels.filter(|&c| c > 0)?,
+        duration,
+        bitrate,
+    })
+}
+
+/// Whether the `ffmpeg` and `ffprobe` binaries can be run.
+#[must_use]
+pub fn ffmpeg_available() -> bool {
+    ["ffmpeg", "ffprobe"].iter().all(|bin| {
+        Command::new(bin)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    })
+}
+
+/// Decoder that pipes PCM out of an `ffmpeg` child process.
+pub struct FfmpegDecoder {
+    path: PathBuf,
+    info: ProbeInfo,
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    /// Bytes of a sample split across two reads.
+    partial: Vec<u8>,
+}
+
+impl FfmpegDecoder {
+    pub fn open(path: &Path) -> Result<Self> {
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "a:0"])
+            .args([
+                "-show_entries",
+                "stream=sample_rate,channels,duration,bit_rate",
+            ])
+            .args(["-show_entries", "format=duration,bit_rate"])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| RmpdError::Player(format!("Failed to run ffprobe: {e}")))?;
+        if !output.status.success() {
+            return Err(RmpdError::Player(format!(
+                "ffprobe cannot read {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let info = parse_probe(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+            RmpdError::Player(format!("No audio stream found in {}", path.display()))
+        })?;
+
+        let (child, stdout) = Self::spawn(path, 0.0)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            info,
+            child,
+            stdout,
+            partial: Vec::new(),
+        })
+    }
+
+    fn spawn(path: &Path, position: f64) -> Result<(Child, BufReader<ChildStdout>)> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin"])
+            .args(["-ss", &format!("{position:.6}")])
+            .arg("-i")
+            .arg(path)
+            .args(["-map", "0:a:0", "-vn", "-f"])
+            .arg(if cfg!(target_endian = "big") {
+                "f32be"
+            } else {
+                "f32le"
+            })
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| RmpdError::Player(format!("Failed to run ffmpeg: {e}")))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| RmpdError::Player("ffmpeg stdout unavailable".to_owned()))?;
+        Ok((child, BufReader::new(stdout)))
+    }
+
+    fn stop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Fill `buffer` with interleaved samples; returns the number written
+    /// (0 at end of stream).
+    pub fn read(&mut self, buffer: &mut [f32]) -> Result<usize> {
+        let mut bytes = std::mem::take(&mut self.partial);
+        let want = buffer.len() * 4;
+        let mut chunk = vec![0u8; want.saturating_sub(bytes.len())];
+        let mut filled = 0;
+        while bytes.len() + filled < want {
+            match self.stdout.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(RmpdError::Player(format!("ffmpeg read failed: {e}"))),
+            }
+        }
+        bytes.extend_from_slice(&chunk[..filled]);
+
+        let samples = bytes.len() / 4;
+        for (dst, src) in buffer.iter_mut().zip(bytes.chunks_exact(4)) {
+            *dst = f32::from_ne_bytes([src[0], src[1], src[2], src[3]]);
+        }
+        self.partial = bytes[samples * 4..].to_vec();
+        Ok(samples)
+    }
+
+    pub fn seek(&mut self, position: f64) -> Result<()> {
+        if position < 0.0 {
+            return Err(RmpdError::Player("Invalid seek position".to_owned()));
+        }
+        self.stop();
+        let (child, stdout) = Self::spawn(&self.path, position)?;
+        self.child = child;
+        self.stdout = stdout;
+        self.partial.clear();
+        Ok(())
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        AudioFormat {
+            sample_rate: self.info.sample_rate,
+            channels: self.info.channels,
+            bits_per_sample: 16, // decoded to f32; reported as 16-bit like Symphonia
+        }
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.info.duration
+    }
+
+    pub fn current_bitrate(&self) -> Option<u32> {
+        self.info.bitrate
+    }
+}
+
+impl Drop for FfmpegDecoder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ffprobe_output() {
+        let out = "sample_rate=44100\nchannels=2\nduration=N/A\nbit_rate=N/A\n\
+                   duration=3.500000\nbit_rate=645000\n";
+        assert_eq!(
+            parse_probe(out),
+            Some(ProbeInfo {
+                sample_rate: 44100,
+                channels: 2,
+                duration: Some(3.5),
+                bitrate: Some(645),
+            })
+        );
+        assert_eq!(parse_probe("duration=1.0\n"), None);
+    }
+
+    #[test]
+    fn decodes_tta_through_ffmpeg() {
+        if !ffmpeg_available() {
+            eprintln!("FFmpeg not available - skipping test");
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.tta");
+        let status = Command::new("ffmpeg")
+            .args([
+                "-v",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "sine=frequency=440:duration=1",
+            ])
+            .args(["-ac", "2", "-ar", "44100", "-codec:a", "tta", "-y"])
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut dec = FfmpegDecoder::open(&path).unwrap();
+        assert_eq!(dec.format().sample_rate, 44100);
+        assert_eq!(dec.format().channels, 2);
+        assert!((dec.duration().unwrap() - 1.0).abs() < 0.05);
+
+        let mut buf = vec![0f32; 4096];
+        let mut total = 0;
+        loop {
+            let n = dec.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(buf[..n].iter().all(|s| s.abs() <= 1.0));
+            total += n;
+        }
+        assert!(
+            (total as i64 - 88_200).abs() < 2_000,
+            "decoded {total} samples"
+        );
+
+        // Seeking restarts the pipe near the requested position.
+        dec.seek(0.5).unwrap();
+        let mut rest = 0;
+        while let Ok(n) = dec.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            rest += n;
+        }
+        assert!(
+            (rest as i64 - 44_100).abs() < 2_000,
+            "decoded {rest} samples"
+        );
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! [`AudioFilter`] is the in-place DSP stage trait.  [`FilterChain`] composes
 //! them in order.  [`VolumeFilter`] reads a live `Arc<AtomicU8>` (0..=100) so
-//! the volume can be changed without touching the chain.
+//! the volume can be changed without touching the chain.  [`NormalizeFilter`]
+//! is the automatic gain control behind `volume_normalization`.
 //!
 //! [`Mixer`] is the seam for future hardware mixer integration (ALSA, Pulse).
 //! [`SoftwareMixer`] is the v1 implementation backed by the same atomic.
@@ -56,6 +57,86 @@ impl AudioFilter for VolumeFilter {
     }
 }
 
+/// Level the normalizer aims the loudest recent peak at (half of full scale).
+const NORMALIZE_TARGET: f32 = 0.5;
+/// Largest boost the normalizer applies.
+const NORMALIZE_MAX_GAIN: f32 = 32.0;
+/// Inertia of gain changes: each block moves 1/256 of the way to the target.
+const NORMALIZE_SMOOTH: f32 = 256.0;
+/// Blocks of peak history: about 9 s of 4096-sample stereo blocks at 44.1 kHz.
+const NORMALIZE_HISTORY: usize = 200;
+/// Floor for a block peak, so silence does not ask for infinite gain.
+const NORMALIZE_MIN_PEAK: f32 = 1.0 / 32768.0;
+
+/// Automatic gain control for `volume_normalization`, after MPD's `normalize`
+/// filter (AudioCompress).
+///
+/// Keeps the peaks of the last few seconds and slowly raises the gain until the
+/// loudest of them sits at half of full scale.  It only ever boosts, by at most
+/// 32×, and drops the gain at once when the next block would clip.  The gain is
+/// ramped linearly across each block to avoid zipper noise.
+pub struct NormalizeFilter {
+    peaks: Vec<f32>,
+    pos: usize,
+    gain: f32,
+}
+
+impl NormalizeFilter {
+    pub fn new() -> Self {
+        Self {
+            peaks: vec![0.0; NORMALIZE_HISTORY],
+            pos: 0,
+            gain: 1.0,
+        }
+    }
+
+    /// The gain reached at the end of the last block.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+}
+
+impl Default for NormalizeFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioFilter for NormalizeFilter {
+    fn name(&self) -> &str {
+        "normalize"
+    }
+
+    fn apply(&mut self, buf: &mut [f32]) {
+        if buf.is_empty() {
+            return;
+        }
+        let block_peak = buf
+            .iter()
+            .fold(NORMALIZE_MIN_PEAK, |peak, s| peak.max(s.abs()));
+        self.pos = (self.pos + 1) % self.peaks.len();
+        self.peaks[self.pos] = block_peak;
+        let peak = self.peaks.iter().fold(block_peak, |a, &b| a.max(b));
+
+        let wanted = NORMALIZE_TARGET / peak;
+        let mut gain = (self.gain * (NORMALIZE_SMOOTH - 1.0) + wanted) / NORMALIZE_SMOOTH;
+        gain = gain.clamp(1.0, NORMALIZE_MAX_GAIN);
+        if peak * gain > 1.0 {
+            gain = 1.0 / peak;
+        }
+        // Ramping down from the old gain would clip this block: cut at once.
+        if block_peak * self.gain > 1.0 {
+            self.gain = gain;
+        }
+
+        let step = (gain - self.gain) / buf.len() as f32;
+        for (i, s) in buf.iter_mut().enumerate() {
+            *s *= self.gain + step * (i + 1) as f32;
+        }
+        self.gain = gain;
+    }
+}
+
 // ── FilterChain ──────────────────────────────────────────────────────────────
 
 /// Ordered chain of [`AudioFilter`]s applied left-to-right in sequence.
@@ -201,6 +282,57 @@ mod tests {
         assert!(!chain.is_empty());
     }
 
+    // NormalizeFilter: a quiet signal is raised steadily towards half scale.
+    #[test]
+    fn normalize_filter_boosts_quiet_signal() {
+        let mut f = NormalizeFilter::new();
+        let mut last = 0.0;
+        for _ in 0..2000 {
+            let mut buf = vec![0.05f32, -0.05, 0.05, -0.05];
+            f.apply(&mut buf);
+            assert!(
+                buf[3].abs() >= last,
+                "gain must not fall on a steady signal"
+            );
+            last = buf[3].abs();
+        }
+        assert!((last - NORMALIZE_TARGET).abs() < 0.01, "settled at {last}");
+    }
+
+    // NormalizeFilter: never attenuates, never boosts past the maximum gain.
+    #[test]
+    fn normalize_filter_gain_is_bounded() {
+        let mut f = NormalizeFilter::new();
+        for _ in 0..5000 {
+            f.apply(&mut [0.8f32, -0.8]);
+        }
+        assert!(
+            (f.gain() - 1.0).abs() < f32::EPSILON,
+            "loud audio is left alone"
+        );
+
+        let mut f = NormalizeFilter::new();
+        for _ in 0..5000 {
+            f.apply(&mut [1e-4f32, -1e-4]);
+        }
+        assert!(f.gain() <= NORMALIZE_MAX_GAIN);
+    }
+
+    // NormalizeFilter: a sudden loud block after a quiet stretch does not clip.
+    #[test]
+    fn normalize_filter_does_not_clip_on_transients() {
+        let mut f = NormalizeFilter::new();
+        for _ in 0..3000 {
+            f.apply(&mut [0.02f32, -0.02]);
+        }
+        assert!(f.gain() > 4.0, "quiet stretch should be boosted");
+        let mut buf = vec![0.9f32; 64];
+        f.apply(&mut buf);
+        for s in &buf {
+            assert!(*s <= 1.0 + f32::EPSILON, "clipped: {s}");
+        }
+    }
+
     // SoftwareMixer: set/get roundtrip.
     #[test]
     fn software_mixer_set_get() {
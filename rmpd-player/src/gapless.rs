@@ -0,0 +1,362 @@
+//! Gapless playback: encoder delay and padding of MP3 and AAC files.
+//!
+//! Both codecs prepend priming samples to the audio and pad the last frame,
+//! so decoding two consecutive tracks verbatim leaves a short silence between
+//! them. Like MPD, the decoder drops those samples using the counts the
+//! encoder recorded: the LAME extension of the Xing/Info header in the first
+//! MP3 frame, or the iTunes `iTunSMPB` comment of an MP4 file.
+//!
+//! Symphonia's own gapless handling stays disabled, so nothing is trimmed
+//! twice.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Delay of the MP3 decoder's filterbank, which LAME's delay field omits.
+const MP3_DECODER_DELAY: u64 = 529;
+
+/// Largest `moov` atom read while looking for `iTunSMPB`.
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Where the real audio lies in a decoded stream, in frames (samples per
+/// channel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaplessInfo {
+    /// Priming frames to drop from the start.
+    pub skip: u64,
+    /// Frames of audio following the priming; the rest is padding.
+    pub frames: u64,
+}
+
+/// Read the gapless info of a local MP3 or MP4 file, if it carries any.
+pub fn read_gapless_info(path: &Path) -> Option<GaplessInfo> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    match ext.as_str() {
+        "mp3" => read_mp3(&mut reader),
+        "m4a" | "m4b" | "mp4" => read_mp4(&mut reader),
+        _ => None,
+    }
+}
+
+/// Skip a leading ID3v2 tag and parse the first MP3 frame.
+fn read_mp3<R: Read + Seek>(reader: &mut R) -> Option<GaplessInfo> {
+    let mut head = [0u8; 10];
+    reader.read_exact(&mut head).ok()?;
+    let start = if head.starts_with(b"ID3") {
+        let size = head[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | u64::from(b & 0x7F));
+        // Footer flag: a second 10-byte block follows the frames.
+        let footer = if head[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    reader.seek(SeekFrom::Start(start)).ok()?;
+
+    let mut frame = Vec::with_capacity(4096);
+    reader.take(4096).read_to_end(&mut frame).ok()?;
+    let sync = frame
+        .windows(2)
+        .position(|w| w[0] == 0xFF && w[1] & 0xE0 == 0xE0)?;
+    parse_lame(&frame[sync..])
+}
+
+/// Gapless info from the Xing/Info header and LAME extension of the first
+/// MP3 frame. FFmpeg writes the same extension under its own version string.
+pub fn parse_lame(frame: &[u8]) -> Option<GaplessInfo> {
+    let header = frame.get(..4)?;
+    // Frame sync, Layer III.
+    if header[0] != 0xFF || header[1] & 0xE0 != 0xE0 || (header[1] >> 1) & 3 != 1 {
+        return None;
+    }
+    let mpeg1 = (header[1] >> 3) & 3 == 3;
+    let mono = header[3] >> 6 == 3;
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+    let crc = if header[1] & 1 == 0 { 2 } else { 0 };
+
+    let xing = frame.get(4 + crc + side_info..)?;
+    if !xing.starts_with(b"Xing") && !xing.starts_with(b"Info") {
+        return None;
+    }
+    let u32_at = |i: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(xing.get(i..i + 4)?.try_into().ok()?))
+    };
+    let flags = u32_at(4)?;
+    if flags & 1 == 0 {
+        return None; // no frame count
+    }
+    let frame_count = u64::from(u32_at(8)?);
+    let mut lame = 12;
+    for (flag, len) in [(2, 4), (4, 100), (8, 4)] {
+        if flags & flag != 0 {
+            lame += len;
+        }
+    }
+
+    let ext = xing.get(lame..lame + 24)?;
+    if !matches!(&ext[..4], b"LAME" | b"Lavc" | b"Lavf") {
+        return None;
+    }
+    let delay = (u64::from(ext[21]) << 4) | u64::from(ext[22] >> 4);
+    let padding = (u64::from(ext[22] & 0x0F) << 8) | u64::from(ext[23]);
+    let samples_per_frame = if mpeg1 { 1152 } else { 576 };
+    Some(GaplessInfo {
+        skip: delay + MP3_DECODER_DELAY,
+        frames: (frame_count * samples_per_frame).checked_sub(delay + padding)?,
+    })
+}
+
+/// Walk the top-level atoms to `moov` and look for `iTunSMPB` inside it.
+fn read_mp4<R: Read + Seek>(reader: &mut R) -> Option<GaplessInfo> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let size = u64::from(u32::from_be_bytes(header[..4].try_into().ok()?));
+        let body_len = match size {
+            // 64-bit size follows the type.
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                u64::from_be_bytes(large).checked_sub(16)?
+            }
+            // Extends to the end of the file.
+            0 => u64::MAX,
+            n => n.checked_sub(8)?,
+        };
+        if &header[4..] == b"moov" {
+            let mut moov = Vec::new();
+            reader
+                .take(body_len.min(MAX_MOOV_SIZE))
+                .read_to_end(&mut moov)
+                .ok()?;
+            return parse_itunsmpb(&find_itunsmpb(&moov)?);
+        }
+        if size == 0 {
+            return None;
+        }
+        reader
+            .seek(SeekFrom::Current(i64::try_from(body_len).ok()?))
+            .ok()?;
+    }
+}
+
+/// The text of the `----:com.apple.iTunes:iTunSMPB` item in `moov`: the
+/// `data` atom that follows its `name` atom.
+fn find_itunsmpb(moov: &[u8]) -> Option<String> {
+    let pos = moov.windows(8).position(|w| w == b"iTunSMPB")?;
+    let name_start = pos.checked_sub(12)?;
+    if &moov[name_start + 4..name_start + 8] != b"name" {
+        return None;
+    }
+    let atom_size = |at: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(moov.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let data_start = name_start + atom_size(name_start)?;
+    let data_size = atom_size(data_start)?;
+    if moov.get(data_start + 4..data_start + 8)? != b"data" || data_size < 16 {
+        return None;
+    }
+    let text = moov.get(data_start + 16..data_start + data_size)?;
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+/// Gapless info from an `iTunSMPB` value: hex fields
+/// ` 00000000 <delay> <padding> <samples> ...`.
+pub fn parse_itunsmpb(value: &str) -> Option<GaplessInfo> {
+    let mut fields = value
+        .split_whitespace()
+        .skip(1)
+        .map(|f| u64::from_str_radix(f, 16).ok());
+    let skip = fields.next()??;
+    let _padding = fields.next()??;
+    let frames = fields.next()??;
+    (frames > 0).then_some(GaplessInfo { skip, frames })
+}
+
+/// Drops priming and padding frames from a stream of decoded blocks.
+#[derive(Debug, Clone)]
+pub struct GaplessTrimmer {
+    info: GaplessInfo,
+    /// Priming frames still to drop.
+    skip: u64,
+    /// Audio frames still to pass through.
+    remaining: u64,
+}
+
+impl GaplessTrimmer {
+    pub fn new(info: GaplessInfo) -> Self {
+        Self {
+            info,
+            skip: info.skip,
+            remaining: info.frames,
+        }
+    }
+
+    /// Whether all audio frames have been passed through.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Trim one decoded block of interleaved samples in place.
+    pub fn trim(&mut self, samples: &mut Vec<f32>, channels: usize) {
+        let channels = channels.max(1);
+        let frames = (samples.len() / channels) as u64;
+        let skip = self.skip.min(frames);
+        self.skip -= skip;
+        let keep = (frames - skip).min(self.remaining);
+        self.remaining -= keep;
+        samples.drain(..skip as usize * channels);
+        samples.truncate(keep as usize * channels);
+    }
+
+    /// The stream position (in frames, priming included) to seek to for
+    /// `frame` of the trimmed audio; trimming resumes from there.
+    pub fn seek(&mut self, frame: u64) -> u64 {
+        let frame = frame.min(self.info.frames);
+        self.skip = 0;
+        self.remaining = self.info.frames - frame;
+        frame + self.info.skip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// First frame of an MPEG-1 Layer III stereo stream holding a Xing
+    /// header (all fields) and a LAME extension.
+    fn xing_frame(encoder: &[u8; 9], frames: u32, delay: u16, padding: u16) -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xFB, 0x50, 0x00];
+        frame.extend_from_slice(&[0; 32]); // side info
+        frame.extend_from_slice(b"Xing");
+        frame.extend_from_slice(&0x0Fu32.to_be_bytes());
+        frame.extend_from_slice(&frames.to_be_bytes());
+        frame.extend_from_slice(&[0; 4 + 100 + 4]); // bytes, TOC, quality
+        let mut ext = [0u8; 36];
+        ext[..9].copy_from_slice(encoder);
+        ext[21] = (delay >> 4) as u8;
+        ext[22] = ((delay & 0x0F) << 4) as u8 | (padding >> 8) as u8;
+        ext[23] = padding as u8;
+        frame.extend_from_slice(&ext);
+        frame
+    }
+
+    #[test]
+    fn parses_lame_extension() {
+        // What FFmpeg writes for one second at 44.1 kHz.
+        let frame = xing_frame(b"Lavc62.11", 40, 576, 1404);
+        assert_eq!(
+            parse_lame(&frame),
+            Some(GaplessInfo {
+                skip: 576 + 529,
+                frames: 44100
+            })
+        );
+        let frame = xing_frame(b"LAME3.100", 40, 576, 1404);
+        assert_eq!(parse_lame(&frame).map(|i| i.frames), Some(44100));
+    }
+
+    #[test]
+    fn ignores_xing_without_lame_extension() {
+        assert_eq!(
+            parse_lame(&xing_frame(b"\0\0\0\0\0\0\0\0\0", 40, 0, 0)),
+            None
+        );
+        // An ordinary audio frame.
+        let mut frame = vec![0xFF, 0xFB, 0x50, 0x00];
+        frame.resize(417, 0x55);
+        assert_eq!(parse_lame(&frame), None);
+    }
+
+    #[test]
+    fn reads_mp3_after_id3v2_tag() {
+        let mut file = b"ID3\x04\x00\x00\x00\x00\x00\x05".to_vec();
+        file.extend_from_slice(&[0; 5]);
+        file.extend(xing_frame(b"LAME3.100", 40, 576, 1404));
+        let info = read_mp3(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!(info.skip, 1105);
+        assert_eq!(info.frames, 44100);
+    }
+
+    #[test]
+    fn parses_itunsmpb() {
+        let value = " 00000000 00000840 0000037C 000000000000AC44 00000000 00000000";
+        assert_eq!(
+            parse_itunsmpb(value),
+            Some(GaplessInfo {
+                skip: 2112,
+                frames: 44100
+            })
+        );
+        assert_eq!(parse_itunsmpb(" 00000000 00000840"), None);
+        assert_eq!(parse_itunsmpb("garbage"), None);
+    }
+
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    #[test]
+    fn finds_itunsmpb_in_moov() {
+        let value = b" 00000000 00000840 0000037C 000000000000AC44";
+        let mut item = atom(b"mean", b"\0\0\0\0com.apple.iTunes");
+        item.extend(atom(b"name", b"\0\0\0\0iTunSMPB"));
+        item.extend(atom(
+            b"data",
+            &[&[0, 0, 0, 1, 0, 0, 0, 0][..], value].concat(),
+        ));
+        let ilst = atom(b"ilst", &atom(b"----", &item));
+        let meta = atom(b"meta", &[&[0u8; 4][..], &ilst].concat());
+        let moov = atom(b"moov", &atom(b"udta", &meta));
+
+        let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+        file.extend(atom(b"mdat", &[0; 64]));
+        file.extend(moov);
+        let info = read_mp4(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!(info.skip, 2112);
+        assert_eq!(info.frames, 44100);
+    }
+
+    #[test]
+    fn trimmer_drops_priming_and_padding_across_blocks() {
+        let mut trimmer = GaplessTrimmer::new(GaplessInfo { skip: 3, frames: 4 });
+        // Stereo blocks of two frames, frame n holding (n, -n).
+        let block = |first: i32| -> Vec<f32> {
+            (first..first + 2)
+                .flat_map(|n| [n as f32, -(n as f32)])
+                .collect()
+        };
+        let mut out = Vec::new();
+        for first in [0, 2, 4, 6, 8] {
+            let mut samples = block(first);
+            trimmer.trim(&mut samples, 2);
+            out.extend(samples);
+        }
+        assert_eq!(out, [3.0, -3.0, 4.0, -4.0, 5.0, -5.0, 6.0, -6.0]);
+        assert!(trimmer.is_done());
+    }
+
+    #[test]
+    fn trimmer_seek_offsets_by_priming() {
+        let mut trimmer = GaplessTrimmer::new(GaplessInfo {
+            skip: 1105,
+            frames: 44100,
+        });
+        assert_eq!(trimmer.seek(22050), 22050 + 1105);
+        let mut samples = vec![0.0; 2 * 30000];
+        trimmer.trim(&mut samples, 2);
+        assert_eq!(samples.len(), 2 * 22050);
+        assert!(trimmer.is_done());
+    }
+}
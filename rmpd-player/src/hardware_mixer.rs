@@ -0,0 +1,244 @@
+//! Hardware mixers and external-change monitoring.
+//!
+//! An output configured with `mixer_type = "hardware"` drives a device mixer
+//! (ALSA simple element) through the [`Mixer`] seam instead of scaling samples
+//! in software. Other applications (`alsamixer`, desktop volume keys) can
+//! change that mixer behind rmpd's back, so [`MixerMonitor`] polls it and, when
+//! the hardware value no longer matches the last known volume, updates
+//! `PlayerStatus::volume` and emits [`Event::VolumeChanged`] — which wakes
+//! `idle mixer` clients, exactly like MPD's mixer monitor.
+
+use crate::filter::Mixer;
+use rmpd_core::config::OutputConfig;
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::event::{Event, EventBus};
+use rmpd_core::state::PlayerStatus;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// How often [`MixerMonitor`] re-reads the hardware volume.
+pub const MIXER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether `cfg` asks for a hardware mixer (`mixer_type = "hardware"`).
+#[must_use]
+pub fn wants_hardware_mixer(cfg: &OutputConfig) -> bool {
+    cfg.setting_str("mixer_type")
+        .is_some_and(|t| t.eq_ignore_ascii_case("hardware"))
+}
+
+/// Open the hardware mixer described by an `[[output]]` block.
+///
+/// Config keys:
+/// - `mixer_device`  — ALSA control device (default `"default"`)
+/// - `mixer_control` — simple element name (default `"PCM"`)
+/// - `mixer_index`   — element index (default `0`)
+#[cfg(all(feature = "alsa-mixer", target_os = "linux"))]
+pub fn open_hardware_mixer(cfg: &OutputConfig) -> Result<Arc<dyn Mixer + Sync>> {
+    let device = cfg
+        .setting_str("mixer_device")
+        .unwrap_or_else(|| "default".to_owned());
+    let control = cfg
+        .setting_str("mixer_control")
+        .unwrap_or_else(|| "PCM".to_owned());
+    let index = cfg
+        .setting_str("mixer_index")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    Ok(Arc::new(AlsaMixer::open(&device, &control, index)?))
+}
+
+/// Open the hardware mixer described by an `[[output]]` block.
+#[cfg(not(all(feature = "alsa-mixer", target_os = "linux")))]
+pub fn open_hardware_mixer(cfg: &OutputConfig) -> Result<Arc<dyn Mixer + Sync>> {
+    Err(RmpdError::Player(format!(
+        "output \"{}\": hardware mixer support is not compiled in (build with --features alsa-mixer)",
+        cfg.name
+    )))
+}
+
+// ── AlsaMixer ────────────────────────────────────────────────────────────────
+
+/// ALSA simple-element playback volume, scaled to 0..=100.
+#[cfg(all(feature = "alsa-mixer", target_os = "linux"))]
+pub struct AlsaMixer {
+    mixer: parking_lot::Mutex<alsa::Mixer>,
+    control: String,
+    index: u32,
+    /// Raw `(min, max)` playback volume range of the element.
+    range: (i64, i64),
+}
+
+#[cfg(all(feature = "alsa-mixer", target_os = "linux"))]
+impl AlsaMixer {
+    pub fn open(device: &str, control: &str, index: u32) -> Result<Self> {
+        let mixer = alsa::Mixer::new(device, false)
+            .map_err(|e| RmpdError::Player(format!("cannot open mixer {device}: {e}")))?;
+        let range = mixer
+            .find_selem(&alsa::mixer::SelemId::new(control, index))
+            .ok_or_else(|| {
+                RmpdError::Player(format!("mixer {device} has no control \"{control}\""))
+            })?
+            .get_playback_volume_range();
+        Ok(Self {
+            mixer: parking_lot::Mutex::new(mixer),
+            control: control.to_owned(),
+            index,
+            range,
+        })
+    }
+
+    fn selem_id(&self) -> alsa::mixer::SelemId {
+        alsa::mixer::SelemId::new(&self.control, self.index)
+    }
+}
+
+#[cfg(all(feature = "alsa-mixer", target_os = "linux"))]
+impl Mixer for AlsaMixer {
+    fn set_volume(&self, v: u8) {
+        let (min, max) = self.range;
+        let raw = min + ((max - min) * i64::from(v.min(100)) + 50) / 100;
+        let mixer = self.mixer.lock();
+        if let Some(selem) = mixer.find_selem(&self.selem_id())
+            && let Err(e) = selem.set_playback_volume_all(raw)
+        {
+            tracing::warn!("hardware mixer: cannot set volume: {e}");
+        }
+    }
+
+    fn volume(&self) -> u8 {
+        let (min, max) = self.range;
+        let mixer = self.mixer.lock();
+        // Process pending events so the cached element value reflects changes
+        // made by other applications since the last read.
+        let _ = mixer.handle_events();
+        let raw = mixer
+            .find_selem(&self.selem_id())
+            .and_then(|s| {
+                s.get_playback_volume(alsa::mixer::SelemChannelId::FrontLeft)
+                    .ok()
+            })
+            .unwrap_or(max);
+        if max <= min {
+            return 100;
+        }
+        // Round to nearest so a value written by `set_volume` reads back unchanged.
+        (((raw - min) * 100 + (max - min) / 2) / (max - min)).clamp(0, 100) as u8
+    }
+}
+
+// ── MixerMonitor ─────────────────────────────────────────────────────────────
+
+/// Compare the mixer's current volume with the last known value in `known`.
+/// Returns the new volume (and records it) when it changed externally.
+fn poll_once(mixer: &dyn Mixer, known: &AtomicU8) -> Option<u8> {
+    let hw = mixer.volume();
+    (known.swap(hw, Ordering::AcqRel) != hw).then_some(hw)
+}
+
+/// Background thread that detects external hardware volume changes.
+/// Stops and joins on drop.
+pub struct MixerMonitor {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MixerMonitor {
+    /// Poll `mixer` every `interval`. `known` is the engine's volume atomic:
+    /// volume changes made through rmpd update it before the monitor looks,
+    /// so only changes made elsewhere produce an event.
+    pub fn spawn(
+        mixer: Arc<dyn Mixer + Sync>,
+        known: Arc<AtomicU8>,
+        status: Arc<RwLock<PlayerStatus>>,
+        event_bus: EventBus,
+        interval: Duration,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let handle = thread::Builder::new()
+            .name("mixer-monitor".to_owned())
+            .spawn(move || {
+                while !stop_flag.load(Ordering::Acquire) {
+                    if let Some(v) = poll_once(mixer.as_ref(), &known) {
+                        tracing::debug!("hardware mixer changed externally: volume {v}");
+                        status.blocking_write().volume = v;
+                        event_bus.emit(Event::VolumeChanged(v));
+                    }
+                    thread::park_timeout(interval);
+                }
+            })
+            .map_err(|e| tracing::warn!("cannot spawn mixer monitor: {e}"))
+            .ok();
+        Self { stop, handle }
+    }
+}
+
+impl Drop for MixerMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::SoftwareMixer;
+
+    #[test]
+    fn poll_reports_only_external_changes() {
+        let hw = Arc::new(AtomicU8::new(60));
+        let mixer = SoftwareMixer::new(hw.clone());
+        let known = AtomicU8::new(60);
+        assert_eq!(poll_once(&mixer, &known), None);
+
+        // Another application turns the hardware down.
+        hw.store(35, Ordering::Release);
+        assert_eq!(poll_once(&mixer, &known), Some(35));
+        assert_eq!(known.load(Ordering::Acquire), 35);
+        assert_eq!(poll_once(&mixer, &known), None);
+    }
+
+    #[test]
+    fn monitor_emits_mixer_event_and_updates_status() {
+        let hw = Arc::new(AtomicU8::new(80));
+        let mixer: Arc<dyn Mixer + Sync> = Arc::new(SoftwareMixer::new(hw.clone()));
+        let known = Arc::new(AtomicU8::new(80));
+        let status = Arc::new(RwLock::new(PlayerStatus::default()));
+        let bus = EventBus::new();
+        let mut rx = bus.subscribe();
+
+        let monitor =
+            MixerMonitor::spawn(mixer, known, status.clone(), bus, Duration::from_millis(5));
+        hw.store(20, Ordering::Release);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let event = loop {
+            match rx.try_recv() {
+                Ok(e) => break e,
+                Err(_) if std::time::Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                Err(e) => panic!("no mixer event: {e}"),
+            }
+        };
+        drop(monitor);
+        assert!(matches!(event, Event::VolumeChanged(20)));
+        assert_eq!(status.blocking_read().volume, 20);
+    }
+
+    #[test]
+    fn hardware_mixer_type_is_detected() {
+        let mut cfg = OutputConfig::cpal_default();
+        assert!(!wants_hardware_mixer(&cfg));
+        cfg.settings
+            .insert("mixer_type".to_owned(), "Hardware".into());
+        assert!(wants_hardware_mixer(&cfg));
+    }
+}
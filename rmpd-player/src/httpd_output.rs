@@ -6,10 +6,12 @@
 //! real time.  Uses only `std::net` — no async runtime.
 //!
 //! Clients that send `Icy-MetaData: 1` receive a Shoutcast v1 greeting and
-//! interleaved ICY metadata blocks every `ICY_METAINT` audio bytes.
+//! interleaved ICY metadata blocks every `ICY_METAINT` audio bytes, unless the
+//! output is configured with `tags = "no"`, in which case every client gets the
+//! plain HTTP greeting and no metadata is injected into the stream.
 
 use crate::audio_output::{AudioOutput, PauseState};
-use crate::encoder::{Encoder, PcmEncoder, WavEncoder};
+use crate::encoder::{Encoder, create_encoder};
 use parking_lot::Mutex;
 use rmpd_core::config::OutputConfig;
 use rmpd_core::error::{Result, RmpdError};
@@ -141,6 +143,9 @@ pub struct HttpdOutput {
     port: u16,
     /// Station name for `icy-name` header; resolved in `new()`.
     name: String,
+    /// Whether current-song metadata is injected into the stream (MPD's
+    /// per-output `tags` option). When `false`, ICY requests are ignored.
+    tags: bool,
     /// All currently-connected client streams; dead streams are pruned on write.
     clients: Arc<Mutex<Vec<HttpdClient>>>,
     /// Set to `false` by `stop()` to signal the accept thread to exit.
@@ -158,8 +163,10 @@ impl HttpdOutput {
     /// Config keys read from `cfg`:
     /// - `bind_to_address` — interface to bind (default `"0.0.0.0"`)
     /// - `port`            — TCP port (default `8000`; `0` = OS-assigned)
-    /// - `encoder`         — `"wav"` (default) or `"pcm"`
-    pub fn new(format: AudioFormat, cfg: &OutputConfig) -> Self {
+    /// - `encoder`         — any [`crate::encoder::ENCODER_PLUGINS`] name
+    ///   (default `"wave"`), tuned by `quality` / `bitrate`
+    /// - `tags`            — `"yes"` (default) or `"no"` to disable ICY metadata
+    pub fn new(format: AudioFormat, cfg: &OutputConfig) -> Result<Self> {
         let addr = cfg
             .setting_str("bind_to_address")
             .unwrap_or_else(|| "0.0.0.0".to_owned());
@@ -169,11 +176,7 @@ impl HttpdOutput {
             .and_then(|s| s.parse().ok())
             .unwrap_or(8000);
 
-        let encoder: Box<dyn Encoder> = match cfg.setting_str("encoder").as_deref().unwrap_or("wav")
-        {
-            "pcm" => Box::new(PcmEncoder::new(format)),
-            _ => Box::new(WavEncoder::new(format)),
-        };
+        let encoder = create_encoder(format, cfg, crate::output_registry::DEFAULT_ENCODER)?;
 
         let name = if cfg.name.is_empty() {
             "rmpd".to_owned()
@@ -181,17 +184,20 @@ impl HttpdOutput {
             cfg.name.clone()
         };
 
-        Self {
+        let tags = cfg.setting_bool("tags").unwrap_or(true);
+
+        Ok(Self {
             addr,
             port,
             name,
+            tags,
             clients: Arc::new(Mutex::new(Vec::new())),
             running: Arc::new(AtomicBool::new(false)),
             accept_handle: None,
             encoder,
             bound: None,
             pause_state: PauseState::new(),
-        }
+        })
     }
 
     /// Returns the bound local address; populated after [`AudioOutput::start`].
@@ -222,6 +228,28 @@ fn has_icy_metadata(request: &[u8]) -> bool {
     false
 }
 
+/// Response to a `GET`/`HEAD /health` probe. The stream itself answers 200
+/// only once a client is accepted, which is not a useful liveness signal.
+const HEALTH_RESPONSE: &str = "HTTP/1.0 200 OK\r\n\
+    Content-Type: text/plain\r\n\
+    Content-Length: 3\r\n\
+    Connection: close\r\n\
+    \r\n\
+    OK\n";
+
+/// Whether a raw HTTP request head asks for `/health`.
+fn is_health_check(request: &[u8]) -> bool {
+    let line = request
+        .split(|&b| b == b'\r' || b == b'\n')
+        .next()
+        .unwrap_or_default();
+    let mut parts = line.split(|&b| b == b' ');
+    matches!(
+        (parts.next(), parts.next()),
+        (Some(b"GET" | b"HEAD"), Some(b"/health"))
+    )
+}
+
 impl AudioOutput for HttpdOutput {
     fn start(&mut self) -> Result<()> {
         use std::net::TcpListener;
@@ -248,6 +276,7 @@ impl AudioOutput for HttpdOutput {
         let content_type = self.encoder.content_type().to_owned();
         let header_bytes = self.encoder.header();
         let icy_name = self.name.clone();
+        let tags = self.tags;
 
         let handle = thread::spawn(move || {
             // HTTP response for plain (non-ICY) clients — identical to the
@@ -278,26 +307,30 @@ impl AudioOutput for HttpdOutput {
                         // Read the request head with a short timeout so a silent
                         // client cannot stall this loop indefinitely.
                         let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
-                        let wants_meta = {
-                            let mut buf: Vec<u8> = Vec::with_capacity(256);
-                            let mut tmp = [0u8; 128];
-                            let mut found_end = false;
-                            while buf.len() < 4096 {
-                                match stream.read(&mut tmp) {
-                                    Ok(0) => break,
-                                    Ok(n) => {
-                                        buf.extend_from_slice(&tmp[..n]);
-                                        if buf.windows(4).any(|w| w == b"\r\n\r\n") {
-                                            found_end = true;
-                                            break;
-                                        }
+                        let mut buf: Vec<u8> = Vec::with_capacity(256);
+                        let mut tmp = [0u8; 128];
+                        let mut found_end = false;
+                        while buf.len() < 4096 {
+                            match stream.read(&mut tmp) {
+                                Ok(0) => break,
+                                Ok(n) => {
+                                    buf.extend_from_slice(&tmp[..n]);
+                                    if buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                                        found_end = true;
+                                        break;
                                     }
-                                    // Timeout or other read error → treat as non-meta.
-                                    Err(_) => break,
                                 }
+                                // Timeout or other read error → treat as non-meta.
+                                Err(_) => break,
                             }
-                            found_end && has_icy_metadata(&buf)
-                        };
+                        }
+                        if is_health_check(&buf) {
+                            let _ = stream.write_all(HEALTH_RESPONSE.as_bytes());
+                            continue;
+                        }
+                        // With tags disabled the request is still drained, but
+                        // the client is served as a plain HTTP listener.
+                        let wants_meta = tags && found_end && has_icy_metadata(&buf);
 
                         let head: &str = if wants_meta { &icy_head } else { &http_head };
                         let ok = stream.write_all(head.as_bytes()).is_ok()
@@ -351,6 +384,15 @@ impl AudioOutput for HttpdOutput {
             // The accept thread wakes at most every 50 ms; join waits one cycle.
             let _ = handle.join();
         }
+        // Flush whatever the encoder still buffers (e.g. a partial FLAC block)
+        // so listeners receive the end of the stream.
+        let tail = self.encoder.finish();
+        if !tail.is_empty() {
+            let cur = now_playing();
+            self.clients
+                .lock()
+                .retain_mut(|client| client.serve(&tail, &cur));
+        }
         self.clients.lock().clear();
         Ok(())
     }
@@ -369,6 +411,7 @@ impl AudioOutput for HttpdOutput {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::encoder::PcmEncoder;
     use std::io::Read;
     use std::time::Instant;
 
@@ -382,6 +425,7 @@ mod tests {
             addr: "127.0.0.1".to_owned(),
             port,
             name: "rmpd".to_owned(),
+            tags: true,
             clients: Arc::new(Mutex::new(Vec::new())),
             running: Arc::new(AtomicBool::new(false)),
             accept_handle: None,
@@ -641,6 +685,88 @@ mod tests {
         output.stop().unwrap();
     }
 
+    #[test]
+    fn health_check_is_answered_and_not_streamed() {
+        let mut output = make_pcm_output(0);
+        output.start().expect("start failed");
+        let port = output.local_addr().unwrap().port();
+        thread::sleep(Duration::from_millis(30));
+
+        let mut probe = TcpStream::connect(format!("127.0.0.1:{port}")).unwrap();
+        probe
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        probe
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let received = read_until(&mut probe, |b| b.ends_with(b"OK\n"));
+
+        assert_eq!(received, HEALTH_RESPONSE.as_bytes());
+        assert!(
+            output.clients.lock().is_empty(),
+            "probe must not be streamed to"
+        );
+
+        output.stop().unwrap();
+    }
+
+    #[test]
+    fn health_check_request_line() {
+        assert!(is_health_check(b"GET /health HTTP/1.0\r\n\r\n"));
+        assert!(is_health_check(b"HEAD /health HTTP/1.1\r\nHost: x\r\n\r\n"));
+        assert!(!is_health_check(b"GET / HTTP/1.0\r\n\r\n"));
+        assert!(!is_health_check(b"POST /health HTTP/1.0\r\n\r\n"));
+        assert!(!is_health_check(b""));
+    }
+
+    #[test]
+    fn tags_disabled_serves_icy_client_plain_http() {
+        let mut output = make_pcm_output(0);
+        output.tags = false;
+        output.start().expect("start failed");
+        let port = output.local_addr().unwrap().port();
+        thread::sleep(Duration::from_millis(30));
+
+        let mut icy_client = TcpStream::connect(format!("127.0.0.1:{port}")).unwrap();
+        icy_client
+            .write_all(b"GET / HTTP/1.0\r\nIcy-MetaData: 1\r\n\r\n")
+            .unwrap();
+        icy_client
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+
+        wait_for_clients(&output, 1);
+        set_now_playing(Some("Hidden Artist - Hidden Song".to_owned()));
+        output.write(&vec![0.0_f32; 8000]).unwrap();
+
+        // Header plus one full metaint worth of audio, with no block after it.
+        let received = read_until(&mut icy_client, |b| {
+            b.windows(4)
+                .position(|w| w == b"\r\n\r\n")
+                .is_some_and(|h| b.len() >= h + 4 + ICY_METAINT)
+        });
+
+        assert!(
+            received.starts_with(b"HTTP/1.0 200"),
+            "tags=no must answer ICY requests with a plain HTTP greeting"
+        );
+        assert!(
+            !received
+                .windows(b"icy-metaint".len())
+                .any(|w| w.eq_ignore_ascii_case(b"icy-metaint")),
+            "icy-metaint must not be advertised when tags are disabled"
+        );
+        assert!(
+            !received
+                .windows(b"StreamTitle".len())
+                .any(|w| w == b"StreamTitle"),
+            "no metadata may be injected when tags are disabled"
+        );
+
+        set_now_playing(None);
+        output.stop().unwrap();
+    }
+
     #[test]
     fn icy_metadata_interleaved_at_metaint_boundary() {
         let mut output = make_pcm_output(0);
@@ -0,0 +1,242 @@
+//! MP3 encoder backed by LAME (through `mp3lame-encoder`).
+//!
+//! Follows MPD's `lame` encoder: `quality` selects VBR on LAME's `-V` scale
+//! (0 best … 9 smallest), `bitrate` selects constant bitrate in kbit/s and is
+//! rounded to the nearest MPEG bitrate. Without either, LAME's default VBR
+//! quality (4) is used. MP3 carries mono or stereo at up to 48 kHz; faster
+//! streams are resampled first.
+
+use crate::conversion::f32_to_i16;
+use crate::encoder::{Encoder, EncoderPlugin, EncoderSettings};
+use crate::resampler::StreamResampler;
+use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, InterleavedPcm, MonoPcm, Quality, VbrMode};
+use rmpd_core::config::ResamplerQuality;
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::AudioFormat;
+
+/// LAME's default VBR quality (`-V 4`).
+const DEFAULT_QUALITY: f32 = 4.0;
+
+fn lame_factory(format: AudioFormat, settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    Ok(Box::new(LameEncoder::new(format, *settings)?))
+}
+
+pub static LAME_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "lame",
+    aliases: &["mp3"],
+    quality: Some(0.0..=9.0),
+    bitrate: Some(8..=320),
+    factory: lame_factory,
+};
+
+/// The MPEG bitrates LAME encodes at, in kbit/s.
+const BITRATES: [(u32, Bitrate); 16] = [
+    (8, Bitrate::Kbps8),
+    (16, Bitrate::Kbps16),
+    (24, Bitrate::Kbps24),
+    (32, Bitrate::Kbps32),
+    (40, Bitrate::Kbps40),
+    (48, Bitrate::Kbps48),
+    (64, Bitrate::Kbps64),
+    (80, Bitrate::Kbps80),
+    (96, Bitrate::Kbps96),
+    (112, Bitrate::Kbps112),
+    (128, Bitrate::Kbps128),
+    (160, Bitrate::Kbps160),
+    (192, Bitrate::Kbps192),
+    (224, Bitrate::Kbps224),
+    (256, Bitrate::Kbps256),
+    (320, Bitrate::Kbps320),
+];
+
+/// The MPEG bitrate closest to `kbps`.
+fn nearest_bitrate(kbps: u32) -> Bitrate {
+    BITRATES
+        .iter()
+        .min_by_key(|(rate, _)| rate.abs_diff(kbps))
+        .map_or(Bitrate::Kbps128, |&(_, bitrate)| bitrate)
+}
+
+/// LAME's VBR quality for an MPD `quality` value, rounded to `-V 0 … 9`.
+fn vbr_quality(quality: f32) -> Quality {
+    match quality.round() as i32 {
+        ..=0 => Quality::Best,
+        1 => Quality::SecondBest,
+        2 => Quality::NearBest,
+        3 => Quality::VeryNice,
+        4 => Quality::Nice,
+        5 => Quality::Good,
+        6 => Quality::Decent,
+        7 => Quality::Ok,
+        8 => Quality::SecondWorst,
+        _ => Quality::Worst,
+    }
+}
+
+/// The rate LAME is fed `rate` at: unchanged up to 48 kHz, otherwise the
+/// 44.1 or 48 kHz family member it divides into.
+fn lame_rate(rate: u32) -> u32 {
+    match rate {
+        ..=48_000 => rate,
+        _ if rate % 44_100 == 0 => 44_100,
+        _ => 48_000,
+    }
+}
+
+/// MPEG-1/2 Layer III stream of interleaved f32 input.
+///
+/// MP3 frames are self-describing, so there is no stream header.
+/// [`Encoder::finish`] flushes LAME's buffered frames and opens a fresh
+/// encoder, so the output can be started again.
+pub struct LameEncoder {
+    format: AudioFormat,
+    settings: EncoderSettings,
+    encoder: mp3lame_encoder::Encoder,
+    /// Converts rates above 48 kHz; `None` when LAME takes the input as is.
+    resampler: Option<StreamResampler>,
+}
+
+impl LameEncoder {
+    pub fn new(format: AudioFormat, settings: EncoderSettings) -> Result<Self> {
+        if !(1..=2).contains(&format.channels) {
+            return Err(RmpdError::Player(format!(
+                "lame encoder supports 1-2 channels, got {}",
+                format.channels
+            )));
+        }
+        let rate = lame_rate(format.sample_rate);
+        let resampler = if rate == format.sample_rate {
+            None
+        } else {
+            Some(
+                StreamResampler::new(
+                    format.sample_rate,
+                    rate,
+                    usize::from(format.channels),
+                    ResamplerQuality::default(),
+                )
+                .ok_or_else(|| {
+                    RmpdError::Player(format!(
+                        "lame encoder: cannot resample {} Hz to {rate} Hz",
+                        format.sample_rate
+                    ))
+                })?,
+            )
+        };
+
+        let mut builder = Builder::new()
+            .ok_or_else(|| RmpdError::Player("lame encoder: cannot allocate LAME".into()))?;
+        builder
+            .set_num_channels(format.channels)
+            .map_err(lame_error)?;
+        builder.set_sample_rate(rate).map_err(lame_error)?;
+        match settings.bitrate {
+            Some(kbps) => {
+                builder.set_vbr_mode(VbrMode::Off).map_err(lame_error)?;
+                builder
+                    .set_brate(nearest_bitrate(kbps))
+                    .map_err(lame_error)?;
+            }
+            None => {
+                builder.set_vbr_mode(VbrMode::Mtrh).map_err(lame_error)?;
+                builder
+                    .set_vbr_quality(vbr_quality(settings.quality.unwrap_or(DEFAULT_QUALITY)))
+                    .map_err(lame_error)?;
+            }
+        }
+        let encoder = builder.build().map_err(lame_error)?;
+        Ok(Self {
+            format,
+            settings,
+            encoder,
+            resampler,
+        })
+    }
+}
+
+fn lame_error(e: impl std::fmt::Display) -> RmpdError {
+    RmpdError::Player(format!("lame encoder: {e}"))
+}
+
+impl Encoder for LameEncoder {
+    fn content_type(&self) -> &str {
+        "audio/mpeg"
+    }
+
+    fn header(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        let pcm: Vec<i16> = match &mut self.resampler {
+            Some(resampler) => resampler
+                .process(samples)
+                .into_iter()
+                .map(f32_to_i16)
+                .collect(),
+            None => samples.iter().map(|&s| f32_to_i16(s)).collect(),
+        };
+        let mut out = Vec::new();
+        let result = if self.format.channels == 1 {
+            self.encoder.encode_to_vec(MonoPcm(&pcm), &mut out)
+        } else {
+            self.encoder.encode_to_vec(InterleavedPcm(&pcm), &mut out)
+        };
+        if let Err(e) = result {
+            tracing::warn!("lame encoder: {e}");
+        }
+        out
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Err(e) = self.encoder.flush_to_vec::<FlushNoGap>(&mut out) {
+            tracing::warn!("lame encoder: {e}");
+        }
+        match Self::new(self.format, self.settings) {
+            Ok(fresh) => *self = fresh,
+            Err(e) => tracing::warn!("{e}"),
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitrates_round_to_mpeg_rates() {
+        assert!(matches!(nearest_bitrate(128), Bitrate::Kbps128));
+        assert!(matches!(nearest_bitrate(150), Bitrate::Kbps160));
+        assert!(matches!(nearest_bitrate(1000), Bitrate::Kbps320));
+    }
+
+    #[test]
+    fn fast_rates_are_fed_at_a_family_rate() {
+        assert_eq!(lame_rate(44_100), 44_100);
+        assert_eq!(lame_rate(88_200), 44_100);
+        assert_eq!(lame_rate(96_000), 48_000);
+    }
+
+    #[test]
+    fn lame_stream_starts_with_a_frame_sync() {
+        let format = AudioFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let mut enc = LameEncoder::new(format, EncoderSettings::default()).unwrap();
+        let input: Vec<f32> = (0..2 * 44100)
+            .map(|i| ((i / 2) as f32 * 0.05).sin() * 0.5)
+            .collect();
+        let mut body = enc.encode(&input);
+        body.extend(enc.finish());
+        assert!(body.len() > 4);
+        assert!(body.len() < input.len() * 2, "mp3 must compress");
+        let sync = body
+            .windows(2)
+            .position(|w| w[0] == 0xFF && w[1] & 0xE0 == 0xE0);
+        assert!(sync.is_some(), "no MPEG frame sync in the output");
+    }
+}
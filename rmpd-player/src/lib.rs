@@ -8,11 +8,19 @@ pub mod dop;
 pub mod dop_output;
 pub mod encoder;
 pub mod engine;
+#[cfg(feature = "ffmpeg")]
+pub mod ffmpeg_decoder;
 pub mod fifo_output;
 pub mod filter;
+pub mod gapless;
+pub mod hardware_mixer;
 pub mod httpd_output;
+#[cfg(feature = "lame")]
+pub mod lame_encoder;
 pub mod multi_output;
 pub mod null_output;
+#[cfg(feature = "opus")]
+pub mod opus_encoder;
 pub mod output;
 pub mod output_registry;
 pub mod output_slot;
@@ -21,21 +29,37 @@ pub mod pipe_output;
 pub mod pipewire_output;
 pub mod recorder_output;
 pub mod resampler;
+pub mod stats;
+#[cfg(feature = "vorbis")]
+pub mod vorbis_encoder;
 
 pub use cpal_utils::set_output_device;
 pub use decoder::{
-    DECODER_PLUGINS, Decoder, DecoderPlugin, SymphoniaDecoder, decoder_for_suffix,
-    is_supported_suffix,
+    DECODER_PLUGINS, Decoder, DecoderPlugin, SongDecoder, SymphoniaDecoder, decoder_for_suffix,
+    is_supported_suffix, open_decoder,
 };
 pub use dop::DopEncoder;
-pub use encoder::{Encoder, PcmEncoder, WavEncoder};
+pub use encoder::{
+    ENCODER_PLUGINS, Encoder, EncoderPlugin, EncoderSettings, FlacEncoder, PcmEncoder, WavEncoder,
+    create_encoder, encoder_plugin, validate_encoder,
+};
 pub use engine::PlaybackEngine;
+#[cfg(feature = "ffmpeg")]
+pub use ffmpeg_decoder::{FfmpegDecoder, ffmpeg_available};
 pub use filter::{AudioFilter, FilterChain, Mixer, SoftwareMixer, VolumeFilter};
 pub use httpd_output::HttpdOutput;
+#[cfg(feature = "lame")]
+pub use lame_encoder::LameEncoder;
 pub use multi_output::MultiOutput;
 pub use null_output::NullOutput;
+#[cfg(feature = "opus")]
+pub use opus_encoder::OpusEncoder;
 pub use output::CpalOutput;
-pub use output_registry::{OUTPUT_PLUGINS, create_output};
+pub use output_registry::{
+    OUTPUT_PLUGINS, OutputParams, OutputPlugin, create_output, output_plugin, validate_output,
+};
 pub use output_slot::{OutputKey, OutputSlot};
 #[cfg(all(feature = "pipewire", target_os = "linux"))]
 pub use pipewire_output::PipeWireOutput;
+#[cfg(feature = "vorbis")]
+pub use vorbis_encoder::VorbisEncoder;
@@ -23,6 +23,12 @@
 //! than in real time. The `Pause`/`Resume` enum messages still flow through
 //! for the hardware-level `AudioOutput::pause`/`resume` call (device state),
 //! but the audible effect no longer waits on their queue position.
+//!
+//! ## Draining
+//!
+//! [`MultiOutput::drain`] is the exception: used when playback ends naturally,
+//! it queues a `Drain` behind the remaining chunks so they ARE played, and
+//! waits for the primary to report that its device has played them out.
 
 use crate::audio_output::AudioOutput;
 use crate::filter::{AudioFilter, VolumeFilter};
@@ -31,12 +37,15 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::mpsc::{SyncSender, sync_channel};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use tracing::{debug, warn};
 
 enum OutputMsg {
     Samples(Arc<[f32]>),
     Pause,
     Resume,
+    /// Play out everything queued before it, then acknowledge.
+    Drain(SyncSender<()>),
     Stop,
 }
 
@@ -115,6 +124,14 @@ impl MultiOutput {
                             Ok(OutputMsg::Resume) => {
                                 let _ = out.resume();
                             }
+                            Ok(OutputMsg::Drain(ack)) => {
+                                if worker_active.load(Ordering::Acquire)
+                                    && let Err(e) = out.drain()
+                                {
+                                    warn!("output drain failed: {}", e);
+                                }
+                                let _ = ack.try_send(());
+                            }
                             Ok(OutputMsg::Stop) => {
                                 let _ = out.stop();
                                 break;
@@ -176,6 +193,27 @@ impl MultiOutput {
         }
     }
 
+    /// Play out everything already written, then return.
+    ///
+    /// Called when a song ends naturally, before the output is torn down, so
+    /// the last fraction of a second reaches the speakers. Waits for the
+    /// primary only (bounded by `timeout`); secondaries drain on a best-effort
+    /// basis. A paused output is not drained.
+    pub fn drain(&self, timeout: Duration) {
+        for w in &self.workers {
+            let (ack_tx, ack_rx) = sync_channel(1);
+            if w.primary {
+                if w.tx.send(OutputMsg::Drain(ack_tx)).is_ok()
+                    && ack_rx.recv_timeout(timeout).is_err()
+                {
+                    warn!("primary output did not drain within {:?}", timeout);
+                }
+            } else {
+                let _ = w.tx.try_send(OutputMsg::Drain(ack_tx));
+            }
+        }
+    }
+
     /// Send `Stop` to all workers and join cleanly.
     ///
     /// The primary is joined so the caller knows it has fully drained.
@@ -411,4 +449,41 @@ mod tests {
 
         multi.stop();
     }
+
+    /// Draining must play the queued backlog (not discard it like pause and
+    /// stop do) and only return once the primary has written all of it.
+    #[test]
+    fn drain_plays_out_queued_backlog() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let depth = 8;
+
+        let primary = SlowOutput {
+            count: Arc::clone(&count),
+            delay: Duration::from_millis(10),
+            state: PauseState::new(),
+        };
+
+        let multi = MultiOutput::spawn(
+            vec![Box::new(primary)],
+            depth,
+            Arc::new(std::sync::atomic::AtomicU8::new(100)),
+        )
+        .expect("spawn failed");
+
+        let chunk: Arc<[f32]> = Arc::from(vec![0.0f32; 64].as_slice());
+        for _ in 0..depth {
+            multi
+                .write(Arc::clone(&chunk))
+                .expect("write must not fail");
+        }
+        multi.drain(Duration::from_secs(5));
+
+        assert_eq!(
+            count.load(Ordering::SeqCst),
+            depth,
+            "drain must return only after every queued chunk was played"
+        );
+
+        multi.stop();
+    }
 }
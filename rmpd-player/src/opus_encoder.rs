@@ -0,0 +1,383 @@
+//! Ogg Opus encoder backed by libopus (through the `opus` crate).
+//!
+//! Follows MPD's `opus` encoder: `bitrate` sets the target in kbit/s and
+//! leaves libopus to pick one otherwise; there is no `quality` setting. Opus
+//! always runs at 48 kHz, so other rates are resampled first, and it carries
+//! mono or stereo only. The Ogg framing (RFC 7845) is written here.
+
+use crate::encoder::{Encoder, EncoderPlugin, EncoderSettings};
+use crate::resampler::StreamResampler;
+use rmpd_core::config::ResamplerQuality;
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::AudioFormat;
+
+/// Opus' internal rate, which Ogg Opus granule positions count in.
+const OPUS_RATE: u32 = 48_000;
+
+/// Frames per Opus packet: 20 ms, libopus' default.
+const PACKET_FRAMES: usize = OPUS_RATE as usize / 50;
+
+/// Largest packet libopus produces (RFC 6716 §3.2.1 recommends this bound).
+const MAX_PACKET: usize = 4000;
+
+fn opus_factory(format: AudioFormat, settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    Ok(Box::new(OpusEncoder::new(format, *settings)?))
+}
+
+pub static OPUS_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "opus",
+    aliases: &[],
+    quality: None,
+    bitrate: Some(6..=510),
+    factory: opus_factory,
+};
+
+/// Ogg Opus stream of interleaved f32 input.
+///
+/// The `OpusHead` and `OpusTags` pages make up [`Encoder::header`]. Each
+/// [`Encoder::encode`] call returns the pages of the packets it completed;
+/// [`Encoder::finish`] pads and emits the last packet, marks the end of the
+/// logical stream and opens a fresh one, so the output can be started again.
+pub struct OpusEncoder {
+    format: AudioFormat,
+    settings: EncoderSettings,
+    encoder: opus::Encoder,
+    /// Converts to 48 kHz; `None` when the input already is.
+    resampler: Option<StreamResampler>,
+    ogg: OggWriter,
+    header: Vec<u8>,
+    /// Encoder lookahead at 48 kHz, which decoders drop.
+    pre_skip: u64,
+    /// Interleaved 48 kHz samples not yet making up a whole packet.
+    pending: Vec<f32>,
+    /// Input frames received, at the input rate.
+    input_frames: u64,
+    /// 48 kHz frames in the packets emitted so far.
+    granule: u64,
+}
+
+impl OpusEncoder {
+    pub fn new(format: AudioFormat, settings: EncoderSettings) -> Result<Self> {
+        let channels = match format.channels {
+            1 => opus::Channels::Mono,
+            2 => opus::Channels::Stereo,
+            n => {
+                return Err(RmpdError::Player(format!(
+                    "opus encoder supports 1-2 channels, got {n}"
+                )));
+            }
+        };
+        let mut encoder = opus::Encoder::new(OPUS_RATE, channels, opus::Application::Audio)
+            .map_err(opus_error)?;
+        if let Some(kbps) = settings.bitrate {
+            let bits = i32::try_from(kbps.saturating_mul(1000)).unwrap_or(i32::MAX);
+            encoder
+                .set_bitrate(opus::Bitrate::Bits(bits))
+                .map_err(opus_error)?;
+        }
+        let pre_skip = encoder.get_lookahead().map_err(opus_error)?;
+
+        let resampler = if format.sample_rate == OPUS_RATE {
+            None
+        } else {
+            Some(
+                StreamResampler::new(
+                    format.sample_rate,
+                    OPUS_RATE,
+                    usize::from(format.channels),
+                    ResamplerQuality::default(),
+                )
+                .ok_or_else(|| {
+                    RmpdError::Player(format!(
+                        "opus encoder: cannot resample {} Hz to {OPUS_RATE} Hz",
+                        format.sample_rate
+                    ))
+                })?,
+            )
+        };
+
+        let mut ogg = OggWriter::new();
+        let pre_skip = u16::try_from(pre_skip).unwrap_or(0);
+        let mut header = ogg.pages(&[(opus_head(format, pre_skip), 0)], BOS);
+        header.extend(ogg.pages(&[(opus_tags(), 0)], 0));
+        Ok(Self {
+            format,
+            settings,
+            encoder,
+            resampler,
+            ogg,
+            header,
+            pre_skip: u64::from(pre_skip),
+            pending: Vec::new(),
+            input_frames: 0,
+            granule: 0,
+        })
+    }
+
+    /// Encode the whole packets in `pending`, each with the granule position
+    /// it ends at.
+    fn packets(&mut self) -> Vec<(Vec<u8>, u64)> {
+        let packet_len = PACKET_FRAMES * usize::from(self.format.channels);
+        let mut packets = Vec::new();
+        let mut buf = [0u8; MAX_PACKET];
+        while self.pending.len() >= packet_len {
+            let frame: Vec<f32> = self.pending.drain(..packet_len).collect();
+            self.granule += PACKET_FRAMES as u64;
+            match self.encoder.encode_float(&frame, &mut buf) {
+                Ok(n) => packets.push((buf[..n].to_vec(), self.granule)),
+                Err(e) => tracing::warn!("opus encoder: {e}"),
+            }
+        }
+        packets
+    }
+}
+
+fn opus_error(e: opus::Error) -> RmpdError {
+    RmpdError::Player(format!("opus encoder: {e}"))
+}
+
+/// The `OpusHead` identification header (RFC 7845 §5.1).
+fn opus_head(format: AudioFormat, pre_skip: u16) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(format.channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&format.sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family: mono/stereo
+    head
+}
+
+/// The `OpusTags` comment header (RFC 7845 §5.2), without comments.
+fn opus_tags() -> Vec<u8> {
+    const VENDOR: &[u8] = concat!("rmpd ", env!("CARGO_PKG_VERSION")).as_bytes();
+    let mut tags = Vec::with_capacity(16 + VENDOR.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    tags.extend_from_slice(VENDOR);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // user comments
+    tags
+}
+
+impl Encoder for OpusEncoder {
+    fn content_type(&self) -> &str {
+        "audio/ogg"
+    }
+
+    fn header(&self) -> Vec<u8> {
+        self.header.clone()
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        self.input_frames += (samples.len() / usize::from(self.format.channels)) as u64;
+        match &mut self.resampler {
+            Some(resampler) => self.pending.extend(resampler.process(samples)),
+            None => self.pending.extend_from_slice(samples),
+        }
+        let packets = self.packets();
+        self.ogg.pages(&packets, 0)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let channels = usize::from(self.format.channels);
+        // Push the resampler's last partial chunk through with silence.
+        if let Some(resampler) = &mut self.resampler {
+            let flush = vec![0.0; 1024 * channels];
+            self.pending.extend(resampler.process(&flush));
+        }
+        // Pad to whole packets, and to at least one once there was input so
+        // the stream gets its end-of-stream page.
+        let packet_len = PACKET_FRAMES * channels;
+        let count = self
+            .pending
+            .len()
+            .div_ceil(packet_len)
+            .max(usize::from(self.input_frames > 0));
+        self.pending.resize(count * packet_len, 0.0);
+        let before = self.granule;
+        let mut packets = self.packets();
+
+        // The end granule trims the padding: it counts the pre-skip plus the
+        // real input at 48 kHz, but can only cut into this last page.
+        let end = self.pre_skip
+            + self.input_frames * u64::from(OPUS_RATE) / u64::from(self.format.sample_rate);
+        if let Some((_, granule)) = packets.last_mut() {
+            *granule = end.clamp(before, *granule);
+        }
+        let tail = if packets.is_empty() {
+            Vec::new()
+        } else {
+            self.ogg.pages(&packets, EOS)
+        };
+
+        match Self::new(self.format, self.settings) {
+            Ok(fresh) => *self = fresh,
+            Err(e) => tracing::warn!("{e}"),
+        }
+        tail
+    }
+}
+
+// ──────────────────────────────────────────────────────────────────────────────
+// Ogg pages
+// ──────────────────────────────────────────────────────────────────────────────
+
+/// Page header flag: first page of a logical stream.
+const BOS: u8 = 0x02;
+/// Page header flag: last page of a logical stream.
+const EOS: u8 = 0x04;
+/// Page header flag: the page starts with the rest of a packet.
+const CONTINUED: u8 = 0x01;
+
+/// Most lacing values (255-byte segments) one page can hold.
+const MAX_SEGMENTS: usize = 255;
+
+/// Packs packets into the pages of one logical Ogg stream.
+struct OggWriter {
+    serial: u32,
+    sequence: u32,
+}
+
+impl OggWriter {
+    fn new() -> Self {
+        // Serial numbers only need to differ between chained streams.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        Self {
+            serial: nanos ^ std::process::id().rotate_left(16),
+            sequence: 0,
+        }
+    }
+
+    /// Pages holding `packets`, each with the granule position it ends at.
+    /// `flags` (`BOS` / `EOS`) go on the first / last page respectively.
+    fn pages(&mut self, packets: &[(Vec<u8>, u64)], flags: u8) -> Vec<u8> {
+        // Lacing values, each with the granule of the packet it ends.
+        let mut segments: Vec<(u8, Option<u64>)> = Vec::new();
+        let mut data = Vec::new();
+        for (packet, granule) in packets {
+            let full = packet.len() / 255;
+            segments.extend(std::iter::repeat_n((255, None), full));
+            segments.push(((packet.len() % 255) as u8, Some(*granule)));
+            data.extend_from_slice(packet);
+        }
+
+        let mut out = Vec::new();
+        let mut offset = 0;
+        let mut continued = false;
+        let pages = segments.len().div_ceil(MAX_SEGMENTS);
+        for (i, lacing) in segments.chunks(MAX_SEGMENTS).enumerate() {
+            let mut header_type = if continued { CONTINUED } else { 0 };
+            if i == 0 {
+                header_type |= flags & BOS;
+            }
+            if i + 1 == pages {
+                header_type |= flags & EOS;
+            }
+            // -1: no packet ends on this page.
+            let granule = lacing
+                .iter()
+                .rev()
+                .find_map(|&(_, g)| g)
+                .map_or(-1, |g| g as i64);
+            let len: usize = lacing.iter().map(|&(v, _)| usize::from(v)).sum();
+
+            let start = out.len();
+            out.extend_from_slice(b"OggS");
+            out.push(0); // version
+            out.push(header_type);
+            out.extend_from_slice(&granule.to_le_bytes());
+            out.extend_from_slice(&self.serial.to_le_bytes());
+            out.extend_from_slice(&self.sequence.to_le_bytes());
+            out.extend_from_slice(&[0; 4]); // CRC, filled in below
+            out.push(lacing.len() as u8);
+            out.extend(lacing.iter().map(|&(v, _)| v));
+            out.extend_from_slice(&data[offset..offset + len]);
+            let crc = ogg_crc(&out[start..]);
+            out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+
+            offset += len;
+            continued = lacing.last().is_some_and(|&(_, g)| g.is_none());
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+        out
+    }
+}
+
+/// CRC-32 of an Ogg page (polynomial 0x04C11DB7, init 0, unreflected).
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |mut crc, &b| {
+        crc ^= u32::from(b) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ogg_crc_check_value() {
+        // CRC-32/CKSUM without its final XOR.
+        assert_eq!(ogg_crc(b""), 0);
+        assert_eq!(ogg_crc(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn long_packets_span_pages() {
+        let mut ogg = OggWriter::new();
+        let packet = vec![7u8; 255 * 300];
+        let bytes = ogg.pages(&[(packet, 960)], EOS);
+        assert_eq!(ogg.sequence, 2);
+        assert_eq!(&bytes[..4], b"OggS");
+        // First page: 255 full segments, no packet ends, so granule -1.
+        assert_eq!(bytes[5], 0);
+        assert_eq!(i64::from_le_bytes(bytes[6..14].try_into().unwrap()), -1);
+        let second = 27 + 255 + 255 * 255;
+        assert_eq!(&bytes[second..second + 4], b"OggS");
+        assert_eq!(bytes[second + 5], CONTINUED | EOS);
+        assert_eq!(
+            i64::from_le_bytes(bytes[second + 6..second + 14].try_into().unwrap()),
+            960
+        );
+    }
+
+    #[test]
+    fn opus_stream_has_head_and_tags() {
+        let format = AudioFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let mut enc = OpusEncoder::new(format, EncoderSettings::default()).unwrap();
+        let header = enc.header();
+        assert_eq!(&header[..4], b"OggS");
+        assert_eq!(&header[28..36], b"OpusHead");
+        let input: Vec<f32> = (0..2 * 44100)
+            .map(|i| ((i / 2) as f32 * 0.05).sin() * 0.5)
+            .collect();
+        let mut body = enc.encode(&input);
+        body.extend(enc.finish());
+        assert!(!body.is_empty());
+        assert!(body.len() < input.len() * 2, "opus must compress");
+    }
+
+    #[test]
+    fn opus_rejects_surround() {
+        let format = AudioFormat {
+            sample_rate: 48000,
+            channels: 6,
+            bits_per_sample: 16,
+        };
+        assert!(OpusEncoder::new(format, EncoderSettings::default()).is_err());
+    }
+}
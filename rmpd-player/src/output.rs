@@ -1,5 +1,5 @@
 use crate::audio_output::{AudioOutput, PauseState};
-use crate::conversion::{self, SampleBuffer};
+use crate::conversion::{self, Backlog, SampleBuffer};
 use crate::cpal_utils::CpalDeviceConfig;
 use crate::resampler::StreamResampler;
 use cpal::traits::{DeviceTrait, StreamTrait};
@@ -8,6 +8,7 @@ use rmpd_core::config::ResamplerQuality;
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::AudioFormat;
 use std::sync::mpsc::{SyncSender, sync_channel};
+use std::time::Duration;
 
 pub struct CpalOutput {
     device: Device,
@@ -18,6 +19,8 @@ pub struct CpalOutput {
     resampler: Option<StreamResampler>,
     /// Output buffer time in milliseconds; sizes the sync-channel depth.
     buffer_time_ms: u32,
+    /// Samples sent to the device callback but not yet played; see `drain`.
+    backlog: Backlog,
 }
 
 impl CpalOutput {
@@ -89,6 +92,7 @@ impl CpalOutput {
             pause_state: PauseState::new(),
             resampler,
             buffer_time_ms,
+            backlog: Backlog::new(),
         })
     }
 
@@ -114,6 +118,7 @@ impl CpalOutput {
             pause_state: PauseState::new(),
             resampler: None,
             buffer_time_ms,
+            backlog: Backlog::new(),
         })
     }
 
@@ -128,6 +133,7 @@ impl CpalOutput {
             pause_state: PauseState::new(),
             resampler: None,
             buffer_time_ms,
+            backlog: Backlog::new(),
         })
     }
 
@@ -162,7 +168,7 @@ impl CpalOutput {
 
         let stream = match sample_format {
             SampleFormat::F32 => {
-                let mut buf = SampleBuffer::new(rx);
+                let mut buf = SampleBuffer::with_backlog(rx, self.backlog.clone());
                 self.device
                     .build_output_stream(
                         self.config,
@@ -179,7 +185,7 @@ impl CpalOutput {
                     .map_err(|e| RmpdError::Player(format!("Failed to build F32 stream: {e}")))?
             }
             SampleFormat::I16 => {
-                let mut buf = SampleBuffer::new(rx);
+                let mut buf = SampleBuffer::with_backlog(rx, self.backlog.clone());
                 self.device
                     .build_output_stream(
                         self.config,
@@ -196,7 +202,7 @@ impl CpalOutput {
                     .map_err(|e| RmpdError::Player(format!("Failed to build I16 stream: {e}")))?
             }
             SampleFormat::I32 => {
-                let mut buf = SampleBuffer::new(rx);
+                let mut buf = SampleBuffer::with_backlog(rx, self.backlog.clone());
                 self.device
                     .build_output_stream(
                         self.config,
@@ -252,6 +258,7 @@ impl CpalOutput {
         match self.sample_sender {
             Some(ref sender) => {
                 if n > 0 {
+                    self.backlog.add(n);
                     sender.send(out).map_err(|_| {
                         RmpdError::Player("Failed to send samples to output".to_owned())
                     })?;
@@ -282,11 +289,29 @@ impl CpalOutput {
         Ok(())
     }
 
+    /// Wait for the device to play everything already written, so the tail
+    /// of the last song is not cut off when the stream is dropped.
+    pub fn drain(&mut self) -> Result<()> {
+        if self.stream.is_none() || self.pause_state.is_paused() {
+            return Ok(());
+        }
+        // Bounded: a device that stopped pulling data must not hang playback.
+        let timeout = Duration::from_millis(u64::from(self.buffer_time_ms) * 2 + 500);
+        if !self.backlog.wait_empty(timeout) {
+            tracing::warn!(
+                "pcm output drain timed out with {} samples unplayed",
+                self.backlog.pending()
+            );
+        }
+        Ok(())
+    }
+
     pub fn stop(&mut self) -> Result<()> {
         if let Some(stream) = self.stream.take() {
             drop(stream);
         }
         self.sample_sender = None;
+        self.backlog.reset();
         self.pause_state.set_paused(false);
         Ok(())
     }
@@ -309,6 +334,9 @@ impl AudioOutput for CpalOutput {
     fn write(&mut self, samples: &[f32]) -> rmpd_core::error::Result<()> {
         CpalOutput::write(self, samples).map(|_| ())
     }
+    fn drain(&mut self) -> rmpd_core::error::Result<()> {
+        CpalOutput::drain(self)
+    }
     fn stop(&mut self) -> rmpd_core::error::Result<()> {
         CpalOutput::stop(self)
     }
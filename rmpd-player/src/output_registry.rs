@@ -1,9 +1,15 @@
 //! MPD-faithful audio output registry.
 //!
-//! Maps an `OutputConfig.output_type` string to a factory function that
-//! constructs the appropriate `AudioOutput` backend.
+//! Every output backend is described by a static [`OutputPlugin`]: its
+//! canonical name (reported by the `outputs` command), any aliases accepted in
+//! `[[output]] type = "…"`, the config keys it reads, and a factory that builds
+//! the [`AudioOutput`]. The engine only ever goes through [`create_output`], so
+//! adding a backend means writing the `AudioOutput` impl, declaring its
+//! `OutputPlugin` here and listing it in [`OUTPUT_PLUGINS`] — nothing in
+//! `engine.rs` needs to change.
 
 use crate::audio_output::AudioOutput;
+use crate::encoder::{create_encoder, validate_encoder};
 use crate::fifo_output::FifoOutput;
 use crate::httpd_output::HttpdOutput;
 use crate::null_output::NullOutput;
@@ -14,32 +20,92 @@ use rmpd_core::config::{OutputConfig, ResamplerQuality};
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::AudioFormat;
 
-pub type OutputFactory =
-    fn(AudioFormat, ResamplerQuality, &OutputConfig) -> Result<Box<dyn AudioOutput>>;
+/// Per-stream parameters handed to every output factory, alongside the
+/// `[[output]]` block the output was configured from.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputParams {
+    /// Format of the decoded PCM the output will receive.
+    pub format: AudioFormat,
+    /// Resampler quality for outputs that must convert to a device rate.
+    pub quality: ResamplerQuality,
+    /// `[audio].buffer_time` in milliseconds.
+    pub buffer_time_ms: u32,
+    /// Device rate to open at for DSD-to-PCM streams (see `engine.rs`);
+    /// `None` opens at `format.sample_rate`. Only cpal honors it.
+    pub dsd_target_rate: Option<u32>,
+}
+
+/// Builds an output from the stream parameters and its `[[output]]` block.
+/// Must not block on I/O beyond opening the device; `AudioOutput::start` is
+/// called later on the output's worker thread.
+pub type OutputFactory = fn(&OutputParams, &OutputConfig) -> Result<Box<dyn AudioOutput>>;
+
+/// Static descriptor for one output backend.
+pub struct OutputPlugin {
+    /// Canonical plugin name, reported as `plugin:` by the `outputs` command.
+    pub name: &'static str,
+    /// Additional `type` values that select this plugin.
+    pub aliases: &'static [&'static str],
+    /// `(key, description)` for every `[[output]]` setting the plugin reads.
+    /// Settings present in the config are reported as `attribute:` lines.
+    pub settings: &'static [(&'static str, &'static str)],
+    pub factory: OutputFactory,
+}
 
-// cpal_factory is kept for the OutputPlugins table but is not used by
-// create_output (which routes cpal directly to carry buffer_time_ms).
-fn cpal_factory(
-    format: AudioFormat,
-    quality: ResamplerQuality,
-    _cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
-    Ok(Box::new(CpalOutput::new(format, quality, 500)?))
+impl OutputPlugin {
+    /// Whether `output_type` (already lowercased) selects this plugin.
+    fn matches(&self, output_type: &str) -> bool {
+        self.name == output_type || self.aliases.contains(&output_type)
+    }
+}
+
+fn cpal_factory(params: &OutputParams, _cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    let out = match params.dsd_target_rate {
+        Some(rate) => CpalOutput::with_target_rate(
+            params.format,
+            params.quality,
+            params.buffer_time_ms,
+            rate,
+        )?,
+        None => CpalOutput::new(params.format, params.quality, params.buffer_time_ms)?,
+    };
+    Ok(Box::new(out))
 }
 
-fn null_factory(
-    _format: AudioFormat,
-    _quality: ResamplerQuality,
-    _cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
+/// System audio via cpal. When the native PipeWire backend isn't compiled in,
+/// a `type = "pipewire"` output is routed here so the config still plays.
+pub static CPAL_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "cpal",
+    aliases: &[
+        "default",
+        #[cfg(not(all(feature = "pipewire", target_os = "linux")))]
+        "pipewire",
+    ],
+    settings: &[
+        (
+            "device",
+            "output device id (ALSA PCM name); empty = system default",
+        ),
+        ("dop", "DSD over PCM: \"yes\" or \"no\""),
+        ("mixer_type", "\"software\" (default) or \"hardware\""),
+        ("mixer_device", "ALSA mixer device (default \"default\")"),
+        ("mixer_control", "ALSA mixer element (default \"PCM\")"),
+    ],
+    factory: cpal_factory,
+};
+
+fn null_factory(_params: &OutputParams, _cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
     Ok(Box::new(NullOutput::new()))
 }
 
-fn fifo_factory(
-    _format: AudioFormat,
-    _quality: ResamplerQuality,
-    cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
+pub static NULL_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "null",
+    aliases: &[],
+    settings: &[],
+    factory: null_factory,
+};
+
+fn fifo_factory(_params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
     let path = cfg
         .setting_str("path")
         .or_else(|| cfg.setting_str("fifo_path"))
@@ -49,122 +115,283 @@ fn fifo_factory(
     Ok(Box::new(FifoOutput::new(path)))
 }
 
-fn pipe_factory(
-    _format: AudioFormat,
-    _quality: ResamplerQuality,
-    cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
+pub static FIFO_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "fifo",
+    aliases: &[],
+    settings: &[
+        ("path", "named pipe to write s16le PCM to (required)"),
+        ("fifo_path", "alias for `path`"),
+    ],
+    factory: fifo_factory,
+};
+
+fn pipe_factory(_params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
     let command = cfg
         .setting_str("command")
         .ok_or_else(|| RmpdError::Player("pipe output requires a 'command' setting".into()))?;
     Ok(Box::new(PipeOutput::new(command)))
 }
 
-fn recorder_factory(
-    format: AudioFormat,
-    _quality: ResamplerQuality,
-    cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
+pub static PIPE_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "pipe",
+    aliases: &[],
+    settings: &[("command", "shell command fed s16le PCM on stdin (required)")],
+    factory: pipe_factory,
+};
+
+fn recorder_factory(params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
     let path = cfg
         .setting_str("path")
         .ok_or_else(|| RmpdError::Player("recorder output requires a 'path' setting".into()))?;
-    Ok(Box::new(RecorderOutput::new(path, format)))
+    let encoder = create_encoder(params.format, cfg, DEFAULT_ENCODER)?;
+    Ok(Box::new(RecorderOutput::new(path, encoder)))
 }
 
+/// Encoder of encoding outputs without an `encoder` setting.
+pub(crate) const DEFAULT_ENCODER: &str = "wave";
+
+/// `(key, description)` for the encoder settings shared by every encoding
+/// output (see [`crate::encoder::ENCODER_PLUGINS`]).
+const ENCODER_SETTINGS: [(&str, &str); 3] = [
+    (
+        "encoder",
+        "\"wave\" (default), \"flac\", \"pcm\", or when built in \"vorbis\", \"opus\" or \"lame\"",
+    ),
+    ("quality", "encoder VBR quality (exclusive with `bitrate`)"),
+    (
+        "bitrate",
+        "encoder bitrate in kbit/s (exclusive with `quality`)",
+    ),
+];
+
+pub static RECORDER_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "recorder",
+    aliases: &[],
+    settings: &[
+        ("path", "file to record to (required)"),
+        ENCODER_SETTINGS[0],
+        ENCODER_SETTINGS[1],
+        ENCODER_SETTINGS[2],
+    ],
+    factory: recorder_factory,
+};
+
+fn httpd_factory(params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    Ok(Box::new(HttpdOutput::new(params.format, cfg)?))
+}
+
+pub static HTTPD_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "httpd",
+    aliases: &[],
+    settings: &[
+        (
+            "bind_to_address",
+            "interface to listen on (default 0.0.0.0)",
+        ),
+        ("port", "TCP port (default 8000)"),
+        ENCODER_SETTINGS[0],
+        ENCODER_SETTINGS[1],
+        ENCODER_SETTINGS[2],
+        (
+            "tags",
+            "inject now-playing metadata for ICY clients (default yes)",
+        ),
+    ],
+    factory: httpd_factory,
+};
+
+#[cfg(all(feature = "pipewire", target_os = "linux"))]
+fn pipewire_factory(params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    // PipeWire owns the graph rate: we open at the decoded rate and let it
+    // follow/resample, so `dsd_target_rate` is intentionally ignored.
+    Ok(Box::new(crate::pipewire_output::PipeWireOutput::new(
+        params.format,
+        cfg,
+        params.buffer_time_ms,
+    )?))
+}
+
+#[cfg(all(feature = "pipewire", target_os = "linux"))]
+pub static PIPEWIRE_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "pipewire",
+    aliases: &[],
+    settings: &[],
+    factory: pipewire_factory,
+};
+
 #[cfg(feature = "jack")]
-fn jack_factory(
-    format: AudioFormat,
-    _quality: ResamplerQuality,
-    _cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
-    Ok(Box::new(CpalOutput::new_jack(format, 500)?))
+fn jack_factory(params: &OutputParams, _cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    Ok(Box::new(CpalOutput::new_jack(
+        params.format,
+        params.buffer_time_ms,
+    )?))
 }
 
+#[cfg(feature = "jack")]
+pub static JACK_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "jack",
+    aliases: &[],
+    settings: &[],
+    factory: jack_factory,
+};
+
 #[cfg(all(feature = "asio", target_os = "windows"))]
-fn asio_factory(
-    format: AudioFormat,
-    _quality: ResamplerQuality,
-    _cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
-    Ok(Box::new(CpalOutput::new_asio(format, 500)?))
-}
-
-fn httpd_factory(
-    format: AudioFormat,
-    _quality: ResamplerQuality,
-    cfg: &OutputConfig,
-) -> Result<Box<dyn AudioOutput>> {
-    Ok(Box::new(HttpdOutput::new(format, cfg)))
-}
-
-pub static OUTPUT_PLUGINS: &[(&str, OutputFactory)] = &[
-    ("cpal", cpal_factory),
-    ("default", cpal_factory),
-    ("null", null_factory),
-    ("fifo", fifo_factory),
-    ("pipe", pipe_factory),
-    ("recorder", recorder_factory),
+fn asio_factory(params: &OutputParams, _cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    Ok(Box::new(CpalOutput::new_asio(
+        params.format,
+        params.buffer_time_ms,
+    )?))
+}
+
+#[cfg(all(feature = "asio", target_os = "windows"))]
+pub static ASIO_OUTPUT: OutputPlugin = OutputPlugin {
+    name: "asio",
+    aliases: &[],
+    settings: &[],
+    factory: asio_factory,
+};
+
+/// All compiled-in output plugins (compile-time registry, MPD-style).
+pub static OUTPUT_PLUGINS: &[&OutputPlugin] = &[
+    &CPAL_OUTPUT,
+    &NULL_OUTPUT,
+    &FIFO_OUTPUT,
+    &PIPE_OUTPUT,
+    &RECORDER_OUTPUT,
+    &HTTPD_OUTPUT,
+    #[cfg(all(feature = "pipewire", target_os = "linux"))]
+    &PIPEWIRE_OUTPUT,
     #[cfg(feature = "jack")]
-    ("jack", jack_factory),
+    &JACK_OUTPUT,
     #[cfg(all(feature = "asio", target_os = "windows"))]
-    ("asio", asio_factory),
-    ("httpd", httpd_factory),
+    &ASIO_OUTPUT,
 ];
 
-pub fn create_output(
-    format: AudioFormat,
-    quality: ResamplerQuality,
-    cfg: &OutputConfig,
-    buffer_time_ms: u32,
-    dsd_target_rate: Option<u32>,
-) -> Result<Box<dyn AudioOutput>> {
-    let type_lower = cfg.output_type.to_lowercase();
-    // When the native PipeWire backend isn't compiled in, route a
-    // `type = "pipewire"` output through the default cpal device so the config
-    // still plays.
-    #[cfg(not(all(feature = "pipewire", target_os = "linux")))]
-    let type_lower = if type_lower == "pipewire" {
+/// Find the plugin selected by an `[[output]] type` value (case-insensitive,
+/// aliases included).
+#[must_use]
+pub fn output_plugin(output_type: &str) -> Option<&'static OutputPlugin> {
+    let ty = output_type.to_lowercase();
+    OUTPUT_PLUGINS.iter().copied().find(|p| p.matches(&ty))
+}
+
+/// Check the settings of `cfg` that can be checked before the output is
+/// opened, currently the encoder of encoding outputs. Unknown types are left
+/// to [`create_output`], since a config may name backends of other builds.
+pub fn validate_output(cfg: &OutputConfig) -> Result<()> {
+    match output_plugin(&cfg.output_type) {
+        Some(plugin) if plugin.settings.iter().any(|&(key, _)| key == "encoder") => {
+            validate_encoder(cfg, DEFAULT_ENCODER)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Build the output configured by `cfg` for a stream described by `params`.
+pub fn create_output(params: &OutputParams, cfg: &OutputConfig) -> Result<Box<dyn AudioOutput>> {
+    let plugin = output_plugin(&cfg.output_type).ok_or_else(|| {
+        RmpdError::Player(format!("unknown audio output type: {}", cfg.output_type))
+    })?;
+    if !plugin.name.eq_ignore_ascii_case(&cfg.output_type) {
         tracing::debug!(
-            "pipewire feature not built; routing output \"{}\" via the default cpal device",
-            cfg.name
+            "output \"{}\" (type \"{}\") served by the {} plugin",
+            cfg.name,
+            cfg.output_type,
+            plugin.name
         );
-        "default".to_owned()
-    } else {
-        type_lower
-    };
-    // Route cpal-family types directly so buffer_time_ms is forwarded.
-    match type_lower.as_str() {
-        "cpal" | "default" => {
-            let out = match dsd_target_rate {
-                Some(rate) => CpalOutput::with_target_rate(format, quality, buffer_time_ms, rate)?,
-                None => CpalOutput::new(format, quality, buffer_time_ms)?,
-            };
-            return Ok(Box::new(out));
+    }
+    (plugin.factory)(params, cfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> OutputParams {
+        OutputParams {
+            format: AudioFormat {
+                sample_rate: 44100,
+                channels: 2,
+                bits_per_sample: 16,
+            },
+            quality: ResamplerQuality::default(),
+            buffer_time_ms: 500,
+            dsd_target_rate: None,
         }
-        #[cfg(all(feature = "pipewire", target_os = "linux"))]
-        "pipewire" => {
-            // PipeWire owns the graph rate: we open at the decoded rate and let
-            // it follow/resample, so `dsd_target_rate` is intentionally ignored.
-            return Ok(Box::new(crate::pipewire_output::PipeWireOutput::new(
-                format,
-                cfg,
-                buffer_time_ms,
-            )?));
+    }
+
+    fn cfg(output_type: &str) -> OutputConfig {
+        OutputConfig {
+            output_type: output_type.to_owned(),
+            ..OutputConfig::cpal_default()
+        }
+    }
+
+    #[test]
+    fn lookup_is_case_insensitive_and_resolves_aliases() {
+        assert_eq!(output_plugin("HTTPD").map(|p| p.name), Some("httpd"));
+        assert_eq!(output_plugin("default").map(|p| p.name), Some("cpal"));
+        assert!(output_plugin("bogus").is_none());
+    }
+
+    #[test]
+    fn validation_checks_the_encoder_of_encoding_outputs() {
+        let mut httpd = cfg("httpd");
+        httpd
+            .settings
+            .insert("encoder".into(), toml::Value::String("bogus".into()));
+        assert!(validate_output(&httpd).is_err());
+        httpd
+            .settings
+            .insert("encoder".into(), toml::Value::String("flac".into()));
+        assert!(validate_output(&httpd).is_ok());
+        httpd
+            .settings
+            .insert("bitrate".into(), toml::Value::Integer(128));
+        assert!(validate_output(&httpd).is_err(), "flac has no bitrate");
+
+        let mut null = cfg("null");
+        null.settings
+            .insert("encoder".into(), toml::Value::String("bogus".into()));
+        assert!(validate_output(&null).is_ok(), "null encodes nothing");
+    }
+
+    #[test]
+    fn plugin_names_are_unique() {
+        for (i, a) in OUTPUT_PLUGINS.iter().enumerate() {
+            for b in &OUTPUT_PLUGINS[i + 1..] {
+                assert!(!b.matches(a.name), "{} shadowed by {}", a.name, b.name);
+                for alias in a.aliases {
+                    assert!(!b.matches(alias), "alias {alias} claimed twice");
+                }
+            }
         }
-        #[cfg(feature = "jack")]
-        "jack" => return Ok(Box::new(CpalOutput::new_jack(format, buffer_time_ms)?)),
-        #[cfg(all(feature = "asio", target_os = "windows"))]
-        "asio" => return Ok(Box::new(CpalOutput::new_asio(format, buffer_time_ms)?)),
-        _ => {}
     }
-    OUTPUT_PLUGINS
-        .iter()
-        .find(|(name, _)| *name == type_lower)
-        .map(|(_, factory)| factory(format, quality, cfg))
-        .unwrap_or_else(|| {
-            Err(RmpdError::Player(format!(
-                "unknown audio output type: {}",
-                cfg.output_type
-            )))
-        })
+
+    #[test]
+    fn create_output_builds_registered_plugin() {
+        assert!(create_output(&params(), &cfg("null")).is_ok());
+    }
+
+    #[test]
+    fn create_output_rejects_unknown_type() {
+        let err = create_output(&params(), &cfg("bogus"))
+            .err()
+            .expect("unknown type must fail");
+        assert!(err.to_string().contains("bogus"));
+    }
+
+    #[test]
+    fn unknown_encoder_fails_output_creation() {
+        let mut cfg = cfg("recorder");
+        cfg.settings
+            .insert("path".to_owned(), "/tmp/rmpd-test.wav".into());
+        cfg.settings.insert("encoder".to_owned(), "vorbis".into());
+        assert!(create_output(&params(), &cfg).is_err());
+    }
+
+    #[test]
+    fn missing_required_setting_is_reported() {
+        assert!(create_output(&params(), &cfg("pipe")).is_err());
+    }
 }
@@ -11,11 +11,22 @@
 //! the next decoder before EOS); that is a separate, larger change. This module
 //! delivers the device-persistence half, which removes the audible pop/gap of
 //! reopening the sound device between same-format tracks.
+//!
+//! When a track plays to its end the decode thread [`mark_finished`]s the slot.
+//! If the cached output is then torn down (format change, or `stop` because
+//! the queue ran out) it is drained first so its buffered tail is played; a
+//! user stop mid-track still tears down immediately.
+//!
+//! [`mark_finished`]: OutputSlot::mark_finished
 
 use crate::multi_output::MultiOutput;
 use parking_lot::Mutex;
 use rmpd_core::error::Result;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Upper bound on waiting for a finished output to play out its buffer.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Identifies an output configuration for reuse. Two tracks share a cached
 /// output only if their keys are equal.
@@ -31,6 +42,17 @@ pub struct OutputKey {
 struct Cached {
     key: OutputKey,
     multi: Arc<MultiOutput>,
+    /// The last track written to `multi` played to its end; drain before
+    /// tearing it down.
+    finished: bool,
+}
+
+impl Cached {
+    fn release(self) {
+        if self.finished {
+            self.multi.drain(DRAIN_TIMEOUT);
+        }
+    }
 }
 
 /// Caches one live [`MultiOutput`] for reuse across same-key tracks.
@@ -56,26 +78,42 @@ impl OutputSlot {
         build: impl FnOnce() -> Result<Arc<MultiOutput>>,
     ) -> Result<Arc<MultiOutput>> {
         let mut guard = self.inner.lock();
-        if let Some(cached) = guard.as_ref()
+        if let Some(cached) = guard.as_mut()
             && cached.key == key
         {
+            cached.finished = false;
             return Ok(cached.multi.clone());
         }
         // Miss: drop the old output first (its `Drop` joins the workers and
         // closes the device) so the new device opens cleanly, then build.
-        *guard = None;
+        if let Some(old) = guard.take() {
+            old.release();
+        }
         let multi = build()?;
         *guard = Some(Cached {
             key,
             multi: multi.clone(),
+            finished: false,
         });
         Ok(multi)
     }
 
-    /// Tear down the cached output. The device closes once the last user (e.g.
-    /// the decode thread) also drops its handle.
+    /// Record that the current track played to its end, so a following
+    /// teardown drains the output instead of discarding its buffer.
+    pub fn mark_finished(&self) {
+        if let Some(cached) = self.inner.lock().as_mut() {
+            cached.finished = true;
+        }
+    }
+
+    /// Tear down the cached output, draining it first if its track finished.
+    /// The device closes once the last user (e.g. the decode thread) also
+    /// drops its handle. May block for up to the output's buffer time.
     pub fn clear(&self) {
-        *self.inner.lock() = None;
+        let cached = self.inner.lock().take();
+        if let Some(cached) = cached {
+            cached.release();
+        }
     }
 
     #[cfg(test)]
@@ -87,6 +125,7 @@ impl OutputSlot {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio_output::{AudioOutput, PauseState};
     use crate::null_output::NullOutput;
     use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 
@@ -202,4 +241,89 @@ mod tests {
             "clear (e.g. on stop or DoP) must force a rebuild"
         );
     }
+
+    /// Counts chunks written, taking a little real time for each so an
+    /// undrained backlog is visibly discarded on teardown.
+    struct PacedOutput {
+        written: Arc<AtomicUsize>,
+        state: PauseState,
+    }
+
+    impl AudioOutput for PacedOutput {
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn write(&mut self, _samples: &[f32]) -> Result<()> {
+            std::thread::sleep(Duration::from_millis(10));
+            self.written.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn pause_state(&self) -> &PauseState {
+            &self.state
+        }
+        fn pause_state_mut(&mut self) -> &mut PauseState {
+            &mut self.state
+        }
+    }
+
+    /// Fill a paced output with `chunks` chunks, then tear it down with
+    /// `clear`, returning how many chunks it actually played.
+    fn played_before_clear(finished: bool, chunks: usize) -> usize {
+        let slot = OutputSlot::new();
+        let written = Arc::new(AtomicUsize::new(0));
+        let multi = slot
+            .acquire(key(44100, "paced|Out"), || {
+                Ok(Arc::new(MultiOutput::spawn(
+                    vec![Box::new(PacedOutput {
+                        written: written.clone(),
+                        state: PauseState::new(),
+                    })],
+                    chunks,
+                    Arc::new(AtomicU8::new(100)),
+                )?))
+            })
+            .unwrap();
+        let chunk: Arc<[f32]> = Arc::from(vec![0.0f32; 64].as_slice());
+        for _ in 0..chunks {
+            multi.write(chunk.clone()).unwrap();
+        }
+        drop(multi);
+        if finished {
+            slot.mark_finished();
+        }
+        slot.clear();
+        written.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn clear_drains_a_finished_output() {
+        assert_eq!(
+            played_before_clear(true, 8),
+            8,
+            "a track that ended naturally must play out its buffered tail"
+        );
+    }
+
+    #[test]
+    fn clear_discards_an_unfinished_output() {
+        assert!(
+            played_before_clear(false, 8) < 8,
+            "a user stop mid-track must not wait for the backlog"
+        );
+    }
+
+    #[test]
+    fn reuse_clears_the_finished_mark() {
+        let slot = OutputSlot::new();
+        let _ = slot.acquire(key(44100, "null|Out"), build_null).unwrap();
+        slot.mark_finished();
+        let _ = slot.acquire(key(44100, "null|Out"), build_null).unwrap();
+        assert!(
+            !slot.inner.lock().as_ref().unwrap().finished,
+            "the next track on the same output is not finished yet"
+        );
+    }
 }
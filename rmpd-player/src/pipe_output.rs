@@ -63,6 +63,14 @@ impl AudioOutput for PipeOutput {
         Ok(())
     }
 
+    fn drain(&mut self) -> Result<()> {
+        if let Some(w) = &mut self.stdin {
+            w.flush()
+                .map_err(|e| RmpdError::Player(format!("pipe flush error: {e}")))?;
+        }
+        Ok(())
+    }
+
     fn stop(&mut self) -> Result<()> {
         drop(self.stdin.take());
         if let Some(mut c) = self.child.take() {
@@ -26,7 +26,7 @@
 //!   [`std::sync::mpsc`] channel so `start()` can surface connection errors.
 
 use crate::audio_output::{AudioOutput, PauseState};
-use crate::conversion::SampleBuffer;
+use crate::conversion::{Backlog, SampleBuffer};
 use pipewire as pw;
 use pw::properties::properties;
 use rmpd_core::config::OutputConfig;
@@ -34,6 +34,7 @@ use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::song::AudioFormat;
 use std::sync::mpsc::{SyncSender, sync_channel};
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 /// Bytes per interleaved F32LE sample.
 const SIZE_F32: usize = std::mem::size_of::<f32>();
@@ -51,6 +52,8 @@ pub struct PipeWireOutput {
     /// Requested output buffer time; sizes the PCM sync-channel depth.
     buffer_time_ms: u32,
     pause_state: PauseState,
+    /// Samples sent to the loop thread but not yet played; see `drain`.
+    backlog: Backlog,
 
     // Runtime handles, populated by `start()` and cleared by `stop()`.
     /// Sends decoded PCM chunks to the loop thread's `SampleBuffer`.
@@ -75,6 +78,7 @@ impl PipeWireOutput {
             node_name,
             buffer_time_ms,
             pause_state: PauseState::new(),
+            backlog: Backlog::new(),
             sample_sender: None,
             terminate: None,
             loop_thread: None,
@@ -101,6 +105,7 @@ impl PipeWireOutput {
             std::sync::mpsc::channel::<std::result::Result<(), String>>();
 
         let node_name = self.node_name.clone();
+        let backlog = self.backlog.clone();
 
         let handle = std::thread::Builder::new()
             .name("rmpd-pipewire".to_owned())
@@ -150,7 +155,7 @@ impl PipeWireOutput {
                 // non-blocking try_recv that returns 0.0 silence on underrun).
                 let _listener = bail!(
                     stream
-                        .add_local_listener_with_user_data(SampleBuffer::new(rx))
+                        .add_local_listener_with_user_data(SampleBuffer::with_backlog(rx, backlog))
                         .process(move |stream, samples| {
                             let Some(mut buffer) = stream.dequeue_buffer() else {
                                 return;
@@ -285,13 +290,32 @@ impl PipeWireOutput {
             return Ok(());
         }
         match &self.sample_sender {
-            Some(sender) => sender
-                .send(samples.to_vec())
-                .map_err(|_| RmpdError::Player("pipewire output gone".to_owned())),
+            Some(sender) => {
+                self.backlog.add(samples.len());
+                sender
+                    .send(samples.to_vec())
+                    .map_err(|_| RmpdError::Player("pipewire output gone".to_owned()))
+            }
             None => Err(RmpdError::Player("pipewire output not started".to_owned())),
         }
     }
 
+    /// Wait for the graph to pull everything already written, so the tail of
+    /// the last song is not cut off when the stream is torn down.
+    pub fn drain(&mut self) -> Result<()> {
+        if self.loop_thread.is_none() || self.pause_state.is_paused() {
+            return Ok(());
+        }
+        let timeout = Duration::from_millis(u64::from(self.buffer_time_ms) * 2 + 500);
+        if !self.backlog.wait_empty(timeout) {
+            tracing::warn!(
+                "pipewire output drain timed out with {} samples unplayed",
+                self.backlog.pending()
+            );
+        }
+        Ok(())
+    }
+
     pub fn stop(&mut self) -> Result<()> {
         if let Some(term) = self.terminate.take() {
             // Best-effort: the loop may already be gone.
@@ -302,6 +326,7 @@ impl PipeWireOutput {
         if let Some(handle) = self.loop_thread.take() {
             let _ = handle.join();
         }
+        self.backlog.reset();
         self.pause_state.set_paused(false);
         Ok(())
     }
@@ -338,6 +363,9 @@ impl AudioOutput for PipeWireOutput {
     fn write(&mut self, samples: &[f32]) -> Result<()> {
         PipeWireOutput::write(self, samples)
     }
+    fn drain(&mut self) -> Result<()> {
+        PipeWireOutput::drain(self)
+    }
     fn stop(&mut self) -> Result<()> {
         PipeWireOutput::stop(self)
     }
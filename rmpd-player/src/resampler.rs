@@ -1,17 +1,23 @@
 //! Streaming sample-rate conversion.
 //!
-//! Used only as a fallback when the output device cannot natively play the
+//! Used as a fallback when the output device cannot natively play the
 //! decoded stream's sample rate (for example a hardware-locked 48 kHz device
 //! handed a 44.1 kHz-family DSD-to-PCM stream). When the device supports the
 //! source rate natively no resampler is created and samples pass through
 //! untouched.
 //!
+//! [`FixedRate`] additionally converts every song to one configured rate
+//! (`[audio].output_sample_rate`), so consecutive tracks at different rates
+//! keep the same output open.
+//!
 //! Backed by `rubato`'s asynchronous resampler. The sinc modes apply a real
 //! anti-aliasing filter — essential when downsampling DSD-derived PCM, which
 //! carries large ultrasonic shaped noise that would otherwise alias into the
 //! audible band — while the `Linear` mode uses cheap polynomial interpolation
 //! with no anti-aliasing.
 
+use std::borrow::Cow;
+
 use audioadapter_buffers::direct::InterleavedSlice;
 use rmpd_core::config::ResamplerQuality;
 use rubato::{
@@ -144,6 +150,65 @@ impl StreamResampler {
     }
 }
 
+/// Converts the decoded stream to a fixed output rate, following the source
+/// rate across in-thread track changes.
+pub struct FixedRate {
+    target: u32,
+    quality: ResamplerQuality,
+    /// Rate and channel count of the current source.
+    source: Option<(u32, usize)>,
+    /// `None` while the source already plays at `target`.
+    resampler: Option<StreamResampler>,
+}
+
+impl FixedRate {
+    pub fn new(target: u32, quality: ResamplerQuality) -> Self {
+        Self {
+            target,
+            quality,
+            source: None,
+            resampler: None,
+        }
+    }
+
+    /// The rate every block is converted to.
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Switch to a source decoded at `rate` with `channels` channels. Input
+    /// still buffered for the previous source (less than one chunk) is
+    /// dropped.
+    pub fn set_source(&mut self, rate: u32, channels: usize) {
+        if self.source == Some((rate, channels)) {
+            return;
+        }
+        self.source = Some((rate, channels));
+        self.resampler = if rate == self.target {
+            None
+        } else {
+            let rs = StreamResampler::new(rate, self.target, channels, self.quality);
+            if rs.is_none() {
+                tracing::error!(
+                    "failed to build resampler {} -> {} Hz; audio may play at the wrong speed",
+                    rate,
+                    self.target
+                );
+            }
+            rs
+        };
+    }
+
+    /// Convert one block of interleaved source samples. May return an empty
+    /// block while the resampler fills its first chunk.
+    pub fn convert<'a>(&mut self, input: &'a [f32]) -> Cow<'a, [f32]> {
+        match &mut self.resampler {
+            Some(rs) => Cow::Owned(rs.process(input)),
+            None => Cow::Borrowed(input),
+        }
+    }
+}
+
 /// Map a quality level to rubato sinc interpolation parameters.
 fn sinc_params(quality: ResamplerQuality) -> SincInterpolationParameters {
     let (sinc_len, oversampling_factor, interpolation, window) = match quality {
@@ -229,6 +294,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fixed_rate_follows_source_changes() {
+        let mut fixed = FixedRate::new(48000, ResamplerQuality::SincFast);
+        let block = vec![0.1f32; CHUNK_FRAMES * 2 * 20];
+
+        fixed.set_source(48000, 2);
+        assert!(matches!(fixed.convert(&block), Cow::Borrowed(_)));
+
+        fixed.set_source(96000, 2);
+        let down = fixed.convert(&block).len() / 2;
+        fixed.set_source(44100, 2);
+        let up = fixed.convert(&block).len() / 2;
+        assert!(down < CHUNK_FRAMES * 20 && up > down, "down={down} up={up}");
+    }
+
     #[test]
     fn mono_is_frame_aligned() {
         let mut rs = StreamResampler::new(88200, 48000, 1, ResamplerQuality::SincFast).unwrap();
@@ -0,0 +1,29 @@
+//! Process-wide playback counters, reported by the daemon's statistics.
+//!
+//! Decoders and outputs run on their own threads, deep below anything that
+//! knows about the daemon, so they bump these counters instead of reporting
+//! each event. They only ever grow; readers take differences or totals.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static DECODE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static UNDERRUNS: AtomicU64 = AtomicU64::new(0);
+
+/// Packets that could not be read or decoded since the process started.
+pub fn decode_errors() -> u64 {
+    DECODE_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Times an output device had played everything it was given before the
+/// next chunk arrived, since the process started.
+pub fn underruns() -> u64 {
+    UNDERRUNS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record_decode_error() {
+    DECODE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_underrun() {
+    UNDERRUNS.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,171 @@
+//! Ogg Vorbis encoder backed by libvorbis (through `vorbis_rs`).
+//!
+//! Follows MPD's `vorbis` encoder: `quality` selects quality-based VBR on
+//! MPD's `-1.0 … 10.0` scale (default 3), `bitrate` selects average-bitrate
+//! mode in kbit/s.
+
+use crate::encoder::{Encoder, EncoderPlugin, EncoderSettings};
+use parking_lot::Mutex;
+use rmpd_core::error::{Result, RmpdError};
+use rmpd_core::song::AudioFormat;
+use std::io::Write;
+use std::num::{NonZeroU8, NonZeroU32};
+use std::sync::Arc;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// MPD's default `quality` for the vorbis encoder.
+const DEFAULT_QUALITY: f32 = 3.0;
+
+fn vorbis_factory(format: AudioFormat, settings: &EncoderSettings) -> Result<Box<dyn Encoder>> {
+    Ok(Box::new(VorbisEncoder::new(format, *settings)?))
+}
+
+pub static VORBIS_ENCODER: EncoderPlugin = EncoderPlugin {
+    name: "vorbis",
+    aliases: &[],
+    quality: Some(-1.0..=10.0),
+    bitrate: Some(32..=500),
+    factory: vorbis_factory,
+};
+
+/// `Write` sink whose bytes the encoder hands out after every call.
+#[derive(Clone, Default)]
+struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+impl SharedSink {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock())
+    }
+}
+
+impl Write for SharedSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Ogg Vorbis stream of interleaved f32 input.
+///
+/// The three Vorbis header packets are written when the stream is opened and
+/// make up [`Encoder::header`]. [`Encoder::finish`] ends the logical stream
+/// and opens a fresh one, so the output can be started again.
+pub struct VorbisEncoder {
+    format: AudioFormat,
+    settings: EncoderSettings,
+    /// `None` only after reopening the stream failed.
+    inner: Option<vorbis_rs::VorbisEncoder<SharedSink>>,
+    sink: SharedSink,
+    header: Vec<u8>,
+}
+
+impl VorbisEncoder {
+    pub fn new(format: AudioFormat, settings: EncoderSettings) -> Result<Self> {
+        let sampling_frequency = NonZeroU32::new(format.sample_rate)
+            .ok_or_else(|| RmpdError::Player("vorbis encoder: sample rate is zero".into()))?;
+        let channels = NonZeroU8::new(format.channels)
+            .ok_or_else(|| RmpdError::Player("vorbis encoder: no channels".into()))?;
+        let average_bitrate = settings
+            .bitrate
+            .and_then(|b| b.checked_mul(1000))
+            .and_then(NonZeroU32::new);
+        let strategy = match average_bitrate {
+            Some(average_bitrate) => VorbisBitrateManagementStrategy::Abr { average_bitrate },
+            None => VorbisBitrateManagementStrategy::QualityVbr {
+                target_quality: settings.quality.unwrap_or(DEFAULT_QUALITY) / 10.0,
+            },
+        };
+
+        let sink = SharedSink::default();
+        let mut builder = VorbisEncoderBuilder::new(sampling_frequency, channels, sink.clone())
+            .map_err(vorbis_error)?;
+        builder.bitrate_management_strategy(strategy);
+        let inner = builder.build().map_err(vorbis_error)?;
+        let header = sink.take();
+        Ok(Self {
+            format,
+            settings,
+            inner: Some(inner),
+            sink,
+            header,
+        })
+    }
+}
+
+fn vorbis_error(e: vorbis_rs::VorbisError) -> RmpdError {
+    RmpdError::Player(format!("vorbis encoder: {e}"))
+}
+
+impl Encoder for VorbisEncoder {
+    fn content_type(&self) -> &str {
+        "audio/ogg"
+    }
+
+    fn header(&self) -> Vec<u8> {
+        self.header.clone()
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        let Some(inner) = &mut self.inner else {
+            return Vec::new();
+        };
+        let channels = usize::from(self.format.channels);
+        if samples.len() < channels {
+            return Vec::new();
+        }
+        let planar: Vec<Vec<f32>> = (0..channels)
+            .map(|ch| {
+                samples
+                    .chunks_exact(channels)
+                    .map(|frame| frame[ch])
+                    .collect()
+            })
+            .collect();
+        if let Err(e) = inner.encode_audio_block(&planar) {
+            tracing::warn!("vorbis encoder: {e}");
+        }
+        self.sink.take()
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        if let Some(inner) = self.inner.take() {
+            if let Err(e) = inner.finish() {
+                tracing::warn!("vorbis encoder: {e}");
+            }
+        }
+        let tail = self.sink.take();
+        match Self::new(self.format, self.settings) {
+            Ok(fresh) => *self = fresh,
+            Err(e) => tracing::warn!("{e}"),
+        }
+        tail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vorbis_stream_starts_with_an_ogg_page() {
+        let format = AudioFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let mut enc = VorbisEncoder::new(format, EncoderSettings::default()).unwrap();
+        assert_eq!(&enc.header()[..4], b"OggS");
+        let input: Vec<f32> = (0..2 * 44100)
+            .map(|i| ((i / 2) as f32 * 0.05).sin() * 0.5)
+            .collect();
+        let mut body = enc.encode(&input);
+        body.extend(enc.finish());
+        assert!(!body.is_empty());
+        assert!(body.len() < input.len() * 2, "vorbis must compress");
+        assert_eq!(&enc.header()[..4], b"OggS", "finish reopens the stream");
+    }
+}
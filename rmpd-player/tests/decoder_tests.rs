@@ -10,7 +10,7 @@ mod fixtures;
 
 use approx::assert_relative_eq;
 use fixtures::pregenerated;
-use fixtures::reference::{calculate_rms, verify_sine_wave};
+use fixtures::reference::{calculate_correlation, calculate_rms, verify_sine_wave};
 use rmpd_player::decoder::SymphoniaDecoder;
 use std::path::Path;
 
@@ -213,6 +213,78 @@ fn test_mp3_sine_wave_reasonable() {
     }
 }
 
+/// Left channel of interleaved stereo samples.
+fn left_channel(samples: &[f32]) -> Vec<f32> {
+    samples.iter().step_by(2).copied().collect()
+}
+
+#[test]
+fn test_mp3_gapless_trim() {
+    let path = pregenerated::sine_1khz_mp3();
+    if !path.exists() {
+        eprintln!("Skipping test: fixture not found");
+        return;
+    }
+
+    // The encoder delay (576 + 529 decoder delay) and padding recorded in
+    // the LAME header leave exactly the 44100 frames that were encoded.
+    let decoder = SymphoniaDecoder::open(&path).expect("Failed to open MP3 file");
+    assert_relative_eq!(decoder.duration().unwrap(), 1.0, epsilon = 1e-9);
+    let (samples, _, channels) = decode_entire_file(&path).expect("Failed to decode MP3 file");
+    assert_eq!(channels, 2);
+    assert_eq!(samples.len(), 44100 * 2);
+
+    // Trimmed output lines up with the lossless source sample for sample.
+    let (reference, _, _) =
+        decode_entire_file(&pregenerated::sine_1khz_wav()).expect("Failed to decode WAV file");
+    let len = samples.len().min(reference.len());
+    let correlation = calculate_correlation(
+        &left_channel(&samples[..len]),
+        &left_channel(&reference[..len]),
+    );
+    assert!(
+        correlation > 0.98,
+        "MP3 misaligned: correlation {correlation}"
+    );
+}
+
+#[test]
+fn test_mp3_gapless_concatenation_is_continuous() {
+    let path = pregenerated::sine_1khz_mp3();
+    if !path.exists() {
+        eprintln!("Skipping test: fixture not found");
+        return;
+    }
+
+    // One second of 1 kHz is a whole number of periods, so playing the file
+    // twice in a row must sound like one unbroken two-second sine.
+    let (samples, _, _) = decode_entire_file(&path).expect("Failed to decode MP3 file");
+    let track = left_channel(&samples);
+    let joined: Vec<f32> = track.iter().chain(&track).copied().collect();
+    let junction = track.len();
+
+    let max_step = |window: &[f32]| {
+        window
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0f32, f32::max)
+    };
+    let inner_step = max_step(&track[100..track.len() - 100]);
+    let junction_step = max_step(&joined[junction - 100..junction + 100]);
+    assert!(
+        junction_step <= inner_step * 1.5,
+        "discontinuity at the track boundary: step {junction_step} vs {inner_step}"
+    );
+
+    // No silence either side of the boundary.
+    let window_rms = calculate_rms(&joined[junction - 441..junction + 441]);
+    let track_rms = calculate_rms(&track);
+    assert!(
+        (window_rms - track_rms).abs() < track_rms * 0.1,
+        "gap at the track boundary: RMS {window_rms} vs {track_rms}"
+    );
+}
+
 #[test]
 fn test_duration_accuracy() {
     let path = pregenerated::sine_1khz_flac();
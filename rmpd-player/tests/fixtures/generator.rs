@@ -158,6 +158,12 @@ impl FixtureGenerator {
                     _ => "pcm_s16le",
                 });
             }
+            AudioFormat::WavPack => {
+                cmd.arg("-codec:a").arg("wavpack");
+            }
+            AudioFormat::Tta => {
+                cmd.arg("-codec:a").arg("tta");
+            }
         }
 
         cmd.arg(output_path);
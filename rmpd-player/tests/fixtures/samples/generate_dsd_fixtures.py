@@ -0,0 +1,64 @@
+#!/usr/bin/env python3
+"""Generate the DSF fixtures for the DSD/DoP tests.
+
+FFmpeg cannot write DSD containers, so the files are assembled here:
+- dsd64_pattern.dsf / dsd128_pattern.dsf: stereo byte ramps (left byte i is
+  i mod 256, right byte i is its complement) for checking DoP framing
+- dsd64_sine_1khz.dsf: a 1 kHz sine at half scale through a second-order
+  sigma-delta modulator, for checking DSD-to-PCM conversion
+"""
+import math
+import struct
+
+BLOCK = 4096  # DSF block size per channel
+DSD64 = 2_822_400
+DSD128 = 5_644_800
+
+
+def write_dsf(path, rate, channels):
+    """Write `channels` (one bytes object per channel, LSB-first DSD)."""
+    length = len(channels[0])
+    assert length % BLOCK == 0 and all(len(c) == length for c in channels)
+    data = bytearray()
+    for block in range(length // BLOCK):
+        for ch in channels:
+            data += ch[block * BLOCK:(block + 1) * BLOCK]
+
+    fmt = struct.pack("<4sQIIIIIIQII", b"fmt ", 52, 1, 0,
+                      2 if len(channels) == 2 else 1, len(channels), rate, 1,
+                      length * 8, BLOCK, 0)
+    body = fmt + struct.pack("<4sQ", b"data", 12 + len(data)) + data
+    header = struct.pack("<4sQQQ", b"DSD ", 28, 28 + len(body), 0)
+    with open(path, "wb") as f:
+        f.write(header + body)
+
+
+def ramp(blocks):
+    left = bytes(i & 0xFF for i in range(blocks * BLOCK))
+    right = bytes(~i & 0xFF for i in range(blocks * BLOCK))
+    return [left, right]
+
+
+def sine(rate, blocks, freq=1000.0, amplitude=0.5):
+    out = bytearray()
+    i1 = i2 = 0.0
+    y = -1.0
+    for n in range(blocks * BLOCK):
+        byte = 0
+        for bit in range(8):  # DSF stores the earliest sample in the LSB
+            t = (n * 8 + bit) / rate
+            x = amplitude * math.sin(2 * math.pi * freq * t)
+            i1 += x - y
+            i2 += i1 - y
+            y = 1.0 if i2 >= 0 else -1.0
+            if y > 0:
+                byte |= 1 << bit
+        out.append(byte)
+    return bytes(out)
+
+
+if __name__ == "__main__":
+    write_dsf("dsd64_pattern.dsf", DSD64, ramp(2))
+    write_dsf("dsd128_pattern.dsf", DSD128, ramp(1))
+    tone = sine(DSD64, 5)
+    write_dsf("dsd64_sine_1khz.dsf", DSD64, [tone, tone])
@@ -0,0 +1,118 @@
+/// Track-boundary format change tests
+///
+/// Plays consecutive fixtures that differ in sample rate and bit depth
+/// (44.1 kHz/16-bit `sine_1khz.flac`, 96 kHz/24-bit `highres.flac`) through
+/// the engine with a null output:
+/// - without a fixed output rate the engine finishes the song and the next
+///   one reopens the output at its own rate
+/// - with `output_sample_rate` set the songs follow each other in-thread
+mod fixtures;
+
+use fixtures::pregenerated;
+use rmpd_core::config::OutputConfig;
+use rmpd_core::event::{Event, EventBus};
+use rmpd_core::playback::PlaybackSong;
+use rmpd_core::state::PlayerStatus;
+use rmpd_player::PlaybackEngine;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU8;
+use std::time::Duration;
+use tokio::sync::{RwLock, broadcast};
+
+fn null_engine(event_bus: &EventBus, output_sample_rate: Option<u32>) -> PlaybackEngine {
+    let status = Arc::new(RwLock::new(PlayerStatus::default()));
+    let mut engine = PlaybackEngine::new(event_bus.clone(), status, Arc::new(AtomicU8::new(0)));
+    engine.set_outputs(vec![OutputConfig {
+        output_type: "null".into(),
+        ..OutputConfig::cpal_default()
+    }]);
+    engine.set_output_sample_rate(output_sample_rate);
+    engine
+}
+
+fn playback_song(path: PathBuf) -> PlaybackSong {
+    let path = path.to_str().expect("fixture path is UTF-8").to_owned();
+    PlaybackSong {
+        song: Arc::new(rmpd_core::test_utils::make_test_song(&path, 1)),
+        resolved_path: path.as_str().into(),
+        range: None,
+    }
+}
+
+/// Wait for the engine's next `SongFinished` or `AdvancedToNext`.
+async fn next_transition(rx: &mut broadcast::Receiver<Event>) -> Event {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match rx.recv().await.expect("event bus closed") {
+                event @ (Event::SongFinished(_) | Event::AdvancedToNext(_)) => return event,
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("engine never reached the end of the song")
+}
+
+#[tokio::test]
+async fn rate_change_reopens_output_between_songs() {
+    let event_bus = EventBus::new();
+    let mut rx = event_bus.subscribe();
+    let mut engine = null_engine(&event_bus, None);
+
+    engine
+        .play(playback_song(pregenerated::sine_1khz_flac()))
+        .await
+        .unwrap();
+    engine.set_next_song(Some(playback_song(pregenerated::highres_flac())));
+    let generation = engine.generation();
+    assert!(matches!(
+        next_transition(&mut rx).await,
+        Event::SongFinished(g) if g == generation
+    ));
+
+    engine
+        .play(playback_song(pregenerated::highres_flac()))
+        .await
+        .unwrap();
+    let generation = engine.generation();
+    assert!(matches!(
+        next_transition(&mut rx).await,
+        Event::SongFinished(g) if g == generation
+    ));
+
+    engine
+        .play(playback_song(pregenerated::sine_1khz_flac()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_transition(&mut rx).await,
+        Event::SongFinished(_)
+    ));
+    engine.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn fixed_output_rate_advances_across_rates() {
+    for (first, second) in [
+        (pregenerated::sine_1khz_flac(), pregenerated::highres_flac()),
+        (pregenerated::highres_flac(), pregenerated::sine_1khz_flac()),
+    ] {
+        let event_bus = EventBus::new();
+        let mut rx = event_bus.subscribe();
+        let mut engine = null_engine(&event_bus, Some(48000));
+
+        engine.play(playback_song(first)).await.unwrap();
+        engine.set_next_song(Some(playback_song(second)));
+        let generation = engine.generation();
+        assert!(matches!(
+            next_transition(&mut rx).await,
+            Event::AdvancedToNext(g) if g == generation
+        ));
+        assert!(matches!(
+            next_transition(&mut rx).await,
+            Event::SongFinished(g) if g == generation
+        ));
+        engine.stop().await.unwrap();
+    }
+}
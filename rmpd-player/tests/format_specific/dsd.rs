@@ -6,8 +6,21 @@
 /// - Bit order handling (LSB-first vs MSB-first)
 /// - Channel layout (planar vs interleaved)
 /// - Sample rate conversions (DSD64 → 176.4kHz, DSD128 → 352.8kHz)
+/// - DSF fixtures end to end: decoder → DoP framing, and DSD-to-PCM fallback
+use crate::fixtures::pregenerated;
+use rmpd_core::config::{DopMode, OutputConfig};
+use rmpd_core::event::{Event, EventBus};
+use rmpd_core::playback::PlaybackSong;
+use rmpd_core::state::PlayerStatus;
+use rmpd_player::PlaybackEngine;
+use rmpd_player::decoder::SymphoniaDecoder;
 use rmpd_player::dop::DopEncoder;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU8;
+use std::time::Duration;
 use symphonia::core::codecs::audio::{BitOrder, ChannelDataLayout};
+use tokio::sync::RwLock;
 
 const DOP_MARKER_1: u8 = 0x05;
 const DOP_MARKER_2: u8 = 0xFA;
@@ -335,3 +348,183 @@ fn test_dop_sample_range() {
         );
     }
 }
+
+/// DSD bytes per channel in the pattern and sine fixtures' DSF blocks.
+const DSF_BLOCK: usize = 4096;
+
+/// DSD64 stereo DSF: 2 blocks of byte ramps (left byte `i` is `i % 256`,
+/// right byte `i` is its complement), for DoP framing checks.
+fn dsd64_pattern_dsf() -> PathBuf {
+    pregenerated::get_fixture("dsd64_pattern.dsf")
+}
+
+/// DSD128 stereo DSF: 1 block of the same byte ramps.
+fn dsd128_pattern_dsf() -> PathBuf {
+    pregenerated::get_fixture("dsd128_pattern.dsf")
+}
+
+/// DSD64 stereo DSF: ~58 ms of a half-scale 1 kHz sine.
+fn dsd64_sine_1khz_dsf() -> PathBuf {
+    pregenerated::get_fixture("dsd64_sine_1khz.dsf")
+}
+
+/// DoP-encode a DSF fixture packet by packet, as the engine's DoP loop does.
+/// Returns the DoP PCM rate, the channel count and the interleaved samples.
+fn dop_encode_file(path: &Path) -> (u32, usize, Vec<i32>) {
+    let mut decoder = SymphoniaDecoder::open(path).expect("open DSF fixture");
+    assert!(decoder.is_dsd(), "DSF fixture should decode as DSD");
+    let channels = decoder.channels() as usize;
+    let mut encoder = DopEncoder::new(
+        decoder.sample_rate(),
+        channels,
+        decoder
+            .channel_data_layout()
+            .unwrap_or(ChannelDataLayout::Planar),
+        decoder.bit_order().unwrap_or(BitOrder::LsbFirst),
+    )
+    .expect("DoP encoder for fixture rate");
+
+    let mut raw = Vec::new();
+    let mut chunk = Vec::new();
+    let mut dop = Vec::new();
+    while decoder.read_dsd_raw(&mut raw).expect("read DSD packet") > 0 {
+        encoder.encode(&raw, &mut chunk);
+        dop.extend_from_slice(&chunk);
+    }
+    (encoder.pcm_sample_rate(), channels, dop)
+}
+
+/// Check DoP framing of a pattern fixture: every frame carries one marker on
+/// all channels, markers alternate across packet boundaries, the low byte is
+/// zero and the payload is the fixture's DSD in MSB-first order.
+fn assert_pattern_framing(path: &Path, expected_rate: u32, blocks: usize) {
+    let (rate, channels, dop) = dop_encode_file(path);
+    assert_eq!(rate, expected_rate);
+    assert_eq!(channels, 2);
+    // 16 DSD bits per channel per DoP frame.
+    assert_eq!(dop.len(), blocks * DSF_BLOCK / 2 * channels);
+
+    for (frame_idx, frame) in dop.chunks(channels).enumerate() {
+        let marker = if frame_idx % 2 == 0 {
+            DOP_MARKER_1
+        } else {
+            DOP_MARKER_2
+        };
+        for (ch, &sample) in frame.iter().enumerate() {
+            let [top, byte1, byte2, low] = sample.to_be_bytes();
+            assert_eq!(top, marker, "frame {frame_idx} channel {ch} marker");
+            assert_eq!(low, 0, "frame {frame_idx} channel {ch} padding");
+
+            // Fixture bytes are LSB-first; DoP carries them MSB-first.
+            let source = |i: usize| {
+                let byte = if ch == 0 { i as u8 } else { !(i as u8) };
+                byte.reverse_bits()
+            };
+            assert_eq!(
+                (byte1, byte2),
+                (source(frame_idx * 2), source(frame_idx * 2 + 1)),
+                "frame {frame_idx} channel {ch} payload"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dsf_dsd64_dop_framing() {
+    assert_pattern_framing(&dsd64_pattern_dsf(), 176400, 2);
+}
+
+#[test]
+fn test_dsf_dsd128_dop_framing() {
+    assert_pattern_framing(&dsd128_pattern_dsf(), 352800, 1);
+}
+
+#[test]
+fn test_dsd_to_pcm_fallback_keeps_the_tone() {
+    let mut decoder = SymphoniaDecoder::open(&dsd64_sine_1khz_dsf()).expect("open DSF fixture");
+    decoder
+        .enable_pcm_conversion(88200)
+        .expect("enable PCM conversion");
+    let rate = decoder.sample_rate();
+    assert_eq!(rate % 44100, 0, "PCM rate {rate} should be 44.1 kHz-family");
+    let channels = decoder.channels() as usize;
+
+    let mut samples = Vec::new();
+    let mut buffer = vec![0.0f32; 4096];
+    loop {
+        let n = decoder.read(&mut buffer).expect("read PCM");
+        if n == 0 {
+            break;
+        }
+        samples.extend_from_slice(&buffer[..n]);
+    }
+    let left: Vec<f32> = samples.iter().step_by(channels).copied().collect();
+
+    // 5 blocks of 32768 one-bit samples at 2.8224 MHz, about 58 ms.
+    let expected_frames = (5 * DSF_BLOCK * 8) as f64 * f64::from(rate) / 2_822_400.0;
+    let frames = left.len() as f64;
+    assert!(
+        (frames - expected_frames).abs() < expected_frames * 0.2,
+        "decoded {frames} frames, expected about {expected_frames}"
+    );
+    assert!(
+        left.iter().all(|s| s.is_finite() && s.abs() <= 1.0),
+        "PCM samples must stay in range"
+    );
+
+    // Skip the decimation filter's settling time, then count the tone's
+    // zero crossings with a little hysteresis against residual DSD noise.
+    let settled = &left[(rate / 100) as usize..];
+    let rms = (settled.iter().map(|s| s * s).sum::<f32>() / settled.len() as f32).sqrt();
+    assert!(rms > 0.1, "half-scale tone came out at RMS {rms}");
+    let mut crossings = 0;
+    let mut positive = settled[0] > 0.0;
+    for &s in settled {
+        if (positive && s < -0.1) || (!positive && s > 0.1) {
+            positive = !positive;
+            crossings += 1;
+        }
+    }
+    let frequency = crossings as f64 / 2.0 / (settled.len() as f64 / f64::from(rate));
+    assert!(
+        (frequency - 1000.0).abs() < 50.0,
+        "converted tone is {frequency:.0} Hz, expected 1 kHz"
+    );
+}
+
+#[tokio::test]
+async fn test_engine_plays_dsf_through_pcm_fallback() {
+    let event_bus = EventBus::new();
+    let mut rx = event_bus.subscribe();
+    let status = Arc::new(RwLock::new(PlayerStatus::default()));
+    let mut engine = PlaybackEngine::new(event_bus.clone(), status, Arc::new(AtomicU8::new(0)));
+    engine.set_outputs(vec![OutputConfig {
+        output_type: "null".into(),
+        ..OutputConfig::cpal_default()
+    }]);
+    engine.set_dop_mode(DopMode::No);
+
+    let path = dsd64_sine_1khz_dsf();
+    let path = path.to_str().expect("fixture path is UTF-8").to_owned();
+    engine
+        .play(PlaybackSong {
+            song: Arc::new(rmpd_core::test_utils::make_test_song(&path, 1)),
+            resolved_path: path.as_str().into(),
+            range: None,
+        })
+        .await
+        .expect("play DSF");
+
+    let generation = engine.generation();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Event::SongFinished(g) = rx.recv().await.expect("event bus closed") {
+                assert_eq!(g, generation);
+                return;
+            }
+        }
+    })
+    .await
+    .expect("DSF should play to the end through PCM conversion");
+    engine.stop().await.unwrap();
+}
@@ -0,0 +1,65 @@
+//! Response serialization benchmarks: rendering 10k songs the way
+//! `playlistinfo` and `find` do, and a full `status` block.
+//!
+//! Run with: cargo bench -p rmpd-protocol --bench responses
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rmpd_core::song::Song;
+use rmpd_core::state::PlayerStatus;
+use rmpd_core::test_utils::make_test_song;
+use rmpd_protocol::response::ResponseBuilder;
+use std::hint::black_box;
+
+const SONG_COUNT: u32 = 10_000;
+
+fn songs() -> Vec<Song> {
+    (0..SONG_COUNT)
+        .map(|i| {
+            make_test_song(
+                &format!("Artist {:03}/Album {:04}/{i:05}.flac", i / 100, i / 10),
+                i % 10 + 1,
+            )
+        })
+        .collect()
+}
+
+fn song_listing(c: &mut Criterion) {
+    let songs = songs();
+    let mut group = c.benchmark_group("response");
+    group.throughput(Throughput::Elements(SONG_COUNT.into()));
+
+    group.bench_function("playlistinfo_10k", |b| {
+        b.iter(|| {
+            let mut resp = ResponseBuilder::new();
+            for (pos, song) in (0..).zip(&songs) {
+                resp.song(song, Some(pos), Some(pos));
+            }
+            black_box(resp.ok())
+        })
+    });
+    group.bench_function("find_10k", |b| {
+        b.iter(|| {
+            let mut resp = ResponseBuilder::new();
+            for song in &songs {
+                resp.song(song, None, None);
+            }
+            black_box(resp.ok())
+        })
+    });
+
+    group.finish();
+}
+
+fn status(c: &mut Criterion) {
+    let status = PlayerStatus::default();
+    c.bench_function("response/status", |b| {
+        b.iter(|| {
+            let mut resp = ResponseBuilder::new();
+            resp.status(black_box(&status));
+            black_box(resp.ok())
+        })
+    });
+}
+
+criterion_group!(benches, song_listing, status);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Fuzz target for the protocol command parser.
+//!
+//! Feeds arbitrary bytes through the same lossy UTF-8 decoding a client line
+//! goes through and asserts `parse_command` never panics.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmpd_protocol::parser::parse_command;
+
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+    let _ = parse_command(&line);
+});
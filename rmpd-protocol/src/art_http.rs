@@ -0,0 +1,519 @@
+//! Album art over HTTP.
+//!
+//! Web clients and browser-based UIs cannot use the chunked `albumart`
+//! command, so with `network.art_port` set rmpd also answers
+//! `GET /art/<id>` (a song id from the queue of any partition) and
+//! `GET /art/<album>` (a percent-encoded album name; all-digit names are
+//! read as song ids) with the embedded cover of that song, or of the first
+//! song of the album that has one. Images go through the database's artwork cache, and responses
+//! carry an `ETag` made from the cached image's hash plus a `Cache-Control`
+//! lifetime; a request whose `If-None-Match` still matches gets
+//! `304 Not Modified` without the image. Like the MPD protocol, requests
+//! need the `read` permission, passing the password as `?password=<secret>`
+//! when `default_permissions` does not grant it. With `[[api_token]]`s
+//! configured, a token with the `read` scope is needed instead (see
+//! [`crate::http_auth`]).
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rmpd_core::config::ApiScope;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::commands::utils::open_db;
+use crate::http_auth::{self, Access};
+use crate::state::AppState;
+
+/// How long clients may reuse an image before revalidating it.
+pub const CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// Largest accepted request head in bytes.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Time a client has to send its request.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handle that keeps the endpoint listening. Dropping it stops accepting
+/// requests.
+pub struct ArtHttpHandle {
+    task: tokio::task::JoinHandle<()>,
+    local_addr: SocketAddr,
+}
+
+impl ArtHttpHandle {
+    /// The address the endpoint listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for ArtHttpHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start serving album art from `state` on `addr`:`port` (0 picks a free
+/// port).
+pub async fn spawn(state: AppState, addr: &str, port: u16) -> std::io::Result<ArtHttpHandle> {
+    let listener = TcpListener::bind((addr, port)).await?;
+    let local_addr = listener.local_addr()?;
+    info!("album art HTTP endpoint listening on {local_addr}");
+
+    let task = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(&state, stream).await {
+                            debug!("art http: {peer}: {e}");
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("art http: accept failed: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(ArtHttpHandle { task, local_addr })
+}
+
+/// Answer the one request sent on `stream`, then close it.
+async fn serve(state: &AppState, mut stream: TcpStream) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+    let response = match head.as_deref().and_then(Request::parse) {
+        Some(request) => {
+            let token = head.as_deref().and_then(http_auth::request_token);
+            let password = head.as_deref().and_then(http_auth::request_password);
+            match http_auth::check(&state.api_tokens, token.as_deref(), ApiScope::Read) {
+                Access::Granted => respond(state, &request).await,
+                Access::Open if http_auth::authorized(state, password.as_deref()) => {
+                    respond(state, &request).await
+                }
+                Access::Open => status_response("403 Forbidden"),
+                denied => http_auth::refusal(denied),
+            }
+        }
+        None => status_response("400 Bad Request"),
+    };
+    stream.write_all(&response).await?;
+    stream.shutdown().await
+}
+
+/// The request head, up to the blank line; `None` when it is too long or
+/// not UTF-8.
+pub(crate) async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(String::from_utf8(buf).ok())
+}
+
+/// What an art request asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    SongId(u32),
+    Album(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    /// `HEAD`: headers only.
+    head_only: bool,
+    /// `None` when the path is not an art path.
+    target: Option<Target>,
+    if_none_match: Option<String>,
+}
+
+impl Request {
+    /// Parse a request head; `None` when malformed or not `GET`/`HEAD`.
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split(' ');
+        let head_only = match request_line.next()? {
+            "GET" => false,
+            "HEAD" => true,
+            _ => return None,
+        };
+        let path = request_line.next()?;
+        let if_none_match = lines.find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("if-none-match")
+                .then(|| value.trim().to_owned())
+        });
+        Some(Self {
+            head_only,
+            target: parse_target(path),
+            if_none_match,
+        })
+    }
+}
+
+/// The target of an `/art/...` path, ignoring any query string.
+fn parse_target(path: &str) -> Option<Target> {
+    let path = path.split('?').next()?;
+    let name = percent_decode(path.strip_prefix("/art/")?)?;
+    if name.is_empty() {
+        return None;
+    }
+    if name.bytes().all(|b| b.is_ascii_digit()) {
+        return name.parse().ok().map(Target::SongId);
+    }
+    Some(Target::Album(name))
+}
+
+/// Decode `%XX` escapes; `None` for a bad escape or non-UTF-8 result.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Whether an `If-None-Match` header value matches `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// A cover image ready to serve.
+struct Image {
+    data: Vec<u8>,
+    mime_type: String,
+    etag: String,
+}
+
+async fn respond(state: &AppState, request: &Request) -> Vec<u8> {
+    let Some(target) = &request.target else {
+        return status_response("404 Not Found");
+    };
+    let Some(image) = find_image(state, target).await else {
+        return status_response("404 Not Found");
+    };
+
+    if request
+        .if_none_match
+        .as_deref()
+        .is_some_and(|tags| etag_matches(tags, &image.etag))
+    {
+        return format!(
+            "HTTP/1.1 304 Not Modified\r\nETag: {}\r\nCache-Control: {CACHE_CONTROL}\r\n\
+             Connection: close\r\n\r\n",
+            image.etag
+        )
+        .into_bytes();
+    }
+
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nETag: {}\r\n\
+         Cache-Control: {CACHE_CONTROL}\r\nConnection: close\r\n\r\n",
+        image.mime_type,
+        image.data.len(),
+        image.etag
+    )
+    .into_bytes();
+    if !request.head_only {
+        response.extend_from_slice(&image.data);
+    }
+    response
+}
+
+pub(crate) fn status_response(status: &str) -> Vec<u8> {
+    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").into_bytes()
+}
+
+/// The path of queued song `id`. Every partition numbers the songs of its
+/// queue on its own, so the default partition is asked first and then the
+/// others by name.
+async fn queued_song(state: &AppState, id: u32) -> Option<String> {
+    if let Some(item) = state.queue.read().await.get_by_id(id) {
+        return Some(item.song.path.to_string());
+    }
+    let manager = state.partition_manager.as_ref()?;
+    let mut names = manager.list_partitions().await;
+    names.sort();
+    for name in names.iter().filter(|name| **name != state.partition) {
+        if let Some(partition) = manager.get_partition(name).await
+            && let Some(item) = partition.queue.read().await.get_by_id(id)
+        {
+            return Some(item.song.path.to_string());
+        }
+    }
+    None
+}
+
+/// The cover of the song or album `target`, extracted into the artwork cache
+/// if it is not there yet.
+async fn find_image(state: &AppState, target: &Target) -> Option<Image> {
+    let queued = match target {
+        Target::SongId(id) => Some(queued_song(state, *id).await?),
+        Target::Album(_) => None,
+    };
+    let target = target.clone();
+    let state = state.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let db = open_db(&state, "art").ok()?;
+        let uris: Vec<String> = match &target {
+            Target::SongId(_) => queued.into_iter().collect(),
+            Target::Album(album) => db
+                .find_songs("album", album)
+                .ok()?
+                .into_iter()
+                .map(|song| song.path.to_string())
+                .collect(),
+        };
+        let extractor = rmpd_library::AlbumArtExtractor::new(db);
+        uris.iter().find_map(|uri| {
+            if rmpd_core::path::is_uri(uri) {
+                return None;
+            }
+            let path = state.resolve_client_path(uri).ok()?;
+            let (data, mime_type) = extractor.extract_and_cache(uri, path.to_str()?).ok()??;
+            let hash = extractor.cached_hash(uri).ok()??;
+            Some(Image {
+                data,
+                mime_type,
+                etag: format!("\"{hash}\""),
+            })
+        })
+    })
+    .await
+    .ok()?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::http_request;
+    use rmpd_core::test_utils::make_test_song;
+
+    #[test]
+    fn targets_are_song_ids_or_album_names() {
+        assert_eq!(parse_target("/art/42"), Some(Target::SongId(42)));
+        assert_eq!(
+            parse_target("/art/Kind%20of%20Blue?size=300"),
+            Some(Target::Album("Kind of Blue".to_owned()))
+        );
+        assert_eq!(
+            parse_target("/art/Sigur%20R%C3%B3s"),
+            Some(Target::Album("Sigur Rós".to_owned()))
+        );
+        assert_eq!(parse_target("/art/"), None);
+        assert_eq!(parse_target("/art/bad%2"), None);
+        assert_eq!(parse_target("/cover/1"), None);
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let request =
+            Request::parse("HEAD /art/7 HTTP/1.1\r\nHost: x\r\nIf-None-Match: \"abc\"\r\n\r\n")
+                .unwrap();
+        assert!(request.head_only);
+        assert_eq!(request.target, Some(Target::SongId(7)));
+        assert_eq!(request.if_none_match.as_deref(), Some("\"abc\""));
+        assert_eq!(Request::parse("POST /art/7 HTTP/1.1\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn etags_match_lists_weak_tags_and_wildcards() {
+        assert!(etag_matches("\"a\", W/\"b\"", "\"b\""));
+        assert!(etag_matches("*", "\"b\""));
+        assert!(!etag_matches("\"a\"", "\"b\""));
+    }
+
+    #[tokio::test]
+    async fn cached_art_is_served_with_validators() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("art.db").to_string_lossy().into_owned();
+        {
+            let db = rmpd_library::Database::open(&db_path).unwrap();
+            db.add_song(&make_test_song("a.flac", 1)).unwrap();
+            db.store_artwork("a.flac", "front", "image/png", b"PNGDATA", "f00d")
+                .unwrap();
+        }
+        let state = AppState::with_paths(db_path, dir.path().to_string_lossy().into_owned());
+        let id = state.queue.write().await.add(make_test_song("a.flac", 1));
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+        let addr = handle.local_addr();
+
+        let resp = http_request(addr, &format!("GET /art/{id} HTTP/1.1\r\n\r\n")).await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+        assert!(resp.contains("ETag: \"f00d\"\r\n"));
+        assert!(resp.contains(&format!("Cache-Control: {CACHE_CONTROL}\r\n")));
+        assert!(resp.contains("Content-Type: image/png\r\n"));
+        assert!(resp.ends_with("\r\n\r\nPNGDATA"));
+
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id} HTTP/1.1\r\nIf-None-Match: \"f00d\"\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{resp}");
+        assert!(resp.ends_with("\r\n\r\n"));
+
+        let resp = http_request(addr, "HEAD /art/Test%20Album HTTP/1.1\r\n\r\n").await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+        assert!(resp.ends_with("\r\n\r\n"), "HEAD carries no body");
+
+        let resp = http_request(addr, "GET /art/999 HTTP/1.1\r\n\r\n").await;
+        assert!(resp.starts_with("HTTP/1.1 404 Not Found\r\n"), "{resp}");
+        let resp = http_request(addr, "DELETE /art/1 HTTP/1.1\r\n\r\n").await;
+        assert!(resp.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{resp}");
+    }
+
+    #[tokio::test]
+    async fn songs_queued_in_other_partitions_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("art.db").to_string_lossy().into_owned();
+        {
+            let db = rmpd_library::Database::open(&db_path).unwrap();
+            db.add_song(&make_test_song("a.flac", 1)).unwrap();
+            db.store_artwork("a.flac", "front", "image/png", b"PNGDATA", "f00d")
+                .unwrap();
+        }
+        let state = AppState::with_paths(db_path, dir.path().to_string_lossy().into_owned());
+        let other = state.create_partition("other").await.unwrap();
+        let id = {
+            let mut queue = other.queue.write().await;
+            queue.add(make_test_song("b.flac", 2));
+            queue.add(make_test_song("a.flac", 1))
+        };
+        assert!(state.queue.read().await.get_by_id(id).is_none());
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+
+        let resp = http_request(
+            handle.local_addr(),
+            &format!("GET /art/{id} HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+        assert!(resp.ends_with("\r\n\r\nPNGDATA"));
+    }
+
+    #[tokio::test]
+    async fn api_tokens_are_required_once_configured() {
+        use rmpd_core::config::ApiToken;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("art.db").to_string_lossy().into_owned();
+        {
+            let db = rmpd_library::Database::open(&db_path).unwrap();
+            db.add_song(&make_test_song("a.flac", 1)).unwrap();
+            db.store_artwork("a.flac", "front", "image/png", b"PNGDATA", "f00d")
+                .unwrap();
+        }
+        let mut state = AppState::with_paths(db_path, dir.path().to_string_lossy().into_owned());
+        let token = |token: &str, scopes: &[ApiScope]| ApiToken {
+            name: token.to_owned(),
+            token: token.to_owned(),
+            scopes: scopes.to_vec(),
+        };
+        state.set_api_tokens(vec![
+            token("viewer", &[ApiScope::Read]),
+            token("operator", &[ApiScope::Control]),
+        ]);
+        let id = state.queue.write().await.add(make_test_song("a.flac", 1));
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+        let addr = handle.local_addr();
+
+        let resp = http_request(addr, &format!("GET /art/{id} HTTP/1.1\r\n\r\n")).await;
+        assert!(resp.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{resp}");
+        assert!(resp.contains("WWW-Authenticate: Bearer"));
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id}?token=operator HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{resp}");
+
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id} HTTP/1.1\r\nAuthorization: Bearer viewer\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id}?token=viewer HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.ends_with("PNGDATA"), "{resp}");
+    }
+
+    #[tokio::test]
+    async fn the_password_guards_art_like_the_protocol() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("art.db").to_string_lossy().into_owned();
+        {
+            let db = rmpd_library::Database::open(&db_path).unwrap();
+            for uri in ["a.flac", "../b.flac"] {
+                db.add_song(&make_test_song(uri, 1)).unwrap();
+                db.store_artwork(uri, "front", "image/png", b"PNGDATA", "f00d")
+                    .unwrap();
+            }
+        }
+        let mut state = AppState::with_paths(db_path, dir.path().to_string_lossy().into_owned());
+        state.set_password(Some("secret".into()));
+        let id = state.queue.write().await.add(make_test_song("a.flac", 1));
+        let outside = state
+            .queue
+            .write()
+            .await
+            .add(make_test_song("../b.flac", 2));
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+        let addr = handle.local_addr();
+
+        let resp = http_request(addr, &format!("GET /art/{id} HTTP/1.1\r\n\r\n")).await;
+        assert!(resp.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{resp}");
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id}?password=wrong HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{resp}");
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{id}?password=secret HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.ends_with("PNGDATA"), "{resp}");
+
+        // Paths escaping the library are not served, even from the cache.
+        let resp = http_request(
+            addr,
+            &format!("GET /art/{outside}?password=secret HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 404 Not Found\r\n"), "{resp}");
+    }
+}
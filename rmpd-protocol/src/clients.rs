@@ -0,0 +1,166 @@
+//! Registry of connected clients.
+//!
+//! Every connection gets a numeric id. A client may name itself with the
+//! `client <name>` extension command; the name is attached to its log lines
+//! and shown by `listclients`. The tag types and protocol features of a named
+//! client are remembered when it disconnects and restored when a connection
+//! registers the same name again, so a reconnecting client resumes where it
+//! left off instead of renegotiating them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Names whose settings are kept; clients pick their own names, so this
+/// bounds memory.
+pub const MAX_SAVED_CLIENTS: usize = 256;
+
+/// Per-connection settings kept across reconnects of a named client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientSettings {
+    pub enabled_tags: Option<HashSet<String>>,
+    pub enabled_features: Option<HashSet<String>>,
+}
+
+/// A connected client, as listed by `listclients`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub id: u64,
+    /// Peer address, or `unix:<uid>` for the local socket (`unix` when the
+    /// peer's credentials cannot be read).
+    pub address: String,
+    pub name: Option<String>,
+}
+
+/// Connected clients and the saved settings of named ones. Clones share the
+/// same registry.
+#[derive(Clone, Default)]
+pub struct ClientRegistry {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    connected: BTreeMap<u64, ClientInfo>,
+    saved: HashMap<String, ClientSettings>,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add a client connected from `address`. It stays listed until the
+    /// returned guard is dropped.
+    pub fn register(&self, address: String) -> ClientGuard {
+        let mut inner = self.lock();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.connected.insert(
+            id,
+            ClientInfo {
+                id,
+                address,
+                name: None,
+            },
+        );
+        ClientGuard {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// Name client `id`. Returns the settings an earlier client of that name
+    /// left behind, if any.
+    pub fn set_name(&self, id: u64, name: &str) -> Option<ClientSettings> {
+        let mut inner = self.lock();
+        if let Some(client) = inner.connected.get_mut(&id) {
+            client.name = Some(name.to_owned());
+        }
+        inner.saved.get(name).cloned()
+    }
+
+    /// Remember `settings` for the next client named `name`.
+    pub fn save(&self, name: &str, settings: ClientSettings) {
+        let mut inner = self.lock();
+        if inner.saved.len() >= MAX_SAVED_CLIENTS && !inner.saved.contains_key(name) {
+            let evicted = inner.saved.keys().next().cloned();
+            if let Some(evicted) = evicted {
+                inner.saved.remove(&evicted);
+            }
+        }
+        inner.saved.insert(name.to_owned(), settings);
+    }
+
+    /// Connected clients, oldest first.
+    pub fn list(&self) -> Vec<ClientInfo> {
+        self.lock().connected.values().cloned().collect()
+    }
+}
+
+/// Keeps a client listed in its [`ClientRegistry`] while alive.
+pub struct ClientGuard {
+    registry: ClientRegistry,
+    id: u64,
+}
+
+impl ClientGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.registry.lock().connected.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_are_listed_while_connected() {
+        let registry = ClientRegistry::new();
+        let a = registry.register("127.0.0.1:5000".to_owned());
+        let b = registry.register("unix".to_owned());
+        assert_ne!(a.id(), b.id());
+        assert_eq!(registry.set_name(b.id(), "ncmpcpp"), None);
+
+        let listed = registry.list();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].address, "127.0.0.1:5000");
+        assert_eq!(listed[1].name.as_deref(), Some("ncmpcpp"));
+
+        drop(a);
+        assert_eq!(registry.list().len(), 1);
+    }
+
+    #[test]
+    fn settings_are_restored_by_name() {
+        let registry = ClientRegistry::new();
+        let settings = ClientSettings {
+            enabled_tags: Some(HashSet::from(["Artist".to_owned()])),
+            enabled_features: None,
+        };
+        registry.save("phone", settings.clone());
+
+        let client = registry.register("10.0.0.2:4000".to_owned());
+        assert_eq!(registry.set_name(client.id(), "phone"), Some(settings));
+        assert_eq!(registry.set_name(client.id(), "tablet"), None);
+    }
+
+    #[test]
+    fn saved_settings_are_bounded() {
+        let registry = ClientRegistry::new();
+        for i in 0..MAX_SAVED_CLIENTS + 10 {
+            registry.save(&format!("client{i}"), ClientSettings::default());
+        }
+        assert_eq!(registry.lock().saved.len(), MAX_SAVED_CLIENTS);
+    }
+}
@@ -0,0 +1,212 @@
+//! Queue snapshot ("bookmark") command handlers
+//!
+//! A bookmark is the queue saved under a name together with the song that
+//! was playing and how far into it, kept in the library database. Unlike a
+//! stored playlist, loading one resumes where playback left off, which is
+//! what an audiobook or a long podcast episode needs.
+
+use crate::response::ResponseBuilder;
+use crate::state::AppState;
+
+use super::utils::{
+    ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, format_iso8601_timestamp, open_db, read_unscanned_song,
+};
+
+pub async fn handle_savebookmark_command(state: &AppState, name: &str) -> String {
+    let uris: Vec<String> = {
+        let queue = state.queue.read().await;
+        queue
+            .items()
+            .iter()
+            .map(|item| item.song.path.to_string())
+            .collect()
+    };
+    let (current, elapsed) = {
+        let status = state.status.read().await;
+        let current = status.current_song.map(|c| c.position);
+        let elapsed = current.and(status.elapsed).map_or(0.0, |e| e.as_secs_f64());
+        (current, elapsed)
+    };
+
+    let state = state.clone();
+    let name = name.to_string();
+    match tokio::task::spawn_blocking(move || {
+        let db = open_db(&state, "savebookmark")?;
+        db.save_queue_snapshot(&name, &uris, current, elapsed)
+            .map_err(|e| {
+                ResponseBuilder::error(
+                    ACK_ERROR_SYS,
+                    0,
+                    "savebookmark",
+                    &format!("database error: {e}"),
+                )
+            })
+    })
+    .await
+    {
+        Ok(Ok(())) => ResponseBuilder::new().ok(),
+        Ok(Err(e)) => e,
+        Err(_) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "savebookmark", "internal error"),
+    }
+}
+
+/// Replace the queue with the bookmark `name` and resume its current song
+/// at the saved position. Songs that have left the library since are
+/// skipped.
+pub async fn handle_loadbookmark_command(state: &AppState, name: &str) -> String {
+    let state_clone = state.clone();
+    let name_owned = name.to_string();
+    let (songs, current, elapsed) = match tokio::task::spawn_blocking(move || {
+        let db = open_db(&state_clone, "loadbookmark")?;
+        let snapshot = match db.get_queue_snapshot(&name_owned) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => {
+                return Err(ResponseBuilder::error(
+                    ACK_ERROR_NO_EXIST,
+                    0,
+                    "loadbookmark",
+                    "No such bookmark",
+                ));
+            }
+            Err(e) => {
+                return Err(ResponseBuilder::error(
+                    ACK_ERROR_SYS,
+                    0,
+                    "loadbookmark",
+                    &format!("database error: {e}"),
+                ));
+            }
+        };
+
+        let mut songs = Vec::with_capacity(snapshot.uris.len());
+        let mut current = None;
+        for (position, uri) in snapshot.uris.iter().enumerate() {
+            let song = match db.get_song_by_path(uri).ok().flatten() {
+                Some(song) => song,
+                None if rmpd_stream::is_http_uri(uri) => crate::helpers::create_stream_song(uri),
+                None => match read_unscanned_song(&state_clone, "loadbookmark", uri) {
+                    Ok(Some(song)) => song,
+                    _ => continue,
+                },
+            };
+            // Skipped songs shift the current one forward.
+            if snapshot.current == Some(position as u32) {
+                current = Some(songs.len() as u32);
+            }
+            songs.push(song);
+        }
+        Ok((songs, current, snapshot.elapsed))
+    })
+    .await
+    {
+        Ok(Ok(loaded)) => loaded,
+        Ok(Err(e)) => return e,
+        Err(_) => {
+            return ResponseBuilder::error(ACK_ERROR_SYS, 0, "loadbookmark", "internal error");
+        }
+    };
+
+    state.engine.write().await.stop().await.ok();
+    let mut streams = Vec::new();
+    {
+        let mut queue = state.queue.write().await;
+        queue.clear();
+        for song in songs {
+            let uri = song.path.to_string();
+            let id = queue.add(song);
+            if rmpd_stream::is_http_uri(&uri) {
+                streams.push((id, uri));
+            }
+        }
+    }
+    {
+        let mut status = state.status.write().await;
+        status.state = rmpd_core::state::PlayerState::Stop;
+        status.current_song = None;
+        status.next_song = None;
+        status.elapsed = None;
+    }
+    crate::helpers::update_playlist_version(state).await;
+    for (id, uri) in streams {
+        crate::helpers::spawn_stream_probe(state, id, &uri);
+    }
+
+    let Some(position) = current else {
+        return ResponseBuilder::new().ok();
+    };
+    let response = super::playback::handle_play_command(state, Some(position)).await;
+    if elapsed > 0.0 && !response.starts_with("ACK ") {
+        // The song is current now, so this seeks within it.
+        return super::playback::handle_seek_command(state, position, elapsed).await;
+    }
+    response
+}
+
+pub async fn handle_listbookmarks_command(state: &AppState) -> String {
+    let state = state.clone();
+    match tokio::task::spawn_blocking(move || {
+        let db = open_db(&state, "listbookmarks")?;
+        let snapshots = db.list_queue_snapshots().map_err(|e| {
+            ResponseBuilder::error(
+                ACK_ERROR_SYS,
+                0,
+                "listbookmarks",
+                &format!("database error: {e}"),
+            )
+        })?;
+
+        let mut resp = ResponseBuilder::new();
+        for snapshot in &snapshots {
+            resp.field("bookmark", &snapshot.name);
+            resp.field(
+                "Last-Modified",
+                format_iso8601_timestamp(snapshot.last_modified),
+            );
+            resp.field("songs", snapshot.uris.len());
+            if let Some(current) = snapshot.current {
+                resp.field("song", current);
+                resp.field("elapsed", format!("{:.3}", snapshot.elapsed));
+            }
+        }
+        Ok(resp.ok())
+    })
+    .await
+    {
+        Ok(Ok(resp)) | Ok(Err(resp)) => resp,
+        Err(_) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "listbookmarks", "internal error"),
+    }
+}
+
+pub async fn handle_deletebookmark_command(state: &AppState, name: &str) -> String {
+    let state = state.clone();
+    let name = name.to_string();
+    match tokio::task::spawn_blocking(move || {
+        let db = open_db(&state, "deletebookmark")?;
+        let exists = db
+            .get_queue_snapshot(&name)
+            .map(|s| s.is_some())
+            .unwrap_or(false);
+        if !exists {
+            return Err(ResponseBuilder::error(
+                ACK_ERROR_NO_EXIST,
+                0,
+                "deletebookmark",
+                "No such bookmark",
+            ));
+        }
+        db.delete_queue_snapshot(&name).map_err(|e| {
+            ResponseBuilder::error(
+                ACK_ERROR_SYS,
+                0,
+                "deletebookmark",
+                &format!("database error: {e}"),
+            )
+        })
+    })
+    .await
+    {
+        Ok(Ok(())) => ResponseBuilder::new().ok(),
+        Ok(Err(e)) => e,
+        Err(_) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "deletebookmark", "internal error"),
+    }
+}
@@ -4,16 +4,20 @@
 //! and connection management.
 
 use super::{AppState, ResponseBuilder};
-use crate::commands::utils::ACK_ERROR_PASSWORD;
+use crate::commands::utils::{ACK_ERROR_ARG, ACK_ERROR_PASSWORD};
 use crate::connection::ConnectionState;
+use tracing::info;
 
 /// Return server configuration
 ///
-/// Returns server configuration information from AppState.
+/// Reports the directories and database in use and, when the server was
+/// started from a config file, its audio and `[[output]]` settings. Needs
+/// the admin permission, which `local_permissions` can grant to clients on
+/// the Unix socket.
 pub async fn handle_config_command(state: &AppState) -> String {
     let mut resp = ResponseBuilder::new();
 
-    if let Some(music_dir) = &state.music_dir {
+    if let Some(music_dir) = state.music_dir() {
         resp.field("music_directory", music_dir);
     }
 
@@ -21,11 +25,99 @@ pub async fn handle_config_command(state: &AppState) -> String {
         resp.field("playlist_directory", playlist_dir);
     }
 
+    if let Some(db_file) = &state.db_path {
+        resp.field("db_file", db_file);
+    }
+
+    if let Some(config) = &state.config {
+        let audio = &config.audio;
+        resp.field("default_output", &audio.default_output);
+        resp.optional_field("device", config.output_device());
+        resp.field("buffer_time", audio.buffer_time);
+        resp.field("resampler_quality", audio.resampler_quality.as_str());
+        resp.field("dop", config.dop_mode().as_str());
+        resp.field("replay_gain", audio.replay_gain);
+        resp.optional_field("output_sample_rate", audio.output_sample_rate);
+        for output in &config.output {
+            resp.field("audio_output", &output.name);
+            resp.field("audio_output_type", &output.output_type);
+            resp.field("audio_output_enabled", u8::from(output.enabled));
+        }
+    }
+
     // MPD reports pcre support; rmpd uses basic regex matching
     resp.field("pcre", "0");
     resp.ok()
 }
 
+/// `client <name>` (rmpd extension): name this connection. The name tags the
+/// client's log lines and `listclients` entry; tag types and protocol
+/// features saved by an earlier connection of the same name are restored.
+pub fn handle_client_command(
+    state: &AppState,
+    conn_state: &mut ConnectionState,
+    name: &str,
+) -> String {
+    if name.is_empty() {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, "client", "empty client name");
+    }
+    // Renaming: leave the current settings under the old name.
+    if let Some(old) = conn_state.client_name.take() {
+        state.clients.save(&old, conn_state.settings());
+    }
+    let restored = state.clients.set_name(conn_state.client_id, name);
+    tracing::Span::current().record("name", name);
+    let id = conn_state.client_id;
+    match restored {
+        Some(settings) => {
+            info!("client {id} is {name:?}, settings restored");
+            conn_state.restore(settings);
+        }
+        None => info!("client {id} is {name:?}"),
+    }
+    conn_state.client_name = Some(name.to_owned());
+    ResponseBuilder::new().ok()
+}
+
+/// Smallest `binarylimit` accepted, as in MPD.
+const MIN_BINARY_LIMIT: u32 = 64;
+
+/// Room left in the output buffer for the fields around a binary chunk, as
+/// in MPD.
+const BINARY_HEADER_ROOM: usize = 4096;
+
+/// Set the largest binary chunk (`albumart`, `readpicture`) sent to this
+/// client. Clients raise it to fetch big covers in fewer round trips, up to
+/// what still fits the connection's output buffer.
+pub fn handle_binarylimit_command(conn_state: &mut ConnectionState, size: u32) -> String {
+    if size < MIN_BINARY_LIMIT {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, "binarylimit", "Value too small");
+    }
+    if size as usize
+        > conn_state
+            .max_output_buffer_size
+            .saturating_sub(BINARY_HEADER_ROOM)
+    {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, "binarylimit", "Value too large");
+    }
+    conn_state.binary_limit = size as usize;
+    ResponseBuilder::new().ok()
+}
+
+/// `listclients` (rmpd extension): connected clients with their id, address
+/// and registered name.
+pub fn handle_listclients_command(state: &AppState) -> String {
+    let mut resp = ResponseBuilder::new();
+    for client in state.clients.list() {
+        resp.field("client", client.id);
+        resp.field("address", &client.address);
+        if let Some(name) = &client.name {
+            resp.field("name", name);
+        }
+    }
+    resp.ok()
+}
+
 /// Kill the server (graceful shutdown)
 ///
 /// Sends a shutdown signal to the main server loop, triggering graceful shutdown.
@@ -39,26 +131,25 @@ pub async fn handle_kill_command(state: &AppState) -> String {
 
 /// Handle the `password` command.
 ///
-/// If no password is configured any value is accepted.
-/// On success all permissions are granted; on failure an ACK error is returned.
+/// If no password is configured any value is accepted, unless
+/// `default_permissions` restricts clients: then there is nothing to unlock.
+/// On success the password's permissions are granted; on failure an ACK error
+/// is returned.
 pub async fn handle_password_command(
     state: &AppState,
     conn_state: &mut ConnectionState,
     password: &str,
 ) -> String {
     match &state.password {
-        None => {
+        None if state.default_permissions.is_none() => {
             // No password configured — any password is accepted, grant all permissions
             conn_state.grant_all_permissions();
             ResponseBuilder::new().ok()
         }
-        Some(configured) => {
-            if password == configured.as_str() {
-                conn_state.grant_all_permissions();
-                ResponseBuilder::new().ok()
-            } else {
-                ResponseBuilder::error(ACK_ERROR_PASSWORD, 0, "password", "incorrect password")
-            }
+        Some(configured) if password == configured.as_str() => {
+            conn_state.grant_permissions(state.password_permissions);
+            ResponseBuilder::new().ok()
         }
+        _ => ResponseBuilder::error(ACK_ERROR_PASSWORD, 0, "password", "incorrect password"),
     }
 }
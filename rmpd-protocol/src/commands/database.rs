@@ -1,5 +1,9 @@
 //! Database and library browsing command handlers
 
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rmpd_library::collation;
 use tracing::{debug, error};
 
 use crate::helpers;
@@ -28,8 +32,8 @@ fn strip_music_dir_prefix<'a>(path: &'a str, music_dir: Option<&str>) -> &'a str
 }
 
 use super::utils::{
-    ACK_ERROR_ARG, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, apply_range, build_and_filter,
-    format_iso8601_timestamp, open_db,
+    ACK_ERROR_ARG, ACK_ERROR_NO_EXIST, ACK_ERROR_PERMISSION, ACK_ERROR_SYS, NO_MUSIC_DIR,
+    apply_range, build_and_filter, format_iso8601_timestamp, open_db, path_error,
 };
 
 /// Helper function to get tag value with MPD-style fallback.
@@ -39,42 +43,72 @@ fn get_tag_value<'a>(song: &'a rmpd_core::song::Song, tag: &str) -> std::borrow:
     Cow::Borrowed(song.tag_with_fallback(tag).unwrap_or_default())
 }
 
+/// Sort `find`/`search` results by `[-]KEY`: `Added` and `Last-Modified`
+/// order by timestamp, `Disc` and `Track` numerically by disc then track,
+/// any other key by tag value; a leading `-` sorts descending. Songs of one
+/// album stay in disc and track order; other equal keys keep their previous
+/// order.
+fn sort_songs(songs: &mut [rmpd_core::song::Song], sort: &str) {
+    let (key, descending) = match sort.strip_prefix('-') {
+        Some(key) => (key, true),
+        None => (sort, false),
+    };
+    let key_lower = key.to_ascii_lowercase();
+    songs.sort_by(|a, b| {
+        let ord = match key_lower.as_str() {
+            "added" => a.added_at.cmp(&b.added_at),
+            "last-modified" => a.last_modified.cmp(&b.last_modified),
+            "disc" | "track" => a.disc_and_track().cmp(&b.disc_and_track()),
+            _ => get_tag_value(a, key).cmp(&get_tag_value(b, key)),
+        };
+        let ord = if descending { ord.reverse() } else { ord };
+        if key_lower == "album" {
+            ord.then_with(|| a.disc_and_track().cmp(&b.disc_and_track()))
+        } else {
+            ord
+        }
+    });
+}
+
 async fn handle_find_search_core(
     state: &AppState,
     filters: &[(String, String)],
     sort: Option<&str>,
     window: Option<(u32, u32)>,
     case_sensitive: bool,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
     let cmd = if case_sensitive { "find" } else { "search" };
     let state = state.clone();
+    let key = search_cache_key(cmd, filters, sort, window, enabled_tags);
+    let enabled_tags = enabled_tags.cloned();
     let filters = filters.to_vec();
     let sort = sort.map(|s| s.to_string());
     match tokio::task::spawn_blocking(move || {
-        let db = match open_db(&state, cmd) {
-            Ok(d) => d,
-            Err(e) => return e,
-        };
+        // An `Err` response is sent but not cached.
+        let result = state.search_cache.get_or_load(&key, || {
+            let db = open_db(&state, cmd)?;
+            let mut songs = helpers::resolve_filters(&db, &filters, cmd, case_sensitive)?;
 
-        let mut songs = match helpers::resolve_filters(&db, &filters, cmd, case_sensitive) {
-            Ok(s) => s,
-            Err(e) => return e,
-        };
-
-        if let Some(sort_tag) = sort.as_deref() {
-            songs.sort_by(|a, b| {
-                let a_val = get_tag_value(a, sort_tag);
-                let b_val = get_tag_value(b, sort_tag);
-                a_val.cmp(&b_val)
-            });
-        }
+            if let Some(sort) = sort.as_deref() {
+                sort_songs(&mut songs, sort);
+            }
 
-        let filtered = apply_range(&songs, window);
-        let mut resp = ResponseBuilder::new();
-        for song in filtered {
-            resp.song(song, None, None);
+            let filtered = apply_range(&songs, window);
+            let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
+            for song in filtered {
+                resp.song(song, None, None);
+            }
+            let resp = resp.ok();
+            if resp.len() > MAX_CACHED_SEARCH_BYTES {
+                return Err(resp);
+            }
+            Ok(Arc::from(resp))
+        });
+        match result {
+            Ok(resp) => resp.to_string(),
+            Err(resp) => resp,
         }
-        resp.ok()
     })
     .await
     {
@@ -83,13 +117,46 @@ async fn handle_find_search_core(
     }
 }
 
+/// Responses larger than this (a search matching much of the library) are
+/// not kept in the search cache.
+const MAX_CACHED_SEARCH_BYTES: usize = 256 * 1024;
+
+/// Search cache key: the command and its arguments, with tag names folded
+/// to lower case since MPD matches them case-insensitively, plus the
+/// client's enabled tag types, which shape the response.
+fn search_cache_key(
+    cmd: &str,
+    filters: &[(String, String)],
+    sort: Option<&str>,
+    window: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
+    let filters: Vec<(String, &str)> = filters
+        .iter()
+        .map(|(tag, value)| {
+            if tag.starts_with('(') {
+                (tag.trim().to_owned(), value.as_str())
+            } else {
+                (tag.to_ascii_lowercase(), value.as_str())
+            }
+        })
+        .collect();
+    let enabled_tags = enabled_tags.map(|tags| {
+        let mut tags: Vec<&String> = tags.iter().collect();
+        tags.sort();
+        tags
+    });
+    format!("{cmd} {filters:?} {sort:?} {window:?} {enabled_tags:?}")
+}
+
 pub async fn handle_find_command(
     state: &AppState,
     filters: &[(String, String)],
     sort: Option<&str>,
     window: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
-    handle_find_search_core(state, filters, sort, window, true).await
+    handle_find_search_core(state, filters, sort, window, true, enabled_tags).await
 }
 
 pub async fn handle_search_command(
@@ -97,8 +164,78 @@ pub async fn handle_search_command(
     filters: &[(String, String)],
     sort: Option<&str>,
     window: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
-    handle_find_search_core(state, filters, sort, window, false).await
+    handle_find_search_core(state, filters, sort, window, false, enabled_tags).await
+}
+
+/// Print the values of `tag` in `songs` grouped by the `groups` tags, as
+/// `list` does. Like MPD, the last group is the outermost: each group value
+/// is printed once, followed by the values nested under it, every level
+/// sorted. A song without a tag counts as having the empty value.
+fn print_grouped_tags(
+    resp: &mut ResponseBuilder,
+    songs: &[rmpd_core::song::Song],
+    tag: &str,
+    groups: &[String],
+) {
+    let levels: Vec<String> = groups
+        .iter()
+        .rev()
+        .map(String::as_str)
+        .chain([tag])
+        .map(str::to_lowercase)
+        .collect();
+
+    // Every combination of the levels' values, one row per combination.
+    let mut rows = std::collections::BTreeSet::<Vec<&str>>::new();
+    for song in songs {
+        let mut song_rows = vec![Vec::with_capacity(levels.len())];
+        for level in &levels {
+            let mut values = song.tag_values_with_fallback(level);
+            if values.is_empty() {
+                values.push("");
+            }
+            song_rows = song_rows
+                .into_iter()
+                .flat_map(|row| {
+                    values.iter().map(move |value| {
+                        let mut row = row.clone();
+                        row.push(*value);
+                        row
+                    })
+                })
+                .collect();
+        }
+        rows.extend(song_rows);
+    }
+    let mut rows: Vec<_> = rows.into_iter().collect();
+    rows.sort_by(|a, b| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| collation::compare(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let keys: Vec<&str> = levels
+        .iter()
+        .map(|level| rmpd_core::song::canonical_tag_name(level))
+        .collect();
+    let mut previous: Option<&[&str]> = None;
+    for row in &rows {
+        // Print a level again from the first one whose value changed.
+        let changed = previous.map_or(0, |previous| {
+            row.iter()
+                .zip(previous)
+                .position(|(a, b)| a != b)
+                .unwrap_or(row.len())
+        });
+        for (key, value) in keys.iter().zip(row).skip(changed) {
+            resp.field(key, value);
+        }
+        previous = Some(row.as_slice());
+    }
 }
 
 pub async fn handle_list_command(
@@ -106,27 +243,27 @@ pub async fn handle_list_command(
     tag: &str,
     filter_tag: Option<&str>,
     filter_value: Option<&str>,
-    group: Option<&str>,
+    groups: &[String],
 ) -> String {
     let state = state.clone();
     let tag = tag.to_string();
     let filter_tag = filter_tag.map(|s| s.to_string());
     let filter_value = filter_value.map(|s| s.to_string());
-    let group = group.map(|s| s.to_string());
+    let groups = groups.to_vec();
     match tokio::task::spawn_blocking(move || {
         let tag = tag.as_str();
         let filter_tag = filter_tag.as_deref();
         let filter_value = filter_value.as_deref();
-        let group = group.as_deref();
+        let groups = groups.as_slice();
         let db = match open_db(&state, "list") {
             Ok(d) => d,
             Err(e) => return e,
         };
 
-        // For grouped queries we need the full song list to extract both the group tag
+        // For grouped queries we need the full song list to extract both the group tags
         // and the requested tag. For non-grouped queries we can use the optimised path.
-        if let Some(group_tag) = group {
-            // Grouped: get all matching songs, then group by group_tag
+        if !groups.is_empty() {
+            // Grouped: get all matching songs, then group by the group tags
             let songs = if let Some(ft) = filter_tag {
                 if ft.starts_with('(') {
                     match rmpd_core::filter::FilterExpression::parse(ft) {
@@ -184,48 +321,8 @@ pub async fn handle_list_command(
                 }
             };
 
-            // Build map: group_value -> BTreeSet<tag_value> (sorted set)
-            // Group values are sorted by MPD's std::map order (lexicographic)
-            #[allow(clippy::disallowed_types)]
-            let mut groups: std::collections::BTreeMap<
-                String,
-                std::collections::BTreeSet<String>,
-            > = std::collections::BTreeMap::new();
-
-            let group_tag_lower = group_tag.to_lowercase();
-            let tag_lower = tag.to_lowercase();
-            for song in &songs {
-                let group_vals = song.tag_values_with_fallback(&group_tag_lower);
-                let tag_vals = song.tag_values_with_fallback(&tag_lower);
-
-                let group_vals: Vec<&str> = if group_vals.is_empty() {
-                    vec![""]
-                } else {
-                    group_vals
-                };
-
-                for gv in &group_vals {
-                    let tag_set = groups.entry(gv.to_string()).or_default();
-                    if tag_vals.is_empty() {
-                        tag_set.insert(String::new());
-                    } else {
-                        for tv in &tag_vals {
-                            tag_set.insert(tv.to_string());
-                        }
-                    }
-                }
-            }
-
-            let group_key = rmpd_core::song::canonical_tag_name(&group_tag_lower);
-            let tag_key = rmpd_core::song::canonical_tag_name(&tag_lower);
-
             let mut resp = ResponseBuilder::new();
-            for (group_val, tag_vals) in &groups {
-                resp.field(group_key, group_val);
-                for tv in tag_vals {
-                    resp.field(tag_key, tv);
-                }
-            }
+            print_grouped_tags(&mut resp, &songs, tag, groups);
             return resp.ok();
         }
 
@@ -246,7 +343,9 @@ pub async fn handle_list_command(
                                     }
                                 }
                             }
-                            seen.into_iter().collect()
+                            let mut values: Vec<String> = seen.into_iter().collect();
+                            values.sort_by(|a, b| collation::compare(a, b));
+                            values
                         }
                         Err(e) => {
                             return ResponseBuilder::error(
@@ -455,13 +554,8 @@ pub async fn handle_update_command(state: &AppState, _path: Option<&str>) -> Str
     if state.db_path.is_none() {
         return ResponseBuilder::error(ACK_ERROR_SYS, 0, "update", "database not configured");
     }
-    if state.music_dir.is_none() {
-        return ResponseBuilder::error(
-            ACK_ERROR_SYS,
-            0,
-            "update",
-            "music directory not configured",
-        );
+    if state.music_dir().is_none() {
+        return ResponseBuilder::error(ACK_ERROR_SYS, 0, "update", NO_MUSIC_DIR);
     }
 
     // Spawn the background scan (shared with auto-update on startup).
@@ -475,7 +569,168 @@ pub async fn handle_update_command(state: &AppState, _path: Option<&str>) -> Str
     resp.ok()
 }
 
-pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize) -> Response {
+/// `updatepreview` (rmpd extension): list what `update` would add, update
+/// and remove, as `added:`/`updated:`/`removed:` lines, without touching the
+/// database.
+pub async fn handle_updatepreview_command(state: &AppState) -> String {
+    let Some(music_dir) = state.music_dir() else {
+        return ResponseBuilder::error(ACK_ERROR_SYS, 0, "updatepreview", NO_MUSIC_DIR);
+    };
+    let state = state.clone();
+
+    match tokio::task::spawn_blocking(move || {
+        let Some(backend) = state.db_pool.as_ref() else {
+            return ResponseBuilder::error(
+                ACK_ERROR_SYS,
+                0,
+                "updatepreview",
+                "database not configured",
+            );
+        };
+        let scanner = rmpd_library::Scanner::new(state.event_bus.clone(), state.follow_symlinks)
+            .with_roots(state.music_roots.to_vec());
+        let report = match backend.plan_scan(&scanner, std::path::Path::new(&music_dir)) {
+            Ok(report) => report,
+            Err(e) => {
+                return ResponseBuilder::error(ACK_ERROR_SYS, 0, "updatepreview", &e.to_string());
+            }
+        };
+
+        let mut resp = ResponseBuilder::new();
+        for path in &report.added {
+            resp.field("added", path);
+        }
+        for path in &report.updated {
+            resp.field("updated", path);
+        }
+        for path in &report.removed {
+            resp.field("removed", path);
+        }
+        resp.ok()
+    })
+    .await
+    {
+        Ok(resp) => resp,
+        Err(_) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "updatepreview", "internal error"),
+    }
+}
+
+/// `libraryreport [URI]` (rmpd extension): composition of the local library,
+/// or of the directory `URI`. Totals (`songs`, `playtime`, `size` in bytes)
+/// come first, then `count`-style groups: `Quality` (lossless/lossy),
+/// `Format` (file extension), `SampleRate` and `BitDepth`, each followed by
+/// its `songs` and `playtime`.
+pub async fn handle_libraryreport_command(state: &AppState, uri: Option<&str>) -> String {
+    let dir = uri.unwrap_or("").to_owned();
+    if let Err(e) = rmpd_core::path::check_uri(&dir) {
+        return path_error("libraryreport", e);
+    }
+    let state = state.clone();
+
+    match tokio::task::spawn_blocking(move || {
+        let db = open_db(&state, "libraryreport")?;
+        db.format_report(&dir, &|uri| state.resolve_uri(uri))
+            .map_err(|e| ResponseBuilder::error(ACK_ERROR_SYS, 0, "libraryreport", &e.to_string()))
+    })
+    .await
+    {
+        Ok(Ok(report)) => {
+            if uri.is_some_and(|uri| !uri.is_empty()) && report.total.songs == 0 {
+                return ResponseBuilder::error(
+                    ACK_ERROR_NO_EXIST,
+                    0,
+                    "libraryreport",
+                    "No such directory",
+                );
+            }
+            let mut resp = ResponseBuilder::new();
+            resp.field("songs", report.total.songs);
+            resp.field("playtime", report.total.playtime.floor() as u64);
+            resp.field("size", report.size);
+            let group = |resp: &mut ResponseBuilder, group: &rmpd_library::FormatGroup| {
+                resp.field("songs", group.songs);
+                resp.field("playtime", group.playtime.floor() as u64);
+            };
+            for (quality, totals) in [("lossless", &report.lossless), ("lossy", &report.lossy)] {
+                resp.field("Quality", quality);
+                group(&mut resp, totals);
+            }
+            for (format, totals) in &report.formats {
+                resp.field("Format", format);
+                group(&mut resp, totals);
+            }
+            for (rate, totals) in &report.sample_rates {
+                resp.field("SampleRate", rate);
+                group(&mut resp, totals);
+            }
+            for (bits, totals) in &report.bit_depths {
+                resp.field("BitDepth", bits);
+                group(&mut resp, totals);
+            }
+            resp.ok()
+        }
+        Ok(Err(e)) => e,
+        Err(_) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "libraryreport", "internal error"),
+    }
+}
+
+/// Which binary artwork command a chunk is being framed for.
+#[derive(Clone, Copy)]
+enum ArtCommand {
+    AlbumArt,
+    ReadPicture,
+}
+
+impl ArtCommand {
+    fn name(self) -> &'static str {
+        match self {
+            ArtCommand::AlbumArt => "albumart",
+            ArtCommand::ReadPicture => "readpicture",
+        }
+    }
+}
+
+/// Frame one artwork chunk exactly as MPD does:
+///
+/// ```text
+/// size: <total bytes>
+/// type: <mime>          (readpicture only, when known)
+/// binary: <chunk bytes>
+/// <chunk bytes of raw data>
+/// OK
+/// ```
+///
+/// `artwork.data` is the chunk starting at `offset` (at most one binary-limit
+/// worth of bytes). An offset exactly at the end yields `binary: 0`; an offset
+/// past the end is `ACK_ERROR_ARG "Offset too large"`, like MPD.
+fn artwork_response(
+    command: ArtCommand,
+    artwork: &rmpd_library::ArtworkData,
+    offset: usize,
+) -> Response {
+    if offset > artwork.total_size {
+        return Response::Text(ResponseBuilder::error(
+            ACK_ERROR_ARG,
+            0,
+            command.name(),
+            "Offset too large",
+        ));
+    }
+    let mut resp = ResponseBuilder::new();
+    resp.field("size", artwork.total_size);
+    if matches!(command, ArtCommand::ReadPicture) && !artwork.mime_type.is_empty() {
+        resp.field("type", &artwork.mime_type);
+    }
+    resp.binary_field("binary", &artwork.data);
+    Response::Binary(resp.to_binary_response())
+}
+
+pub async fn handle_albumart_command(
+    state: &AppState,
+    uri: &str,
+    offset: usize,
+    binary_limit: usize,
+) -> Response {
     debug!("albumart command: uri=[{}], offset={}", uri, offset);
 
     let state_open = state.clone();
@@ -497,7 +752,7 @@ pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize)
     if state.sources.owns_path(uri) {
         let uri_owned = uri.to_string();
         let (extractor, is_cached) = match tokio::task::spawn_blocking(move || {
-            let extractor = rmpd_library::AlbumArtExtractor::new(db);
+            let extractor = rmpd_library::AlbumArtExtractor::new(db).with_chunk_size(binary_limit);
             let cached = extractor.is_cached(&uri_owned);
             (extractor, cached)
         })
@@ -522,13 +777,7 @@ pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize)
             })
             .await
             {
-                Ok(Ok(Some(artwork))) => {
-                    let mut resp = ResponseBuilder::new();
-                    resp.field("size", artwork.total_size);
-                    resp.field("type", &artwork.mime_type);
-                    resp.binary_field("binary", &artwork.data);
-                    Response::Binary(resp.to_binary_response())
-                }
+                Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::AlbumArt, &artwork, offset),
                 Ok(_) => {
                     Response::Text(ResponseBuilder::error(50, 0, "albumart", "No file exists"))
                 }
@@ -547,13 +796,7 @@ pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize)
         })
         .await
         {
-            Ok(Ok(Some(artwork))) => {
-                let mut resp = ResponseBuilder::new();
-                resp.field("size", artwork.total_size);
-                resp.field("type", &artwork.mime_type);
-                resp.binary_field("binary", &artwork.data);
-                Response::Binary(resp.to_binary_response())
-            }
+            Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::AlbumArt, &artwork, offset),
             Ok(_) => Response::Text(ResponseBuilder::error(50, 0, "albumart", "No file exists")),
             Err(_) => Response::Text(ResponseBuilder::error(
                 ACK_ERROR_SYS,
@@ -564,45 +807,44 @@ pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize)
         };
     }
 
-    // Resolve relative path to absolute path
-    let absolute_path = if uri.starts_with('/') {
-        // Already absolute
-        uri.to_string()
-    } else {
-        // Relative to music directory
-        match &state.music_dir {
-            Some(music_dir) => {
-                let path = format!("{music_dir}/{uri}");
-                path
-            }
-            None => {
-                return Response::Text(ResponseBuilder::error(
-                    50,
-                    0,
-                    "albumart",
-                    "music directory not configured",
-                ));
-            }
-        }
+    let path = match state.resolve_client_path(uri) {
+        Ok(path) => path,
+        Err(e) => return Response::Text(path_error("albumart", e)),
     };
 
-    let uri_owned = uri.to_string();
+    // Directory URIs (`albumart Album/`): cover file first, then any song's art.
+    if !uri.starts_with('/') && (uri.ends_with('/') || path.is_dir()) {
+        let uri_owned = uri.to_string();
+        let state = state.clone();
+        return match tokio::task::spawn_blocking(move || {
+            let extractor = rmpd_library::AlbumArtExtractor::new(db).with_chunk_size(binary_limit);
+            extractor.get_directory_artwork(&uri_owned, |u| state.resolve_uri(u), offset)
+        })
+        .await
+        {
+            Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::AlbumArt, &artwork, offset),
+            Ok(_) => Response::Text(ResponseBuilder::error(50, 0, "albumart", "No file exists")),
+            Err(_) => Response::Text(ResponseBuilder::error(
+                ACK_ERROR_SYS,
+                0,
+                "albumart",
+                "internal error",
+            )),
+        };
+    }
+
+    // A song: the cover file of its directory. Embedded pictures are
+    // `readpicture`'s, as in MPD.
+    let absolute_path = path.to_string_lossy().into_owned();
     match tokio::task::spawn_blocking(move || {
-        let extractor = rmpd_library::AlbumArtExtractor::new(db);
-        extractor.get_artwork(&uri_owned, &absolute_path, offset)
+        let extractor = rmpd_library::AlbumArtExtractor::new(db).with_chunk_size(binary_limit);
+        extractor.get_song_cover(&absolute_path, offset)
     })
     .await
     {
-        Ok(Ok(Some(artwork))) => {
-            // Binary response with proper format
-            let mut resp = ResponseBuilder::new();
-            resp.field("size", artwork.total_size);
-            resp.field("type", &artwork.mime_type);
-            resp.binary_field("binary", &artwork.data);
-            Response::Binary(resp.to_binary_response())
-        }
+        Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::AlbumArt, &artwork, offset),
         Ok(Ok(None)) => {
-            // File exists but no album art found
+            // No cover file next to the song
             Response::Text(ResponseBuilder::error(50, 0, "albumart", "No file exists"))
         }
         Ok(Err(_)) => Response::Text(ResponseBuilder::error(50, 0, "albumart", "No file exists")),
@@ -615,7 +857,12 @@ pub async fn handle_albumart_command(state: &AppState, uri: &str, offset: usize)
     }
 }
 
-pub async fn handle_readpicture_command(state: &AppState, uri: &str, offset: usize) -> Response {
+pub async fn handle_readpicture_command(
+    state: &AppState,
+    uri: &str,
+    offset: usize,
+    binary_limit: usize,
+) -> Response {
     // readpicture returns embedded pictures from audio files.
     // Unlike albumart: file-not-found -> "No such song", no picture -> OK (empty)
     let state_open = state.clone();
@@ -637,7 +884,7 @@ pub async fn handle_readpicture_command(state: &AppState, uri: &str, offset: usi
     if state.sources.owns_path(uri) {
         let uri_owned = uri.to_string();
         let (extractor, is_cached) = match tokio::task::spawn_blocking(move || {
-            let extractor = rmpd_library::AlbumArtExtractor::new(db);
+            let extractor = rmpd_library::AlbumArtExtractor::new(db).with_chunk_size(binary_limit);
             let cached = extractor.is_cached(&uri_owned);
             (extractor, cached)
         })
@@ -682,13 +929,7 @@ pub async fn handle_readpicture_command(state: &AppState, uri: &str, offset: usi
         })
         .await
         {
-            Ok(Ok(Some(artwork))) => {
-                let mut resp = ResponseBuilder::new();
-                resp.field("size", artwork.total_size);
-                resp.field("type", &artwork.mime_type);
-                resp.binary_field("binary", &artwork.data);
-                Response::Binary(resp.to_binary_response())
-            }
+            Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::ReadPicture, &artwork, offset),
             Ok(_) => Response::Text(ResponseBuilder::new().ok()),
             Err(_) => Response::Text(ResponseBuilder::error(
                 ACK_ERROR_SYS,
@@ -699,37 +940,20 @@ pub async fn handle_readpicture_command(state: &AppState, uri: &str, offset: usi
         };
     }
 
-    let absolute_path = if uri.starts_with('/') {
-        uri.to_string()
-    } else {
-        match &state.music_dir {
-            Some(music_dir) => format!("{music_dir}/{uri}"),
-            None => {
-                return Response::Text(ResponseBuilder::error(
-                    50,
-                    0,
-                    "readpicture",
-                    "music directory not configured",
-                ));
-            }
-        }
+    let absolute_path = match state.resolve_client_path(uri) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(e) => return Response::Text(path_error("readpicture", e)),
     };
 
     let uri_owned = uri.to_string();
     let absolute_path_for_check = absolute_path.clone();
     match tokio::task::spawn_blocking(move || {
-        let extractor = rmpd_library::AlbumArtExtractor::new(db);
+        let extractor = rmpd_library::AlbumArtExtractor::new(db).with_chunk_size(binary_limit);
         extractor.get_artwork(&uri_owned, &absolute_path, offset)
     })
     .await
     {
-        Ok(Ok(Some(artwork))) => {
-            let mut resp = ResponseBuilder::new();
-            resp.field("size", artwork.total_size);
-            resp.field("type", &artwork.mime_type);
-            resp.binary_field("binary", &artwork.data);
-            Response::Binary(resp.to_binary_response())
-        }
+        Ok(Ok(Some(artwork))) => artwork_response(ArtCommand::ReadPicture, &artwork, offset),
         Ok(Ok(None)) => {
             // File exists but no embedded picture — return empty OK
             Response::Text(ResponseBuilder::new().ok())
@@ -754,19 +978,22 @@ pub async fn handle_readpicture_command(state: &AppState, uri: &str, offset: usi
 }
 
 // Queue inspection
-pub async fn handle_currentsong_command(state: &AppState) -> String {
+pub async fn handle_currentsong_command(
+    state: &AppState,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let status = state.status.read().await;
     let queue = state.queue.read().await;
 
     if let Some(current) = status.current_song
         && let Some(item) = queue.get(current.position)
     {
-        let mut resp = ResponseBuilder::new();
+        let mut resp = ResponseBuilder::with_tags(enabled_tags);
         // For remote streams, surface the live ICY "now playing" title as Title.
         if rmpd_core::path::is_uri(item.song.path.as_str())
             && let Some(title) = state.stream_title.read().await.clone()
         {
-            let mut song = (*item.song).clone();
+            let mut song = item.tagged_song().into_owned();
             if let Some(slot) = song.tags.iter_mut().find(|(k, _)| k == "title") {
                 slot.1 = title;
             } else {
@@ -774,7 +1001,11 @@ pub async fn handle_currentsong_command(state: &AppState) -> String {
             }
             resp.song(&song, Some(current.position), Some(current.id));
         } else {
-            resp.song(&item.song, Some(current.position), Some(current.id));
+            resp.song(
+                &item.tagged_song(),
+                Some(current.position),
+                Some(current.id),
+            );
         }
         return resp.ok();
     }
@@ -784,9 +1015,14 @@ pub async fn handle_currentsong_command(state: &AppState) -> String {
 }
 
 // Browsing commands
-pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> String {
+pub async fn handle_lsinfo_command(
+    state: &AppState,
+    path: Option<&str>,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let state = state.clone();
     let path = path.map(|s| s.to_string());
+    let enabled_tags = enabled_tags.cloned();
     match tokio::task::spawn_blocking(move || {
         let path = path.as_deref();
         let db = match open_db(&state, "lsinfo") {
@@ -797,12 +1033,14 @@ pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> Stri
         let path_str = path.unwrap_or("");
 
         // First check if path refers to a single file (song), matching MPD behavior
-        // where `lsinfo <file>` returns just that file's info.
-        if !path_str.is_empty() && path_str != "/" {
+        // where `lsinfo <file>` returns just that file's info. A cached listing
+        // means the path is a directory.
+        if !path_str.is_empty() && path_str != "/" && state.browse_cache.get(path_str).is_none() {
             match db.get_song_by_path(path_str) {
                 Ok(Some(song)) => {
-                    let mut resp = ResponseBuilder::new();
-                    let music_dir = state.music_dir.as_deref();
+                    let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
+                    let music_dir = state.music_dir();
+                    let music_dir = music_dir.as_deref();
                     let display_path = strip_music_dir_prefix(song.path.as_str(), music_dir);
                     let mut display_song = song.clone();
                     display_song.path = display_path.into();
@@ -815,10 +1053,14 @@ pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> Stri
         }
 
         // Get directory listing
-        match db.list_directory(path_str) {
+        match state
+            .browse_cache
+            .get_or_load(path_str, || db.list_directory(path_str).map(Arc::new))
+        {
             Ok(listing) => {
-                let mut resp = ResponseBuilder::new();
-                let music_dir = state.music_dir.as_deref();
+                let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
+                let music_dir = state.music_dir();
+                let music_dir = music_dir.as_deref();
 
                 // Songs first, then directories (matches MPD's lsinfo output order)
                 for song in &listing.songs {
@@ -835,6 +1077,10 @@ pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> Stri
                         resp.field("Last-Modified", &ts);
                     }
                 }
+                for (playlist, mtime) in &listing.playlists {
+                    resp.field("playlist", playlist);
+                    resp.field("Last-Modified", &format_iso8601_timestamp(*mtime));
+                }
 
                 // For root directory, also list playlists (read from filesystem, matching MPD behavior)
                 if (path_str.is_empty() || path_str == "/")
@@ -869,6 +1115,13 @@ pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> Stri
                 resp.ok()
             }
             Err(e) => {
+                // Not a real directory: maybe the stored-playlist namespace
+                if let Some(sub) = playlists_subdir(path_str)
+                    && let Some(playlist_dir) = &state.playlist_dir
+                    && let Some(resp) = list_playlist_dir(playlist_dir, sub)
+                {
+                    return resp;
+                }
                 // Strip the "Library error: " prefix that RmpdError::Library adds
                 let msg = e.to_string();
                 let msg = msg.strip_prefix("Library error: ").unwrap_or(&msg);
@@ -883,6 +1136,79 @@ pub async fn handle_lsinfo_command(state: &AppState, path: Option<&str>) -> Stri
     }
 }
 
+/// For `playlists` and `playlists/<sub>`, the part below the virtual
+/// directory (`""` or `<sub>`) that mirrors the playlist directory.
+fn playlists_subdir(path: &str) -> Option<&str> {
+    let path = path.trim_matches('/');
+    let sub = path.strip_prefix("playlists")?;
+    if sub.is_empty() {
+        return Some("");
+    }
+    let sub = sub.strip_prefix('/')?;
+    std::path::Path::new(sub)
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+        .then_some(sub)
+}
+
+/// `lsinfo` of `playlists/<sub>`: subdirectories of the playlist directory
+/// as `directory:` entries and the stored playlists in it as `playlist:`
+/// names that `listplaylist` and `load` accept. `None` if `sub` is missing.
+fn list_playlist_dir(playlist_dir: &str, sub: &str) -> Option<String> {
+    let mtime_of = |meta: &std::fs::Metadata| {
+        meta.modified()
+            .map(rmpd_core::time::system_time_to_unix_secs)
+            .unwrap_or(0)
+    };
+    let join = |prefix: &str, name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{prefix}/{name}")
+        }
+    };
+
+    let mut directories = Vec::new();
+    let mut playlists = Vec::new();
+    for entry in std::fs::read_dir(std::path::Path::new(playlist_dir).join(sub))
+        .ok()?
+        .flatten()
+    {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            directories.push((join(&join("playlists", sub), file_name), mtime_of(&meta)));
+        } else if let Some(ext) = path.extension().and_then(|e| e.to_str())
+            && super::playlists::STORED_PLAYLIST_EXTENSIONS.contains(&ext)
+            && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+        {
+            playlists.push((join(sub, stem), mtime_of(&meta)));
+        }
+    }
+    directories.sort_by(|a, b| collation::compare(&a.0, &b.0));
+    playlists.sort_by(|a, b| collation::compare(&a.0, &b.0));
+    playlists.dedup_by(|a, b| a.0 == b.0);
+
+    let mut resp = ResponseBuilder::new();
+    for (dir, mtime) in &directories {
+        resp.field("directory", dir);
+        resp.field("Last-Modified", &format_iso8601_timestamp(*mtime));
+    }
+    for (name, mtime) in &playlists {
+        resp.field("playlist", name);
+        resp.field("Last-Modified", &format_iso8601_timestamp(*mtime));
+    }
+    Some(resp.ok())
+}
+
 pub async fn handle_listall_command(state: &AppState, path: Option<&str>) -> String {
     let state = state.clone();
     let path = path.map(|s| s.to_string());
@@ -939,9 +1265,14 @@ pub async fn handle_listall_command(state: &AppState, path: Option<&str>) -> Str
     }
 }
 
-pub async fn handle_listallinfo_command(state: &AppState, path: Option<&str>) -> String {
+pub async fn handle_listallinfo_command(
+    state: &AppState,
+    path: Option<&str>,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let state = state.clone();
     let path = path.map(|s| s.to_string());
+    let enabled_tags = enabled_tags.cloned();
     match tokio::task::spawn_blocking(move || {
         let path = path.as_deref();
         let db = match open_db(&state, "listallinfo") {
@@ -950,7 +1281,7 @@ pub async fn handle_listallinfo_command(state: &AppState, path: Option<&str>) ->
         };
 
         let path_str = path.unwrap_or("");
-        let mut resp = ResponseBuilder::new();
+        let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
 
         // If a specific path is given, check if it's a file first
         if !path_str.is_empty() && path_str != "/" {
@@ -1078,23 +1409,36 @@ pub async fn handle_findadd_command(state: &AppState, tag: &str, value: &str) ->
 pub async fn handle_listfiles_command(state: &AppState, uri: Option<&str>) -> String {
     let path = uri.unwrap_or("");
     // Prefer filesystem listing (like MPD) to show all files with size.
-    if let Some(music_dir) = state.music_dir.as_deref() {
+    if let Some(music_dir) = state.music_dir().as_deref() {
         let full_path = if path.is_empty() {
             std::path::PathBuf::from(music_dir)
         } else {
-            std::path::PathBuf::from(music_dir).join(path)
+            match state.resolve_client_path(path) {
+                Ok(full_path) => full_path,
+                Err(e) => return path_error("listfiles", e),
+            }
         };
 
-        // Safety: reject path traversal
-        if path.contains("..") {
-            return ResponseBuilder::error(ACK_ERROR_ARG, 0, "listfiles", "bad path");
-        }
-
         let path_owned = path.to_string();
+        let roots = state.music_roots.clone();
         let fs_result = tokio::task::spawn_blocking(move || {
             match std::fs::read_dir(&full_path) {
                 Ok(entries) => {
                     let mut resp = ResponseBuilder::new();
+                    // Extra music roots appear as top-level directories.
+                    if path_owned.is_empty() {
+                        for root in roots.iter() {
+                            resp.field("directory", &root.name);
+                            if let Ok(mtime) =
+                                std::fs::metadata(&root.path).and_then(|m| m.modified())
+                            {
+                                let ts = format_iso8601_timestamp(
+                                    rmpd_core::time::system_time_to_unix_secs(mtime),
+                                );
+                                resp.field("Last-Modified", &ts);
+                            }
+                        }
+                    }
                     // MPD streams entries in readdir order with dirs and files
                     // interleaved — no sorting, no separation.
                     for entry in entries.flatten() {
@@ -1173,7 +1517,7 @@ pub async fn handle_listfiles_command(state: &AppState, uri: Option<&str>) -> St
     // Fallback: use database listing when music_dir is not available
     let state_db = state.clone();
     let path_owned = path.to_string();
-    let music_dir_owned = state.music_dir.clone();
+    let music_dir_owned = state.music_dir();
     match tokio::task::spawn_blocking(move || {
         let db = match open_db(&state_db, "listfiles") {
             Ok(d) => d,
@@ -1306,16 +1650,20 @@ pub async fn handle_readcomments_command(state: &AppState, uri: &str) -> String
         return ResponseBuilder::new().ok();
     }
 
-    // Resolve absolute path from music_dir + relative URI
-    let abs_path = if let Some(music_dir) = &state.music_dir {
-        let base = music_dir.trim_end_matches('/');
-        format!("{base}/{uri}")
-    } else {
-        // Try as-is (absolute path)
-        uri.to_string()
+    let path = match state.resolve_client_path(uri) {
+        Ok(path) => match Utf8PathBuf::from_path_buf(path) {
+            Ok(path) => path,
+            Err(_) => {
+                return ResponseBuilder::error(
+                    ACK_ERROR_NO_EXIST,
+                    0,
+                    "readcomments",
+                    "No such song",
+                );
+            }
+        },
+        Err(e) => return path_error("readcomments", e),
     };
-
-    let path = Utf8PathBuf::from(&abs_path);
     if !path.exists() {
         return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "readcomments", "No such song");
     }
@@ -1344,3 +1692,227 @@ pub async fn handle_readcomments_command(state: &AppState, uri: &str) -> String
         }
     }
 }
+
+/// Read a song's lyrics (rmpd extension).
+///
+/// Serves what the scanner stored from embedded lyrics tags or an `.lrc`
+/// sidecar. Each line is sent as its own `line` field so the text survives
+/// the line-based protocol; synced lyrics keep their LRC time tags.
+pub async fn handle_readlyrics_command(state: &AppState, uri: &str) -> String {
+    let state = state.clone();
+    let uri = uri.to_string();
+    tokio::task::spawn_blocking(move || {
+        let db = match open_db(&state, "readlyrics") {
+            Ok(d) => d,
+            Err(e) => return e,
+        };
+
+        match db.get_song_by_path(&uri) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "readlyrics", "No such song");
+            }
+            Err(e) => {
+                return ResponseBuilder::error(
+                    ACK_ERROR_SYS,
+                    0,
+                    "readlyrics",
+                    &format!("database error: {e}"),
+                );
+            }
+        }
+
+        match db.get_lyrics(&uri) {
+            Ok(Some(lyrics)) => {
+                let mut resp = ResponseBuilder::new();
+                resp.field("source", lyrics.source.as_str());
+                resp.field("synced", u8::from(lyrics.synced));
+                for line in lyrics.text.lines() {
+                    resp.field("line", line);
+                }
+                resp.ok()
+            }
+            Ok(None) => ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "readlyrics", "No lyrics"),
+            Err(e) => ResponseBuilder::error(
+                ACK_ERROR_SYS,
+                0,
+                "readlyrics",
+                &format!("database error: {e}"),
+            ),
+        }
+    })
+    .await
+    .unwrap_or_else(|_| ResponseBuilder::error(ACK_ERROR_SYS, 0, "readlyrics", "internal error"))
+}
+
+/// Write a tag back to a library file (rmpd extension).
+///
+/// Serves `writetag {URI} {TAG} {VALUE}`, which replaces every value of the
+/// tag, and `cleartag {URI} {TAG}` (`value` is `None`), which removes it.
+/// Refused unless `database.tag_editing` is enabled. The song's database row
+/// is refreshed from the rewritten file right away; a `rating` is also stored
+/// as the song's `rating` sticker, where MPD clients look for it.
+pub async fn handle_writetag_command(
+    state: &AppState,
+    command: &'static str,
+    uri: &str,
+    tag: &str,
+    value: Option<&str>,
+) -> String {
+    use rmpd_core::event::Event;
+    use rmpd_library::tag_writer;
+
+    if !state.tag_editing {
+        return ResponseBuilder::error(ACK_ERROR_PERMISSION, 0, command, "tag editing is disabled");
+    }
+    if !tag_writer::is_writable(tag) {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, command, "Unsupported tag type");
+    }
+    if value.is_some_and(|v| v.bytes().any(|b| b < 0x20)) {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, command, "Invalid tag value");
+    }
+    if state.sources.owns_path(uri) {
+        return ResponseBuilder::error(
+            ACK_ERROR_PERMISSION,
+            0,
+            command,
+            "Remote songs are read-only",
+        );
+    }
+    let path = match state.resolve_client_path(uri) {
+        Ok(path) => match camino::Utf8PathBuf::from_path_buf(path) {
+            Ok(path) => path,
+            Err(_) => {
+                return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, command, "No such song");
+            }
+        },
+        Err(e) => return path_error(command, e),
+    };
+
+    let rating = tag.eq_ignore_ascii_case("rating");
+    let job_state = state.clone();
+    let uri = uri.to_string();
+    let tag = tag.to_string();
+    let value = value.map(str::to_string);
+    let result = tokio::task::spawn_blocking(move || {
+        let _update = rmpd_library::update_lock::lock();
+        let db = open_db(&job_state, command)?;
+        match db.get_song_by_path(&uri) {
+            Ok(Some(_)) if path.is_file() => {}
+            Ok(_) => {
+                return Err(ResponseBuilder::error(
+                    ACK_ERROR_NO_EXIST,
+                    0,
+                    command,
+                    "No such song",
+                ));
+            }
+            Err(e) => {
+                return Err(ResponseBuilder::error(
+                    ACK_ERROR_SYS,
+                    0,
+                    command,
+                    &format!("database error: {e}"),
+                ));
+            }
+        }
+
+        let sys_error = |e: rmpd_core::error::RmpdError| {
+            error!("{command} {tag} on {uri} failed: {e}");
+            ResponseBuilder::error(ACK_ERROR_SYS, 0, command, &e.to_string())
+        };
+        tag_writer::write_tag(&path, &tag, value.as_deref()).map_err(sys_error)?;
+        let mut song =
+            rmpd_library::MetadataExtractor::extract_from_file(&path).map_err(sys_error)?;
+        song.path = uri.as_str().into();
+        job_state.tag_rewriter.apply(&mut song);
+        db.add_song(&song).map_err(sys_error)?;
+        if rating {
+            match &value {
+                Some(v) => db.set_sticker(&uri, "rating", v),
+                None => db.delete_sticker(&uri, Some("rating")),
+            }
+            .map_err(sys_error)?;
+        }
+        Ok(song)
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(ResponseBuilder::error(
+            ACK_ERROR_SYS,
+            0,
+            command,
+            "internal error",
+        ))
+    });
+
+    match result {
+        Ok(song) => {
+            debug!("{command}: rewrote tags of {}", song.path);
+            let uri = song.path.to_string();
+            state.event_bus.emit(Event::SongUpdated(song));
+            state.event_bus.emit(Event::DatabaseChanged);
+            if rating {
+                state.event_bus.emit(Event::StickerChanged { uri });
+            }
+            ResponseBuilder::new().ok()
+        }
+        Err(ack) => ack,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmpd_core::song::intern_tag_key;
+    use rmpd_core::test_utils::create_test_song;
+
+    #[test]
+    fn sort_songs_by_added_descending() {
+        let mut songs: Vec<_> = [(1, 300), (2, 100), (3, 200), (4, 300)]
+            .into_iter()
+            .map(|(id, added_at)| {
+                let mut song = create_test_song(id, &id.to_string());
+                song.added_at = added_at;
+                song
+            })
+            .collect();
+
+        sort_songs(&mut songs, "-added");
+        let ids: Vec<u64> = songs.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [1, 4, 3, 2]);
+
+        sort_songs(&mut songs, "Added");
+        let ids: Vec<u64> = songs.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [2, 3, 1, 4]);
+
+        sort_songs(&mut songs, "-Title");
+        let ids: Vec<u64> = songs.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn sort_songs_by_disc_then_track() {
+        // (id, disc, track) of a two-disc album, listed by filename.
+        let mut songs: Vec<_> = [(1, "1", "1"), (2, "2", "1"), (3, "1", "10"), (4, "1", "2")]
+            .into_iter()
+            .map(|(id, disc, track)| {
+                let mut song = create_test_song(id, &id.to_string());
+                song.tags
+                    .push((intern_tag_key("album"), "Box Set".to_owned()));
+                song.tags.push((intern_tag_key("disc"), disc.to_owned()));
+                song.tags.push((intern_tag_key("track"), track.to_owned()));
+                song
+            })
+            .collect();
+
+        sort_songs(&mut songs, "Track");
+        let ids: Vec<u64> = songs.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [1, 4, 3, 2]);
+
+        songs.reverse();
+        sort_songs(&mut songs, "Album");
+        let ids: Vec<u64> = songs.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [1, 4, 3, 2]);
+    }
+}
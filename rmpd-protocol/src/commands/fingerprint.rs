@@ -1,8 +1,7 @@
 use super::ResponseBuilder;
-use super::utils::{ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, ACK_ERROR_UNKNOWN};
+use super::utils::{ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, ACK_ERROR_UNKNOWN, path_error};
 use crate::state::AppState;
 use rmpd_library::Fingerprinter;
-use std::path::PathBuf;
 use tracing::{debug, error};
 
 /// Generate an audio fingerprint for a file
@@ -14,16 +13,9 @@ use tracing::{debug, error};
 /// Only the first 120 seconds of audio are processed.
 pub async fn handle_getfingerprint_command(state: &AppState, uri: &str) -> String {
     // Resolve the URI to an actual file path
-    let path = match resolve_music_path(state, uri) {
+    let path = match state.resolve_client_path(uri) {
         Ok(p) => p,
-        Err(e) => {
-            return ResponseBuilder::error(
-                50,
-                0,
-                "getfingerprint",
-                &format!("Failed to resolve URI: {e}"),
-            );
-        }
+        Err(e) => return path_error("getfingerprint", e),
     };
 
     // Check if file exists
@@ -67,66 +59,20 @@ pub async fn handle_getfingerprint_command(state: &AppState, uri: &str) -> Strin
     }
 }
 
-/// Resolve a URI to an absolute file path
-fn resolve_music_path(state: &AppState, uri: &str) -> Result<PathBuf, String> {
-    let music_dir = state
-        .music_dir
-        .as_ref()
-        .ok_or_else(|| "Music directory not configured".to_string())?;
-
-    // Remove leading slash if present
-    let uri = uri.strip_prefix('/').unwrap_or(uri);
-
-    // Security: Prevent path traversal attacks
-    if uri.contains("..") {
-        return Err("Path traversal not allowed".to_string());
-    }
-
-    let path = PathBuf::from(music_dir).join(uri);
-
-    // Canonicalize to resolve symlinks and check bounds
-    match path.canonicalize() {
-        Ok(canonical) => {
-            // Ensure the path is still within music_dir
-            let music_dir_canonical = PathBuf::from(music_dir)
-                .canonicalize()
-                .map_err(|e| format!("Invalid music directory: {e}"))?;
-
-            if canonical.starts_with(&music_dir_canonical) {
-                Ok(canonical)
-            } else {
-                Err("Path outside music directory".to_string())
-            }
-        }
-        Err(_) => {
-            // File doesn't exist or can't be accessed, but return the path anyway
-            // for better error messages
-            Ok(path)
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_resolve_music_path_traversal() {
+    #[tokio::test]
+    async fn test_getfingerprint_path_traversal() {
         let state = AppState::with_paths("/tmp/db".to_string(), "/music".to_string());
 
         // Should reject path traversal
-        let result = resolve_music_path(&state, "../etc/passwd");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("traversal"));
-    }
-
-    #[test]
-    fn test_resolve_music_path_normal() {
-        let state = AppState::with_paths("/tmp/db".to_string(), "/tmp".to_string());
+        let response = handle_getfingerprint_command(&state, "../etc/passwd").await;
+        assert!(response.starts_with("ACK [2@0] {getfingerprint} Malformed path"));
 
-        // Normal path should work (even if file doesn't exist)
-        let result = resolve_music_path(&state, "music/song.mp3");
-        assert!(result.is_ok());
+        let response = handle_getfingerprint_command(&state, "/etc/passwd").await;
+        assert!(response.starts_with("ACK [4@0] {getfingerprint} Access denied"));
     }
 
     #[tokio::test]
@@ -137,7 +83,7 @@ mod tests {
 
         // Should return error about missing music directory
         assert!(response.contains("ACK"));
-        assert!(response.contains("Music directory"));
+        assert!(response.contains("music directory"));
     }
 
     #[tokio::test]
@@ -2,10 +2,23 @@
 //!
 //! MPD supports a publish-subscribe messaging system for clients to communicate.
 //! This module handles channel subscription and message passing commands.
+//! Subscriptions and message queues are kept per client in the shared
+//! [`MessageBroker`](rmpd_core::messaging::MessageBroker), keyed by the
+//! connection's client id.
 
 use super::{AppState, ResponseBuilder};
 use crate::commands::utils::{ACK_ERROR_ARG, ACK_ERROR_EXIST, ACK_ERROR_NO_EXIST};
 use crate::connection::ConnectionState;
+use rmpd_core::event::Event;
+use rmpd_core::messaging::SubscribeResult;
+
+/// Whether `channel` is a valid channel name: alphanumeric or `_-.:` (MPD rule)
+fn valid_channel_name(channel: &str) -> bool {
+    !channel.is_empty()
+        && channel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':')
+}
 
 /// Subscribe to a message channel
 ///
@@ -15,28 +28,34 @@ pub async fn handle_subscribe_command(
     conn_state: &mut ConnectionState,
     channel: &str,
 ) -> String {
-    // Validate channel name: alphanumeric or _-.:  (MPD rule)
-    if channel.is_empty()
-        || !channel
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':')
-    {
+    if !valid_channel_name(channel) {
         return ResponseBuilder::error(ACK_ERROR_ARG, 0, "subscribe", "invalid channel name");
     }
-    // Check if already subscribed
-    if conn_state
-        .subscribed_channels()
-        .contains(&channel.to_string())
+    match state
+        .message_broker
+        .subscribe(conn_state.client_id, channel)
+        .await
     {
-        return ResponseBuilder::error(
-            ACK_ERROR_EXIST,
-            0,
-            "subscribe",
-            "already subscribed to this channel",
-        );
+        SubscribeResult::Ok => {}
+        SubscribeResult::AlreadySubscribed => {
+            return ResponseBuilder::error(
+                ACK_ERROR_EXIST,
+                0,
+                "subscribe",
+                "already subscribed to this channel",
+            );
+        }
+        SubscribeResult::Full => {
+            return ResponseBuilder::error(
+                ACK_ERROR_EXIST,
+                0,
+                "subscribe",
+                "subscription list is full",
+            );
+        }
     }
     conn_state.subscribe(channel.to_string());
-    state.message_broker.register_subscriber(channel).await;
+    state.event_bus.emit(Event::SubscriptionChanged);
     ResponseBuilder::new().ok()
 }
 
@@ -48,9 +67,10 @@ pub async fn handle_unsubscribe_command(
     conn_state: &mut ConnectionState,
     channel: &str,
 ) -> String {
-    if !conn_state
-        .subscribed_channels()
-        .contains(&channel.to_string())
+    if !state
+        .message_broker
+        .unsubscribe(conn_state.client_id, channel)
+        .await
     {
         return ResponseBuilder::error(
             ACK_ERROR_NO_EXIST,
@@ -60,13 +80,13 @@ pub async fn handle_unsubscribe_command(
         );
     }
     conn_state.unsubscribe(channel);
-    state.message_broker.unregister_subscriber(channel).await;
+    state.event_bus.emit(Event::SubscriptionChanged);
     ResponseBuilder::new().ok()
 }
 
 /// List all available message channels
 ///
-/// Returns channels that currently have messages or subscribers.
+/// Returns channels that currently have at least one subscriber.
 pub async fn handle_channels_command(state: &AppState) -> String {
     let channels = state.message_broker.list_channels().await;
     let mut resp = ResponseBuilder::new();
@@ -80,12 +100,12 @@ pub async fn handle_channels_command(state: &AppState) -> String {
 
 /// Read messages from subscribed channels
 ///
-/// Returns all messages from channels this client is subscribed to,
-/// and removes them from the queue.
+/// Returns the messages queued for this client since its last
+/// `readmessages`, and removes them from its queue.
 pub async fn handle_readmessages_command(state: &AppState, conn_state: &ConnectionState) -> String {
     let messages = state
         .message_broker
-        .read_messages(conn_state.subscribed_channels())
+        .read_messages(conn_state.client_id)
         .await;
 
     let mut resp = ResponseBuilder::new();
@@ -100,21 +120,21 @@ pub async fn handle_readmessages_command(state: &AppState, conn_state: &Connecti
 
 /// Send a message to a channel
 ///
-/// Broadcasts a message to a channel. All subscribed clients will receive it
-/// when they call readmessages.
+/// Queues a message for every client subscribed to the channel, which is
+/// woken from `idle message` and reads it with `readmessages`.
 pub async fn handle_sendmessage_command(state: &AppState, channel: &str, message: &str) -> String {
-    let ok = state
-        .message_broker
-        .send_message(channel.to_string(), message.to_string())
-        .await;
-    if ok {
-        ResponseBuilder::new().ok()
-    } else {
-        ResponseBuilder::error(
+    if !valid_channel_name(channel) {
+        return ResponseBuilder::error(ACK_ERROR_ARG, 0, "sendmessage", "invalid channel name");
+    }
+    let recipients = state.message_broker.send_message(channel, message).await;
+    if recipients.is_empty() {
+        return ResponseBuilder::error(
             ACK_ERROR_NO_EXIST,
             0,
             "sendmessage",
             "nobody is subscribed to this channel",
-        )
+        );
     }
+    state.event_bus.emit(Event::MessageQueued { recipients });
+    ResponseBuilder::new().ok()
 }
@@ -3,6 +3,7 @@
 //! This module splits the large server.rs file into logical categories for better
 //! organization and maintainability.
 
+pub mod bookmarks;
 pub mod connection;
 pub mod database;
 pub mod fingerprint;
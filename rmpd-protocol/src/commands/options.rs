@@ -1,25 +1,50 @@
 //! Playback option command handlers (volume, repeat, random, etc.)
 
+use crate::helpers;
+use crate::queue_playback::QueuePlaybackManager;
 use crate::response::ResponseBuilder;
 use crate::state::AppState;
 
 use super::utils::{ACK_ERROR_ARG, ACK_ERROR_SYS};
 
 /// Notify idle clients (subsystem `options`) and MPRIS that a playback option
-/// changed (repeat/random/single/consume/crossfade/mixramp/replaygain).
+/// changed (repeat/random/single/consume/crossfade/mixramp/replaygain). Like
+/// MPD, callers only notify when the value actually changed, so setting an
+/// option to its current value wakes nobody.
 fn notify_options(state: &AppState) {
     state
         .event_bus
         .emit(rmpd_core::event::Event::QueueOptionsChanged);
 }
 
+/// Change a mode that decides which song plays next (repeat/random/single/
+/// consume), re-pick `nextsong` and the engine's look-ahead to match, then
+/// notify the `options` subsystem. A no-op when the mode already had the
+/// requested value.
+async fn set_playback_mode(
+    state: &AppState,
+    apply: impl FnOnce(&mut rmpd_core::state::PlayerStatus),
+) {
+    {
+        let mut status = state.status.write().await;
+        let modes = |s: &rmpd_core::state::PlayerStatus| (s.repeat, s.random, s.single, s.consume);
+        let before = modes(&status);
+        apply(&mut status);
+        if modes(&status) == before {
+            return;
+        }
+        let queue = state.queue.read().await;
+        helpers::sync_queue_positions(&mut status, &queue);
+    }
+    QueuePlaybackManager::feed_next_song(state).await;
+    notify_options(state);
+}
+
+/// The engine records the new volume in `status` and notifies the `mixer`
+/// subsystem itself (see `PlaybackEngine::set_volume`).
 pub async fn handle_setvol_command(state: &AppState, volume: u8) -> String {
     match state.engine.write().await.set_volume(volume).await {
-        Ok(_) => {
-            let mut status = state.status.write().await;
-            status.volume = volume;
-            ResponseBuilder::new().ok()
-        }
+        Ok(_) => ResponseBuilder::new().ok(),
         Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "setvol", &format!("Volume error: {e}")),
     }
 }
@@ -29,23 +54,18 @@ pub async fn handle_volume_command(state: &AppState, change: i32) -> String {
     let new_vol = (current_vol as i32 + change).clamp(0, 100) as u8;
 
     match state.engine.write().await.set_volume(new_vol).await {
-        Ok(_) => {
-            state.status.write().await.volume = new_vol;
-            ResponseBuilder::new().ok()
-        }
+        Ok(_) => ResponseBuilder::new().ok(),
         Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "volume", &format!("Volume error: {e}")),
     }
 }
 
 pub async fn handle_repeat_command(state: &AppState, enabled: bool) -> String {
-    state.status.write().await.repeat = enabled;
-    notify_options(state);
+    set_playback_mode(state, |status| status.repeat = enabled).await;
     ResponseBuilder::new().ok()
 }
 
 pub async fn handle_random_command(state: &AppState, enabled: bool) -> String {
-    state.status.write().await.random = enabled;
-    notify_options(state);
+    set_playback_mode(state, |status| status.random = enabled).await;
     ResponseBuilder::new().ok()
 }
 
@@ -63,8 +83,7 @@ pub async fn handle_single_command(state: &AppState, mode: &str) -> String {
             );
         }
     };
-    state.status.write().await.single = single_mode;
-    notify_options(state);
+    set_playback_mode(state, |status| status.single = single_mode).await;
     ResponseBuilder::new().ok()
 }
 
@@ -82,46 +101,51 @@ pub async fn handle_consume_command(state: &AppState, mode: &str) -> String {
             );
         }
     };
-    state.status.write().await.consume = consume_mode;
-    notify_options(state);
+    set_playback_mode(state, |status| status.consume = consume_mode).await;
     ResponseBuilder::new().ok()
 }
 
 pub async fn handle_crossfade_command(state: &AppState, seconds: u32) -> String {
-    state.status.write().await.crossfade = seconds;
-    state.engine.write().await.set_crossfade(seconds);
-    notify_options(state);
+    if std::mem::replace(&mut state.status.write().await.crossfade, seconds) != seconds {
+        state.engine.write().await.set_crossfade(seconds);
+        notify_options(state);
+    }
     ResponseBuilder::new().ok()
 }
 
 pub async fn handle_mixrampdb_command(state: &AppState, decibels: f32) -> String {
-    let delay = {
+    let (previous, delay) = {
         let mut status = state.status.write().await;
-        status.mixramp_db = decibels;
-        status.mixramp_delay
+        let previous = std::mem::replace(&mut status.mixramp_db, decibels);
+        (previous, status.mixramp_delay)
     };
-    state.engine.write().await.set_mixramp(decibels, delay);
-    notify_options(state);
+    if previous != decibels {
+        state.engine.write().await.set_mixramp(decibels, delay);
+        notify_options(state);
+    }
     ResponseBuilder::new().ok()
 }
 
 pub async fn handle_mixrampdelay_command(state: &AppState, seconds: f32) -> String {
-    let db = {
+    let (previous, db) = {
         let mut status = state.status.write().await;
-        status.mixramp_delay = seconds;
-        status.mixramp_db
+        let previous = std::mem::replace(&mut status.mixramp_delay, seconds);
+        (previous, status.mixramp_db)
     };
-    state.engine.write().await.set_mixramp(db, seconds);
-    notify_options(state);
+    if previous != seconds {
+        state.engine.write().await.set_mixramp(db, seconds);
+        notify_options(state);
+    }
     ResponseBuilder::new().ok()
 }
 
 pub async fn handle_replaygain_mode_command(state: &AppState, mode: &str) -> String {
     match mode {
         "off" | "track" | "album" | "auto" => {
-            state.status.write().await.replay_gain_mode =
-                rmpd_core::state::ReplayGainMode::parse_mode(mode);
-            notify_options(state);
+            let mode = rmpd_core::state::ReplayGainMode::parse_mode(mode);
+            if std::mem::replace(&mut state.status.write().await.replay_gain_mode, mode) != mode {
+                notify_options(state);
+            }
             ResponseBuilder::new().ok()
         }
         _ => ResponseBuilder::error(
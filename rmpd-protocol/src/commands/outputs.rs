@@ -5,14 +5,16 @@ use crate::state::AppState;
 
 use super::utils::ACK_ERROR_NO_EXIST;
 
-/// Reconcile the engine's active output set after an enabled-flag change.
-/// Feeds the engine ALL enabled outputs; if none are enabled, stops playback.
-async fn reconcile_active_output(state: &AppState) {
+/// Reconcile the engine's active output set after an enabled-flag change or
+/// an output moving between partitions. Feeds the engine ALL enabled outputs
+/// of its partition; if none are enabled, stops playback.
+pub(crate) async fn reconcile_active_output(state: &AppState) {
     let enabled: Vec<rmpd_core::config::OutputConfig> = {
         let outputs = state.outputs.read().await;
         outputs
             .iter()
             .filter(|o| o.enabled)
+            .filter(|o| o.partition.as_deref().unwrap_or("default") == state.partition)
             .filter_map(|o| o.config.clone())
             .collect()
     };
@@ -10,13 +10,18 @@
 //! - listpartitions: List all partitions ✅
 //! - moveoutput: Move output to partition ✅
 //!
-//! Note: Command handlers still need updating to use partition context
+//! Each partition has its own queue, player status, playback engine and
+//! queue/player idle events; commands run against the client's partition
+//! (see [`AppState::for_partition`]). The library, stored playlists,
+//! stickers, mounts and outputs are shared.
 
+use super::outputs::reconcile_active_output;
 use super::utils::{
     ACK_ERROR_ARG, ACK_ERROR_EXIST, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, ACK_ERROR_UNKNOWN,
 };
 use super::{AppState, ResponseBuilder};
 use crate::connection::ConnectionState;
+use rmpd_core::event::Event;
 use tracing::info;
 
 /// Switch to a specific partition
@@ -120,20 +125,13 @@ pub async fn handle_newpartition_command(state: &AppState, name: &str) -> String
         return ResponseBuilder::error(ACK_ERROR_ARG, 0, "newpartition", "Invalid partition name");
     }
 
-    let manager = match &state.partition_manager {
-        Some(m) => m,
-        None => {
-            return ResponseBuilder::error(
-                50,
-                0,
-                "newpartition",
-                "Partition support not initialized",
-            );
-        }
-    };
+    if state.partition_manager.is_none() {
+        return ResponseBuilder::error(50, 0, "newpartition", "Partition support not initialized");
+    }
 
-    match manager.create_partition(name.to_string()).await {
+    match state.create_partition(name).await {
         Ok(_) => {
+            state.event_bus.emit(Event::PartitionsChanged);
             info!("created new partition: {}", name);
             ResponseBuilder::new().ok()
         }
@@ -148,13 +146,15 @@ pub async fn handle_newpartition_command(state: &AppState, name: &str) -> String
 
 /// Delete an existing partition
 ///
-/// Deletes a partition and all its associated state. Cannot delete the
-/// default partition. Clients in the deleted partition are moved to default.
+/// Deletes a partition and all its associated state, stopping its player.
+/// Cannot delete the default partition, nor one that a client has selected or
+/// that outputs are assigned to.
 ///
 /// Returns:
 /// - OK if partition deleted successfully
 /// - ACK `[50@0]` {delpartition} Cannot delete default partition
 /// - ACK `[50@0]` {delpartition} No such partition
+/// - ACK `[50@0]` {delpartition} partition still has clients/outputs
 pub async fn handle_delpartition_command(state: &AppState, name: &str) -> String {
     let manager = match &state.partition_manager {
         Some(m) => m,
@@ -170,6 +170,8 @@ pub async fn handle_delpartition_command(state: &AppState, name: &str) -> String
 
     match manager.delete_partition(name).await {
         Ok(_) => {
+            state.stop_partition_player(name).await;
+            state.event_bus.emit(Event::PartitionsChanged);
             info!("deleted partition: {}", name);
             ResponseBuilder::new().ok()
         }
@@ -207,7 +209,7 @@ pub async fn handle_delpartition_command(state: &AppState, name: &str) -> String
 ///
 /// Transfers ownership of an output from its current partition to the
 /// client's current partition. The output will play audio from the new
-/// partition's queue.
+/// partition's queue, from the next song on.
 ///
 /// Returns:
 /// - OK if output moved successfully
@@ -291,6 +293,13 @@ pub async fn handle_moveoutput_command(
                     output.partition = Some(to.clone());
                 }
             }
+            let from = current_partition.unwrap_or_else(|| "default".to_string());
+            for name in [from.as_str(), to.as_str()] {
+                if let Some(partition_state) = state.for_partition(name).await {
+                    reconcile_active_output(&partition_state).await;
+                }
+            }
+            state.event_bus.emit(Event::OutputsChanged);
 
             info!("moved output '{}' to partition '{}'", output_name, to);
             ResponseBuilder::new().ok()
@@ -44,20 +44,25 @@ pub async fn handle_play_command(state: &AppState, position: Option<u32>) -> Str
         .and_then(|it| it.range);
     drop(queue);
 
-    let playback_song =
-        match prepare_song_for_playback(&song, state.music_dir.as_deref(), range, &state.sources)
-            .await
-        {
-            Ok(ps) => ps,
-            Err(e) => {
-                return ResponseBuilder::error(
-                    ACK_ERROR_NO_EXIST,
-                    0,
-                    "play",
-                    &format!("Cannot resolve song: {}", e),
-                );
-            }
-        };
+    let playback_song = match prepare_song_for_playback(
+        &song,
+        state.music_dir().as_deref(),
+        &state.music_roots,
+        range,
+        &state.sources,
+    )
+    .await
+    {
+        Ok(ps) => ps,
+        Err(e) => {
+            return ResponseBuilder::error(
+                ACK_ERROR_NO_EXIST,
+                0,
+                "play",
+                &format!("Cannot resolve song: {}", e),
+            );
+        }
+    };
 
     match state.engine.write().await.play(playback_song).await {
         Ok(_) => {
@@ -175,7 +180,8 @@ pub async fn handle_next_command(state: &AppState) -> String {
 
         let playback_song = match prepare_song_for_playback(
             &song,
-            state.music_dir.as_deref(),
+            state.music_dir().as_deref(),
+            &state.music_roots,
             range,
             &state.sources,
         )
@@ -241,7 +247,8 @@ pub async fn handle_previous_command(state: &AppState) -> String {
 
         let playback_song = match prepare_song_for_playback(
             &song,
-            state.music_dir.as_deref(),
+            state.music_dir().as_deref(),
+            &state.music_roots,
             range,
             &state.sources,
         )
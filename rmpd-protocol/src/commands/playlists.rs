@@ -4,10 +4,12 @@ use crate::response::ResponseBuilder;
 use crate::state::AppState;
 
 use super::utils::{
-    ACK_ERROR_ARG, ACK_ERROR_EXIST, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, format_iso8601_timestamp,
-    open_db,
+    ACK_ERROR_ARG, ACK_ERROR_EXIST, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, apply_range,
+    format_iso8601_timestamp, open_db, read_unscanned_song,
 };
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
 
 fn strip_file_uri_prefix(value: &str) -> String {
     if let Some(rest) = value.strip_prefix("file://localhost") {
@@ -29,22 +31,150 @@ fn notify_stored_playlist(state: &AppState) {
         .emit(rmpd_core::event::Event::StoredPlaylistChanged);
 }
 
+/// One entry of a playlist file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PlaylistEntry {
+    uri: String,
+    /// Display name from an extended M3U `#EXTINF` line, reported as the
+    /// song's `Name` tag.
+    name: Option<String>,
+}
+
+impl From<String> for PlaylistEntry {
+    fn from(uri: String) -> Self {
+        Self { uri, name: None }
+    }
+}
+
+/// Give `song` the display name its playlist entry carries, unless its own
+/// tags already name it (MPD complements library tags the same way).
+fn apply_entry_name(song: &mut rmpd_core::song::Song, name: Option<&str>) {
+    if let Some(name) = name
+        && song.tag("name").is_none()
+    {
+        song.tags.push(("name".into(), name.to_owned()));
+    }
+}
+
+/// The file of the stored M3U playlist `name`: `<name>.m3u`, or
+/// `<name>.m3u8` when only that exists (as written by MPD and other
+/// players). New playlists are created as `.m3u`.
+fn m3u_playlist_path(playlist_dir: &str, name: &str) -> PathBuf {
+    let m3u = Path::new(playlist_dir).join(format!("{name}.m3u"));
+    let m3u8 = Path::new(playlist_dir).join(format!("{name}.m3u8"));
+    if !m3u.exists() && m3u8.exists() {
+        m3u8
+    } else {
+        m3u
+    }
+}
+
 /// Parse an .m3u playlist file and return the list of relative paths.
 /// Lines starting with '#' are comments and are skipped.
 fn read_m3u_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let path = std::path::Path::new(playlist_dir).join(format!("{name}.m3u"));
-    let content = std::fs::read_to_string(&path).map_err(|_| "No such playlist".to_string())?;
-    let paths: Vec<String> = content
-        .lines()
-        .filter(|l| !l.trim_start().starts_with('#') && !l.trim().is_empty())
-        .map(|l| l.to_string())
+    let path = m3u_playlist_path(playlist_dir, name);
+    Ok(read_m3u_file(&path)?.into_iter().map(|e| e.uri).collect())
+}
+
+/// Write `uris` to the M3U file at `path`, one per line, as MPD does.
+fn write_m3u_playlist(path: &Path, uris: &[String]) -> std::io::Result<()> {
+    let mut content = String::new();
+    for uri in uris {
+        content.push_str(uri);
+        content.push('\n');
+    }
+    std::fs::write(path, content)
+}
+
+/// Mirror the stored playlist `name` into the library database: its entries
+/// while a file of that name exists, nothing once it is gone. A failure only
+/// leaves the index stale, so it is logged instead of failing the command.
+fn index_stored_playlist(state: &AppState, playlist_dir: &str, name: &str) {
+    let Ok(db) = open_db(state, "playlist index") else {
+        return;
+    };
+    let result = match read_playlist(playlist_dir, name) {
+        Ok(entries) => {
+            let uris: Vec<String> = entries.into_iter().map(|e| e.uri).collect();
+            db.index_playlist(name, &uris)
+        }
+        Err(_) => match db.list_playlists() {
+            Ok(indexed) if indexed.iter().any(|p| p.name == name) => db.delete_playlist(name),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        },
+    };
+    if let Err(e) = result {
+        warn!("failed to index stored playlist \"{name}\": {e}");
+    }
+}
+
+/// Index every stored playlist file of the playlist directory in the
+/// library database and drop indexed playlists whose file is gone, so
+/// playlists written by MPD or by hand are known without a `save`. Returns
+/// the number of playlists indexed.
+pub fn index_playlist_directory(state: &AppState) -> usize {
+    let Some(playlist_dir) = &state.playlist_dir else {
+        return 0;
+    };
+    let Ok(dir) = std::fs::read_dir(playlist_dir) else {
+        return 0;
+    };
+    let names: HashSet<String> = dir
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let ext = path.extension()?.to_str()?;
+            if !STORED_PLAYLIST_EXTENSIONS.contains(&ext) {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_owned)
+        })
         .collect();
-    Ok(paths)
+    for name in &names {
+        index_stored_playlist(state, playlist_dir, name);
+    }
+    if let Ok(db) = open_db(state, "playlist index")
+        && let Ok(indexed) = db.list_playlists()
+    {
+        for stale in indexed.iter().filter(|p| !names.contains(&p.name)) {
+            if let Err(e) = db.delete_playlist(&stale.name) {
+                warn!("failed to unindex stored playlist \"{}\": {e}", stale.name);
+            }
+        }
+    }
+    names.len()
 }
 
-fn read_pls_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let path = std::path::Path::new(playlist_dir).join(format!("{name}.pls"));
-    let content = std::fs::read_to_string(&path).map_err(|_| "No such playlist".to_string())?;
+/// Entries of an .m3u file. The title of an `#EXTINF:<seconds>,<title>`
+/// line names the entry that follows it; other `#` lines are comments.
+fn read_m3u_file(path: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let content = std::fs::read_to_string(path).map_err(|_| "No such playlist".to_string())?;
+    let mut entries = Vec::new();
+    let mut name = None;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(info) = trimmed.strip_prefix("#EXTINF:") {
+            name = info
+                .split_once(',')
+                .map(|(_, title)| title.trim())
+                .filter(|title| !title.is_empty())
+                .map(str::to_owned);
+            continue;
+        }
+        if trimmed.starts_with('#') || trimmed.is_empty() {
+            continue;
+        }
+        entries.push(PlaylistEntry {
+            uri: line.to_string(),
+            name: name.take(),
+        });
+    }
+    Ok(entries)
+}
+
+fn read_pls_file(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|_| "No such playlist".to_string())?;
     let mut paths = Vec::new();
 
     for line in content.lines() {
@@ -78,9 +208,8 @@ fn extract_xml_tag_content(xml: &str, tag: &str) -> Vec<String> {
     results
 }
 
-fn read_xspf_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let path = std::path::Path::new(playlist_dir).join(format!("{name}.xspf"));
-    let content = std::fs::read_to_string(&path).map_err(|_| "No such playlist".to_string())?;
+fn read_xspf_file(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|_| "No such playlist".to_string())?;
 
     let mut paths = extract_xml_tag_content(&content, "location");
     if paths.is_empty() {
@@ -93,9 +222,8 @@ fn read_xspf_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, Str
         .collect())
 }
 
-fn read_asx_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let path = std::path::Path::new(playlist_dir).join(format!("{name}.asx"));
-    let content = std::fs::read_to_string(&path).map_err(|_| "No such playlist".to_string())?;
+fn read_asx_file(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|_| "No such playlist".to_string())?;
 
     // ASX: <REF HREF="..."/> or <ref href="..."/>
     let mut paths = Vec::new();
@@ -122,9 +250,10 @@ fn read_asx_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, Stri
     Ok(paths)
 }
 
-fn read_cue_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let cue_path = std::path::Path::new(playlist_dir).join(format!("{name}.cue"));
-    let content = std::fs::read_to_string(&cue_path).map_err(|_| "No such playlist".to_string())?;
+/// Audio files referenced by a `.cue` sheet, resolved against its directory.
+fn read_cue_file(cue_path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(cue_path).map_err(|_| "No such playlist".to_string())?;
+    let base = cue_path.parent().unwrap_or(Path::new(""));
     let mut paths = Vec::new();
 
     for line in content.lines() {
@@ -141,7 +270,7 @@ fn read_cue_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, Stri
                 let resolved = if file_path.is_absolute() {
                     file_path.to_path_buf()
                 } else {
-                    std::path::Path::new(playlist_dir).join(file_path)
+                    base.join(file_path)
                 };
                 let resolved_str = resolved.to_string_lossy().to_string();
                 if !paths.contains(&resolved_str) {
@@ -212,27 +341,92 @@ fn read_cue_tracks(
     Ok(out)
 }
 
-fn read_playlist(playlist_dir: &str, name: &str) -> Result<Vec<String>, String> {
-    let path_m3u = std::path::Path::new(playlist_dir).join(format!("{name}.m3u"));
-    let path_pls = std::path::Path::new(playlist_dir).join(format!("{name}.pls"));
-    let path_xspf = std::path::Path::new(playlist_dir).join(format!("{name}.xspf"));
-    let path_cue = std::path::Path::new(playlist_dir).join(format!("{name}.cue"));
-
-    let path_asx = std::path::Path::new(playlist_dir).join(format!("{name}.asx"));
-
-    if path_m3u.exists() {
-        read_m3u_playlist(playlist_dir, name)
-    } else if path_pls.exists() {
-        read_pls_playlist(playlist_dir, name)
-    } else if path_xspf.exists() {
-        read_xspf_playlist(playlist_dir, name)
-    } else if path_cue.exists() {
-        read_cue_playlist(playlist_dir, name)
-    } else if path_asx.exists() {
-        read_asx_playlist(playlist_dir, name)
-    } else {
-        Err(format!("No such playlist: {name}"))
+/// Extensions of stored playlists, in the order `read_playlist` prefers them
+/// when several files share a name.
+pub(super) const STORED_PLAYLIST_EXTENSIONS: &[&str] =
+    &["m3u", "m3u8", "pls", "xspf", "cue", "asx"];
+
+fn read_playlist(playlist_dir: &str, name: &str) -> Result<Vec<PlaylistEntry>, String> {
+    STORED_PLAYLIST_EXTENSIONS
+        .iter()
+        .map(|ext| Path::new(playlist_dir).join(format!("{name}.{ext}")))
+        .find(|path| path.exists())
+        .map_or_else(
+            || Err(format!("No such playlist: {name}")),
+            |path| read_playlist_file(&path),
+        )
+}
+
+/// Read any supported playlist file, picking the parser by extension.
+fn read_playlist_file(path: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let uris = match ext.as_deref() {
+        Some("m3u" | "m3u8") => return read_m3u_file(path),
+        Some("pls") => read_pls_file(path)?,
+        Some("xspf") => read_xspf_file(path)?,
+        Some("cue") => read_cue_file(path)?,
+        Some("asx") => read_asx_file(path)?,
+        _ => return Err("No such playlist".to_string()),
+    };
+    Ok(uris.into_iter().map(PlaylistEntry::from).collect())
+}
+
+/// Entries of the playlist `name` as `listplaylist`, `listplaylistinfo` and
+/// `load` see it. Stored playlists win and may sit in subdirectories of the
+/// playlist directory (`rock/best`); otherwise `name` is the URI of a
+/// playlist file inside the music directory (`Album/list.m3u`), whose
+/// relative entries resolve against the playlist's own directory.
+fn read_named_playlist(state: &AppState, name: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let uri = Path::new(name);
+    if !uri.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err("Bad playlist name".to_string());
+    }
+
+    if let Some(playlist_dir) = &state.playlist_dir
+        && let Ok(paths) = read_playlist(playlist_dir, name)
+    {
+        return Ok(paths);
+    }
+
+    if let Some(music_dir) = state.music_dir()
+        && rmpd_library::scanner::is_playlist_file(uri)
+    {
+        let music_dir = Path::new(&music_dir);
+        if let Ok(file) = state.resolve_client_path(name)
+            && file.is_file()
+        {
+            let base = uri.parent().unwrap_or(Path::new(""));
+            return Ok(read_playlist_file(&file)?
+                .into_iter()
+                .map(|entry| PlaylistEntry {
+                    uri: resolve_music_dir_entry(music_dir, base, &entry.uri),
+                    ..entry
+                })
+                .collect());
+        }
     }
+
+    Err("No such playlist".to_string())
+}
+
+/// Turn an entry of a playlist in the music directory into a song URI:
+/// remote URIs stay as they are, absolute paths inside the music directory
+/// become relative, and relative paths are taken from the playlist's `base`.
+fn resolve_music_dir_entry(music_dir: &Path, base: &Path, entry: &str) -> String {
+    if rmpd_core::path::is_uri(entry) {
+        return entry.to_string();
+    }
+    let path = Path::new(entry);
+    if path.is_absolute() {
+        return path
+            .strip_prefix(music_dir)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| entry.to_string());
+    }
+    base.join(path).to_string_lossy().into_owned()
 }
 
 pub async fn handle_listplaylists_command(state: &AppState) -> String {
@@ -268,10 +462,8 @@ pub async fn handle_listplaylists_command(state: &AppState) -> String {
         for entry in dir.flatten() {
             let path = entry.path();
             let ext = path.extension().and_then(|e| e.to_str());
-            if matches!(
-                ext,
-                Some("m3u") | Some("pls") | Some("xspf") | Some("cue") | Some("asx")
-            ) && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            if ext.is_some_and(|ext| STORED_PLAYLIST_EXTENSIONS.contains(&ext))
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
             {
                 let mtime = entry
                     .metadata()
@@ -319,7 +511,7 @@ pub async fn handle_save_command(
             );
         }
     };
-    let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
+    let pl_path = m3u_playlist_path(&playlist_dir, name);
     let mode = mode.unwrap_or(SaveMode::Replace);
 
     // Enforce mode preconditions (matching MPD's PlaylistSave.cxx spl_save_queue)
@@ -355,6 +547,7 @@ pub async fn handle_save_command(
     };
 
     let name_owned = name.to_string();
+    let index_state = state.clone();
     let result = tokio::task::spawn_blocking(move || {
         // For append mode, prepend existing paths
         let paths_to_write: Vec<String> = if matches!(mode, SaveMode::Append) {
@@ -365,18 +558,9 @@ pub async fn handle_save_command(
             new_paths
         };
 
-        // Write the .m3u file
-        let content = paths_to_write
-            .iter()
-            .map(|p| p.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let content = if content.is_empty() {
-            content
-        } else {
-            content + "\n"
-        };
-        std::fs::write(&pl_path, &content)
+        write_m3u_playlist(&pl_path, &paths_to_write)?;
+        index_stored_playlist(&index_state, &playlist_dir, &name_owned);
+        Ok::<_, std::io::Error>(())
     })
     .await;
 
@@ -419,6 +603,7 @@ pub async fn handle_load_command(
         let p = |ext: &str| Path::new(&playlist_dir).join(format!("{name}.{ext}"));
         p("cue").exists()
             && !p("m3u").exists()
+            && !p("m3u8").exists()
             && !p("pls").exists()
             && !p("xspf").exists()
             && !p("asx").exists()
@@ -428,17 +613,16 @@ pub async fn handle_load_command(
     }
 
     let state_clone = state.clone();
-    let playlist_dir_clone = playlist_dir.clone();
     let name_owned = name.to_string();
     let songs = match tokio::task::spawn_blocking(move || {
-        let mut paths = read_playlist(&playlist_dir_clone, &name_owned)
+        let mut paths = read_named_playlist(&state_clone, &name_owned)
             .map_err(|e| ResponseBuilder::error(ACK_ERROR_SYS, 0, "load", &e))?;
 
         // Apply range filter if specified
         if let Some((start, end)) = range {
             let start = start as usize;
             let end = (end as usize).min(paths.len());
-            if start <= paths.len() {
+            if start <= end {
                 paths = paths[start..end].to_vec();
             } else {
                 return Err(ResponseBuilder::error(
@@ -450,11 +634,25 @@ pub async fn handle_load_command(
             }
         }
 
-        // Look up songs from DB; fall back to stub Song if not found
+        // Look up songs from DB; entries it does not know are streams (probed
+        // once queued) or local files not scanned yet (tags read from disk).
         let db = open_db(&state_clone, "load")?;
         let songs: Vec<rmpd_core::song::Song> = paths
             .iter()
-            .filter_map(|path| db.get_song_by_path(path).ok().flatten())
+            .filter_map(|entry| {
+                let path = &entry.uri;
+                let mut song = match db.get_song_by_path(path).ok().flatten() {
+                    Some(song) => song,
+                    None if rmpd_stream::is_http_uri(path) => {
+                        crate::helpers::create_stream_song(path)
+                    }
+                    None => read_unscanned_song(&state_clone, "load", path)
+                        .ok()
+                        .flatten()?,
+                };
+                apply_entry_name(&mut song, entry.name.as_deref());
+                Some(song)
+            })
             .collect();
         Ok(songs)
     })
@@ -465,20 +663,25 @@ pub async fn handle_load_command(
         Err(_) => return ResponseBuilder::error(ACK_ERROR_SYS, 0, "load", "internal error"),
     };
 
+    let mut streams = Vec::new();
     {
         let mut queue = state.queue.write().await;
-        if let Some(pos) = position {
-            for (i, song) in songs.into_iter().enumerate() {
-                queue.add_at(song, Some(pos + i as u32));
-            }
-        } else {
-            for song in songs {
-                queue.add(song);
+        for (i, song) in songs.into_iter().enumerate() {
+            let uri = song.path.to_string();
+            let id = match position {
+                Some(pos) => queue.add_at(song, Some(pos + i as u32)),
+                None => queue.add(song),
+            };
+            if rmpd_stream::is_http_uri(&uri) {
+                streams.push((id, uri));
             }
         }
     }
 
     crate::helpers::update_playlist_version(state).await;
+    for (id, uri) in streams {
+        crate::helpers::spawn_stream_probe(state, id, &uri);
+    }
     ResponseBuilder::new().ok()
 }
 
@@ -572,7 +775,7 @@ pub async fn handle_searchaddpl_command(
             }
         };
 
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
         let mut paths = if pl_path.exists() {
             read_m3u_playlist(&playlist_dir, &name).unwrap_or_default()
         } else {
@@ -581,18 +784,9 @@ pub async fn handle_searchaddpl_command(
         for song in &songs {
             paths.push(song.path.to_string());
         }
-        let content = paths
-            .iter()
-            .map(|p| p.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let content = if content.is_empty() {
-            content
-        } else {
-            content + "\n"
-        };
-        match std::fs::write(&pl_path, &content) {
+        match write_m3u_playlist(&pl_path, &paths) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -613,36 +807,18 @@ pub async fn handle_listplaylist_command(
     name: &str,
     range: Option<(u32, u32)>,
 ) -> String {
-    let playlist_dir = match &state.playlist_dir {
-        Some(d) => d.clone(),
-        None => {
-            return ResponseBuilder::error(
-                ACK_ERROR_SYS,
-                0,
-                "listplaylist",
-                "playlist directory not configured",
-            );
-        }
-    };
+    let state = state.clone();
     let name = name.to_string();
 
     match tokio::task::spawn_blocking(move || {
-        let paths = match read_m3u_playlist(&playlist_dir, &name) {
+        let paths = match read_named_playlist(&state, &name) {
             Ok(p) => p,
             Err(e) => return ResponseBuilder::error(ACK_ERROR_SYS, 0, "listplaylist", &e),
         };
 
-        let total = paths.len();
-        let (start, end) = if let Some((s, e)) = range {
-            (s as usize, (e as usize).min(total))
-        } else {
-            (0, total)
-        };
-        let slice = &paths[start.min(total)..end.min(total)];
-
         let mut resp = ResponseBuilder::new();
-        for path in slice {
-            resp.field("file", path);
+        for entry in apply_range(&paths, range) {
+            resp.field("file", &entry.uri);
         }
         resp.ok()
     })
@@ -656,23 +832,13 @@ pub async fn handle_listplaylistinfo_command(
     state: &AppState,
     name: &str,
     range: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
     let state = state.clone();
     let name = name.to_string();
+    let enabled_tags = enabled_tags.cloned();
     match tokio::task::spawn_blocking(move || {
-        let playlist_dir = match &state.playlist_dir {
-            Some(d) => d.clone(),
-            None => {
-                return ResponseBuilder::error(
-                    ACK_ERROR_SYS,
-                    0,
-                    "listplaylistinfo",
-                    "playlist directory not configured",
-                );
-            }
-        };
-
-        let paths = match read_m3u_playlist(&playlist_dir, &name) {
+        let paths = match read_named_playlist(&state, &name) {
             Ok(p) => p,
             Err(e) => return ResponseBuilder::error(ACK_ERROR_SYS, 0, "listplaylistinfo", &e),
         };
@@ -681,23 +847,21 @@ pub async fn handle_listplaylistinfo_command(
             Err(e) => return e,
         };
 
-        let total = paths.len();
-        let (start, end) = if let Some((s, e)) = range {
-            (s as usize, (e as usize).min(total))
-        } else {
-            (0, total)
-        };
-        let slice = &paths[start.min(total)..end.min(total)];
-
-        let mut resp = ResponseBuilder::new();
-        for path in slice {
-            match db.find_songs("file", path) {
-                Ok(songs) if !songs.is_empty() => {
-                    resp.song(&songs[0], None, None);
+        // The range counts entries in file order, whether or not the
+        // database knows them.
+        let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
+        for entry in apply_range(&paths, range) {
+            match db.get_song_by_path(&entry.uri) {
+                Ok(Some(mut song)) => {
+                    apply_entry_name(&mut song, entry.name.as_deref());
+                    resp.song(&song, None, None);
                 }
                 _ => {
                     // Song not in DB — emit just the file path like MPD does for unknown tracks
-                    resp.field("file", path);
+                    resp.field("file", &entry.uri);
+                    if let Some(name) = &entry.name {
+                        resp.field("Name", name);
+                    }
                 }
             }
         }
@@ -757,7 +921,7 @@ pub async fn handle_playlistadd_command(
             }
         };
 
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
         let mut paths = if pl_path.exists() {
             read_m3u_playlist(&playlist_dir, &name).unwrap_or_default()
         } else {
@@ -776,18 +940,9 @@ pub async fn handle_playlistadd_command(
             paths.extend(new_paths);
         }
 
-        let content = paths
-            .iter()
-            .map(|p| p.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let content = if content.is_empty() {
-            content
-        } else {
-            content + "\n"
-        };
-        match std::fs::write(&pl_path, &content) {
+        match write_m3u_playlist(&pl_path, &paths) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -818,7 +973,7 @@ pub async fn handle_playlistclear_command(state: &AppState, name: &str) -> Strin
     let state = state.clone();
     let name = name.to_string();
     match tokio::task::spawn_blocking(move || {
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
         if !pl_path.exists() {
             return ResponseBuilder::error(
                 ACK_ERROR_NO_EXIST,
@@ -829,6 +984,7 @@ pub async fn handle_playlistclear_command(state: &AppState, name: &str) -> Strin
         }
         match std::fs::write(&pl_path, "") {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -868,19 +1024,10 @@ pub async fn handle_playlistdelete_command(state: &AppState, name: &str, positio
             return ResponseBuilder::error(ACK_ERROR_ARG, 0, "playlistdelete", "Bad song index");
         }
         paths.remove(pos);
-        let content = paths
-            .iter()
-            .map(|p| p.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let content = if content.is_empty() {
-            content
-        } else {
-            content + "\n"
-        };
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
-        match std::fs::write(&pl_path, &content) {
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
+        match write_m3u_playlist(&pl_path, &paths) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -928,19 +1075,10 @@ pub async fn handle_playlistmove_command(
         let song = paths.remove(from);
         let insert_pos = if to > from { to - 1 } else { to };
         paths.insert(insert_pos.min(paths.len()), song);
-        let content = paths
-            .iter()
-            .map(|p| p.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let content = if content.is_empty() {
-            content
-        } else {
-            content + "\n"
-        };
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
-        match std::fs::write(&pl_path, &content) {
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
+        match write_m3u_playlist(&pl_path, &paths) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -971,12 +1109,13 @@ pub async fn handle_rm_command(state: &AppState, name: &str) -> String {
     let state = state.clone();
     let name = name.to_string();
     match tokio::task::spawn_blocking(move || {
-        let pl_path = Path::new(&playlist_dir).join(format!("{name}.m3u"));
+        let pl_path = m3u_playlist_path(&playlist_dir, &name);
         if !pl_path.exists() {
             return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "rm", "No such playlist");
         }
         match std::fs::remove_file(&pl_path) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &name);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -1006,16 +1145,25 @@ pub async fn handle_rename_command(state: &AppState, from: &str, to: &str) -> St
     let from = from.to_string();
     let to = to.to_string();
     match tokio::task::spawn_blocking(move || {
-        let from_path = Path::new(&playlist_dir).join(format!("{from}.m3u"));
-        let to_path = Path::new(&playlist_dir).join(format!("{to}.m3u"));
+        let from_path = m3u_playlist_path(&playlist_dir, &from);
         if !from_path.exists() {
             return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "rename", "No such playlist");
         }
-        if to_path.exists() {
+        if m3u_playlist_path(&playlist_dir, &to).exists() {
             return ResponseBuilder::error(ACK_ERROR_EXIST, 0, "rename", "Playlist exists already");
         }
+        // The playlist keeps its `.m3u` or `.m3u8` extension.
+        let to_path = from_path.with_file_name(format!(
+            "{to}.{}",
+            from_path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("m3u")
+        ));
         match std::fs::rename(&from_path, &to_path) {
             Ok(_) => {
+                index_stored_playlist(&state, &playlist_dir, &from);
+                index_stored_playlist(&state, &playlist_dir, &to);
                 notify_stored_playlist(&state);
                 ResponseBuilder::new().ok()
             }
@@ -1035,11 +1183,13 @@ pub async fn handle_searchplaylist_command(
     name: &str,
     tag: &str,
     value: &str,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
     let state = state.clone();
     let name = name.to_string();
     let tag = tag.to_string();
     let value = value.to_string();
+    let enabled_tags = enabled_tags.cloned();
     match tokio::task::spawn_blocking(move || {
         let playlist_dir = match &state.playlist_dir {
             Some(d) => d.clone(),
@@ -1068,7 +1218,7 @@ pub async fn handle_searchplaylist_command(
             Err(e) => return e,
         };
 
-        let mut resp = ResponseBuilder::new();
+        let mut resp = ResponseBuilder::with_tags(enabled_tags.as_ref());
         let value_lower = value.to_lowercase();
         let tag_lower = tag.to_lowercase();
         for path in &paths {
@@ -1,5 +1,7 @@
 //! Queue (current playlist) manipulation and inspection commands
 
+use std::collections::HashSet;
+
 use tracing::debug;
 
 use crate::commands::playback;
@@ -8,8 +10,8 @@ use crate::response::ResponseBuilder;
 use crate::state::AppState;
 
 use super::utils::{
-    ACK_ERROR_ARG, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, add_queue_item_metadata, apply_range,
-    open_db, prepare_song_for_playback, update_next_song,
+    ACK_ERROR_ARG, ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, add_queue_item_metadata, open_db, path_error,
+    prepare_song_for_playback, read_unscanned_song, update_next_song,
 };
 
 pub async fn handle_add_command(state: &AppState, uri: &str, position: Option<u32>) -> String {
@@ -25,45 +27,16 @@ pub async fn handle_add_command(state: &AppState, uri: &str, position: Option<u3
         if scheme != "file" {
             let stream_song = helpers::create_stream_song(uri);
             // `add` returns no Id (unlike `addid`) — MPD replies with bare OK.
-            state.queue.write().await.add_at(stream_song, position);
+            let id = state.queue.write().await.add_at(stream_song, position);
             helpers::update_playlist_version(state).await;
+            helpers::spawn_stream_probe(state, id, uri);
             return ResponseBuilder::new().ok();
         }
     }
-    // Get song from database (file:// or relative path) — run the blocking
-    // DB open + query on a blocking-pool thread so it never stalls the async
-    // runtime. The closure returns either the resolved `Song` or the fully
-    // formatted error response, matching the original inline error handling.
-    let state_clone = state.clone();
-    let uri_owned = uri.to_string();
-    let song_result: Result<rmpd_core::song::Song, String> =
-        match tokio::task::spawn_blocking(move || {
-            let db = open_db(&state_clone, "add")?;
-            match db.get_song_by_path(&uri_owned) {
-                Ok(Some(s)) => Ok(s),
-                Ok(None) => Err(ResponseBuilder::error(
-                    ACK_ERROR_NO_EXIST,
-                    0,
-                    "add",
-                    "No such directory",
-                )),
-                Err(e) => Err(ResponseBuilder::error(
-                    ACK_ERROR_SYS,
-                    0,
-                    "add",
-                    &format!("query error: {e}"),
-                )),
-            }
-        })
-        .await
-        {
-            Ok(res) => res,
-            Err(_) => {
-                return ResponseBuilder::error(ACK_ERROR_SYS, 0, "add", "internal error");
-            }
-        };
-
-    let song = match song_result {
+    if let Err(e) = rmpd_core::path::check_uri(uri) {
+        return path_error("add", e);
+    }
+    let song = match lookup_song(state, "add", "No such directory", uri).await {
         Ok(s) => s,
         Err(resp) => return resp,
     };
@@ -75,6 +48,51 @@ pub async fn handle_add_command(state: &AppState, uri: &str, position: Option<u3
     ResponseBuilder::new().ok()
 }
 
+/// Find the song `add`/`addid` should queue for a local URI.
+///
+/// The database is consulted first. Like MPD, a file under the music directory
+/// that has not been scanned yet is still accepted, with its tags read on the
+/// fly; only a URI that is neither in the database nor on disk is answered
+/// with `not_found` (`ACK_ERROR_NO_EXIST`). The blocking DB query and tag
+/// read run on a blocking-pool thread; errors come back as the formatted ACK.
+async fn lookup_song(
+    state: &AppState,
+    command: &'static str,
+    not_found: &'static str,
+    uri: &str,
+) -> Result<rmpd_core::song::Song, String> {
+    let state = state.clone();
+    let uri = uri.to_string();
+    tokio::task::spawn_blocking(move || {
+        let no_exist = || ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, command, not_found);
+        if state.db_pool.is_some() {
+            let db = open_db(&state, command)?;
+            match db.get_song_by_path(&uri) {
+                Ok(Some(s)) => return Ok(s),
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(ResponseBuilder::error(
+                        ACK_ERROR_SYS,
+                        0,
+                        command,
+                        &format!("query error: {e}"),
+                    ));
+                }
+            }
+        }
+        read_unscanned_song(&state, command, &uri)?.ok_or_else(no_exist)
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(ResponseBuilder::error(
+            ACK_ERROR_SYS,
+            0,
+            command,
+            "internal error",
+        ))
+    })
+}
+
 pub async fn handle_clear_command(state: &AppState) -> String {
     state.queue.write().await.clear();
     state.engine.write().await.stop().await.ok();
@@ -103,7 +121,8 @@ pub async fn handle_delete_command(
             }
             if queue.delete(position).is_some() {
                 drop(queue);
-                helpers::update_playlist_version(state).await;
+                let removed = helpers::update_playlist_version(state).await;
+                helpers::stop_if_current_removed(state, removed).await;
                 ResponseBuilder::new().ok()
             } else {
                 ResponseBuilder::error(ACK_ERROR_ARG, 0, "delete", "Bad song index")
@@ -127,7 +146,8 @@ pub async fn handle_delete_command(
                 queue.delete(pos);
             }
             drop(queue);
-            helpers::update_playlist_version(state).await;
+            let removed = helpers::update_playlist_version(state).await;
+            helpers::stop_if_current_removed(state, removed).await;
             ResponseBuilder::new().ok()
         }
     }
@@ -150,45 +170,16 @@ pub async fn handle_addid_command(state: &AppState, uri: &str, position: Option<
             let stream_song = helpers::create_stream_song(uri);
             let id = state.queue.write().await.add_at(stream_song, position);
             helpers::update_playlist_version(state).await;
+            helpers::spawn_stream_probe(state, id, uri);
             let mut resp = ResponseBuilder::new();
             resp.field("Id", id);
             return resp.ok();
         }
     }
-    // Get song from database (file:// or relative path) — run the blocking
-    // DB open + query on a blocking-pool thread so it never stalls the async
-    // runtime. The closure returns either the resolved `Song` or the fully
-    // formatted error response, matching the original inline error handling.
-    let state_clone = state.clone();
-    let uri_owned = uri.to_string();
-    let song_result: Result<rmpd_core::song::Song, String> =
-        match tokio::task::spawn_blocking(move || {
-            let db = open_db(&state_clone, "addid")?;
-            match db.get_song_by_path(&uri_owned) {
-                Ok(Some(s)) => Ok(s),
-                Ok(None) => Err(ResponseBuilder::error(
-                    ACK_ERROR_NO_EXIST,
-                    0,
-                    "addid",
-                    "No such song",
-                )),
-                Err(e) => Err(ResponseBuilder::error(
-                    ACK_ERROR_SYS,
-                    0,
-                    "addid",
-                    &format!("query error: {e}"),
-                )),
-            }
-        })
-        .await
-        {
-            Ok(res) => res,
-            Err(_) => {
-                return ResponseBuilder::error(ACK_ERROR_SYS, 0, "addid", "internal error");
-            }
-        };
-
-    let song = match song_result {
+    if let Err(e) = rmpd_core::path::check_uri(uri) {
+        return path_error("addid", e);
+    }
+    let song = match lookup_song(state, "addid", "No such song", uri).await {
         Ok(s) => s,
         Err(resp) => return resp,
     };
@@ -204,7 +195,8 @@ pub async fn handle_addid_command(state: &AppState, uri: &str, position: Option<
 
 pub async fn handle_deleteid_command(state: &AppState, id: u32) -> String {
     if state.queue.write().await.delete_id(id).is_some() {
-        helpers::update_playlist_version(state).await;
+        let removed = helpers::update_playlist_version(state).await;
+        helpers::stop_if_current_removed(state, removed).await;
         ResponseBuilder::new().ok()
     } else {
         ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "deleteid", "No such song")
@@ -316,23 +308,45 @@ pub async fn handle_swapid_command(state: &AppState, id1: u32, id2: u32) -> Stri
 }
 
 pub async fn handle_shuffle_command(state: &AppState, range: Option<(u32, u32)>) -> String {
-    if let Some((start, end)) = range {
-        state.queue.write().await.shuffle_range(start, end);
-    } else {
-        state.queue.write().await.shuffle();
+    // MPD keeps the playing song in the queue's shuffled range at the front
+    // of that range rather than moving it somewhere random.
+    let playing = !matches!(
+        rmpd_core::state::PlayerState::from_atomic(
+            state
+                .atomic_state
+                .load(std::sync::atomic::Ordering::Acquire)
+        ),
+        rmpd_core::state::PlayerState::Stop
+    );
+    let current = state
+        .status
+        .read()
+        .await
+        .current_song
+        .filter(|_| playing)
+        .map(|c| c.position);
+    {
+        let mut queue = state.queue.write().await;
+        let (start, end) = range.unwrap_or((0, queue.len() as u32));
+        queue.shuffle_around(start, end, current);
     }
     helpers::update_playlist_version(state).await;
     ResponseBuilder::new().ok()
 }
 
-pub async fn handle_playlistid_command(state: &AppState, id: Option<u32>) -> String {
+/// List the queue, or only song `id`.
+pub async fn handle_playlistid_command(
+    state: &AppState,
+    id: Option<u32>,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let queue = state.queue.read().await;
-    let mut resp = ResponseBuilder::new();
+    let mut resp = ResponseBuilder::with_tags(enabled_tags);
 
     if let Some(song_id) = id {
         // Get specific song by ID
         if let Some(item) = queue.get_by_id(song_id) {
-            resp.song(&item.song, Some(item.position), Some(item.id));
+            resp.song(&item.tagged_song(), Some(item.position), Some(item.id));
             add_queue_item_metadata(&mut resp, item);
         } else {
             return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "playlistid", "No such song");
@@ -340,7 +354,7 @@ pub async fn handle_playlistid_command(state: &AppState, id: Option<u32>) -> Str
     } else {
         // Get all songs with IDs
         for item in queue.items() {
-            resp.song(&item.song, Some(item.position), Some(item.id));
+            resp.song(&item.tagged_song(), Some(item.position), Some(item.id));
             add_queue_item_metadata(&mut resp, item);
         }
     }
@@ -348,17 +362,19 @@ pub async fn handle_playlistid_command(state: &AppState, id: Option<u32>) -> Str
     resp.ok()
 }
 
-pub async fn handle_playlistinfo_command(state: &AppState, range: Option<(u32, u32)>) -> String {
+/// List the queue, optionally limited to a position range.
+pub async fn handle_playlistinfo_command(
+    state: &AppState,
+    range: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let queue = state.queue.read().await;
-    let items = queue.items();
-    let mut resp = ResponseBuilder::new();
+    let mut resp = ResponseBuilder::with_tags(enabled_tags);
 
-    // MPD returns empty for out-of-bounds positions (apply_range handles slicing).
-
-    let filtered = apply_range(items, range);
-
-    for item in filtered {
-        resp.song(&item.song, Some(item.position), Some(item.id));
+    // MPD returns empty for out-of-bounds positions; only the requested
+    // positions are visited, however long the queue.
+    for item in queue.items_in(range) {
+        resp.song(&item.tagged_song(), Some(item.position), Some(item.id));
         add_queue_item_metadata(&mut resp, item);
     }
 
@@ -377,7 +393,8 @@ pub async fn handle_playid_command(state: &AppState, id: Option<u32>) -> String
 
             let playback_song = match prepare_song_for_playback(
                 &song,
-                state.music_dir.as_deref(),
+                state.music_dir().as_deref(),
+                &state.music_roots,
                 range,
                 &state.sources,
             )
@@ -451,12 +468,20 @@ pub async fn handle_prio_command(state: &AppState, priority: u8, ranges: &[(u32,
         queue.set_priority_range(priority, ranges);
         queue.version()
     };
-    state.status.write().await.playlist_version = version;
+    set_priority_version(state, version).await;
     state.event_bus.emit(rmpd_core::event::Event::QueueChanged);
 
     ResponseBuilder::new().ok()
 }
 
+/// Publish the queue version after a priority change and re-pick `nextsong`,
+/// which favours higher priorities in random mode.
+async fn set_priority_version(state: &AppState, version: u32) {
+    let mut status = state.status.write().await;
+    status.playlist_version = version;
+    helpers::sync_queue_positions(&mut status, &*state.queue.read().await);
+}
+
 /// Set priority for songs in queue by ID
 ///
 /// Sets the priority for all songs with the specified IDs.
@@ -479,7 +504,7 @@ pub async fn handle_prioid_command(state: &AppState, priority: u8, ids: &[u32])
     };
 
     if changed {
-        state.status.write().await.playlist_version = version;
+        set_priority_version(state, version).await;
         state.event_bus.emit(rmpd_core::event::Event::QueueChanged);
     }
 
@@ -488,11 +513,17 @@ pub async fn handle_prioid_command(state: &AppState, priority: u8, ids: &[u32])
 
 /// Set playback range for a song
 ///
-/// Sets a playback range (start and end time in seconds) for a song.
-pub async fn handle_rangeid_command(state: &AppState, id: u32, range: (f64, f64)) -> String {
+/// Sets a playback range (start and end time in seconds) for a song, or
+/// clears it with `None`. Playback seeks to the start and moves on at the
+/// end; an end of 0 plays to the end of the song.
+pub async fn handle_rangeid_command(
+    state: &AppState,
+    id: u32,
+    range: Option<(f64, f64)>,
+) -> String {
     let found = {
         let mut queue = state.queue.write().await;
-        queue.set_range_by_id(id, Some(range))
+        queue.set_range_by_id(id, range)
     };
 
     if found {
@@ -505,8 +536,10 @@ pub async fn handle_rangeid_command(state: &AppState, id: u32, range: (f64, f64)
 
 /// Add a tag to a queue item
 ///
-/// Adds a custom tag to a queue item.
-pub async fn handle_addtagid_command(state: &AppState, id: u32, tag: &str, _value: &str) -> String {
+/// The value is kept with the queue item only (neither the file nor the
+/// database changes) and replaces the song's own values of the tag in queue
+/// listings, e.g. to name a stream that carries no metadata.
+pub async fn handle_addtagid_command(state: &AppState, id: u32, tag: &str, value: &str) -> String {
     // Validate tag type
     if rmpd_core::song::canonical_tag_name(&tag.to_lowercase()) == "Unknown" {
         return ResponseBuilder::error(
@@ -517,18 +550,19 @@ pub async fn handle_addtagid_command(state: &AppState, id: u32, tag: &str, _valu
         );
     }
 
-    // Check song exists
-    let queue = state.queue.read().await;
-    if queue.get_by_id(id).is_none() {
+    let found = state
+        .queue
+        .write()
+        .await
+        .add_tag_by_id(id, tag, value.to_owned());
+    if !found {
         return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "addtagid", "No such song");
     }
-    drop(queue);
-
-    // MPD allows tag editing on queued songs (in-memory only)
+    helpers::update_playlist_version(state).await;
     ResponseBuilder::new().ok()
 }
 
-/// Clear tags from a queue item
+/// Clear tags added with `addtagid` from a queue item
 ///
 /// If tag is specified, clears only that tag. Otherwise clears all tags.
 pub async fn handle_cleartagid_command(state: &AppState, id: u32, tag: Option<&str>) -> String {
@@ -547,37 +581,28 @@ pub async fn handle_cleartagid_command(state: &AppState, id: u32, tag: Option<&s
         );
     }
 
-    // Check song exists
-    let queue = state.queue.read().await;
-    if queue.get_by_id(id).is_none() {
+    if !state.queue.write().await.clear_tags_by_id(id, tag) {
         return ResponseBuilder::error(ACK_ERROR_NO_EXIST, 0, "cleartagid", "No such song");
     }
-    drop(queue);
-
-    // MPD allows clearing tags on queued songs (in-memory only)
+    helpers::update_playlist_version(state).await;
     ResponseBuilder::new().ok()
 }
 
 /// Return changes in queue since version
 ///
 /// MPD protocol: version 0 means "give me current playlist"
-/// Otherwise, return items if playlist has changed since given version
+/// Otherwise, return the items added, moved or edited since given version
 pub async fn handle_plchanges_command(
     state: &AppState,
     version: u32,
     range: Option<(u32, u32)>,
+    enabled_tags: Option<&HashSet<String>>,
 ) -> String {
-    let current_version = state.status.read().await.playlist_version;
     let queue = state.queue.read().await;
-    let mut resp = ResponseBuilder::new();
+    let mut resp = ResponseBuilder::with_tags(enabled_tags);
 
-    if version == 0 || current_version > version {
-        let items = queue.items();
-        let filtered = apply_range(items, range);
-
-        for item in filtered {
-            resp.song(&item.song, Some(item.position), Some(item.id));
-        }
+    for item in queue.changes_since(version, range) {
+        resp.song(&item.tagged_song(), Some(item.position), Some(item.id));
     }
     resp.ok()
 }
@@ -585,37 +610,37 @@ pub async fn handle_plchanges_command(
 /// Return position/id changes since version
 ///
 /// MPD protocol: version 0 means "give me current playlist"
-/// Otherwise, return items if playlist has changed since given version
+/// Otherwise, return the items added, moved or edited since given version
 pub async fn handle_plchangesposid_command(
     state: &AppState,
     version: u32,
     range: Option<(u32, u32)>,
 ) -> String {
-    let current_version = state.status.read().await.playlist_version;
     let queue = state.queue.read().await;
     let mut resp = ResponseBuilder::new();
 
-    if version == 0 || current_version > version {
-        let items = queue.items();
-        let filtered = apply_range(items, range);
-
-        for item in filtered {
-            resp.field("cpos", item.position.to_string());
-            resp.field("Id", item.id.to_string());
-        }
+    for item in queue.changes_since(version, range) {
+        resp.field("cpos", item.position.to_string());
+        resp.field("Id", item.id.to_string());
     }
     resp.ok()
 }
 
 /// Search queue for exact tag matches
-pub async fn handle_playlistfind_command(state: &AppState, tag: &str, value: &str) -> String {
+pub async fn handle_playlistfind_command(
+    state: &AppState,
+    tag: &str,
+    value: &str,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let queue = state.queue.read().await;
-    let mut resp = ResponseBuilder::new();
+    let mut resp = ResponseBuilder::with_tags(enabled_tags);
     let tag_lower = tag.to_lowercase();
 
     for item in queue.items() {
-        if item.song.tag_eq(&tag_lower, value) {
-            resp.song(&item.song, Some(item.position), Some(item.id));
+        let song = item.tagged_song();
+        if song.tag_eq(&tag_lower, value) {
+            resp.song(&song, Some(item.position), Some(item.id));
             add_queue_item_metadata(&mut resp, item);
         }
     }
@@ -623,15 +648,21 @@ pub async fn handle_playlistfind_command(state: &AppState, tag: &str, value: &st
 }
 
 /// Case-insensitive search in queue
-pub async fn handle_playlistsearch_command(state: &AppState, tag: &str, value: &str) -> String {
+pub async fn handle_playlistsearch_command(
+    state: &AppState,
+    tag: &str,
+    value: &str,
+    enabled_tags: Option<&HashSet<String>>,
+) -> String {
     let queue = state.queue.read().await;
-    let mut resp = ResponseBuilder::new();
+    let mut resp = ResponseBuilder::with_tags(enabled_tags);
     let value_lower = value.to_lowercase();
     let tag_lower = tag.to_lowercase();
 
     for item in queue.items() {
-        if item.song.tag_contains(&tag_lower, &value_lower) {
-            resp.song(&item.song, Some(item.position), Some(item.id));
+        let song = item.tagged_song();
+        if song.tag_contains(&tag_lower, &value_lower) {
+            resp.song(&song, Some(item.position), Some(item.id));
             add_queue_item_metadata(&mut resp, item);
         }
     }
@@ -3,200 +3,71 @@
 //! These commands allow clients to query the server's capabilities,
 //! supported commands, tag types, decoders, and URL handlers.
 
-use crate::connection::{
-    ConnectionState, PERMISSION_ADD, PERMISSION_ADMIN, PERMISSION_CONTROL, PERMISSION_NONE,
-    PERMISSION_READ,
-};
+use crate::connection::{ConnectionState, TAG_TYPE_NAMES};
+use crate::registry;
 use crate::response::ResponseBuilder;
 
-const COMMAND_PERMISSIONS: &[(&str, u8)] = &[
-    ("add", PERMISSION_ADD),
-    ("addid", PERMISSION_ADD),
-    ("addtagid", PERMISSION_CONTROL),
-    ("albumart", PERMISSION_READ),
-    ("binarylimit", PERMISSION_NONE),
-    ("channels", PERMISSION_READ),
-    ("clear", PERMISSION_CONTROL),
-    ("clearerror", PERMISSION_CONTROL),
-    ("cleartagid", PERMISSION_CONTROL),
-    ("close", PERMISSION_NONE),
-    ("commands", PERMISSION_NONE),
-    ("config", PERMISSION_ADMIN),
-    ("consume", PERMISSION_CONTROL),
-    ("count", PERMISSION_READ),
-    ("crossfade", PERMISSION_CONTROL),
-    ("currentsong", PERMISSION_READ),
-    ("decoders", PERMISSION_READ),
-    ("delete", PERMISSION_CONTROL),
-    ("deleteid", PERMISSION_CONTROL),
-    ("delpartition", PERMISSION_ADMIN),
-    ("disableoutput", PERMISSION_ADMIN),
-    ("enableoutput", PERMISSION_ADMIN),
-    ("find", PERMISSION_READ),
-    ("findadd", PERMISSION_ADD),
-    ("getfingerprint", PERMISSION_READ),
-    ("getvol", PERMISSION_READ),
-    ("idle", PERMISSION_READ),
-    ("kill", PERMISSION_ADMIN),
-    ("list", PERMISSION_READ),
-    ("listall", PERMISSION_READ),
-    ("listallinfo", PERMISSION_READ),
-    ("listfiles", PERMISSION_READ),
-    ("listmounts", PERMISSION_READ),
-    ("listpartitions", PERMISSION_READ),
-    ("listplaylist", PERMISSION_READ),
-    ("listplaylistinfo", PERMISSION_READ),
-    ("listplaylists", PERMISSION_READ),
-    ("load", PERMISSION_ADD),
-    ("lsinfo", PERMISSION_READ),
-    ("mixrampdb", PERMISSION_CONTROL),
-    ("mixrampdelay", PERMISSION_CONTROL),
-    ("mount", PERMISSION_ADMIN),
-    ("move", PERMISSION_CONTROL),
-    ("moveid", PERMISSION_CONTROL),
-    ("moveoutput", PERMISSION_ADMIN),
-    ("newpartition", PERMISSION_ADMIN),
-    ("next", PERMISSION_CONTROL),
-    ("notcommands", PERMISSION_NONE),
-    ("outputset", PERMISSION_ADMIN),
-    ("outputs", PERMISSION_READ),
-    ("partition", PERMISSION_CONTROL),
-    ("password", PERMISSION_NONE),
-    ("pause", PERMISSION_CONTROL),
-    ("ping", PERMISSION_NONE),
-    ("play", PERMISSION_CONTROL),
-    ("playid", PERMISSION_CONTROL),
-    ("playlist", PERMISSION_READ),
-    ("playlistadd", PERMISSION_CONTROL),
-    ("playlistclear", PERMISSION_CONTROL),
-    ("playlistdelete", PERMISSION_CONTROL),
-    ("playlistfind", PERMISSION_READ),
-    ("playlistid", PERMISSION_READ),
-    ("playlistinfo", PERMISSION_READ),
-    ("playlistlength", PERMISSION_READ),
-    ("playlistmove", PERMISSION_CONTROL),
-    ("playlistsearch", PERMISSION_READ),
-    ("plchanges", PERMISSION_READ),
-    ("plchangesposid", PERMISSION_READ),
-    ("previous", PERMISSION_CONTROL),
-    ("prio", PERMISSION_CONTROL),
-    ("prioid", PERMISSION_CONTROL),
-    ("protocol", PERMISSION_NONE),
-    ("random", PERMISSION_CONTROL),
-    ("rangeid", PERMISSION_CONTROL),
-    ("readcomments", PERMISSION_READ),
-    ("readmessages", PERMISSION_CONTROL),
-    ("readpicture", PERMISSION_READ),
-    ("rename", PERMISSION_CONTROL),
-    ("repeat", PERMISSION_CONTROL),
-    ("replay_gain_mode", PERMISSION_CONTROL),
-    ("replay_gain_status", PERMISSION_READ),
-    ("rescan", PERMISSION_CONTROL),
-    ("rm", PERMISSION_CONTROL),
-    ("save", PERMISSION_CONTROL),
-    ("search", PERMISSION_READ),
-    ("searchadd", PERMISSION_ADD),
-    ("searchaddpl", PERMISSION_ADD),
-    ("searchcount", PERMISSION_READ),
-    ("searchplaylist", PERMISSION_READ),
-    ("seek", PERMISSION_CONTROL),
-    ("seekcur", PERMISSION_CONTROL),
-    ("seekid", PERMISSION_CONTROL),
-    ("sendmessage", PERMISSION_CONTROL),
-    ("setvol", PERMISSION_CONTROL),
-    ("shuffle", PERMISSION_CONTROL),
-    ("single", PERMISSION_CONTROL),
-    ("stats", PERMISSION_READ),
-    ("status", PERMISSION_READ),
-    ("sticker", PERMISSION_CONTROL),
-    ("stickernames", PERMISSION_READ),
-    ("stickernamestypes", PERMISSION_READ),
-    ("stickertypes", PERMISSION_READ),
-    ("stop", PERMISSION_CONTROL),
-    ("stringnormalization", PERMISSION_NONE),
-    ("subscribe", PERMISSION_CONTROL),
-    ("swap", PERMISSION_CONTROL),
-    ("swapid", PERMISSION_CONTROL),
-    ("tagtypes", PERMISSION_NONE),
-    ("toggleoutput", PERMISSION_ADMIN),
-    ("unmount", PERMISSION_ADMIN),
-    ("unsubscribe", PERMISSION_CONTROL),
-    ("update", PERMISSION_CONTROL),
-    ("urlhandlers", PERMISSION_READ),
-    ("volume", PERMISSION_CONTROL),
-];
-
+/// List the commands the connection is currently allowed to run.
+///
+/// Driven by the command [`registry`], so the output always matches what the
+/// parser accepts and what the dispatcher enforces.
 pub async fn handle_commands_command(conn_state: &ConnectionState) -> String {
     let mut resp = ResponseBuilder::new();
-    for (cmd, perm) in COMMAND_PERMISSIONS {
-        if conn_state.has_permission(*perm) {
-            resp.field("command", *cmd);
+    for spec in registry::advertised() {
+        if conn_state.has_permission(spec.permission) {
+            resp.field("command", spec.name);
         }
     }
     resp.ok()
 }
 
+/// List the commands the connection lacks permission for.
 pub async fn handle_notcommands_command(conn_state: &ConnectionState) -> String {
     let mut resp = ResponseBuilder::new();
-    for (cmd, perm) in COMMAND_PERMISSIONS {
-        if !conn_state.has_permission(*perm) {
-            resp.field("command", *cmd);
+    for spec in registry::advertised() {
+        if !conn_state.has_permission(spec.permission) {
+            resp.field("command", spec.name);
         }
     }
     resp.ok()
 }
 
+/// Canonical spellings of the tag types named by a client, which MPD
+/// accepts in any case; `None` if one of them is unknown.
+fn canonical_tag_types(tags: Vec<String>) -> Option<Vec<String>> {
+    tags.iter()
+        .map(|tag| {
+            TAG_TYPE_NAMES
+                .iter()
+                .chain(&["Comment"])
+                .find(|name| name.eq_ignore_ascii_case(tag))
+                .map(|name| name.to_string())
+        })
+        .collect()
+}
+
 pub async fn handle_tagtypes_command(
     conn_state: &mut ConnectionState,
     subcommand: Option<crate::parser::TagTypesSubcommand>,
 ) -> String {
+    use crate::commands::utils::ACK_ERROR_ARG;
     use crate::parser::TagTypesSubcommand;
 
     let mut resp = ResponseBuilder::new();
 
+    // The named tag types, or an error response for an unknown one.
+    let names = |tags: Vec<String>| {
+        canonical_tag_types(tags)
+            .ok_or_else(|| ResponseBuilder::error(ACK_ERROR_ARG, 0, "tagtypes", "Unknown tag type"))
+    };
+
     match subcommand {
         None | Some(TagTypesSubcommand::Available) => {
             // List all currently enabled metadata tags for this connection.
-            // Must match MPD's tagtypes output order.
-            let all_tags = vec![
-                "Artist",
-                "ArtistSort",
-                "Album",
-                "AlbumSort",
-                "AlbumArtist",
-                "AlbumArtistSort",
-                "Title",
-                "TitleSort",
-                "Track",
-                "Name",
-                "Genre",
-                "Mood",
-                "Date",
-                "OriginalDate",
-                "Composer",
-                "ComposerSort",
-                "Performer",
-                "Conductor",
-                "Work",
-                "Movement",
-                "MovementNumber",
-                "ShowMovement",
-                "Ensemble",
-                "Location",
-                "Grouping",
-                "Disc",
-                "Label",
-                "MUSICBRAINZ_ARTISTID",
-                "MUSICBRAINZ_ALBUMID",
-                "MUSICBRAINZ_ALBUMARTISTID",
-                "MUSICBRAINZ_TRACKID",
-                "MUSICBRAINZ_RELEASETRACKID",
-                "MUSICBRAINZ_WORKID",
-                "MUSICBRAINZ_RELEASEGROUPID",
-            ];
-
-            for tag in all_tags {
-                if conn_state.is_tag_enabled(tag) {
+            // Types outside metadata_to_use are never stored, so not offered.
+            let mask = rmpd_core::tag::metadata_to_use();
+            for tag in TAG_TYPE_NAMES {
+                if conn_state.is_tag_enabled(tag) && mask.is_none_or(|m| m.allows(tag)) {
                     resp.field("tagtype", tag);
                 }
             }
@@ -209,18 +80,18 @@ pub async fn handle_tagtypes_command(
             // Disable all tag types for this client
             conn_state.disable_all_tags();
         }
-        Some(TagTypesSubcommand::Enable { tags }) => {
-            // Enable specific tags for this client
-            conn_state.enable_tags(tags);
-        }
-        Some(TagTypesSubcommand::Disable { tags }) => {
-            // Disable specific tags for this client
-            conn_state.disable_tags(tags);
-        }
-        Some(TagTypesSubcommand::Reset { tags }) => {
-            // Reset specific tags to default state for this client
-            conn_state.reset_tags(tags);
-        }
+        Some(TagTypesSubcommand::Enable { tags }) => match names(tags) {
+            Ok(tags) => conn_state.enable_tags(tags),
+            Err(e) => return e,
+        },
+        Some(TagTypesSubcommand::Disable { tags }) => match names(tags) {
+            Ok(tags) => conn_state.disable_tags(tags),
+            Err(e) => return e,
+        },
+        Some(TagTypesSubcommand::Reset { tags }) => match names(tags) {
+            Ok(tags) => conn_state.reset_tags(tags),
+            Err(e) => return e,
+        },
     }
 
     resp.ok()
@@ -349,5 +220,28 @@ pub async fn handle_decoders_command() -> String {
     resp.field("suffix", "wav");
     resp.field("mime_type", "audio/wav");
 
+    resp.field("plugin", "dsdiff");
+    resp.field("suffix", "dff");
+    resp.field("mime_type", "application/x-dff");
+
+    resp.field("plugin", "dsf");
+    resp.field("suffix", "dsf");
+    resp.field("mime_type", "application/x-dsf");
+
+    // Fallback decoders compiled in next to Symphonia (e.g. `ffmpeg` for
+    // WavPack, Musepack, Monkey's Audio and TTA).
+    for plugin in rmpd_player::DECODER_PLUGINS
+        .iter()
+        .filter(|p| p.name != "symphonia")
+    {
+        resp.field("plugin", plugin.name);
+        for suffix in plugin.suffixes {
+            resp.field("suffix", suffix);
+        }
+        for mime_type in plugin.mime_types {
+            resp.field("mime_type", mime_type);
+        }
+    }
+
     resp.ok()
 }
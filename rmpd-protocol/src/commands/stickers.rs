@@ -9,7 +9,7 @@ use crate::state::AppState;
 
 use super::utils::{ACK_ERROR_NO_EXIST, ACK_ERROR_SYS, open_db};
 
-fn get_sticker_i32(db: &rmpd_library::Database, uri: &str, name: &str) -> i32 {
+fn get_sticker_i32(db: &dyn rmpd_library::LibraryStore, uri: &str, name: &str) -> i32 {
     db.get_sticker(uri, name)
         .ok()
         .flatten()
@@ -17,8 +17,18 @@ fn get_sticker_i32(db: &rmpd_library::Database, uri: &str, name: &str) -> i32 {
         .unwrap_or(0)
 }
 
+/// Notify idle clients that a sticker on `uri` changed, mirroring MPD's
+/// `idle_add(IDLE_STICKER)` after a successful mutation.
+fn notify_sticker(state: &AppState, uri: &str) {
+    state
+        .event_bus
+        .emit(rmpd_core::event::Event::StickerChanged {
+            uri: uri.to_owned(),
+        });
+}
+
 /// Return `Err(error_response)` when the song at `uri` does not exist in the DB.
-fn require_song(db: &rmpd_library::Database, uri: &str) -> Result<(), String> {
+fn require_song(db: &dyn rmpd_library::LibraryStore, uri: &str) -> Result<(), String> {
     match db.get_song_by_path(uri) {
         Ok(None) => Err(ResponseBuilder::error(
             ACK_ERROR_NO_EXIST,
@@ -142,7 +152,10 @@ pub async fn handle_sticker_set_command(
         }
 
         match db.set_sticker(&uri, &name, &value) {
-            Ok(_) => ResponseBuilder::new().ok(),
+            Ok(_) => {
+                notify_sticker(&state, &uri);
+                ResponseBuilder::new().ok()
+            }
             Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "sticker", &format!("Error: {e}")),
         }
     })
@@ -194,7 +207,10 @@ pub async fn handle_sticker_delete_command(
         }
 
         match db.delete_sticker(&uri, name) {
-            Ok(_) => ResponseBuilder::new().ok(),
+            Ok(_) => {
+                notify_sticker(&state, &uri);
+                ResponseBuilder::new().ok()
+            }
             Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "sticker", &format!("Error: {e}")),
         }
     })
@@ -284,6 +300,7 @@ async fn adjust_sticker_value(state: &AppState, uri: &str, name: &str, delta: i3
         let new_value = get_sticker_i32(&db, &uri, &name) + delta;
         match db.set_sticker(&uri, &name, &new_value.to_string()) {
             Ok(_) => {
+                notify_sticker(&state, &uri);
                 let mut resp = ResponseBuilder::new();
                 resp.field("sticker", format!("{name}={new_value}"));
                 resp.ok()
@@ -313,26 +330,24 @@ pub async fn handle_sticker_dec_command(
     adjust_sticker_value(state, uri, name, -delta.unwrap_or(1)).await
 }
 
-pub async fn handle_sticker_names_command(state: &AppState, uri: Option<&str>) -> String {
-    // List unique sticker names (optionally for specific URI)
-    let Some(uri_str) = uri else {
-        return ResponseBuilder::new().ok();
-    };
+/// `stickernames`: every distinct sticker name in the database.
+pub async fn handle_sticker_names_command(state: &AppState) -> String {
     let state = state.clone();
-    let uri_str = uri_str.to_string();
     tokio::task::spawn_blocking(move || {
         let db = match open_db(&state, "stickernames") {
             Ok(d) => d,
             Err(e) => return e,
         };
-        if let Ok(stickers) = db.list_stickers(&uri_str) {
-            let mut resp = ResponseBuilder::new();
-            for (name, _) in stickers {
-                resp.field("sticker", &name);
+        match db.sticker_names() {
+            Ok(names) => {
+                let mut resp = ResponseBuilder::new();
+                for name in &names {
+                    resp.field("name", name);
+                }
+                resp.ok()
             }
-            return resp.ok();
+            Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "stickernames", &e.to_string()),
         }
-        ResponseBuilder::new().ok()
     })
     .await
     .unwrap_or_else(|_| ResponseBuilder::error(ACK_ERROR_SYS, 0, "stickernames", "internal error"))
@@ -370,26 +385,30 @@ pub async fn handle_sticker_types_command() -> String {
     resp.ok()
 }
 
-pub async fn handle_sticker_namestypes_command(state: &AppState, uri: Option<&str>) -> String {
-    // List sticker names and types
-    let Some(uri_str) = uri else {
-        return ResponseBuilder::new().ok();
-    };
+/// `stickernamestypes [TYPE]`: every distinct sticker name with its type,
+/// optionally restricted to one sticker type.
+pub async fn handle_sticker_namestypes_command(
+    state: &AppState,
+    sticker_type: Option<&str>,
+) -> String {
     let state = state.clone();
-    let uri_str = uri_str.to_string();
+    let sticker_type = sticker_type.map(str::to_string);
     tokio::task::spawn_blocking(move || {
         let db = match open_db(&state, "stickernamestypes") {
             Ok(d) => d,
             Err(e) => return e,
         };
-        if let Ok(stickers) = db.list_stickers(&uri_str) {
-            let mut resp = ResponseBuilder::new();
-            for (name, _) in stickers {
-                resp.field("sticker", format!("{name} song"));
+        match db.sticker_names_types(sticker_type.as_deref()) {
+            Ok(pairs) => {
+                let mut resp = ResponseBuilder::new();
+                for (name, sticker_type) in &pairs {
+                    resp.field("name", name);
+                    resp.field("type", sticker_type);
+                }
+                resp.ok()
             }
-            return resp.ok();
+            Err(e) => ResponseBuilder::error(ACK_ERROR_SYS, 0, "stickernamestypes", &e.to_string()),
         }
-        ResponseBuilder::new().ok()
     })
     .await
     .unwrap_or_else(|_| {
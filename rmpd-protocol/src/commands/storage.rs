@@ -8,7 +8,7 @@
 //! - mount/unmount/listmounts: ✅ Tier 1 (tracking) + Tier 2 (actual mounting) implemented
 
 use super::ResponseBuilder;
-use super::utils::ACK_ERROR_SYS;
+use super::utils::{ACK_ERROR_SYS, NO_MUSIC_DIR};
 use crate::state::AppState;
 use rmpd_core::storage::platform::get_default_backend;
 use std::path::PathBuf;
@@ -35,16 +35,8 @@ pub async fn handle_mount_command(state: &AppState, path: &str, uri: &str) -> St
     }
 
     // Check if music directory is configured
-    let music_dir = match &state.music_dir {
-        Some(dir) => dir,
-        None => {
-            return ResponseBuilder::error(
-                ACK_ERROR_SYS,
-                0,
-                "mount",
-                "Music directory not configured",
-            );
-        }
+    let Some(music_dir) = state.music_dir() else {
+        return ResponseBuilder::error(ACK_ERROR_SYS, 0, "mount", NO_MUSIC_DIR);
     };
 
     // Create full mountpoint path
@@ -129,16 +121,8 @@ pub async fn handle_mount_command(state: &AppState, path: &str, uri: &str) -> St
 /// and only remove from registry (Tier 1 mode).
 pub async fn handle_unmount_command(state: &AppState, path: &str) -> String {
     // Check if music directory is configured
-    let music_dir = match &state.music_dir {
-        Some(dir) => dir,
-        None => {
-            return ResponseBuilder::error(
-                ACK_ERROR_SYS,
-                0,
-                "unmount",
-                "Music directory not configured",
-            );
-        }
+    let Some(music_dir) = state.music_dir() else {
+        return ResponseBuilder::error(ACK_ERROR_SYS, 0, "unmount", NO_MUSIC_DIR);
     };
 
     // Create full mountpoint path
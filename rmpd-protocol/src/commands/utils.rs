@@ -22,23 +22,78 @@ pub const ACK_ERROR_UPDATE_ALREADY: i32 = 54;
 pub const ACK_ERROR_PLAYER_SYNC: i32 = 55;
 pub const ACK_ERROR_EXIST: i32 = 56;
 
-/// Borrow a pooled database connection, returning an error response string on
-/// failure. Reuses connections from the shared pool instead of opening a fresh
-/// SQLite connection (and re-running schema init) on every command.
+/// ACK message for commands that need the music directory when none is
+/// configured (or the configured one does not exist).
+pub const NO_MUSIC_DIR: &str =
+    "music directory not configured: set general.music_directory and reload the config";
+
+/// Borrow a library store from the shared backend, returning an error
+/// response string on failure. With SQLite this reuses pooled connections
+/// instead of opening a fresh one (and re-running schema init) per command.
 pub fn open_db(
     state: &crate::state::AppState,
     command: &str,
-) -> Result<rmpd_library::Database, String> {
+) -> Result<Box<dyn rmpd_library::LibraryStore>, String> {
     let pool = state.db_pool.as_ref().ok_or_else(|| {
         ResponseBuilder::error(ACK_ERROR_SYS, 0, command, "database not configured")
     })?;
-    rmpd_library::Database::from_pool(pool).map_err(|e| {
+    pool.store().map_err(|e| {
         ResponseBuilder::error(ACK_ERROR_SYS, 0, command, &format!("database error: {e}"))
     })
 }
 
 pub use rmpd_core::time::format_iso8601 as format_iso8601_timestamp;
 
+/// ACK for a client URI refused by [`crate::state::AppState::resolve_client_path`].
+pub fn path_error(command: &str, err: rmpd_core::path::PathError) -> String {
+    use rmpd_core::path::PathError;
+    let code = match err {
+        PathError::Malformed => ACK_ERROR_ARG,
+        PathError::OutsideLibrary => ACK_ERROR_PERMISSION,
+        PathError::NoMusicDirectory => ACK_ERROR_NO_EXIST,
+    };
+    ResponseBuilder::error(code, 0, command, &err.to_string())
+}
+
+/// Read a song's tags straight from a local file the database does not know
+/// yet (added since the last scan), as MPD does for ad-hoc additions.
+///
+/// `Ok(None)` when `uri` names no file inside the library; `Err` carries the
+/// ACK for a path outside it or a file whose tags cannot be read. Blocking.
+pub fn read_unscanned_song(
+    state: &crate::state::AppState,
+    command: &str,
+    uri: &str,
+) -> Result<Option<rmpd_core::song::Song>, String> {
+    // Remote source paths only exist through the database.
+    if state.sources.owns_path(uri) {
+        return Ok(None);
+    }
+    let path = match state.resolve_client_path(uri) {
+        Ok(path) => path,
+        Err(e @ rmpd_core::path::PathError::OutsideLibrary) => return Err(path_error(command, e)),
+        Err(_) => return Ok(None),
+    };
+    let Ok(path) = camino::Utf8PathBuf::from_path_buf(path) else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut song = rmpd_library::MetadataExtractor::extract_from_file(&path).map_err(|e| {
+        ResponseBuilder::error(
+            ACK_ERROR_SYS,
+            0,
+            command,
+            &format!("failed to read {uri}: {e}"),
+        )
+    })?;
+    tracing::debug!("{command}: {uri} is not in the database, read its tags from disk");
+    song.path = uri.into();
+    state.tag_rewriter.apply(&mut song);
+    Ok(Some(song))
+}
+
 /// Build a FilterExpression from multiple tag/value pairs joined with AND.
 /// Panics if `filters` is empty.
 pub fn build_and_filter(filters: &[(String, String)]) -> rmpd_core::filter::FilterExpression {
@@ -89,13 +144,8 @@ pub fn build_search_filter(filters: &[(String, String)]) -> rmpd_core::filter::F
 /// Apply a range/window filter to a slice, returning the filtered sub-slice.
 pub fn apply_range<T>(items: &[T], range: Option<(u32, u32)>) -> &[T] {
     if let Some((start, end)) = range {
-        let start_idx = start as usize;
-        let end_idx = end.min(items.len() as u32) as usize;
-        if start_idx < items.len() {
-            &items[start_idx..end_idx]
-        } else {
-            &[]
-        }
+        let end_idx = (end as usize).min(items.len());
+        items.get(start as usize..end_idx).unwrap_or(&[])
     } else {
         items
     }
@@ -109,24 +159,32 @@ pub fn add_queue_item_metadata(
     if item.priority > 0 {
         resp.field("Prio", item.priority);
     }
-    if let Some((start, end)) = item.range {
-        resp.field("Range", format!("{start:.3}-{end:.3}"));
+    // An end at or before the start plays to the end of the song, which
+    // MPD prints as an open range.
+    match item.range {
+        Some((start, end)) if end > start => {
+            resp.field("Range", format!("{start:.3}-{end:.3}"));
+        }
+        Some((start, _)) => {
+            resp.field("Range", format!("{start:.3}-"));
+        }
+        None => {}
     }
 }
 
-/// Update next_song in status based on the current position in the queue.
+/// Update next_song in status to the song auto-advance will play after the
+/// one at `current_pos` (see `queue_playback::next_song_position`).
 pub fn update_next_song(
     status: &mut rmpd_core::state::PlayerStatus,
     queue: &rmpd_core::queue::Queue,
     current_pos: u32,
 ) {
-    status.next_song =
-        queue
-            .get(current_pos + 1)
-            .map(|next_item| rmpd_core::state::QueuePosition {
-                position: current_pos + 1,
-                id: next_item.id,
-            });
+    status.next_song = crate::queue_playback::next_song_position(status, queue, current_pos)
+        .and_then(|position| queue.get(position))
+        .map(|next_item| rmpd_core::state::QueuePosition {
+            position: next_item.position,
+            id: next_item.id,
+        });
 }
 
 /// Prepare a song for playback by resolving its path.
@@ -142,6 +200,7 @@ pub fn update_next_song(
 pub async fn prepare_song_for_playback(
     song: &rmpd_core::song::Song,
     music_dir: Option<&str>,
+    roots: &[rmpd_core::config::MusicRoot],
     range: Option<(f64, f64)>,
     sources: &std::sync::Arc<rmpd_source::SourceRegistry>,
 ) -> Result<rmpd_core::playback::PlaybackSong, rmpd_source::SourceError> {
@@ -149,8 +208,9 @@ pub async fn prepare_song_for_playback(
     let path = song.path.as_str();
     // Mount-style source paths (e.g. `alarm-music/Artist/Album/id.flac`) are
     // owned by a live source and resolve to a real `http(s)://` stream URL.
-    // Everything else — local relative/absolute paths and plain radio URIs —
-    // passes through `resolve_path` unchanged.
+    // Everything else — local relative/absolute paths (including those under
+    // an extra music root) and plain radio URIs — goes through
+    // `resolve_in_roots`.
     let resolved_path: String = if sources.owns_path(path) {
         // Spawn the resolution onto a Tokio task so the non-Sync async_trait
         // future does not poison the outer future with a non-Sync bound
@@ -163,7 +223,7 @@ pub async fn prepare_song_for_playback(
                 rmpd_source::SourceError::Protocol(format!("resolve task panicked: {e}"))
             })?? // JoinError then SourceError
     } else {
-        resolve_path(path, music_dir)
+        rmpd_core::path::resolve_in_roots(path, music_dir, roots)
     };
     Ok(rmpd_core::playback::PlaybackSong {
         song: Arc::new(song.clone()),
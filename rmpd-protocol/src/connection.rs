@@ -5,6 +5,8 @@
 
 use std::collections::HashSet;
 
+use crate::clients::ClientSettings;
+
 /// Permission level constants matching MPD's permission system.
 pub const PERMISSION_NONE: u8 = 0;
 pub const PERMISSION_READ: u8 = 1;
@@ -14,6 +16,74 @@ pub const PERMISSION_ADMIN: u8 = 8;
 pub const PERMISSION_ALL: u8 =
     PERMISSION_READ | PERMISSION_ADD | PERMISSION_CONTROL | PERMISSION_ADMIN;
 
+/// Parse an MPD permission list such as `"read,add"` (names: `read`, `add`,
+/// `control`, `admin`) into a bitmask.
+pub fn parse_permissions(spec: &str) -> Result<u8, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(PERMISSION_NONE, |mask, name| {
+            let bit = match name {
+                "read" => PERMISSION_READ,
+                "add" => PERMISSION_ADD,
+                "control" => PERMISSION_CONTROL,
+                "admin" => PERMISSION_ADMIN,
+                _ => return Err(format!("unknown permission {name:?}")),
+            };
+            Ok(mask | bit)
+        })
+}
+
+/// Split a configured password into the secret and the permissions it
+/// grants. MPD's `secret@read,add` form limits them; a password without a
+/// valid permission suffix (including one that merely contains `@`) grants
+/// everything.
+pub fn parse_password(spec: &str) -> (&str, u8) {
+    spec.rsplit_once('@')
+        .and_then(|(secret, perms)| Some((secret, parse_permissions(perms).ok()?)))
+        .filter(|(secret, perms)| !secret.is_empty() && *perms != PERMISSION_NONE)
+        .unwrap_or((spec, PERMISSION_ALL))
+}
+
+/// Tag types offered by `tagtypes`, in MPD's output order. Comment is
+/// left out, as it is disabled by default.
+pub(crate) const TAG_TYPE_NAMES: &[&str] = &[
+    "Artist",
+    "ArtistSort",
+    "Album",
+    "AlbumSort",
+    "AlbumArtist",
+    "AlbumArtistSort",
+    "Title",
+    "TitleSort",
+    "Track",
+    "Name",
+    "Genre",
+    "Mood",
+    "Date",
+    "OriginalDate",
+    "Composer",
+    "ComposerSort",
+    "Performer",
+    "Conductor",
+    "Work",
+    "Movement",
+    "MovementNumber",
+    "ShowMovement",
+    "Ensemble",
+    "Location",
+    "Grouping",
+    "Disc",
+    "Label",
+    "MUSICBRAINZ_ARTISTID",
+    "MUSICBRAINZ_ALBUMID",
+    "MUSICBRAINZ_ALBUMARTISTID",
+    "MUSICBRAINZ_TRACKID",
+    "MUSICBRAINZ_RELEASETRACKID",
+    "MUSICBRAINZ_WORKID",
+    "MUSICBRAINZ_RELEASEGROUPID",
+];
+
 /// Per-client connection state
 ///
 /// Each client connection maintains its own state for:
@@ -21,6 +91,8 @@ pub const PERMISSION_ALL: u8 =
 /// - Protocol feature negotiation (which MPD protocol features are enabled)
 /// - Subscribed message channels
 /// - Current partition (for multi-partition support)
+/// - The client's id and the name it registered with `client`
+/// - The size of binary chunks (`binarylimit`)
 #[derive(Debug, Clone)]
 pub struct ConnectionState {
     /// Set of enabled tag types for this connection
@@ -41,6 +113,19 @@ pub struct ConnectionState {
 
     /// MPD permissions bitmask for this connection
     pub permissions: u8,
+
+    /// Id in the server's client registry (0 when not registered)
+    pub client_id: u64,
+
+    /// Name registered with the `client` command
+    pub client_name: Option<String>,
+
+    /// Largest binary chunk sent to this client, set with `binarylimit`
+    pub binary_limit: usize,
+
+    /// Largest response the server writes to this client
+    /// (`network.max_output_buffer_size`), which bounds `binarylimit`
+    pub max_output_buffer_size: usize,
 }
 
 impl ConnectionState {
@@ -55,9 +140,27 @@ impl ConnectionState {
             subscribed_channels: Vec::new(),
             current_partition: "default".to_string(),
             permissions: PERMISSION_ALL,
+            client_id: 0,
+            client_name: None,
+            binary_limit: rmpd_library::artwork::DEFAULT_CHUNK_SIZE,
+            max_output_buffer_size: usize::MAX,
+        }
+    }
+
+    /// The settings kept for this client across reconnects
+    pub fn settings(&self) -> ClientSettings {
+        ClientSettings {
+            enabled_tags: self.enabled_tags.clone(),
+            enabled_features: self.enabled_features.clone(),
         }
     }
 
+    /// Restore settings saved by an earlier connection of this client
+    pub fn restore(&mut self, settings: ClientSettings) {
+        self.enabled_tags = settings.enabled_tags;
+        self.enabled_features = settings.enabled_features;
+    }
+
     /// Subscribe to a channel
     pub fn subscribe(&mut self, channel: String) {
         if !self.subscribed_channels.contains(&channel) {
@@ -173,33 +276,7 @@ impl ConnectionState {
     /// Get the default set of tag types
     fn default_tags() -> HashSet<String> {
         // MPD default = All tags EXCEPT Comment (see Settings.cxx: All & ~TAG_COMMENT)
-        let mut tags = HashSet::new();
-        tags.insert("Artist".to_string());
-        tags.insert("ArtistSort".to_string());
-        tags.insert("Album".to_string());
-        tags.insert("AlbumSort".to_string());
-        tags.insert("AlbumArtist".to_string());
-        tags.insert("AlbumArtistSort".to_string());
-        tags.insert("Title".to_string());
-        tags.insert("Track".to_string());
-        tags.insert("Name".to_string());
-        tags.insert("Genre".to_string());
-        tags.insert("Date".to_string());
-        tags.insert("OriginalDate".to_string());
-        tags.insert("Composer".to_string());
-        tags.insert("Performer".to_string());
-        tags.insert("Grouping".to_string());
-        // Comment is excluded by default (matches MPD's global_tag_mask)
-        tags.insert("Disc".to_string());
-        tags.insert("Label".to_string());
-        tags.insert("MUSICBRAINZ_ARTISTID".to_string());
-        tags.insert("MUSICBRAINZ_ALBUMID".to_string());
-        tags.insert("MUSICBRAINZ_ALBUMARTISTID".to_string());
-        tags.insert("MUSICBRAINZ_TRACKID".to_string());
-        tags.insert("MUSICBRAINZ_RELEASETRACKID".to_string());
-        tags.insert("MUSICBRAINZ_WORKID".to_string());
-        tags.insert("MUSICBRAINZ_RELEASEGROUPID".to_string());
-        tags
+        TAG_TYPE_NAMES.iter().map(|tag| tag.to_string()).collect()
     }
 
     /// Enable all protocol features
@@ -274,6 +351,33 @@ impl Default for ConnectionState {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_permissions() {
+        assert_eq!(parse_permissions("read"), Ok(PERMISSION_READ));
+        assert_eq!(
+            parse_permissions("read, add"),
+            Ok(PERMISSION_READ | PERMISSION_ADD)
+        );
+        assert_eq!(
+            parse_permissions("read,add,control,admin"),
+            Ok(PERMISSION_ALL)
+        );
+        assert_eq!(parse_permissions(""), Ok(PERMISSION_NONE));
+        assert!(parse_permissions("read,write").is_err());
+    }
+
+    #[test]
+    fn test_parse_password() {
+        assert_eq!(parse_password("secret"), ("secret", PERMISSION_ALL));
+        assert_eq!(
+            parse_password("secret@read,add"),
+            ("secret", PERMISSION_READ | PERMISSION_ADD)
+        );
+        // An `@` not followed by permissions is part of the password.
+        assert_eq!(parse_password("me@host"), ("me@host", PERMISSION_ALL));
+        assert_eq!(parse_password("a@b@admin"), ("a@b", PERMISSION_ADMIN));
+    }
+
     #[test]
     fn test_new_connection_state() {
         let state = ConnectionState::new();
@@ -281,6 +385,21 @@ mod tests {
         assert!(!state.is_feature_enabled("binary"));
     }
 
+    #[test]
+    fn test_settings_round_trip() {
+        let mut state = ConnectionState::new();
+        state.disable_all_tags();
+        state.enable_tags(vec!["Artist".to_string()]);
+        state.enable_features(vec!["hide_playlists_in_root".to_string()]);
+        let settings = state.settings();
+
+        let mut resumed = ConnectionState::new();
+        resumed.restore(settings);
+        assert!(resumed.is_tag_enabled("Artist"));
+        assert!(!resumed.is_tag_enabled("Album"));
+        assert!(resumed.is_feature_enabled("hide_playlists_in_root"));
+    }
+
     #[test]
     fn test_disable_all_tags() {
         let mut state = ConnectionState::new();
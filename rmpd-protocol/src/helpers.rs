@@ -1,23 +1,71 @@
 //! Shared `pub(crate)` helpers for protocol command handlers.
 
-use crate::commands::utils::{ACK_ERROR_ARG, ACK_ERROR_SYS, build_and_filter, build_search_filter};
+use crate::commands::utils::{
+    ACK_ERROR_ARG, ACK_ERROR_SYS, build_and_filter, build_search_filter, update_next_song,
+};
 use crate::response::ResponseBuilder;
 use crate::state::AppState;
 use rmpd_core::event::Event;
+use rmpd_core::queue::Queue;
 use rmpd_core::song::{AudioFormat, Song};
-use rmpd_core::state::PlayerState;
+use rmpd_core::state::{PlayerState, PlayerStatus, QueuePosition};
 
-/// Bump the queue (playlist) version and length, then notify the `playlist`
-/// idle subsystem so event-driven clients (rmpc, ncmpcpp, …) refetch the queue
-/// after it changes. Acquires a write lock on `state.status` and a read lock on
-/// `state.queue`.
-pub(crate) async fn update_playlist_version(state: &AppState) {
-    {
+/// Publish the queue (playlist) version and length, re-derive the current and
+/// next song positions from their ids (see [`sync_queue_positions`]), then
+/// notify the `playlist` idle subsystem so event-driven clients (rmpc,
+/// ncmpcpp, …) refetch the queue after it changes. Every queue edit ends here,
+/// so `song`/`nextsong` in `status` never point at a stale position.
+/// Acquires a write lock on `state.status` and a read lock on `state.queue`.
+///
+/// Returns true when the current song is no longer in the queue.
+pub(crate) async fn update_playlist_version(state: &AppState) -> bool {
+    let current_removed = {
         let mut status = state.status.write().await;
-        status.playlist_version += 1;
-        status.playlist_length = state.queue.read().await.len() as u32;
-    }
+        let queue = state.queue.read().await;
+        status.playlist_version = queue.version();
+        status.playlist_length = queue.len() as u32;
+        let had_current = status.current_song.is_some();
+        sync_queue_positions(&mut status, &queue);
+        had_current && status.current_song.is_none()
+    };
     state.event_bus.emit(Event::QueueChanged);
+    current_removed
+}
+
+/// Point `current_song` at the position its id now occupies (clearing it
+/// when the song left the queue) and recompute `next_song` from it.
+pub(crate) fn sync_queue_positions(status: &mut PlayerStatus, queue: &Queue) {
+    let current = status.current_song.and_then(|current| {
+        queue.get_by_id(current.id).map(|item| QueuePosition {
+            position: item.position,
+            id: current.id,
+        })
+    });
+    status.current_song = current;
+    match current {
+        Some(current) => update_next_song(status, queue, current.position),
+        None => status.next_song = None,
+    }
+}
+
+/// Stop playback after a queue edit removed the playing song (see
+/// [`update_playlist_version`]'s return value).
+pub(crate) async fn stop_if_current_removed(state: &AppState, current_removed: bool) {
+    if !current_removed {
+        return;
+    }
+    let playing = !matches!(
+        PlayerState::from_atomic(
+            state
+                .atomic_state
+                .load(std::sync::atomic::Ordering::Acquire)
+        ),
+        PlayerState::Stop
+    );
+    if playing {
+        state.engine.write().await.stop().await.ok();
+        update_player_state(state, PlayerState::Stop).await;
+    }
 }
 
 pub(crate) fn is_known_uri_scheme(scheme: &str) -> bool {
@@ -73,6 +121,58 @@ pub(crate) fn create_stream_song(uri: &str) -> Song {
     }
 }
 
+/// How long a background stream probe may take before it is abandoned.
+const STREAM_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Describe an ad-hoc `http(s)://` queue entry from a quick probe of the
+/// stream (station name, genre, bitrate, first "now playing" title).
+///
+/// Runs in the background so `add` does not wait on the network. The result
+/// is stored on the queue item `id` and lives as long as it does; nothing is
+/// updated if the item was removed in the meantime or the probe fails.
+pub(crate) fn spawn_stream_probe(state: &AppState, id: u32, uri: &str) {
+    if !rmpd_stream::is_http_uri(uri) {
+        return;
+    }
+    let state = state.clone();
+    let uri = uri.to_owned();
+    tokio::spawn(async move {
+        let probe_uri = uri.clone();
+        let info = match tokio::task::spawn_blocking(move || {
+            rmpd_stream::probe(&probe_uri, STREAM_PROBE_TIMEOUT)
+        })
+        .await
+        {
+            Ok(Ok(info)) => info,
+            Ok(Err(e)) => {
+                tracing::debug!("probing {uri} failed: {e}");
+                return;
+            }
+            Err(_) => return,
+        };
+        if info == rmpd_stream::StreamInfo::default() {
+            return;
+        }
+        let updated = state.queue.write().await.update_song_by_id(id, |song| {
+            for (tag, value) in [
+                ("name", info.name),
+                ("genre", info.genre),
+                ("title", info.title),
+            ] {
+                if let Some(value) = value
+                    && song.tag(tag).is_none()
+                {
+                    song.tags.push((tag.into(), value));
+                }
+            }
+            song.bitrate = song.bitrate.or(info.bitrate);
+        });
+        if updated {
+            update_playlist_version(&state).await;
+        }
+    });
+}
+
 /// Sets `status.state` and emits `PlayerStateChanged`. Call-sites needing
 /// additional status mutations (e.g. clearing `current_song`) do so separately.
 pub(crate) async fn update_player_state(state: &AppState, new_state: PlayerState) {
@@ -93,7 +193,7 @@ pub(crate) fn extract_audio_format(song: &Song) -> Option<AudioFormat> {
 
 /// `case_sensitive=true` → exact match (`find`), `false` → substring/FTS (`search`).
 pub(crate) fn resolve_filters(
-    db: &rmpd_library::Database,
+    db: &dyn rmpd_library::LibraryStore,
     filters: &[(String, String)],
     command: &str,
     case_sensitive: bool,
@@ -0,0 +1,257 @@
+//! External command hooks.
+//!
+//! Runs the `[[hook]]` commands from the config when their event happens — a
+//! new song, play/pause/stop, or the end of a library update — so users no
+//! longer need mpdcron-style wrapper scripts sitting in `idle` loops. Each
+//! command runs through `sh -c` in the background (a slow script never holds
+//! up playback) with the event described in environment variables:
+//!
+//! - `RMPD_EVENT`: `song_change`, `play`, `pause`, `stop` or `database_update`
+//! - `RMPD_STATE`: the player state (`play`, `pause`, `stop`)
+//! - `RMPD_MUSIC_DIRECTORY`: the main music directory, when configured
+//! - `RMPD_SONG_URI`, `RMPD_SONG_TITLE`, `RMPD_SONG_ARTIST`,
+//!   `RMPD_SONG_ALBUM`, `RMPD_SONG_DURATION` (seconds), `RMPD_SONG_POS`,
+//!   `RMPD_SONG_ID`: the current song, when there is one
+
+use std::process::Stdio;
+
+use rmpd_core::config::{Hook, HookEvent};
+use rmpd_core::event::Event;
+use rmpd_core::state::PlayerState;
+use tokio::process::Command;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::state::AppState;
+
+/// Handle that keeps the hook dispatcher alive. Dropping it stops running
+/// hooks (commands already started keep running).
+pub struct HooksHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for HooksHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start running the enabled `hooks` on events from `state`'s event bus.
+/// Returns `None` when no hook is enabled.
+pub fn spawn(state: AppState, hooks: &[Hook]) -> Option<HooksHandle> {
+    let hooks: Vec<Hook> = hooks.iter().filter(|h| h.enabled).cloned().collect();
+    if hooks.is_empty() {
+        return None;
+    }
+    info!("{} event hook(s) enabled", hooks.len());
+
+    let mut rx = state.event_bus.subscribe();
+    let task = tokio::spawn(async move {
+        let mut tracker = Tracker::default();
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let Some(kind) = tracker.observe(&event) else {
+                        continue;
+                    };
+                    if !hooks.iter().any(|h| h.event == kind) {
+                        continue;
+                    }
+                    let env = environment(&state, kind, tracker.state).await;
+                    for hook in hooks.iter().filter(|h| h.event == kind) {
+                        run(hook, &env);
+                    }
+                }
+                Err(RecvError::Lagged(n)) => {
+                    warn!("hooks: event receiver lagged, skipped {n} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Some(HooksHandle { task })
+}
+
+/// Turns the event stream into hook events. The player re-announces its
+/// state on every song start, so play/pause/stop only fire on a change.
+#[derive(Debug, Default)]
+struct Tracker {
+    state: PlayerState,
+}
+
+impl Tracker {
+    fn observe(&mut self, event: &Event) -> Option<HookEvent> {
+        match event {
+            Event::SongChanged(Some(_)) => Some(HookEvent::SongChange),
+            Event::PlayerStateChanged(s) if *s != self.state => {
+                self.state = *s;
+                Some(match s {
+                    PlayerState::Play => HookEvent::Play,
+                    PlayerState::Pause => HookEvent::Pause,
+                    PlayerState::Stop => HookEvent::Stop,
+                })
+            }
+            Event::DatabaseUpdateFinished => Some(HookEvent::DatabaseUpdate),
+            _ => None,
+        }
+    }
+}
+
+/// Environment for a hook run: the event, the player state and the current
+/// song as found in the queue.
+async fn environment(
+    state: &AppState,
+    kind: HookEvent,
+    player: PlayerState,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("RMPD_EVENT", kind.as_str().to_owned()),
+        ("RMPD_STATE", player.to_string()),
+    ];
+    if let Some(dir) = state.music_dir() {
+        env.push(("RMPD_MUSIC_DIRECTORY", dir));
+    }
+
+    let Some(pos) = state.status.read().await.current_song else {
+        return env;
+    };
+    let queue = state.queue.read().await;
+    let Some(item) = queue.get_by_id(pos.id) else {
+        return env;
+    };
+    let song = item.tagged_song();
+    env.push(("RMPD_SONG_URI", song.path.to_string()));
+    env.push(("RMPD_SONG_POS", pos.position.to_string()));
+    env.push(("RMPD_SONG_ID", pos.id.to_string()));
+    env.push(("RMPD_SONG_TITLE", song.display_title().to_owned()));
+    env.push(("RMPD_SONG_ARTIST", song.display_artist().to_owned()));
+    env.push(("RMPD_SONG_ALBUM", song.display_album().to_owned()));
+    if let Some(duration) = song.duration {
+        env.push((
+            "RMPD_SONG_DURATION",
+            format!("{:.3}", duration.as_secs_f64()),
+        ));
+    }
+    env
+}
+
+/// Start `hook`'s command and log its exit status once it finishes.
+fn run(hook: &Hook, env: &[(&'static str, String)]) {
+    debug!(
+        "hooks: running {} hook: {}",
+        hook.event.as_str(),
+        hook.command
+    );
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .envs(env.iter().map(|(k, v)| (*k, v)))
+        .stdin(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("hooks: failed to start {:?}: {e}", hook.command);
+            return;
+        }
+    };
+    let command = hook.command.clone();
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                warn!("hooks: {command:?} exited with {status}");
+            }
+            Ok(_) => {}
+            Err(e) => warn!("hooks: failed to wait for {command:?}: {e}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmpd_core::test_utils::make_test_song;
+    use std::time::Duration;
+
+    #[test]
+    fn tracker_fires_on_state_changes_only() {
+        let mut tracker = Tracker::default();
+        let play = Event::PlayerStateChanged(PlayerState::Play);
+        assert_eq!(tracker.observe(&play), Some(HookEvent::Play));
+        assert_eq!(tracker.observe(&play), None, "repeated state is ignored");
+        assert_eq!(
+            tracker.observe(&Event::PlayerStateChanged(PlayerState::Pause)),
+            Some(HookEvent::Pause)
+        );
+        assert_eq!(
+            tracker.observe(&Event::PlayerStateChanged(PlayerState::Stop)),
+            Some(HookEvent::Stop)
+        );
+        assert_eq!(tracker.observe(&Event::SongChanged(None)), None);
+        assert_eq!(
+            tracker.observe(&Event::SongChanged(Some(make_test_song("a.flac", 1)))),
+            Some(HookEvent::SongChange)
+        );
+        assert_eq!(
+            tracker.observe(&Event::DatabaseUpdateFinished),
+            Some(HookEvent::DatabaseUpdate)
+        );
+        assert_eq!(tracker.observe(&Event::QueueChanged), None);
+    }
+
+    #[tokio::test]
+    async fn environment_describes_current_song() {
+        let state = AppState::new();
+        let id = state
+            .queue
+            .write()
+            .await
+            .add(make_test_song("Artist/Album/01.flac", 1));
+        state.status.write().await.current_song =
+            Some(rmpd_core::state::QueuePosition { position: 0, id });
+
+        let env = environment(&state, HookEvent::SongChange, PlayerState::Play).await;
+        let get = |key: &str| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("RMPD_EVENT"), Some("song_change"));
+        assert_eq!(get("RMPD_STATE"), Some("play"));
+        assert_eq!(get("RMPD_SONG_URI"), Some("Artist/Album/01.flac"));
+        assert_eq!(get("RMPD_SONG_TITLE"), Some("Track 1"));
+        assert_eq!(get("RMPD_SONG_ARTIST"), Some("Test Artist"));
+        assert_eq!(get("RMPD_SONG_DURATION"), Some("180.000"));
+        assert_eq!(get("RMPD_SONG_POS"), Some("0"));
+    }
+
+    #[tokio::test]
+    async fn hook_command_runs_with_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hook.out");
+        let state = AppState::new();
+        let hooks = [Hook {
+            event: HookEvent::DatabaseUpdate,
+            command: format!("echo \"$RMPD_EVENT $RMPD_STATE\" > '{}'", out.display()),
+            enabled: true,
+        }];
+        let _handle = spawn(state.clone(), &hooks).expect("one hook is enabled");
+
+        state.event_bus.emit(Event::DatabaseUpdateFinished);
+        let mut written = String::new();
+        for _ in 0..100 {
+            written = std::fs::read_to_string(&out).unwrap_or_default();
+            if !written.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(written, "database_update stop\n");
+    }
+
+    #[tokio::test]
+    async fn disabled_hooks_spawn_nothing() {
+        let hook = Hook {
+            event: HookEvent::Play,
+            command: "true".to_owned(),
+            enabled: false,
+        };
+        assert!(spawn(AppState::new(), &[hook]).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+//! API tokens for the HTTP endpoints.
+//!
+//! `[[api_token]]` blocks let web UIs and remote listeners use the HTTP
+//! endpoints (album art, transcoded streams) without knowing the MPD
+//! password. A request presents its token as `Authorization: Bearer
+//! <token>`, or as a `token` query parameter where the client cannot set
+//! headers (`<img>` and `<audio>` elements). Every endpoint checks the
+//! token against the scope it needs before routing the request; with no
+//! token configured, every endpoint follows the MPD password and
+//! `default_permissions` instead (see [`authorized`]).
+
+use rmpd_core::config::{ApiScope, ApiToken};
+use tracing::debug;
+
+use crate::art_http::percent_decode;
+use crate::connection::PERMISSION_READ;
+use crate::state::AppState;
+
+/// Outcome of checking the token of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// No token is configured.
+    Open,
+    /// The token grants the needed scope.
+    Granted,
+    /// No token, or an unknown one.
+    Unauthenticated,
+    /// A known token without the needed scope.
+    Forbidden,
+}
+
+/// Whether a request presenting `token` may use an endpoint needing `scope`.
+pub fn check(tokens: &[ApiToken], token: Option<&str>, scope: ApiScope) -> Access {
+    if tokens.is_empty() {
+        return Access::Open;
+    }
+    let Some(token) = token else {
+        return Access::Unauthenticated;
+    };
+    // Compare against every token so the time taken reveals nothing.
+    let found = tokens.iter().fold(None, |found, candidate| {
+        if constant_time_eq(candidate.token.as_bytes(), token.as_bytes()) {
+            Some(candidate)
+        } else {
+            found
+        }
+    });
+    match found {
+        None => Access::Unauthenticated,
+        Some(found) if found.scopes.contains(&scope) => Access::Granted,
+        Some(found) => {
+            debug!("api token {:?} lacks the {scope:?} scope", found.name);
+            Access::Forbidden
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The token presented in a request head: its bearer `Authorization`
+/// header, else its `token` query parameter.
+pub fn request_token(head: &str) -> Option<String> {
+    let mut lines = head.split("\r\n");
+    let target = lines.next()?.split(' ').nth(1)?;
+    let bearer = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.eq_ignore_ascii_case("authorization") {
+            return None;
+        }
+        let (scheme, token) = value.trim().split_once(' ')?;
+        scheme
+            .eq_ignore_ascii_case("bearer")
+            .then(|| token.trim().to_owned())
+    });
+    bearer.or_else(|| query_param(target, "token"))
+}
+
+/// The `password` query parameter of a request head.
+pub fn request_password(head: &str) -> Option<String> {
+    let target = head.split("\r\n").next()?.split(' ').nth(1)?;
+    query_param(target, "password")
+}
+
+/// The percent-decoded value of the `name` parameter of a request target.
+fn query_param(target: &str, name: &str) -> Option<String> {
+    let (_, query) = target.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key == name {
+            percent_decode(value)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether a client sending `password` (if any) may read through an HTTP
+/// endpoint when no token is configured: it needs the `read` permission,
+/// from `default_permissions` (or the absence of a password) or from the
+/// password.
+pub fn authorized(state: &AppState, password: Option<&str>) -> bool {
+    if state.initial_permissions() & PERMISSION_READ != 0 {
+        return true;
+    }
+    match (&state.password, password) {
+        (Some(secret), Some(password)) => {
+            constant_time_eq(secret.as_bytes(), password.as_bytes())
+                && state.password_permissions & PERMISSION_READ != 0
+        }
+        _ => false,
+    }
+}
+
+/// The response refusing a request that `access` does not let in.
+pub fn refusal(access: Access) -> Vec<u8> {
+    match access {
+        Access::Unauthenticated => b"HTTP/1.1 401 Unauthorized\r\n\
+              WWW-Authenticate: Bearer realm=\"rmpd\"\r\n\
+              Content-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_vec(),
+        _ => crate::art_http::status_response("403 Forbidden"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(token: &str, scopes: &[ApiScope]) -> ApiToken {
+        ApiToken {
+            name: token.to_owned(),
+            token: token.to_owned(),
+            scopes: scopes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn tokens_grant_their_scopes_only() {
+        let tokens = [
+            token("listener", &[ApiScope::Read]),
+            token("remote", &[ApiScope::Read, ApiScope::Control]),
+        ];
+        assert_eq!(check(&[], None, ApiScope::Admin), Access::Open);
+        assert_eq!(
+            check(&tokens, None, ApiScope::Read),
+            Access::Unauthenticated
+        );
+        assert_eq!(
+            check(&tokens, Some("listene"), ApiScope::Read),
+            Access::Unauthenticated
+        );
+        assert_eq!(
+            check(&tokens, Some("listener"), ApiScope::Read),
+            Access::Granted
+        );
+        assert_eq!(
+            check(&tokens, Some("listener"), ApiScope::Control),
+            Access::Forbidden
+        );
+        assert_eq!(
+            check(&tokens, Some("remote"), ApiScope::Control),
+            Access::Granted
+        );
+    }
+
+    #[test]
+    fn reading_without_tokens_follows_the_password() {
+        let mut state = AppState::new();
+        assert!(authorized(&state, None), "no password: open");
+        state.set_password(Some("secret".into()));
+        assert!(!authorized(&state, None));
+        assert!(!authorized(&state, Some("wrong")));
+        assert!(authorized(&state, Some("secret")));
+        state.set_default_permissions(Some(PERMISSION_READ));
+        assert!(authorized(&state, None));
+        assert_eq!(
+            request_password("GET /art/1?password=s%20t HTTP/1.1\r\n\r\n").as_deref(),
+            Some("s t")
+        );
+    }
+
+    #[test]
+    fn tokens_come_from_header_or_query() {
+        assert_eq!(
+            request_token("GET /art/1 HTTP/1.1\r\nAuthorization: Bearer abc\r\n\r\n").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            request_token("GET /live?format=mp3&token=a%2Bb HTTP/1.1\r\n\r\n").as_deref(),
+            Some("a+b")
+        );
+        assert_eq!(
+            request_token("GET /live HTTP/1.1\r\nAuthorization: Basic eDp5\r\n\r\n"),
+            None
+        );
+    }
+}
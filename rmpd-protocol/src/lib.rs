@@ -1,16 +1,29 @@
 #![allow(clippy::cargo_common_metadata)]
 
+pub mod art_http;
+pub mod clients;
 pub mod commands;
 pub mod connection;
 pub mod discovery;
 pub(crate) mod helpers;
+pub mod hooks;
+pub mod http_auth;
+pub mod library_cache;
+pub mod metrics_http;
 pub mod mpris;
 pub mod parser;
+pub mod power;
 pub mod queue_playback;
+pub mod registry;
 pub mod response;
+pub mod resume;
 pub mod server;
 pub mod state;
 pub mod statefile;
+pub mod stats;
+pub mod stream_http;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 pub use connection::ConnectionState;
 pub use queue_playback::QueuePlaybackManager;
@@ -0,0 +1,217 @@
+//! In-memory caches of library query results.
+//!
+//! Clients such as MALP run `lsinfo` on the root every time they connect, and
+//! ncmpcpp re-runs the same `search` as the user types; each costs several
+//! database queries. Results are kept here until the event bus reports a
+//! database change. Events are drained lazily on each lookup, so no
+//! background task is needed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rmpd_core::event::{Event, EventBus, Subsystem};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::TryRecvError;
+
+/// Directory listings kept for `lsinfo`; bounds memory for clients that walk
+/// the whole tree.
+pub const BROWSE_CACHE_SIZE: usize = 1024;
+
+/// `find`/`search` responses kept; only recent queries are worth keeping.
+pub const SEARCH_CACHE_SIZE: usize = 32;
+
+/// Least-recently-used cache of values keyed by query, emptied when the
+/// database changes. Clones share the same entries.
+#[derive(Clone)]
+pub struct LibraryCache<V> {
+    inner: Arc<Mutex<Inner<V>>>,
+}
+
+struct Inner<V> {
+    events: Receiver<Event>,
+    /// Values with the tick of their last use.
+    entries: HashMap<String, (V, u64)>,
+    capacity: usize,
+    tick: u64,
+    /// Bumped on every invalidation, so a value loaded across a database
+    /// change is not stored.
+    generation: u64,
+    /// `get_or_load` calls answered from the cache, and those that loaded.
+    hits: u64,
+    misses: u64,
+}
+
+impl<V> Inner<V> {
+    /// Drop every entry if the database changed since the last call.
+    fn sync(&mut self) {
+        let mut stale = false;
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => stale |= invalidates(&event),
+                // Missed events may have included a database change.
+                Err(TryRecvError::Lagged(_)) => stale = true,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+        if stale {
+            self.entries.clear();
+            self.generation += 1;
+        }
+    }
+
+    fn touch(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// Whether `event` may change a query result.
+fn invalidates(event: &Event) -> bool {
+    matches!(event, Event::DatabaseUpdateFinished)
+        || event.subsystems().contains(&Subsystem::Database)
+}
+
+impl<V: Clone> LibraryCache<V> {
+    /// An empty cache of at most `capacity` entries, invalidated by database
+    /// events on `event_bus`.
+    pub fn new(event_bus: &EventBus, capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                events: event_bus.subscribe(),
+                entries: HashMap::new(),
+                capacity,
+                tick: 0,
+                generation: 0,
+                hits: 0,
+                misses: 0,
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<V>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached value for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut inner = self.lock();
+        inner.sync();
+        let tick = inner.touch();
+        let (value, used) = inner.entries.get_mut(key)?;
+        *used = tick;
+        Some(value.clone())
+    }
+
+    /// How many [`Self::get_or_load`] calls were `(hits, misses)`.
+    pub fn hit_counts(&self) -> (u64, u64) {
+        let inner = self.lock();
+        (inner.hits, inner.misses)
+    }
+
+    /// The value for `key`, from the cache or else from `load`. The lock is
+    /// not held while loading; errors are returned and not cached.
+    pub fn get_or_load<E>(&self, key: &str, load: impl FnOnce() -> Result<V, E>) -> Result<V, E> {
+        let generation = {
+            let mut inner = self.lock();
+            inner.sync();
+            let tick = inner.touch();
+            if let Some((value, used)) = inner.entries.get_mut(key) {
+                *used = tick;
+                let value = value.clone();
+                inner.hits += 1;
+                return Ok(value);
+            }
+            inner.misses += 1;
+            inner.generation
+        };
+
+        let value = load()?;
+
+        let mut inner = self.lock();
+        inner.sync();
+        if inner.generation == generation && inner.capacity > 0 {
+            if inner.entries.len() >= inner.capacity {
+                let oldest = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (_, used))| *used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    inner.entries.remove(&oldest);
+                }
+            }
+            let tick = inner.touch();
+            inner.entries.insert(key.to_owned(), (value.clone(), tick));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn values_are_cached_until_database_changes() {
+        let bus = EventBus::new();
+        let cache = LibraryCache::new(&bus, 4);
+        let loads = Cell::new(0);
+        let load = || {
+            loads.set(loads.get() + 1);
+            Ok::<_, ()>(loads.get())
+        };
+
+        assert_eq!(cache.get_or_load("", load), Ok(1));
+        assert_eq!(cache.get_or_load("", load), Ok(1));
+        assert_eq!(cache.get(""), Some(1));
+
+        bus.emit(Event::QueueChanged);
+        assert_eq!(cache.get(""), Some(1), "unrelated events keep the cache");
+
+        bus.emit(Event::DatabaseChanged);
+        assert_eq!(cache.get(""), None);
+        assert_eq!(cache.get_or_load("", load), Ok(2));
+        assert_eq!(cache.hit_counts(), (1, 2));
+    }
+
+    #[test]
+    fn value_loaded_across_a_change_is_not_stored() {
+        let bus = EventBus::new();
+        let cache = LibraryCache::new(&bus, 4);
+
+        let loaded = cache.get_or_load("dir", || {
+            bus.emit(Event::SongDeleted {
+                path: "dir/a.flac".into(),
+            });
+            Ok::<_, ()>("old")
+        });
+        assert_eq!(loaded, Ok("old"));
+        assert_eq!(cache.get("dir"), None);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let bus = EventBus::new();
+        let cache = LibraryCache::<u32>::new(&bus, 4);
+        assert_eq!(
+            cache.get_or_load("missing", || Err("no such directory")),
+            Err("no such directory")
+        );
+        assert_eq!(cache.get("missing"), None);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let bus = EventBus::new();
+        let cache = LibraryCache::new(&bus, 2);
+        cache.get_or_load("a", || Ok::<_, ()>(1)).unwrap();
+        cache.get_or_load("b", || Ok::<_, ()>(2)).unwrap();
+        assert_eq!(cache.get("a"), Some(1));
+
+        cache.get_or_load("c", || Ok::<_, ()>(3)).unwrap();
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c"), Some(3));
+    }
+}
@@ -0,0 +1,177 @@
+//! Daemon statistics over HTTP.
+//!
+//! With `network.metrics_port` set, rmpd answers `GET /metrics` with the
+//! counters of [`crate::stats`] in the Prometheus text format, so they can
+//! be scraped and graphed next to other services. Like the `stats` command,
+//! requests need the `read` permission, passing the password as
+//! `?password=<secret>` when `default_permissions` does not grant it. With
+//! `[[api_token]]`s configured, a token with the `read` scope is needed
+//! instead (see [`crate::http_auth`]).
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rmpd_core::config::ApiScope;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::art_http::{REQUEST_TIMEOUT, read_head, status_response};
+use crate::http_auth::{self, Access};
+use crate::state::AppState;
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Handle that keeps the endpoint listening. Dropping it stops accepting
+/// requests.
+pub struct MetricsHttpHandle {
+    task: tokio::task::JoinHandle<()>,
+    local_addr: SocketAddr,
+}
+
+impl MetricsHttpHandle {
+    /// The address the endpoint listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsHttpHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start serving the statistics of `state` on `addr`:`port` (0 picks a free
+/// port).
+pub async fn spawn(state: AppState, addr: &str, port: u16) -> std::io::Result<MetricsHttpHandle> {
+    let listener = TcpListener::bind((addr, port)).await?;
+    let local_addr = listener.local_addr()?;
+    info!("metrics HTTP endpoint listening on {local_addr}");
+
+    let task = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(&state, stream).await {
+                            debug!("metrics http: {peer}: {e}");
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("metrics http: accept failed: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(MetricsHttpHandle { task, local_addr })
+}
+
+/// Answer the one request sent on `stream`, then close it.
+async fn serve(state: &AppState, mut stream: TcpStream) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+    let response = match head.as_deref().and_then(parse_request) {
+        Some(head_only) => {
+            let token = head.as_deref().and_then(http_auth::request_token);
+            let password = head.as_deref().and_then(http_auth::request_password);
+            match http_auth::check(&state.api_tokens, token.as_deref(), ApiScope::Read) {
+                Access::Granted => respond(state, head_only),
+                Access::Open if http_auth::authorized(state, password.as_deref()) => {
+                    respond(state, head_only)
+                }
+                Access::Open => status_response("403 Forbidden"),
+                denied => http_auth::refusal(denied),
+            }
+        }
+        None => match head {
+            Some(_) => status_response("404 Not Found"),
+            None => status_response("400 Bad Request"),
+        },
+    };
+    stream.write_all(&response).await?;
+    stream.shutdown().await
+}
+
+/// Whether a request for the metrics is `HEAD` (headers only); `None` for
+/// any other request.
+fn parse_request(head: &str) -> Option<bool> {
+    let mut request_line = head.split("\r\n").next()?.split(' ');
+    let head_only = match request_line.next()? {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return None,
+    };
+    let path = request_line.next()?.split('?').next()?;
+    (path == "/metrics").then_some(head_only)
+}
+
+fn respond(state: &AppState, head_only: bool) -> Vec<u8> {
+    let body = crate::stats::metrics(&state.stats.snapshot(state));
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    if !head_only {
+        response.extend_from_slice(body.as_bytes());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::http_request;
+
+    #[test]
+    fn only_the_metrics_path_is_served() {
+        assert_eq!(parse_request("GET /metrics HTTP/1.1\r\n\r\n"), Some(false));
+        assert_eq!(
+            parse_request("HEAD /metrics?x=1 HTTP/1.1\r\n\r\n"),
+            Some(true)
+        );
+        assert_eq!(parse_request("GET /metrics/x HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(parse_request("POST /metrics HTTP/1.1\r\n\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn counters_are_served_as_prometheus_text() {
+        let state = AppState::new();
+        state.stats.record_command();
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+        let addr = handle.local_addr();
+
+        let response = http_request(addr, "GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("\r\n\r\n# HELP rmpd_uptime_seconds_total"));
+        assert!(response.contains("\nrmpd_commands_total 1\n"));
+
+        let response = http_request(addr, "GET /stats HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn the_password_guards_the_metrics() {
+        let mut state = AppState::new();
+        state.set_password(Some("secret".into()));
+        let handle = spawn(state, "127.0.0.1", 0).await.unwrap();
+        let addr = handle.local_addr();
+
+        let response = http_request(addr, "GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(
+            response.starts_with("HTTP/1.1 403 Forbidden\r\n"),
+            "{response}"
+        );
+        let response = http_request(addr, "GET /metrics?password=secret HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    }
+}
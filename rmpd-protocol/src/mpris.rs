@@ -179,7 +179,7 @@ async fn build_metadata(state: &AppState) -> Metadata {
     let Some(item) = queue.get_by_id(id) else {
         return Metadata::new();
     };
-    let song: Song = (*item.song).clone();
+    let song: Song = item.tagged_song().into_owned();
     drop(queue);
 
     let mut m = Metadata::new();
@@ -220,7 +220,7 @@ async fn build_metadata(state: &AppState) -> Metadata {
     if let Some(d) = song.duration.or(length) {
         m.set_length(Some(Time::from_micros(d.as_micros() as i64)));
     }
-    m.set_url(Some(song_url(&song, state.music_dir.as_deref())));
+    m.set_url(Some(song_url(&song, state)));
     m
 }
 
@@ -230,12 +230,12 @@ fn track_id(id: u32) -> TrackId {
 }
 
 /// Build a `file://` URI (or pass through an existing stream URL) for a song.
-fn song_url(song: &Song, music_dir: Option<&str>) -> String {
+fn song_url(song: &Song, state: &AppState) -> String {
     let path = song.path.as_str();
     if path.contains("://") {
         return path.to_owned();
     }
-    let abs = rmpd_core::path::resolve_path(path, music_dir);
+    let abs = state.resolve_uri(path);
     // Minimal escaping: percent-encode characters that are invalid in a URI path.
     let mut encoded = String::with_capacity(abs.len() + 8);
     for b in abs.bytes() {
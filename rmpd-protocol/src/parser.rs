@@ -4,256 +4,297 @@ use winnow::error::{ContextError, ErrMode};
 use winnow::prelude::*;
 use winnow::token::{take_till, take_while};
 
+use crate::registry;
+
 // Type alias for parser results (winnow 0.7 compatibility)
 type PResult<O> = Result<O, ErrMode<ContextError>>;
 
 #[derive(Debug, Clone, PartialEq, rmpd_macros::CommandMetadata)]
 pub enum Command {
     // Playback control
-    #[command(name = "play", permission = 4)]
+    #[command(name = "play", permission = 4, args = "0..=1")]
     Play { position: Option<u32> },
-    #[command(name = "playid", permission = 4)]
+    #[command(name = "playid", permission = 4, args = "0..=1")]
     PlayId { id: Option<u32> },
-    #[command(name = "pause", permission = 4)]
+    #[command(name = "pause", permission = 4, args = "0..=1")]
     Pause { state: Option<bool> },
-    #[command(name = "stop", permission = 4)]
+    #[command(name = "stop", permission = 4, args = "0")]
     Stop,
-    #[command(name = "next", permission = 4)]
+    #[command(name = "next", permission = 4, args = "0")]
     Next,
-    #[command(name = "previous", permission = 4)]
+    #[command(name = "previous", permission = 4, args = "0")]
     Previous,
-    #[command(name = "seek", permission = 4)]
+    #[command(name = "seek", permission = 4, args = "2")]
     Seek { position: u32, time: f64 },
-    #[command(name = "seekid", permission = 4)]
+    #[command(name = "seekid", permission = 4, args = "2")]
     SeekId { id: u32, time: f64 },
-    #[command(name = "seekcur", permission = 4)]
+    #[command(name = "seekcur", permission = 4, args = "1")]
     SeekCur { time: f64, relative: bool },
 
     // Queue management
-    #[command(name = "add", permission = 2)]
+    #[command(name = "add", permission = 2, args = "1..=2")]
     Add { uri: String, position: Option<u32> },
-    #[command(name = "addid", permission = 2)]
+    #[command(name = "addid", permission = 2, args = "1..=2")]
     AddId { uri: String, position: Option<u32> },
-    #[command(name = "delete", permission = 4)]
+    #[command(name = "delete", permission = 4, args = "1")]
     Delete { target: DeleteTarget },
-    #[command(name = "deleteid", permission = 4)]
+    #[command(name = "deleteid", permission = 4, args = "1")]
     DeleteId { id: u32 },
-    #[command(name = "clear", permission = 4)]
+    #[command(name = "clear", permission = 4, args = "0")]
     Clear,
-    #[command(name = "move", permission = 4)]
+    #[command(name = "move", permission = 4, args = "2")]
     Move { from: MoveFrom, to: u32 },
-    #[command(name = "moveid", permission = 4)]
+    #[command(name = "moveid", permission = 4, args = "2")]
     MoveId { id: u32, to: u32 },
-    #[command(name = "shuffle", permission = 4)]
+    #[command(name = "shuffle", permission = 4, args = "0..=1")]
     Shuffle { range: Option<(u32, u32)> },
-    #[command(name = "swap", permission = 4)]
+    #[command(name = "swap", permission = 4, args = "2")]
     Swap { pos1: u32, pos2: u32 },
-    #[command(name = "swapid", permission = 4)]
+    #[command(name = "swapid", permission = 4, args = "2")]
     SwapId { id1: u32, id2: u32 },
 
     // Status
-    #[command(name = "status", permission = 1)]
+    #[command(name = "status", permission = 1, args = "0")]
     Status,
-    #[command(name = "currentsong", permission = 1)]
+    #[command(name = "currentsong", permission = 1, args = "0")]
     CurrentSong,
-    #[command(name = "stats", permission = 1)]
+    #[command(name = "stats", permission = 1, args = "0")]
     Stats,
-    #[command(name = "clearerror", permission = 4)]
+    /// rmpd extension: daemon-wide counters and library scan history.
+    #[command(name = "daemonstats", permission = 1, args = "0")]
+    DaemonStats,
+    #[command(name = "clearerror", permission = 4, args = "0")]
     ClearError,
 
     // Queue inspection
-    #[command(name = "playlistinfo", permission = 1)]
+    #[command(name = "playlistinfo", permission = 1, args = "0..=1")]
     PlaylistInfo { range: Option<(u32, u32)> },
-    #[command(name = "playlistid", permission = 1)]
+    #[command(name = "playlistid", permission = 1, args = "0..=1")]
     PlaylistId { id: Option<u32> },
-    #[command(name = "playlist", permission = 1)]
+    #[command(name = "playlist", permission = 1, args = "0")]
     Playlist,
-    #[command(name = "plchanges", permission = 1)]
+    #[command(name = "plchanges", permission = 1, args = "1..=2")]
     PlChanges {
         version: u32,
         range: Option<(u32, u32)>,
     },
-    #[command(name = "plchangesposid", permission = 1)]
+    #[command(name = "plchangesposid", permission = 1, args = "1..=2")]
     PlChangesPosId {
         version: u32,
         range: Option<(u32, u32)>,
     },
-    #[command(name = "playlistfind", permission = 1)]
+    #[command(name = "playlistfind", permission = 1, args = "1..")]
     PlaylistFind { tag: String, value: String },
-    #[command(name = "playlistsearch", permission = 1)]
+    #[command(name = "playlistsearch", permission = 1, args = "1..")]
     PlaylistSearch { tag: String, value: String },
 
     // Volume
-    #[command(name = "setvol", permission = 4)]
+    #[command(name = "setvol", permission = 4, args = "1")]
     SetVol { volume: u8 },
-    #[command(name = "volume", permission = 4)]
+    #[command(name = "volume", permission = 4, args = "1")]
     Volume { change: i32 },
-    #[command(name = "getvol", permission = 1)]
+    #[command(name = "getvol", permission = 1, args = "0")]
     GetVol,
 
     // Options
-    #[command(name = "repeat", permission = 4)]
+    #[command(name = "repeat", permission = 4, args = "1")]
     Repeat { enabled: bool },
-    #[command(name = "random", permission = 4)]
+    #[command(name = "random", permission = 4, args = "1")]
     Random { enabled: bool },
-    #[command(name = "single", permission = 4)]
+    #[command(name = "single", permission = 4, args = "1")]
     Single { mode: String },
-    #[command(name = "consume", permission = 4)]
+    #[command(name = "consume", permission = 4, args = "1")]
     Consume { mode: String },
-    #[command(name = "crossfade", permission = 4)]
+    #[command(name = "crossfade", permission = 4, args = "1")]
     Crossfade { seconds: u32 },
-    #[command(name = "replay_gain_mode", permission = 4)]
+    #[command(name = "replay_gain_mode", permission = 4, args = "1")]
     ReplayGainMode { mode: String },
-    #[command(name = "replay_gain_status", permission = 1)]
+    #[command(name = "replay_gain_status", permission = 1, args = "0")]
     ReplayGainStatus,
 
     // Connection
     #[command(name = "close")]
     Close,
-    #[command(name = "ping")]
+    #[command(name = "ping", args = "0")]
     Ping,
-    #[command(name = "password")]
+    #[command(name = "password", args = "1")]
     Password { password: String },
-    #[command(name = "binarylimit")]
+    #[command(name = "binarylimit", args = "1")]
     BinaryLimit { size: u32 },
-    #[command(name = "protocol")]
+    #[command(name = "client", args = "1")]
+    Client { name: String },
+    #[command(name = "listclients", permission = 8, args = "0")]
+    ListClients,
+    #[command(name = "protocol", args = "0..")]
     Protocol {
         subcommand: Option<ProtocolSubcommand>,
     },
 
     // Reflection
-    #[command(name = "commands")]
+    #[command(name = "commands", args = "0")]
     Commands,
-    #[command(name = "notcommands")]
+    #[command(name = "notcommands", args = "0")]
     NotCommands,
-    #[command(name = "tagtypes")]
+    #[command(name = "tagtypes", args = "0..")]
     TagTypes {
         subcommand: Option<TagTypesSubcommand>,
     },
-    #[command(name = "urlhandlers", permission = 1)]
+    #[command(name = "urlhandlers", permission = 1, args = "0")]
     UrlHandlers,
-    #[command(name = "decoders", permission = 1)]
+    #[command(name = "decoders", permission = 1, args = "0")]
     Decoders,
-    #[command(name = "stringnormalization")]
+    #[command(name = "stringnormalization", args = "0..")]
     StringNormalization,
 
     // Database
-    #[command(name = "update", permission = 4)]
+    #[command(name = "update", permission = 4, args = "0..=1")]
     Update { path: Option<String> },
-    #[command(name = "rescan", permission = 4)]
+    #[command(name = "rescan", permission = 4, args = "0..=1")]
     Rescan { path: Option<String> },
-    #[command(name = "find", permission = 1)]
+    /// rmpd extension: dry run of `update`.
+    #[command(name = "updatepreview", permission = 4, args = "0")]
+    UpdatePreview,
+    /// rmpd extension: format/sample-rate/bit-depth composition of the
+    /// library or one directory.
+    #[command(name = "libraryreport", permission = 1, args = "0..=1")]
+    LibraryReport { uri: Option<String> },
+    #[command(name = "find", permission = 1, args = "1..")]
     Find {
         filters: Vec<(String, String)>,
         sort: Option<String>,
         window: Option<(u32, u32)>,
     },
-    #[command(name = "search", permission = 1)]
+    #[command(name = "search", permission = 1, args = "1..")]
     Search {
         filters: Vec<(String, String)>,
         sort: Option<String>,
         window: Option<(u32, u32)>,
     },
-    #[command(name = "list", permission = 1)]
+    #[command(name = "list", permission = 1, args = "1..")]
     List {
         tag: String,
         filter_tag: Option<String>,
         filter_value: Option<String>,
-        group: Option<String>,
+        /// Tags of the `group` clauses, in the order given.
+        groups: Vec<String>,
     },
-    #[command(name = "listall", permission = 1)]
+    #[command(name = "listall", permission = 1, args = "0..=1")]
     ListAll { path: Option<String> },
-    #[command(name = "listallinfo", permission = 1)]
+    #[command(name = "listallinfo", permission = 1, args = "0..=1")]
     ListAllInfo { path: Option<String> },
-    #[command(name = "lsinfo", permission = 1)]
+    #[command(name = "lsinfo", permission = 1, args = "0..=1")]
     LsInfo { path: Option<String> },
-    #[command(name = "count", permission = 1)]
+    #[command(name = "count", permission = 1, args = "1..")]
     Count {
         filters: Vec<(String, String)>,
         group: Option<String>,
     },
-    #[command(name = "searchcount", permission = 1)]
+    #[command(name = "searchcount", permission = 1, args = "1..")]
     SearchCount {
         tag: String,
         value: String,
         group: Option<String>,
     },
-    #[command(name = "getfingerprint", permission = 1)]
+    #[command(name = "getfingerprint", permission = 1, args = "1")]
     GetFingerprint { uri: String },
-    #[command(name = "readcomments", permission = 1)]
+    #[command(name = "readcomments", permission = 1, args = "1")]
     ReadComments { uri: String },
+    #[command(name = "readlyrics", permission = 1, args = "1")]
+    ReadLyrics { uri: String },
+    #[command(name = "writetag", permission = 8, args = "3")]
+    WriteTag {
+        uri: String,
+        tag: String,
+        value: String,
+    },
+    #[command(name = "cleartag", permission = 8, args = "2")]
+    ClearTag { uri: String, tag: String },
 
     // Album art
-    #[command(name = "albumart", permission = 1)]
+    #[command(name = "albumart", permission = 1, args = "2")]
     AlbumArt { uri: String, offset: usize },
-    #[command(name = "readpicture", permission = 1)]
+    #[command(name = "readpicture", permission = 1, args = "2")]
     ReadPicture { uri: String, offset: usize },
 
     // Stored playlists
-    #[command(name = "save", permission = 4)]
+    #[command(name = "save", permission = 4, args = "1..=2")]
     Save {
         name: String,
         mode: Option<SaveMode>,
     },
-    #[command(name = "load", permission = 2)]
+    #[command(name = "load", permission = 2, args = "1..=3")]
     Load {
         name: String,
         range: Option<(u32, u32)>,
         position: Option<u32>,
     },
-    #[command(name = "listplaylists", permission = 1)]
+    #[command(name = "listplaylists", permission = 1, args = "0")]
     ListPlaylists,
-    #[command(name = "listplaylist", permission = 1)]
+    #[command(name = "listplaylist", permission = 1, args = "1..=2")]
     ListPlaylist {
         name: String,
         range: Option<(u32, u32)>,
     },
-    #[command(name = "listplaylistinfo", permission = 1)]
+    #[command(name = "listplaylistinfo", permission = 1, args = "1..=2")]
     ListPlaylistInfo {
         name: String,
         range: Option<(u32, u32)>,
     },
-    #[command(name = "playlistadd", permission = 4)]
+    #[command(name = "playlistadd", permission = 4, args = "2..=3")]
     PlaylistAdd {
         name: String,
         uri: String,
         position: Option<u32>,
     },
-    #[command(name = "playlistclear", permission = 4)]
+    #[command(name = "playlistclear", permission = 4, args = "1")]
     PlaylistClear { name: String },
-    #[command(name = "playlistdelete", permission = 4)]
+    #[command(name = "playlistdelete", permission = 4, args = "2")]
     PlaylistDelete { name: String, position: u32 },
-    #[command(name = "playlistmove", permission = 4)]
+    #[command(name = "playlistmove", permission = 4, args = "3")]
     PlaylistMove { name: String, from: u32, to: u32 },
-    #[command(name = "rm", permission = 4)]
+    #[command(name = "rm", permission = 4, args = "1")]
     Rm { name: String },
-    #[command(name = "rename", permission = 4)]
+    #[command(name = "rename", permission = 4, args = "2")]
     Rename { from: String, to: String },
-    #[command(name = "searchplaylist", permission = 1)]
+    #[command(name = "searchplaylist", permission = 1, args = "2..")]
     SearchPlaylist {
         name: String,
         tag: String,
         value: String,
     },
-    #[command(name = "playlistlength", permission = 1)]
+    #[command(name = "playlistlength", permission = 1, args = "1")]
     PlaylistLength { name: String },
 
+    // Queue snapshots (rmpd extension)
+    /// rmpd extension: save the queue and playback position as a bookmark.
+    #[command(name = "savebookmark", permission = 4, args = "1")]
+    SaveBookmark { name: String },
+    /// rmpd extension: replace the queue with a bookmark and resume it.
+    #[command(name = "loadbookmark", permission = 4, args = "1")]
+    LoadBookmark { name: String },
+    /// rmpd extension: list saved bookmarks.
+    #[command(name = "listbookmarks", permission = 1, args = "0")]
+    ListBookmarks,
+    /// rmpd extension: delete a bookmark.
+    #[command(name = "deletebookmark", permission = 4, args = "1")]
+    DeleteBookmark { name: String },
+
     // Idle notifications
-    #[command(name = "idle")]
+    #[command(name = "idle", permission = 1, args = "0..")]
     Idle { subsystems: Vec<String> },
-    #[command(name = "noidle")]
+    #[command(name = "noidle", args = "0", hidden)]
     NoIdle,
 
     // Output control
-    #[command(name = "outputs", permission = 1)]
+    #[command(name = "outputs", permission = 1, args = "0")]
     Outputs,
-    #[command(name = "enableoutput", permission = 8)]
+    #[command(name = "enableoutput", permission = 8, args = "1")]
     EnableOutput { id: u32 },
-    #[command(name = "disableoutput", permission = 8)]
+    #[command(name = "disableoutput", permission = 8, args = "1")]
     DisableOutput { id: u32 },
-    #[command(name = "toggleoutput", permission = 8)]
+    #[command(name = "toggleoutput", permission = 8, args = "1")]
     ToggleOutput { id: u32 },
-    #[command(name = "outputset", permission = 8)]
+    #[command(name = "outputset", permission = 8, args = "3")]
     OutputSet {
         id: u32,
         name: String,
@@ -261,130 +302,130 @@ pub enum Command {
     },
 
     // Command batching
-    #[command(name = "command_list")]
+    #[command(name = "command_list_begin", args = "0", hidden)]
     CommandListBegin,
-    #[command(name = "command_list")]
+    #[command(name = "command_list_ok_begin", args = "0", hidden)]
     CommandListOkBegin,
-    #[command(name = "command_list")]
+    #[command(name = "command_list_end", args = "0", hidden)]
     CommandListEnd,
 
     // Advanced database
-    #[command(name = "searchadd", permission = 2)]
+    #[command(name = "searchadd", permission = 2, args = "1..")]
     SearchAdd { tag: String, value: String },
-    #[command(name = "searchaddpl", permission = 2)]
+    #[command(name = "searchaddpl", permission = 2, args = "2..")]
     SearchAddPl {
         name: String,
         tag: String,
         value: String,
     },
-    #[command(name = "findadd", permission = 2)]
+    #[command(name = "findadd", permission = 2, args = "1..")]
     FindAdd { tag: String, value: String },
-    #[command(name = "listfiles", permission = 1)]
+    #[command(name = "listfiles", permission = 1, args = "0..=1")]
     ListFiles { uri: Option<String> },
 
     // Sticker database
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerGet { uri: String, name: String },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerSet {
         uri: String,
         name: String,
         value: String,
     },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerDelete { uri: String, name: Option<String> },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerList { uri: String },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerFind {
         uri: String,
         name: String,
         value: Option<String>,
     },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerInc {
         uri: String,
         name: String,
         delta: Option<i32>,
     },
-    #[command(name = "sticker", permission = 4)]
+    #[command(name = "sticker", permission = 4, args = "3..")]
     StickerDec {
         uri: String,
         name: String,
         delta: Option<i32>,
     },
-    #[command(name = "stickernames", permission = 1)]
-    StickerNames { uri: Option<String> },
-    #[command(name = "stickertypes", permission = 1)]
+    #[command(name = "stickernames", permission = 1, args = "0")]
+    StickerNames,
+    #[command(name = "stickertypes", permission = 1, args = "0")]
     StickerTypes,
-    #[command(name = "stickernamestypes", permission = 1)]
-    StickerNamesTypes { uri: Option<String> },
+    #[command(name = "stickernamestypes", permission = 1, args = "0..=1")]
+    StickerNamesTypes { sticker_type: Option<String> },
 
     // Partitions
-    #[command(name = "partition", permission = 4)]
+    #[command(name = "partition", permission = 4, args = "1")]
     Partition { name: String },
-    #[command(name = "listpartitions", permission = 1)]
+    #[command(name = "listpartitions", permission = 1, args = "0")]
     ListPartitions,
-    #[command(name = "newpartition", permission = 8)]
+    #[command(name = "newpartition", permission = 8, args = "1")]
     NewPartition { name: String },
-    #[command(name = "delpartition", permission = 8)]
+    #[command(name = "delpartition", permission = 8, args = "1")]
     DelPartition { name: String },
-    #[command(name = "moveoutput", permission = 8)]
+    #[command(name = "moveoutput", permission = 8, args = "1")]
     MoveOutput { name: String },
 
     // Mounts
-    #[command(name = "mount", permission = 8)]
+    #[command(name = "mount", permission = 8, args = "2")]
     Mount { path: String, uri: String },
-    #[command(name = "unmount", permission = 8)]
+    #[command(name = "unmount", permission = 8, args = "1")]
     Unmount { path: String },
-    #[command(name = "listmounts", permission = 1)]
+    #[command(name = "listmounts", permission = 1, args = "0")]
     ListMounts,
-    #[command(name = "listneighbors", permission = 1)]
+    #[command(name = "listneighbors", permission = 1, args = "0")]
     ListNeighbors,
 
     // Client-to-client messaging
-    #[command(name = "subscribe", permission = 4)]
+    #[command(name = "subscribe", permission = 4, args = "1")]
     Subscribe { channel: String },
-    #[command(name = "unsubscribe", permission = 4)]
+    #[command(name = "unsubscribe", permission = 4, args = "1")]
     Unsubscribe { channel: String },
-    #[command(name = "channels", permission = 1)]
+    #[command(name = "channels", permission = 1, args = "0")]
     Channels,
-    #[command(name = "readmessages", permission = 4)]
+    #[command(name = "readmessages", permission = 4, args = "0")]
     ReadMessages,
-    #[command(name = "sendmessage", permission = 4)]
+    #[command(name = "sendmessage", permission = 4, args = "2")]
     SendMessage { channel: String, message: String },
 
     // Advanced queue operations
-    #[command(name = "prio", permission = 4)]
+    #[command(name = "prio", permission = 4, args = "2..")]
     Prio {
         priority: u8,
         ranges: Vec<(u32, u32)>,
     },
-    #[command(name = "prioid", permission = 4)]
+    #[command(name = "prioid", permission = 4, args = "2..")]
     PrioId { priority: u8, ids: Vec<u32> },
-    #[command(name = "rangeid", permission = 4)]
-    RangeId { id: u32, range: (f64, f64) },
-    #[command(name = "addtagid", permission = 4)]
+    #[command(name = "rangeid", permission = 4, args = "2")]
+    RangeId { id: u32, range: Option<(f64, f64)> },
+    #[command(name = "addtagid", permission = 4, args = "3")]
     AddTagId { id: u32, tag: String, value: String },
-    #[command(name = "cleartagid", permission = 4)]
+    #[command(name = "cleartagid", permission = 4, args = "1..=2")]
     ClearTagId { id: u32, tag: Option<String> },
 
     // Miscellaneous
-    #[command(name = "config", permission = 8)]
+    #[command(name = "config", permission = 8, args = "0")]
     Config,
     #[command(name = "kill", permission = 8)]
     Kill,
-    #[command(name = "mixrampdb", permission = 4)]
+    #[command(name = "mixrampdb", permission = 4, args = "1")]
     MixRampDb { decibels: f32 },
-    #[command(name = "mixrampdelay", permission = 4)]
+    #[command(name = "mixrampdelay", permission = 4, args = "1")]
     MixRampDelay { seconds: f32 },
 
     // Unknown/Invalid
-    #[command(name = "unknown")]
+    #[command(name = "unknown", internal)]
     Unknown(String),
-    #[command(name = "unknown")]
+    #[command(name = "unknown", internal)]
     UnknownSubcmd(String, String),
-    #[command(name = "unknown")]
+    #[command(name = "unknown", internal)]
     ArgError(String, String, String),
 }
 
@@ -426,43 +467,107 @@ pub enum SaveMode {
     Replace, // Replace existing playlist
 }
 
+/// Parse one protocol line into a [`Command`].
+///
+/// The line is first split into arguments with MPD's tokenizer rules (see
+/// [`tokenize_args`]); malformed quoting yields [`Command::ArgError`] with
+/// MPD's message. The command name is then resolved through the
+/// [`registry`](crate::registry): names it does not know become
+/// [`Command::Unknown`], and argument counts outside the registered arity are
+/// rejected with MPD's wording before the per-command grammar runs.
 pub fn parse_command(input: &str) -> Result<Command, String> {
     let input = input.trim();
     if input.is_empty() {
         return Err("Empty command".to_string());
     }
 
-    command_parser.parse(input).map_err(|_| {
-        // Extract just the command name (first token) for a useful error message.
-        // If parsing fails after the command name is known, it is almost always an
-        // arg-count mismatch, so report "wrong number of arguments for \"cmd\"".
-        let cmd_name = input.split_whitespace().next().unwrap_or(input);
-        // Commands with min=max args use "wrong number"; those with min<max use
-        // "too few". We use a small lookup table to match MPD exactly.
-        let too_few = matches!(
-            cmd_name,
-            "addid"
-                | "add"
-                | "find"
-                | "search"
-                | "list"
-                | "findadd"
-                | "searchadd"
-                | "searchaddpl"
-                | "listplaylist"
-                | "listplaylistinfo"
-                | "searchplaylist"
-                | "load"
-                | "save"
-                | "playlistfind"
-                | "playlistsearch"
-        );
-        if too_few {
-            format!("too few arguments for \"{cmd_name}\"")
+    let (name, rest) = registry::split_name(input);
+    if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        let token = input.split_whitespace().next().unwrap_or(input);
+        return Err(format!("unknown command \"{token}\""));
+    }
+    if rest.starts_with(|c: char| !is_separator(c)) {
+        return Err("Invalid word character".to_string());
+    }
+
+    // MPD tokenizes the whole line before looking the command up, so quoting
+    // errors win over "unknown command".
+    let args = match tokenize_args(rest) {
+        Ok(args) => args,
+        Err(msg) => {
+            return Ok(Command::ArgError(
+                name.to_string(),
+                msg.to_string(),
+                input.to_string(),
+            ));
+        }
+    };
+
+    let Some(spec) = registry::lookup(name) else {
+        return Ok(Command::Unknown(name.to_string()));
+    };
+    spec.check_arity(args.len())?;
+
+    command_parser.parse(input).map_err(|_| spec.arity_error())
+}
+
+/// MPD treats every byte up to and including space (controls, tab, space)
+/// as an argument separator.
+fn is_separator(c: char) -> bool {
+    c <= ' '
+}
+
+/// Characters MPD accepts in an unquoted argument.
+fn is_unquoted_char(c: char) -> bool {
+    !is_separator(c) && c != '"' && c != '\''
+}
+
+/// Split the argument part of a command line exactly like MPD's `Tokenizer`:
+///
+/// - a `"`-quoted argument may contain anything; `\` escapes the following
+///   character (so `\"` and `\\` are a literal quote and backslash) and the
+///   closing quote must be followed by a separator or end of line;
+/// - an unquoted argument is a run of characters above space, excluding `"`
+///   and `'`;
+/// - `""` is a valid, empty argument.
+///
+/// Errors carry MPD's ACK message text.
+fn tokenize_args(input: &str) -> Result<Vec<String>, &'static str> {
+    let mut args = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        while chars.next_if(|&c| is_separator(c)).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(args);
+        };
+
+        let mut arg = String::new();
+        if first == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => arg.push(c),
+                        None => return Err("Missing closing '\"'"),
+                    },
+                    Some(c) => arg.push(c),
+                    None => return Err("Missing closing '\"'"),
+                }
+            }
+            if chars.peek().is_some_and(|&c| !is_separator(c)) {
+                return Err("Space expected after closing '\"'");
+            }
         } else {
-            format!("wrong number of arguments for \"{cmd_name}\"")
+            while let Some(c) = chars.next_if(|&c| !is_separator(c)) {
+                if !is_unquoted_char(c) {
+                    return Err("Invalid unquoted character");
+                }
+                arg.push(c);
+            }
         }
-    })
+        args.push(arg);
+    }
 }
 
 fn command_parser(input: &mut &str) -> PResult<Command> {
@@ -583,6 +688,7 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
         "status" => Ok(Command::Status),
         "currentsong" => Ok(Command::CurrentSong),
         "stats" => Ok(Command::Stats),
+        "daemonstats" => Ok(Command::DaemonStats),
         "clearerror" => Ok(Command::ClearError),
         "playlistinfo" => {
             let range = opt(parse_range).parse_next(input)?;
@@ -713,6 +819,11 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let size = parse_u32_or_quoted.parse_next(input)?;
             Ok(Command::BinaryLimit { size })
         }
+        "client" => {
+            let name = parse_string.parse_next(input)?;
+            Ok(Command::Client { name })
+        }
+        "listclients" => Ok(Command::ListClients),
         "protocol" => {
             // Check for subcommand
             if input.is_empty() {
@@ -832,6 +943,11 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let path = opt(parse_string).parse_next(input)?;
             Ok(Command::Rescan { path })
         }
+        "updatepreview" => Ok(Command::UpdatePreview),
+        "libraryreport" => {
+            let uri = opt(parse_string).parse_next(input)?;
+            Ok(Command::LibraryReport { uri })
+        }
         "find" => {
             let (filters, sort, window) = parse_find_search_filters(input)?;
             Ok(Command::Find {
@@ -852,43 +968,22 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let tag = parse_quoted_or_unquoted.parse_next(input)?;
             let _ = space0.parse_next(input)?;
 
-            // Try to parse optional filter or group
+            // Try to parse optional filter or groups
             let saved_input = *input;
             let next_token = opt(parse_quoted_or_unquoted)
                 .parse_next(input)?
                 .filter(|s| !s.is_empty()); // Filter out empty strings
 
-            let (filter_tag, filter_value, group) = if next_token.as_deref() == Some("group") {
-                // Format: list TAG group GROUPTYPE
-                let _ = space0.parse_next(input)?;
-                let group_type = opt(parse_quoted_or_unquoted)
-                    .parse_next(input)?
-                    .filter(|s| !s.is_empty());
-                (None, None, group_type)
+            let (filter_tag, filter_value, groups) = if next_token.as_deref() == Some("group") {
+                // Format: list TAG group GROUPTYPE [group GROUPTYPE ...]
+                *input = saved_input;
+                (None, None, parse_list_groups(input)?)
             } else if let Some(ft) = next_token.clone() {
                 if ft.starts_with('(') {
-                    // Filter expression: list TAG "(expr)" [group GROUPTYPE]
-                    let _ = space0.parse_next(input)?;
-
-                    // Check for optional "group" keyword
-                    let saved_input2 = *input;
-                    let group_keyword = opt(parse_quoted_or_unquoted)
-                        .parse_next(input)?
-                        .filter(|s| !s.is_empty());
-
-                    let group_type = if group_keyword.as_deref() == Some("group") {
-                        let _ = space0.parse_next(input)?;
-                        opt(parse_quoted_or_unquoted)
-                            .parse_next(input)?
-                            .filter(|s| !s.is_empty())
-                    } else {
-                        *input = saved_input2;
-                        None
-                    };
-
-                    (Some(ft), None, group_type)
+                    // Filter expression: list TAG "(expr)" [group GROUPTYPE ...]
+                    (Some(ft), None, parse_list_groups(input)?)
                 } else {
-                    // Traditional: list TAG FILTER_TAG FILTER_VALUE [t2 v2 ...] [group GROUPTYPE]
+                    // Traditional: list TAG FILTER_TAG FILTER_VALUE [t2 v2 ...] [group GROUPTYPE ...]
                     let _ = space0.parse_next(input)?;
                     let fv = parse_quoted_or_unquoted.parse_next(input)?;
                     let _ = space0.parse_next(input)?;
@@ -924,21 +1019,7 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
                         }
                     }
 
-                    // Check for optional "group" keyword
-                    let saved_input2 = *input;
-                    let group_keyword = opt(parse_quoted_or_unquoted)
-                        .parse_next(input)?
-                        .filter(|s| !s.is_empty());
-
-                    let group_type = if group_keyword.as_deref() == Some("group") {
-                        let _ = space0.parse_next(input)?;
-                        opt(parse_quoted_or_unquoted)
-                            .parse_next(input)?
-                            .filter(|s| !s.is_empty())
-                    } else {
-                        *input = saved_input2;
-                        None
-                    };
+                    let groups = parse_list_groups(input)?;
 
                     // If we have extra pairs, build a combined expression string
                     let (ft_out, fv_out) = if extra_pairs.is_empty() {
@@ -952,19 +1033,19 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
                         (Some(expr), None)
                     };
 
-                    (ft_out, fv_out, group_type)
+                    (ft_out, fv_out, groups)
                 }
             } else {
                 // Format: list TAG
                 *input = saved_input;
-                (None, None, None)
+                (None, None, Vec::new())
             };
 
             Ok(Command::List {
                 tag,
                 filter_tag,
                 filter_value,
-                group,
+                groups,
             })
         }
         "listall" => {
@@ -1083,6 +1164,24 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let uri = parse_quoted_or_unquoted.parse_next(input)?;
             Ok(Command::ReadComments { uri })
         }
+        "readlyrics" => {
+            let uri = parse_quoted_or_unquoted.parse_next(input)?;
+            Ok(Command::ReadLyrics { uri })
+        }
+        "writetag" => {
+            let uri = parse_quoted_or_unquoted.parse_next(input)?;
+            let _ = space0.parse_next(input)?;
+            let tag = parse_string.parse_next(input)?;
+            let _ = space0.parse_next(input)?;
+            let value = parse_quoted_or_unquoted.parse_next(input)?;
+            Ok(Command::WriteTag { uri, tag, value })
+        }
+        "cleartag" => {
+            let uri = parse_quoted_or_unquoted.parse_next(input)?;
+            let _ = space0.parse_next(input)?;
+            let tag = parse_string.parse_next(input)?;
+            Ok(Command::ClearTag { uri, tag })
+        }
         "albumart" => {
             let uri = parse_quoted_or_unquoted.parse_next(input)?;
             let _ = space0.parse_next(input)?;
@@ -1206,6 +1305,19 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let name = parse_quoted_or_unquoted.parse_next(input)?;
             Ok(Command::PlaylistLength { name })
         }
+        "savebookmark" => {
+            let name = parse_quoted_or_unquoted.parse_next(input)?;
+            Ok(Command::SaveBookmark { name })
+        }
+        "loadbookmark" => {
+            let name = parse_quoted_or_unquoted.parse_next(input)?;
+            Ok(Command::LoadBookmark { name })
+        }
+        "listbookmarks" => Ok(Command::ListBookmarks),
+        "deletebookmark" => {
+            let name = parse_quoted_or_unquoted.parse_next(input)?;
+            Ok(Command::DeleteBookmark { name })
+        }
         "idle" => {
             // Parse optional subsystem list
             let mut subsystems = Vec::new();
@@ -1345,14 +1457,11 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
                 _ => Ok(Command::Unknown(format!("sticker {operation}"))),
             }
         }
-        "stickernames" => {
-            let uri = opt(parse_quoted_or_unquoted).parse_next(input)?;
-            Ok(Command::StickerNames { uri })
-        }
+        "stickernames" => Ok(Command::StickerNames),
         "stickertypes" => Ok(Command::StickerTypes),
         "stickernamestypes" => {
-            let uri = opt(parse_quoted_or_unquoted).parse_next(input)?;
-            Ok(Command::StickerNamesTypes { uri })
+            let sticker_type = opt(parse_quoted_or_unquoted).parse_next(input)?;
+            Ok(Command::StickerNamesTypes { sticker_type })
         }
         // Partitions
         "partition" => {
@@ -1451,27 +1560,38 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
             let id = parse_u32.parse_next(input)?;
             let _ = space0.parse_next(input)?;
             // MPD format: start:end (floats with colon separator)
-            // Both parts optional: ":" means clear range, "0.5:" means start only, etc.
-            let rest = input.trim();
+            // Both parts optional: ":" clears the range, "0.5:" plays from
+            // 0.5s to the end, ":10" the first ten seconds.
+            let rest = parse_quoted_or_unquoted.parse_next(input)?;
             let range = if let Some(colon_pos) = rest.find(':') {
                 let start_str = &rest[..colon_pos];
                 let end_str = &rest[colon_pos + 1..];
-                let start: Option<f64> = if start_str.is_empty() {
-                    None
-                } else {
-                    Some(start_str.parse().map_err(|_| {
-                        winnow::error::ErrMode::Backtrack(winnow::error::ContextError::new())
-                    })?)
-                };
-                let end: Option<f64> = if end_str.is_empty() {
-                    None
-                } else {
-                    Some(end_str.parse().map_err(|_| {
-                        winnow::error::ErrMode::Backtrack(winnow::error::ContextError::new())
-                    })?)
+                // A bound is a time in the song: neither negative nor
+                // `nan`/`inf`, which `f64` would otherwise accept.
+                let bound = |s: &str| -> PResult<Option<f64>> {
+                    if s.is_empty() {
+                        return Ok(None);
+                    }
+                    match s.parse::<f64>() {
+                        Ok(t) if t.is_finite() && t >= 0.0 => Ok(Some(t)),
+                        _ => Err(ErrMode::Backtrack(ContextError::new())),
+                    }
                 };
-                *input = "";
-                (start.unwrap_or(0.0), end.unwrap_or(0.0))
+                let start = bound(start_str)?;
+                let end = bound(end_str)?;
+                // An end before the start cannot be played; an end of 0
+                // (or none) plays to the end of the song.
+                if let (Some(start), Some(end)) = (start, end)
+                    && end < start
+                {
+                    return Err(winnow::error::ErrMode::Backtrack(
+                        winnow::error::ContextError::new(),
+                    ));
+                }
+                match (start, end) {
+                    (None, None) => None,
+                    _ => Some((start.unwrap_or(0.0), end.unwrap_or(0.0))),
+                }
             } else {
                 return Err(winnow::error::ErrMode::Backtrack(
                     winnow::error::ContextError::new(),
@@ -1508,7 +1628,19 @@ fn command_parser(input: &mut &str) -> PResult<Command> {
     }
 }
 
+/// Parse a double-quoted token as a number (libmpdclient quotes numeric
+/// arguments too).
+fn parse_quoted_number<T: std::str::FromStr>(input: &mut &str) -> PResult<T> {
+    parse_quoted_string
+        .parse_next(input)?
+        .parse()
+        .map_err(|_| ErrMode::Cut(ContextError::default()))
+}
+
 fn parse_u32(input: &mut &str) -> PResult<u32> {
+    if input.starts_with('"') {
+        return parse_quoted_number(input);
+    }
     take_while(1.., |c: char| c.is_ascii_digit())
         .parse_next(input)?
         .parse()
@@ -1524,6 +1656,9 @@ fn parse_u32_or_quoted(input: &mut &str) -> PResult<u32> {
 }
 
 fn parse_u8(input: &mut &str) -> PResult<u8> {
+    if input.starts_with('"') {
+        return parse_quoted_number(input);
+    }
     take_while(1.., |c: char| c.is_ascii_digit())
         .parse_next(input)?
         .parse()
@@ -1688,7 +1823,30 @@ fn parse_sort_window(input: &mut &str) -> PResult<(Option<String>, Option<(u32,
     Ok((sort, window))
 }
 
+/// Parse the trailing `group TAG` clauses of `list`, as many as given.
+/// Stops at end of input or any other token (which it leaves unconsumed).
+fn parse_list_groups(input: &mut &str) -> PResult<Vec<String>> {
+    let mut groups = Vec::new();
+    loop {
+        let _ = space0.parse_next(input)?;
+        let saved_input = *input;
+        if opt(parse_quoted_or_unquoted).parse_next(input)?.as_deref() != Some("group") {
+            *input = saved_input;
+            break;
+        }
+        let _ = space0.parse_next(input)?;
+        match opt(parse_quoted_or_unquoted).parse_next(input)? {
+            Some(tag) if !tag.is_empty() => groups.push(tag),
+            _ => break,
+        }
+    }
+    Ok(groups)
+}
+
 fn parse_f64(input: &mut &str) -> PResult<f64> {
+    if input.starts_with('"') {
+        return parse_quoted_number(input);
+    }
     take_while(1.., |c: char| {
         c.is_ascii_digit() || c == '.' || c == '-' || c == '+'
     })
@@ -1715,8 +1873,13 @@ fn parse_bool_or_quoted(input: &mut &str) -> PResult<bool> {
     }
 }
 
+/// Parse a single (quoted or bare) word argument.
 fn parse_string(input: &mut &str) -> PResult<String> {
-    take_till(1.., |c: char| c.is_whitespace() || c == '\n' || c == '\r')
+    parse_quoted_or_unquoted.parse_next(input)
+}
+
+fn parse_unquoted(input: &mut &str) -> PResult<String> {
+    take_till(1.., is_separator)
         .map(|s: &str| s.to_string())
         .parse_next(input)
 }
@@ -1725,7 +1888,7 @@ fn parse_quoted_or_unquoted(input: &mut &str) -> PResult<String> {
     if input.starts_with('"') {
         parse_quoted_string.parse_next(input)
     } else {
-        parse_string.parse_next(input)
+        parse_unquoted.parse_next(input)
     }
 }
 
@@ -1992,6 +2155,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_list_with_groups() {
+        let list =
+            |filter_tag: Option<&str>, filter_value: Option<&str>, groups: &[&str]| Command::List {
+                tag: "album".to_string(),
+                filter_tag: filter_tag.map(str::to_string),
+                filter_value: filter_value.map(str::to_string),
+                groups: groups.iter().map(|g| g.to_string()).collect(),
+            };
+        assert_eq!(
+            parse_command("list album group albumartist group date").unwrap(),
+            list(None, None, &["albumartist", "date"])
+        );
+        assert_eq!(
+            parse_command("list album \"(genre == \\\"Rock\\\")\" group \"date\"").unwrap(),
+            list(Some("(genre == \"Rock\")"), None, &["date"])
+        );
+        assert_eq!(
+            parse_command("list album artist Metallica group date group genre").unwrap(),
+            list(Some("artist"), Some("Metallica"), &["date", "genre"])
+        );
+    }
+
     // libmpdclient (used by mympd, mpc, ncmpcpp, …) quotes *every* command
     // argument, and MPD's tokenizer accepts quoted or unquoted uniformly. These
     // guard the two commands whose parsers were not quote-aware, which broke the
@@ -2130,4 +2316,105 @@ mod tests {
             }
         );
     }
+
+    /// Quote an argument like libmpdclient's `mpd_quote`: wrap in `"` and
+    /// backslash-escape `"` and `\`.
+    fn mpd_quote(arg: &str) -> String {
+        let mut out = String::from("\"");
+        for c in arg.chars() {
+            if c == '"' || c == '\\' {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out.push('"');
+        out
+    }
+
+    #[test]
+    fn test_tokenizer_libmpdclient_quoting_matrix() {
+        let cases = [
+            "",
+            "plain",
+            "with space",
+            "  leading and trailing  ",
+            "tab\there",
+            "quote\"inside",
+            "\"",
+            "back\\slash",
+            "\\",
+            "trailing backslash\\",
+            "it's",
+            "ünïcödé ♫",
+            "(artist == 'x')",
+        ];
+        for case in cases {
+            assert_eq!(
+                tokenize_args(&format!(" {}", mpd_quote(case))),
+                Ok(vec![case.to_string()]),
+                "{case:?}"
+            );
+            assert_eq!(
+                parse_command(&format!("add {}", mpd_quote(case))).unwrap(),
+                Command::Add {
+                    uri: case.to_string(),
+                    position: None
+                },
+                "{case:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokenizer_splits_like_mpd() {
+        assert_eq!(tokenize_args(""), Ok(vec![]));
+        assert_eq!(
+            tokenize_args(" a  \"b c\"\t\"\" d"),
+            Ok(vec![
+                "a".to_string(),
+                "b c".to_string(),
+                String::new(),
+                "d".to_string()
+            ])
+        );
+        // Any escaped character stands for itself.
+        assert_eq!(tokenize_args(r#" "\a\b""#), Ok(vec!["ab".to_string()]));
+    }
+
+    #[test]
+    fn test_tokenizer_errors_match_mpd() {
+        assert_eq!(tokenize_args(r#" "open"#), Err("Missing closing '\"'"));
+        assert_eq!(tokenize_args(r#" "open\""#), Err("Missing closing '\"'"));
+        assert_eq!(
+            tokenize_args(r#" "a"b"#),
+            Err("Space expected after closing '\"'")
+        );
+        assert_eq!(tokenize_args(" it's"), Err("Invalid unquoted character"));
+        assert_eq!(tokenize_args(r#" a"b""#), Err("Invalid unquoted character"));
+    }
+
+    #[test]
+    fn test_quoting_errors_become_arg_errors() {
+        assert_eq!(
+            parse_command(r#"add "song.flac"#).unwrap(),
+            Command::ArgError(
+                "add".to_string(),
+                "Missing closing '\"'".to_string(),
+                r#"add "song.flac"#.to_string()
+            )
+        );
+        // Tokenizing happens before lookup, like MPD.
+        assert!(matches!(
+            parse_command(r#"frobnicate "x"y"#).unwrap(),
+            Command::ArgError(name, _, _) if name == "frobnicate"
+        ));
+        assert_eq!(
+            parse_command("play3").unwrap(),
+            Command::Unknown("play3".to_string())
+        );
+        assert_eq!(
+            parse_command("play-1"),
+            Err("Invalid word character".to_string())
+        );
+    }
 }
@@ -0,0 +1,333 @@
+//! Sleep inhibition while playing.
+//!
+//! A laptop left alone suspends after its idle timeout, cutting the music
+//! off mid-song since nobody touches the keyboard while listening. With
+//! `audio.inhibit_sleep` on, rmpd holds a sleep inhibitor for as long as
+//! the player is playing and releases it on pause and stop:
+//!
+//! - Linux: a systemd-logind `sleep:idle` inhibitor lock, taken over the
+//!   system D-Bus and held as the file descriptor logind hands back
+//! - macOS: an IOKit `PreventUserIdleSystemSleep` power assertion
+//!
+//! Other platforms have no inhibitor and play as before.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use rmpd_core::event::{Event, EventBus};
+use rmpd_core::partition::PartitionState;
+use rmpd_core::state::PlayerState;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::state::AppState;
+
+/// Who and why, as shown by `systemd-inhibit --list` and `pmset -g
+/// assertions`.
+const WHO: &str = "rmpd";
+const WHY: &str = "Playing music";
+
+/// Handle that keeps the inhibitor tracking alive. Dropping it releases a
+/// held inhibitor and stops taking new ones.
+pub struct PowerHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for PowerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start holding a sleep inhibitor whenever the player of any of `state`'s
+/// partitions is playing.
+pub fn spawn(state: AppState) -> PowerHandle {
+    let task = tokio::spawn(async move {
+        // Each partition's player announces its state on that partition's
+        // own bus; a change on any of them has all of them checked again.
+        let (changed_tx, mut changed_rx) = mpsc::channel(1);
+        let mut watched = HashMap::new();
+        let mut inhibitor = None;
+        let mut warned = false;
+        loop {
+            watch_partitions(&state, &mut watched, &changed_tx).await;
+            // Playback may have resumed from the state file before this
+            // started, so the first pass looks too.
+            if any_playing(&state).await {
+                if inhibitor.is_none() {
+                    match platform::Inhibitor::acquire().await {
+                        Ok(held) => {
+                            debug!("sleep inhibitor taken");
+                            inhibitor = Some(held);
+                        }
+                        // The player re-announces Play on every song; only
+                        // the first failure is worth a warning.
+                        Err(e) if !warned => {
+                            warn!("cannot keep the system awake while playing: {e}");
+                            warned = true;
+                        }
+                        Err(e) => debug!("sleep inhibitor: {e}"),
+                    }
+                }
+            } else if inhibitor.take().is_some() {
+                debug!("sleep inhibitor released");
+            }
+            if changed_rx.recv().await.is_none() {
+                break;
+            }
+        }
+    });
+    info!("inhibiting system sleep while playing");
+    PowerHandle { task }
+}
+
+/// Start listening to the buses of partitions not in `watched` yet, and
+/// forget deleted ones. Partitions are told apart by identity, so one
+/// deleted and created again under the same name is listened to afresh.
+async fn watch_partitions(
+    state: &AppState,
+    watched: &mut HashMap<String, Weak<PartitionState>>,
+    changed: &mpsc::Sender<()>,
+) {
+    let Some(manager) = &state.partition_manager else {
+        if watched.is_empty() {
+            forward_changes(&state.event_bus, changed.clone());
+            watched.insert(state.partition.clone(), Weak::new());
+        }
+        return;
+    };
+    let mut current = HashMap::new();
+    for name in manager.list_partitions().await {
+        if let Some(partition) = manager.get_partition(&name).await {
+            current.insert(name, partition);
+        }
+    }
+    watched.retain(|name, seen| {
+        current
+            .get(name)
+            .is_some_and(|partition| Weak::ptr_eq(seen, &Arc::downgrade(partition)))
+    });
+    for (name, partition) in current {
+        if !watched.contains_key(&name) {
+            forward_changes(&partition.event_bus, changed.clone());
+            watched.insert(name, Arc::downgrade(&partition));
+        }
+    }
+}
+
+/// Poke `changed` whenever `bus` reports an event that may change whether
+/// the system should be kept awake, until the bus or the receiver is gone.
+fn forward_changes(bus: &EventBus, changed: mpsc::Sender<()>) {
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let relevant = match rx.recv().await {
+                Ok(event) => may_change_playing(&event),
+                Err(RecvError::Lagged(n)) => {
+                    debug!("power: event receiver lagged, skipped {n} events");
+                    true
+                }
+                Err(RecvError::Closed) => break,
+            };
+            // A full channel already has a check pending.
+            if relevant && let Err(mpsc::error::TrySendError::Closed(())) = changed.try_send(()) {
+                break;
+            }
+        }
+    });
+}
+
+/// Whether `event` may change whether some partition is playing: its
+/// player changed state, or partitions came or went.
+fn may_change_playing(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::PlayerStateChanged(_) | Event::PartitionsChanged
+    )
+}
+
+/// Whether the player of any partition is playing.
+async fn any_playing(state: &AppState) -> bool {
+    let Some(manager) = &state.partition_manager else {
+        return state.status.read().await.state == PlayerState::Play;
+    };
+    for name in manager.list_partitions().await {
+        if let Some(partition) = manager.get_partition(&name).await
+            && partition.status.read().await.state == PlayerState::Play
+        {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use mpris_server::zbus::{self, Connection, zvariant::OwnedFd};
+
+    /// A logind inhibitor lock, released when logind sees the descriptor
+    /// close.
+    pub struct Inhibitor {
+        _fd: OwnedFd,
+    }
+
+    impl Inhibitor {
+        pub async fn acquire() -> zbus::Result<Self> {
+            let connection = Connection::system().await?;
+            let reply = connection
+                .call_method(
+                    Some("org.freedesktop.login1"),
+                    "/org/freedesktop/login1",
+                    Some("org.freedesktop.login1.Manager"),
+                    "Inhibit",
+                    &("sleep:idle", super::WHO, super::WHY, "block"),
+                )
+                .await?;
+            let fd: OwnedFd = reply.body().deserialize()?;
+            Ok(Self { _fd: fd })
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{CString, c_char, c_void};
+    use std::io;
+
+    type CFStringRef = *const c_void;
+
+    const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    const K_IOPM_ASSERTION_LEVEL_ON: u32 = 255;
+    const K_IO_RETURN_SUCCESS: i32 = 0;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFStringCreateWithCString(
+            alloc: *const c_void,
+            c_str: *const c_char,
+            encoding: u32,
+        ) -> CFStringRef;
+        fn CFRelease(cf: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    unsafe extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    /// An IOKit power assertion, released on drop.
+    pub struct Inhibitor {
+        id: u32,
+    }
+
+    impl Inhibitor {
+        pub async fn acquire() -> io::Result<Self> {
+            let kind = CfString::new("PreventUserIdleSystemSleep")?;
+            let name = CfString::new(&format!("{}: {}", super::WHO, super::WHY))?;
+            let mut id = 0;
+            // SAFETY: both strings are live CFStrings and `id` is a valid
+            // out pointer.
+            let ret = unsafe {
+                IOPMAssertionCreateWithName(kind.0, K_IOPM_ASSERTION_LEVEL_ON, name.0, &mut id)
+            };
+            if ret != K_IO_RETURN_SUCCESS {
+                return Err(io::Error::other(format!(
+                    "IOPMAssertionCreateWithName failed: {ret:#x}"
+                )));
+            }
+            Ok(Self { id })
+        }
+    }
+
+    impl Drop for Inhibitor {
+        fn drop(&mut self) {
+            // SAFETY: `id` names an assertion this process created and has
+            // not released.
+            unsafe { IOPMAssertionRelease(self.id) };
+        }
+    }
+
+    /// An owned CFString.
+    struct CfString(CFStringRef);
+
+    impl CfString {
+        fn new(s: &str) -> io::Result<Self> {
+            let c_str = CString::new(s).map_err(io::Error::other)?;
+            // SAFETY: `c_str` is NUL-terminated UTF-8; CoreFoundation copies
+            // it.
+            let cf = unsafe {
+                CFStringCreateWithCString(
+                    std::ptr::null(),
+                    c_str.as_ptr(),
+                    K_CF_STRING_ENCODING_UTF8,
+                )
+            };
+            if cf.is_null() {
+                return Err(io::Error::other("CFStringCreateWithCString failed"));
+            }
+            Ok(Self(cf))
+        }
+    }
+
+    impl Drop for CfString {
+        fn drop(&mut self) {
+            // SAFETY: the string was created above and is released once.
+            unsafe { CFRelease(self.0) };
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use std::io;
+
+    /// No sleep inhibitor on this platform.
+    pub enum Inhibitor {}
+
+    impl Inhibitor {
+        pub async fn acquire() -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no sleep inhibitor on this platform",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_and_partition_changes_are_rechecked() {
+        assert!(may_change_playing(&Event::PlayerStateChanged(
+            PlayerState::Play
+        )));
+        assert!(may_change_playing(&Event::PlayerStateChanged(
+            PlayerState::Stop
+        )));
+        assert!(may_change_playing(&Event::PartitionsChanged));
+        assert!(!may_change_playing(&Event::QueueChanged));
+    }
+
+    #[tokio::test]
+    async fn any_partition_playing_counts() {
+        let state = AppState::new();
+        assert!(!any_playing(&state).await);
+
+        let manager = state.partition_manager.clone().unwrap();
+        let other = manager.create_partition("other".into()).await.unwrap();
+        other.status.write().await.state = PlayerState::Play;
+        assert!(any_playing(&state).await);
+
+        other.status.write().await.state = PlayerState::Pause;
+        assert!(!any_playing(&state).await);
+    }
+}
@@ -1,11 +1,12 @@
-use crate::commands::utils::prepare_song_for_playback;
+use crate::commands::utils::{prepare_song_for_playback, update_next_song};
 use crate::helpers;
 use crate::state::AppState;
 use rmpd_core::event::Event;
 use rmpd_core::state::{PlayerState, QueuePosition};
 use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 /// Queue playback manager that handles automatic song advancement
 #[derive(Debug)]
@@ -30,9 +31,9 @@ impl QueuePlaybackManager {
         let task = tokio::spawn(async move {
             loop {
                 match event_rx.recv().await {
-                    Ok(Event::SongFinished) => {
+                    Ok(Event::SongFinished(generation)) => {
                         info!("song finished, advancing to next");
-                        if let Err(e) = Self::handle_song_finished(&state).await {
+                        if let Err(e) = Self::handle_song_finished(&state, generation).await {
                             error!("error advancing to next song: {}", e);
                         }
                     }
@@ -78,9 +79,9 @@ impl QueuePlaybackManager {
                         debug!("stream title changed to: {:?}", title);
                         *state.stream_title.write().await = title;
                     }
-                    Ok(Event::AdvancedToNext) => {
+                    Ok(Event::AdvancedToNext(generation)) => {
                         info!("engine advanced to next song in-thread (gapless/crossfade)");
-                        if let Err(e) = Self::handle_advanced(&state).await {
+                        if let Err(e) = Self::handle_advanced(&state, generation).await {
                             error!("error handling in-thread advance: {}", e);
                         }
                         Self::feed_next_song(&state).await;
@@ -93,8 +94,13 @@ impl QueuePlaybackManager {
                         Self::feed_next_song(&state).await;
                     }
                     Ok(_) => {} // Ignore other events
-                    Err(e) => {
-                        error!("event receive error: {}", e);
+                    Err(RecvError::Lagged(skipped)) => {
+                        // Keep listening: dropping out here would silently
+                        // end auto-advance for the rest of the session.
+                        warn!("playback manager lagged, {} events skipped", skipped);
+                    }
+                    Err(RecvError::Closed) => {
+                        error!("event bus closed, stopping playback manager");
                         break;
                     }
                 }
@@ -112,9 +118,20 @@ impl QueuePlaybackManager {
     }
 
     /// Handle song finished event - advance to next song
-    async fn handle_song_finished(state: &AppState) -> rmpd_core::error::Result<()> {
+    ///
+    /// `generation` is the engine generation the finished song played under.
+    /// A manual `play`/`next`/`stop` that got to the engine first has started
+    /// a new one, and the event is dropped instead of advancing a second time;
+    /// the check is repeated under the engine lock right before acting.
+    async fn handle_song_finished(
+        state: &AppState,
+        generation: u64,
+    ) -> rmpd_core::error::Result<()> {
+        if state.engine.read().await.generation() != generation {
+            debug!("ignoring song-finished event from a replaced playback");
+            return Ok(());
+        }
         let status = state.status.read().await;
-        let queue = state.queue.read().await;
 
         // Get current song position
         let current_pos = match status.current_song {
@@ -123,66 +140,65 @@ impl QueuePlaybackManager {
         };
 
         // Check playback modes
-        let repeat = status.repeat;
-        let random = status.random;
         let single = status.single;
         let consume = status.consume;
+        let consuming = consume.is_on() || consume.is_oneshot();
 
+        // Play the song `status` announced as `nextsong`; only re-pick when a
+        // path forgot to refresh it.
+        let next_pos = {
+            let queue = state.queue.read().await;
+            match preselected_next(&status, &queue) {
+                Some(pos) => Some(pos),
+                None => next_song_position(&status, &queue, current_pos),
+            }
+        };
         drop(status);
 
-        // Determine next position
-        let queue_len = queue.len() as u32;
-        let next_pos = if random {
-            // Random mode: pick a random song
-            if queue_len > 0 {
-                use rand::RngExt;
-                let mut rng = rand::rng();
-                rng.random_range(0..queue_len)
-            } else {
-                return Ok(());
-            }
-        } else {
-            // Sequential mode
-            let next = current_pos + 1;
-
-            if next >= queue_len {
-                // Reached end of queue
-                if repeat {
-                    // Repeat mode: go back to start
-                    0
-                } else {
-                    // No repeat: stop playback
-                    debug!("end of queue reached, stopping playback");
-                    drop(queue);
-                    state.engine.write().await.stop().await?;
-                    helpers::update_player_state(state, PlayerState::Stop).await;
-                    state.status.write().await.current_song = None;
+        let Some(next_pos) = next_pos else {
+            // End of queue, single mode, or nothing left after consuming
+            debug!("no next song, stopping playback");
+            {
+                let mut engine = state.engine.write().await;
+                if engine.generation() != generation {
                     return Ok(());
                 }
-            } else {
-                next
+                engine.stop().await?;
+            }
+            helpers::update_player_state(state, PlayerState::Stop).await;
+            if consuming {
+                Self::consume_song(state, current_pos).await;
             }
+            let mut status = state.status.write().await;
+            status.current_song = None;
+            status.next_song = None;
+            if Self::spend_oneshot_modes(&mut status, single, consume) {
+                state.event_bus.emit(Event::QueueOptionsChanged);
+            }
+            return Ok(());
         };
 
         // Get the next song
-        if let Some(item) = queue.get(next_pos) {
-            let song = (*item.song).clone();
-            let item_id = item.id;
-            let range = item.range;
-            drop(queue);
-
+        let next = state
+            .queue
+            .read()
+            .await
+            .get(next_pos)
+            .map(|item| ((*item.song).clone(), item.id, item.range));
+        if let Some((song, item_id, range)) = next {
             // Handle consume mode (remove current song after playing)
-            if consume.is_on() {
-                state.queue.write().await.delete(current_pos);
-                // Notify the `playlist` idle subsystem that the consumed song was
-                // removed from the queue.
-                helpers::update_playlist_version(state).await;
-            }
+            let position = if consuming {
+                Self::consume_song(state, current_pos).await;
+                Self::position_after_consume(next_pos, current_pos)
+            } else {
+                next_pos
+            };
 
             // Play the next song
             let playback_song = match prepare_song_for_playback(
                 &song,
-                state.music_dir.as_deref(),
+                state.music_dir().as_deref(),
+                &state.music_roots,
                 range,
                 &state.sources,
             )
@@ -194,7 +210,15 @@ impl QueuePlaybackManager {
                     return Ok(());
                 }
             };
-            match state.engine.write().await.play(playback_song).await {
+            let played = {
+                let mut engine = state.engine.write().await;
+                if engine.generation() != generation {
+                    debug!("playback replaced while resolving the next song");
+                    return Ok(());
+                }
+                engine.play(playback_song).await
+            };
+            match played {
                 Ok(_) => {
                     let mut status = state.status.write().await;
                     status.state = PlayerState::Play;
@@ -202,41 +226,26 @@ impl QueuePlaybackManager {
                     status.duration = song.duration;
                     status.bitrate = song.bitrate;
                     status.audio_format = helpers::extract_audio_format(&song);
-
                     status.current_song = Some(QueuePosition {
-                        position: if consume.is_on() && next_pos > current_pos {
-                            // Adjust position if we deleted a song before it
-                            next_pos - 1
-                        } else {
-                            next_pos
-                        },
+                        position,
                         id: item_id,
                     });
 
-                    // Handle single mode
-                    let should_stop_after = single.is_on();
+                    // Oneshot modes are spent once a song has been repeated or
+                    // consumed; reset them before picking the next song.
+                    let options_changed = Self::spend_oneshot_modes(&mut status, single, consume);
+                    update_next_song(&mut status, &*state.queue.read().await, position);
 
-                    if single.is_oneshot() {
-                        // Single oneshot: play one more song then stop
-                        status.single = rmpd_core::state::SingleMode::Off;
-                    }
+                    drop(status);
 
-                    // Handle consume oneshot
-                    if consume.is_oneshot() {
-                        status.consume = rmpd_core::state::ConsumeMode::Off;
+                    if options_changed {
+                        state.event_bus.emit(Event::QueueOptionsChanged);
                     }
 
-                    drop(status);
-
                     state
                         .event_bus
                         .emit(Event::PlayerStateChanged(PlayerState::Play));
                     state.event_bus.emit(Event::SongChanged(Some(song)));
-
-                    if should_stop_after {
-                        state.engine.write().await.stop().await?;
-                        helpers::update_player_state(state, PlayerState::Stop).await;
-                    }
                 }
                 Err(e) => {
                     error!("failed to play next song: {}", e);
@@ -249,92 +258,120 @@ impl QueuePlaybackManager {
         Ok(())
     }
 
+    /// Turn off oneshot single/consume that were in effect (`single`,
+    /// `consume`) for the song that just ended, unless a client has changed
+    /// them since. Returns true when an option changed.
+    fn spend_oneshot_modes(
+        status: &mut rmpd_core::state::PlayerStatus,
+        single: rmpd_core::state::SingleMode,
+        consume: rmpd_core::state::ConsumeMode,
+    ) -> bool {
+        let mut changed = false;
+        if single.is_oneshot() && status.single.is_oneshot() {
+            status.single = rmpd_core::state::SingleMode::Off;
+            changed = true;
+        }
+        if consume.is_oneshot() && status.consume.is_oneshot() {
+            status.consume = rmpd_core::state::ConsumeMode::Off;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Queue position of the song at `next_pos` after the finished song at
+    /// `current_pos` was consumed.
+    fn position_after_consume(next_pos: u32, current_pos: u32) -> u32 {
+        if next_pos > current_pos {
+            next_pos - 1
+        } else {
+            next_pos
+        }
+    }
+
+    /// Consume mode: delete the finished song at `current_pos` and notify the
+    /// `playlist` idle subsystem.
+    async fn consume_song(state: &AppState, current_pos: u32) {
+        state.queue.write().await.delete(current_pos);
+        helpers::update_playlist_version(state).await;
+    }
+
     /// Returns the next position to look ahead to, or None when look-ahead must be
-    /// disabled (random, single engaged, or end-of-queue without repeat).
+    /// disabled (random and single, or no next song). Random picks are left to
+    /// the `SongFinished` path so a re-pick after a queue edit can't diverge
+    /// from what the engine already buffered.
     fn lookahead_next_pos(
+        status: &rmpd_core::state::PlayerStatus,
+        queue: &rmpd_core::queue::Queue,
         current_pos: u32,
-        queue_len: u32,
-        repeat: bool,
-        random: bool,
-        single: rmpd_core::state::SingleMode,
     ) -> Option<u32> {
-        if random || single.is_on() || single.is_oneshot() || queue_len == 0 {
+        if status.random || status.single.is_on() || status.single.is_oneshot() {
             return None;
         }
-        let next = current_pos + 1;
-        if next >= queue_len {
-            if repeat { Some(0) } else { None }
-        } else {
-            Some(next)
-        }
+        next_song_position(status, queue, current_pos)
     }
 
     /// Feed the engine the upcoming song for gapless/crossfade look-ahead.
     pub async fn feed_next_song(state: &AppState) {
-        let (current_pos, repeat, random, single) = {
+        let next_song = {
             let status = state.status.read().await;
-            match status.current_song {
-                Some(ref p) => (p.position, status.repeat, status.random, status.single),
-                None => {
-                    drop(status);
-                    state.engine.read().await.set_next_song(None);
-                    return;
-                }
-            }
-        };
-        let next_ps = {
+            let Some(current) = status.current_song else {
+                drop(status);
+                state.engine.read().await.set_next_song(None);
+                return;
+            };
             let queue = state.queue.read().await;
-            match Self::lookahead_next_pos(current_pos, queue.len() as u32, repeat, random, single)
+            // Range-restricted songs (CUE virtual tracks / rangeid) are not
+            // eligible for the in-thread gapless/crossfade look-ahead, which
+            // doesn't seek/limit. They fall back to the SongFinished path,
+            // where play() honors the range.
+            Self::lookahead_next_pos(&status, &queue, current.position)
+                .and_then(|np| queue.get(np))
+                .filter(|item| item.range.is_none())
+                .map(|item| (*item.song).clone())
+        };
+        let next_ps = match next_song {
+            Some(song) => match prepare_song_for_playback(
+                &song,
+                state.music_dir().as_deref(),
+                &state.music_roots,
+                None,
+                &state.sources,
+            )
+            .await
             {
-                // Range-restricted songs (CUE virtual tracks / rangeid) are not
-                // eligible for the in-thread gapless/crossfade look-ahead, which
-                // doesn't seek/limit. They fall back to the SongFinished path,
-                // where play() honors the range.
-                Some(np) => match queue.get(np).filter(|item| item.range.is_none()) {
-                    Some(item) => {
-                        match prepare_song_for_playback(
-                            &(*item.song).clone(),
-                            state.music_dir.as_deref(),
-                            item.range,
-                            &state.sources,
-                        )
-                        .await
-                        {
-                            Ok(ps) => Some(ps),
-                            Err(e) => {
-                                tracing::warn!("failed to resolve look-ahead song: {}", e);
-                                None
-                            }
-                        }
-                    }
-                    None => None,
-                },
-                None => None,
-            }
+                Ok(ps) => Some(ps),
+                Err(e) => {
+                    tracing::warn!("failed to resolve look-ahead song: {}", e);
+                    None
+                }
+            },
+            None => None,
         };
         state.engine.read().await.set_next_song(next_ps);
     }
 
     /// Handle in-thread advance event — the engine already started the next song
     /// gaplessly/via crossfade; we only update bookkeeping (no engine.play call).
-    async fn handle_advanced(state: &AppState) -> rmpd_core::error::Result<()> {
-        let (current_pos, repeat, random, single, consume) = {
+    /// Events from a replaced playback (see [`Self::handle_song_finished`])
+    /// are dropped.
+    async fn handle_advanced(state: &AppState, generation: u64) -> rmpd_core::error::Result<()> {
+        if state.engine.read().await.generation() != generation {
+            debug!("ignoring in-thread advance from a replaced playback");
+            return Ok(());
+        }
+        let (current_pos, single, consume, next_pos) = {
             let s = state.status.read().await;
-            match s.current_song {
-                Some(ref p) => (p.position, s.repeat, s.random, s.single, s.consume),
-                None => return Ok(()),
+            let Some(current) = s.current_song else {
+                return Ok(());
+            };
+            let next_pos =
+                Self::lookahead_next_pos(&s, &*state.queue.read().await, current.position);
+            match next_pos {
+                Some(np) => (current.position, s.single, s.consume, np),
+                None => return Ok(()), // shouldn't happen — engine only advances when we fed
             }
         };
-        let next_pos = match Self::lookahead_next_pos(
-            current_pos,
-            state.queue.read().await.len() as u32,
-            repeat,
-            random,
-            single,
-        ) {
-            Some(np) => np,
-            None => return Ok(()), // shouldn't happen — engine only advances when we fed
-        };
+        let consuming = consume.is_on() || consume.is_oneshot();
         let (song, item_id) = {
             let q = state.queue.read().await;
             match q.get(next_pos) {
@@ -342,10 +379,12 @@ impl QueuePlaybackManager {
                 None => return Ok(()),
             }
         };
-        if consume.is_on() {
-            state.queue.write().await.delete(current_pos);
-            helpers::update_playlist_version(state).await;
-        }
+        let position = if consuming {
+            Self::consume_song(state, current_pos).await;
+            Self::position_after_consume(next_pos, current_pos)
+        } else {
+            next_pos
+        };
         {
             let mut status = state.status.write().await;
             status.state = PlayerState::Play;
@@ -354,25 +393,88 @@ impl QueuePlaybackManager {
             status.bitrate = song.bitrate;
             status.audio_format = helpers::extract_audio_format(&song);
             status.current_song = Some(QueuePosition {
-                position: if consume.is_on() && next_pos > current_pos {
-                    next_pos - 1
-                } else {
-                    next_pos
-                },
+                position,
                 id: item_id,
             });
-            if single.is_oneshot() {
-                status.single = rmpd_core::state::SingleMode::Off;
-            }
-            if consume.is_oneshot() {
-                status.consume = rmpd_core::state::ConsumeMode::Off;
+            if Self::spend_oneshot_modes(&mut status, single, consume) {
+                state.event_bus.emit(Event::QueueOptionsChanged);
             }
+            update_next_song(&mut status, &*state.queue.read().await, position);
         }
         state.event_bus.emit(Event::SongChanged(Some(song)));
         Ok(())
     }
 }
 
+/// Position of the song to play after the one at `current_pos`, or None
+/// when playback stops there. This is the one strategy behind both the
+/// `nextsong` reported by `status` and auto-advance, so a client's "up next"
+/// is what actually plays:
+///
+/// - single mode stops after the current song, or replays it under repeat;
+/// - random mode draws from the highest-priority songs other than the
+///   current one;
+/// - otherwise the following song plays, wrapping to the start under repeat.
+///
+/// In consume mode the current song is about to be deleted, so it is never
+/// picked again.
+pub(crate) fn next_song_position(
+    status: &rmpd_core::state::PlayerStatus,
+    queue: &rmpd_core::queue::Queue,
+    current_pos: u32,
+) -> Option<u32> {
+    let queue_len = queue.len() as u32;
+    if current_pos >= queue_len {
+        return None;
+    }
+    let consuming = status.consume.is_on() || status.consume.is_oneshot();
+    let replay_current = (status.repeat && !consuming).then_some(current_pos);
+
+    if status.single.is_on() || status.single.is_oneshot() {
+        return replay_current;
+    }
+    if status.random {
+        use rand::RngExt;
+        let others = || {
+            queue
+                .items()
+                .iter()
+                .filter(|item| item.position != current_pos)
+        };
+        let Some(top) = others().map(|item| item.priority).max() else {
+            return replay_current;
+        };
+        let candidates: Vec<u32> = others()
+            .filter(|item| item.priority == top)
+            .map(|item| item.position)
+            .collect();
+        return Some(candidates[rand::rng().random_range(0..candidates.len())]);
+    }
+    let next = current_pos + 1;
+    if next < queue_len {
+        Some(next)
+    } else if status.repeat && !(consuming && current_pos == 0) {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+/// `status.next_song`'s position, provided that song is still there.
+fn preselected_next(
+    status: &rmpd_core::state::PlayerStatus,
+    queue: &rmpd_core::queue::Queue,
+) -> Option<u32> {
+    status
+        .next_song
+        .filter(|next| {
+            queue
+                .get(next.position)
+                .is_some_and(|item| item.id == next.id)
+        })
+        .map(|next| next.position)
+}
+
 impl Drop for QueuePlaybackManager {
     fn drop(&mut self) {
         self.stop();
@@ -404,3 +506,99 @@ impl ModeExt for rmpd_core::state::ConsumeMode {
         matches!(self, rmpd_core::state::ConsumeMode::Oneshot)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::QueuePlaybackManager as Manager;
+    use super::{next_song_position, preselected_next};
+    use rmpd_core::queue::Queue;
+    use rmpd_core::state::{ConsumeMode, PlayerStatus, QueuePosition, SingleMode};
+    use rmpd_core::test_utils::create_test_song;
+
+    fn queue_of(len: usize) -> Queue {
+        let mut queue = Queue::new();
+        for i in 0..len {
+            queue.add(create_test_song(i as u64, &i.to_string()));
+        }
+        queue
+    }
+
+    #[test]
+    fn next_song_sequential_and_repeat() {
+        let queue = queue_of(3);
+        let mut status = PlayerStatus::default();
+        assert_eq!(next_song_position(&status, &queue, 0), Some(1));
+        assert_eq!(next_song_position(&status, &queue, 2), None);
+        status.repeat = true;
+        assert_eq!(next_song_position(&status, &queue, 2), Some(0));
+        status.consume = ConsumeMode::On;
+        assert_eq!(next_song_position(&status, &queue, 2), Some(0));
+
+        // A consumed single song is not replayed under repeat.
+        let queue = queue_of(1);
+        assert_eq!(next_song_position(&status, &queue, 0), None);
+        status.consume = ConsumeMode::Off;
+        assert_eq!(next_song_position(&status, &queue, 0), Some(0));
+    }
+
+    #[test]
+    fn next_song_single_stops_or_repeats_current() {
+        let queue = queue_of(3);
+        let mut status = PlayerStatus {
+            single: SingleMode::On,
+            ..Default::default()
+        };
+        assert_eq!(next_song_position(&status, &queue, 1), None);
+        status.repeat = true;
+        assert_eq!(next_song_position(&status, &queue, 1), Some(1));
+        status.single = SingleMode::Oneshot;
+        assert_eq!(next_song_position(&status, &queue, 1), Some(1));
+        status.consume = ConsumeMode::On;
+        assert_eq!(next_song_position(&status, &queue, 1), None);
+    }
+
+    #[test]
+    fn next_song_random_skips_current_and_prefers_priority() {
+        let mut queue = queue_of(4);
+        let mut status = PlayerStatus {
+            random: true,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let pick = next_song_position(&status, &queue, 1).unwrap();
+            assert_ne!(pick, 1);
+            assert!(pick < 4);
+        }
+
+        queue.set_priority_range(10, &[(2, 4)]);
+        for _ in 0..50 {
+            let pick = next_song_position(&status, &queue, 3).unwrap();
+            assert_eq!(pick, 2);
+        }
+
+        let queue = queue_of(1);
+        assert_eq!(next_song_position(&status, &queue, 0), None);
+        status.repeat = true;
+        assert_eq!(next_song_position(&status, &queue, 0), Some(0));
+    }
+
+    #[test]
+    fn preselected_next_must_still_match_its_id() {
+        let mut queue = queue_of(3);
+        let mut status = PlayerStatus {
+            next_song: Some(QueuePosition { position: 1, id: 1 }),
+            ..Default::default()
+        };
+        assert_eq!(preselected_next(&status, &queue), Some(1));
+        queue.delete(0);
+        assert_eq!(preselected_next(&status, &queue), None);
+        status.next_song = None;
+        assert_eq!(preselected_next(&status, &queue), None);
+    }
+
+    #[test]
+    fn consumed_song_shifts_later_positions() {
+        assert_eq!(Manager::position_after_consume(3, 2), 2);
+        assert_eq!(Manager::position_after_consume(0, 2), 0);
+    }
+}
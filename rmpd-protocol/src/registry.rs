@@ -0,0 +1,84 @@
+//! Central command registry
+//!
+//! Every MPD command rmpd understands is described once, by the
+//! `#[command(...)]` attribute on its [`Command`] variant. The
+//! `CommandMetadata` derive turns those attributes into [`Command::COMMANDS`],
+//! a name-sorted table of [`CommandSpec`]s that drives:
+//!
+//! - parsing: [`crate::parser::parse_command`] resolves the command name and
+//!   checks the argument count here before running the per-command grammar;
+//! - dispatch: `handle_command` enforces [`CommandSpec::permission`] and
+//!   matches exhaustively on [`Command`], so a registered command without a
+//!   handler does not compile;
+//! - reflection: `commands`/`notcommands` list the non-hidden entries;
+//! - error reporting: names missing from the table are answered with
+//!   `ACK [5@N] {} unknown command "NAME"`, names present but called with the
+//!   wrong arity with `ACK [2@N] {NAME} ...` using MPD's wording.
+
+use crate::parser::Command;
+
+/// Static description of one wire-level command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Wire name as sent by clients (e.g. `"playlistadd"`).
+    pub name: &'static str,
+    /// Required permission bitmask (see [`crate::connection`]).
+    pub permission: u8,
+    /// Minimum number of arguments.
+    pub min_args: usize,
+    /// Maximum number of arguments, `None` when unbounded.
+    pub max_args: Option<usize>,
+    /// Accepted by the parser but not advertised by `commands`.
+    pub hidden: bool,
+}
+
+impl CommandSpec {
+    /// Validate an argument count, returning MPD's ACK message on mismatch.
+    pub fn check_arity(&self, argc: usize) -> Result<(), String> {
+        let name = self.name;
+        match self.max_args {
+            Some(max) if max == self.min_args && argc != max => {
+                Err(format!("wrong number of arguments for \"{name}\""))
+            }
+            _ if argc < self.min_args => Err(format!("too few arguments for \"{name}\"")),
+            Some(max) if argc > max => Err(format!("too many arguments for \"{name}\"")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Arity error for a command whose grammar rejected its arguments.
+    ///
+    /// MPD reports "wrong number" for fixed-arity commands and "too few" for
+    /// variadic ones.
+    pub fn arity_error(&self) -> String {
+        let name = self.name;
+        if self.max_args == Some(self.min_args) {
+            format!("wrong number of arguments for \"{name}\"")
+        } else {
+            format!("too few arguments for \"{name}\"")
+        }
+    }
+}
+
+/// Split a protocol line into its command name (the leading run of ASCII
+/// letters, digits and underscores, as MPD's tokenizer reads it) and the
+/// remaining argument text.
+pub fn split_name(line: &str) -> (&str, &str) {
+    let len = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(line.len());
+    line.split_at(len)
+}
+
+/// Look up a command by wire name.
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    Command::COMMANDS
+        .binary_search_by(|spec| spec.name.cmp(name))
+        .ok()
+        .map(|idx| &Command::COMMANDS[idx])
+}
+
+/// Commands advertised to clients, in name order.
+pub fn advertised() -> impl Iterator<Item = &'static CommandSpec> {
+    Command::COMMANDS.iter().filter(|spec| !spec.hidden)
+}
@@ -1,5 +1,6 @@
 use rmpd_core::song::Song;
 use rmpd_core::state::PlayerStatus;
+use std::collections::HashSet;
 use std::fmt::Write as FmtWrite;
 
 /// Database statistics
@@ -45,6 +46,8 @@ impl From<Vec<u8>> for Response {
 pub struct ResponseBuilder {
     buffer: String,
     binary_data: Option<Vec<u8>>,
+    /// Tag types the client enabled with `tagtypes`; `None` allows all.
+    enabled_tags: Option<HashSet<String>>,
 }
 
 impl ResponseBuilder {
@@ -52,6 +55,17 @@ impl ResponseBuilder {
         Self {
             buffer: String::with_capacity(4096),
             binary_data: None,
+            enabled_tags: None,
+        }
+    }
+
+    /// A builder whose [`song`](Self::song) entries carry only the tag types
+    /// in `enabled_tags` (canonical names such as `Artist`), as set by a
+    /// client's `tagtypes` commands.
+    pub fn with_tags(enabled_tags: Option<&HashSet<String>>) -> Self {
+        Self {
+            enabled_tags: enabled_tags.cloned(),
+            ..Self::new()
         }
     }
 
@@ -133,7 +147,7 @@ impl ResponseBuilder {
         self
     }
 
-    pub fn status(&mut self, status: &PlayerStatus) -> &mut Self {
+    pub fn status(&mut self, status: &PlayerStatus, partition: &str) -> &mut Self {
         self.field("volume", status.volume);
         self.field("repeat", if status.repeat { 1 } else { 0 });
         self.field("random", if status.random { 1 } else { 0 });
@@ -152,7 +166,7 @@ impl ResponseBuilder {
         };
         self.field("consume", consume_val);
 
-        self.field("partition", "default");
+        self.field("partition", partition);
 
         self.field("playlist", status.playlist_version);
         self.field("playlistlength", status.playlist_length);
@@ -231,20 +245,34 @@ impl ResponseBuilder {
         }
         // Format: samplerate:bits:channels — before tags (matching MPD's SongPrint.cxx order)
         if let Some(sr) = song.sample_rate {
-            let bits = match song.bits_per_sample {
-                Some(0) | None => "f".to_string(),
-                Some(b) => b.to_string(),
-            };
             let ch = song.channels.unwrap_or(2);
-            self.field("Format", format!("{}:{}:{}", sr, bits, ch));
+            let format = match song.bits_per_sample {
+                // 1-bit DSD: MPD prints the rate as a multiple of 44.1 kHz.
+                Some(1) => format!("dsd{}:{}", sr / 44_100, ch),
+                Some(0) | None => format!("{}:f:{}", sr, ch),
+                Some(b) => format!("{}:{}:{}", sr, b, ch),
+            };
+            self.field("Format", format);
         }
         // Tags in file insertion order (matching MPD which outputs tags as stored in the file).
-        // Comment is excluded from default tag mask (MPD's Settings.cxx: All & ~TAG_COMMENT)
+        // Comment is excluded from default tag mask (MPD's Settings.cxx: All & ~TAG_COMMENT);
+        // a configured metadata_to_use replaces that mask, also for songs not from the database.
+        let mask = rmpd_core::tag::metadata_to_use();
         for (tag, value) in &song.tags {
-            if tag == "comment" || value.is_empty() {
+            let masked = match mask {
+                Some(mask) => !mask.allows(tag),
+                None => tag == "comment",
+            };
+            if masked || value.is_empty() {
                 continue;
             }
             let canonical = rmpd_core::song::canonical_tag_name(tag);
+            if let Some(enabled) = &self.enabled_tags
+                && rmpd_core::tag::TAG_TYPES.contains(&tag.as_ref())
+                && !enabled.contains(canonical)
+            {
+                continue;
+            }
             self.field(canonical, value);
         }
         // Duration
@@ -309,6 +337,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn song_carries_only_enabled_tags() {
+        let enabled = HashSet::from(["Title".to_owned()]);
+        let mut song = source_song();
+        song.tags.push((
+            rmpd_core::song::intern_tag_key("artist"),
+            "Pink Floyd".to_owned(),
+        ));
+        let mut rb = ResponseBuilder::with_tags(Some(&enabled));
+        rb.song(&song, Some(0), Some(1));
+        let out = rb.ok();
+
+        assert!(out.contains("Title: Echoes\n"), "{out}");
+        assert!(!out.contains("Artist:"), "{out}");
+        assert!(out.contains("Format: 48000:24:2\n"), "{out}");
+        assert!(out.contains("Id: 1\n"), "{out}");
+    }
+
     /// The source song emits the MPD `Format: <rate>:<bits>:<channels>` line,
     /// and its mount-style `file` path keeps the codec-revealing `.flac` suffix.
     #[test]
@@ -326,4 +372,16 @@ mod tests {
             "expected mount-style file line with .flac extension, got:\n{out}"
         );
     }
+
+    #[test]
+    fn dsd_song_format_uses_dsd_rate_notation() {
+        let mut song = source_song();
+        song.sample_rate = Some(5_644_800);
+        song.bits_per_sample = Some(1);
+        let mut rb = ResponseBuilder::new();
+        rb.song(&song, None, None);
+        let out = rb.ok();
+
+        assert!(out.contains("Format: dsd128:2\n"), "got:\n{out}");
+    }
 }
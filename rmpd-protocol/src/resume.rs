@@ -0,0 +1,397 @@
+//! Resuming long songs where they were left off.
+//!
+//! Audiobooks and podcast episodes run for hours and are rarely heard in
+//! one sitting. With `audio.resume_min_duration` or
+//! `audio.resume_directories` set, stopping, pausing or skipping such a
+//! song mid-way saves how far it got in its `resume_position` sticker, and
+//! the next time it plays it continues from there. Playing a song to its
+//! end (or leaving it in the first seconds) forgets the position, so the
+//! next listen starts over.
+
+use std::time::Duration;
+
+use rmpd_core::event::Event;
+use rmpd_core::song::Song;
+use rmpd_core::state::PlayerState;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::commands::utils::open_db;
+use crate::state::AppState;
+
+/// Sticker holding the saved position, in seconds.
+pub const RESUME_STICKER: &str = "resume_position";
+
+/// Leaving a song this close to its start or end counts as not having
+/// started it, or as having finished it.
+const MARGIN: Duration = Duration::from_secs(10);
+
+/// A song that just started is only resumed while it is still this close to
+/// its start, so an explicit `seek` that got there first is kept.
+const RESUME_WINDOW: Duration = Duration::from_secs(2);
+
+/// Handle that keeps position tracking alive. Dropping it stops saving and
+/// resuming positions.
+pub struct ResumeHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for ResumeHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start saving and resuming the positions of songs at least `min_duration`
+/// seconds long or under one of `directories`. Returns `None` when neither
+/// is set.
+pub fn spawn(
+    state: AppState,
+    min_duration: Option<u32>,
+    directories: &[String],
+) -> Option<ResumeHandle> {
+    let policy = Policy {
+        min_duration: min_duration.map(|s| Duration::from_secs(s.into())),
+        directories: directories
+            .iter()
+            .map(|d| d.trim_matches('/').to_owned())
+            .filter(|d| !d.is_empty())
+            .collect(),
+    };
+    if policy.min_duration.is_none() && policy.directories.is_empty() {
+        return None;
+    }
+    info!("resuming long songs where they were left off");
+
+    let mut rx = state.event_bus.subscribe();
+    let task = tokio::spawn(async move {
+        let mut tracker = Tracker::new(policy);
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let generation = state.engine.read().await.generation();
+                    for action in tracker.observe(&event, generation) {
+                        if let Some(resumed) = perform(&state, action).await {
+                            tracker.resumed_at(resumed);
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(n)) => {
+                    warn!("resume: event receiver lagged, skipped {n} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Some(ResumeHandle { task })
+}
+
+/// Which songs get their position remembered.
+#[derive(Debug)]
+struct Policy {
+    min_duration: Option<Duration>,
+    directories: Vec<String>,
+}
+
+impl Policy {
+    fn applies(&self, song: &Song) -> bool {
+        let long = matches!(
+            (self.min_duration, song.duration),
+            (Some(min), Some(duration)) if duration >= min
+        );
+        long || self.directories.iter().any(|dir| {
+            song.path
+                .as_str()
+                .strip_prefix(dir.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    /// Save `elapsed` as the resume position of `uri`.
+    Save { uri: String, elapsed: Duration },
+    /// Drop any resume position of `uri`.
+    Forget { uri: String },
+    /// Seek the just started `uri` to its resume position, if it has one.
+    Resume { uri: String },
+}
+
+/// The song being played, when its position is remembered.
+#[derive(Debug)]
+struct Tracked {
+    uri: String,
+    duration: Option<Duration>,
+    elapsed: Duration,
+}
+
+/// Turns the event stream into sticker updates and seeks.
+#[derive(Debug)]
+struct Tracker {
+    policy: Policy,
+    current: Option<Tracked>,
+}
+
+impl Tracker {
+    fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            current: None,
+        }
+    }
+
+    /// The actions `event` calls for. `generation` is the engine's current
+    /// playback generation: an end-of-song event carrying an older one raced
+    /// a manual `play`/`next` and concerns a song that is no longer playing.
+    fn observe(&mut self, event: &Event, generation: u64) -> Vec<Action> {
+        match event {
+            Event::PositionChanged(elapsed) => {
+                if let Some(current) = &mut self.current {
+                    current.elapsed = *elapsed;
+                }
+                Vec::new()
+            }
+            Event::PlayerStateChanged(PlayerState::Pause) => {
+                self.current.as_ref().map(leave).into_iter().collect()
+            }
+            Event::SongFinished(finished) | Event::AdvancedToNext(finished)
+                if *finished != generation =>
+            {
+                debug!("resume: ignoring the end of a replaced playback");
+                Vec::new()
+            }
+            // Played to its end: start over next time.
+            Event::SongFinished(_) | Event::AdvancedToNext(_) => self
+                .current
+                .take()
+                .map(|t| Action::Forget { uri: t.uri })
+                .into_iter()
+                .collect(),
+            Event::SongChanged(song) => {
+                let mut actions: Vec<Action> = self
+                    .current
+                    .take()
+                    .as_ref()
+                    .map(leave)
+                    .into_iter()
+                    .collect();
+                if let Some(song) = song.as_ref().filter(|s| self.policy.applies(s)) {
+                    let uri = song.path.to_string();
+                    actions.push(Action::Resume { uri: uri.clone() });
+                    self.current = Some(Tracked {
+                        uri,
+                        duration: song.duration,
+                        elapsed: Duration::ZERO,
+                    });
+                }
+                actions
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The current song was sought to `position` on resuming.
+    fn resumed_at(&mut self, position: Duration) {
+        if let Some(current) = &mut self.current {
+            current.elapsed = position;
+        }
+    }
+}
+
+/// What to remember of `tracked` when playback leaves it.
+fn leave(tracked: &Tracked) -> Action {
+    let uri = tracked.uri.clone();
+    let near_end = tracked
+        .duration
+        .is_some_and(|d| tracked.elapsed + MARGIN >= d);
+    if tracked.elapsed < MARGIN || near_end {
+        Action::Forget { uri }
+    } else {
+        Action::Save {
+            uri,
+            elapsed: tracked.elapsed,
+        }
+    }
+}
+
+/// Carry out `action`. Returns the position sought to by a resume.
+async fn perform(state: &AppState, action: Action) -> Option<Duration> {
+    match action {
+        Action::Save { uri, elapsed } => {
+            let value = format!("{:.3}", elapsed.as_secs_f64());
+            update_sticker(state, uri, Some(value)).await;
+            None
+        }
+        Action::Forget { uri } => {
+            update_sticker(state, uri, None).await;
+            None
+        }
+        Action::Resume { uri } => resume(state, &uri).await,
+    }
+}
+
+/// Set (`Some`) or delete (`None`) the resume position of `uri`, telling
+/// idle clients when it changed.
+async fn update_sticker(state: &AppState, uri: String, value: Option<String>) {
+    let db_state = state.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let db = open_db(&db_state, "resume").ok()?;
+        let result = match value {
+            Some(value) => db.set_sticker(&uri, RESUME_STICKER, &value),
+            None => match db.get_sticker(&uri, RESUME_STICKER) {
+                Ok(Some(_)) => db.delete_sticker(&uri, Some(RESUME_STICKER)),
+                // Nothing saved, nothing changed.
+                Ok(None) => return None,
+                Err(e) => Err(e),
+            },
+        };
+        match result {
+            Ok(()) => Some(uri),
+            Err(e) => {
+                warn!("cannot save the resume position of {uri}: {e}");
+                None
+            }
+        }
+    })
+    .await;
+    if let Ok(Some(uri)) = result {
+        state.event_bus.emit(Event::StickerChanged { uri });
+    }
+}
+
+/// Seek the current song `uri` to its saved position.
+async fn resume(state: &AppState, uri: &str) -> Option<Duration> {
+    let db_state = state.clone();
+    let sticker_uri = uri.to_owned();
+    let saved = tokio::task::spawn_blocking(move || {
+        let db = open_db(&db_state, "resume").ok()?;
+        db.get_sticker(&sticker_uri, RESUME_STICKER).ok().flatten()
+    })
+    .await
+    .ok()
+    .flatten()?;
+    let position = Duration::try_from_secs_f64(saved.parse().ok()?).ok()?;
+
+    let (current, elapsed) = {
+        let status = state.status.read().await;
+        (status.current_song, status.elapsed)
+    };
+    let still_current = match current {
+        Some(c) => state
+            .queue
+            .read()
+            .await
+            .get_by_id(c.id)
+            .is_some_and(|item| item.song.path.as_str() == uri),
+        None => false,
+    };
+    if !still_current || elapsed.is_some_and(|e| e >= RESUME_WINDOW) {
+        return None;
+    }
+
+    if let Err(e) = state.engine.read().await.seek(position.as_secs_f64()).await {
+        debug!("cannot resume {uri}: {e}");
+        return None;
+    }
+    state.status.write().await.elapsed = Some(position);
+    debug!("resumed {uri} at {:.1}s", position.as_secs_f64());
+    Some(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmpd_core::test_utils::make_test_song;
+
+    fn song(path: &str, minutes: u64) -> Song {
+        let mut song = make_test_song(path, 1);
+        song.duration = Some(Duration::from_secs(minutes * 60));
+        song
+    }
+
+    fn tracker() -> Tracker {
+        Tracker::new(Policy {
+            min_duration: Some(Duration::from_secs(30 * 60)),
+            directories: vec!["Podcasts".into()],
+        })
+    }
+
+    #[test]
+    fn long_songs_and_listed_directories_are_resumed() {
+        let policy = tracker().policy;
+        assert!(policy.applies(&song("Books/novel.m4b", 600)));
+        assert!(policy.applies(&song("Podcasts/ep1.mp3", 5)));
+        assert!(!policy.applies(&song("PodcastsOld/ep1.mp3", 5)));
+        assert!(!policy.applies(&song("Music/track.flac", 4)));
+    }
+
+    #[test]
+    fn leaving_mid_way_saves_and_finishing_forgets() {
+        let mut tracker = tracker();
+        let book = song("Books/novel.m4b", 600);
+        let uri = || "Books/novel.m4b".to_owned();
+
+        assert_eq!(
+            tracker.observe(&Event::SongChanged(Some(book.clone())), 1),
+            [Action::Resume { uri: uri() }]
+        );
+        tracker.observe(&Event::PositionChanged(Duration::from_secs(754)), 1);
+        assert_eq!(
+            tracker.observe(&Event::PlayerStateChanged(PlayerState::Pause), 1),
+            [Action::Save {
+                uri: uri(),
+                elapsed: Duration::from_secs(754)
+            }]
+        );
+        assert_eq!(
+            tracker.observe(&Event::SongChanged(Some(song("Music/track.flac", 4))), 1),
+            [Action::Save {
+                uri: uri(),
+                elapsed: Duration::from_secs(754)
+            }]
+        );
+        // Untracked songs leave nothing behind.
+        assert!(tracker.observe(&Event::SongChanged(None), 1).is_empty());
+
+        tracker.observe(&Event::SongChanged(Some(book)), 1);
+        tracker.observe(&Event::PositionChanged(Duration::from_secs(3)), 1);
+        assert_eq!(
+            tracker.observe(&Event::SongChanged(None), 1),
+            [Action::Forget { uri: uri() }]
+        );
+    }
+
+    #[test]
+    fn songs_played_to_the_end_start_over() {
+        let mut tracker = tracker();
+        tracker.observe(&Event::SongChanged(Some(song("Podcasts/ep1.mp3", 40))), 1);
+        tracker.observe(&Event::PositionChanged(Duration::from_secs(40 * 60 - 1)), 1);
+        assert_eq!(
+            tracker.observe(&Event::SongFinished(1), 1),
+            [Action::Forget {
+                uri: "Podcasts/ep1.mp3".into()
+            }]
+        );
+        assert!(tracker.observe(&Event::SongChanged(None), 1).is_empty());
+    }
+
+    #[test]
+    fn stale_end_of_song_events_are_ignored() {
+        let mut tracker = tracker();
+        // A manual `next` started generation 2 and the new song before the
+        // end of the previous one (generation 1) was reported.
+        tracker.observe(&Event::SongChanged(Some(song("Books/novel.m4b", 600))), 2);
+        tracker.observe(&Event::PositionChanged(Duration::from_secs(754)), 2);
+        assert!(tracker.observe(&Event::SongFinished(1), 2).is_empty());
+        assert!(tracker.observe(&Event::AdvancedToNext(1), 2).is_empty());
+        assert_eq!(
+            tracker.observe(&Event::PlayerStateChanged(PlayerState::Pause), 2),
+            [Action::Save {
+                uri: "Books/novel.m4b".into(),
+                elapsed: Duration::from_secs(754)
+            }],
+            "the new song is still tracked"
+        );
+    }
+}
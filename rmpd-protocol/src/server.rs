@@ -1,16 +1,22 @@
 use rmpd_core::error::Result;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use rmpd_core::partition::PartitionClient;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream, UnixStream};
 use tokio::sync::broadcast;
-use tracing::{debug, error, info};
+use tracing::{Instrument, debug, error, info};
 
-use crate::commands::utils::{ACK_ERROR_ARG, ACK_ERROR_PERMISSION, ACK_ERROR_UNKNOWN};
+use crate::clients::ClientGuard;
+use crate::commands::utils::{
+    ACK_ERROR_ARG, ACK_ERROR_NO_EXIST, ACK_ERROR_PERMISSION, ACK_ERROR_UNKNOWN,
+};
 use crate::commands::{
-    connection, database, fingerprint, messaging, options, outputs, partition, playback, playlists,
-    queue, reflection, stickers, storage,
+    bookmarks, connection, database, fingerprint, messaging, options, outputs, partition, playback,
+    playlists, queue, reflection, stickers, storage,
 };
+use crate::connection::PERMISSION_READ;
 use crate::parser::{Command, parse_command};
 use crate::queue_playback::QueuePlaybackManager;
+use crate::registry;
 use crate::response::{Response, ResponseBuilder, Stats};
 use crate::state::AppState;
 
@@ -26,33 +32,93 @@ const DEFAULT_MAX_CONNECTIONS: usize = 100;
 /// command line, when not overridden via `with_connection_timeout`.
 const DEFAULT_CONNECTION_TIMEOUT_SECS: u64 = 60;
 
+/// Default quiet time (seconds) before TCP keepalive probes start, when not
+/// overridden via `with_tcp_keepalive`.
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+
+/// Keepalive probes sent before a silent peer is given up on.
+const TCP_KEEPALIVE_RETRIES: u32 = 4;
+
+/// Default longest accepted command line (bytes, newline included), when not
+/// overridden via `with_max_line_length`.
+const DEFAULT_MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Default cap on the total size of one command list (bytes), when not
+/// overridden via `with_max_command_list_size`. Matches MPD's 2048 KiB.
+const DEFAULT_MAX_COMMAND_LIST_SIZE: usize = 2048 * 1024;
+
+/// Default time (seconds) a client gets to drain one response before it is
+/// considered stalled and disconnected, when not overridden via
+/// `with_write_timeout`.
+const DEFAULT_WRITE_TIMEOUT_SECS: u64 = 30;
+
+/// Default cap on a single buffered response (bytes), when not overridden via
+/// `with_max_output_buffer_size`. Matches MPD's 8192 KiB.
+const DEFAULT_MAX_OUTPUT_BUFFER_SIZE: usize = 8192 * 1024;
+
+/// Per-connection I/O limits, copied into every client task.
+#[derive(Debug, Clone, Copy)]
+struct ClientLimits {
+    timeout: std::time::Duration,
+    max_line_length: usize,
+    max_command_list_size: usize,
+    write_timeout: std::time::Duration,
+    max_output_buffer_size: usize,
+}
+
 /// Convert a `parse_command` error into the correct ACK response string.
-/// Arg-count errors ("wrong number / too few arguments") → code 2;
-/// unknown-command errors → code 5.
+/// Errors for commands present in the registry (arity or malformed
+/// arguments) → code 2 under the command's name; anything else is an
+/// unknown command → code 5 with an empty command name, as MPD does.
 fn parse_error_to_ack(cmd_line: &str, err: &str, index: i32) -> String {
-    if err.starts_with("wrong number of arguments for") || err.starts_with("too few arguments for")
-    {
-        // Extract the command name from the double-quoted portion of `err`.
-        let cmd_name = err
-            .find('"')
-            .and_then(|s| {
-                let rest = &err[s + 1..];
-                rest.find('"').map(|e| &rest[..e])
-            })
-            .unwrap_or(cmd_line);
-        ResponseBuilder::error(ACK_ERROR_ARG, index, cmd_name, err)
-    } else {
-        // Unknown command or malformed syntax.
-        let cmd_name = cmd_line.split_whitespace().next().unwrap_or(cmd_line);
-        ResponseBuilder::error(
-            ACK_ERROR_UNKNOWN,
-            index,
-            cmd_name,
-            &format!("unknown command \"{cmd_name}\""),
-        )
+    let (name, _) = registry::split_name(cmd_line.trim());
+    match registry::lookup(name) {
+        Some(spec) => ResponseBuilder::error(ACK_ERROR_ARG, index, spec.name, err),
+        None => {
+            let cmd_name = cmd_line.split_whitespace().next().unwrap_or(cmd_line);
+            ResponseBuilder::error(
+                ACK_ERROR_UNKNOWN,
+                index,
+                "",
+                &format!("unknown command \"{cmd_name}\""),
+            )
+        }
     }
 }
 
+/// ACK for a command line that is not valid UTF-8. The command name is taken
+/// from the leading ASCII word, when there is one, so the client can tell
+/// which request was rejected.
+fn malformed_line_ack(raw: &[u8], index: i32) -> String {
+    let name_len = raw
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+        .count();
+    let name = String::from_utf8_lossy(&raw[..name_len]);
+    ResponseBuilder::error(ACK_ERROR_ARG, index, &name, "Malformed UTF-8")
+}
+
+/// Reply to an HTTP request that reached the MPD port, typically a load
+/// balancer or uptime probe.
+const HTTP_REFUSAL: &str = "HTTP/1.0 400 Bad Request\r\n\
+    Content-Type: text/plain\r\n\
+    Connection: close\r\n\
+    \r\n\
+    This is an MPD server, not an HTTP server.\n";
+
+/// Whether `line` is an HTTP request line (`GET / HTTP/1.1`). No MPD command
+/// has an upper-case name or an `HTTP/` last argument, so this never matches
+/// a real command.
+fn is_http_request(line: &str) -> bool {
+    const METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "CONNECT"];
+    let mut parts = line.split(' ');
+    matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(method), Some(_), Some(version), None)
+            if METHODS.contains(&method) && version.starts_with("HTTP/")
+    )
+}
+
 /// Convert Unix timestamp to ISO 8601 format (RFC 3339)
 #[derive(Debug)]
 pub struct MpdServer {
@@ -62,6 +128,12 @@ pub struct MpdServer {
     shutdown_rx: broadcast::Receiver<()>,
     max_connections: usize,
     connection_timeout: std::time::Duration,
+    tcp_keepalive: Option<std::time::Duration>,
+    max_line_length: usize,
+    max_command_list_size: usize,
+    write_timeout: std::time::Duration,
+    max_output_buffer_size: usize,
+    ready_tx: Option<tokio::sync::oneshot::Sender<std::net::SocketAddr>>,
 }
 
 impl MpdServer {
@@ -73,6 +145,12 @@ impl MpdServer {
             shutdown_rx,
             max_connections: DEFAULT_MAX_CONNECTIONS,
             connection_timeout: std::time::Duration::from_secs(DEFAULT_CONNECTION_TIMEOUT_SECS),
+            tcp_keepalive: Some(std::time::Duration::from_secs(DEFAULT_TCP_KEEPALIVE_SECS)),
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_command_list_size: DEFAULT_MAX_COMMAND_LIST_SIZE,
+            write_timeout: std::time::Duration::from_secs(DEFAULT_WRITE_TIMEOUT_SECS),
+            max_output_buffer_size: DEFAULT_MAX_OUTPUT_BUFFER_SIZE,
+            ready_tx: None,
         }
     }
 
@@ -88,6 +166,12 @@ impl MpdServer {
             shutdown_rx,
             max_connections: DEFAULT_MAX_CONNECTIONS,
             connection_timeout: std::time::Duration::from_secs(DEFAULT_CONNECTION_TIMEOUT_SECS),
+            tcp_keepalive: Some(std::time::Duration::from_secs(DEFAULT_TCP_KEEPALIVE_SECS)),
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_command_list_size: DEFAULT_MAX_COMMAND_LIST_SIZE,
+            write_timeout: std::time::Duration::from_secs(DEFAULT_WRITE_TIMEOUT_SECS),
+            max_output_buffer_size: DEFAULT_MAX_OUTPUT_BUFFER_SIZE,
+            ready_tx: None,
         }
     }
 
@@ -106,11 +190,61 @@ impl MpdServer {
     /// Set the idle timeout for a connection waiting on its next command
     /// line. A client that connects and then sends nothing is disconnected
     /// after this duration. Does not apply while a client is in `idle` mode.
+    /// A zero duration disables the timeout.
     pub fn with_connection_timeout(mut self, d: std::time::Duration) -> Self {
         self.connection_timeout = d;
         self
     }
 
+    /// Set how long a TCP connection may be quiet before the kernel starts
+    /// sending keepalive probes, or `None` to leave keepalive off. Unlike
+    /// the connection timeout this also catches peers that vanished while
+    /// in `idle`, which would otherwise hold their socket forever.
+    pub fn with_tcp_keepalive(mut self, d: Option<std::time::Duration>) -> Self {
+        self.tcp_keepalive = d;
+        self
+    }
+
+    /// Set the longest accepted command line in bytes (newline included).
+    /// A longer line is answered with an ACK and the client is disconnected,
+    /// so a single line can never grow the read buffer without bound.
+    pub fn with_max_line_length(mut self, n: usize) -> Self {
+        self.max_line_length = n;
+        self
+    }
+
+    /// Set the maximum total size in bytes of one command list. A client
+    /// exceeding it is answered with an ACK and disconnected.
+    pub fn with_max_command_list_size(mut self, n: usize) -> Self {
+        self.max_command_list_size = n;
+        self
+    }
+
+    /// Set how long a client may take to drain one response. A client that
+    /// stops reading is disconnected instead of pinning its task and buffer.
+    pub fn with_write_timeout(mut self, d: std::time::Duration) -> Self {
+        self.write_timeout = d;
+        self
+    }
+
+    /// Set the largest response in bytes buffered for one client. A
+    /// command producing more output disconnects the client, as MPD does.
+    pub fn with_max_output_buffer_size(mut self, n: usize) -> Self {
+        self.max_output_buffer_size = n;
+        self
+    }
+
+    /// Report the bound TCP address on `tx` once the server is accepting
+    /// connections. Lets callers bind port 0 and learn the actual port
+    /// without guessing or sleeping.
+    pub fn with_ready_signal(
+        mut self,
+        tx: tokio::sync::oneshot::Sender<std::net::SocketAddr>,
+    ) -> Self {
+        self.ready_tx = Some(tx);
+        self
+    }
+
     pub async fn run(self) -> Result<()> {
         let listener = TcpListener::bind(&self.bind_address).await?;
         info!("mpd server listening on {}", self.bind_address);
@@ -143,7 +277,17 @@ impl MpdServer {
         // connection immediately if none are available.
         let connection_limiter =
             std::sync::Arc::new(tokio::sync::Semaphore::new(self.max_connections));
-        let connection_timeout = self.connection_timeout;
+        let limits = ClientLimits {
+            timeout: self.connection_timeout,
+            max_line_length: self.max_line_length,
+            max_command_list_size: self.max_command_list_size,
+            write_timeout: self.write_timeout,
+            max_output_buffer_size: self.max_output_buffer_size,
+        };
+
+        if let Some(tx) = self.ready_tx.take() {
+            let _ = tx.send(listener.local_addr()?);
+        }
 
         loop {
             tokio::select! {
@@ -152,12 +296,17 @@ impl MpdServer {
                     match result {
                         Ok((stream, addr)) => {
                             debug!("new connection from {}", addr);
+                            if let Some(time) = self.tcp_keepalive
+                                && let Err(e) = set_keepalive(&stream, time)
+                            {
+                                debug!("failed to enable keepalive for {}: {}", addr, e);
+                            }
                             match connection_limiter.clone().try_acquire_owned() {
                                 Ok(permit) => {
                                     let state = self.state.clone();
                                     tokio::spawn(async move {
                                         let _permit = permit;
-                                        if let Err(e) = handle_client(stream, state, connection_timeout).await {
+                                        if let Err(e) = handle_client(stream, state, limits).await {
                                             log_client_error("client", &e);
                                         }
                                     });
@@ -190,7 +339,7 @@ impl MpdServer {
                                     let state = self.state.clone();
                                     tokio::spawn(async move {
                                         let _permit = permit;
-                                        if let Err(e) = handle_unix_client(stream, state, connection_timeout).await {
+                                        if let Err(e) = handle_unix_client(stream, state, limits).await {
                                             log_client_error("unix client", &e);
                                         }
                                     });
@@ -228,8 +377,9 @@ impl MpdServer {
 }
 
 /// Log an error from a client connection. A client closing its socket
-/// (connection reset / broken pipe / EOF) is routine for MPD clients, so those
-/// are logged at debug; anything else is a genuine error.
+/// (connection reset / broken pipe / EOF) or stalling until the write timeout
+/// is routine for MPD clients, so those are logged at debug; anything else is
+/// a genuine error.
 fn log_client_error(kind: &str, e: &rmpd_core::error::RmpdError) {
     use std::io::ErrorKind;
     let benign = matches!(
@@ -241,6 +391,7 @@ fn log_client_error(kind: &str, e: &rmpd_core::error::RmpdError) {
                     | ErrorKind::ConnectionAborted
                     | ErrorKind::BrokenPipe
                     | ErrorKind::UnexpectedEof
+                    | ErrorKind::TimedOut
             )
     );
     if benign {
@@ -250,11 +401,17 @@ fn log_client_error(kind: &str, e: &rmpd_core::error::RmpdError) {
     }
 }
 
-async fn handle_client(
-    mut stream: TcpStream,
-    state: AppState,
-    timeout: std::time::Duration,
-) -> Result<()> {
+/// Have the kernel probe `stream` after `time` of silence and reset it once
+/// the peer stops answering.
+fn set_keepalive(stream: &TcpStream, time: std::time::Duration) -> std::io::Result<()> {
+    let keepalive = socket2::TcpKeepalive::new()
+        .with_time(time)
+        .with_interval((time / TCP_KEEPALIVE_RETRIES).max(std::time::Duration::from_secs(1)))
+        .with_retries(TCP_KEEPALIVE_RETRIES);
+    socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)
+}
+
+async fn handle_client(mut stream: TcpStream, state: AppState, limits: ClientLimits) -> Result<()> {
     // Enable TCP_NODELAY for low-latency responses (disable Nagle's algorithm)
     stream.set_nodelay(true)?;
 
@@ -263,59 +420,118 @@ async fn handle_client(
         .write_all(format!("OK MPD {PROTOCOL_VERSION}\n").as_bytes())
         .await?;
 
+    let address = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown".to_owned(), |addr| addr.to_string());
     let (reader, writer) = stream.into_split();
-    handle_client_inner(tokio::io::BufReader::new(reader), writer, state, timeout).await
+    let client = state.clients.register(address);
+    let span = client_span(&client);
+    let permissions = state.initial_permissions();
+    handle_client_inner(
+        tokio::io::BufReader::new(reader),
+        writer,
+        state,
+        limits,
+        client,
+        permissions,
+    )
+    .instrument(span)
+    .await
+}
+
+/// Span around everything logged for one client; the `client` command
+/// fills in its name.
+fn client_span(client: &ClientGuard) -> tracing::Span {
+    tracing::info_span!("client", id = client.id(), name = tracing::field::Empty)
 }
 
 async fn handle_unix_client(
     mut stream: UnixStream,
     state: AppState,
-    timeout: std::time::Duration,
+    limits: ClientLimits,
 ) -> Result<()> {
     // Send greeting
     stream
         .write_all(format!("OK MPD {PROTOCOL_VERSION}\n").as_bytes())
         .await?;
 
+    // Attribute the connection to the local user on the other end.
+    let address = match stream.peer_cred() {
+        Ok(cred) => {
+            debug!(
+                "new local connection from uid {} (pid {:?})",
+                cred.uid(),
+                cred.pid()
+            );
+            format!("unix:{}", cred.uid())
+        }
+        Err(e) => {
+            debug!("failed to read local peer credentials: {}", e);
+            "unix".to_owned()
+        }
+    };
     let (reader, writer) = stream.into_split();
-    handle_client_inner(tokio::io::BufReader::new(reader), writer, state, timeout).await
+    let client = state.clients.register(address);
+    let span = client_span(&client);
+    let permissions = state.initial_local_permissions();
+    handle_client_inner(
+        tokio::io::BufReader::new(reader),
+        writer,
+        state,
+        limits,
+        client,
+        permissions,
+    )
+    .instrument(span)
+    .await
 }
 
 async fn handle_client_inner(
     mut reader: tokio::io::BufReader<impl tokio::io::AsyncRead + Unpin>,
     mut writer: impl tokio::io::AsyncWrite + Unpin,
     state: AppState,
-    timeout: std::time::Duration,
+    limits: ClientLimits,
+    client: ClientGuard,
+    permissions: u8,
 ) -> Result<()> {
-    let mut line = String::new();
+    let mut line = Vec::new();
 
     // Subscribe to event bus for idle notifications
     let mut event_rx = state.event_bus.subscribe();
 
     // Per-client connection state
     let mut conn_state = crate::ConnectionState::new();
-    // Grant full permissions immediately when no password is configured;
-    // otherwise the client starts with zero permissions and must `password` in.
-    if state.password.is_none() {
-        conn_state.grant_all_permissions();
-    } else {
-        conn_state.permissions = 0;
-    }
+    let mut partition = conn_state.current_partition.clone();
+    let mut _partition_client = enter_partition(&state, &partition).await;
+    conn_state.client_id = client.id();
+    conn_state.max_output_buffer_size = limits.max_output_buffer_size;
+    // Without a password or default_permissions every client may do
+    // everything; otherwise it starts limited and must `password` in.
+    conn_state.permissions = permissions;
 
     // Command batching state
     let mut batch_mode = false;
     let mut batch_ok_mode = false;
     let mut batch_commands: Vec<String> = Vec::new();
+    let mut batch_size = 0usize;
 
     loop {
         line.clear();
-        let bytes_read = match tokio::time::timeout(timeout, reader.read_line(&mut line)).await {
-            Ok(result) => result?,
-            Err(_elapsed) => {
-                // Idle timeout: client connected but sent nothing for
-                // `timeout`. Disconnect as if it had closed the socket.
-                debug!("connection idle for {:?}, closing", timeout);
-                break;
+        // Never buffer more than one line's worth of input: a client that
+        // streams bytes without a newline is cut off at the limit.
+        let mut limited = (&mut reader).take(limits.max_line_length as u64);
+        let read = limited.read_until(b'\n', &mut line);
+        let bytes_read = if limits.timeout.is_zero() {
+            read.await?
+        } else {
+            match tokio::time::timeout(limits.timeout, read).await {
+                Ok(result) => result?,
+                Err(_elapsed) => {
+                    // Idle timeout: client connected but sent nothing for
+                    // `timeout`. Disconnect as if it had closed the socket.
+                    debug!("connection idle for {:?}, closing", limits.timeout);
+                    break;
+                }
             }
         };
 
@@ -324,11 +540,47 @@ async fn handle_client_inner(
             break;
         }
 
-        let trimmed = line.trim();
+        if bytes_read >= limits.max_line_length && !line.ends_with(b"\n") {
+            let index = if batch_mode { batch_commands.len() } else { 0 };
+            debug!(
+                "command line exceeds {} bytes, closing",
+                limits.max_line_length
+            );
+            let ack = ResponseBuilder::error(ACK_ERROR_ARG, index as i32, "", "line too long");
+            write_response(&mut writer, ack.as_bytes(), &limits).await?;
+            break;
+        }
+
+        // Undecodable input gets an ACK instead of tearing down the
+        // connection; an open command list is abandoned.
+        let Ok(decoded) = std::str::from_utf8(&line) else {
+            let index = if batch_mode { batch_commands.len() } else { 0 };
+            debug!(
+                "received malformed UTF-8: {:?}",
+                String::from_utf8_lossy(&line)
+            );
+            batch_mode = false;
+            batch_ok_mode = false;
+            batch_commands.clear();
+            batch_size = 0;
+            let ack = malformed_line_ack(&line, index as i32);
+            write_response(&mut writer, ack.as_bytes(), &limits).await?;
+            continue;
+        };
+
+        let trimmed = decoded.trim();
         if trimmed.is_empty() {
             continue;
         }
 
+        // Health checks probing the port over HTTP get a short refusal and
+        // the connection is closed, instead of an ACK per request header.
+        if is_http_request(trimmed) {
+            debug!("HTTP request on the MPD port, closing: {}", trimmed);
+            write_response(&mut writer, HTTP_REFUSAL.as_bytes(), &limits).await?;
+            break;
+        }
+
         debug!("received command: {}", trimmed);
 
         let response = match parse_command(trimmed) {
@@ -336,12 +588,14 @@ async fn handle_client_inner(
                 batch_mode = true;
                 batch_ok_mode = false;
                 batch_commands.clear();
+                batch_size = 0;
                 continue; // Don't send response yet
             }
             Ok(Command::CommandListOkBegin) => {
                 batch_mode = true;
                 batch_ok_mode = true;
                 batch_commands.clear();
+                batch_size = 0;
                 continue; // Don't send response yet
             }
             Ok(Command::CommandListEnd) => {
@@ -363,14 +617,44 @@ async fn handle_client_inner(
                     batch_mode = false;
                     batch_ok_mode = false;
                     batch_commands.clear();
+                    batch_size = 0;
                     response
                 }
             }
-            Ok(Command::Idle { subsystems }) if !batch_mode => {
-                Response::Text(handle_idle(&mut reader, &mut event_rx, subsystems).await)
+            // Without read permission `idle` falls through to `handle_command`,
+            // which answers with the usual permission ACK.
+            Ok(Command::Idle { subsystems })
+                if !batch_mode && conn_state.has_permission(PERMISSION_READ) =>
+            {
+                Response::Text(
+                    handle_idle(
+                        &mut reader,
+                        &mut event_rx,
+                        subsystems,
+                        conn_state.client_id,
+                        limits.max_line_length,
+                    )
+                    .await,
+                )
             }
             Ok(_cmd) if batch_mode => {
-                // Accumulate commands in batch
+                // Accumulate commands in batch, bounded like MPD's
+                // `max_command_list_size`.
+                batch_size += trimmed.len();
+                if batch_size > limits.max_command_list_size {
+                    debug!(
+                        "command list exceeds {} bytes, closing",
+                        limits.max_command_list_size
+                    );
+                    let ack = ResponseBuilder::error(
+                        ACK_ERROR_ARG,
+                        batch_commands.len() as i32,
+                        "",
+                        "command list too long",
+                    );
+                    write_response(&mut writer, ack.as_bytes(), &limits).await?;
+                    break;
+                }
                 batch_commands.push(trimmed.to_string());
                 continue; // Don't send response yet
             }
@@ -392,17 +676,44 @@ async fn handle_client_inner(
             Err(e) => Response::Text(parse_error_to_ack(trimmed, &e, 0)),
         };
 
-        writer.write_all(response.as_bytes()).await?;
-        writer.flush().await?; // Flush immediately to ensure low latency
+        // Idle then waits on the events of the partition switched to.
+        if conn_state.current_partition != partition {
+            partition = conn_state.current_partition.clone();
+            _partition_client = enter_partition(&state, &partition).await;
+            if let Some(partition_state) = state.for_partition(&partition).await {
+                event_rx = partition_state.event_bus.subscribe();
+            }
+        }
+
+        // Flushed immediately to ensure low latency
+        write_response(&mut writer, response.as_bytes(), &limits).await?;
+        state.stats.record_bytes_sent(response.as_bytes().len());
     }
 
-    // Cleanup: unregister any channel subscriptions when connection closes
-    for channel in conn_state.subscribed_channels() {
-        state.message_broker.unregister_subscriber(channel).await;
+    // Cleanup: drop channel subscriptions and unread messages
+    if state
+        .message_broker
+        .remove_client(conn_state.client_id)
+        .await
+    {
+        state
+            .event_bus
+            .emit(rmpd_core::event::Event::SubscriptionChanged);
+    }
+    // A named client finds its settings again when it reconnects.
+    if let Some(name) = &conn_state.client_name {
+        state.clients.save(name, conn_state.settings());
     }
     Ok(())
 }
 
+/// Count a client into partition `name`, which cannot be deleted until the
+/// returned guard is dropped.
+async fn enter_partition(state: &AppState, name: &str) -> Option<PartitionClient> {
+    let manager = state.partition_manager.as_ref()?;
+    Some(manager.get_partition(name).await?.enter())
+}
+
 async fn execute_command_list(
     commands: &[String],
     state: &AppState,
@@ -489,10 +800,45 @@ async fn execute_command_list(
     Response::Text(response)
 }
 
+/// Write and flush one response, enforcing the output limits: a response
+/// larger than `max_output_buffer_size`, or one the client does not drain
+/// within `write_timeout`, ends the connection with an error.
+async fn write_response(
+    writer: &mut (impl tokio::io::AsyncWrite + Unpin),
+    data: &[u8],
+    limits: &ClientLimits,
+) -> Result<()> {
+    if data.len() > limits.max_output_buffer_size {
+        return Err(std::io::Error::other(format!(
+            "output buffer is full ({} bytes, limit {})",
+            data.len(),
+            limits.max_output_buffer_size
+        ))
+        .into());
+    }
+    let write = async {
+        writer.write_all(data).await?;
+        writer.flush().await
+    };
+    match tokio::time::timeout(limits.write_timeout, write).await {
+        Ok(result) => Ok(result?),
+        Err(_elapsed) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!(
+                "client stalled for {:?} while reading a response",
+                limits.write_timeout
+            ),
+        )
+        .into()),
+    }
+}
+
 async fn handle_idle(
     reader: &mut tokio::io::BufReader<impl tokio::io::AsyncRead + Unpin>,
     event_rx: &mut broadcast::Receiver<rmpd_core::event::Event>,
     subsystems: Vec<String>,
+    client_id: u64,
+    max_line_length: usize,
 ) -> String {
     use rmpd_core::event::Subsystem;
     use tokio::sync::broadcast::error::RecvError;
@@ -525,6 +871,8 @@ async fn handle_idle(
     };
 
     let mut line = String::new();
+    // Any line ends idle, so one length budget covers the whole wait.
+    let mut limited = (&mut *reader).take(max_line_length as u64);
 
     loop {
         tokio::select! {
@@ -533,6 +881,12 @@ async fn handle_idle(
                 match event_result {
                     Ok(event) => {
                         debug!("idle received event: {:?}", event);
+                        // A message only wakes the clients it was queued for.
+                        if let rmpd_core::event::Event::MessageQueued { recipients } = &event
+                            && !recipients.contains(&client_id)
+                        {
+                            continue;
+                        }
                         let event_subsystems = event.subsystems();
 
                         // Check if event matches any subscribed subsystem
@@ -567,7 +921,7 @@ async fn handle_idle(
                 }
             }
             // Wait for noidle command
-            line_result = reader.read_line(&mut line) => {
+            line_result = limited.read_line(&mut line) => {
                 if let Ok(bytes) = line_result
                     && bytes > 0 && line.trim() == "noidle" {
                         // Cancel idle
@@ -605,6 +959,8 @@ async fn handle_command(
     state: &AppState,
     conn_state: &mut crate::ConnectionState,
 ) -> Response {
+    state.stats.record_command();
+
     // Enforce permissions. PERMISSION_NONE commands always pass.
     let required = cmd.command_required_permission();
     if !conn_state.has_permission(required) {
@@ -617,13 +973,37 @@ async fn handle_command(
         ));
     }
 
+    // Queue and player commands act on the client's partition.
+    let partition_state;
+    let state = if conn_state.current_partition == state.partition {
+        state
+    } else {
+        let Some(found) = state.for_partition(&conn_state.current_partition).await else {
+            return Response::Text(ResponseBuilder::error(
+                ACK_ERROR_NO_EXIST,
+                0,
+                cmd.command_name(),
+                "No such partition",
+            ));
+        };
+        partition_state = found;
+        &partition_state
+    };
+
     // Special handling for binary commands
     match cmd {
         Command::AlbumArt { uri, offset } => {
-            return database::handle_albumart_command(state, &uri, offset).await;
+            return database::handle_albumart_command(state, &uri, offset, conn_state.binary_limit)
+                .await;
         }
         Command::ReadPicture { uri, offset } => {
-            return database::handle_readpicture_command(state, &uri, offset).await;
+            return database::handle_readpicture_command(
+                state,
+                &uri,
+                offset,
+                conn_state.binary_limit,
+            )
+            .await;
         }
         _ => {}
     }
@@ -658,20 +1038,22 @@ async fn handle_command(
             };
 
             let mut resp = ResponseBuilder::new();
-            resp.status(&status);
+            resp.status(&status, &state.partition);
             resp.ok()
         }
         Command::Stats => {
-            // Get stats from database if available
+            // Get stats from database if available; the aggregate queries
+            // scan the whole library, so keep them off the async workers.
+            let pool = state.db_pool.clone();
             let (songs, artists, albums, db_playtime, db_update) =
-                if let Some(ref pool) = state.db_pool {
-                    match rmpd_library::Database::from_pool(pool) {
-                        Ok(db) => db.get_stats().unwrap_or((0, 0, 0, 0, 0)),
-                        Err(_) => (0, 0, 0, 0, 0),
-                    }
-                } else {
-                    (0, 0, 0, 0, 0)
-                };
+                tokio::task::spawn_blocking(move || {
+                    let db = pool.as_ref()?.store().ok()?;
+                    db.get_stats().ok()
+                })
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or((0, 0, 0, 0, 0));
 
             // Calculate uptime in seconds
             let uptime = state.start_time.elapsed().as_secs();
@@ -690,6 +1072,11 @@ async fn handle_command(
             resp.stats(&stats);
             resp.ok()
         }
+        Command::DaemonStats => {
+            let mut resp = ResponseBuilder::new();
+            crate::stats::write_response(&mut resp, &state.stats.snapshot(state));
+            resp.ok()
+        }
         Command::ClearError => {
             // Clear the error field in status
             state.status.write().await.error = None;
@@ -698,28 +1085,50 @@ async fn handle_command(
         Command::Update { path } | Command::Rescan { path } => {
             database::handle_update_command(state, path.as_deref()).await
         }
+        Command::UpdatePreview => database::handle_updatepreview_command(state).await,
+        Command::LibraryReport { uri } => {
+            database::handle_libraryreport_command(state, uri.as_deref()).await
+        }
         Command::Find {
             filters,
             sort,
             window,
-        } => database::handle_find_command(state, &filters, sort.as_deref(), window).await,
+        } => {
+            database::handle_find_command(
+                state,
+                &filters,
+                sort.as_deref(),
+                window,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
+        }
         Command::Search {
             filters,
             sort,
             window,
-        } => database::handle_search_command(state, &filters, sort.as_deref(), window).await,
+        } => {
+            database::handle_search_command(
+                state,
+                &filters,
+                sort.as_deref(),
+                window,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
+        }
         Command::List {
             tag,
             filter_tag,
             filter_value,
-            group,
+            groups,
         } => {
             database::handle_list_command(
                 state,
                 &tag,
                 filter_tag.as_deref(),
                 filter_value.as_deref(),
-                group.as_deref(),
+                &groups,
             )
             .await
         }
@@ -728,26 +1137,55 @@ async fn handle_command(
         }
         Command::ListAll { path } => database::handle_listall_command(state, path.as_deref()).await,
         Command::ListAllInfo { path } => {
-            database::handle_listallinfo_command(state, path.as_deref()).await
+            database::handle_listallinfo_command(
+                state,
+                path.as_deref(),
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
+        }
+        Command::LsInfo { path } => {
+            database::handle_lsinfo_command(
+                state,
+                path.as_deref(),
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
+        }
+        Command::CurrentSong => {
+            database::handle_currentsong_command(state, conn_state.enabled_tags.as_ref()).await
+        }
+        Command::PlaylistInfo { range } => {
+            queue::handle_playlistinfo_command(state, range, conn_state.enabled_tags.as_ref()).await
         }
-        Command::LsInfo { path } => database::handle_lsinfo_command(state, path.as_deref()).await,
-        Command::CurrentSong => database::handle_currentsong_command(state).await,
-        Command::PlaylistInfo { range } => queue::handle_playlistinfo_command(state, range).await,
         Command::Playlist => {
             // Deprecated, same as playlistinfo without range
-            queue::handle_playlistinfo_command(state, None).await
+            queue::handle_playlistinfo_command(state, None, conn_state.enabled_tags.as_ref()).await
         }
         Command::PlChanges { version, range } => {
-            queue::handle_plchanges_command(state, version, range).await
+            queue::handle_plchanges_command(state, version, range, conn_state.enabled_tags.as_ref())
+                .await
         }
         Command::PlChangesPosId { version, range } => {
             queue::handle_plchangesposid_command(state, version, range).await
         }
         Command::PlaylistFind { tag, value } => {
-            queue::handle_playlistfind_command(state, &tag, &value).await
+            queue::handle_playlistfind_command(
+                state,
+                &tag,
+                &value,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
         }
         Command::PlaylistSearch { tag, value } => {
-            queue::handle_playlistsearch_command(state, &tag, &value).await
+            queue::handle_playlistsearch_command(
+                state,
+                &tag,
+                &value,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
         }
         // Playback commands
         Command::Play { position } => playback::handle_play_command(state, position).await,
@@ -778,10 +1216,14 @@ async fn handle_command(
         Command::SwapId { id1, id2 } => queue::handle_swapid_command(state, id1, id2).await,
         Command::Move { from, to } => queue::handle_move_command(state, from, to).await,
         Command::Shuffle { range } => queue::handle_shuffle_command(state, range).await,
-        Command::PlaylistId { id } => queue::handle_playlistid_command(state, id).await,
+        Command::PlaylistId { id } => {
+            queue::handle_playlistid_command(state, id, conn_state.enabled_tags.as_ref()).await
+        }
         Command::Password { password } => {
             connection::handle_password_command(state, conn_state, &password).await
         }
+        Command::Client { name } => connection::handle_client_command(state, conn_state, &name),
+        Command::ListClients => connection::handle_listclients_command(state),
         Command::AlbumArt { .. } | Command::ReadPicture { .. } => {
             // Already handled at the beginning of the function
             unreachable!()
@@ -817,12 +1259,7 @@ async fn handle_command(
             options::handle_replaygain_mode_command(state, &mode).await
         }
         Command::ReplayGainStatus => options::handle_replaygain_status_command(state).await,
-        Command::BinaryLimit { size } => {
-            // Set binary limit (for large responses like images)
-            // Store in connection state if needed, for now just acknowledge
-            let _ = size;
-            ResponseBuilder::new().ok()
-        }
+        Command::BinaryLimit { size } => connection::handle_binarylimit_command(conn_state, size),
         Command::Protocol { subcommand } => {
             reflection::handle_protocol_command(conn_state, subcommand).await
         }
@@ -838,7 +1275,13 @@ async fn handle_command(
             playlists::handle_listplaylist_command(state, &name, range).await
         }
         Command::ListPlaylistInfo { name, range } => {
-            playlists::handle_listplaylistinfo_command(state, &name, range).await
+            playlists::handle_listplaylistinfo_command(
+                state,
+                &name,
+                range,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
         }
         Command::PlaylistAdd {
             name,
@@ -857,11 +1300,29 @@ async fn handle_command(
         Command::Rm { name } => playlists::handle_rm_command(state, &name).await,
         Command::Rename { from, to } => playlists::handle_rename_command(state, &from, &to).await,
         Command::SearchPlaylist { name, tag, value } => {
-            playlists::handle_searchplaylist_command(state, &name, &tag, &value).await
+            playlists::handle_searchplaylist_command(
+                state,
+                &name,
+                &tag,
+                &value,
+                conn_state.enabled_tags.as_ref(),
+            )
+            .await
         }
         Command::PlaylistLength { name } => {
             playlists::handle_playlistlength_command(state, &name).await
         }
+        // Queue snapshots
+        Command::SaveBookmark { name } => {
+            bookmarks::handle_savebookmark_command(state, &name).await
+        }
+        Command::LoadBookmark { name } => {
+            bookmarks::handle_loadbookmark_command(state, &name).await
+        }
+        Command::ListBookmarks => bookmarks::handle_listbookmarks_command(state).await,
+        Command::DeleteBookmark { name } => {
+            bookmarks::handle_deletebookmark_command(state, &name).await
+        }
         // Output control
         Command::Outputs => outputs::handle_outputs_command(state).await,
         Command::EnableOutput { id } => outputs::handle_enableoutput_command(state, id).await,
@@ -890,6 +1351,13 @@ async fn handle_command(
             fingerprint::handle_getfingerprint_command(state, &uri).await
         }
         Command::ReadComments { uri } => database::handle_readcomments_command(state, &uri).await,
+        Command::ReadLyrics { uri } => database::handle_readlyrics_command(state, &uri).await,
+        Command::WriteTag { uri, tag, value } => {
+            database::handle_writetag_command(state, "writetag", &uri, &tag, Some(&value)).await
+        }
+        Command::ClearTag { uri, tag } => {
+            database::handle_writetag_command(state, "cleartag", &uri, &tag, None).await
+        }
         // Stickers
         Command::StickerGet { uri, name } => {
             stickers::handle_sticker_get_command(state, &uri, &name).await
@@ -910,12 +1378,10 @@ async fn handle_command(
         Command::StickerDec { uri, name, delta } => {
             stickers::handle_sticker_dec_command(state, &uri, &name, delta).await
         }
-        Command::StickerNames { uri } => {
-            stickers::handle_sticker_names_command(state, uri.as_deref()).await
-        }
+        Command::StickerNames => stickers::handle_sticker_names_command(state).await,
         Command::StickerTypes => stickers::handle_sticker_types_command().await,
-        Command::StickerNamesTypes { uri } => {
-            stickers::handle_sticker_namestypes_command(state, uri.as_deref()).await
+        Command::StickerNamesTypes { sticker_type } => {
+            stickers::handle_sticker_namestypes_command(state, sticker_type.as_deref()).await
         }
         // Partitions
         Command::Partition { name } => {
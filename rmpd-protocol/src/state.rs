@@ -1,11 +1,18 @@
+use crate::clients::ClientRegistry;
+use crate::connection::{PERMISSION_ALL, PERMISSION_NONE, parse_password};
 use crate::discovery::DiscoveryService;
+use crate::library_cache::{BROWSE_CACHE_SIZE, LibraryCache, SEARCH_CACHE_SIZE};
+use crate::queue_playback::QueuePlaybackManager;
+use crate::stats::{DaemonStats, ScanRecord};
+use rmpd_core::config::{ApiToken, Config, MusicRoot};
 use rmpd_core::event::EventBus;
 use rmpd_core::messaging::MessageBroker;
-use rmpd_core::partition::PartitionManager;
+use rmpd_core::partition::{PartitionManager, PartitionState};
 use rmpd_core::queue::Queue;
 use rmpd_core::state::PlayerStatus;
 use rmpd_core::storage::MountRegistry;
 use rmpd_player::PlaybackEngine;
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use std::time::Instant;
@@ -23,18 +30,41 @@ pub struct OutputInfo {
     pub attributes: std::collections::HashMap<String, String>,
 }
 
-/// Shared application state
+/// The player of one partition: what [`AppState::for_partition`] swaps in
+/// besides the partition's queue, status and event bus.
+struct PartitionPlayer {
+    engine: Arc<RwLock<PlaybackEngine>>,
+    atomic_state: Arc<std::sync::atomic::AtomicU8>,
+    stream_title: Arc<RwLock<Option<String>>>,
+    /// Advances through the partition's queue; `None` for the default
+    /// partition, whose manager the server owns.
+    _playback: Option<QueuePlaybackManager>,
+}
+
+/// Shared application state. The queue, status, player and event bus are
+/// those of one partition, the default one unless obtained through
+/// [`AppState::for_partition`]; everything else is server-wide.
 #[derive(Clone)]
 pub struct AppState {
+    /// Partition the queue, status, player and event bus belong to.
+    pub partition: String,
     pub queue: Arc<RwLock<Queue>>,
     pub status: Arc<RwLock<PlayerStatus>>,
     pub engine: Arc<RwLock<PlaybackEngine>>,
     pub atomic_state: Arc<std::sync::atomic::AtomicU8>, // Lock-free state access
     pub event_bus: EventBus,
     pub db_path: Option<String>,
-    pub db_pool: Option<Arc<rmpd_library::DbPool>>,
-    pub music_dir: Option<String>,
+    pub db_pool: Option<Arc<dyn rmpd_library::LibraryBackend>>,
+    /// Main music directory, `None` when unconfigured. Shared by every
+    /// clone so a config reload reaches all connections; see
+    /// [`AppState::music_dir`].
+    music_dir: Arc<std::sync::RwLock<Option<String>>>,
     pub playlist_dir: Option<String>,
+    /// Configuration the server was started with, reported by `config`.
+    /// `None` when the state was not built from a config file. A config
+    /// reload leaves it alone: only the music directory is applied without a
+    /// restart, and that is reported from [`AppState::music_dir`].
+    pub config: Option<Arc<Config>>,
     pub outputs: Arc<RwLock<Vec<OutputInfo>>>,
     pub start_time: Instant,
     pub message_broker: MessageBroker,
@@ -44,6 +74,19 @@ pub struct AppState {
     pub shutdown_tx: Option<broadcast::Sender<()>>,
     pub disable_actual_mount: bool,
     pub password: Option<String>,
+    /// What a correct `password` grants; see [`crate::connection::parse_password`].
+    pub password_permissions: u8,
+    /// Permissions of clients that have not sent a password, from
+    /// `network.default_permissions`. `None` keeps MPD's default: everything
+    /// without a password, nothing with one.
+    pub default_permissions: Option<u8>,
+    /// Permissions of Unix socket clients that have not sent a password,
+    /// from `network.local_permissions`. `None` treats them like any other
+    /// client.
+    pub local_permissions: Option<u8>,
+    /// Tokens the HTTP endpoints accept. Mirrors `[[api_token]]` from the
+    /// config file; empty leaves the endpoints to their own access rules.
+    pub api_tokens: Arc<Vec<ApiToken>>,
     /// Music-source registry built from `[[source]]` config blocks.
     pub sources: std::sync::Arc<rmpd_source::SourceRegistry>,
     /// Latest ICY "now playing" title for a remote stream (None when not
@@ -52,14 +95,33 @@ pub struct AppState {
     /// Whether to follow symlinks when scanning the music directory.
     /// Mirrors `general.follow_symlinks` from the config file.
     pub follow_symlinks: bool,
+    /// Extra music directories mounted into the virtual tree under their
+    /// names. Mirrors `[[general.music_root]]` from the config file.
+    pub music_roots: Arc<Vec<MusicRoot>>,
+    /// Compiled `[[tag_rule]]` rewrites handed to every library scan.
+    pub tag_rewriter: rmpd_library::TagRewriter,
+    /// Whether `writetag`/`cleartag` may modify files. Mirrors
+    /// `database.tag_editing` from the config file.
+    pub tag_editing: bool,
+    /// Connected clients and the settings of named ones.
+    pub clients: ClientRegistry,
+    /// Directory listings served by `lsinfo`, dropped on database changes.
+    pub browse_cache: LibraryCache<Arc<rmpd_library::DirectoryListing>>,
+    /// Recent `find`/`search` responses, dropped on database changes.
+    pub search_cache: LibraryCache<Arc<str>>,
+    /// Counters reported by `daemonstats` and the metrics endpoint.
+    pub stats: Arc<DaemonStats>,
+    /// Player of every partition, by name.
+    partition_players: Arc<RwLock<HashMap<String, PartitionPlayer>>>,
 }
 
 impl fmt::Debug for AppState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("AppState")
+            .field("partition", &self.partition)
             .field("event_bus", &self.event_bus)
             .field("db_path", &self.db_path)
-            .field("music_dir", &self.music_dir)
+            .field("music_dir", &self.music_dir())
             .field("start_time", &self.start_time)
             .finish_non_exhaustive()
     }
@@ -76,7 +138,12 @@ impl AppState {
         let atomic_state = Arc::new(std::sync::atomic::AtomicU8::new(
             rmpd_core::state::PlayerState::Stop as u8,
         ));
-        let engine = PlaybackEngine::new(event_bus.clone(), status.clone(), atomic_state.clone());
+        let engine = Arc::new(RwLock::new(PlaybackEngine::new(
+            event_bus.clone(),
+            status.clone(),
+            atomic_state.clone(),
+        )));
+        let stream_title = Arc::new(RwLock::new(None));
 
         let default_output = OutputInfo {
             id: 0,
@@ -97,33 +164,50 @@ impl AppState {
         // Initialize mount registry
         let mount_registry = MountRegistry::new();
 
-        // Initialize partition manager with default partition
-        let partition_manager = PartitionManager::new();
-        // Note: Creating the default partition is async, so we'll handle it during actual usage
-        // For now, partition_manager exists but has no partitions until first command
+        // The default partition is the server's main queue, status, player
+        // and event bus; other partitions get their own.
+        let queue = Arc::new(RwLock::new(Queue::new()));
+        let partition_manager = PartitionManager::with_default(PartitionState::with_queue(
+            "default".to_string(),
+            queue.clone(),
+            status.clone(),
+            event_bus.clone(),
+        ));
+        let default_player = PartitionPlayer {
+            engine: engine.clone(),
+            atomic_state: atomic_state.clone(),
+            stream_title: stream_title.clone(),
+            _playback: None,
+        };
+
         // Create a pooled database connection up front (schema is initialised
         // once here). Reused across commands so a chatty client doesn't pay the
         // cost of opening a fresh SQLite connection per request.
         let db_pool = db_path
             .as_ref()
             .and_then(|path| match rmpd_library::DbPool::new(path) {
-                Ok(pool) => Some(pool),
+                Ok(pool) => Some(pool as Arc<dyn rmpd_library::LibraryBackend>),
                 Err(e) => {
                     tracing::warn!("failed to create database connection pool: {e}");
                     None
                 }
             });
 
+        let browse_cache = LibraryCache::new(&event_bus, BROWSE_CACHE_SIZE);
+        let search_cache = LibraryCache::new(&event_bus, SEARCH_CACHE_SIZE);
+
         Self {
-            queue: Arc::new(RwLock::new(Queue::new())),
+            partition: "default".to_string(),
+            queue,
             status,
-            engine: Arc::new(RwLock::new(engine)),
+            engine,
             atomic_state,
             event_bus,
             db_path,
             db_pool,
-            music_dir,
+            music_dir: Arc::new(std::sync::RwLock::new(music_dir)),
             playlist_dir,
+            config: None,
             outputs: Arc::new(RwLock::new(vec![default_output])),
             start_time: Instant::now(),
             message_broker: MessageBroker::new(),
@@ -135,9 +219,24 @@ impl AppState {
                 .map(|v| v == "1" || v.to_lowercase() == "true")
                 .unwrap_or(false),
             password: None,
-            stream_title: Arc::new(RwLock::new(None)),
+            password_permissions: PERMISSION_ALL,
+            default_permissions: None,
+            local_permissions: None,
+            api_tokens: Arc::new(Vec::new()),
+            stream_title,
             sources: std::sync::Arc::new(rmpd_source::SourceRegistry::from_config(&[])),
             follow_symlinks: false,
+            music_roots: Arc::new(Vec::new()),
+            tag_rewriter: rmpd_library::TagRewriter::default(),
+            tag_editing: false,
+            clients: ClientRegistry::new(),
+            browse_cache,
+            search_cache,
+            stats: Arc::new(DaemonStats::new()),
+            partition_players: Arc::new(RwLock::new(HashMap::from([(
+                "default".to_string(),
+                default_player,
+            )]))),
         }
     }
 
@@ -153,13 +252,58 @@ impl AppState {
         Self::build(Some(db_path), Some(music_dir), Some(playlist_dir))
     }
 
+    /// Like [`AppState::with_all_paths`], for a server that may start without
+    /// a music directory (see [`AppState::set_music_dir`]).
+    pub fn with_library(db_path: String, music_dir: Option<String>, playlist_dir: String) -> Self {
+        Self::build(Some(db_path), music_dir, Some(playlist_dir))
+    }
+
+    /// Keep the loaded configuration for `config` to report.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = Some(Arc::new(config));
+    }
+
     /// Set the shutdown sender for graceful shutdown support
     pub fn set_shutdown_sender(&mut self, tx: broadcast::Sender<()>) {
         self.shutdown_tx = Some(tx);
     }
 
+    /// Set the password clients send to gain permissions; an MPD-style
+    /// `secret@read,add` value limits what it grants.
     pub fn set_password(&mut self, password: Option<String>) {
-        self.password = password;
+        self.password_permissions = PERMISSION_ALL;
+        self.password = password.map(|spec| {
+            let (secret, permissions) = parse_password(&spec);
+            self.password_permissions = permissions;
+            secret.to_owned()
+        });
+    }
+
+    /// Limit what clients may do before sending a password, e.g. to
+    /// `PERMISSION_READ` for a listening-only endpoint.
+    pub fn set_default_permissions(&mut self, permissions: Option<u8>) {
+        self.default_permissions = permissions;
+    }
+
+    /// Grant clients on the Unix socket different permissions before they
+    /// send a password, e.g. `PERMISSION_ALL` so local users need none.
+    pub fn set_local_permissions(&mut self, permissions: Option<u8>) {
+        self.local_permissions = permissions;
+    }
+
+    /// Permissions a new connection starts with.
+    pub fn initial_permissions(&self) -> u8 {
+        match (self.default_permissions, &self.password) {
+            (Some(permissions), _) => permissions,
+            (None, None) => PERMISSION_ALL,
+            (None, Some(_)) => PERMISSION_NONE,
+        }
+    }
+
+    /// Permissions a new Unix socket connection starts with.
+    pub fn initial_local_permissions(&self) -> u8 {
+        self.local_permissions
+            .unwrap_or_else(|| self.initial_permissions())
     }
 
     /// Set the music-source registry. Call at startup after building the
@@ -172,6 +316,125 @@ impl AppState {
         self.follow_symlinks = v;
     }
 
+    pub fn set_music_roots(&mut self, roots: Vec<MusicRoot>) {
+        self.music_roots = Arc::new(roots);
+    }
+
+    /// Require one of `tokens` on the HTTP endpoints.
+    pub fn set_api_tokens(&mut self, tokens: Vec<ApiToken>) {
+        self.api_tokens = Arc::new(tokens);
+    }
+
+    pub fn set_tag_rewriter(&mut self, rewriter: rmpd_library::TagRewriter) {
+        self.tag_rewriter = rewriter;
+    }
+
+    pub fn set_tag_editing(&mut self, v: bool) {
+        self.tag_editing = v;
+    }
+
+    /// The main music directory, or `None` when no (existing) directory is
+    /// configured: the library is then limited to what the database holds.
+    pub fn music_dir(&self) -> Option<String> {
+        self.music_dir
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the music directory at runtime (config reload). Affects every
+    /// clone of this state.
+    pub fn set_music_dir(&self, dir: Option<String>) {
+        *self.music_dir.write().unwrap_or_else(|e| e.into_inner()) = dir;
+    }
+
+    /// This state as seen from partition `name`: its queue, status, player
+    /// and event bus. `None` when there is no such partition.
+    pub async fn for_partition(&self, name: &str) -> Option<AppState> {
+        if name == self.partition {
+            return Some(self.clone());
+        }
+        let partition = self.partition_manager.as_ref()?.get_partition(name).await?;
+        let players = self.partition_players.read().await;
+        Some(self.with_player(&partition, players.get(name)?))
+    }
+
+    fn with_player(&self, partition: &PartitionState, player: &PartitionPlayer) -> AppState {
+        AppState {
+            partition: partition.name.clone(),
+            queue: partition.queue.clone(),
+            status: partition.status.clone(),
+            event_bus: partition.event_bus.clone(),
+            engine: player.engine.clone(),
+            atomic_state: player.atomic_state.clone(),
+            stream_title: player.stream_title.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Create partition `name` with a player of its own. It has this
+    /// player's audio settings and no outputs until some are moved to it.
+    ///
+    /// The players stay locked until the new one is in: the partition is
+    /// visible as soon as the manager has it, and [`Self::for_partition`]
+    /// then waits for its player instead of finding none.
+    pub async fn create_partition(&self, name: &str) -> Result<Arc<PartitionState>, String> {
+        let Some(manager) = &self.partition_manager else {
+            return Err("Partition support not initialized".to_string());
+        };
+        let mut players = self.partition_players.write().await;
+        let partition = manager.create_partition(name.to_string()).await?;
+        let engine = self.engine.read().await.new_sibling(
+            partition.event_bus.clone(),
+            partition.status.clone(),
+            partition.atomic_state.clone(),
+        );
+        let player = PartitionPlayer {
+            engine: Arc::new(RwLock::new(engine)),
+            atomic_state: partition.atomic_state.clone(),
+            stream_title: Arc::new(RwLock::new(None)),
+            _playback: None,
+        };
+        let mut playback = QueuePlaybackManager::new(self.with_player(&partition, &player));
+        playback.start();
+        players.insert(
+            partition.name.clone(),
+            PartitionPlayer {
+                _playback: Some(playback),
+                ..player
+            },
+        );
+        Ok(partition)
+    }
+
+    /// Stop and drop the player of a deleted partition.
+    pub async fn stop_partition_player(&self, name: &str) {
+        let player = self.partition_players.write().await.remove(name);
+        if let Some(player) = player {
+            let _ = player.engine.write().await.stop().await;
+        }
+    }
+
+    /// Resolve a song URI to its location on disk, honouring extra music
+    /// roots (see [`rmpd_core::path::resolve_in_roots`]).
+    pub fn resolve_uri(&self, uri: &str) -> String {
+        rmpd_core::path::resolve_in_roots(uri, self.music_dir().as_deref(), &self.music_roots)
+    }
+
+    /// Resolve a URI received from a client to a local file, refusing paths
+    /// that escape the library (see [`rmpd_core::path::resolve_library_path`]).
+    pub fn resolve_client_path(
+        &self,
+        uri: &str,
+    ) -> Result<std::path::PathBuf, rmpd_core::path::PathError> {
+        rmpd_core::path::resolve_library_path(
+            uri,
+            self.music_dir().as_deref(),
+            &self.music_roots,
+            self.follow_symlinks,
+        )
+    }
+
     pub fn advertise_mdns(&self, port: u16) {
         if let Some(ref discovery) = self.discovery
             && let Err(e) = discovery.advertise(port)
@@ -187,33 +450,55 @@ impl AppState {
     /// progress/results via the event bus and the tracing log. Does nothing if
     /// the database or music directory is not configured.
     pub fn spawn_library_update(&self) {
-        let (Some(db_path), Some(music_dir)) = (self.db_path.clone(), self.music_dir.clone())
-        else {
+        if let Some(job) = self.library_update_job() {
+            tokio::task::spawn_blocking(job);
+        }
+    }
+
+    /// Scan the music directory like [`Self::spawn_library_update`], but
+    /// return only once the scan is done. Used to fill an in-memory library
+    /// at startup before anything looks songs up in it.
+    pub async fn update_library(&self) {
+        if let Some(job) = self.library_update_job()
+            && let Err(e) = tokio::task::spawn_blocking(job).await
+        {
+            tracing::error!("library update task failed: {e}");
+        }
+    }
+
+    /// The blocking scan run by [`Self::spawn_library_update`].
+    fn library_update_job(&self) -> Option<impl FnOnce() + Send + 'static> {
+        let (Some(backend), Some(music_dir)) = (self.db_pool.clone(), self.music_dir()) else {
             tracing::warn!("library update requested but database/music_dir not configured");
-            return;
+            return None;
         };
         let event_bus = self.event_bus.clone();
         let follow_symlinks = self.follow_symlinks;
+        let roots = self.music_roots.clone();
+        let tag_rewriter = self.tag_rewriter.clone();
+        let daemon_stats = self.stats.clone();
 
-        tokio::task::spawn_blocking(move || {
+        Some(move || {
             tracing::info!("starting library update");
-            match rmpd_library::Database::open(&db_path) {
-                Ok(db) => {
-                    let scanner = rmpd_library::Scanner::new(event_bus.clone(), follow_symlinks);
-                    match scanner.scan_directory(&db, std::path::Path::new(&music_dir)) {
-                        Ok(stats) => tracing::info!(
-                            "library scan complete: {} scanned, {} added, {} updated, {} errors",
-                            stats.scanned,
-                            stats.added,
-                            stats.updated,
-                            stats.errors
-                        ),
-                        Err(e) => tracing::error!("library scan error: {}", e),
-                    }
+            let started = Instant::now();
+            let scanner = rmpd_library::Scanner::new(event_bus.clone(), follow_symlinks)
+                .with_roots(roots.to_vec())
+                .with_tag_rewriter(tag_rewriter);
+            match backend.scan(&scanner, std::path::Path::new(&music_dir)) {
+                Ok(stats) => {
+                    tracing::info!(
+                        "library scan complete: {} scanned, {} added, {} updated, {} removed, {} errors",
+                        stats.scanned,
+                        stats.added,
+                        stats.updated,
+                        stats.removed,
+                        stats.errors
+                    );
+                    daemon_stats.record_scan(ScanRecord::new(&stats, started.elapsed()));
                 }
-                Err(e) => tracing::error!("failed to open database for update: {}", e),
+                Err(e) => tracing::error!("library scan error: {}", e),
             }
-        });
+        })
     }
 
     /// Spawn a background source sync for every enabled music source.
@@ -221,7 +506,8 @@ impl AppState {
     /// Each source is pinged first; on success the catalog is synced into the
     /// database via `rmpd_source::sync_source`. Sources that fail ping are
     /// skipped (cached rows are kept intact). Emits `DatabaseUpdateStarted` /
-    /// `DatabaseUpdateFinished` idle events so waiting clients wake up.
+    /// `DatabaseUpdateFinished` idle events so waiting clients wake up, and
+    /// `DatabaseChanged` when a source synced a non-empty catalog.
     /// Does nothing when no sources are configured or the database is absent.
     pub fn spawn_source_sync(&self) {
         let db_path = match self.db_path.clone() {
@@ -236,6 +522,7 @@ impl AppState {
 
         tokio::spawn(async move {
             event_bus.emit(rmpd_core::event::Event::DatabaseUpdateStarted);
+            let mut changed = false;
             for source in sources.iter() {
                 let scheme = source.scheme().to_owned();
                 let name = source.name().to_owned();
@@ -244,6 +531,7 @@ impl AppState {
                         tracing::info!("syncing music source '{}://{}'", scheme, name);
                         match rmpd_source::sync_source(source, &db_path).await {
                             Ok(count) => {
+                                changed |= count > 0;
                                 tracing::info!(
                                     "music source '{}://{}' synced {} songs",
                                     scheme,
@@ -271,6 +559,9 @@ impl AppState {
                     }
                 }
             }
+            if changed {
+                event_bus.emit(rmpd_core::event::Event::DatabaseChanged);
+            }
             event_bus.emit(rmpd_core::event::Event::DatabaseUpdateFinished);
         });
     }
@@ -298,14 +589,36 @@ impl AppState {
             outputs
                 .iter()
                 .enumerate()
-                .map(|(i, c)| OutputInfo {
-                    id: i as u32,
-                    name: c.name.clone(),
-                    plugin: c.output_type.clone(),
-                    enabled: c.enabled,
-                    partition: Some("default".to_string()),
-                    config: Some(c.clone()),
-                    attributes: std::collections::HashMap::new(),
+                .map(|(i, c)| {
+                    // Report the canonical plugin name (e.g. "default" → "cpal")
+                    // and the plugin's declared settings as attributes.
+                    let plugin = rmpd_player::output_plugin(&c.output_type);
+                    if plugin.is_none() {
+                        tracing::warn!(
+                            "output \"{}\" has unknown type \"{}\"; it will fail to open",
+                            c.name,
+                            c.output_type
+                        );
+                    }
+                    let attributes = plugin
+                        .map(|p| {
+                            p.settings
+                                .iter()
+                                .filter_map(|(key, _)| {
+                                    c.setting_str(key).map(|v| ((*key).to_owned(), v))
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    OutputInfo {
+                        id: i as u32,
+                        name: c.name.clone(),
+                        plugin: plugin.map_or_else(|| c.output_type.clone(), |p| p.name.to_owned()),
+                        enabled: c.enabled,
+                        partition: Some("default".to_string()),
+                        config: Some(c.clone()),
+                        attributes,
+                    }
                 })
                 .collect()
         };
@@ -1,3 +1,4 @@
+use crate::stats::{ScanRecord, StatsSnapshot};
 use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::queue::Queue;
 use rmpd_core::state::{PlayerState, PlayerStatus, ReplayGainMode};
@@ -22,6 +23,7 @@ impl StateFile {
         status: &PlayerStatus,
         queue: &Queue,
         disabled_outputs: &[String],
+        stats: &StatsSnapshot,
     ) -> Result<()> {
         let mut content = String::new();
 
@@ -78,6 +80,14 @@ impl StateFile {
             content.push_str(&format!("audio_device_state:0:{name}\n"));
         }
 
+        // Daemon statistics (rmpd only; MPD ignores unknown lines)
+        for (name, value) in stats.counters.fields() {
+            content.push_str(&format!("stats_{name}: {value}\n"));
+        }
+        for scan in &stats.scans {
+            content.push_str(&format!("stats_scan: {}\n", scan.to_line()));
+        }
+
         // Playlist
         content.push_str("playlist_begin\n");
         for item in queue.items() {
@@ -202,7 +212,19 @@ impl StateFile {
                             }
                             // malformed or state "1" (enabled) → skip
                         }
-                        _ => {} // Ignore unknown keys
+                        "stats_scan" => {
+                            if let Some(scan) = ScanRecord::parse_line(value) {
+                                state.stats.scans.push(scan);
+                            }
+                        }
+                        _ => {
+                            if let Some(name) = key.strip_prefix("stats_")
+                                && let Ok(value) = value.parse()
+                            {
+                                state.stats.counters.set(name, value);
+                            }
+                            // Ignore other unknown keys
+                        }
                     }
                 }
             }
@@ -231,6 +253,8 @@ pub struct SavedState {
     pub replay_gain_mode: ReplayGainMode,
     pub playlist_paths: Vec<String>,
     pub disabled_outputs: Vec<String>,
+    /// Daemon statistics of earlier runs.
+    pub stats: StatsSnapshot,
 }
 
 #[cfg(test)]
@@ -279,7 +303,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
 
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.volume, 75);
@@ -325,7 +352,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.state, Some(PlayerState::Play));
         assert!(loaded.random);
@@ -338,13 +368,19 @@ mod tests {
 
         // Test Pause state
         status.state = PlayerState::Pause;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.state, Some(PlayerState::Pause));
 
         // Test Stop state
         status.state = PlayerState::Stop;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.state, Some(PlayerState::Stop));
     }
@@ -380,19 +416,28 @@ mod tests {
         };
 
         // Test SingleMode::Off
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.single, SingleMode::Off);
 
         // Test SingleMode::On
         status.single = SingleMode::On;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.single, SingleMode::On);
 
         // Test SingleMode::Oneshot
         status.single = SingleMode::Oneshot;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.single, SingleMode::Oneshot);
     }
@@ -428,19 +473,28 @@ mod tests {
         };
 
         // Test ConsumeMode::Off
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.consume, ConsumeMode::Off);
 
         // Test ConsumeMode::On
         status.consume = ConsumeMode::On;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.consume, ConsumeMode::On);
 
         // Test ConsumeMode::Oneshot
         status.consume = ConsumeMode::Oneshot;
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.consume, ConsumeMode::Oneshot);
     }
@@ -475,7 +529,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.playlist_paths.len(), 0);
     }
@@ -514,7 +571,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.playlist_paths.len(), 1000);
         assert_eq!(loaded.playlist_paths[0], "/music/song0.mp3");
@@ -551,7 +611,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
 
         // Verify temp file doesn't exist
         let temp_path = format!("{state_path}.tmp");
@@ -672,7 +735,10 @@ mod tests {
             replay_gain_mode: ReplayGainMode::Off,
         };
 
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert!(loaded.elapsed_seconds.is_some());
         let elapsed = loaded.elapsed_seconds.unwrap();
@@ -733,7 +799,10 @@ mod tests {
 
         // Persist two disabled outputs; one name contains a colon.
         let disabled = vec!["Some Output".to_string(), "HDMI:Output 1".to_string()];
-        statefile.save(&status, &queue, &disabled).await.unwrap();
+        statefile
+            .save(&status, &queue, &disabled, &StatsSnapshot::default())
+            .await
+            .unwrap();
 
         let loaded = statefile.load().unwrap().unwrap();
         assert_eq!(loaded.disabled_outputs.len(), 2);
@@ -745,8 +814,38 @@ mod tests {
         );
 
         // Enabled output should NOT appear in disabled_outputs.
-        statefile.save(&status, &queue, &[]).await.unwrap();
+        statefile
+            .save(&status, &queue, &[], &StatsSnapshot::default())
+            .await
+            .unwrap();
         let loaded = statefile.load().unwrap().unwrap();
         assert!(loaded.disabled_outputs.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_save_and_load_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let state_path = temp_dir.path().join("state").to_str().unwrap().to_string();
+        let statefile = StateFile::new(state_path);
+
+        let mut stats = StatsSnapshot::default();
+        stats.counters.commands = 1234;
+        stats.counters.underruns = 3;
+        stats.scans.push(ScanRecord {
+            finished: 1_700_000_000,
+            duration_ms: 2500,
+            scanned: 40,
+            added: 4,
+            updated: 0,
+            removed: 1,
+            errors: 0,
+        });
+        statefile
+            .save(&PlayerStatus::default(), &Queue::new(), &[], &stats)
+            .await
+            .unwrap();
+
+        let loaded = statefile.load().unwrap().unwrap();
+        assert_eq!(loaded.stats, stats);
+    }
 }
@@ -0,0 +1,459 @@
+//! Daemon-wide statistics.
+//!
+//! MPD's `stats` describes the library and the current run. The counters
+//! here follow the daemon over its whole life instead: commands served,
+//! bytes sent, library cache hits and misses, decode errors, output
+//! underruns and the latest library scans. The state file keeps their
+//! totals across restarts, so they show long-term trends. Clients read them
+//! with the `daemonstats` extension command, monitoring systems from the
+//! metrics endpoint (see [`crate::metrics_http`]).
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rmpd_library::ScanStats;
+
+use crate::response::ResponseBuilder;
+use crate::state::AppState;
+
+/// Library scans kept in the history.
+pub const SCAN_HISTORY_SIZE: usize = 10;
+
+/// Counter totals, named as in `daemonstats` responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Seconds the daemon has been running.
+    pub uptime: u64,
+    pub commands: u64,
+    pub bytes_sent: u64,
+    pub browse_cache_hits: u64,
+    pub browse_cache_misses: u64,
+    pub search_cache_hits: u64,
+    pub search_cache_misses: u64,
+    pub decode_errors: u64,
+    pub underruns: u64,
+    pub library_scans: u64,
+}
+
+impl Counters {
+    /// `(name, value)` of every counter.
+    pub fn fields(&self) -> [(&'static str, u64); 10] {
+        [
+            ("uptime", self.uptime),
+            ("commands", self.commands),
+            ("bytes_sent", self.bytes_sent),
+            ("browse_cache_hits", self.browse_cache_hits),
+            ("browse_cache_misses", self.browse_cache_misses),
+            ("search_cache_hits", self.search_cache_hits),
+            ("search_cache_misses", self.search_cache_misses),
+            ("decode_errors", self.decode_errors),
+            ("underruns", self.underruns),
+            ("library_scans", self.library_scans),
+        ]
+    }
+
+    /// Set the counter called `name`; `false` when there is none.
+    pub fn set(&mut self, name: &str, value: u64) -> bool {
+        let counter = match name {
+            "uptime" => &mut self.uptime,
+            "commands" => &mut self.commands,
+            "bytes_sent" => &mut self.bytes_sent,
+            "browse_cache_hits" => &mut self.browse_cache_hits,
+            "browse_cache_misses" => &mut self.browse_cache_misses,
+            "search_cache_hits" => &mut self.search_cache_hits,
+            "search_cache_misses" => &mut self.search_cache_misses,
+            "decode_errors" => &mut self.decode_errors,
+            "underruns" => &mut self.underruns,
+            "library_scans" => &mut self.library_scans,
+            _ => return false,
+        };
+        *counter = value;
+        true
+    }
+
+    fn add(&self, other: &Counters) -> Counters {
+        let mut sum = *self;
+        for ((name, a), (_, b)) in self.fields().into_iter().zip(other.fields()) {
+            sum.set(name, a.saturating_add(b));
+        }
+        sum
+    }
+}
+
+/// One finished library scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanRecord {
+    /// When the scan finished, in seconds since the Unix epoch.
+    pub finished: u64,
+    pub duration_ms: u64,
+    pub scanned: u32,
+    pub added: u32,
+    pub updated: u32,
+    pub removed: u32,
+    pub errors: u32,
+}
+
+impl ScanRecord {
+    /// A scan that just finished after `duration` with `stats`.
+    pub fn new(stats: &ScanStats, duration: Duration) -> Self {
+        Self {
+            finished: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            duration_ms: duration.as_millis() as u64,
+            scanned: stats.scanned,
+            added: stats.added,
+            updated: stats.updated,
+            removed: stats.removed,
+            errors: stats.errors,
+        }
+    }
+
+    /// The record as saved in the state file: its numbers, space-separated.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {}",
+            self.finished,
+            self.duration_ms,
+            self.scanned,
+            self.added,
+            self.updated,
+            self.removed,
+            self.errors
+        )
+    }
+
+    /// Parse a [`Self::to_line`] line.
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let mut numbers = line.split_whitespace();
+        let mut next = || numbers.next()?.parse::<u64>().ok();
+        let record = Self {
+            finished: next()?,
+            duration_ms: next()?,
+            scanned: next()?.try_into().ok()?,
+            added: next()?.try_into().ok()?,
+            updated: next()?.try_into().ok()?,
+            removed: next()?.try_into().ok()?,
+            errors: next()?.try_into().ok()?,
+        };
+        Some(record)
+    }
+}
+
+/// Totals and scan history at one moment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub counters: Counters,
+    /// The latest scans, oldest first.
+    pub scans: Vec<ScanRecord>,
+}
+
+/// Counters shared by every connection and partition.
+#[derive(Debug)]
+pub struct DaemonStats {
+    commands: AtomicU64,
+    bytes_sent: AtomicU64,
+    library_scans: AtomicU64,
+    scans: Mutex<VecDeque<ScanRecord>>,
+    /// Totals of earlier runs, from the state file.
+    earlier: Mutex<Counters>,
+    /// The process-wide player counters when this daemon started.
+    decode_errors_base: u64,
+    underruns_base: u64,
+}
+
+impl Default for DaemonStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DaemonStats {
+    pub fn new() -> Self {
+        Self {
+            commands: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            library_scans: AtomicU64::new(0),
+            scans: Mutex::new(VecDeque::new()),
+            earlier: Mutex::new(Counters::default()),
+            decode_errors_base: rmpd_player::stats::decode_errors(),
+            underruns_base: rmpd_player::stats::underruns(),
+        }
+    }
+
+    pub fn record_command(&self) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_scan(&self, scan: ScanRecord) {
+        self.library_scans.fetch_add(1, Ordering::Relaxed);
+        let mut scans = lock(&self.scans);
+        if scans.len() >= SCAN_HISTORY_SIZE {
+            scans.pop_front();
+        }
+        scans.push_back(scan);
+    }
+
+    /// Continue from the totals and scan history saved by an earlier run.
+    pub fn restore(&self, saved: &StatsSnapshot) {
+        *lock(&self.earlier) = saved.counters;
+        let mut scans = lock(&self.scans);
+        let recent: Vec<ScanRecord> = scans.drain(..).collect();
+        scans.extend(saved.scans.iter().chain(&recent).copied());
+        while scans.len() > SCAN_HISTORY_SIZE {
+            scans.pop_front();
+        }
+    }
+
+    /// Totals over every run so far, including this one.
+    pub fn snapshot(&self, state: &AppState) -> StatsSnapshot {
+        let (browse_cache_hits, browse_cache_misses) = state.browse_cache.hit_counts();
+        let (search_cache_hits, search_cache_misses) = state.search_cache.hit_counts();
+        let current = Counters {
+            uptime: state.start_time.elapsed().as_secs(),
+            commands: self.commands.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            browse_cache_hits,
+            browse_cache_misses,
+            search_cache_hits,
+            search_cache_misses,
+            decode_errors: rmpd_player::stats::decode_errors()
+                .saturating_sub(self.decode_errors_base),
+            underruns: rmpd_player::stats::underruns().saturating_sub(self.underruns_base),
+            library_scans: self.library_scans.load(Ordering::Relaxed),
+        };
+        StatsSnapshot {
+            counters: lock(&self.earlier).add(&current),
+            scans: lock(&self.scans).iter().copied().collect(),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hits as a share of all lookups; `None` before the first lookup.
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    let lookups = hits + misses;
+    (lookups > 0).then(|| hits as f64 / lookups as f64)
+}
+
+/// The `daemonstats` response: every counter, the cache hit rates, then one
+/// `scan_finished`-led group per scan, oldest first.
+pub fn write_response(resp: &mut ResponseBuilder, snapshot: &StatsSnapshot) {
+    let counters = &snapshot.counters;
+    for (name, value) in counters.fields() {
+        resp.field(name, value);
+    }
+    let rates = [
+        (
+            "browse_cache_hit_rate",
+            hit_rate(counters.browse_cache_hits, counters.browse_cache_misses),
+        ),
+        (
+            "search_cache_hit_rate",
+            hit_rate(counters.search_cache_hits, counters.search_cache_misses),
+        ),
+    ];
+    for (name, rate) in rates {
+        if let Some(rate) = rate {
+            resp.field(name, format!("{rate:.3}"));
+        }
+    }
+    for scan in &snapshot.scans {
+        resp.field("scan_finished", scan.finished);
+        resp.field(
+            "scan_duration",
+            format!("{:.3}", scan.duration_ms as f64 / 1000.0),
+        );
+        resp.field("scan_scanned", scan.scanned);
+        resp.field("scan_added", scan.added);
+        resp.field("scan_updated", scan.updated);
+        resp.field("scan_removed", scan.removed);
+        resp.field("scan_errors", scan.errors);
+    }
+}
+
+/// The snapshot in the Prometheus text exposition format.
+pub fn metrics(snapshot: &StatsSnapshot) -> String {
+    let c = &snapshot.counters;
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+        let _ = writeln!(out, "# HELP rmpd_{name} {help}");
+        let _ = writeln!(out, "# TYPE rmpd_{name} {kind}");
+        for (labels, value) in samples {
+            let _ = writeln!(out, "rmpd_{name}{labels} {value}");
+        }
+    };
+    metric(
+        "uptime_seconds_total",
+        "counter",
+        "Seconds the daemon has been running.",
+        &[("", c.uptime as f64)],
+    );
+    metric(
+        "commands_total",
+        "counter",
+        "Commands served.",
+        &[("", c.commands as f64)],
+    );
+    metric(
+        "sent_bytes_total",
+        "counter",
+        "Bytes of responses sent to clients.",
+        &[("", c.bytes_sent as f64)],
+    );
+    metric(
+        "cache_hits_total",
+        "counter",
+        "Library queries answered from a cache.",
+        &[
+            ("{cache=\"browse\"}", c.browse_cache_hits as f64),
+            ("{cache=\"search\"}", c.search_cache_hits as f64),
+        ],
+    );
+    metric(
+        "cache_misses_total",
+        "counter",
+        "Library queries that missed a cache.",
+        &[
+            ("{cache=\"browse\"}", c.browse_cache_misses as f64),
+            ("{cache=\"search\"}", c.search_cache_misses as f64),
+        ],
+    );
+    metric(
+        "decode_errors_total",
+        "counter",
+        "Audio packets that could not be read or decoded.",
+        &[("", c.decode_errors as f64)],
+    );
+    metric(
+        "underruns_total",
+        "counter",
+        "Times an output device ran out of audio.",
+        &[("", c.underruns as f64)],
+    );
+    metric(
+        "library_scans_total",
+        "counter",
+        "Library scans finished.",
+        &[("", c.library_scans as f64)],
+    );
+    if let Some(scan) = snapshot.scans.last() {
+        metric(
+            "last_scan_timestamp_seconds",
+            "gauge",
+            "When the latest library scan finished.",
+            &[("", scan.finished as f64)],
+        );
+        metric(
+            "last_scan_duration_seconds",
+            "gauge",
+            "How long the latest library scan took.",
+            &[("", scan.duration_ms as f64 / 1000.0)],
+        );
+        metric(
+            "last_scan_songs",
+            "gauge",
+            "Songs handled by the latest library scan.",
+            &[
+                ("{change=\"scanned\"}", f64::from(scan.scanned)),
+                ("{change=\"added\"}", f64::from(scan.added)),
+                ("{change=\"updated\"}", f64::from(scan.updated)),
+                ("{change=\"removed\"}", f64::from(scan.removed)),
+                ("{change=\"error\"}", f64::from(scan.errors)),
+            ],
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(finished: u64) -> ScanRecord {
+        ScanRecord {
+            finished,
+            duration_ms: 1500,
+            scanned: 10,
+            added: 2,
+            updated: 1,
+            removed: 0,
+            errors: 1,
+        }
+    }
+
+    #[test]
+    fn scan_records_round_trip_through_lines() {
+        let record = scan(1_700_000_000);
+        assert_eq!(record.to_line(), "1700000000 1500 10 2 1 0 1");
+        assert_eq!(ScanRecord::parse_line(&record.to_line()), Some(record));
+        assert_eq!(ScanRecord::parse_line("1700000000 1500 10"), None);
+    }
+
+    #[test]
+    fn totals_continue_from_earlier_runs() {
+        let state = AppState::new();
+        let stats = DaemonStats::new();
+        stats.record_command();
+        stats.record_bytes_sent(100);
+        stats.record_scan(scan(3));
+
+        let saved = StatsSnapshot {
+            counters: Counters {
+                commands: 41,
+                bytes_sent: 900,
+                library_scans: 2,
+                ..Counters::default()
+            },
+            scans: vec![scan(1), scan(2)],
+        };
+        stats.restore(&saved);
+
+        let snapshot = stats.snapshot(&state);
+        assert_eq!(snapshot.counters.commands, 42);
+        assert_eq!(snapshot.counters.bytes_sent, 1000);
+        assert_eq!(snapshot.counters.library_scans, 3);
+        assert_eq!(snapshot.scans, vec![scan(1), scan(2), scan(3)]);
+    }
+
+    #[test]
+    fn scan_history_is_bounded() {
+        let stats = DaemonStats::new();
+        stats.record_scan(scan(100));
+        stats.restore(&StatsSnapshot {
+            counters: Counters::default(),
+            scans: (0..20).map(scan).collect(),
+        });
+        let scans = stats.snapshot(&AppState::new()).scans;
+        assert_eq!(scans.len(), SCAN_HISTORY_SIZE);
+        assert_eq!(scans.first(), Some(&scan(11)));
+        assert_eq!(scans.last(), Some(&scan(100)), "this run's scans are kept");
+    }
+
+    #[test]
+    fn metrics_use_prometheus_names() {
+        let snapshot = StatsSnapshot {
+            counters: Counters {
+                commands: 7,
+                browse_cache_hits: 3,
+                ..Counters::default()
+            },
+            scans: vec![scan(5)],
+        };
+        let text = metrics(&snapshot);
+        assert!(text.contains("# TYPE rmpd_commands_total counter\nrmpd_commands_total 7\n"));
+        assert!(text.contains("rmpd_cache_hits_total{cache=\"browse\"} 3\n"));
+        assert!(text.contains("rmpd_last_scan_songs{change=\"added\"} 2\n"));
+        assert!(text.contains("rmpd_last_scan_duration_seconds 1.5\n"));
+    }
+}
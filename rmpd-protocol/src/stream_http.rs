@@ -0,0 +1,548 @@
+//! Transcoded audio over HTTP.
+//!
+//! With `network.stream_port` set, rmpd also streams audio to browsers and
+//! other remote players, decoded and re-encoded in-process by the same
+//! encoders the `httpd` and `recorder` outputs use, so the home library stays
+//! listenable over a slow link:
+//!
+//! - `GET /stream/<uri>`: the library song at the percent-encoded URI
+//! - `GET /live`: what the player is playing, taken from the first enabled
+//!   `httpd` output
+//!
+//! `?format=<encoder>` picks the codec (`opus`, `vorbis`, `mp3`, `flac`, …,
+//! whichever are compiled in; by default the first of `opus`, `vorbis`,
+//! `mp3` and `flac` that is) and `?bitrate=<kbit/s>` overrides
+//! `network.stream_bitrate` for encoders that take a bitrate. At most
+//! `network.stream_max_clients` streams run at once; further requests are
+//! answered `503`. Listening needs the `read` permission: clients have it
+//! when `default_permissions` (or the absence of a password) grants it, or
+//! by passing the password as `?password=<secret>`. With `[[api_token]]`s
+//! configured, a token with the `read` scope is needed instead (see
+//! [`crate::http_auth`]).
+
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rmpd_core::config::{ApiScope, OutputConfig};
+use rmpd_core::error::Result;
+use rmpd_core::song::AudioFormat;
+use rmpd_player::decoder::{SongDecoder, open_decoder};
+use rmpd_player::encoder::{Encoder, create_encoder, encoder_plugin};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Semaphore, mpsc};
+use tracing::{debug, info, warn};
+
+use crate::art_http::{REQUEST_TIMEOUT, percent_decode, read_head, status_response};
+use crate::commands::utils::open_db;
+use crate::http_auth::{self, Access};
+use crate::state::{AppState, OutputInfo};
+
+/// Accepted bitrates in kbit/s.
+pub const BITRATE_RANGE: RangeInclusive<u32> = 8..=320;
+
+/// Encoders a request without `?format=` gets, in order of preference.
+const DEFAULT_ENCODERS: [&str; 4] = ["opus", "vorbis", "lame", "flac"];
+
+/// Port of an `httpd` output that does not set one.
+const DEFAULT_HTTPD_PORT: u16 = 8000;
+
+/// PCM rate DSD songs are converted to before encoding.
+const DSD_PCM_RATE: u32 = 88_200;
+
+/// Interleaved samples decoded at a time: a multiple of every channel count
+/// up to 8, so each chunk holds whole frames.
+const CHUNK_SAMPLES: usize = 840 * 8;
+
+/// Encoded chunks buffered ahead of a slow client.
+const CHUNK_QUEUE: usize = 8;
+
+/// Handle that keeps the endpoint listening. Dropping it stops accepting
+/// requests; streams already running go on until their client leaves.
+pub struct StreamHttpHandle {
+    task: tokio::task::JoinHandle<()>,
+    local_addr: SocketAddr,
+}
+
+impl StreamHttpHandle {
+    /// The address the endpoint listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for StreamHttpHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start streaming from `state` on `addr`:`port` (0 picks a free port),
+/// at `bitrate` kbit/s unless a request asks for another, to at most
+/// `max_clients` clients at once.
+pub async fn spawn(
+    state: AppState,
+    addr: &str,
+    port: u16,
+    bitrate: u32,
+    max_clients: usize,
+) -> std::io::Result<StreamHttpHandle> {
+    let listener = TcpListener::bind((addr, port)).await?;
+    let local_addr = listener.local_addr()?;
+    let bitrate = bitrate.clamp(*BITRATE_RANGE.start(), *BITRATE_RANGE.end());
+    info!("transcoding stream HTTP endpoint listening on {local_addr} ({bitrate} kbit/s)");
+
+    let streams = Arc::new(Semaphore::new(max_clients));
+    let task = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((mut stream, peer)) => {
+                    let Ok(permit) = Arc::clone(&streams).try_acquire_owned() else {
+                        debug!("stream http: {peer}: client limit ({max_clients}) reached");
+                        tokio::spawn(async move {
+                            // Read the request first, so closing does not
+                            // reset the connection before the reply arrives.
+                            tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+                                .await
+                                .ok();
+                            reply(&mut stream, "503 Service Unavailable").await.ok();
+                        });
+                        continue;
+                    };
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        let _permit = permit;
+                        if let Err(e) = serve(&state, stream, bitrate).await {
+                            debug!("stream http: {peer}: {e}");
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("stream http: accept failed: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(StreamHttpHandle { task, local_addr })
+}
+
+/// Answer the one request sent on `stream`, streaming audio until the
+/// source ends or the client goes away, then close it.
+async fn serve(state: &AppState, mut stream: TcpStream, bitrate: u32) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+    let Some(request) = head.as_deref().and_then(Request::parse) else {
+        return reply(&mut stream, "400 Bad Request").await;
+    };
+    let token = head.as_deref().and_then(http_auth::request_token);
+    match http_auth::check(&state.api_tokens, token.as_deref(), ApiScope::Read) {
+        Access::Granted => {}
+        Access::Open if http_auth::authorized(state, request.password.as_deref()) => {}
+        Access::Open => return reply(&mut stream, "403 Forbidden").await,
+        denied => {
+            stream.write_all(&http_auth::refusal(denied)).await?;
+            return stream.shutdown().await;
+        }
+    }
+    let Some(input) = find_input(state, request.source.as_ref()).await else {
+        return reply(&mut stream, "404 Not Found").await;
+    };
+    let encoder = encoder_config(request.encoder, request.bitrate.unwrap_or(bitrate));
+
+    if request.head_only {
+        // Any format tells the content type; nothing is decoded.
+        let format = AudioFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        return match create_encoder(format, &encoder, request.encoder) {
+            Ok(encoder) => {
+                stream
+                    .write_all(ok_header(encoder.content_type()).as_bytes())
+                    .await?;
+                stream.shutdown().await
+            }
+            Err(e) => {
+                warn!("stream http: {e}");
+                reply(&mut stream, "503 Service Unavailable").await
+            }
+        };
+    }
+
+    let name = request.encoder;
+    let transcoder =
+        match tokio::task::spawn_blocking(move || Transcoder::open(&input, &encoder, name)).await {
+            Ok(Ok(transcoder)) => transcoder,
+            Ok(Err(e)) => {
+                debug!("stream http: {e}");
+                return reply(&mut stream, "503 Service Unavailable").await;
+            }
+            Err(_) => return reply(&mut stream, "503 Service Unavailable").await,
+        };
+    stream
+        .write_all(ok_header(transcoder.encoder.content_type()).as_bytes())
+        .await?;
+    let (tx, mut rx) = mpsc::channel(CHUNK_QUEUE);
+    // A client leaving ends the loop with an error, and the transcoder stops
+    // once it finds the channel closed.
+    tokio::task::spawn_blocking(move || transcoder.run(&tx));
+    while let Some(chunk) = rx.recv().await {
+        stream.write_all(&chunk).await?;
+    }
+    stream.shutdown().await
+}
+
+async fn reply(stream: &mut TcpStream, status: &str) -> std::io::Result<()> {
+    stream.write_all(&status_response(status)).await?;
+    stream.shutdown().await
+}
+
+fn ok_header(content_type: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nCache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n"
+    )
+}
+
+/// The encoder a request without `?format=` gets.
+fn default_encoder() -> &'static str {
+    DEFAULT_ENCODERS
+        .into_iter()
+        .find_map(encoder_plugin)
+        .map_or("flac", |plugin| plugin.name)
+}
+
+/// Output settings selecting the encoder `name`, at `bitrate` kbit/s (kept
+/// within the encoder's range) when it takes one.
+fn encoder_config(name: &str, bitrate: u32) -> OutputConfig {
+    let mut settings = toml::Table::new();
+    settings.insert("encoder".to_owned(), toml::Value::String(name.to_owned()));
+    if let Some(range) = encoder_plugin(name).and_then(|plugin| plugin.bitrate.as_ref()) {
+        let bitrate = bitrate.clamp(*range.start(), *range.end());
+        settings.insert("bitrate".to_owned(), toml::Value::Integer(bitrate.into()));
+    }
+    OutputConfig {
+        name: "stream".to_owned(),
+        output_type: "stream".to_owned(),
+        enabled: true,
+        settings,
+    }
+}
+
+/// What a stream request asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    /// A library song URI.
+    Song(String),
+    Live,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    /// `HEAD`: headers only, nothing is transcoded.
+    head_only: bool,
+    /// `None` when the path is not a stream path.
+    source: Option<Source>,
+    /// Name of the encoder plugin.
+    encoder: &'static str,
+    /// kbit/s; `None` for the configured bitrate.
+    bitrate: Option<u32>,
+    password: Option<String>,
+}
+
+impl Request {
+    /// Parse a request head; `None` when malformed, not `GET`/`HEAD`, or
+    /// asking for an encoder that is not compiled in or a bitrate outside
+    /// [`BITRATE_RANGE`].
+    fn parse(head: &str) -> Option<Self> {
+        let mut request_line = head.split("\r\n").next()?.split(' ');
+        let head_only = match request_line.next()? {
+            "GET" => false,
+            "HEAD" => true,
+            _ => return None,
+        };
+        let target = request_line.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            head_only,
+            source: parse_source(path),
+            encoder: default_encoder(),
+            bitrate: None,
+            password: None,
+        };
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match key {
+                "format" => request.encoder = encoder_plugin(&value)?.name,
+                "bitrate" => {
+                    let kbps = value.parse().ok().filter(|b| BITRATE_RANGE.contains(b))?;
+                    request.bitrate = Some(kbps);
+                }
+                "password" => request.password = Some(value),
+                _ => {}
+            }
+        }
+        Some(request)
+    }
+}
+
+/// The source named by a request path (without its query string).
+fn parse_source(path: &str) -> Option<Source> {
+    if path == "/live" {
+        return Some(Source::Live);
+    }
+    let uri = percent_decode(path.strip_prefix("/stream/")?)?;
+    (!uri.is_empty()).then_some(Source::Song(uri))
+}
+
+/// What to decode for `source`: the song's file, or the stream of the live
+/// `httpd` output.
+async fn find_input(state: &AppState, source: Option<&Source>) -> Option<PathBuf> {
+    match source? {
+        Source::Live => live_url(&state.outputs.read().await).map(PathBuf::from),
+        Source::Song(uri) => {
+            if rmpd_core::path::is_uri(uri) {
+                return None;
+            }
+            let path = state.resolve_client_path(uri).ok()?;
+            let uri = uri.clone();
+            let state = state.clone();
+            tokio::task::spawn_blocking(move || {
+                open_db(&state, "stream")
+                    .ok()?
+                    .get_song_by_path(&uri)
+                    .ok()?
+            })
+            .await
+            .ok()??;
+            Some(path)
+        }
+    }
+}
+
+/// URL of the first enabled `httpd` output, reached over loopback when it
+/// listens on every interface.
+fn live_url(outputs: &[OutputInfo]) -> Option<String> {
+    let config = outputs
+        .iter()
+        .find(|o| o.enabled && o.plugin == "httpd")?
+        .config
+        .as_ref()?;
+    let port = config
+        .setting_str("port")
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_HTTPD_PORT);
+    let host = match config.setting_str("bind_to_address").as_deref() {
+        None | Some("0.0.0.0" | "any") => "127.0.0.1".to_owned(),
+        Some("::") => "[::1]".to_owned(),
+        Some(addr) if addr.contains(':') => format!("[{addr}]"),
+        Some(addr) => addr.to_owned(),
+    };
+    Some(format!("http://{host}:{port}/"))
+}
+
+/// One client's audio, decoded and re-encoded on a blocking thread.
+struct Transcoder {
+    decoder: SongDecoder,
+    encoder: Box<dyn Encoder>,
+    /// The first decoded samples, read before the encoder is built because
+    /// the channel count is only known once audio was decoded.
+    first: Vec<f32>,
+}
+
+impl Transcoder {
+    /// Open `input` and the encoder `name` configured by `encoder`.
+    fn open(input: &Path, encoder: &OutputConfig, name: &str) -> Result<Self> {
+        let mut decoder = open_decoder(input)?;
+        decoder.enable_pcm_conversion(DSD_PCM_RATE)?;
+        let mut first = vec![0.0; CHUNK_SAMPLES];
+        let samples = decoder.read(&mut first)?;
+        first.truncate(samples);
+        let encoder = create_encoder(decoder.format(), encoder, name)?;
+        Ok(Self {
+            decoder,
+            encoder,
+            first,
+        })
+    }
+
+    /// Send the encoded stream to `tx` until the source ends or the
+    /// receiver is gone.
+    fn run(mut self, tx: &mpsc::Sender<Vec<u8>>) {
+        let mut chunk = self.encoder.header();
+        chunk.extend(self.encoder.encode(&self.first));
+        let mut samples = vec![0.0; CHUNK_SAMPLES];
+        loop {
+            if !chunk.is_empty() && tx.blocking_send(std::mem::take(&mut chunk)).is_err() {
+                return;
+            }
+            match self.decoder.read(&mut samples) {
+                Ok(0) => break,
+                Ok(n) => chunk = self.encoder.encode(&samples[..n]),
+                Err(e) => {
+                    debug!("stream http: decoding failed: {e}");
+                    break;
+                }
+            }
+        }
+        tx.blocking_send(self.encoder.finish()).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::http_request;
+    use rmpd_core::test_utils::make_test_song;
+
+    #[test]
+    fn requests_are_parsed() {
+        let request = Request::parse(
+            "GET /stream/Album/01%20Intro.flac?format=FLAC&bitrate=64 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert!(!request.head_only);
+        assert_eq!(
+            request.source,
+            Some(Source::Song("Album/01 Intro.flac".to_owned()))
+        );
+        assert_eq!(request.encoder, "flac");
+        assert_eq!(request.bitrate, Some(64));
+
+        let request = Request::parse("HEAD /live?password=a%26b HTTP/1.1\r\n\r\n").unwrap();
+        assert!(request.head_only);
+        assert_eq!(request.source, Some(Source::Live));
+        assert_eq!(request.encoder, default_encoder());
+        assert_eq!(request.password.as_deref(), Some("a&b"));
+
+        assert_eq!(
+            Request::parse("GET /art/1 HTTP/1.1\r\n\r\n")
+                .unwrap()
+                .source,
+            None
+        );
+        assert_eq!(
+            Request::parse("GET /live?format=aac HTTP/1.1\r\n\r\n"),
+            None
+        );
+        assert_eq!(
+            Request::parse("GET /live?bitrate=1000 HTTP/1.1\r\n\r\n"),
+            None
+        );
+        assert_eq!(Request::parse("POST /live HTTP/1.1\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn live_url_points_at_httpd_output() {
+        let output = |plugin: &str, enabled: bool, settings: &str| {
+            let config: rmpd_core::config::OutputConfig = toml::from_str(&format!(
+                "type = \"{plugin}\"\nname = \"{plugin}\"\n{settings}"
+            ))
+            .unwrap();
+            OutputInfo {
+                id: 0,
+                name: config.name.clone(),
+                plugin: plugin.to_owned(),
+                enabled,
+                partition: None,
+                config: Some(config),
+                attributes: Default::default(),
+            }
+        };
+        assert_eq!(live_url(&[output("cpal", true, "")]), None);
+        assert_eq!(live_url(&[output("httpd", false, "port = \"8001\"")]), None);
+        assert_eq!(
+            live_url(&[output("cpal", true, ""), output("httpd", true, "")]),
+            Some("http://127.0.0.1:8000/".to_owned())
+        );
+        assert_eq!(
+            live_url(&[output(
+                "httpd",
+                true,
+                "bind_to_address = \"::1\"\nport = \"8002\""
+            )]),
+            Some("http://[::1]:8002/".to_owned())
+        );
+    }
+
+    #[test]
+    fn bitrates_are_only_set_for_encoders_that_take_one() {
+        assert_eq!(encoder_config("flac", 96).setting_str("bitrate"), None);
+        assert_eq!(
+            encoder_config("wave", 96).setting_str("encoder").as_deref(),
+            Some("wave")
+        );
+    }
+
+    #[tokio::test]
+    async fn library_songs_are_found_and_guarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("stream.db").to_string_lossy().into_owned();
+        rmpd_library::Database::open(&db_path)
+            .unwrap()
+            .add_song(&make_test_song("a.wav", 1))
+            .unwrap();
+        rmpd_core::test_utils::write_wav(&dir.path().join("a.wav"), 4410);
+        let mut state = AppState::with_paths(db_path, dir.path().to_string_lossy().into_owned());
+        state.set_password(Some("secret".to_owned()));
+        let handle = spawn(state, "127.0.0.1", 0, 96, 4).await.unwrap();
+        let addr = handle.local_addr();
+
+        let resp = http_request(addr, "HEAD /stream/a.wav HTTP/1.1\r\n\r\n").await;
+        assert!(resp.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{resp}");
+        let resp = http_request(addr, "HEAD /stream/a.wav?password=wrong HTTP/1.1\r\n\r\n").await;
+        assert!(resp.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{resp}");
+
+        let resp = http_request(
+            addr,
+            "HEAD /stream/a.wav?password=secret&format=flac HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+        assert!(resp.contains("Content-Type: audio/flac\r\n"));
+        assert!(resp.ends_with("\r\n\r\n"), "HEAD carries no body");
+
+        let resp = http_request(
+            addr,
+            "GET /stream/a.wav?password=secret&format=flac HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        let (header, body) = resp.split_once("\r\n\r\n").unwrap();
+        assert!(header.starts_with("HTTP/1.1 200 OK\r\n"), "{header}");
+        assert!(body.starts_with("fLaC"), "transcoded in-process");
+
+        for path in ["/stream/b.flac", "/stream/../a.wav", "/live"] {
+            let resp = http_request(
+                addr,
+                &format!("GET {path}?password=secret HTTP/1.1\r\n\r\n"),
+            )
+            .await;
+            assert!(
+                resp.starts_with("HTTP/1.1 404 Not Found\r\n"),
+                "{path}: {resp}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_beyond_the_limit_are_refused() {
+        let handle = spawn(AppState::new(), "127.0.0.1", 0, 96, 1).await.unwrap();
+        let addr = handle.local_addr();
+
+        // A client that has not sent its request yet holds the only slot.
+        let _idle = TcpStream::connect(addr).await.unwrap();
+        let resp = http_request(addr, "GET /live HTTP/1.1\r\n\r\n").await;
+        assert!(
+            resp.starts_with("HTTP/1.1 503 Service Unavailable\r\n"),
+            "{resp}"
+        );
+    }
+}
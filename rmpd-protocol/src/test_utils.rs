@@ -0,0 +1,277 @@
+//! Helpers for driving a real [`MpdServer`] over TCP from tests.
+//!
+//! Enabled with the `test-utils` feature. [`MpdTestServer`] binds an
+//! OS-assigned port and waits for the server's ready signal before returning,
+//! so tests never race the accept loop or collide on a fixed port;
+//! [`MpdTestClient`] speaks the line protocol and panics on I/O failures or
+//! timeouts.
+
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, oneshot};
+use tokio::time::{Duration, timeout};
+
+use crate::MpdServer;
+use crate::state::AppState;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A test MPD server bound to a random OS-assigned port.
+pub struct MpdTestServer {
+    addr: SocketAddr,
+    shutdown_tx: broadcast::Sender<()>,
+}
+
+impl MpdTestServer {
+    /// Start a server with default (empty) state.
+    pub async fn start() -> Self {
+        Self::start_with_state(AppState::new()).await
+    }
+
+    /// Start a server with pre-configured state.
+    pub async fn start_with_state(state: AppState) -> Self {
+        Self::start_configured(state, |server| server).await
+    }
+
+    /// Start a server with pre-configured state, letting the caller adjust
+    /// the `MpdServer` (limits, timeouts) before it starts listening.
+    ///
+    /// Returns once the server reports its bound address, so clients can
+    /// connect immediately.
+    pub async fn start_configured(
+        mut state: AppState,
+        configure: impl FnOnce(MpdServer) -> MpdServer,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind test listener");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel::<()>(1);
+        state.set_shutdown_sender(shutdown_tx.clone());
+
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let bind_address = listener.local_addr().expect("listener address").to_string();
+        let server = configure(MpdServer::with_state(bind_address, state, shutdown_rx))
+            .with_ready_signal(ready_tx);
+
+        tokio::spawn(async move {
+            let _ = server.run_with_listener(listener).await;
+        });
+
+        let addr = timeout(READ_TIMEOUT, ready_rx)
+            .await
+            .expect("server did not become ready")
+            .expect("server exited before becoming ready");
+
+        Self { addr, shutdown_tx }
+    }
+
+    /// Address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for MpdTestServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown_tx.send(());
+    }
+}
+
+/// Send one raw HTTP `request` to `addr` and read the response until the
+/// server closes the connection, for the HTTP endpoints.
+pub async fn http_request(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// A TCP client that speaks the MPD protocol.
+pub struct MpdTestClient {
+    reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+    writer: tokio::net::tcp::OwnedWriteHalf,
+}
+
+impl MpdTestClient {
+    /// Connect to a running test server and consume the greeting line.
+    pub async fn connect(port: u16) -> Self {
+        Self::connect_addr(SocketAddr::from(([127, 0, 0, 1], port))).await
+    }
+
+    /// Connect to a server at `addr` and consume the greeting line.
+    pub async fn connect_addr(addr: SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let (read_half, write_half) = stream.into_split();
+        let mut client = Self {
+            reader: BufReader::new(read_half),
+            writer: write_half,
+        };
+
+        // Read and validate greeting
+        let greeting = client.read_line().await;
+        assert!(
+            greeting.starts_with("OK MPD "),
+            "unexpected greeting: {greeting}"
+        );
+
+        client
+    }
+
+    /// Send a single command and read the full response (up to OK or ACK).
+    pub async fn command(&mut self, cmd: &str) -> String {
+        self.send_raw(&format!("{cmd}\n")).await;
+        self.read_response().await
+    }
+
+    /// Send a command list (command_list_begin / end) and read the response.
+    pub async fn command_list(&mut self, cmds: &[&str]) -> String {
+        let mut payload = String::from("command_list_begin\n");
+        for cmd in cmds {
+            payload.push_str(cmd);
+            payload.push('\n');
+        }
+        payload.push_str("command_list_end\n");
+        self.send_raw(&payload).await;
+        self.read_response().await
+    }
+
+    /// Send a command list with OK separators and read the response.
+    pub async fn command_list_ok(&mut self, cmds: &[&str]) -> String {
+        let mut payload = String::from("command_list_ok_begin\n");
+        for cmd in cmds {
+            payload.push_str(cmd);
+            payload.push('\n');
+        }
+        payload.push_str("command_list_end\n");
+        self.send_raw(&payload).await;
+        self.read_response().await
+    }
+
+    /// Send raw bytes to the server.
+    pub async fn send_raw(&mut self, data: &str) {
+        self.writer.write_all(data.as_bytes()).await.unwrap();
+        self.writer.flush().await.unwrap();
+    }
+
+    /// Send arbitrary bytes (not necessarily UTF-8) to the server.
+    pub async fn send_bytes(&mut self, data: &[u8]) {
+        self.writer.write_all(data).await.unwrap();
+        self.writer.flush().await.unwrap();
+    }
+
+    /// Read a single line (including the trailing newline).
+    pub async fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        timeout(READ_TIMEOUT, self.reader.read_line(&mut line))
+            .await
+            .expect("read_line timed out")
+            .expect("read_line IO error");
+        line
+    }
+
+    /// Wait for the server to close the connection. Returns `true` on EOF
+    /// (or reset), `false` if more data arrives first.
+    pub async fn is_closed(&mut self) -> bool {
+        let mut buf = [0u8; 1];
+        match timeout(READ_TIMEOUT, self.reader.read(&mut buf))
+            .await
+            .expect("is_closed timed out")
+        {
+            Ok(n) => n == 0,
+            Err(_) => true,
+        }
+    }
+
+    /// Read lines until we see `OK\n` or a line starting with `ACK`.
+    pub async fn read_response(&mut self) -> String {
+        let mut response = String::new();
+        loop {
+            let line = self.read_line().await;
+            if line.is_empty() {
+                // Connection closed unexpectedly.
+                break;
+            }
+            response.push_str(&line);
+            if line == "OK\n" || line.starts_with("ACK ") {
+                break;
+            }
+        }
+        response
+    }
+}
+
+/// One response to a binary command (`albumart`, `readpicture`), split into
+/// its text fields and raw payload. `data` is `None` when the response carried
+/// no `binary:` field (e.g. `readpicture` on a file without a picture).
+#[derive(Debug)]
+pub struct BinaryResponse {
+    pub fields: Vec<(String, String)>,
+    pub data: Option<Vec<u8>>,
+}
+
+impl BinaryResponse {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl MpdTestClient {
+    /// Send a binary command and read its response byte-exactly.
+    ///
+    /// Panics on any framing violation: the payload must be exactly the
+    /// advertised `binary: <n>` bytes, followed by a lone `\n` and `OK\n`.
+    /// Returns `Err` with the ACK line when the command fails.
+    pub async fn command_binary(&mut self, cmd: &str) -> Result<BinaryResponse, String> {
+        self.send_raw(&format!("{cmd}\n")).await;
+        let mut fields = Vec::new();
+        loop {
+            let line = self.read_line().await;
+            assert!(!line.is_empty(), "connection closed mid-response");
+            if line.starts_with("ACK ") {
+                return Err(line);
+            }
+            if line == "OK\n" {
+                return Ok(BinaryResponse { fields, data: None });
+            }
+            let (key, value) = line
+                .trim_end_matches('\n')
+                .split_once(": ")
+                .unwrap_or_else(|| panic!("malformed response line: {line:?}"));
+            if key == "binary" {
+                let len: usize = value.parse().expect("binary length");
+                let mut data = vec![0u8; len];
+                timeout(READ_TIMEOUT, self.reader.read_exact(&mut data))
+                    .await
+                    .expect("binary payload timed out")
+                    .expect("binary payload IO error");
+                assert_eq!(
+                    self.read_line().await,
+                    "\n",
+                    "payload must end with a newline"
+                );
+                assert_eq!(
+                    self.read_line().await,
+                    "OK\n",
+                    "binary response must end with OK"
+                );
+                return Ok(BinaryResponse {
+                    fields,
+                    data: Some(data),
+                });
+            }
+            fields.push((key.to_owned(), value.to_owned()));
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use rmpd_protocol::parser::{Command, DeleteTarget, MoveFrom};
+use rmpd_protocol::parser::{Command, DeleteTarget, MoveFrom, parse_command};
+use rmpd_protocol::registry;
 
 const PERMISSION_NONE: u8 = 0;
 const PERMISSION_READ: u8 = 1;
@@ -124,6 +125,7 @@ fn status_metadata() {
     check(&Command::Status, "status", PERMISSION_READ);
     check(&Command::CurrentSong, "currentsong", PERMISSION_READ);
     check(&Command::Stats, "stats", PERMISSION_READ);
+    check(&Command::DaemonStats, "daemonstats", PERMISSION_READ);
     check(&Command::ClearError, "clearerror", PERMISSION_CONTROL);
 }
 
@@ -243,6 +245,8 @@ fn connection_metadata() {
         "protocol",
         PERMISSION_NONE,
     );
+    check(&Command::Client { name: s("") }, "client", PERMISSION_NONE);
+    check(&Command::ListClients, "listclients", PERMISSION_ADMIN);
 }
 
 #[test]
@@ -275,6 +279,12 @@ fn database_metadata() {
         "rescan",
         PERMISSION_CONTROL,
     );
+    check(&Command::UpdatePreview, "updatepreview", PERMISSION_CONTROL);
+    check(
+        &Command::LibraryReport { uri: None },
+        "libraryreport",
+        PERMISSION_READ,
+    );
     check(
         &Command::Find {
             filters: vec![],
@@ -298,7 +308,7 @@ fn database_metadata() {
             tag: s(""),
             filter_tag: None,
             filter_value: None,
-            group: None,
+            groups: Vec::new(),
         },
         "list",
         PERMISSION_READ,
@@ -337,6 +347,28 @@ fn database_metadata() {
         "readcomments",
         PERMISSION_READ,
     );
+    check(
+        &Command::ReadLyrics { uri: s("") },
+        "readlyrics",
+        PERMISSION_READ,
+    );
+    check(
+        &Command::WriteTag {
+            uri: s(""),
+            tag: s(""),
+            value: s(""),
+        },
+        "writetag",
+        PERMISSION_ADMIN,
+    );
+    check(
+        &Command::ClearTag {
+            uri: s(""),
+            tag: s(""),
+        },
+        "cleartag",
+        PERMISSION_ADMIN,
+    );
 }
 
 #[test]
@@ -451,12 +483,32 @@ fn stored_playlists_metadata() {
     );
 }
 
+#[test]
+fn bookmark_metadata() {
+    check(
+        &Command::SaveBookmark { name: s("") },
+        "savebookmark",
+        PERMISSION_CONTROL,
+    );
+    check(
+        &Command::LoadBookmark { name: s("") },
+        "loadbookmark",
+        PERMISSION_CONTROL,
+    );
+    check(&Command::ListBookmarks, "listbookmarks", PERMISSION_READ);
+    check(
+        &Command::DeleteBookmark { name: s("") },
+        "deletebookmark",
+        PERMISSION_CONTROL,
+    );
+}
+
 #[test]
 fn idle_metadata() {
     check(
         &Command::Idle { subsystems: vec![] },
         "idle",
-        PERMISSION_NONE,
+        PERMISSION_READ,
     );
     check(&Command::NoIdle, "noidle", PERMISSION_NONE);
 }
@@ -492,13 +544,21 @@ fn output_control_metadata() {
 
 #[test]
 fn command_batching_metadata() {
-    check(&Command::CommandListBegin, "command_list", PERMISSION_NONE);
+    check(
+        &Command::CommandListBegin,
+        "command_list_begin",
+        PERMISSION_NONE,
+    );
     check(
         &Command::CommandListOkBegin,
-        "command_list",
+        "command_list_ok_begin",
+        PERMISSION_NONE,
+    );
+    check(
+        &Command::CommandListEnd,
+        "command_list_end",
         PERMISSION_NONE,
     );
-    check(&Command::CommandListEnd, "command_list", PERMISSION_NONE);
 }
 
 #[test]
@@ -594,14 +654,10 @@ fn sticker_metadata() {
         "sticker",
         PERMISSION_CONTROL,
     );
-    check(
-        &Command::StickerNames { uri: None },
-        "stickernames",
-        PERMISSION_READ,
-    );
+    check(&Command::StickerNames, "stickernames", PERMISSION_READ);
     check(&Command::StickerTypes, "stickertypes", PERMISSION_READ);
     check(
-        &Command::StickerNamesTypes { uri: None },
+        &Command::StickerNamesTypes { sticker_type: None },
         "stickernamestypes",
         PERMISSION_READ,
     );
@@ -694,10 +750,7 @@ fn advanced_queue_metadata() {
         PERMISSION_CONTROL,
     );
     check(
-        &Command::RangeId {
-            id: 0,
-            range: (0.0, 0.0),
-        },
+        &Command::RangeId { id: 0, range: None },
         "rangeid",
         PERMISSION_CONTROL,
     );
@@ -747,3 +800,76 @@ fn unknown_variants_metadata() {
         PERMISSION_NONE,
     );
 }
+
+#[test]
+fn command_registry_is_sorted_and_unique() {
+    let names: Vec<&str> = Command::COMMANDS.iter().map(|spec| spec.name).collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    assert_eq!(names, sorted);
+}
+
+#[test]
+fn command_registry_matches_variant_metadata() {
+    let perm = |name: &str| registry::lookup(name).map(|spec| spec.permission);
+    assert_eq!(perm("play"), Some(PERMISSION_CONTROL));
+    assert_eq!(perm("add"), Some(PERMISSION_ADD));
+    assert_eq!(perm("status"), Some(PERMISSION_READ));
+    assert_eq!(perm("idle"), Some(PERMISSION_READ));
+    assert_eq!(perm("config"), Some(PERMISSION_ADMIN));
+    assert_eq!(perm("sticker"), Some(PERMISSION_CONTROL));
+    assert_eq!(perm("password"), Some(PERMISSION_NONE));
+    assert_eq!(perm("unknown"), None);
+}
+
+#[test]
+fn command_registry_hides_pseudo_commands() {
+    for hidden in ["noidle", "command_list_begin", "command_list_end"] {
+        assert!(registry::lookup(hidden).is_some(), "{hidden} must parse");
+        assert!(
+            !registry::advertised().any(|spec| spec.name == hidden),
+            "{hidden} should not be advertised"
+        );
+    }
+}
+
+#[test]
+fn command_registry_arity() {
+    let spec = |name: &str| registry::lookup(name).unwrap();
+    assert_eq!(spec("status").check_arity(0), Ok(()));
+    assert_eq!(
+        spec("status").check_arity(1),
+        Err("wrong number of arguments for \"status\"".to_string())
+    );
+    assert_eq!(
+        spec("add").check_arity(0),
+        Err("too few arguments for \"add\"".to_string())
+    );
+    assert_eq!(
+        spec("add").check_arity(3),
+        Err("too many arguments for \"add\"".to_string())
+    );
+    assert_eq!(spec("find").check_arity(9), Ok(()));
+    assert_eq!(spec("close").check_arity(3), Ok(()));
+}
+
+#[test]
+fn parser_rejects_bad_arity_from_registry() {
+    assert_eq!(
+        parse_command("status extra"),
+        Err("wrong number of arguments for \"status\"".to_string())
+    );
+    assert_eq!(
+        parse_command("add"),
+        Err("too few arguments for \"add\"".to_string())
+    );
+    assert_eq!(
+        parse_command(r#"add "a b.flac" 1 2"#),
+        Err("too many arguments for \"add\"".to_string())
+    );
+    assert_eq!(
+        parse_command("frobnicate 1"),
+        Ok(Command::Unknown(s("frobnicate")))
+    );
+}
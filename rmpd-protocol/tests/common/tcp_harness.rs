@@ -1,156 +1,17 @@
 //! TCP-level test harness for MPD protocol conformance tests.
 //!
-//! Provides `MpdTestServer` (binds to port 0, spawns the real server) and
-//! `MpdTestClient` (connects via TCP, sends commands, validates responses).
+//! Re-exports `MpdTestServer` and `MpdTestClient` from
+//! `rmpd_protocol::test_utils` and adds response assertions plus setup
+//! helpers for common server configurations.
 //!
 // Shared across multiple test binaries; each uses only a subset of the helpers.
 #![allow(dead_code)]
 
 use rmpd_core::test_utils::make_test_song;
-use rmpd_protocol::MpdServer;
 use rmpd_protocol::state::AppState;
 use tempfile::TempDir;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
-use tokio::time::{Duration, timeout};
 
-const READ_TIMEOUT: Duration = Duration::from_secs(5);
-
-/// A test MPD server bound to a random OS-assigned port.
-pub struct MpdTestServer {
-    port: u16,
-    shutdown_tx: broadcast::Sender<()>,
-}
-
-impl MpdTestServer {
-    /// Start a server with default (empty) state.
-    pub async fn start() -> Self {
-        Self::start_with_state(AppState::new()).await
-    }
-
-    /// Start a server with pre-configured state.
-    pub async fn start_with_state(mut state: AppState) -> Self {
-        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-        let port = listener.local_addr().unwrap().port();
-
-        let (shutdown_tx, shutdown_rx) = broadcast::channel::<()>(1);
-        state.set_shutdown_sender(shutdown_tx.clone());
-
-        let server = MpdServer::with_state(format!("127.0.0.1:{port}"), state, shutdown_rx);
-
-        tokio::spawn(async move {
-            let _ = server.run_with_listener(listener).await;
-        });
-
-        // Give the server a moment to be ready.
-        tokio::time::sleep(Duration::from_millis(50)).await;
-
-        Self { port, shutdown_tx }
-    }
-
-    pub fn port(&self) -> u16 {
-        self.port
-    }
-}
-
-impl Drop for MpdTestServer {
-    fn drop(&mut self) {
-        let _ = self.shutdown_tx.send(());
-    }
-}
-
-/// A TCP client that speaks the MPD protocol.
-pub struct MpdTestClient {
-    reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
-    writer: tokio::net::tcp::OwnedWriteHalf,
-}
-
-impl MpdTestClient {
-    /// Connect to a running test server and consume the greeting line.
-    pub async fn connect(port: u16) -> Self {
-        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
-        stream.set_nodelay(true).unwrap();
-
-        let (read_half, write_half) = stream.into_split();
-        let mut client = Self {
-            reader: BufReader::new(read_half),
-            writer: write_half,
-        };
-
-        // Read and validate greeting
-        let greeting = client.read_line().await;
-        assert!(
-            greeting.starts_with("OK MPD "),
-            "unexpected greeting: {greeting}"
-        );
-
-        client
-    }
-
-    /// Send a single command and read the full response (up to OK or ACK).
-    pub async fn command(&mut self, cmd: &str) -> String {
-        self.send_raw(&format!("{cmd}\n")).await;
-        self.read_response().await
-    }
-
-    /// Send a command list (command_list_begin / end) and read the response.
-    pub async fn command_list(&mut self, cmds: &[&str]) -> String {
-        let mut payload = String::from("command_list_begin\n");
-        for cmd in cmds {
-            payload.push_str(cmd);
-            payload.push('\n');
-        }
-        payload.push_str("command_list_end\n");
-        self.send_raw(&payload).await;
-        self.read_response().await
-    }
-
-    /// Send a command list with OK separators and read the response.
-    pub async fn command_list_ok(&mut self, cmds: &[&str]) -> String {
-        let mut payload = String::from("command_list_ok_begin\n");
-        for cmd in cmds {
-            payload.push_str(cmd);
-            payload.push('\n');
-        }
-        payload.push_str("command_list_end\n");
-        self.send_raw(&payload).await;
-        self.read_response().await
-    }
-
-    /// Send raw bytes to the server.
-    pub async fn send_raw(&mut self, data: &str) {
-        self.writer.write_all(data.as_bytes()).await.unwrap();
-        self.writer.flush().await.unwrap();
-    }
-
-    /// Read a single line (including the trailing newline).
-    pub async fn read_line(&mut self) -> String {
-        let mut line = String::new();
-        timeout(READ_TIMEOUT, self.reader.read_line(&mut line))
-            .await
-            .expect("read_line timed out")
-            .expect("read_line IO error");
-        line
-    }
-
-    /// Read lines until we see `OK\n` or a line starting with `ACK`.
-    pub async fn read_response(&mut self) -> String {
-        let mut response = String::new();
-        loop {
-            let line = self.read_line().await;
-            if line.is_empty() {
-                // Connection closed unexpectedly.
-                break;
-            }
-            response.push_str(&line);
-            if line == "OK\n" || line.starts_with("ACK ") {
-                break;
-            }
-        }
-        response
-    }
-}
+pub use rmpd_protocol::test_utils::{BinaryResponse, MpdTestClient, MpdTestServer};
 
 // ── Static assertion helpers ─────────────────────────────────────────
 
@@ -0,0 +1,178 @@
+//! MPD compatibility suite: replays client sessions against an in-process
+//! server and diffs every response against the output MPD gives.
+//!
+//! Sessions live in `tests/traces/*.trace`, one per client, modelled on the
+//! handshakes and everyday commands of mpc, ncmpcpp and Cantata. The format is
+//! line based:
+//!
+//! - `# ...` and blank lines are ignored;
+//! - `C: <line>` is sent to the server verbatim;
+//! - `S: <line>` is the next expected response line; `S: key: *` accepts any
+//!   value for `key`;
+//! - `S* key: *` accepts zero or more consecutive `key: ...` lines (used for
+//!   lists such as `commands` whose exact contents are covered elsewhere).
+//!
+//! Consecutive `C:` lines form one request (so a command list is written out
+//! line by line) and the following `S` lines describe its complete response,
+//! up to and including the final `OK` or `ACK`.
+//!
+//! Run with: cargo test --test compat_traces
+
+use std::path::Path;
+
+use rmpd_protocol::test_utils::{MpdTestClient, MpdTestServer};
+
+/// One expected response line.
+#[derive(Debug)]
+enum Expect {
+    Exact(String),
+    AnyValue(String),
+    Repeated(String),
+}
+
+impl Expect {
+    fn matches(&self, line: &str) -> bool {
+        match self {
+            Expect::Exact(expected) => line == expected,
+            Expect::AnyValue(key) | Expect::Repeated(key) => line
+                .strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.starts_with(": ")),
+        }
+    }
+}
+
+/// A request (one or more protocol lines) and its expected response.
+#[derive(Debug, Default)]
+struct Exchange {
+    line_no: usize,
+    send: Vec<String>,
+    expect: Vec<Expect>,
+}
+
+fn parse_trace(text: &str) -> Vec<Exchange> {
+    let mut exchanges: Vec<Exchange> = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        if raw.trim().is_empty() || raw.starts_with('#') {
+            continue;
+        }
+        if let Some(cmd) = raw.strip_prefix("C: ") {
+            match exchanges.last_mut() {
+                Some(ex) if ex.expect.is_empty() => ex.send.push(cmd.to_owned()),
+                _ => exchanges.push(Exchange {
+                    line_no,
+                    send: vec![cmd.to_owned()],
+                    expect: Vec::new(),
+                }),
+            }
+            continue;
+        }
+        let expect = if let Some(pattern) = raw.strip_prefix("S* ") {
+            let key = pattern
+                .strip_suffix(": *")
+                .unwrap_or_else(|| panic!("line {line_no}: `S*` needs a `key: *` pattern"));
+            Expect::Repeated(key.to_owned())
+        } else if let Some(line) = raw.strip_prefix("S: ") {
+            match line.strip_suffix(": *") {
+                Some(key) => Expect::AnyValue(key.to_owned()),
+                None => Expect::Exact(line.to_owned()),
+            }
+        } else {
+            panic!("line {line_no}: unrecognised trace line {raw:?}");
+        };
+        exchanges
+            .last_mut()
+            .unwrap_or_else(|| panic!("line {line_no}: response before any request"))
+            .expect
+            .push(expect);
+    }
+    exchanges
+}
+
+/// Match `actual` against `expect`, returning a description of the first
+/// difference.
+fn diff_response(expect: &[Expect], actual: &[&str]) -> Result<(), String> {
+    let mut pos = 0;
+    for pattern in expect {
+        if let Expect::Repeated(_) = pattern {
+            while pos < actual.len() && pattern.matches(actual[pos]) {
+                pos += 1;
+            }
+            continue;
+        }
+        match actual.get(pos) {
+            Some(line) if pattern.matches(line) => pos += 1,
+            Some(line) => return Err(format!("expected {pattern:?}, got {line:?}")),
+            None => return Err(format!("expected {pattern:?}, response ended")),
+        }
+    }
+    match actual.get(pos) {
+        Some(extra) => Err(format!("unexpected trailing line {extra:?}")),
+        None => Ok(()),
+    }
+}
+
+async fn replay(name: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/traces")
+        .join(format!("{name}.trace"));
+    let text = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("reading {}: {e}", path.display()));
+
+    let server = MpdTestServer::start().await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+
+    for exchange in parse_trace(&text) {
+        let mut request = exchange.send.join("\n");
+        request.push('\n');
+        client.send_raw(&request).await;
+        let response = client.read_response().await;
+        let lines: Vec<&str> = response.lines().collect();
+        if let Err(diff) = diff_response(&exchange.expect, &lines) {
+            panic!(
+                "{name}.trace:{}: {:?}\n{diff}\nfull response:\n{response}",
+                exchange.line_no, exchange.send
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn mpc_session() {
+    replay("mpc").await;
+}
+
+#[tokio::test]
+async fn ncmpcpp_session() {
+    replay("ncmpcpp").await;
+}
+
+#[tokio::test]
+async fn cantata_session() {
+    replay("cantata").await;
+}
+
+#[test]
+fn trace_parser_groups_command_lists() {
+    let exchanges = parse_trace(
+        "# comment\nC: command_list_begin\nC: ping\nC: command_list_end\nS: OK\n\nC: ping\nS: OK\n",
+    );
+    assert_eq!(exchanges.len(), 2);
+    assert_eq!(exchanges[0].send.len(), 3);
+    assert_eq!(exchanges[0].line_no, 2);
+    assert_eq!(exchanges[1].send, vec!["ping".to_owned()]);
+}
+
+#[test]
+fn diff_reports_mismatches() {
+    let expect = [
+        Expect::Repeated("command".to_owned()),
+        Expect::AnyValue("volume".to_owned()),
+        Expect::Exact("OK".to_owned()),
+    ];
+    assert!(diff_response(&expect, &["command: a", "command: b", "volume: 5", "OK"]).is_ok());
+    assert!(diff_response(&expect, &["volume: -1", "OK"]).is_ok());
+    assert!(diff_response(&expect, &["volume: 5", "state: stop", "OK"]).is_err());
+    assert!(diff_response(&expect, &["volume: 5"]).is_err());
+    assert!(diff_response(&expect, &["volume: 5", "OK", "OK"]).is_err());
+}
@@ -0,0 +1,221 @@
+//! Byte-level framing of the binary commands (`albumart`, `readpicture`).
+//!
+//! Mirrors what libmpdclient expects: `size:`, an optional `type:` (readpicture
+//! only), `binary: <n>`, exactly `n` raw bytes, a newline and `OK`. Chunks are
+//! at most 8192 bytes (MPD's default `binarylimit`) unless the client sets
+//! another limit.
+
+use crate::tcp_harness::*;
+use rmpd_protocol::state::AppState;
+use tempfile::TempDir;
+
+const SONG: &str = "music/song1.flac";
+const CHUNK: usize = 8192;
+
+/// Artwork that would break a line-oriented reader: newlines, `OK\n`, `ACK`
+/// and non-UTF-8 bytes all appear in the payload.
+fn tricky_art(len: usize) -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    let filler = b"\nOK\nACK [5@0] {x} y\n\xff\xfe\x00";
+    while data.len() < len {
+        data.extend_from_slice(filler);
+    }
+    data.truncate(len);
+    data
+}
+
+/// The same `art` both as `SONG`'s embedded picture (`readpicture`) and as
+/// the cover file of its directory (`albumart`).
+async fn setup_with_art(len: usize) -> (MpdTestServer, MpdTestClient, TempDir, Vec<u8>) {
+    let (server, client, tmp) = setup_with_db(2).await;
+    let art = tricky_art(len);
+    let db_path = tmp.path().join("test.db");
+    let db = rmpd_library::Database::open(db_path.to_str().unwrap()).unwrap();
+    db.store_artwork(SONG, "front", "image/png", &art, "test")
+        .unwrap();
+    let song_dir = tmp.path().join("music").join("music");
+    std::fs::create_dir_all(&song_dir).unwrap();
+    std::fs::write(song_dir.join("cover.png"), &art).unwrap();
+    (server, client, tmp, art)
+}
+
+#[tokio::test]
+async fn albumart_chunks_reassemble_byte_for_byte() {
+    let (_server, mut client, _tmp, art) = setup_with_art(3 * CHUNK + 123).await;
+
+    let mut received = Vec::new();
+    while received.len() < art.len() {
+        let resp = client
+            .command_binary(&format!("albumart {SONG} {}", received.len()))
+            .await
+            .expect("albumart chunk");
+        assert_eq!(resp.field("size"), Some(art.len().to_string().as_str()));
+        assert_eq!(resp.field("type"), None, "MPD's albumart sends no type");
+        let chunk = resp.data.expect("binary payload");
+        assert_eq!(chunk.len(), CHUNK.min(art.len() - received.len()));
+        received.extend_from_slice(&chunk);
+    }
+    assert_eq!(received, art);
+
+    // The connection is still in sync after the binary exchanges.
+    assert_ok(&client.command("ping").await);
+}
+
+#[tokio::test]
+async fn binarylimit_sets_chunk_size() {
+    let (_server, mut client, _tmp, art) = setup_with_art(3 * CHUNK).await;
+
+    assert_ok(&client.command("binarylimit 1000").await);
+    for cmd in ["albumart", "readpicture"] {
+        let mut received = Vec::new();
+        while received.len() < art.len() {
+            let resp = client
+                .command_binary(&format!("{cmd} {SONG} {}", received.len()))
+                .await
+                .expect("chunk");
+            assert_eq!(resp.field("size"), Some(art.len().to_string().as_str()));
+            let chunk = resp.data.expect("binary payload");
+            assert_eq!(chunk.len(), 1000.min(art.len() - received.len()));
+            received.extend_from_slice(&chunk);
+        }
+        assert_eq!(received, art);
+    }
+
+    // A limit above the image size sends it in one piece.
+    assert_ok(&client.command("binarylimit 1048576").await);
+    let resp = client
+        .command_binary(&format!("albumart {SONG} 0"))
+        .await
+        .expect("whole image");
+    assert_eq!(resp.data.as_deref(), Some(art.as_slice()));
+
+    let resp = client.command("binarylimit 10").await;
+    assert!(resp.starts_with("ACK [2@0] {binarylimit}"), "{resp}");
+}
+
+#[tokio::test]
+async fn binarylimit_must_fit_the_output_buffer() {
+    let server =
+        MpdTestServer::start_configured(AppState::new(), |s| s.with_max_output_buffer_size(65536))
+            .await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+
+    assert_ok(&client.command("binarylimit 61440").await);
+    let resp = client.command("binarylimit 61441").await;
+    assert_eq!(resp, "ACK [2@0] {binarylimit} Value too large\n");
+    // The connection stays usable.
+    assert_ok(&client.command("ping").await);
+}
+
+#[tokio::test]
+async fn readpicture_sends_size_type_binary_in_order() {
+    let (_server, mut client, _tmp, art) = setup_with_art(100).await;
+
+    let resp = client
+        .command_binary(&format!("readpicture {SONG} 0"))
+        .await
+        .expect("readpicture");
+    let keys: Vec<&str> = resp.fields.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, ["size", "type"]);
+    assert_eq!(resp.field("type"), Some("image/png"));
+    assert_eq!(resp.data.as_deref(), Some(art.as_slice()));
+}
+
+#[tokio::test]
+async fn offset_at_end_returns_empty_chunk() {
+    let (_server, mut client, _tmp, art) = setup_with_art(CHUNK).await;
+
+    let resp = client
+        .command_binary(&format!("albumart {SONG} {}", art.len()))
+        .await
+        .expect("offset == size is valid");
+    assert_eq!(resp.field("size"), Some(art.len().to_string().as_str()));
+    assert_eq!(resp.data.as_deref(), Some(&[][..]));
+}
+
+#[tokio::test]
+async fn offset_past_end_is_rejected() {
+    let (_server, mut client, _tmp, art) = setup_with_art(500).await;
+
+    for cmd in ["albumart", "readpicture"] {
+        let err = client
+            .command_binary(&format!("{cmd} {SONG} {}", art.len() + 1))
+            .await
+            .expect_err("offset past the end must fail");
+        assert_eq!(err, format!("ACK [2@0] {{{cmd}}} Offset too large\n"));
+    }
+    assert_ok(&client.command("ping").await);
+}
+
+#[tokio::test]
+async fn albumart_without_art_is_no_exist() {
+    let (_server, mut client, tmp, art) = setup_with_art(10).await;
+    std::fs::remove_file(tmp.path().join("music/music/cover.png")).unwrap();
+
+    let err = client
+        .command_binary(&format!("albumart {SONG} 0"))
+        .await
+        .expect_err("no cover file");
+    assert!(err.starts_with("ACK [50@0] {albumart}"), "got: {err}");
+
+    // The embedded picture is still there, for `readpicture`.
+    let resp = client
+        .command_binary(&format!("readpicture {SONG} 0"))
+        .await
+        .expect("embedded picture");
+    assert_eq!(resp.data.as_deref(), Some(art.as_slice()));
+}
+
+#[tokio::test]
+async fn albumart_song_uses_preferred_cover_file() {
+    let (_server, mut client, tmp, _art) = setup_with_art(10).await;
+    let song_dir = tmp.path().join("music/music");
+    std::fs::remove_file(song_dir.join("cover.png")).unwrap();
+    let folder = tricky_art(100);
+    std::fs::write(song_dir.join("front.png"), tricky_art(50)).unwrap();
+    std::fs::write(song_dir.join("Folder.JPG"), &folder).unwrap();
+
+    let resp = client
+        .command_binary(&format!("albumart {SONG} 0"))
+        .await
+        .expect("cover file");
+    assert_eq!(resp.field("size"), Some("100"));
+    assert_eq!(resp.data.as_deref(), Some(folder.as_slice()));
+}
+
+#[tokio::test]
+async fn albumart_directory_prefers_cover_file() {
+    let (_server, mut client, tmp, _art) = setup_with_art(10).await;
+    let album = tmp.path().join("music").join("Album");
+    std::fs::create_dir_all(&album).unwrap();
+    let cover = tricky_art(CHUNK + 1);
+    std::fs::write(album.join("cover.jpg"), &cover).unwrap();
+
+    for uri in ["Album/", "Album"] {
+        let resp = client
+            .command_binary(&format!("albumart {uri} 0"))
+            .await
+            .expect("directory cover");
+        assert_eq!(resp.field("size"), Some(cover.len().to_string().as_str()));
+        assert_eq!(resp.data.as_deref(), Some(&cover[..CHUNK]));
+    }
+}
+
+#[tokio::test]
+async fn albumart_directory_falls_back_to_song_art() {
+    // `music/song1.flac` lives in the `music` directory and has cached art.
+    let (_server, mut client, tmp, art) = setup_with_art(64).await;
+    std::fs::remove_file(tmp.path().join("music/music/cover.png")).unwrap();
+
+    let resp = client
+        .command_binary("albumart music/ 0")
+        .await
+        .expect("art of a song in the directory");
+    assert_eq!(resp.data.as_deref(), Some(art.as_slice()));
+
+    let err = client
+        .command_binary("albumart NoSuchDir/ 0")
+        .await
+        .expect_err("unknown directory");
+    assert!(err.starts_with("ACK [50@0] {albumart}"), "got: {err}");
+}
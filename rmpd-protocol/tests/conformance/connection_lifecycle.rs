@@ -65,6 +65,29 @@ async fn unknown_command_returns_ack() {
     assert!(resp.starts_with("ACK "), "expected ACK for unknown command");
 }
 
+#[tokio::test]
+async fn http_request_is_refused_and_closed() {
+    let (_server, mut client) = setup().await;
+    client
+        .send_raw("GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+
+    assert_eq!(client.read_line().await, "HTTP/1.0 400 Bad Request\r\n");
+    let mut rest = String::new();
+    loop {
+        let line = client.read_line().await;
+        if line.is_empty() {
+            break;
+        }
+        rest.push_str(&line);
+    }
+    assert!(rest.contains("not an HTTP server"), "{rest}");
+    assert!(
+        !rest.contains("ACK"),
+        "headers must not be run as commands: {rest}"
+    );
+}
+
 #[tokio::test]
 async fn concurrent_clients() {
     let server = MpdTestServer::start().await;
@@ -115,6 +138,90 @@ async fn password_accepted() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn default_permissions_make_a_read_only_endpoint() {
+    use rmpd_protocol::connection::{PERMISSION_ADD, PERMISSION_READ};
+
+    let mut state = rmpd_protocol::AppState::new();
+    state.set_password(Some("owner".to_owned()));
+    state.set_default_permissions(Some(PERMISSION_READ | PERMISSION_ADD));
+    let (server, mut client) = setup_with_state(state).await;
+
+    assert_ok(&client.command("status").await);
+    // Guests may queue songs (add) but not remove them or control playback.
+    let resp = client.command("clear").await;
+    assert!(resp.starts_with("ACK [4@0] {clear}"), "{resp}");
+    let resp = client.command("play").await;
+    assert!(resp.starts_with("ACK [4@0] {play}"), "{resp}");
+    let resp = client.command("password guess").await;
+    assert!(resp.starts_with("ACK [3@0] {password}"), "{resp}");
+
+    let mut owner = MpdTestClient::connect(server.port()).await;
+    assert_ok(&owner.command("password owner").await);
+    assert_ok(&owner.command("clear").await);
+
+    // The guest is still limited.
+    let resp = client.command("clear").await;
+    assert!(resp.starts_with("ACK [4@0]"), "{resp}");
+}
+
+#[tokio::test]
+async fn password_permissions_limit_what_it_grants() {
+    use rmpd_protocol::connection::PERMISSION_NONE;
+
+    let mut state = rmpd_protocol::AppState::new();
+    state.set_password(Some("dj@read,add,control".to_owned()));
+    state.set_default_permissions(Some(PERMISSION_NONE));
+    let (_server, mut client) = setup_with_state(state).await;
+
+    let resp = client.command("status").await;
+    assert!(resp.starts_with("ACK [4@0] {status}"), "{resp}");
+    assert_ok(&client.command("password dj").await);
+    assert_ok(&client.command("status").await);
+    assert_ok(&client.command("clear").await);
+    let resp = client.command("disableoutput 0").await;
+    assert!(resp.starts_with("ACK [4@0] {disableoutput}"), "{resp}");
+}
+
+#[tokio::test]
+async fn named_client_is_listed_and_resumes_its_tagtypes() {
+    let (server, mut admin) = setup().await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+    assert_ok(&client.command("client phone").await);
+    assert_ok(&client.command("tagtypes clear").await);
+    assert_ok(&client.command("tagtypes enable Artist").await);
+
+    let resp = admin.command("listclients").await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("client: ").count(), 2, "{resp}");
+    assert!(resp.contains("name: phone\n"), "{resp}");
+    assert!(resp.contains("address: 127.0.0.1:"), "{resp}");
+
+    client.send_raw("close\n").await;
+    drop(client);
+    // Wait for the server to notice the disconnect.
+    for _ in 0..50 {
+        if admin
+            .command("listclients")
+            .await
+            .matches("client: ")
+            .count()
+            == 1
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut resumed = MpdTestClient::connect(server.port()).await;
+    assert_ok(&resumed.command("client phone").await);
+    assert_eq!(resumed.command("tagtypes").await, "tagtype: Artist\nOK\n");
+
+    // An unnamed client keeps the defaults.
+    let mut other = MpdTestClient::connect(server.port()).await;
+    assert!(other.command("tagtypes").await.contains("tagtype: Album\n"));
+}
+
 #[tokio::test]
 async fn rapid_connect_disconnect() {
     let server = MpdTestServer::start().await;
@@ -137,3 +244,56 @@ async fn rapid_connect_disconnect() {
     let resp = client.command("ping").await;
     assert_ok(&resp);
 }
+
+#[tokio::test]
+async fn silent_clients_are_pruned_but_idle_ones_are_kept() {
+    let server = MpdTestServer::start_configured(rmpd_protocol::AppState::new(), |s| {
+        s.with_connection_timeout(Duration::from_millis(200))
+            .with_tcp_keepalive(Some(Duration::from_secs(1)))
+    })
+    .await;
+    let mut silent = MpdTestClient::connect(server.port()).await;
+    let mut idle = MpdTestClient::connect(server.port()).await;
+    idle.send_raw("idle\n").await;
+
+    assert!(silent.is_closed().await);
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(idle.command("noidle").await, "OK\n");
+    assert_ok(&idle.command("ping").await);
+}
+
+#[tokio::test]
+async fn local_clients_get_local_permissions_and_are_named_by_uid() {
+    use rmpd_protocol::connection::{PERMISSION_ALL, PERMISSION_NONE};
+    use tokio::net::UnixStream;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let socket = tmp.path().join("rmpd.sock");
+    let mut state = rmpd_protocol::AppState::new();
+    state.set_password(Some("owner".to_owned()));
+    state.set_default_permissions(Some(PERMISSION_NONE));
+    state.set_local_permissions(Some(PERMISSION_ALL));
+    let path = socket.to_str().unwrap().to_owned();
+    let server = MpdTestServer::start_configured(state, |s| s.with_unix_socket(Some(path))).await;
+
+    let (read_half, mut write_half) = UnixStream::connect(&socket).await.unwrap().into_split();
+    let mut reader = BufReader::new(read_half);
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert!(line.starts_with("OK MPD "));
+    write_half.write_all(b"clear\n").await.unwrap();
+    line.clear();
+    reader.read_line(&mut line).await.unwrap();
+    assert_eq!(line, "OK\n");
+
+    // Remote clients still need the password.
+    let mut remote = MpdTestClient::connect(server.port()).await;
+    let resp = remote.command("clear").await;
+    assert!(resp.starts_with("ACK [4@0] {clear}"), "{resp}");
+
+    let (ours, _) = UnixStream::pair().unwrap();
+    let uid = ours.peer_cred().unwrap().uid();
+    assert_ok(&remote.command("password owner").await);
+    let resp = remote.command("listclients").await;
+    assert!(resp.contains(&format!("address: unix:{uid}\n")), "{resp}");
+}
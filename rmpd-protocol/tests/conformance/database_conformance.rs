@@ -18,6 +18,117 @@ async fn lsinfo_root_with_db() {
     assert!(resp.ends_with("OK\n"), "lsinfo should succeed: {resp}");
 }
 
+#[tokio::test]
+async fn library_without_music_directory_degrades_gracefully() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let db_path = tmp.path().join("test.db").to_str().unwrap().to_string();
+    let state = rmpd_protocol::AppState::with_library(
+        db_path,
+        None,
+        tmp.path().to_str().unwrap().to_string(),
+    );
+    let (_server, mut client) = setup_with_state(state.clone()).await;
+
+    // The library is empty, not an error.
+    let resp = client.command("lsinfo").await;
+    assert_ok(&resp);
+    assert!(!resp.contains("file:"), "{resp}");
+
+    let resp = client.command("update").await;
+    assert!(
+        resp.starts_with("ACK [52@0] {update} music directory not configured"),
+        "{resp}"
+    );
+    assert!(
+        resp.contains("reload"),
+        "ACK should say how to fix it: {resp}"
+    );
+
+    // A config reload sets the directory for every connection.
+    let music = tmp.path().join("music");
+    std::fs::create_dir_all(&music).unwrap();
+    state.set_music_dir(Some(music.to_str().unwrap().to_string()));
+    let resp = client.command("update").await;
+    assert!(resp.contains("updating_db:"), "{resp}");
+}
+
+#[tokio::test]
+async fn lsinfo_listing_is_cached_until_database_changes() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let db_path = tmp.path().join("test.db").to_str().unwrap().to_string();
+    let db = rmpd_library::Database::open(&db_path).unwrap();
+    for i in 1..=2 {
+        db.add_song(&rmpd_core::test_utils::make_test_song(
+            &format!("music/song{i}.flac"),
+            i,
+        ))
+        .unwrap();
+    }
+    let state = rmpd_protocol::AppState::with_paths(
+        db_path,
+        tmp.path().join("music").to_str().unwrap().to_string(),
+    );
+    let (_server, mut client) = setup_with_state(state.clone()).await;
+
+    let resp = client.command("lsinfo music").await;
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+
+    // Written behind the server's back: the cached listing is still served.
+    db.add_song(&rmpd_core::test_utils::make_test_song(
+        "music/song3.flac",
+        3,
+    ))
+    .unwrap();
+    let resp = client.command("lsinfo music").await;
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+
+    state
+        .event_bus
+        .emit(rmpd_core::event::Event::DatabaseChanged);
+    let resp = client.command("lsinfo music").await;
+    assert_eq!(resp.matches("file:").count(), 3, "{resp}");
+}
+
+#[tokio::test]
+async fn search_results_are_cached_until_database_changes() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let db_path = tmp.path().join("test.db").to_str().unwrap().to_string();
+    let db = rmpd_library::Database::open(&db_path).unwrap();
+    db.add_song(&rmpd_core::test_utils::make_test_song(
+        "music/song1.flac",
+        1,
+    ))
+    .unwrap();
+    let state = rmpd_protocol::AppState::with_paths(
+        db_path,
+        tmp.path().join("music").to_str().unwrap().to_string(),
+    );
+    let (_server, mut client) = setup_with_state(state.clone()).await;
+
+    let resp = client.command("search artist \"test artist\"").await;
+    assert_eq!(resp.matches("file:").count(), 1, "{resp}");
+
+    db.add_song(&rmpd_core::test_utils::make_test_song(
+        "music/song2.flac",
+        2,
+    ))
+    .unwrap();
+    // Tag names are case-insensitive, so this is the same cached query.
+    let resp = client.command("search Artist \"test artist\"").await;
+    assert_eq!(resp.matches("file:").count(), 1, "{resp}");
+    // A different window is a different query.
+    let resp = client
+        .command("search artist \"test artist\" window 0:10")
+        .await;
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+
+    state
+        .event_bus
+        .emit(rmpd_core::event::Event::DatabaseChanged);
+    let resp = client.command("search artist \"test artist\"").await;
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+}
+
 #[tokio::test]
 async fn find_by_artist() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
@@ -43,6 +154,40 @@ async fn list_artists() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn list_nests_multiple_groups() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let db_path = tmp.path().join("test.db").to_str().unwrap().to_string();
+    let db = rmpd_library::Database::open(&db_path).unwrap();
+    for (i, (album, artist, date)) in [("A", "X", "2020"), ("B", "X", "2020"), ("C", "Y", "2019")]
+        .into_iter()
+        .enumerate()
+    {
+        let mut song =
+            rmpd_core::test_utils::make_test_song(&format!("music/song{i}.flac"), i as u32 + 1);
+        song.tags
+            .retain(|(key, _)| !matches!(key.as_ref(), "album" | "artist" | "date"));
+        for (key, value) in [("album", album), ("artist", artist), ("date", date)] {
+            song.tags
+                .push((rmpd_core::song::intern_tag_key(key), value.to_owned()));
+        }
+        db.add_song(&song).unwrap();
+    }
+    let state = rmpd_protocol::AppState::with_paths(
+        db_path,
+        tmp.path().join("music").to_str().unwrap().to_string(),
+    );
+    let (_server, mut client) = setup_with_state(state).await;
+
+    // The last group is the outermost, as in MPD.
+    let resp = client.command("list album group artist group date").await;
+    assert_eq!(
+        resp,
+        "Date: 2019\nArtist: Y\nAlbum: C\n\
+         Date: 2020\nArtist: X\nAlbum: A\nAlbum: B\nOK\n"
+    );
+}
+
 #[tokio::test]
 async fn count_songs() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
@@ -74,9 +219,71 @@ async fn update_command() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn updatepreview_lists_changes_without_applying_them() {
+    let (_server, mut client, tmp) = setup_with_db(2).await;
+    // The fixture songs have no files on disk; one new file does.
+    std::fs::write(tmp.path().join("music/new.flac"), b"").unwrap();
+
+    let resp = client.command("updatepreview").await;
+    assert_eq!(
+        resp,
+        "added: new.flac\nremoved: music/song1.flac\nremoved: music/song2.flac\nOK\n"
+    );
+
+    let resp = client.command("stats").await;
+    assert_eq!(get_field(&resp, "songs"), Some("2"));
+}
+
 #[tokio::test]
 async fn listfiles_root() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
     let resp = client.command("listfiles").await;
     assert!(resp.ends_with("OK\n") || resp.starts_with("ACK "));
 }
+
+#[tokio::test]
+async fn uri_commands_stay_inside_music_dir() {
+    let (_server, mut client, tmp) = setup_with_db(1).await;
+    std::fs::write(tmp.path().join("secret.flac"), b"").unwrap();
+    std::os::unix::fs::symlink(
+        tmp.path().join("secret.flac"),
+        tmp.path().join("music/link.flac"),
+    )
+    .unwrap();
+
+    for cmd in ["add", "readcomments", "listfiles", "getfingerprint"] {
+        let resp = client.command(&format!("{cmd} ../secret.flac")).await;
+        assert_eq!(resp, format!("ACK [2@0] {{{cmd}}} Malformed path\n"));
+    }
+    let resp = client.command("readcomments /etc/passwd").await;
+    assert_eq!(resp, "ACK [4@0] {readcomments} Access denied\n");
+    // A symlink does not lead out of the library unless follow_symlinks is set.
+    let resp = client.command("readcomments link.flac").await;
+    assert_eq!(resp, "ACK [4@0] {readcomments} Access denied\n");
+    let err = client
+        .command_binary("albumart ../secret.flac 0")
+        .await
+        .expect_err("traversal");
+    assert!(err.starts_with("ACK [2@0] {albumart}"), "got: {err}");
+}
+
+#[tokio::test]
+async fn libraryreport_groups_by_format() {
+    let (_server, mut client, tmp) = setup_with_db(2).await;
+    std::fs::create_dir_all(tmp.path().join("music/music")).unwrap();
+    std::fs::write(tmp.path().join("music/music/song1.flac"), [0u8; 100]).unwrap();
+
+    let expected = "songs: 2\nplaytime: 360\nsize: 100\n\
+        Quality: lossless\nsongs: 2\nplaytime: 360\n\
+        Quality: lossy\nsongs: 0\nplaytime: 0\n\
+        Format: flac\nsongs: 2\nplaytime: 360\n\
+        SampleRate: 44100\nsongs: 2\nplaytime: 360\n\
+        BitDepth: 16\nsongs: 2\nplaytime: 360\nOK\n";
+    assert_eq!(client.command("libraryreport").await, expected);
+    assert_eq!(client.command("libraryreport music").await, expected);
+
+    // A prefix of a directory name is not that directory.
+    let resp = client.command("libraryreport mus").await;
+    assert_eq!(resp, "ACK [50@0] {libraryreport} No such directory\n");
+}
@@ -1,5 +1,6 @@
 //! Extended database command conformance tests.
-//! Tests findadd, searchadd, searchcount, and rescan.
+//! Tests findadd, searchadd, searchcount, rescan, readlyrics and
+//! writetag/cleartag.
 
 use crate::tcp_harness::*;
 
@@ -136,3 +137,131 @@ async fn rescan_returns_updating_db() {
         "rescan should return updating_db field: {resp}"
     );
 }
+
+#[tokio::test]
+async fn readlyrics_returns_stored_lines() {
+    let (_server, mut client, tmp) = setup_with_db(2).await;
+
+    let db = rmpd_library::Database::open(tmp.path().join("test.db").to_str().unwrap()).unwrap();
+    db.store_lyrics(
+        "music/song1.flac",
+        &rmpd_library::Lyrics {
+            text: "[00:01.00]First\n\n[00:02.50]Second".to_string(),
+            synced: true,
+            source: rmpd_library::LyricsSource::Sidecar,
+            mtime: 0,
+        },
+    )
+    .unwrap();
+
+    let resp = client.command("readlyrics \"music/song1.flac\"").await;
+    assert_ok(&resp);
+    assert_eq!(
+        resp,
+        "source: sidecar\nsynced: 1\nline: [00:01.00]First\nline: \nline: [00:02.50]Second\nOK\n"
+    );
+}
+
+#[tokio::test]
+async fn readlyrics_errors() {
+    let (_server, mut client, _tmp) = setup_with_db(2).await;
+
+    let resp = client.command("readlyrics \"music/song2.flac\"").await;
+    assert!(resp.starts_with("ACK [50@0] {readlyrics}"), "{resp}");
+
+    let resp = client.command("readlyrics \"music/missing.flac\"").await;
+    assert!(
+        resp.starts_with("ACK [50@0] {readlyrics} No such song"),
+        "{resp}"
+    );
+}
+
+/// A server with tag editing enabled over a library holding a copy of the
+/// library crate's `basic.flac` fixture as `basic.flac`.
+async fn setup_tag_editing() -> (MpdTestServer, MpdTestClient, tempfile::TempDir) {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let music_dir = tmp.path().join("music");
+    std::fs::create_dir_all(&music_dir).unwrap();
+    let fixture = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../rmpd-library/tests/fixtures/samples/basic.flac");
+    std::fs::copy(fixture, music_dir.join("basic.flac")).unwrap();
+
+    let db_path = tmp.path().join("test.db").to_str().unwrap().to_string();
+    let db = rmpd_library::Database::open(&db_path).unwrap();
+    let mut song = rmpd_library::MetadataExtractor::extract_from_file(
+        &camino::Utf8PathBuf::from_path_buf(music_dir.join("basic.flac")).unwrap(),
+    )
+    .unwrap();
+    song.path = "basic.flac".into();
+    db.add_song(&song).unwrap();
+
+    let mut state =
+        rmpd_protocol::AppState::with_paths(db_path, music_dir.to_str().unwrap().to_string());
+    state.set_tag_editing(true);
+    let (server, client) = setup_with_state(state).await;
+    (server, client, tmp)
+}
+
+#[tokio::test]
+async fn writetag_is_disabled_by_default() {
+    let (_server, mut client, _tmp) = setup_with_db(1).await;
+
+    let resp = client
+        .command("writetag \"music/song1.flac\" title \"New\"")
+        .await;
+    assert_eq!(resp, "ACK [4@0] {writetag} tag editing is disabled\n");
+    let resp = client.command("cleartag \"music/song1.flac\" title").await;
+    assert_eq!(resp, "ACK [4@0] {cleartag} tag editing is disabled\n");
+}
+
+#[tokio::test]
+async fn writetag_updates_file_and_database() {
+    let (_server, mut client, tmp) = setup_tag_editing().await;
+
+    let resp = client
+        .command("writetag basic.flac title \"Edited Title\"")
+        .await;
+    assert_eq!(resp, "OK\n");
+    let resp = client.command("find title \"Edited Title\"").await;
+    assert_eq!(get_field(&resp, "file"), Some("basic.flac"), "{resp}");
+
+    let resp = client.command("cleartag basic.flac genre").await;
+    assert_eq!(resp, "OK\n");
+    let resp = client.command("lsinfo basic.flac").await;
+    assert_eq!(get_field(&resp, "Genre"), None, "{resp}");
+
+    // The file itself was rewritten.
+    let song = rmpd_library::MetadataExtractor::extract_from_file(
+        &camino::Utf8PathBuf::from_path_buf(tmp.path().join("music/basic.flac")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(song.tag("title"), Some("Edited Title"));
+    assert_eq!(song.tag("genre"), None);
+}
+
+#[tokio::test]
+async fn writetag_rating_sets_sticker() {
+    let (_server, mut client, _tmp) = setup_tag_editing().await;
+
+    let resp = client.command("writetag basic.flac rating 8").await;
+    assert_eq!(resp, "OK\n");
+    let resp = client.command("sticker get song basic.flac rating").await;
+    assert_eq!(resp, "sticker: rating=8\nOK\n");
+
+    let resp = client.command("cleartag basic.flac rating").await;
+    assert_eq!(resp, "OK\n");
+    let resp = client.command("sticker get song basic.flac rating").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+}
+
+#[tokio::test]
+async fn writetag_errors() {
+    let (_server, mut client, _tmp) = setup_tag_editing().await;
+
+    let resp = client.command("writetag basic.flac duration 1").await;
+    assert_eq!(resp, "ACK [2@0] {writetag} Unsupported tag type\n");
+    let resp = client.command("writetag missing.flac title x").await;
+    assert_eq!(resp, "ACK [50@0] {writetag} No such song\n");
+    let resp = client.command("writetag ../test.db title x").await;
+    assert_eq!(resp, "ACK [2@0] {writetag} Malformed path\n");
+}
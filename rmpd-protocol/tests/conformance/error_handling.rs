@@ -1,6 +1,8 @@
-//! Tests for MPD error handling: ACK format, malformed args, missing args.
+//! Tests for MPD error handling: ACK format, malformed args, missing args,
+//! malformed input and connection I/O limits.
 
 use crate::tcp_harness::*;
+use rmpd_protocol::state::AppState;
 
 #[tokio::test]
 async fn ack_format_has_code_and_command() {
@@ -81,3 +83,88 @@ async fn ack_for_deleteid_nonexistent() {
         "deleteid non-existent should error: {resp}"
     );
 }
+
+#[tokio::test]
+async fn invalid_utf8_gets_ack_and_keeps_connection() {
+    let (_server, mut client) = setup().await;
+    client.send_bytes(b"add \"\xff\xfe.flac\"\n").await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@0] {add} Malformed UTF-8\n");
+
+    client.send_bytes(b"\x80\x81\x82\n").await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@0] {} Malformed UTF-8\n");
+
+    let resp = client.command("ping").await;
+    assert_eq!(resp, "OK\n");
+}
+
+#[tokio::test]
+async fn invalid_utf8_aborts_command_list() {
+    let (_server, mut client) = setup().await;
+    client
+        .send_bytes(b"command_list_begin\nping\nadd \xc3\x28\ncommand_list_end\n")
+        .await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@1] {add} Malformed UTF-8\n");
+
+    // The trailing command_list_end now arrives outside a list.
+    let resp = client.read_response().await;
+    assert!(
+        resp.starts_with("ACK [5@0] {command_list_end}"),
+        "got: {resp}"
+    );
+
+    let resp = client.command("ping").await;
+    assert_eq!(resp, "OK\n");
+}
+
+#[tokio::test]
+async fn overlong_line_gets_ack_and_closes() {
+    let (_server, mut client) = setup().await;
+    let line = format!("add \"{}\"\n", "a".repeat(16 * 1024));
+    client.send_raw(&line).await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@0] {} line too long\n");
+    assert!(client.is_closed().await);
+}
+
+#[tokio::test]
+async fn line_within_limit_is_accepted() {
+    let server =
+        MpdTestServer::start_configured(AppState::new(), |s| s.with_max_line_length(64)).await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+    // 63 bytes plus the newline is exactly at the limit.
+    let resp = client.command(&format!("ping{}", " ".repeat(59))).await;
+    assert_eq!(resp, "OK\n");
+
+    client.send_raw(&format!("ping{}\n", " ".repeat(60))).await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@0] {} line too long\n");
+}
+
+#[tokio::test]
+async fn oversized_command_list_gets_ack_and_closes() {
+    let server =
+        MpdTestServer::start_configured(AppState::new(), |s| s.with_max_command_list_size(16))
+            .await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+    client
+        .send_raw("command_list_begin\nping\nping\nping\nping\nping\ncommand_list_end\n")
+        .await;
+    let resp = client.read_response().await;
+    assert_eq!(resp, "ACK [2@4] {} command list too long\n");
+    assert!(client.is_closed().await);
+}
+
+#[tokio::test]
+async fn oversized_response_closes_connection() {
+    let server =
+        MpdTestServer::start_configured(AppState::new(), |s| s.with_max_output_buffer_size(16))
+            .await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+    assert_eq!(client.command("ping").await, "OK\n");
+
+    client.send_raw("status\n").await;
+    assert!(client.is_closed().await);
+}
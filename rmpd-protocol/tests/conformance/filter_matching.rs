@@ -51,6 +51,25 @@ async fn find_with_sort() {
     assert!(resp.ends_with("OK\n") || resp.starts_with("ACK "));
 }
 
+#[tokio::test]
+async fn find_added_since_sorted_by_added() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    let resp = client
+        .command("find \"(added-since '2000-01-01')\" sort -added")
+        .await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("file:").count(), 3, "{resp}");
+    assert!(resp.contains("Added: "), "{resp}");
+
+    let resp = client
+        .command("find \"(added-since '2999-01-01T00:00:00Z')\"")
+        .await;
+    assert_eq!(resp, "OK\n");
+
+    let resp = client.command("find \"(added-since 'soon')\"").await;
+    assert!(resp.starts_with("ACK "), "{resp}");
+}
+
 #[tokio::test]
 async fn find_with_window() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
@@ -146,3 +146,150 @@ async fn single_connection_idle_sees_buffered_addid() {
     );
     assert_ok(&idle_resp);
 }
+
+#[tokio::test]
+async fn option_changes_notify_options_only_when_changed() {
+    let (_server, mut client) = setup().await;
+    client.send_raw("idle\n").await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    client.send_raw("noidle\n").await;
+    assert_ok(&client.read_response().await);
+
+    for cmd in [
+        "repeat 1",
+        "random 1",
+        "single oneshot",
+        "consume 1",
+        "crossfade 3",
+        "mixrampdb -10",
+        "replay_gain_mode track",
+    ] {
+        assert_ok(&client.command(cmd).await);
+        let idle_resp = client.command("idle options").await;
+        assert_eq!(
+            idle_resp, "changed: options\nOK\n",
+            "`{cmd}` should notify the options subsystem"
+        );
+
+        // Setting the same value again changes nothing and wakes nobody.
+        assert_ok(&client.command(cmd).await);
+        client.send_raw("idle options\n").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.send_raw("noidle\n").await;
+        assert_eq!(
+            client.read_response().await,
+            "OK\n",
+            "repeating `{cmd}` must not notify"
+        );
+    }
+}
+
+#[tokio::test]
+async fn volume_changes_wake_other_clients_with_the_new_volume() {
+    let server = MpdTestServer::start().await;
+    let mut idler = MpdTestClient::connect(server.port()).await;
+    let mut mixer = MpdTestClient::connect(server.port()).await;
+
+    for (cmd, expected) in [
+        ("setvol 40", "40"),
+        ("volume +5", "45"),
+        ("volume -10", "35"),
+    ] {
+        idler.send_raw("idle mixer\n").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_ok(&mixer.command(cmd).await);
+
+        assert_eq!(
+            idler.read_response().await,
+            "changed: mixer\nOK\n",
+            "`{cmd}` should notify the mixer subsystem"
+        );
+        let status = idler.command("status").await;
+        assert_eq!(get_field(&status, "volume"), Some(expected), "{status}");
+    }
+}
+
+#[tokio::test]
+async fn stored_playlist_mutations_notify_stored_playlist() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    assert_ok(&client.command("add music/song1.flac").await);
+    assert_ok(&client.command("add music/song2.flac").await);
+    assert_eq!(
+        client.command("idle playlist").await,
+        "changed: playlist\nOK\n"
+    );
+
+    for cmd in [
+        "save mix",
+        "playlistadd mix music/song3.flac",
+        "playlistmove mix 0 2",
+        "playlistdelete mix 0",
+        "playlistclear mix",
+        "rename mix other",
+        "rm other",
+    ] {
+        assert_ok(&client.command(cmd).await);
+        assert_eq!(
+            client.command("idle stored_playlist").await,
+            "changed: stored_playlist\nOK\n",
+            "`{cmd}` should notify the stored_playlist subsystem"
+        );
+    }
+
+    // Loading a stored playlist changes the queue, not the playlist itself.
+    assert_ok(&client.command("save mix").await);
+    assert_ok(&client.command("idle stored_playlist").await);
+    assert_ok(&client.command("load mix").await);
+    assert_eq!(
+        client.command("idle playlist").await,
+        "changed: playlist\nOK\n"
+    );
+
+    // A failed mutation leaves stored playlists untouched and wakes nobody.
+    assert!(client.command("rm missing").await.starts_with("ACK"));
+    client.send_raw("idle stored_playlist\n").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client.send_raw("noidle\n").await;
+    assert_eq!(client.read_response().await, "OK\n");
+}
+
+#[tokio::test]
+async fn sticker_mutations_wake_other_clients() {
+    let (server, mut client, _tmp) = setup_with_db(1).await;
+    let mut idler = MpdTestClient::connect(server.port()).await;
+
+    for cmd in [
+        "sticker set song \"music/song1.flac\" rating 5",
+        "sticker inc song \"music/song1.flac\" playcount",
+        "sticker dec song \"music/song1.flac\" playcount",
+        "sticker delete song \"music/song1.flac\" rating",
+        "sticker delete song \"music/song1.flac\"",
+    ] {
+        idler.send_raw("idle sticker\n").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_ok(&client.command(cmd).await);
+        assert_eq!(
+            idler.read_response().await,
+            "changed: sticker\nOK\n",
+            "`{cmd}` should notify the sticker subsystem"
+        );
+    }
+
+    // Reads and failed deletes leave other clients idle.
+    idler.send_raw("idle sticker\n").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_ok(
+        &client
+            .command("sticker list song \"music/song1.flac\"")
+            .await,
+    );
+    assert!(
+        client
+            .command("sticker delete song \"music/song1.flac\" rating")
+            .await
+            .starts_with("ACK")
+    );
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    idler.send_raw("noidle\n").await;
+    assert_eq!(idler.read_response().await, "OK\n");
+}
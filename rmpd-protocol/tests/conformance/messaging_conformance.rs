@@ -1,6 +1,7 @@
 //! Tests for MPD client-to-client messaging commands over TCP.
 
 use crate::tcp_harness::*;
+use std::time::Duration;
 
 #[tokio::test]
 async fn subscribe_and_unsubscribe() {
@@ -25,15 +26,76 @@ async fn sendmessage_and_readmessages() {
     let server = MpdTestServer::start().await;
     let mut client1 = MpdTestClient::connect(server.port()).await;
     let mut client2 = MpdTestClient::connect(server.port()).await;
+    let mut client3 = MpdTestClient::connect(server.port()).await;
 
-    client1.command("subscribe \"msgchan\"").await;
+    assert_ok(&client1.command("subscribe \"msgchan\"").await);
+    assert_ok(&client3.command("subscribe \"msgchan\"").await);
 
     let resp = client2.command("sendmessage \"msgchan\" \"hello\"").await;
     assert_ok(&resp);
 
+    // Every subscriber gets its own copy.
+    for client in [&mut client1, &mut client3] {
+        let resp = client.command("readmessages").await;
+        assert_eq!(resp, "channel: msgchan\nmessage: hello\nOK\n");
+    }
     let resp = client1.command("readmessages").await;
-    assert_ok(&resp);
-    // Message may or may not be present depending on broker implementation
+    assert_eq!(resp, "OK\n", "messages are consumed by reading");
+
+    let resp = client2.command("readmessages").await;
+    assert_eq!(resp, "OK\n", "non-subscribers get nothing");
+}
+
+#[tokio::test]
+async fn sendmessage_without_subscribers_fails() {
+    let (_server, mut client) = setup().await;
+    let resp = client.command("sendmessage \"nobody\" \"hello\"").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+
+    client.command("subscribe \"nobody\"").await;
+    client.command("unsubscribe \"nobody\"").await;
+    let resp = client.command("sendmessage \"nobody\" \"hello\"").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+}
+
+#[tokio::test]
+async fn idle_message_wakes_subscribers_only() {
+    let server = MpdTestServer::start().await;
+    let mut subscriber = MpdTestClient::connect(server.port()).await;
+    let mut bystander = MpdTestClient::connect(server.port()).await;
+    let mut sender = MpdTestClient::connect(server.port()).await;
+
+    assert_ok(&subscriber.command("subscribe \"msgchan\"").await);
+    subscriber.send_raw("idle message\n").await;
+    bystander.send_raw("idle message\n").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_ok(&sender.command("sendmessage \"msgchan\" \"hi\"").await);
+    assert_eq!(subscriber.read_response().await, "changed: message\nOK\n");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    bystander.send_raw("noidle\n").await;
+    assert_eq!(bystander.read_response().await, "OK\n");
+}
+
+#[tokio::test]
+async fn idle_subscription_on_subscribe() {
+    let server = MpdTestServer::start().await;
+    let mut watcher = MpdTestClient::connect(server.port()).await;
+    let mut client = MpdTestClient::connect(server.port()).await;
+
+    watcher.send_raw("idle subscription\n").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_ok(&client.command("subscribe \"newchan\"").await);
+    assert_eq!(watcher.read_response().await, "changed: subscription\nOK\n");
+
+    let resp = watcher.command("channels").await;
+    assert_eq!(resp, "channel: newchan\nOK\n");
+
+    // Disconnecting drops the client's subscriptions.
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(watcher.command("channels").await, "OK\n");
 }
 
 #[tokio::test]
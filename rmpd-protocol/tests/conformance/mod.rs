@@ -1,3 +1,4 @@
+pub mod binary_conformance;
 pub mod command_batching;
 pub mod connection_lifecycle;
 pub mod database_conformance;
@@ -127,3 +127,40 @@ async fn replay_gain_mode() {
     assert_ok(&resp);
     assert!(get_field(&resp, "replay_gain_mode").is_some());
 }
+
+/// `nextsong` follows repeat/single/random and, in random mode, priorities.
+#[tokio::test]
+async fn playback_modes_repick_nextsong() {
+    let state = rmpd_protocol::state::AppState::new();
+    let current_id = {
+        let mut queue = state.queue.write().await;
+        for i in 0..4 {
+            let path = format!("music/song{i}.flac");
+            queue.add(rmpd_core::test_utils::make_test_song(&path, i));
+        }
+        queue.get(3).unwrap().id
+    };
+    state.status.write().await.current_song = Some(rmpd_core::state::QueuePosition {
+        position: 3,
+        id: current_id,
+    });
+    let (_server, mut client) = setup_with_state(state).await;
+
+    assert_ok(&client.command("repeat 1").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "nextsong"), Some("0"));
+
+    assert_ok(&client.command("single 1").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "nextsong"), Some("3"));
+
+    assert_ok(&client.command("repeat 0").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "nextsong"), None);
+
+    assert_ok(&client.command("single 0").await);
+    assert_ok(&client.command("prio 10 1").await);
+    assert_ok(&client.command("random 1").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "nextsong"), Some("1"));
+}
@@ -68,3 +68,34 @@ async fn output_nonexistent_id() {
     let resp = client.command("enableoutput 999").await;
     assert!(resp.starts_with("ACK "), "nonexistent output: {resp}");
 }
+
+#[tokio::test]
+async fn outputs_report_canonical_plugin_and_attributes() {
+    let state = rmpd_protocol::state::AppState::new();
+    let mut settings = toml::Table::new();
+    settings.insert("port".to_owned(), toml::Value::Integer(8000));
+    settings.insert("tags".to_owned(), toml::Value::String("no".to_owned()));
+    let outputs = vec![
+        rmpd_core::config::OutputConfig {
+            name: "Speakers".to_owned(),
+            output_type: "default".to_owned(),
+            enabled: true,
+            settings: toml::Table::new(),
+        },
+        rmpd_core::config::OutputConfig {
+            name: "Stream".to_owned(),
+            output_type: "httpd".to_owned(),
+            enabled: false,
+            settings,
+        },
+    ];
+    state.set_outputs_from_config(&outputs, "default").await;
+
+    let (_server, mut client) = setup_with_state(state).await;
+    let resp = client.command("outputs").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "plugin"), Some("cpal"));
+    assert!(resp.contains("plugin: httpd\n"), "got: {resp}");
+    assert!(resp.contains("attribute: port=8000\n"), "got: {resp}");
+    assert!(resp.contains("attribute: tags=no\n"), "got: {resp}");
+}
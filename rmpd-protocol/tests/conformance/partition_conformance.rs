@@ -1,6 +1,7 @@
 //! Tests for MPD partition commands over TCP.
 
 use crate::tcp_harness::*;
+use std::time::Duration;
 
 #[tokio::test]
 async fn listpartitions_returns_ok() {
@@ -64,3 +65,102 @@ async fn duplicate_newpartition() {
         "duplicate partition should error: {resp}"
     );
 }
+
+#[tokio::test]
+async fn queue_inspection_resolves_against_client_partition() {
+    let (server, mut client, _tmp) = setup_with_db(3).await;
+    client.command("add \"music/song1.flac\"").await;
+    client.command("add \"music/song2.flac\"").await;
+    assert_ok(&client.command("newpartition \"kitchen\"").await);
+
+    let mut other = MpdTestClient::connect(server.port()).await;
+    assert_ok(&other.command("partition \"kitchen\"").await);
+
+    let (default_info, kitchen_info) = tokio::join!(
+        client.command("playlistinfo"),
+        other.command("playlistinfo")
+    );
+    assert_ok(&default_info);
+    assert_eq!(default_info.matches("file:").count(), 2, "{default_info}");
+    assert_eq!(kitchen_info, "OK\n", "new partition has an empty queue");
+
+    let id = get_field(&default_info, "Id").unwrap().to_owned();
+    let (default_id, kitchen_id) = tokio::join!(
+        client.command(&format!("playlistid {id}")),
+        other.command(&format!("playlistid {id}"))
+    );
+    assert_eq!(get_field(&default_id, "Id"), Some(id.as_str()));
+    assert!(
+        kitchen_id.starts_with("ACK [50@0] {playlistid}"),
+        "{kitchen_id}"
+    );
+
+    // Switching back to the default partition sees the main queue again.
+    assert_ok(&other.command("partition \"default\"").await);
+    let resp = other.command("playlistid").await;
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+}
+
+#[tokio::test]
+async fn partitions_have_independent_queues_and_options() {
+    let (server, mut client, _tmp) = setup_with_db(3).await;
+    client.command("add \"music/song1.flac\"").await;
+    assert_ok(&client.command("newpartition \"kitchen\"").await);
+
+    let mut other = MpdTestClient::connect(server.port()).await;
+    assert_ok(&other.command("partition \"kitchen\"").await);
+    assert_ok(&other.command("add \"music/song2.flac\"").await);
+    assert_ok(&other.command("add \"music/song3.flac\"").await);
+    assert_ok(&other.command("random 1").await);
+
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "partition"), Some("default"));
+    assert_eq!(get_field(&status, "playlistlength"), Some("1"));
+    assert_eq!(get_field(&status, "random"), Some("0"));
+
+    let status = other.command("status").await;
+    assert_eq!(get_field(&status, "partition"), Some("kitchen"));
+    assert_eq!(get_field(&status, "playlistlength"), Some("2"));
+    assert_eq!(get_field(&status, "random"), Some("1"));
+
+    let resp = other.command("playlistinfo").await;
+    assert!(!resp.contains("song1.flac"), "{resp}");
+}
+
+#[tokio::test]
+async fn queue_idle_stays_in_partition() {
+    let (server, mut client, _tmp) = setup_with_db(2).await;
+    assert_ok(&client.command("newpartition \"kitchen\"").await);
+    let mut other = MpdTestClient::connect(server.port()).await;
+    assert_ok(&other.command("partition \"kitchen\"").await);
+
+    client.send_raw("idle playlist stored_playlist\n").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_ok(&other.command("add \"music/song1.flac\"").await);
+
+    // Server-wide events still reach every partition.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_ok(&other.command("save \"shared\"").await);
+    assert_eq!(
+        client.read_response().await,
+        "changed: stored_playlist\nOK\n"
+    );
+}
+
+#[tokio::test]
+async fn delpartition_refuses_partition_in_use() {
+    let (server, mut client) = setup().await;
+    assert_ok(&client.command("newpartition \"kitchen\"").await);
+    let mut other = MpdTestClient::connect(server.port()).await;
+    assert_ok(&other.command("partition \"kitchen\"").await);
+
+    let resp = client.command("delpartition \"kitchen\"").await;
+    assert!(resp.contains("partition still has clients"), "{resp}");
+
+    assert_ok(&other.command("partition \"default\"").await);
+    assert_ok(&client.command("delpartition \"kitchen\"").await);
+    assert_eq!(
+        client.command("listpartitions").await,
+        "partition: default\nOK\n"
+    );
+}
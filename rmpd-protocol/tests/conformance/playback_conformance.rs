@@ -105,3 +105,48 @@ async fn play_out_of_range() {
     let resp = client.command("play 999").await;
     assert!(resp.starts_with("ACK "), "play out of range: {resp}");
 }
+
+/// A completion event from a playback that was already replaced (here: the
+/// same `SongFinished` delivered twice, as when a manual `next` races the
+/// natural end of the song) advances the queue only once.
+#[tokio::test]
+async fn stale_song_finished_advances_once() {
+    use rmpd_core::event::Event;
+    use tokio::time::Duration;
+
+    let state = rmpd_protocol::state::AppState::new();
+    let first_id = {
+        let mut queue = state.queue.write().await;
+        for i in 0..3 {
+            let path = format!("music/song{i}.flac");
+            queue.add(rmpd_core::test_utils::make_test_song(&path, i));
+        }
+        queue.get(0).unwrap().id
+    };
+    state.status.write().await.current_song = Some(rmpd_core::state::QueuePosition {
+        position: 0,
+        id: first_id,
+    });
+    let generation = state.engine.read().await.generation();
+    let event_bus = state.event_bus.clone();
+    let (_server, mut client) = setup_with_state(state).await;
+
+    event_bus.emit(Event::SongFinished(generation));
+    event_bus.emit(Event::SongFinished(generation));
+
+    let mut song = None;
+    for _ in 0..100 {
+        let status = client.command("status").await;
+        song = get_field(&status, "song").map(str::to_owned);
+        if song.as_deref() != Some("0") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(song.as_deref(), Some("1"));
+
+    // Give the duplicate time to be (not) acted upon.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "song"), Some("1"));
+}
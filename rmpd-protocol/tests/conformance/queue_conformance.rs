@@ -174,6 +174,30 @@ async fn plchanges_returns_all_for_version_zero() {
     assert_eq!(file_count, 2, "plchanges 0 should return all songs");
 }
 
+#[tokio::test]
+async fn plchanges_returns_only_changed_songs() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    client.command("add \"music/song1.flac\"").await;
+    client.command("add \"music/song2.flac\"").await;
+    client.command("add \"music/song3.flac\"").await;
+    let resp = client.command("status").await;
+    let version = get_field(&resp, "playlist").unwrap().to_owned();
+
+    let resp = client.command(&format!("plchanges {version}")).await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("file:").count(), 0);
+
+    // Swapping the last two leaves the first song in place.
+    assert_ok(&client.command("swap 1 2").await);
+    let resp = client.command(&format!("plchangesposid {version}")).await;
+    assert_ok(&resp);
+    let positions: Vec<_> = resp
+        .lines()
+        .filter_map(|line| line.strip_prefix("cpos: "))
+        .collect();
+    assert_eq!(positions, ["1", "2"]);
+}
+
 #[tokio::test]
 async fn plchangesposid_returns_positions() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
@@ -224,13 +248,100 @@ async fn clear_removes_all_songs() {
 async fn addtagid_and_cleartagid() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
     let r = client.command("addid \"music/song1.flac\"").await;
-    let id = get_field(&r, "Id").unwrap();
+    let id = get_field(&r, "Id").unwrap().to_owned();
 
     let resp = client
         .command(&format!("addtagid {id} Artist \"New Artist\""))
         .await;
     assert_ok(&resp);
+    assert_ok(&client.command(&format!("addtagid {id} title Live")).await);
+
+    let resp = client.command(&format!("playlistid {id}")).await;
+    assert_eq!(get_field(&resp, "Artist"), Some("New Artist"));
+    assert_eq!(get_field(&resp, "Title"), Some("Live"));
+    let resp = client.command("playlistfind artist \"New Artist\"").await;
+    assert_eq!(get_field(&resp, "Id"), Some(id.as_str()));
 
     let resp = client.command(&format!("cleartagid {id} Artist")).await;
     assert_ok(&resp);
+    let resp = client.command("playlistinfo").await;
+    assert_eq!(get_field(&resp, "Artist"), Some("Test Artist"));
+    assert_eq!(get_field(&resp, "Title"), Some("Live"));
+
+    assert_ok(&client.command(&format!("cleartagid {id}")).await);
+    let resp = client.command("playlistinfo").await;
+    assert_eq!(get_field(&resp, "Title"), Some("Track 1"));
+
+    let resp = client.command("addtagid 9999 Artist x").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+}
+
+#[tokio::test]
+async fn rangeid_sets_and_clears_the_range() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    let r = client.command("addid \"music/song1.flac\"").await;
+    let id = get_field(&r, "Id").unwrap().to_owned();
+
+    assert_ok(&client.command(&format!("rangeid {id} 1.5:3")).await);
+    let resp = client.command(&format!("playlistid {id}")).await;
+    assert_eq!(get_field(&resp, "Range"), Some("1.500-3.000"));
+
+    // An open end plays to the end of the song.
+    assert_ok(&client.command(&format!("rangeid {id} 2:")).await);
+    let resp = client.command(&format!("playlistid {id}")).await;
+    assert_eq!(get_field(&resp, "Range"), Some("2.000-"));
+
+    assert_ok(&client.command(&format!("rangeid {id} :")).await);
+    let resp = client.command(&format!("playlistid {id}")).await;
+    assert_eq!(get_field(&resp, "Range"), None);
+
+    for range in ["5:1", "-5:", ":-1", "nan:inf", "1:inf"] {
+        let resp = client.command(&format!("rangeid {id} {range}")).await;
+        assert!(resp.starts_with("ACK [2@0] {rangeid}"), "{range}: {resp}");
+    }
+    let resp = client.command("rangeid 999 1:2").await;
+    assert!(resp.starts_with("ACK [50@0] {rangeid}"), "{resp}");
+}
+
+// ── Current/next song bookkeeping ────────────────────────────────────
+
+/// Queue edits keep `song`/`nextsong` pointing at the current song by id.
+#[tokio::test]
+async fn queue_edits_follow_current_song() {
+    let state = rmpd_protocol::state::AppState::new();
+    let current_id = {
+        let mut queue = state.queue.write().await;
+        for i in 0..4 {
+            let path = format!("music/song{i}.flac");
+            queue.add(rmpd_core::test_utils::make_test_song(&path, i));
+        }
+        queue.get(2).unwrap().id
+    };
+    state.status.write().await.current_song = Some(rmpd_core::state::QueuePosition {
+        position: 2,
+        id: current_id,
+    });
+    let (_server, mut client) = setup_with_state(state).await;
+    let current_id = current_id.to_string();
+
+    assert_ok(&client.command("move 2 0").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "song"), Some("0"));
+    assert_eq!(get_field(&status, "songid"), Some(current_id.as_str()));
+    assert_eq!(get_field(&status, "nextsong"), Some("1"));
+
+    assert_ok(&client.command("delete 1:3").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "song"), Some("0"));
+    assert_eq!(get_field(&status, "nextsong"), Some("1"));
+
+    assert_ok(&client.command("swap 0 1").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "song"), Some("1"));
+    assert_eq!(get_field(&status, "nextsong"), None);
+
+    assert_ok(&client.command(&format!("deleteid {current_id}")).await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "song"), None);
+    assert_eq!(get_field(&status, "songid"), None);
 }
@@ -2,6 +2,7 @@
 //! Tests add/addid with position parameters.
 
 use crate::tcp_harness::*;
+use rmpd_core::test_utils::write_wav;
 
 #[tokio::test]
 async fn add_with_position() {
@@ -78,3 +79,94 @@ async fn add_returns_bare_ok_without_id() {
         "add must NOT return an Id field: {resp}"
     );
 }
+
+#[tokio::test]
+async fn add_unscanned_local_file() {
+    let (_server, mut client, tmp) = setup_with_db(1).await;
+    // On disk under music_directory but not in the database (no rescan yet).
+    write_wav(&tmp.path().join("music/new.wav"), 44100);
+
+    assert_ok(&client.command("add \"new.wav\"").await);
+    let resp = client.command("addid \"new.wav\"").await;
+    assert_ok(&resp);
+    assert!(
+        get_field(&resp, "Id").is_some(),
+        "addid must return Id: {resp}"
+    );
+
+    let info = client.command("playlistinfo").await;
+    assert_ok(&info);
+    assert_eq!(
+        info.matches("file: new.wav\n").count(),
+        2,
+        "the unscanned file keeps its relative URI: {info}"
+    );
+    assert_eq!(
+        get_field(&info, "Time"),
+        Some("1"),
+        "tags read on the fly: {info}"
+    );
+}
+
+#[tokio::test]
+async fn add_missing_file_is_no_exist() {
+    let (_server, mut client, _tmp) = setup_with_db(1).await;
+    assert_eq!(
+        client.command("add \"missing.flac\"").await,
+        "ACK [50@0] {add} No such directory\n"
+    );
+    assert_eq!(
+        client.command("addid \"missing.flac\"").await,
+        "ACK [50@0] {addid} No such song\n"
+    );
+}
+
+/// Serve one ICY response: station headers, 8 audio bytes, then a metadata
+/// block carrying `StreamTitle`.
+fn spawn_icy_station() -> u16 {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let Ok((mut sock, _)) = listener.accept() else {
+            return;
+        };
+        let mut request = [0u8; 1024];
+        let _ = sock.read(&mut request);
+        let meta = b"StreamTitle='Artist - Song';";
+        let blocks = meta.len().div_ceil(16);
+        let mut body = vec![0u8; 8];
+        body.push(blocks as u8);
+        body.extend_from_slice(meta);
+        body.resize(8 + 1 + blocks * 16, 0);
+        let head = "HTTP/1.0 200 OK\r\ncontent-type: audio/mpeg\r\nicy-name: Test Radio\r\n\
+                    icy-genre: Jazz\r\nicy-br: 128\r\nicy-metaint: 8\r\n\r\n";
+        let _ = sock.write_all(head.as_bytes());
+        let _ = sock.write_all(&body);
+    });
+    port
+}
+
+#[tokio::test]
+async fn added_stream_is_described_by_a_probe() {
+    let (_server, mut client) = setup().await;
+    let port = spawn_icy_station();
+    let resp = client
+        .command(&format!("addid \"http://127.0.0.1:{port}/radio\""))
+        .await;
+    assert_ok(&resp);
+    let id = get_field(&resp, "Id").unwrap().to_owned();
+
+    // The probe runs in the background; `add` itself answers immediately.
+    let mut info = String::new();
+    for _ in 0..50 {
+        info = client.command(&format!("playlistid {id}")).await;
+        if info.contains("Name: ") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(get_field(&info, "Name"), Some("Test Radio"), "{info}");
+    assert_eq!(get_field(&info, "Genre"), Some("Jazz"), "{info}");
+    assert_eq!(get_field(&info, "Title"), Some("Artist - Song"), "{info}");
+}
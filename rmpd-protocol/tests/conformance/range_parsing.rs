@@ -92,13 +92,55 @@ async fn playlistinfo_out_of_bounds() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
     client.command("add \"music/song1.flac\"").await;
 
-    // Single position beyond queue should return empty (apply_range returns &[])
+    // Single position beyond queue should return empty
     let resp = client.command("playlistinfo 999").await;
     assert_ok(&resp);
     let file_count = resp.matches("file:").count();
     assert_eq!(file_count, 0, "out of bounds should return 0 songs");
 }
 
+#[tokio::test]
+async fn playlistinfo_inverted_range() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    for i in 1..=3 {
+        client.command(&format!("add \"music/song{i}.flac\"")).await;
+    }
+
+    let resp = client.command("playlistinfo 2:1").await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("file:").count(), 0, "{resp}");
+}
+
+#[tokio::test]
+async fn plchanges_open_ended_range() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    for i in 1..=3 {
+        client.command(&format!("add \"music/song{i}.flac\"")).await;
+    }
+
+    let resp = client.command("plchanges 0 1:").await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("file:").count(), 2, "{resp}");
+    assert_eq!(get_field(&resp, "Pos"), Some("1"));
+}
+
+#[tokio::test]
+async fn plchangesposid_open_ended_range() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    for i in 1..=3 {
+        client.command(&format!("add \"music/song{i}.flac\"")).await;
+    }
+
+    let resp = client.command("plchangesposid 0 2:").await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("cpos:").count(), 1, "{resp}");
+    assert_eq!(get_field(&resp, "cpos"), Some("2"));
+
+    let resp = client.command("plchangesposid 0 5:").await;
+    assert_ok(&resp);
+    assert_eq!(resp.matches("cpos:").count(), 0, "{resp}");
+}
+
 #[tokio::test]
 async fn delete_single_position() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
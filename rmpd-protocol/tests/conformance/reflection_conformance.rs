@@ -1,6 +1,7 @@
 //! Tests for MPD reflection commands over TCP.
 
 use crate::tcp_harness::*;
+use rmpd_protocol::state::AppState;
 
 #[tokio::test]
 async fn commands_lists_available() {
@@ -20,6 +21,51 @@ async fn notcommands_returns_ok() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn notcommands_empty_with_full_permissions() {
+    let (_server, mut client) = setup().await;
+    let resp = client.command("notcommands").await;
+    assert_eq!(resp, "OK\n");
+}
+
+#[tokio::test]
+async fn commands_reflect_password_permissions() {
+    let mut state = AppState::new();
+    state.set_password(Some("secret".to_owned()));
+    let (_server, mut client) = setup_with_state(state).await;
+
+    let resp = client.command("commands").await;
+    assert_ok(&resp);
+    assert!(resp.contains("command: password\n"), "got: {resp}");
+    assert!(resp.contains("command: ping\n"), "got: {resp}");
+    assert!(!resp.contains("command: play\n"), "got: {resp}");
+    assert!(!resp.contains("command: status\n"), "got: {resp}");
+
+    let resp = client.command("notcommands").await;
+    assert_ok(&resp);
+    assert!(resp.contains("command: play\n"), "got: {resp}");
+    assert!(resp.contains("command: status\n"), "got: {resp}");
+    assert!(!resp.contains("command: password\n"), "got: {resp}");
+
+    let resp = client.command("password secret").await;
+    assert_ok(&resp);
+
+    let resp = client.command("commands").await;
+    assert!(resp.contains("command: play\n"), "got: {resp}");
+    let resp = client.command("notcommands").await;
+    assert_eq!(resp, "OK\n");
+}
+
+#[tokio::test]
+async fn idle_requires_read_permission() {
+    let mut state = AppState::new();
+    state.set_password(Some("secret".to_owned()));
+    let (_server, mut client) = setup_with_state(state).await;
+
+    let resp = client.command("idle").await;
+    assert!(resp.starts_with("ACK [4@0] {idle}"), "got: {resp}");
+}
+
 #[tokio::test]
 async fn tagtypes_lists_tags() {
     let (_server, mut client) = setup().await;
@@ -59,6 +105,41 @@ async fn tagtypes_clear_and_all() {
     assert!(resp.contains("tagtype:"), "should have tags after 'all'");
 }
 
+#[tokio::test]
+async fn tagtypes_mask_filters_song_responses() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    assert_ok(&client.command("add \"music/song1.flac\"").await);
+
+    assert_ok(&client.command("tagtypes clear").await);
+    assert_ok(&client.command("tagtypes enable title").await);
+    assert_eq!(client.command("tagtypes").await, "tagtype: Title\nOK\n");
+
+    for cmd in ["playlistinfo", "find file \"music/song1.flac\""] {
+        let resp = client.command(cmd).await;
+        assert_ok(&resp);
+        assert_eq!(get_field(&resp, "Title"), Some("Track 1"), "{resp}");
+        assert_eq!(get_field(&resp, "Artist"), None, "{resp}");
+        assert_eq!(get_field(&resp, "Album"), None, "{resp}");
+        assert_eq!(get_field(&resp, "file"), Some("music/song1.flac"));
+    }
+
+    assert_ok(&client.command("tagtypes disable Title").await);
+    let resp = client.command("playlistinfo").await;
+    assert_eq!(get_field(&resp, "Title"), None, "{resp}");
+    assert!(get_field(&resp, "Id").is_some(), "{resp}");
+
+    assert_ok(&client.command("tagtypes all").await);
+    let resp = client.command("find file \"music/song1.flac\"").await;
+    assert_eq!(get_field(&resp, "Artist"), Some("Test Artist"), "{resp}");
+}
+
+#[tokio::test]
+async fn tagtypes_rejects_unknown_tag() {
+    let (_server, mut client) = setup().await;
+    let resp = client.command("tagtypes enable Nonsense").await;
+    assert!(resp.starts_with("ACK [2@0] {tagtypes}"), "{resp}");
+}
+
 #[tokio::test]
 async fn urlhandlers_returns_ok() {
     let (_server, mut client) = setup().await;
@@ -73,6 +154,18 @@ async fn decoders_returns_ok() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn decoders_list_dsd_plugins() {
+    let (_server, mut client) = setup().await;
+    let resp = client.command("decoders").await;
+    assert_ok(&resp);
+    assert!(resp.contains("plugin: dsf\nsuffix: dsf\n"), "got: {resp}");
+    assert!(
+        resp.contains("plugin: dsdiff\nsuffix: dff\n"),
+        "got: {resp}"
+    );
+}
+
 #[tokio::test]
 async fn config_returns_ok() {
     let (_server, mut client) = setup().await;
@@ -81,6 +174,46 @@ async fn config_returns_ok() {
     assert!(resp.ends_with("OK\n") || resp.starts_with("ACK "));
 }
 
+#[tokio::test]
+async fn config_reports_loaded_configuration() {
+    let mut config = rmpd_core::config::Config::default();
+    config.audio.buffer_time = 250;
+    config.output = vec![rmpd_core::config::OutputConfig::cpal_default()];
+    let tmp = tempfile::tempdir().unwrap();
+    let db_file = tmp.path().join("db").to_str().unwrap().to_owned();
+    let mut state = AppState::with_all_paths(
+        db_file.clone(),
+        "/srv/music".to_owned(),
+        "/srv/playlists".to_owned(),
+    );
+    state.set_config(config);
+    let (_server, mut client) = setup_with_state(state.clone()).await;
+
+    let resp = client.command("config").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "music_directory"), Some("/srv/music"));
+    assert_eq!(
+        get_field(&resp, "playlist_directory"),
+        Some("/srv/playlists")
+    );
+    assert_eq!(get_field(&resp, "db_file"), Some(db_file.as_str()));
+    assert_eq!(get_field(&resp, "buffer_time"), Some("250"));
+    assert_eq!(get_field(&resp, "resampler_quality"), Some("sinc_medium"));
+    assert!(
+        resp.contains(
+            "audio_output: Default Output\naudio_output_type: cpal\naudio_output_enabled: 1\n"
+        ),
+        "got: {resp}"
+    );
+
+    // A reload moves the music directory; the rest keeps what the server
+    // runs with.
+    state.set_music_dir(Some("/srv/other".to_owned()));
+    let resp = client.command("config").await;
+    assert_eq!(get_field(&resp, "music_directory"), Some("/srv/other"));
+    assert_eq!(get_field(&resp, "buffer_time"), Some("250"));
+}
+
 #[tokio::test]
 async fn protocol_clear_and_all() {
     let (_server, mut client) = setup().await;
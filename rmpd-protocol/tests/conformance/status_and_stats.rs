@@ -70,6 +70,27 @@ async fn stats_uptime_is_nonzero() {
     assert!(uptime < 60, "uptime should be small for fresh server");
 }
 
+#[tokio::test]
+async fn daemonstats_counts_commands_and_cache_hits() {
+    let (_server, mut client, _tmp) = setup_with_db(2).await;
+    assert_ok(&client.command("lsinfo").await);
+    assert_ok(&client.command("lsinfo").await);
+
+    let resp = client.command("daemonstats").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "commands"), Some("3"));
+    assert_eq!(get_field(&resp, "browse_cache_hits"), Some("1"));
+    assert_eq!(get_field(&resp, "browse_cache_misses"), Some("1"));
+    assert_eq!(get_field(&resp, "browse_cache_hit_rate"), Some("0.500"));
+    assert_eq!(
+        get_field(&resp, "search_cache_hit_rate"),
+        None,
+        "no rate before the first lookup"
+    );
+    let sent: u64 = get_field(&resp, "bytes_sent").unwrap().parse().unwrap();
+    assert!(sent > 0, "the lsinfo responses were counted");
+}
+
 #[tokio::test]
 async fn clearerror_returns_ok() {
     let (_server, mut client) = setup().await;
@@ -129,3 +129,30 @@ async fn sticker_delete_all_for_song() {
         .await;
     assert_ok(&resp);
 }
+
+#[tokio::test]
+async fn stickernames_lists_distinct_names_globally() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+
+    client
+        .command("sticker set song \"music/song1.flac\" rating 5")
+        .await;
+    client
+        .command("sticker set song \"music/song2.flac\" rating 3")
+        .await;
+    client
+        .command("sticker set song \"music/song2.flac\" playcount 7")
+        .await;
+
+    let resp = client.command("stickernames").await;
+    assert_eq!(resp, "name: playcount\nname: rating\nOK\n");
+
+    let resp = client.command("stickernamestypes").await;
+    assert_eq!(
+        resp,
+        "name: playcount\ntype: song\nname: rating\ntype: song\nOK\n"
+    );
+
+    let resp = client.command("stickernamestypes playlist").await;
+    assert_eq!(resp, "OK\n");
+}
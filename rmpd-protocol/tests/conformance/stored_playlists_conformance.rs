@@ -125,6 +125,64 @@ async fn rename_playlist() {
     assert_ok(&resp);
 }
 
+#[tokio::test]
+async fn m3u8_playlists_are_stored_playlists() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    let playlist_dir = tmp.path().join("playlists");
+    std::fs::write(playlist_dir.join("mpd.m3u8"), "music/song1.flac\n").unwrap();
+
+    let resp = client.command("listplaylists").await;
+    assert!(resp.contains("playlist: mpd\n"), "{resp}");
+
+    assert_ok(
+        &client
+            .command("playlistadd \"mpd\" \"music/song2.flac\"")
+            .await,
+    );
+    assert_eq!(
+        std::fs::read_to_string(playlist_dir.join("mpd.m3u8")).unwrap(),
+        "music/song1.flac\nmusic/song2.flac\n"
+    );
+    assert!(!playlist_dir.join("mpd.m3u").exists());
+
+    assert_ok(&client.command("rename \"mpd\" \"moved\"").await);
+    assert!(playlist_dir.join("moved.m3u8").exists());
+    assert_ok(&client.command("load \"moved\"").await);
+    let status = client.command("status").await;
+    assert_eq!(get_field(&status, "playlistlength"), Some("2"));
+
+    assert_ok(&client.command("rm \"moved\"").await);
+    assert!(!playlist_dir.join("moved.m3u8").exists());
+}
+
+#[tokio::test]
+async fn stored_playlists_are_indexed_in_database() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    let db = rmpd_library::Database::open(tmp.path().join("test.db").to_str().unwrap()).unwrap();
+    let indexed = |db: &rmpd_library::Database| -> Vec<(String, u32)> {
+        db.list_playlists()
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.name, p.song_count))
+            .collect()
+    };
+
+    client.command("add \"music/song1.flac\"").await;
+    assert_ok(&client.command("save \"first\"").await);
+    assert_ok(
+        &client
+            .command("playlistadd \"first\" \"music/song2.flac\"")
+            .await,
+    );
+    assert_eq!(indexed(&db), [("first".to_owned(), 2)]);
+
+    assert_ok(&client.command("rename \"first\" \"second\"").await);
+    assert_eq!(indexed(&db), [("second".to_owned(), 2)]);
+
+    assert_ok(&client.command("rm \"second\"").await);
+    assert!(indexed(&db).is_empty());
+}
+
 #[tokio::test]
 async fn load_nonexistent_playlist() {
     let (_server, mut client, _tmp) = setup_with_db(3).await;
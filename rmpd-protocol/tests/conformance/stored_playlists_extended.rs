@@ -1,5 +1,6 @@
 //! Extended stored playlist conformance tests.
-//! Tests save modes, load with range/position, searchplaylist, playlistlength.
+//! Tests save modes, load with range/position, searchplaylist, playlistlength,
+//! and queue bookmarks.
 
 use crate::tcp_harness::*;
 
@@ -152,3 +153,159 @@ async fn playlistlength_returns_count() {
         "playlistlength must return playtime field: {resp}"
     );
 }
+
+#[tokio::test]
+async fn lsinfo_playlists_lists_nested_stored_playlists() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    let playlist_dir = tmp.path().join("playlists");
+    std::fs::create_dir_all(playlist_dir.join("rock")).unwrap();
+    std::fs::write(playlist_dir.join("top.m3u"), "music/song1.flac\n").unwrap();
+    std::fs::write(playlist_dir.join("rock/best.m3u"), "music/song2.flac\n").unwrap();
+
+    let resp = client.command("lsinfo \"playlists\"").await;
+    assert_ok(&resp);
+    assert!(resp.contains("directory: playlists/rock\n"), "{resp}");
+    assert!(resp.contains("playlist: top\n"), "{resp}");
+
+    let resp = client.command("lsinfo \"playlists/rock\"").await;
+    assert_ok(&resp);
+    assert!(resp.contains("playlist: rock/best\n"), "{resp}");
+
+    let resp = client.command("listplaylist \"rock/best\"").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "file"), Some("music/song2.flac"));
+
+    let resp = client.command("listplaylist \"../playlists/top\"").await;
+    assert!(
+        resp.starts_with("ACK "),
+        "parent components must be rejected: {resp}"
+    );
+}
+
+#[tokio::test]
+async fn music_dir_playlist_files_are_listed_and_readable() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    let album_dir = tmp.path().join("music/music");
+    std::fs::create_dir_all(&album_dir).unwrap();
+    std::fs::write(album_dir.join("mix.m3u8"), "#EXTM3U\nsong3.flac\n").unwrap();
+    {
+        let db =
+            rmpd_library::Database::open(tmp.path().join("test.db").to_str().unwrap()).unwrap();
+        db.set_directory_playlists("music", &[("music/mix.m3u8".to_string(), 0)])
+            .unwrap();
+    }
+
+    let resp = client.command("lsinfo \"music\"").await;
+    assert_ok(&resp);
+    assert!(resp.contains("playlist: music/mix.m3u8\n"), "{resp}");
+
+    let resp = client.command("listplaylist \"music/mix.m3u8\"").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "file"), Some("music/song3.flac"));
+}
+
+#[tokio::test]
+async fn extinf_titles_become_song_names() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    std::fs::write(
+        tmp.path().join("playlists/radio.m3u"),
+        "#EXTM3U\n#EXTINF:-1,Jazz FM\nhttp://127.0.0.1:1/stream\nmusic/song1.flac\n",
+    )
+    .unwrap();
+
+    let resp = client.command("listplaylistinfo \"radio\"").await;
+    assert_ok(&resp);
+    assert!(
+        resp.contains("file: http://127.0.0.1:1/stream\nName: Jazz FM\n"),
+        "{resp}"
+    );
+
+    assert_ok(&client.command("load \"radio\"").await);
+    let resp = client.command("playlistinfo").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "Name"), Some("Jazz FM"));
+    assert_eq!(resp.matches("Name:").count(), 1, "{resp}");
+}
+
+#[tokio::test]
+async fn listplaylistinfo_keeps_missing_songs_in_file_order() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    std::fs::write(
+        tmp.path().join("playlists/mixed.m3u"),
+        "music/song1.flac\nmusic/gone.flac\nmusic/song2.flac\n",
+    )
+    .unwrap();
+
+    // MPD prints a bare `file:` line for entries the database lacks.
+    let resp = client.command("listplaylistinfo \"mixed\"").await;
+    assert_ok(&resp);
+    let files: Vec<&str> = resp
+        .lines()
+        .filter_map(|l| l.strip_prefix("file: "))
+        .collect();
+    assert_eq!(
+        files,
+        ["music/song1.flac", "music/gone.flac", "music/song2.flac"]
+    );
+    assert_eq!(resp.matches("Title:").count(), 2, "{resp}");
+
+    // Ranges count every entry, known or not.
+    let resp = client.command("listplaylistinfo \"mixed\" 1:3").await;
+    assert_ok(&resp);
+    assert!(
+        resp.starts_with("file: music/gone.flac\nfile: music/song2.flac\n"),
+        "{resp}"
+    );
+
+    let resp = client.command("listplaylist \"mixed\" 1:2").await;
+    assert_eq!(resp, "file: music/gone.flac\nOK\n");
+}
+
+#[tokio::test]
+async fn listplaylist_ranges_past_the_end_are_empty() {
+    let (_server, mut client, tmp) = setup_with_db(3).await;
+    std::fs::write(
+        tmp.path().join("playlists/short.m3u"),
+        "music/song1.flac\nmusic/song2.flac\n",
+    )
+    .unwrap();
+
+    assert_eq!(client.command("listplaylist \"short\" 5:9").await, "OK\n");
+    assert_eq!(
+        client.command("listplaylistinfo \"short\" 1:").await,
+        client.command("listplaylistinfo \"short\" 1:2").await
+    );
+    let resp = client.command("load \"short\" 2:1").await;
+    assert!(resp.starts_with("ACK "), "{resp}");
+}
+
+#[tokio::test]
+async fn bookmarks_save_and_restore_the_queue() {
+    let (_server, mut client, _tmp) = setup_with_db(3).await;
+    client.command("add \"music/song1.flac\"").await;
+    client.command("add \"music/song2.flac\"").await;
+    assert_ok(&client.command("savebookmark \"book\"").await);
+
+    let resp = client.command("listbookmarks").await;
+    assert_ok(&resp);
+    assert_eq!(get_field(&resp, "bookmark"), Some("book"));
+    assert_eq!(get_field(&resp, "songs"), Some("2"));
+    // Nothing was playing, so no position was kept.
+    assert_eq!(get_field(&resp, "song"), None);
+
+    client.command("clear").await;
+    client.command("add \"music/song3.flac\"").await;
+    assert_ok(&client.command("loadbookmark \"book\"").await);
+    let resp = client.command("playlistinfo").await;
+    let files: Vec<&str> = resp
+        .lines()
+        .filter_map(|line| line.strip_prefix("file: "))
+        .collect();
+    assert_eq!(files, ["music/song1.flac", "music/song2.flac"]);
+
+    assert_ok(&client.command("deletebookmark \"book\"").await);
+    let resp = client.command("loadbookmark \"book\"").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+    let resp = client.command("deletebookmark \"book\"").await;
+    assert!(resp.starts_with("ACK [50@0]"), "{resp}");
+}
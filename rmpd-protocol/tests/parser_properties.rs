@@ -0,0 +1,132 @@
+//! Property tests for the command parser.
+//!
+//! Malformed client input must never panic `parse_command`, and any string a
+//! client quotes the way libmpdclient does must come back unchanged.
+
+use proptest::prelude::*;
+use rmpd_protocol::parser::{Command, parse_command};
+
+/// Quote an argument the way libmpdclient does: wrap in `"` and backslash
+/// every `"` and `\`.
+fn quote(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// Arbitrary argument text without line breaks (a protocol line never
+/// contains one).
+fn arg() -> impl Strategy<Value = String> {
+    "[^\r\n]{0,64}"
+}
+
+proptest! {
+    #[test]
+    fn never_panics_on_arbitrary_input(line in "\\PC{0,256}") {
+        let _ = parse_command(&line);
+    }
+
+    #[test]
+    fn never_panics_on_quote_and_escape_soup(line in "[a-z_]{1,12}[ \"\\\\a-z0-9:.+-]{0,64}") {
+        let _ = parse_command(&line);
+    }
+
+    #[test]
+    fn never_panics_on_raw_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let _ = parse_command(&String::from_utf8_lossy(&bytes));
+    }
+
+    #[test]
+    fn quoted_uri_round_trips(uri in arg()) {
+        prop_assert_eq!(
+            parse_command(&format!("add {}", quote(&uri))),
+            Ok(Command::Add { uri, position: None })
+        );
+    }
+
+    #[test]
+    fn quoted_message_round_trips(channel in arg(), message in arg()) {
+        prop_assert_eq!(
+            parse_command(&format!("sendmessage {} {}", quote(&channel), quote(&message))),
+            Ok(Command::SendMessage { channel, message })
+        );
+    }
+
+    #[test]
+    fn quoted_password_round_trips(password in arg()) {
+        prop_assert_eq!(
+            parse_command(&format!("password {}", quote(&password))),
+            Ok(Command::Password { password })
+        );
+    }
+
+    #[test]
+    fn quoted_and_bare_numbers_agree(id in any::<u32>(), priority in any::<u8>()) {
+        prop_assert_eq!(
+            parse_command(&format!("prioid {priority} {id}")),
+            parse_command(&format!("prioid \"{priority}\" \"{id}\""))
+        );
+        prop_assert_eq!(
+            parse_command(&format!("swapid {id} {id}")),
+            parse_command(&format!("swapid \"{id}\" \"{id}\""))
+        );
+    }
+
+    #[test]
+    fn over_long_arguments_are_preserved(uri in "[a-z/]{4096,16384}") {
+        prop_assert_eq!(
+            parse_command(&format!("add {}", quote(&uri))),
+            Ok(Command::Add { uri, position: None })
+        );
+    }
+}
+
+#[test]
+fn unterminated_quote_is_an_error() {
+    for line in [r#"add "foo"#, r#"add "foo\"#] {
+        assert!(
+            matches!(parse_command(line), Ok(Command::ArgError(..))),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn escaped_quote_and_backslash_are_unescaped() {
+    assert_eq!(
+        parse_command(r#"add "a \"b\" \\c""#),
+        Ok(Command::Add {
+            uri: r#"a "b" \c"#.to_string(),
+            position: None
+        })
+    );
+}
+
+#[test]
+fn quoted_numeric_arguments_parse() {
+    assert_eq!(
+        parse_command(r#"rangeid "3" "0.5:1.5""#),
+        Ok(Command::RangeId {
+            id: 3,
+            range: Some((0.5, 1.5))
+        })
+    );
+    assert_eq!(
+        parse_command(r#"prio "10" "1:3""#),
+        Ok(Command::Prio {
+            priority: 10,
+            ranges: vec![(1, 3)]
+        })
+    );
+    assert_eq!(
+        parse_command(r#"mixrampdb "-17.5""#),
+        Ok(Command::MixRampDb { decibels: -17.5 })
+    );
+}
@@ -106,9 +106,14 @@ async fn resolve_virtual_path_substitutes_stream_url() {
     ));
     let song = test_song("home/Artist/Album/remote-id-42.flac");
 
-    let result =
-        rmpd_protocol::commands::utils::prepare_song_for_playback(&song, None, None, &registry)
-            .await;
+    let result = rmpd_protocol::commands::utils::prepare_song_for_playback(
+        &song,
+        None,
+        &[],
+        None,
+        &registry,
+    )
+    .await;
 
     assert!(result.is_ok());
     let ps = result.unwrap();
@@ -133,6 +138,7 @@ async fn local_path_passes_through_unchanged() {
     let result = rmpd_protocol::commands::utils::prepare_song_for_playback(
         &song,
         Some("/srv/media"),
+        &[],
         None,
         &registry,
     )
@@ -151,9 +157,14 @@ async fn http_radio_stream_passes_through() {
     let registry = Arc::new(stub_registry("home", "subsonic", None, false));
     let song = test_song("http://radio.example/stream");
 
-    let result =
-        rmpd_protocol::commands::utils::prepare_song_for_playback(&song, None, None, &registry)
-            .await;
+    let result = rmpd_protocol::commands::utils::prepare_song_for_playback(
+        &song,
+        None,
+        &[],
+        None,
+        &registry,
+    )
+    .await;
 
     assert!(result.is_ok());
     let ps = result.unwrap();
@@ -5,6 +5,7 @@
 use rmpd_core::queue::Queue;
 use rmpd_core::state::{ConsumeMode, PlayerState, SingleMode};
 use rmpd_protocol::statefile::{SavedState, StateFile};
+use rmpd_protocol::stats::StatsSnapshot;
 
 #[path = "common/state_helpers.rs"]
 mod state_helpers;
@@ -20,7 +21,12 @@ async fn simulate_restart(
 
     // Save state before "shutdown"
     statefile
-        .save(initial_status, initial_queue, &[])
+        .save(
+            initial_status,
+            initial_queue,
+            &[],
+            &StatsSnapshot::default(),
+        )
         .await
         .unwrap();
 
@@ -198,7 +204,10 @@ async fn test_multiple_restart_cycles() {
 
     // First save
     let statefile1 = StateFile::new(path.clone());
-    statefile1.save(&status, &queue, &[]).await.unwrap();
+    statefile1
+        .save(&status, &queue, &[], &StatsSnapshot::default())
+        .await
+        .unwrap();
 
     // First load
     let statefile2 = StateFile::new(path.clone());
@@ -209,7 +218,10 @@ async fn test_multiple_restart_cycles() {
     status.volume = 60;
     queue.add(make_test_song("/music/new.mp3", 3));
     let statefile3 = StateFile::new(path.clone());
-    statefile3.save(&status, &queue, &[]).await.unwrap();
+    statefile3
+        .save(&status, &queue, &[], &StatsSnapshot::default())
+        .await
+        .unwrap();
 
     // Load again
     let statefile4 = StateFile::new(path.clone());
@@ -292,7 +304,10 @@ async fn test_crash_recovery() {
         .build(2);
 
     let statefile = StateFile::new(path.clone());
-    statefile.save(&status, &queue, &[]).await.unwrap();
+    statefile
+        .save(&status, &queue, &[], &StatsSnapshot::default())
+        .await
+        .unwrap();
 
     // Verify good state loads
     let statefile2 = StateFile::new(path.clone());
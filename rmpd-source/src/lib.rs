@@ -130,6 +130,7 @@ pub async fn sync_source(source: &dyn MusicSource, db_path: &str) -> Result<usiz
     let token = format!("{}:{}", source.scheme(), source.name());
     let db_path = db_path.to_owned();
     tokio::task::spawn_blocking(move || -> Result<usize, SourceError> {
+        let _update = rmpd_library::update_lock::lock();
         let db = rmpd_library::Database::open(&db_path)
             .map_err(|e| SourceError::Protocol(format!("failed to open database: {e}")))?;
         let _old = db
@@ -152,7 +153,7 @@ pub async fn sync_source(source: &dyn MusicSource, db_path: &str) -> Result<usiz
 /// recovering the bare remote id from a mount-style path.
 const AUDIO_EXTENSIONS: &[&str] = &[
     "flac", "mp3", "ogg", "oga", "opus", "m4a", "aac", "mp4", "wav", "wv", "ape", "wma", "alac",
-    "aif", "aiff", "dsf", "dff",
+    "aif", "aiff", "dsf", "dff", "mpc", "tta",
 ];
 
 /// Recover the raw remote id from a mount-style path's last `/`-segment by
@@ -1,8 +1,9 @@
 //! Compile-time name→factory registry for music-source backends.
 //!
-//! Mirrors `OUTPUT_PLUGINS` in `rmpd-player/src/output_registry.rs` exactly:
-//! a `const` slice of `(&str, SourceFactory)` pairs selected by lowercased
-//! `source_type`. No I/O happens at selection time.
+//! Follows the same model as `OUTPUT_PLUGINS` in
+//! `rmpd-player/src/output_registry.rs`: a `const` slice of
+//! `(&str, SourceFactory)` pairs selected by lowercased `source_type`. No I/O
+//! happens at selection time.
 
 use crate::filesystem::filesystem_source_factory;
 use rmpd_core::config::SourceConfig;
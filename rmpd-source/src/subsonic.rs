@@ -65,6 +65,8 @@ fn mime_to_ext(mime: &str) -> Option<&'static str> {
         "audio/wav" | "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => "wav",
         "audio/x-ape" | "audio/ape" | "audio/x-monkeys-audio" => "ape",
         "audio/x-wavpack" | "audio/wavpack" => "wv",
+        "audio/x-musepack" | "audio/musepack" => "mpc",
+        "audio/x-tta" | "audio/tta" => "tta",
         "audio/x-ms-wma" => "wma",
         "audio/aiff" | "audio/x-aiff" => "aiff",
         "audio/dsf" | "audio/x-dsf" => "dsf",
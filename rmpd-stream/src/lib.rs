@@ -5,6 +5,9 @@
 //! (ICY) metadata de-interleaving. Metadata blocks are stripped so the bytes
 //! handed to the decoder are pure audio, and the "now playing" title is
 //! surfaced through a cheap shared handle ([`TitleHandle`]).
+//!
+//! [`probe`] takes a short look at a stream without decoding it, for queue
+//! entries that have no database record to describe them.
 #![allow(clippy::cargo_common_metadata)]
 
 use std::io::{self, Read, Seek, SeekFrom};
@@ -149,6 +152,77 @@ impl MediaSource for HttpSource {
     }
 }
 
+/// Largest `icy-metaint` [`probe`] reads through to reach the first title.
+const PROBE_MAX_METAINT: usize = 64 * 1024;
+
+/// What a quick look at a stream reveals without decoding it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Station name (`icy-name`).
+    pub name: Option<String>,
+    /// Station genre (`icy-genre`).
+    pub genre: Option<String>,
+    /// Advertised bitrate in kbit/s (`icy-br`).
+    pub bitrate: Option<u32>,
+    /// "Now playing" title from the first ICY metadata block.
+    pub title: Option<String>,
+}
+
+/// Connect to `url` and read its ICY headers plus, when the metadata interval
+/// is small enough, the first "now playing" title. The whole exchange is
+/// bounded by `timeout`.
+///
+/// # Errors
+/// Returns an error if the request fails or the server responds with a
+/// non-success status.
+pub fn probe(url: &str, timeout: Duration) -> io::Result<StreamInfo> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .user_agent("rmpd")
+        .build()
+        .map_err(to_io)?;
+    let resp = client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .send()
+        .map_err(to_io)?
+        .error_for_status()
+        .map_err(to_io)?;
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_owned)
+    };
+    let mut info = StreamInfo {
+        name: header("icy-name"),
+        genre: header("icy-genre"),
+        bitrate: header("icy-br").and_then(|v| v.parse().ok()),
+        title: None,
+    };
+    let metaint = header("icy-metaint")
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| (1..=PROBE_MAX_METAINT).contains(n));
+    if let Some(metaint) = metaint {
+        info.title = first_title(
+            HttpSource::with_reader(Box::new(resp), Some(metaint)),
+            metaint,
+        );
+    }
+    tracing::debug!(url, ?info, "probed HTTP stream");
+    Ok(info)
+}
+
+/// Read through the first metadata interval of `source` and return the title
+/// its metadata block carries.
+fn first_title(mut source: HttpSource, metaint: usize) -> Option<String> {
+    // One byte past the interval makes the source parse the block.
+    io::copy(&mut (&mut source).take(metaint as u64 + 1), &mut io::sink()).ok()?;
+    source.title_handle().lock().clone()
+}
+
 fn to_io(e: reqwest::Error) -> io::Error {
     io::Error::other(e.to_string())
 }
@@ -273,4 +347,16 @@ mod tests {
         assert!(!src.is_seekable());
         assert_eq!(src.byte_len(), None);
     }
+
+    #[test]
+    fn first_title_reads_through_one_interval() {
+        let mut data = vec![0u8; 8];
+        data.extend(meta_block("Artist - Song"));
+        data.extend([0u8; 8]);
+        let source = HttpSource::with_reader(Box::new(Cursor::new(data)), Some(8));
+        assert_eq!(first_title(source, 8).as_deref(), Some("Artist - Song"));
+
+        let silent = HttpSource::with_reader(Box::new(Cursor::new(vec![0u8; 4])), Some(8));
+        assert_eq!(first_title(silent, 8), None);
+    }
 }
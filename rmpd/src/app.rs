@@ -1,19 +1,25 @@
 use rmpd_core::config::Config;
-use rmpd_core::error::Result;
+use rmpd_core::error::{Result, RmpdError};
 use rmpd_core::state::PlayerState;
 use rmpd_protocol::{AppState, MpdServer, StateFile};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
 use tracing::{error, info, warn};
 
-pub async fn run(bind_address: String, config: Config) -> Result<()> {
-    // Create application state with database and music directory paths
+pub async fn run(bind_address: String, config: Config, config_path: Option<PathBuf>) -> Result<()> {
+    // Create application state with database and music directory paths. With
+    // no usable music directory the library is just what the database holds.
     let db_path = config.general.db_file.to_string();
-    let music_dir = config.general.music_directory.to_string();
+    let music_dir = config
+        .general
+        .available_music_directory()
+        .map(|d| d.to_string());
     let state_file_path = config.general.state_file.to_string();
     let playlist_dir = config.general.playlist_directory.to_string();
 
-    let mut state = AppState::with_all_paths(db_path.clone(), music_dir.clone(), playlist_dir);
+    rmpd_library::database::set_fts_enabled(config.database.fts_enabled);
+    let mut state = AppState::with_library(db_path.clone(), music_dir.clone(), playlist_dir);
 
     // Configure password authentication if set in config.
 
@@ -21,7 +27,32 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
     let source_registry = Arc::new(rmpd_source::SourceRegistry::from_config(&config.source));
     state.set_sources(source_registry);
     state.set_password(config.network.password.clone());
+    if let Some(spec) = &config.network.default_permissions {
+        let permissions = rmpd_protocol::connection::parse_permissions(spec)
+            .map_err(|e| RmpdError::Config(format!("default_permissions: {e}")))?;
+        state.set_default_permissions(Some(permissions));
+    }
+    if let Some(spec) = &config.network.local_permissions {
+        let permissions = rmpd_protocol::connection::parse_permissions(spec)
+            .map_err(|e| RmpdError::Config(format!("local_permissions: {e}")))?;
+        state.set_local_permissions(Some(permissions));
+    }
     state.set_follow_symlinks(config.general.follow_symlinks);
+    state.set_music_roots(config.general.music_roots.clone());
+    state.set_api_tokens(config.api_tokens.clone());
+    state.set_tag_editing(config.database.tag_editing);
+    let mut tag_rewriter = rmpd_library::TagRewriter::new(&config.tag_rules)?;
+    if let Some(spec) = &config.general.metadata_to_use {
+        let mask = rmpd_core::tag::MetadataMask::parse(spec).map_err(RmpdError::Config)?;
+        rmpd_core::tag::set_metadata_to_use(mask.clone());
+        tag_rewriter = tag_rewriter.with_metadata_mask(mask);
+    }
+    state.set_tag_rewriter(tag_rewriter);
+    state.set_config(config.clone());
+    if let Some(locale) = &config.general.collation {
+        rmpd_library::collation::set_collation(locale)?;
+        info!("sorting listings with the {locale:?} collation");
+    }
     if !config
         .general
         .filesystem_charset
@@ -37,15 +68,24 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
         );
     }
 
+    // Outputs open lazily at playback; catch encoder settings they cannot
+    // honor now rather than on the first song.
+    for output in &config.output {
+        rmpd_player::validate_output(output)?;
+    }
+
     // Apply audio settings from config to the player.
     // - resampler quality: used only when the device can't play a rate natively.
     // - DoP mode: native DSD-over-PCM policy for DSD sources.
+    // - output sample rate: resample every song to one rate so rate changes
+    //   between tracks don't reopen the output.
     // - output device: select a specific (e.g. raw ALSA `hw:`) device, bypassing
     //   PipeWire/PulseAudio for bit-perfect DoP. Env vars still override.
     {
         let mut engine = state.engine.write().await;
         engine.set_resampler_quality(config.audio.resampler_quality);
         engine.set_dop_mode(config.dop_mode());
+        engine.set_output_sample_rate(config.audio.output_sample_rate);
         engine.set_replay_gain(
             config.audio.replay_gain,
             config.audio.replay_gain_preamp,
@@ -54,6 +94,8 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
         engine.set_volume_normalization(config.audio.volume_normalization);
         engine.set_crossfade(config.audio.crossfade as u32);
         engine.set_mixramp(config.audio.mixramp_db, config.audio.mixramp_delay);
+        engine.set_crossfade_loudness_match(config.audio.crossfade_loudness_match);
+        engine.set_crossfade_skip_continuous(config.audio.crossfade_skip_continuous);
         engine.set_buffer_time(config.audio.buffer_time);
         engine.set_outputs({
             let enabled: Vec<rmpd_core::config::OutputConfig> = config
@@ -77,6 +119,22 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
         .set_outputs_from_config(&config.output, &config.audio.default_output)
         .await;
 
+    // An in-memory library starts empty: fill it before the saved queue is
+    // looked up in it.
+    let memory_db = rmpd_library::database::is_memory_db(&db_path);
+    if memory_db && music_dir.is_some() {
+        info!("in-memory database: scanning music directory");
+        state.update_library().await;
+    }
+
+    // Index the stored playlist files, including ones written by MPD or by
+    // hand, in the library database; in the background, as nothing waits on it.
+    let index_state = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let count = rmpd_protocol::commands::playlists::index_playlist_directory(&index_state);
+        info!("indexed {count} stored playlist(s)");
+    });
+
     // Load state from file if it exists
     let state_file = StateFile::new(state_file_path.clone());
     let loaded_state = match tokio::task::spawn_blocking(move || state_file.load()).await {
@@ -92,12 +150,28 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
             &state,
             saved_state,
             &db_path,
-            &music_dir,
+            music_dir.as_deref(),
             config.audio.restore_paused,
         )
         .await;
     }
 
+    // A hardware mixer is the source of truth for volume, so attach it after
+    // the saved state is restored: it overrides the restored software volume.
+    if let Some(out) = config
+        .output
+        .iter()
+        .find(|o| o.enabled && rmpd_player::hardware_mixer::wants_hardware_mixer(o))
+    {
+        match rmpd_player::hardware_mixer::open_hardware_mixer(out) {
+            Ok(mixer) => {
+                info!("using hardware mixer of output \"{}\"", out.name);
+                state.engine.write().await.set_hardware_mixer(mixer).await;
+            }
+            Err(e) => warn!("{e}; falling back to software volume"),
+        }
+    }
+
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
 
@@ -127,8 +201,90 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
         None
     };
 
-    // Trigger an initial library scan on startup when auto-update is enabled.
-    if config.database.auto_update {
+    // Keep the system awake while playing. Kept alive (`_power`) for the
+    // lifetime of the server; dropping it releases a held inhibitor.
+    let _power = config
+        .audio
+        .inhibit_sleep
+        .then(|| rmpd_protocol::power::spawn(state.clone()));
+
+    // Resume long songs where they were left off. Kept alive (`_resume`)
+    // for the lifetime of the server.
+    let _resume = rmpd_protocol::resume::spawn(
+        state.clone(),
+        config.audio.resume_min_duration,
+        &config.audio.resume_directories,
+    );
+
+    // Run the configured [[hook]] commands on player and database events.
+    // Kept alive (`_hooks`) for the lifetime of the server.
+    let _hooks = rmpd_protocol::hooks::spawn(state.clone(), &config.hooks);
+
+    // Serve album art over HTTP for web clients when `art_port` is set.
+    // Kept alive (`_art_http`) for the lifetime of the server; failing to
+    // bind only disables the endpoint.
+    let _art_http = match config.network.art_port {
+        Some(port) => {
+            match rmpd_protocol::art_http::spawn(state.clone(), &config.network.bind_address, port)
+                .await
+            {
+                Ok(handle) => Some(handle),
+                Err(e) => {
+                    warn!("album art HTTP endpoint disabled: {e}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Stream transcoded songs and the live output when `stream_port` is set,
+    // the same way.
+    let _stream_http = match config.network.stream_port {
+        Some(port) => {
+            match rmpd_protocol::stream_http::spawn(
+                state.clone(),
+                &config.network.bind_address,
+                port,
+                config.network.stream_bitrate,
+                config.network.stream_max_clients,
+            )
+            .await
+            {
+                Ok(handle) => Some(handle),
+                Err(e) => {
+                    warn!("transcoding stream HTTP endpoint disabled: {e}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Serve the daemon statistics to monitoring systems when `metrics_port`
+    // is set, the same way.
+    let _metrics_http = match config.network.metrics_port {
+        Some(port) => {
+            match rmpd_protocol::metrics_http::spawn(
+                state.clone(),
+                &config.network.bind_address,
+                port,
+            )
+            .await
+            {
+                Ok(handle) => Some(handle),
+                Err(e) => {
+                    warn!("metrics HTTP endpoint disabled: {e}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Trigger an initial library scan on startup when auto-update is enabled
+    // (an in-memory library was scanned above).
+    if config.database.auto_update && music_dir.is_some() && !memory_db {
         info!("auto-update enabled: scanning music directory");
         state.spawn_library_update();
     }
@@ -140,19 +296,31 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
     }
 
     // Start the filesystem watcher so the database stays in sync with on-disk
-    // changes. Kept alive (`_watcher`) for the lifetime of the server; dropping
-    // it would stop watching.
-    let _watcher = if config.database.filesystem_watch {
-        match start_filesystem_watch(&state, &db_path, &music_dir).await {
-            Ok(w) => Some(w),
-            Err(e) => {
-                warn!("filesystem watch disabled: {}", e);
-                None
+    // changes. Kept alive for the lifetime of the server (by the reload
+    // handler on Unix, which replaces it when the music directory changes);
+    // dropping it would stop watching.
+    let watcher = match &music_dir {
+        Some(dir) if config.database.filesystem_watch => {
+            match start_filesystem_watch(&state, &db_path, dir).await {
+                Ok(w) => Some(w),
+                Err(e) => {
+                    warn!("filesystem watch disabled: {}", e);
+                    None
+                }
             }
         }
-    } else {
-        None
+        _ => None,
     };
+    #[cfg(unix)]
+    spawn_reload_handler(
+        state.clone(),
+        config_path,
+        watcher,
+        config.database.filesystem_watch,
+        db_path.clone(),
+    );
+    #[cfg(not(unix))]
+    let _watcher = (watcher, config_path);
 
     // Clone state for shutdown handler
     let shutdown_state = state.clone();
@@ -181,7 +349,15 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
         .with_max_connections(config.network.max_connections)
         .with_connection_timeout(std::time::Duration::from_secs(
             config.network.connection_timeout,
-        ));
+        ))
+        .with_tcp_keepalive(
+            (config.network.tcp_keepalive > 0)
+                .then(|| std::time::Duration::from_secs(config.network.tcp_keepalive)),
+        )
+        .with_max_line_length(config.network.max_command_length)
+        .with_max_command_list_size(config.network.max_command_list_size * 1024)
+        .with_write_timeout(std::time::Duration::from_secs(config.network.write_timeout))
+        .with_max_output_buffer_size(config.network.max_output_buffer_size * 1024);
 
     if let Some(ref sock) = config.network.unix_socket {
         info!("unix socket: {}", sock);
@@ -198,6 +374,45 @@ pub async fn run(bind_address: String, config: Config) -> Result<()> {
     Ok(())
 }
 
+/// Print what a library update would add, update and remove, without
+/// touching the database. With `verbose`, every affected path is listed.
+pub fn scan_dry_run(config: &Config, verbose: bool) -> Result<()> {
+    let db = rmpd_library::Database::open_read_only(config.general.db_file.as_str())?;
+    let scanner = rmpd_library::Scanner::new(
+        rmpd_core::event::EventBus::new(),
+        config.general.follow_symlinks,
+    )
+    .with_roots(config.general.music_roots.clone());
+    let music_dir = config.general.available_music_directory().ok_or_else(|| {
+        RmpdError::Config(format!(
+            "Music directory not found: {:?}",
+            config.general.music_directory.as_str()
+        ))
+    })?;
+    let report = scanner.plan_directory(&db, music_dir.as_std_path())?;
+
+    if verbose {
+        for path in &report.added {
+            println!("+ {path}");
+        }
+        for path in &report.updated {
+            println!("~ {path}");
+        }
+        for path in &report.removed {
+            println!("- {path}");
+        }
+    }
+    println!(
+        "{} to add, {} to update, {} to remove ({} files checked, {} errors)",
+        report.added.len(),
+        report.updated.len(),
+        report.removed.len(),
+        report.scanned,
+        report.errors
+    );
+    Ok(())
+}
+
 /// Open a dedicated database handle and start watching the music directory for
 /// changes, returning the live watcher (which must be kept alive to keep
 /// watching).
@@ -214,23 +429,85 @@ async fn start_filesystem_watch(
         std::path::PathBuf::from(music_dir),
         Arc::new(Mutex::new(db)),
         state.event_bus.clone(),
-    )?;
+    )?
+    .with_tag_rewriter(state.tag_rewriter.clone());
     watcher.start().await?;
     info!("filesystem watcher started for {}", music_dir);
     Ok(watcher)
 }
 
+/// Re-read the config on SIGHUP and apply a changed music directory: the
+/// library is rescanned and the filesystem watcher moved to the new
+/// directory. Other settings still need a restart.
+#[cfg(unix)]
+fn spawn_reload_handler(
+    state: AppState,
+    config_path: Option<PathBuf>,
+    mut watcher: Option<rmpd_library::FilesystemWatcher>,
+    filesystem_watch: bool,
+    db_path: String,
+) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("config reload on SIGHUP unavailable: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("received SIGHUP, reloading config");
+            let config = match Config::reload(config_path.as_deref()) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("config reload failed, keeping the current settings: {}", e);
+                    continue;
+                }
+            };
+            let music_dir = config
+                .general
+                .available_music_directory()
+                .map(|d| d.to_string());
+            if music_dir == state.music_dir() {
+                info!("music directory unchanged");
+                continue;
+            }
+
+            info!(
+                "music directory is now {}",
+                music_dir.as_deref().unwrap_or("(none)")
+            );
+            state.set_music_dir(music_dir.clone());
+            // Stop watching the old directory before watching the new one.
+            drop(watcher.take());
+            if let Some(dir) = &music_dir {
+                if filesystem_watch {
+                    match start_filesystem_watch(&state, &db_path, dir).await {
+                        Ok(w) => watcher = Some(w),
+                        Err(e) => warn!("filesystem watch disabled: {}", e),
+                    }
+                }
+                state.spawn_library_update();
+            }
+        }
+    });
+}
+
 async fn restore_state(
     state: &AppState,
     saved_state: rmpd_protocol::statefile::SavedState,
     db_path: &str,
-    music_dir: &str,
+    music_dir: Option<&str>,
     restore_paused: bool,
 ) {
+    // Keep counting from the totals of earlier runs
+    state.stats.restore(&saved_state.stats);
+
     // Restore playback options
     {
         let mut status = state.status.write().await;
-        status.volume = saved_state.volume;
         status.random = saved_state.random;
         status.repeat = saved_state.repeat;
         status.single = saved_state.single;
@@ -241,9 +518,11 @@ async fn restore_state(
         status.replay_gain_mode = saved_state.replay_gain_mode;
     }
 
-    // Keep the engine's crossfade + MixRamp settings in sync with restored state.
+    // Keep the engine's volume, crossfade + MixRamp settings in sync with
+    // restored state (`set_volume` also records the volume in `status`).
     {
         let mut engine = state.engine.write().await;
+        let _ = engine.set_volume(saved_state.volume).await;
         engine.set_crossfade(saved_state.crossfade);
         engine.set_mixramp(saved_state.mixramp_db, saved_state.mixramp_delay);
     }
@@ -284,13 +563,29 @@ async fn restore_state(
             saved_state.playlist_paths.len()
         );
 
-        if let Ok(db) = rmpd_library::Database::open(db_path) {
+        // Look every song up on a blocking thread before touching the queue;
+        // a long saved queue means as many queries.
+        let db_path = db_path.to_owned();
+        let paths = saved_state.playlist_paths.clone();
+        let found = tokio::task::spawn_blocking(move || {
+            let db = rmpd_library::Database::open(&db_path).ok()?;
+            Some(
+                paths
+                    .iter()
+                    .map(|path| db.get_song_by_path(path).ok().flatten())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .await
+        .ok()
+        .flatten();
+
+        if let Some(found) = found {
             let mut queue = state.queue.write().await;
             let mut missing = 0usize;
 
-            for (orig_idx, path) in saved_state.playlist_paths.iter().enumerate() {
-                // Try to find song in database
-                if let Ok(Some(song)) = db.get_song_by_path(path) {
+            for (orig_idx, song) in found.into_iter().enumerate() {
+                if let Some(song) = song {
                     queue.add(song);
                 } else {
                     missing += 1;
@@ -313,11 +608,13 @@ async fn restore_state(
             }
 
             let playlist_len = queue.len() as u32;
+            let playlist_version = queue.version();
             drop(queue);
 
-            // Update playlist length in status
+            // Update playlist length and version in status
             let mut status = state.status.write().await;
             status.playlist_length = playlist_len;
+            status.playlist_version = playlist_version;
         }
     }
 
@@ -343,7 +640,8 @@ async fn restore_state(
                     let playback_song =
                         match rmpd_protocol::commands::utils::prepare_song_for_playback(
                             &song,
-                            Some(music_dir),
+                            music_dir,
+                            &state.music_roots,
                             range,
                             &state.sources,
                         )
@@ -417,8 +715,13 @@ async fn save_state_on_shutdown(state: &AppState, state_file_path: &str) {
         .map(|o| o.name.clone())
         .collect();
 
+    let stats = state.stats.snapshot(state);
+
     let state_file = StateFile::new(state_file_path.to_string());
-    if let Err(e) = state_file.save(&status, &queue, &disabled_outputs).await {
+    if let Err(e) = state_file
+        .save(&status, &queue, &disabled_outputs, &stats)
+        .await
+    {
         error!("failed to save state: {}", e);
     }
 }
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use clap::Parser;
-use tracing::info;
+use tracing::{info, warn};
 
 mod app;
 
@@ -66,6 +66,11 @@ struct Args {
     /// Log to syslog/journald instead of stdout (useful when running as a daemon)
     #[arg(long)]
     syslog: bool,
+
+    /// Report what a library scan would add, update and remove, then exit
+    /// without touching the database (combine with --verbose to list paths)
+    #[arg(long)]
+    scan_dry_run: bool,
 }
 
 fn make_bind_addr(addr: &str, port: u16) -> String {
@@ -136,7 +141,8 @@ async fn main() -> Result<()> {
     info!("starting rmpd v{}", env!("CARGO_PKG_VERSION"));
 
     // Load configuration
-    let config = if let Some(config_path) = args.config {
+    let config_path = args.config.map(std::path::PathBuf::from);
+    let config = if let Some(config_path) = &config_path {
         rmpd_core::config::Config::load_from_path(config_path)?
     } else {
         rmpd_core::config::Config::load_or_default()
@@ -151,15 +157,26 @@ async fn main() -> Result<()> {
     let full_address = make_bind_addr(&bind_address, port);
 
     info!("configuration loaded");
-    info!("music directory: {}", config.general.music_directory);
+    match config.general.available_music_directory() {
+        Some(dir) => info!("music directory: {}", dir),
+        None => warn!(
+            "music directory unavailable ({:?}); serving an empty library",
+            config.general.music_directory.as_str()
+        ),
+    }
     info!("database: {}", config.general.db_file);
 
+    if args.scan_dry_run {
+        app::scan_dry_run(&config, args.verbose)?;
+        return Ok(());
+    }
+
     if args.daemonize {
         daemonize()?;
     }
 
     // Start the server
-    app::run(full_address, config).await?;
+    app::run(full_address, config, config_path).await?;
 
     Ok(())
 }